@@ -0,0 +1,121 @@
+//! Benchmarks of the rendering paths, run with `cargo bench`.
+//!
+//! The viewports cover the very different costs an image can have: one far
+//! away from the set where every point escapes at once, one full of boundary
+//! where most points take long to decide, and a deep zoom.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use mandelbrot::{cpu::Kernel, encode_png, render, render::Strategy};
+use num::Complex;
+
+const BOUNDS: (usize, usize) = (320, 240);
+
+const VIEWPORTS: &[(&str, Complex<f64>, Complex<f64>)] = &[
+    (
+        "empty",
+        Complex { re: 1.0, im: 2.0 },
+        Complex { re: 2.0, im: 1.25 },
+    ),
+    (
+        "boundary",
+        Complex { re: -1.2, im: 0.35 },
+        Complex { re: -1.0, im: 0.2 },
+    ),
+    (
+        "deep-zoom",
+        Complex {
+            re: -0.743_643_9,
+            im: 0.131_826_3,
+        },
+        Complex {
+            re: -0.743_643_7,
+            im: 0.131_826_15,
+        },
+    ),
+];
+
+fn escape_time(c: &mut Criterion) {
+    let mut group = c.benchmark_group("escape_time");
+    for (name, point) in [
+        ("escapes", Complex { re: 0.5, im: 0.5 }),
+        ("boundary", Complex { re: -0.75, im: 0.1 }),
+        ("interior", Complex { re: -0.1, im: 0.1 }),
+    ] {
+        group.bench_with_input(BenchmarkId::from_parameter(name), &point, |b, &point| {
+            b.iter(|| render::escape_time(std::hint::black_box(point), 255))
+        });
+    }
+    group.finish();
+}
+
+fn kernels(c: &mut Criterion) {
+    let (_, upper_left, lower_right) = VIEWPORTS[1];
+    let points: Vec<(f64, f64)> = (0..BOUNDS.0 * BOUNDS.1)
+        .map(|i| {
+            let point = render::pixel_to_point(
+                BOUNDS,
+                (i % BOUNDS.0, i / BOUNDS.0),
+                upper_left,
+                lower_right,
+            );
+            (point.re, point.im)
+        })
+        .collect();
+    let mut results = vec![(None, 0); points.len()];
+    let mut group = c.benchmark_group("kernel");
+    group.sample_size(20);
+    for kernel in Kernel::ALL
+        .into_iter()
+        .filter(|kernel| kernel.is_supported())
+    {
+        group.bench_function(BenchmarkId::from_parameter(kernel.name()), |b| {
+            b.iter(|| kernel.escape_times()(&points, 255, 2.0, &mut results))
+        });
+    }
+    group.finish();
+}
+
+fn render_frame(c: &mut Criterion) {
+    let threads = num_cpus::get();
+    let mut group = c.benchmark_group("render");
+    group.sample_size(20);
+    for &(name, upper_left, lower_right) in VIEWPORTS {
+        let mut pixels = vec![0; BOUNDS.0 * BOUNDS.1];
+        for (strategy_name, strategy) in [
+            ("naive", Strategy::Naive),
+            ("border-trace", Strategy::BorderTrace),
+        ] {
+            group.bench_function(BenchmarkId::new(strategy_name, name), |b| {
+                b.iter(|| {
+                    render::render_with(strategy, &mut pixels, BOUNDS, upper_left, lower_right)
+                })
+            });
+        }
+        group.bench_function(BenchmarkId::new("parallel", name), |b| {
+            b.iter(|| {
+                render::render_parallel(
+                    Strategy::Naive,
+                    &mut pixels,
+                    BOUNDS,
+                    upper_left,
+                    lower_right,
+                    &render::Schedule::new(threads),
+                )
+            })
+        });
+    }
+    group.finish();
+}
+
+fn encode(c: &mut Criterion) {
+    let (_, upper_left, lower_right) = VIEWPORTS[1];
+    let mut pixels = vec![0; BOUNDS.0 * BOUNDS.1];
+    render::render(&mut pixels, BOUNDS, upper_left, lower_right);
+
+    c.bench_function("encode_png", |b| {
+        b.iter(|| encode_png(&pixels, BOUNDS, upper_left, lower_right).unwrap())
+    });
+}
+
+criterion_group!(benches, escape_time, kernels, render_frame, encode);
+criterion_main!(benches);
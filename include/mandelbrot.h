@@ -0,0 +1,93 @@
+#ifndef MANDELBROT_H
+#define MANDELBROT_H
+
+/* Generated with cbindgen from src/ffi.rs, don't edit by hand. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+// What a call returned.
+typedef enum MandelbrotStatus {
+  MANDELBROT_STATUS_OK = 0,
+  // A pointer that must not be null was.
+  MANDELBROT_STATUS_NULL_POINTER = 1,
+  // The parameters don't describe a view that can be rendered.
+  MANDELBROT_STATUS_INVALID_PARAMS = 2,
+  // The buffer holds fewer bytes than the image has pixels.
+  MANDELBROT_STATUS_BUFFER_TOO_SMALL = 3,
+  // The renderer panicked, which is a bug.
+  MANDELBROT_STATUS_PANIC = 4,
+} MandelbrotStatus;
+
+// A renderer, kept between renders: the threads it renders with and what
+// went wrong last.
+typedef struct MandelbrotRenderer MandelbrotRenderer;
+
+// The view to render, centered on `center_re + center_im i` and magnified
+// `zoom` times as with `--zoom`, into a `width` by `height` image.
+typedef struct MandelbrotParams {
+  uint32_t width;
+  uint32_t height;
+  double center_re;
+  double center_im;
+  double zoom;
+  // Iterations before a point is taken to be in the set.
+  uint32_t max_iter;
+  // 0 to compute every pixel on its own, 1 to skip rectangles whose
+  // border is all the same color, as `--strategy` does.
+  uint32_t strategy;
+} MandelbrotParams;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// The whole set, as the command line renders it without options, into an
+// image of no pixels, which the caller sizes.
+struct MandelbrotParams mandelbrot_params_default(void);
+
+// A renderer rendering with `threads` threads, or one per CPU if 0. Free it
+// with `mandelbrot_renderer_free`.
+struct MandelbrotRenderer *mandelbrot_renderer_new(uint32_t threads);
+
+// Free `renderer`. Null is ignored.
+//
+// # Safety
+//
+// `renderer` must be null or come from `mandelbrot_renderer_new`, and not
+// have been freed already.
+void mandelbrot_renderer_free(struct MandelbrotRenderer *renderer);
+
+// Render the view of `params` into `buffer`, `buffer_len` bytes long, as
+// one byte a pixel, row after row: black for points in the set to white
+// for points that escape at once.
+//
+// Anything but `MANDELBROT_STATUS_OK` leaves `buffer` as it was, except
+// `MANDELBROT_STATUS_PANIC`, and `mandelbrot_renderer_last_error` tells why.
+//
+// # Safety
+//
+// `renderer` must come from `mandelbrot_renderer_new`, `params` point to a
+// `MandelbrotParams`, and `buffer` to `buffer_len` writable bytes. None of
+// them may be used by another thread during the call.
+enum MandelbrotStatus mandelbrot_render_into_buffer(struct MandelbrotRenderer *renderer,
+                                                    const struct MandelbrotParams *params,
+                                                    uint8_t *buffer,
+                                                    uintptr_t buffer_len);
+
+// Why the last call on `renderer` didn't return `MANDELBROT_STATUS_OK`, or an
+// empty string if it did. The string belongs to `renderer`, and lasts until
+// the next call on it.
+//
+// # Safety
+//
+// `renderer` must be null or come from `mandelbrot_renderer_new`.
+const char *mandelbrot_renderer_last_error(const struct MandelbrotRenderer *renderer);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* MANDELBROT_H */
@@ -0,0 +1,219 @@
+//! Named points of the plane, so that views can be given relative to
+//! landmarks: `seahorse`, or `seahorse + 1.5e-8,-2e-9` for a point just
+//! next to it.
+//!
+//! A few well known places are built in, and bookmarks files add more, one
+//! per line as `NAME RE,IM` or as `NAME` and a compact location, whose
+//! center is the point, with `#` starting a comment:
+//!
+//! ```text
+//! # spots worth a closer look
+//! home -0.5,0
+//! my_spiral -0.7436438870,0.1318259042
+//! valley @-0.75,0.1,z=40
+//! ```
+
+use std::fs;
+
+use num::Complex;
+
+use crate::{
+    locator::{self, Locator},
+    parse_complex, platform, DecimalSeparator, Error, Result,
+};
+
+/// The landmarks every view can be given relative to.
+pub const PRESETS: &[(&str, Complex<f64>)] = &[
+    ("seahorse", Complex { re: -0.75, im: 0.1 }),
+    ("elephant", Complex { re: 0.275, im: 0.0 }),
+    ("scepter", Complex { re: -1.36, im: 0.0 }),
+    (
+        "triple_spiral",
+        Complex {
+            re: -0.088,
+            im: 0.654,
+        },
+    ),
+    (
+        "antenna_mini",
+        Complex {
+            re: -1.754_877_666_246_69,
+            im: 0.0,
+        },
+    ),
+    (
+        "deep_spiral",
+        Complex {
+            re: -0.743_643_887_037_151,
+            im: 0.131_825_904_205_33,
+        },
+    ),
+    ("cusp", Complex { re: 0.25, im: 0.0 }),
+];
+
+/// The named points a coordinate can refer to: the presets, and bookmarks,
+/// which take precedence over them.
+#[derive(Debug, Default, PartialEq)]
+pub struct Anchors {
+    bookmarks: Vec<(String, Complex<f64>)>,
+}
+
+impl Anchors {
+    /// Parse the contents of a bookmarks file, whose points are written with
+    /// decimal points.
+    ///
+    /// Returns the number and the contents of the first line that can't be
+    /// read if there is one.
+    pub fn parse(text: &str) -> std::result::Result<Anchors, (usize, String)> {
+        let mut bookmarks = Vec::new();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            let bookmark = line
+                .split_once(char::is_whitespace)
+                .filter(|(name, _)| is_name(name))
+                .and_then(|(name, point)| {
+                    let point = point.trim();
+                    let point = if locator::is_locator(point) {
+                        point.parse::<Locator>().ok()?.center
+                    } else {
+                        parse_complex(point, DecimalSeparator::Point)?
+                    };
+                    Some((name.to_string(), point))
+                });
+            match bookmark {
+                Some(bookmark) => bookmarks.push(bookmark),
+                None => return Err((number + 1, line.to_string())),
+            }
+        }
+        Ok(Anchors { bookmarks })
+    }
+
+    /// The anchors of the bookmarks `bookmarks`, later ones taking
+    /// precedence over earlier ones of the same name.
+    pub fn new(bookmarks: Vec<(String, Complex<f64>)>) -> Anchors {
+        Anchors { bookmarks }
+    }
+
+    /// The bookmarks, in the order they were given.
+    pub fn bookmarks(&self) -> &[(String, Complex<f64>)] {
+        &self.bookmarks
+    }
+
+    /// Read the bookmarks file named `filename`.
+    pub fn read(filename: &str) -> Result<Anchors> {
+        let text = fs::read_to_string(platform::path(filename)).map_err(Error::io(filename))?;
+        Anchors::parse(&text).map_err(|(number, line)| Error::Parse {
+            what: "bookmark",
+            value: format!("{}:{}: {}", filename, number, line),
+        })
+    }
+
+    /// The point called `name`, if there is one.
+    pub fn get(&self, name: &str) -> Option<Complex<f64>> {
+        self.bookmarks
+            .iter()
+            .rev()
+            .find(|(bookmark, _)| bookmark == name)
+            .map(|&(_, point)| point)
+            .or_else(|| {
+                PRESETS
+                    .iter()
+                    .find(|(preset, _)| *preset == name)
+                    .map(|&(_, point)| point)
+            })
+    }
+
+    /// Parse `s` as a point, which is either given by its coordinates as
+    /// `parse_complex` reads them, or is the name of an anchor, optionally
+    /// followed by `+` or `-` and an offset: `seahorse + 1.5e-8,-2e-9`.
+    ///
+    /// Returns `None` if `s` is neither, or names an unknown anchor.
+    pub fn resolve(&self, s: &str, decimal: DecimalSeparator) -> Option<Complex<f64>> {
+        let s = s.trim();
+        let name_length = s
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+            .unwrap_or(s.len());
+        let name = &s[..name_length];
+        if !is_name(name) {
+            return parse_complex(s, decimal);
+        }
+
+        let anchor = self.get(name)?;
+        let rest = s[name_length..].trim_start();
+        if rest.is_empty() {
+            return Some(anchor);
+        }
+        let (sign, offset) = match rest.split_at(1) {
+            ("+", offset) => (1.0, offset),
+            ("-", offset) => (-1.0, offset),
+            _ => return None,
+        };
+        let offset = parse_complex(offset.trim(), decimal)?;
+        Some(anchor + offset * sign)
+    }
+}
+
+/// Whether `s` can be the name of an anchor: letters, digits and `_`,
+/// starting with a letter.
+pub(crate) fn is_name(s: &str) -> bool {
+    s.starts_with(|c: char| c.is_ascii_alphabetic())
+        && s.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+#[test]
+fn test_resolve() {
+    use DecimalSeparator::*;
+
+    let anchors = Anchors::default();
+    assert_eq!(
+        anchors.resolve("-0.5,0.25", Auto),
+        Some(Complex { re: -0.5, im: 0.25 })
+    );
+    assert_eq!(
+        anchors.resolve("seahorse", Auto),
+        Some(Complex { re: -0.75, im: 0.1 })
+    );
+    assert_eq!(
+        anchors.resolve("cusp + 1.5e-8,-2e-9", Auto),
+        Some(Complex {
+            re: 0.25 + 1.5e-8,
+            im: -2e-9
+        })
+    );
+    assert_eq!(
+        anchors.resolve("cusp-0.25,1", Auto),
+        Some(Complex { re: 0.0, im: -1.0 })
+    );
+    assert_eq!(
+        anchors.resolve("cusp + 0,5;1", Comma),
+        Some(Complex { re: 0.75, im: 1.0 })
+    );
+    assert_eq!(anchors.resolve("nowhere", Auto), None);
+    assert_eq!(anchors.resolve("cusp * 2,0", Auto), None);
+    assert_eq!(anchors.resolve("cusp + ", Auto), None);
+}
+
+#[test]
+fn test_parse_bookmarks() {
+    let anchors = Anchors::parse("# mine\nhome -0.5,0\n\nseahorse  -0.7,0.2 # moved\n").unwrap();
+    assert_eq!(anchors.get("home"), Some(Complex { re: -0.5, im: 0.0 }));
+    // bookmarks win over presets
+    assert_eq!(anchors.get("seahorse"), Some(Complex { re: -0.7, im: 0.2 }));
+    assert_eq!(anchors.get("cusp"), Some(Complex { re: 0.25, im: 0.0 }));
+
+    let anchors = Anchors::parse("valley @-0.75,0.1,z=40\n").unwrap();
+    assert_eq!(anchors.get("valley"), Some(Complex { re: -0.75, im: 0.1 }));
+
+    assert_eq!(Anchors::parse("home\n"), Err((1, "home".to_string())));
+    assert_eq!(
+        Anchors::parse("valley @-0.75,0.1,q=1"),
+        Err((1, "valley @-0.75,0.1,q=1".to_string()))
+    );
+    assert_eq!(
+        Anchors::parse("ok 1,1\n2x -1,0"),
+        Err((2, "2x -1,0".to_string()))
+    );
+}
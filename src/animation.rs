@@ -0,0 +1,504 @@
+//! Animations: a scene lists keyframes, views the camera passes through, and
+//! the frames in between are filled in to move smoothly from one to the next.
+//!
+//! Scenes are JSON files, which `explore` appends keyframes to as the user
+//! finds them:
+//!
+//! ```json
+//! {"version": 1, "keyframes": [{"center": [-0.5, 0], "zoom": 1}, {"center": [-0.75, 0.1], "zoom": 50}]}
+//! ```
+//!
+//! or TOML files, with a `[[keyframes]]` table for each keyframe. Besides
+//! its view, a keyframe can give the time the camera passes through it, in
+//! seconds, the rotation of the palette, in turns, the iteration limit, and
+//! the easing of the move to the next keyframe, which defaults to the
+//! scene's:
+//!
+//! ```toml
+//! version = 1
+//! easing = "smoothstep"
+//!
+//! [[keyframes]]
+//! time = 0
+//! center = [-0.5, 0]
+//! zoom = 1
+//!
+//! [[keyframes]]
+//! time = 8
+//! center = [-0.75, 0.1]
+//! zoom = 50
+//! rotation = 0.5
+//! max_iters = 1000
+//! ```
+//!
+//! The version, of `schema`, is optional.
+
+use std::fs;
+
+use num::Complex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{limit::MaxIters, output, platform, render, schema, toml, units::Zoom, Error, Result};
+
+/// How the camera moves from a keyframe to the next.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Easing {
+    /// The zoom and the center change by the same amount every frame.
+    Linear,
+    /// Like `Exponential`, but starting slowly and slowing down at the end.
+    Smoothstep,
+    /// The zoom changes by the same factor every frame, so the animation
+    /// dives at a steady pace, and the center moves in step with the width
+    /// of the view, so that a point zoomed into stays where it is on screen
+    /// rather than drifting away and coming back.
+    #[default]
+    Exponential,
+}
+
+/// A view the camera passes through.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Keyframe {
+    /// The center of the view, as its real and imaginary parts.
+    pub center: (f64, f64),
+    /// How much the view is magnified, as for `--zoom`.
+    pub zoom: f64,
+    /// When the camera passes through, in seconds from the start.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub time: Option<f64>,
+    /// How far the palette is rotated, in turns.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rotation: Option<f64>,
+    /// Iterations before a point is taken to be in the set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_iters: Option<usize>,
+    /// How the camera moves on to the next keyframe, if not as the scene
+    /// says.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub easing: Option<Easing>,
+}
+
+impl Keyframe {
+    /// The keyframe of the view centered on `center` magnified `zoom` times.
+    pub fn new(center: Complex<f64>, zoom: f64) -> Keyframe {
+        Keyframe {
+            center: (center.re, center.im),
+            zoom,
+            time: None,
+            rotation: None,
+            max_iters: None,
+            easing: None,
+        }
+    }
+
+    /// The center of the view.
+    pub fn center(&self) -> Complex<f64> {
+        Complex::new(self.center.0, self.center.1)
+    }
+
+    /// What is wrong with the keyframe, if anything.
+    fn problem(&self) -> Option<String> {
+        if !(self.zoom > 0.0 && self.zoom.is_finite()) {
+            Some(format!("zoom {} must be positive", self.zoom))
+        } else if !self.center.0.is_finite() || !self.center.1.is_finite() {
+            Some(format!("center {:?} must be finite", self.center))
+        } else if self
+            .time
+            .is_some_and(|time| !(time >= 0.0 && time.is_finite()))
+        {
+            Some(format!("time {:?} must be 0 or more", self.time.unwrap()))
+        } else if self.rotation.is_some_and(|rotation| !rotation.is_finite()) {
+            Some("rotation must be finite".to_string())
+        } else if self.max_iters == Some(0) {
+            Some("max_iters must be at least 1".to_string())
+        } else {
+            None
+        }
+    }
+}
+
+/// What the renderer needs to know to draw a frame.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Frame {
+    pub center: Complex<f64>,
+    pub zoom: Zoom,
+    /// How far the palette is rotated, in turns.
+    pub rotation: f64,
+    /// Iterations before a point is taken to be in the set, if not the
+    /// default.
+    pub max_iters: Option<usize>,
+}
+
+impl Frame {
+    /// The frame showing `keyframe` as it is.
+    pub fn at(keyframe: &Keyframe) -> Frame {
+        Frame {
+            center: keyframe.center(),
+            zoom: Zoom(keyframe.zoom),
+            rotation: keyframe.rotation.unwrap_or(0.0),
+            max_iters: keyframe.max_iters,
+        }
+    }
+
+    /// The frame `t` of the way from `from` to `to`, `t` going from 0 to 1.
+    pub fn between(from: &Keyframe, to: &Keyframe, t: f64, easing: Easing) -> Frame {
+        let s = match easing {
+            Easing::Smoothstep => t * t * (3.0 - 2.0 * t),
+            Easing::Linear | Easing::Exponential => t,
+        };
+        let (zoom, travelled) = match easing {
+            Easing::Linear => (from.zoom + (to.zoom - from.zoom) * s, s),
+            Easing::Smoothstep | Easing::Exponential => {
+                let zoom = from.zoom * (to.zoom / from.zoom).powf(s);
+                let (width_from, width_to) = (Zoom(from.zoom).width().0, Zoom(to.zoom).width().0);
+                let travelled = if width_from == width_to {
+                    s
+                } else {
+                    (width_from - Zoom(zoom).width().0) / (width_from - width_to)
+                };
+                (zoom, travelled)
+            }
+        };
+        let (rotation_from, rotation_to) =
+            (from.rotation.unwrap_or(0.0), to.rotation.unwrap_or(0.0));
+        Frame {
+            center: from.center() + (to.center() - from.center()) * travelled,
+            zoom: Zoom(zoom),
+            rotation: rotation_from + (rotation_to - rotation_from) * s,
+            max_iters: match (from.max_iters, to.max_iters) {
+                (Some(a), Some(b)) => Some((a as f64 + (b as f64 - a as f64) * s).round() as usize),
+                (a, b) => a.or(b),
+            },
+        }
+    }
+
+    /// The corners of the frame's view in an image whose size is given by
+    /// `bounds`.
+    pub fn corners(&self, bounds: (usize, usize)) -> (Complex<f64>, Complex<f64>) {
+        render::corners_from_center(self.center, self.zoom, bounds)
+    }
+
+    /// The iteration limit of the frame, rendered into an image of `bounds`
+    /// pixels: its own, or else `max_iters` for its view, or else the
+    /// default.
+    pub fn limit(&self, max_iters: Option<MaxIters>, bounds: (usize, usize)) -> usize {
+        match (self.max_iters, max_iters) {
+            (Some(limit), _) => limit,
+            (None, Some(max_iters)) => {
+                let (upper_left, lower_right) = self.corners(bounds);
+                max_iters.resolve(bounds, upper_left, lower_right)
+            }
+            (None, None) => render::DEFAULT_LIMIT,
+        }
+    }
+}
+
+#[test]
+fn test_easing() {
+    let from = Keyframe {
+        rotation: Some(1.0),
+        max_iters: Some(100),
+        ..Keyframe::new(Complex::new(0.0, 0.0), 1.0)
+    };
+    let to = Keyframe {
+        max_iters: Some(300),
+        ..Keyframe::new(Complex::new(1.0, 0.0), 4.0)
+    };
+
+    let linear = Frame::between(&from, &to, 0.5, Easing::Linear);
+    assert_eq!(linear.zoom, Zoom(2.5));
+    assert_eq!(linear.center, Complex::new(0.5, 0.0));
+    assert_eq!(linear.rotation, 0.5);
+    assert_eq!(linear.max_iters, Some(200));
+
+    let exponential = Frame::between(&from, &to, 0.5, Easing::Exponential);
+    assert_eq!(exponential.zoom, Zoom(2.0));
+    // half as wide as at the start, two thirds of the way to the end width
+    assert!((exponential.center.re - 2.0 / 3.0).abs() < 1e-12);
+
+    // smoothstep is symmetric, slow at both ends
+    let smooth = |t| Frame::between(&from, &to, t, Easing::Smoothstep);
+    assert_eq!(smooth(0.5).zoom, Zoom(2.0));
+    assert!(smooth(0.1).zoom < Frame::between(&from, &to, 0.1, Easing::Exponential).zoom);
+    assert_eq!(smooth(0.0), Frame::at(&from));
+    assert_eq!(smooth(1.0).zoom, Zoom(4.0));
+}
+
+/// The keyframes of an animation, in order.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Scene {
+    /// How the camera moves between keyframes that don't say otherwise.
+    #[serde(default)]
+    pub easing: Easing,
+    pub keyframes: Vec<Keyframe>,
+}
+
+impl Scene {
+    /// Parse a scene from its JSON description.
+    pub fn from_json(json: &str) -> Result<Scene> {
+        let value =
+            serde_json::from_str(json).map_err(|e| Error::parse("scene", &e.to_string()))?;
+        Scene::from_value(value)
+    }
+
+    /// Parse a scene from its TOML description.
+    pub fn from_toml(text: &str) -> Result<Scene> {
+        Scene::from_value(toml::to_json(text).map_err(|e| Error::parse("scene", &e))?)
+    }
+
+    fn from_value(mut value: Value) -> Result<Scene> {
+        let version = schema::take_version("scene", &mut value)?;
+        if version > schema::VERSION {
+            let mut dropped = Vec::new();
+            Scene::drop_unknown(&mut value, "", &mut dropped);
+            schema::warn_newer("the scene", version, schema::VERSION, &dropped);
+        }
+        let scene: Scene =
+            serde_json::from_value(value).map_err(|e| Error::parse("scene", &e.to_string()))?;
+        scene.checked()
+    }
+
+    /// Drop the fields of the scene `value`, and of its keyframes, that
+    /// scenes don't have, adding their names, after `path`, to `dropped`.
+    pub(crate) fn drop_unknown(value: &mut Value, path: &str, dropped: &mut Vec<String>) {
+        schema::drop_unknown::<Scene>(value, path, dropped);
+        if let Some(Value::Array(keyframes)) = value.get_mut("keyframes") {
+            for (i, keyframe) in keyframes.iter_mut().enumerate() {
+                let path = format!("{}keyframes[{}].", path, i);
+                schema::drop_unknown::<Keyframe>(keyframe, &path, dropped);
+            }
+        }
+    }
+
+    /// The scene, if its keyframes make sense.
+    pub(crate) fn checked(self) -> Result<Scene> {
+        for (number, keyframe) in self.keyframes.iter().enumerate() {
+            if let Some(problem) = keyframe.problem() {
+                return Err(Error::parse(
+                    "scene",
+                    &format!("keyframe {}: {}", number + 1, problem),
+                ));
+            }
+        }
+        let times: Vec<Option<f64>> = self.keyframes.iter().map(|k| k.time).collect();
+        if times.iter().any(Option::is_some) {
+            if times.iter().any(Option::is_none) {
+                return Err(Error::parse(
+                    "scene",
+                    "either every keyframe has a time or none does",
+                ));
+            }
+            if times.windows(2).any(|pair| pair[1] <= pair[0]) {
+                return Err(Error::parse("scene", "keyframe times must increase"));
+            }
+        }
+        Ok(self)
+    }
+
+    /// Read the scene in the file named `filename`, as TOML if its name ends
+    /// in `.toml` and as JSON otherwise.
+    pub fn read(filename: &str) -> Result<Scene> {
+        let text = fs::read_to_string(platform::path(filename)).map_err(Error::io(filename))?;
+        if is_toml(filename) {
+            Scene::from_toml(&text)
+        } else {
+            Scene::from_json(&text)
+        }
+    }
+
+    /// Write the scene to the file named `filename`, one keyframe per line,
+    /// as TOML if its name ends in `.toml` and as JSON otherwise.
+    pub fn write(&self, filename: &str) -> Result<()> {
+        let easing = serde_json::to_string(&self.easing).unwrap();
+        let text = if is_toml(filename) {
+            let mut text = format!("version = {}\neasing = {}\n", schema::VERSION, easing);
+            for keyframe in &self.keyframes {
+                text += "\n[[keyframes]]\n";
+                if let Value::Object(fields) = serde_json::to_value(keyframe).unwrap() {
+                    for (key, value) in fields {
+                        text += &format!("{} = {}\n", key, value);
+                    }
+                }
+            }
+            text
+        } else {
+            let keyframes: Vec<String> = self
+                .keyframes
+                .iter()
+                .map(|keyframe| serde_json::to_string(keyframe).unwrap())
+                .collect();
+            format!(
+                "{{\"version\": {}, \"easing\": {}, \"keyframes\": [\n  {}\n]}}\n",
+                schema::VERSION,
+                easing,
+                keyframes.join(",\n  ")
+            )
+        };
+        output::write(filename, text.as_bytes())
+    }
+
+    /// Every frame of the animation, and the last keyframe.
+    ///
+    /// If the keyframes have times, there are `fps` frames for every second
+    /// from one to the next; otherwise there are `per_keyframe` of them.
+    pub fn frames(&self, per_keyframe: usize, fps: u16) -> Vec<Frame> {
+        let mut frames = Vec::new();
+        for pair in self.keyframes.windows(2) {
+            let (from, to) = (&pair[0], &pair[1]);
+            let count = match (from.time, to.time) {
+                (Some(start), Some(end)) => ((end - start) * fps as f64).round() as usize,
+                _ => per_keyframe,
+            };
+            let easing = from.easing.unwrap_or(self.easing);
+            for i in 0..count {
+                frames.push(Frame::between(from, to, i as f64 / count as f64, easing));
+            }
+        }
+        frames.extend(self.keyframes.last().map(Frame::at));
+        frames
+    }
+}
+
+/// Whether `filename` names a TOML file, going by its extension.
+fn is_toml(filename: &str) -> bool {
+    filename.to_ascii_lowercase().ends_with(".toml")
+}
+
+#[test]
+fn test_frames() {
+    let scene = Scene::from_json(
+        r#"{"keyframes": [{"center": [0, 0], "zoom": 1}, {"center": [1, -1], "zoom": 4},
+            {"center": [3, -1], "zoom": 4}]}"#,
+    )
+    .unwrap();
+    let frames = scene.frames(2, 25);
+    assert_eq!(frames.len(), 5);
+    assert_eq!(frames[0], Frame::at(&scene.keyframes[0]));
+    // halfway, the zoom is 2, so the view is 2 wide: two thirds of the way
+    // from 4 to 1
+    assert_eq!(frames[1].zoom, Zoom(2.0));
+    assert!((frames[1].center - Complex::new(2.0 / 3.0, -2.0 / 3.0)).norm() < 1e-12);
+    assert_eq!(frames[2], Frame::at(&scene.keyframes[1]));
+    // at a steady zoom the center moves steadily
+    assert_eq!(frames[3].center, Complex::new(2.0, -1.0));
+    assert_eq!(frames[4], Frame::at(&scene.keyframes[2]));
+    let (upper_left, lower_right) = frames[4].corners((4, 4));
+    assert_eq!(lower_right.re - upper_left.re, 1.0);
+
+    assert!(Scene::default().frames(10, 25).is_empty());
+    assert!(Scene::from_json(r#"{"keyframes": [{"center": [0, 0], "zoom": 0}]}"#).is_err());
+    assert!(Scene::from_json(r#"{"keyframes": [{"centre": [0, 0], "zoom": 1}]}"#).is_err());
+}
+
+#[test]
+fn test_timed_frames() {
+    let scene = Scene::from_toml(
+        r#"
+        easing = "linear"
+
+        [[keyframes]]
+        time = 0
+        center = [0, 0]
+        zoom = 1
+        easing = "smoothstep"
+
+        [[keyframes]]
+        time = 2
+        center = [1, 0]
+        zoom = 8
+
+        [[keyframes]]
+        time = 2.5
+        center = [1, 0]
+        zoom = 10
+        "#,
+    )
+    .unwrap();
+    assert_eq!(scene.easing, Easing::Linear);
+    let frames = scene.frames(100, 10);
+    // 20 frames over two seconds, 5 over half a second, and the last one
+    assert_eq!(frames.len(), 26);
+    // the first move eases, the second is linear
+    assert_eq!(
+        frames[10],
+        Frame::between(
+            &scene.keyframes[0],
+            &scene.keyframes[1],
+            0.5,
+            Easing::Smoothstep
+        )
+    );
+    assert_eq!(frames[22].zoom, Zoom(8.0 + 2.0 * 2.0 / 5.0));
+
+    let untimed = r#"{"center": [0, 0], "zoom": 1}"#;
+    let timed = |time: f64| format!(r#"{{"center": [0, 0], "zoom": 1, "time": {}}}"#, time);
+    let scene = |keyframes: &[String]| {
+        Scene::from_json(&format!(r#"{{"keyframes": [{}]}}"#, keyframes.join(",")))
+    };
+    assert!(scene(&[timed(0.0), untimed.to_string()]).is_err());
+    assert!(scene(&[timed(1.0), timed(1.0)]).is_err());
+    assert!(scene(&[timed(-1.0)]).is_err());
+    assert!(scene(&[timed(0.0), timed(0.5)]).is_ok());
+}
+
+#[test]
+fn test_newer_scene() {
+    // fields added since are dropped, and the rest is read
+    let json = r#"{"version": 99, "camera": "dolly",
+        "keyframes": [{"center": [0, 0], "zoom": 1, "focus": 2}]}"#;
+    let scene = Scene::from_json(json).unwrap();
+    assert_eq!(
+        scene.keyframes,
+        [Keyframe::new(Complex::new(0.0, 0.0), 1.0)]
+    );
+    // but not in the versions we know
+    assert!(Scene::from_json(&json.replace("99", "1")).is_err());
+    assert!(Scene::from_json(r#"{"version": 1, "keyframes": []}"#).is_ok());
+}
+
+/// Add `keyframe` at the end of the scene in the file named `filename`,
+/// creating the file if there is none. Returns the number of keyframes the
+/// scene has now.
+pub fn append_keyframe(filename: &str, keyframe: Keyframe) -> Result<usize> {
+    let mut scene = if platform::path(filename).exists() {
+        Scene::read(filename)?
+    } else {
+        Scene::default()
+    };
+    scene.keyframes.push(keyframe);
+    scene.write(filename)?;
+    Ok(scene.keyframes.len())
+}
+
+#[test]
+fn test_append_keyframe() {
+    for extension in ["json", "toml"] {
+        let file = std::env::temp_dir().join(format!(
+            "mandelbrot-scene-{}.{}",
+            std::process::id(),
+            extension
+        ));
+        let file = file.to_str().unwrap();
+        let _ = fs::remove_file(file);
+
+        let first = Keyframe::new(Complex::new(-0.5, 0.0), 1.0);
+        let second = Keyframe {
+            rotation: Some(0.25),
+            easing: Some(Easing::Linear),
+            ..Keyframe::new(Complex::new(-0.75, 0.1), 50.0)
+        };
+        assert_eq!(append_keyframe(file, first).unwrap(), 1);
+        assert_eq!(append_keyframe(file, second).unwrap(), 2);
+        assert_eq!(
+            Scene::read(file).unwrap(),
+            Scene {
+                easing: Easing::Exponential,
+                keyframes: vec![first, second]
+            }
+        );
+        fs::remove_file(file).unwrap();
+    }
+}
@@ -0,0 +1,265 @@
+//! Statistics of the set within a view, for `stats area`: its area,
+//! estimated by Monte Carlo from random points in every pixel, the fraction
+//! of pixels inside, how the escape times of the others are distributed,
+//! and how rough the boundary is, as its box-counting dimension at the
+//! scales of the image.
+//!
+//! Points that haven't escaped within the limit count as inside, so the
+//! area is overestimated, the less the higher the limit.
+
+use std::thread;
+
+use num::Complex;
+use serde::Serialize;
+
+use crate::{
+    render,
+    sampling::{splitmix64, Rng},
+};
+
+/// How many pixels escaped after a number of iterations from `from`
+/// (included) to `to` (excluded).
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub struct Bin {
+    pub from: usize,
+    pub to: usize,
+    pub pixels: usize,
+}
+
+/// What `stats area` reports about a view.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct AreaStats {
+    pub pixels: [usize; 2],
+    pub upper_left: [f64; 2],
+    pub lower_right: [f64; 2],
+    pub max_iters: usize,
+    /// The random points drawn, `samples` in every pixel.
+    pub points: usize,
+    /// The area of the set within the view, and the standard error of the
+    /// estimate.
+    pub area: f64,
+    pub area_error: f64,
+    /// The fraction of pixels whose center doesn't escape.
+    pub interior_fraction: f64,
+    /// The escape times of the other pixels, in bins twice as wide as the
+    /// one before, from `[0, 1)` to the one holding `max_iters - 1`.
+    pub escape_times: Vec<Bin>,
+    /// The pixels inside next to one outside.
+    pub boundary_pixels: usize,
+    /// How the number of boxes holding boundary pixels grows as the boxes
+    /// shrink, from 1 for a smooth curve to 2 for one filling the plane;
+    /// `None` without a boundary or with too few scales to measure it.
+    pub boundary_dimension: Option<f64>,
+}
+
+/// The statistics of the view from `upper_left` to `lower_right`, an image
+/// of `bounds` pixels, with `limit` iterations and `samples` random points
+/// in each pixel, drawn from `seed`, using `threads` threads.
+pub fn measure(
+    bounds: (usize, usize),
+    upper_left: Complex<f64>,
+    lower_right: Complex<f64>,
+    limit: usize,
+    samples: usize,
+    seed: u64,
+    threads: usize,
+) -> AreaStats {
+    let pixel_count = bounds.0 * bounds.1;
+    let (width, height) = (
+        (lower_right.re - upper_left.re) / bounds.0 as f64,
+        (upper_left.im - lower_right.im) / bounds.1 as f64,
+    );
+    // the escape time of the center of each pixel, and how many of its
+    // random points are inside
+    let mut pixels = vec![(None, 0); pixel_count];
+    let rows_per_band = bounds.1.div_ceil(threads.max(1)).max(1);
+    thread::scope(|scope| {
+        for (band, rows) in pixels
+            .chunks_mut(rows_per_band * bounds.0.max(1))
+            .enumerate()
+        {
+            scope.spawn(move || {
+                for (i, pixel) in rows.iter_mut().enumerate() {
+                    let index = band * rows_per_band * bounds.0 + i;
+                    let (x, y) = ((index % bounds.0) as f64, (index / bounds.0) as f64);
+                    let at = |dx: f64, dy: f64| {
+                        Complex::new(
+                            upper_left.re + (x + dx) * width,
+                            upper_left.im - (y + dy) * height,
+                        )
+                    };
+                    let mut rng = Rng::new(splitmix64(seed ^ index as u64));
+                    let inside = (0..samples)
+                        .filter(|_| {
+                            let point = at(rng.next_f64(), rng.next_f64());
+                            render::escape_time(point, limit).is_none()
+                        })
+                        .count();
+                    *pixel = (render::escape_time(at(0.5, 0.5), limit), inside);
+                }
+            });
+        }
+    });
+
+    let points = pixel_count * samples;
+    let fraction =
+        pixels.iter().map(|&(_, inside)| inside).sum::<usize>() as f64 / points.max(1) as f64;
+    let view_area = (lower_right.re - upper_left.re) * (upper_left.im - lower_right.im);
+    let interior: Vec<bool> = pixels.iter().map(|(time, _)| time.is_none()).collect();
+    let boundary = boundary(&interior, bounds);
+
+    let mut escape_times: Vec<Bin> = bins(limit)
+        .map(|(from, to)| Bin {
+            from,
+            to,
+            pixels: 0,
+        })
+        .collect();
+    for time in pixels.iter().filter_map(|&(time, _)| time) {
+        if let Some(bin) = escape_times.iter_mut().find(|bin| time < bin.to) {
+            bin.pixels += 1;
+        }
+    }
+
+    AreaStats {
+        pixels: [bounds.0, bounds.1],
+        upper_left: [upper_left.re, upper_left.im],
+        lower_right: [lower_right.re, lower_right.im],
+        max_iters: limit,
+        points,
+        area: view_area * fraction,
+        area_error: view_area * (fraction * (1.0 - fraction) / points.max(1) as f64).sqrt(),
+        interior_fraction: interior.iter().filter(|&&inside| inside).count() as f64
+            / pixel_count.max(1) as f64,
+        escape_times,
+        boundary_pixels: boundary.iter().filter(|&&edge| edge).count(),
+        boundary_dimension: box_dimension(&boundary, bounds),
+    }
+}
+
+/// The bins of escape times below `limit`: `[0, 1)`, `[1, 2)`, `[2, 4)` and
+/// so on, the last one ending at `limit`.
+fn bins(limit: usize) -> impl Iterator<Item = (usize, usize)> {
+    let mut from = 0;
+    std::iter::from_fn(move || {
+        if from >= limit {
+            return None;
+        }
+        let to = (from * 2).clamp(1, limit);
+        let bin = (from, to);
+        from = to;
+        Some(bin)
+    })
+}
+
+#[test]
+fn test_bins() {
+    assert_eq!(
+        bins(10).collect::<Vec<_>>(),
+        [(0, 1), (1, 2), (2, 4), (4, 8), (8, 10)]
+    );
+    assert_eq!(bins(1).collect::<Vec<_>>(), [(0, 1)]);
+}
+
+/// The pixels of `interior`, an image of `bounds` pixels, that are inside
+/// and next to one outside, across or down.
+fn boundary(interior: &[bool], bounds: (usize, usize)) -> Vec<bool> {
+    let outside = |x: usize, y: usize| !interior[y * bounds.0 + x];
+    (0..interior.len())
+        .map(|i| {
+            let (x, y) = (i % bounds.0, i / bounds.0);
+            interior[i]
+                && ((x > 0 && outside(x - 1, y))
+                    || (x + 1 < bounds.0 && outside(x + 1, y))
+                    || (y > 0 && outside(x, y - 1))
+                    || (y + 1 < bounds.1 && outside(x, y + 1)))
+        })
+        .collect()
+}
+
+/// The box-counting dimension of the pixels set in `mask`, an image of
+/// `bounds` pixels: the slope of the number of boxes holding any against
+/// their size, on log scales, fitted by least squares over boxes from 1
+/// pixel to a quarter of the smaller side.
+fn box_dimension(mask: &[bool], bounds: (usize, usize)) -> Option<f64> {
+    let mut logs = Vec::new();
+    let mut size = 1;
+    while size * 4 <= bounds.0.min(bounds.1) {
+        let across = bounds.0.div_ceil(size);
+        let mut boxes = vec![false; across * bounds.1.div_ceil(size)];
+        for (i, _) in mask.iter().enumerate().filter(|&(_, &set)| set) {
+            let (x, y) = (i % bounds.0 / size, i / bounds.0 / size);
+            boxes[y * across + x] = true;
+        }
+        let count = boxes.iter().filter(|&&set| set).count();
+        if count == 0 {
+            return None;
+        }
+        logs.push(((size as f64).ln(), (count as f64).ln()));
+        size *= 2;
+    }
+    if logs.len() < 3 {
+        return None;
+    }
+    let n = logs.len() as f64;
+    let (mean_x, mean_y) = (
+        logs.iter().map(|&(x, _)| x).sum::<f64>() / n,
+        logs.iter().map(|&(_, y)| y).sum::<f64>() / n,
+    );
+    let covariance: f64 = logs.iter().map(|&(x, y)| (x - mean_x) * (y - mean_y)).sum();
+    let variance: f64 = logs.iter().map(|&(x, _)| (x - mean_x).powi(2)).sum();
+    Some(-covariance / variance)
+}
+
+#[test]
+fn test_box_dimension() {
+    let bounds = (256, 256);
+    // a line is one-dimensional, a filled square two
+    let line: Vec<bool> = (0..256 * 256).map(|i| i / 256 == 100).collect();
+    assert!((box_dimension(&line, bounds).unwrap() - 1.0).abs() < 0.01);
+    let full = vec![true; 256 * 256];
+    assert!((box_dimension(&full, bounds).unwrap() - 2.0).abs() < 0.01);
+    // a circle's boundary is a curve
+    let disk: Vec<bool> = (0..256 * 256)
+        .map(|i| {
+            let (x, y) = ((i % 256) as f64 - 128.0, (i / 256) as f64 - 128.0);
+            x * x + y * y < 100.0 * 100.0
+        })
+        .collect();
+    let edge = boundary(&disk, bounds);
+    assert!((box_dimension(&edge, bounds).unwrap() - 1.0).abs() < 0.1);
+    assert_eq!(box_dimension(&vec![false; 256 * 256], bounds), None);
+    assert_eq!(box_dimension(&[true; 64], (8, 8)), None);
+}
+
+#[test]
+fn test_measure() {
+    // the whole set, whose area is about 1.5066
+    let (upper_left, lower_right) = (Complex::new(-2.0, 1.25), Complex::new(0.5, -1.25));
+    let stats = measure((100, 100), upper_left, lower_right, 1000, 4, 0, 4);
+    assert_eq!(stats.points, 40000);
+    assert!((stats.area - 1.5066).abs() < 0.05, "{:?}", stats);
+    assert!(stats.area_error > 0.0 && stats.area_error < 0.02);
+    assert!((stats.interior_fraction - stats.area / 6.25).abs() < 0.02);
+    let escaped: usize = stats.escape_times.iter().map(|bin| bin.pixels).sum();
+    assert_eq!(
+        escaped as f64,
+        ((1.0 - stats.interior_fraction) * 10000.0).round()
+    );
+    assert_eq!(stats.escape_times.last().unwrap().to, 1000);
+    let dimension = stats.boundary_dimension.unwrap();
+    assert!(dimension > 1.0 && dimension < 2.0, "{}", dimension);
+
+    // the same seed, the same estimate, whatever the threads
+    assert_eq!(
+        measure((100, 100), upper_left, lower_right, 1000, 4, 0, 1),
+        stats
+    );
+
+    // inside the main cardioid: all set, no boundary
+    let (upper_left, lower_right) = (Complex::new(-0.2, 0.1), Complex::new(0.0, -0.1));
+    let stats = measure((20, 20), upper_left, lower_right, 100, 1, 0, 2);
+    assert_eq!(stats.interior_fraction, 1.0);
+    assert!((stats.area - 0.04).abs() < 1e-12);
+    assert_eq!((stats.boundary_pixels, stats.boundary_dimension), (0, None));
+}
@@ -0,0 +1,308 @@
+//! Artistic output: the image redrawn as stipple dots or cross-hatch
+//! strokes, the way it would be drawn by hand with a pen.
+//!
+//! The darker a pixel of the escape-time image, the more ink goes around
+//! it: more dots, or more layers of hatching. The marks are kept as shapes,
+//! so they can be written as SVG, for pen plotters, or rasterized into an
+//! image of their own.
+
+use std::{fmt::Write as _, str::FromStr};
+
+use crate::{output, sampling::Rng, Result};
+
+/// How the image is drawn.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Kind {
+    /// Dots, crowding where the image is dark.
+    Stipple,
+    /// Parallel strokes, in up to four directions as the image darkens.
+    Hatch,
+}
+
+impl FromStr for Kind {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Kind, String> {
+        match s {
+            "stipple" => Ok(Kind::Stipple),
+            "hatch" => Ok(Kind::Hatch),
+            _ => Err(format!("unknown art style {}", s)),
+        }
+    }
+}
+
+#[test]
+fn test_parse_kind() {
+    assert_eq!("stipple".parse(), Ok(Kind::Stipple));
+    assert_eq!("hatch".parse(), Ok(Kind::Hatch));
+    assert!("pointillism".parse::<Kind>().is_err());
+}
+
+/// The parameters of the artistic output.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Art {
+    pub kind: Kind,
+    /// The size, in pixels, of the cells dots are scattered in, and the
+    /// distance between hatching strokes.
+    pub spacing: usize,
+}
+
+impl Default for Art {
+    fn default() -> Art {
+        Art {
+            kind: Kind::Stipple,
+            spacing: 6,
+        }
+    }
+}
+
+/// The most dots a cell gets, when it is black.
+const MAX_DOTS: f64 = 4.0;
+
+/// The darkness above which each layer of hatching is drawn, with the
+/// direction of its strokes.
+const HATCH_LAYERS: [(f64, (f64, f64)); 4] = [
+    (0.2, (1.0, 1.0)),
+    (0.45, (1.0, -1.0)),
+    (0.7, (1.0, 0.0)),
+    (0.9, (0.0, 1.0)),
+];
+
+/// The marks making up a drawing, in pixel coordinates.
+#[derive(Debug, PartialEq)]
+pub struct Drawing {
+    pub bounds: (usize, usize),
+    pub dot_radius: f64,
+    pub dots: Vec<(f64, f64)>,
+    pub strokes: Vec<((f64, f64), (f64, f64))>,
+}
+
+/// Redraw `pixels`, a buffer produced by `render` whose dimensions are given
+/// by `bounds`, as `art` says. `seed` decides where the dots fall.
+pub fn draw(pixels: &[u8], bounds: (usize, usize), art: &Art, seed: u64) -> Drawing {
+    assert!(pixels.len() == bounds.0 * bounds.1);
+    let spacing = art.spacing.max(1);
+    let darkness = |x: f64, y: f64| 1.0 - pixels[y as usize * bounds.0 + x as usize] as f64 / 255.0;
+    let mut drawing = Drawing {
+        bounds,
+        dot_radius: spacing as f64 / 5.0,
+        dots: Vec::new(),
+        strokes: Vec::new(),
+    };
+
+    match art.kind {
+        Kind::Stipple => {
+            let mut rng = Rng::new(seed);
+            for top in (0..bounds.1).step_by(spacing) {
+                for left in (0..bounds.0).step_by(spacing) {
+                    let (width, height) = (
+                        spacing.min(bounds.0 - left) as f64,
+                        spacing.min(bounds.1 - top) as f64,
+                    );
+                    let center = (left as f64 + width / 2.0, top as f64 + height / 2.0);
+                    // the fraction of a dot left over is drawn or not at random
+                    let dots = darkness(center.0, center.1) * MAX_DOTS + rng.next_f64();
+                    for _ in 0..dots as usize {
+                        drawing.dots.push((
+                            left as f64 + rng.next_f64() * width,
+                            top as f64 + rng.next_f64() * height,
+                        ));
+                    }
+                }
+            }
+        }
+        Kind::Hatch => {
+            let corners = [
+                (0.0, 0.0),
+                (bounds.0 as f64, 0.0),
+                (0.0, bounds.1 as f64),
+                (bounds.0 as f64, bounds.1 as f64),
+            ];
+            for (threshold, direction) in HATCH_LAYERS {
+                let length = f64::hypot(direction.0, direction.1);
+                let along = (direction.0 / length, direction.1 / length);
+                let across = (-along.1, along.0);
+                let range = |axis: (f64, f64)| {
+                    let projections = corners.map(|(x, y)| x * axis.0 + y * axis.1);
+                    let min = projections.iter().copied().fold(f64::INFINITY, f64::min);
+                    let max = projections
+                        .iter()
+                        .copied()
+                        .fold(f64::NEG_INFINITY, f64::max);
+                    (min, max)
+                };
+                let (first_line, last_line) = range(across);
+                let (start, end) = range(along);
+
+                // walk along every line, a pixel at a time, drawing where the
+                // image is darker than the threshold
+                let mut offset = (first_line / spacing as f64).ceil() * spacing as f64;
+                while offset <= last_line {
+                    let mut stroke_start = None;
+                    let mut t = start;
+                    while t <= end + 1.0 {
+                        let point = (
+                            across.0 * offset + along.0 * t,
+                            across.1 * offset + along.1 * t,
+                        );
+                        let inside = point.0 >= 0.0
+                            && point.1 >= 0.0
+                            && point.0 < bounds.0 as f64
+                            && point.1 < bounds.1 as f64;
+                        let dark = inside && darkness(point.0, point.1) > threshold;
+                        match (dark, stroke_start) {
+                            (true, None) => stroke_start = Some(point),
+                            (false, Some(from)) => {
+                                let to = (point.0 - along.0, point.1 - along.1);
+                                drawing.strokes.push((from, to));
+                                stroke_start = None;
+                            }
+                            _ => {}
+                        }
+                        t += 1.0;
+                    }
+                    offset += spacing as f64;
+                }
+            }
+        }
+    }
+
+    drawing
+}
+
+#[test]
+fn test_draw_stipple() {
+    // black on the left, white on the right
+    let bounds = (60, 30);
+    let pixels: Vec<u8> = (0..bounds.0 * bounds.1)
+        .map(|i| if i % bounds.0 < 30 { 0 } else { 255 })
+        .collect();
+    let art = Art {
+        kind: Kind::Stipple,
+        spacing: 5,
+    };
+    let drawing = draw(&pixels, bounds, &art, 1);
+    assert!(drawing.strokes.is_empty());
+    assert!(drawing.dots.iter().all(|&(x, _)| x < 30.0));
+    // every black cell gets the most dots
+    assert_eq!(drawing.dots.len(), 6 * 6 * MAX_DOTS as usize);
+    assert_eq!(draw(&pixels, bounds, &art, 1), drawing);
+}
+
+#[test]
+fn test_draw_hatch() {
+    let art = Art {
+        kind: Kind::Hatch,
+        spacing: 4,
+    };
+    let white = draw(&[255; 400], (20, 20), &art, 0);
+    assert!(white.strokes.is_empty() && white.dots.is_empty());
+
+    // black gets all four layers, with the horizontal strokes going all the
+    // way across
+    let black = draw(&[0; 400], (20, 20), &art, 0);
+    assert!(black.strokes.contains(&((0.0, 4.0), (19.0, 4.0))));
+    assert!(black.strokes.contains(&((8.0, 0.0), (8.0, 19.0))));
+    assert!(black
+        .strokes
+        .iter()
+        .all(|&((x1, y1), (x2, y2))| [x1, y1, x2, y2].iter().all(|v| (-1e-9..20.0).contains(v))));
+}
+
+impl Drawing {
+    /// The drawing as an SVG document, one `<circle>` per dot and one
+    /// `<line>` per stroke, black on white.
+    pub fn to_svg(&self) -> String {
+        let (width, height) = self.bounds;
+        let mut svg = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" viewBox=\"0 0 {} {}\">\n\
+             <rect width=\"100%\" height=\"100%\" fill=\"white\"/>\n",
+            width, height, width, height
+        );
+        if !self.dots.is_empty() {
+            svg.push_str("<g fill=\"black\">\n");
+            for (x, y) in &self.dots {
+                let _ = writeln!(
+                    svg,
+                    "<circle cx=\"{:.2}\" cy=\"{:.2}\" r=\"{:.2}\"/>",
+                    x, y, self.dot_radius
+                );
+            }
+            svg.push_str("</g>\n");
+        }
+        if !self.strokes.is_empty() {
+            svg.push_str("<g stroke=\"black\" stroke-width=\"1\" stroke-linecap=\"round\">\n");
+            for ((x1, y1), (x2, y2)) in &self.strokes {
+                let _ = writeln!(
+                    svg,
+                    "<line x1=\"{:.2}\" y1=\"{:.2}\" x2=\"{:.2}\" y2=\"{:.2}\"/>",
+                    x1, y1, x2, y2
+                );
+            }
+            svg.push_str("</g>\n");
+        }
+        svg.push_str("</svg>\n");
+        svg
+    }
+
+    /// Draw the marks into a grayscale image `bounds` in size, black on
+    /// white.
+    pub fn rasterize(&self) -> Vec<u8> {
+        let (width, height) = self.bounds;
+        let mut pixels = vec![255; width * height];
+        let mut ink = |x: f64, y: f64| {
+            if x >= 0.0 && y >= 0.0 && (x as usize) < width && (y as usize) < height {
+                pixels[y as usize * width + x as usize] = 0;
+            }
+        };
+
+        let r = self.dot_radius.max(0.5);
+        for &(cx, cy) in &self.dots {
+            let mut y = (cy - r).floor();
+            while y <= cy + r {
+                let mut x = (cx - r).floor();
+                while x <= cx + r {
+                    if (x + 0.5 - cx).powi(2) + (y + 0.5 - cy).powi(2) <= r * r {
+                        ink(x, y);
+                    }
+                    x += 1.0;
+                }
+                y += 1.0;
+            }
+        }
+        for &((x1, y1), (x2, y2)) in &self.strokes {
+            let steps = (f64::hypot(x2 - x1, y2 - y1) * 2.0).ceil().max(1.0);
+            for i in 0..=steps as usize {
+                let t = i as f64 / steps;
+                ink(x1 + (x2 - x1) * t, y1 + (y2 - y1) * t);
+            }
+        }
+        pixels
+    }
+
+    /// Write the drawing as SVG to the file named `filename`, or to stdout
+    /// if `filename` is `-`.
+    pub fn write_svg(&self, filename: &str) -> Result<()> {
+        output::write(filename, self.to_svg().as_bytes())
+    }
+}
+
+#[test]
+fn test_svg_and_rasterize() {
+    let drawing = Drawing {
+        bounds: (10, 10),
+        dot_radius: 1.0,
+        dots: vec![(2.0, 2.0)],
+        strokes: vec![((0.0, 8.0), (9.0, 8.0))],
+    };
+    let svg = drawing.to_svg();
+    assert!(svg.starts_with("<svg "));
+    assert!(svg.contains("<circle cx=\"2.00\" cy=\"2.00\" r=\"1.00\"/>"));
+    assert!(svg.contains("<line x1=\"0.00\" y1=\"8.00\" x2=\"9.00\" y2=\"8.00\"/>"));
+    assert!(svg.ends_with("</svg>\n"));
+
+    let pixels = drawing.rasterize();
+    assert_eq!(pixels[2 * 10 + 2], 0);
+    assert_eq!(pixels[5 * 10 + 5], 255);
+    assert!(pixels[80..90].iter().all(|&p| p == 0));
+}
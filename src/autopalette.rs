@@ -0,0 +1,235 @@
+//! Palettes fitted to the image they color, for `--palette auto`.
+//!
+//! Most of the pixels of a view usually crowd into a few narrow ranges of
+//! gray levels, which a palette made by hand spreads over only a few of its
+//! colors. Clustering the gray levels of the image with k-means, and putting
+//! a stop at the center of every cluster, gives each crowd its own color,
+//! evenly spaced along the palette, whatever the depth of the view.
+
+use crate::{
+    palette::{self, Cycle, Interpolation, Palette, Stop},
+    Result,
+};
+
+/// How many stops fitted palettes have.
+pub const STOPS: usize = 8;
+
+/// How many rounds of k-means are run at most.
+const ROUNDS: usize = 50;
+
+/// A palette as `--palette` gives it: fixed, or fitted to every image.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Choice {
+    Fixed(Palette),
+    /// The colors of the palette, spread over the clusters of gray levels
+    /// of each image.
+    Auto(Palette),
+}
+
+/// The palette `name` stands for: `auto` for gray fitted to the image,
+/// `auto:hues` for a rainbow getting lighter fitted to it, `auto:NAME` for
+/// the colors of palette `NAME` fitted to it, and any other name for that
+/// palette as it is.
+pub fn choose(name: &str) -> Result<Choice> {
+    match name.strip_prefix("auto") {
+        Some("") => Ok(Choice::Auto(Palette::gray())),
+        Some(":hues") => Ok(Choice::Auto(hues())),
+        Some(base) => match base.strip_prefix(':') {
+            Some(base) => Ok(Choice::Auto(palette::load(base)?)),
+            None => palette::load(name).map(Choice::Fixed),
+        },
+        None => palette::load(name).map(Choice::Fixed),
+    }
+}
+
+impl Choice {
+    /// The lookup table, from gray level 0 to 255, coloring the grayscale
+    /// `pixels`, the gradient laid over the gray levels as `cycle` says.
+    pub fn lut_for(&self, pixels: &[u8], cycle: &Cycle) -> Vec<[u8; 3]> {
+        match self {
+            Choice::Fixed(palette) => palette.cycled_lut(256, cycle),
+            Choice::Auto(base) => fit(pixels, base, STOPS).cycled_lut(256, cycle),
+        }
+    }
+
+    /// Like `lut_for`, without rounding the levels of the colors to whole
+    /// numbers, for `dither`.
+    pub fn levels_for(&self, pixels: &[u8], cycle: &Cycle) -> Vec<[f64; 3]> {
+        match self {
+            Choice::Fixed(palette) => palette.cycled_levels(256, cycle),
+            Choice::Auto(base) => fit(pixels, base, STOPS).cycled_levels(256, cycle),
+        }
+    }
+}
+
+#[test]
+fn test_choose() {
+    assert_eq!(choose("auto").unwrap(), Choice::Auto(Palette::gray()));
+    assert_eq!(
+        choose("auto:fire").unwrap(),
+        Choice::Auto(palette::preset("fire").unwrap())
+    );
+    assert!(matches!(choose("auto:hues").unwrap(), Choice::Auto(_)));
+    assert_eq!(
+        choose("ocean").unwrap(),
+        Choice::Fixed(palette::preset("ocean").unwrap())
+    );
+    assert!(choose("auto:plaid").is_err());
+    assert!(choose("automatic").is_err());
+}
+
+/// Hues around the color wheel, from blue to red, getting lighter, so that
+/// neighboring stops differ in both.
+fn hues() -> Palette {
+    let last = (STOPS - 1) as f64;
+    let stops = (0..STOPS)
+        .map(|i| {
+            let t = i as f64 / last;
+            Stop {
+                position: t,
+                color: hsl(240.0 - 300.0 * t, 0.8, 0.1 + 0.8 * t),
+            }
+        })
+        .collect();
+    Palette::new(stops, Interpolation::Smooth).expect("the stops are between 0 and 1")
+}
+
+/// The RGB color of hue `h`, in degrees, saturation `s` and lightness `l`.
+fn hsl(h: f64, s: f64, l: f64) -> [u8; 3] {
+    let c = (1.0 - (2.0 * l - 1.0).abs()) * s;
+    let h = h.rem_euclid(360.0) / 60.0;
+    let x = c * (1.0 - (h % 2.0 - 1.0).abs());
+    let (r, g, b) = match h as u32 {
+        0 => (c, x, 0.0),
+        1 => (x, c, 0.0),
+        2 => (0.0, c, x),
+        3 => (0.0, x, c),
+        4 => (x, 0.0, c),
+        _ => (c, 0.0, x),
+    };
+    let m = l - c / 2.0;
+    [r, g, b].map(|v| ((v + m) * 255.0).round().clamp(0.0, 255.0) as u8)
+}
+
+#[test]
+fn test_hsl() {
+    assert_eq!(hsl(0.0, 1.0, 0.5), [255, 0, 0]);
+    assert_eq!(hsl(120.0, 1.0, 0.5), [0, 255, 0]);
+    assert_eq!(hsl(-120.0, 1.0, 0.5), [0, 0, 255]);
+    assert_eq!(hsl(42.0, 0.0, 1.0), [255, 255, 255]);
+}
+
+/// The centers of `k` clusters of the gray levels counted in `histogram`,
+/// found with k-means, in increasing order. There are fewer if there are
+/// fewer different levels.
+pub fn kmeans(histogram: &[u64; 256], k: usize) -> Vec<f64> {
+    let levels: Vec<usize> = (0..256).filter(|&level| histogram[level] > 0).collect();
+    if levels.len() <= k {
+        return levels.iter().map(|&level| level as f64).collect();
+    }
+
+    // start from the quantiles, so the crowded ranges get more clusters
+    let total: u64 = histogram.iter().sum();
+    let mut centers = Vec::with_capacity(k);
+    let mut seen = 0;
+    let mut next = 0;
+    for &level in &levels {
+        seen += histogram[level];
+        while next < k && seen * k as u64 > next as u64 * total + total / 2 {
+            centers.push(level as f64);
+            next += 1;
+        }
+    }
+    centers.dedup();
+
+    for _ in 0..ROUNDS {
+        let mut sums = vec![(0.0, 0u64); centers.len()];
+        for &level in &levels {
+            let nearest = nearest(&centers, level as f64);
+            sums[nearest].0 += level as f64 * histogram[level] as f64;
+            sums[nearest].1 += histogram[level];
+        }
+        let moved: Vec<f64> = sums
+            .iter()
+            .zip(&centers)
+            .map(|(&(sum, count), &center)| {
+                if count == 0 {
+                    center
+                } else {
+                    sum / count as f64
+                }
+            })
+            .collect();
+        let done = moved == centers;
+        centers = moved;
+        if done {
+            break;
+        }
+    }
+    centers.sort_by(f64::total_cmp);
+    centers.dedup();
+    centers
+}
+
+/// The index of the center in `centers` closest to `value`.
+fn nearest(centers: &[f64], value: f64) -> usize {
+    (0..centers.len())
+        .min_by(|&a, &b| {
+            (centers[a] - value)
+                .abs()
+                .total_cmp(&(centers[b] - value).abs())
+        })
+        .unwrap()
+}
+
+#[test]
+fn test_kmeans() {
+    let mut histogram = [0; 256];
+    histogram[10..20].fill(100);
+    histogram[200..204].fill(10);
+    let centers = kmeans(&histogram, 2);
+    assert_eq!(centers, [14.5, 201.5]);
+
+    let mut few = [0; 256];
+    few[3] = 1;
+    few[250] = 7;
+    assert_eq!(kmeans(&few, 8), [3.0, 250.0]);
+    assert!(kmeans(&[0; 256], 4).is_empty());
+}
+
+/// The palette coloring the grayscale `pixels` with the colors of `base`:
+/// `stops` of them, evenly spaced along `base`, at the centers of as many
+/// clusters of the gray levels of `pixels`.
+pub fn fit(pixels: &[u8], base: &Palette, stops: usize) -> Palette {
+    let mut histogram = [0; 256];
+    for &level in pixels {
+        histogram[level as usize] += 1;
+    }
+    let centers = kmeans(&histogram, stops);
+    if centers.len() < 2 {
+        return base.clone();
+    }
+    let last = (centers.len() - 1) as f64;
+    let stops = centers
+        .iter()
+        .enumerate()
+        .map(|(i, &center)| Stop {
+            position: center / 255.0,
+            color: base.color_at(i as f64 / last),
+        })
+        .collect();
+    Palette::new(stops, Interpolation::Linear).expect("the centers are gray levels")
+}
+
+#[test]
+fn test_fit() {
+    // a dark image, whose levels all crowd near black
+    let pixels: Vec<u8> = (0..1000).map(|i| (i % 40) as u8).collect();
+    let lut = fit(&pixels, &Palette::gray(), 4).lut(256);
+    // the levels it has get most of the range, not a sixth of it
+    assert!(lut[5][0] < 40, "{:?}", lut[5]);
+    assert!(lut[35][0] > 215, "{:?}", lut[35]);
+
+    let flat = fit(&[9; 100], &Palette::gray(), 4);
+    assert_eq!(flat, Palette::gray());
+}
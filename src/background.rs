@@ -0,0 +1,103 @@
+//! Backdrops for `--background`: the points that escape are clipped away
+//! and a color or an image shows through instead, so the set can be put
+//! over a picture of the user's in one step.
+
+use std::str::FromStr;
+
+use crate::palette;
+
+/// What shows where points escape.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Background {
+    /// One color, `#rrggbb`.
+    Color([u8; 3]),
+    /// A PNG image, resized to the size of the render.
+    Image(String),
+}
+
+impl FromStr for Background {
+    type Err = String;
+
+    /// Parse `#rrggbb` as a color, anything else as the name of an image.
+    fn from_str(s: &str) -> std::result::Result<Background, String> {
+        if s.starts_with('#') {
+            Ok(Background::Color(palette::parse_hex(s)?))
+        } else if s.is_empty() {
+            Err("expected a color or an image file as the background".to_string())
+        } else {
+            Ok(Background::Image(s.to_string()))
+        }
+    }
+}
+
+#[test]
+fn test_parse_background() {
+    assert_eq!("#ff8000".parse(), Ok(Background::Color([255, 128, 0])));
+    assert_eq!(
+        "sky.png".parse(),
+        Ok(Background::Image("sky.png".to_string()))
+    );
+    assert!("#ff80".parse::<Background>().is_err());
+    assert!("".parse::<Background>().is_err());
+}
+
+impl Background {
+    /// The RGB pixels of the backdrop of an image whose dimensions are given
+    /// by `bounds`.
+    #[cfg(feature = "png")]
+    pub fn pixels(&self, bounds: (usize, usize)) -> crate::Result<Vec<u8>> {
+        match self {
+            Background::Color(color) => Ok(color.repeat(bounds.0 * bounds.1)),
+            Background::Image(file) => {
+                let (pixels, size) = crate::read_image(file)?;
+                let rgb = if pixels.len() == size.0 * size.1 {
+                    crate::compare::gray_to_rgb(&pixels)
+                } else {
+                    pixels
+                };
+                Ok(crate::resample::resample(
+                    &rgb,
+                    size,
+                    bounds,
+                    3,
+                    crate::resample::Filter::Mitchell,
+                ))
+            }
+        }
+    }
+}
+
+/// The image `image`, gray or RGB, whose escape times are `escapes`, one
+/// gray level a pixel, with the pixels of the points that escape taken from
+/// `backdrop`, in RGB.
+pub fn apply(escapes: &[u8], image: &[u8], backdrop: &[u8]) -> Vec<u8> {
+    let channels = image.len() / escapes.len().max(1);
+    let mut composited = Vec::with_capacity(3 * escapes.len());
+    for ((&escape, color), behind) in escapes
+        .iter()
+        .zip(image.chunks(channels.max(1)))
+        .zip(backdrop.chunks(3))
+    {
+        match *color {
+            // the set stays in front
+            [gray] if escape == 0 => composited.extend([gray; 3]),
+            [r, g, b] if escape == 0 => composited.extend([r, g, b]),
+            _ => composited.extend_from_slice(behind),
+        }
+    }
+    composited
+}
+
+#[test]
+fn test_apply() {
+    let escapes = [0, 10, 0];
+    let backdrop = [9, 8, 7].repeat(3);
+    assert_eq!(
+        apply(&escapes, &[1, 2, 3], &backdrop),
+        [1, 1, 1, 9, 8, 7, 3, 3, 3]
+    );
+    assert_eq!(
+        apply(&escapes, &[1, 2, 3, 4, 5, 6, 7, 8, 9], &backdrop),
+        [1, 2, 3, 9, 8, 7, 7, 8, 9]
+    );
+}
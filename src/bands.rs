@@ -0,0 +1,104 @@
+//! Contour bands: the "topographic map" style, where escape times are
+//! rounded down into bands of flat color with outlines drawn between them.
+
+/// The parameters of the contour band style.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Bands {
+    /// How many iterations each band spans.
+    pub width: usize,
+    /// The gray level the outlines are drawn in.
+    pub outline: u8,
+    /// How thick the outlines are, 1 or 2 pixels; 0 draws none.
+    pub outline_width: usize,
+}
+
+impl Default for Bands {
+    fn default() -> Bands {
+        Bands {
+            width: 8,
+            outline: 0,
+            outline_width: 1,
+        }
+    }
+}
+
+/// The band a pixel produced by `render` belongs to. The interior of the set
+/// is a band of its own.
+fn band(pixel: u8, width: usize) -> Option<usize> {
+    if pixel == 0 {
+        None
+    } else {
+        Some((255 - pixel as usize) / width.max(1))
+    }
+}
+
+/// Restyle `pixels`, a buffer produced by `render` whose dimensions are
+/// given by `bounds`, into contour bands.
+pub fn apply(pixels: &mut [u8], bounds: (usize, usize), bands: &Bands) {
+    assert!(pixels.len() == bounds.0 * bounds.1);
+    let width = bands.width.max(1);
+    let original: Vec<Option<usize>> = pixels.iter().map(|&p| band(p, width)).collect();
+
+    for (pixel, band) in pixels.iter_mut().zip(&original) {
+        if let Some(band) = band {
+            // the lightest shade the band's escape times have
+            *pixel = (255 - band * width) as u8;
+        }
+    }
+
+    if bands.outline_width == 0 {
+        return;
+    }
+    // a one pixel outline only looks right and down, so that only one side
+    // of each edge is drawn, and a two pixel one looks all around
+    let neighbours: &[(isize, isize)] = if bands.outline_width == 1 {
+        &[(1, 0), (0, 1)]
+    } else {
+        &[(1, 0), (0, 1), (-1, 0), (0, -1)]
+    };
+    for y in 0..bounds.1 {
+        for x in 0..bounds.0 {
+            let here = original[y * bounds.0 + x];
+            let edge = neighbours.iter().any(|&(dx, dy)| {
+                let (nx, ny) = (x as isize + dx, y as isize + dy);
+                nx >= 0
+                    && ny >= 0
+                    && (nx as usize) < bounds.0
+                    && (ny as usize) < bounds.1
+                    && original[ny as usize * bounds.0 + nx as usize] != here
+            });
+            if edge {
+                pixels[y * bounds.0 + x] = bands.outline;
+            }
+        }
+    }
+}
+
+#[test]
+fn test_apply() {
+    // escape times 0, 3, 5 and 9, and the interior
+    let mut pixels = [255, 252, 250, 246, 0];
+    let style = Bands {
+        width: 4,
+        outline: 128,
+        outline_width: 0,
+    };
+    apply(&mut pixels, (5, 1), &style);
+    assert_eq!(pixels, [255, 255, 251, 247, 0]);
+
+    let mut pixels = [255, 252, 250, 246, 0];
+    let style = Bands {
+        outline_width: 1,
+        ..style
+    };
+    apply(&mut pixels, (5, 1), &style);
+    assert_eq!(pixels, [255, 128, 128, 128, 0]);
+
+    let mut pixels = [255, 255, 255, 250, 250, 250];
+    let style = Bands {
+        outline_width: 2,
+        ..style
+    };
+    apply(&mut pixels, (6, 1), &style);
+    assert_eq!(pixels, [255, 255, 128, 128, 251, 251]);
+}
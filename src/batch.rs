@@ -0,0 +1,211 @@
+//! Rendering many images in one run, from a manifest listing their jobs:
+//!
+//! ```json
+//! {
+//!   "version": 1,
+//!   "jobs": [
+//!     {"output": "wallpapers/seahorse.png", "pixels": [3840, 2160], "center": "seahorse", "zoom": 40},
+//!     {"output": "wallpapers/spiral.png", "pixels": "2560x1440", "center": "@-0.7435,0.1314,z=2^12",
+//!      "palette": "fire"}
+//!   ]
+//! }
+//! ```
+//!
+//! Each job is written like a job of `--job`, see `job`. The jobs are all
+//! checked before any is rendered; once rendering, a job that fails doesn't
+//! stop the others.
+
+use std::{
+    collections::HashMap,
+    fs,
+    sync::atomic::{AtomicUsize, Ordering},
+    thread,
+};
+
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::{job::Job, platform, schema, Error, Result};
+
+/// The manifest as it is written.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct File {
+    jobs: Vec<Value>,
+}
+
+/// The jobs of a batch.
+#[derive(Debug, PartialEq)]
+pub struct Manifest {
+    pub jobs: Vec<Job>,
+}
+
+impl Manifest {
+    /// Parse a manifest from its JSON description, and check every job.
+    pub fn from_json(json: &str) -> Result<Manifest> {
+        let mut value: Value =
+            serde_json::from_str(json).map_err(|e| Error::parse("manifest", &e.to_string()))?;
+        let version = schema::take_version("manifest", &mut value)?;
+        if version > schema::VERSION {
+            let mut dropped = Vec::new();
+            schema::drop_unknown::<File>(&mut value, "", &mut dropped);
+            if let Some(Value::Array(jobs)) = value.get_mut("jobs") {
+                for (i, job) in jobs.iter_mut().enumerate() {
+                    let path = format!("jobs[{}].", i);
+                    schema::drop_unknown::<Job>(job, &path, &mut dropped);
+                }
+            }
+            schema::warn_newer("the manifest", version, schema::VERSION, &dropped);
+        }
+        let file: File =
+            serde_json::from_value(value).map_err(|e| Error::parse("manifest", &e.to_string()))?;
+        if file.jobs.is_empty() {
+            return Err(Error::parse("manifest", "no jobs"));
+        }
+
+        let mut jobs = Vec::new();
+        let mut problems = Vec::new();
+        let mut outputs: HashMap<String, usize> = HashMap::new();
+        for (i, job) in file.jobs.into_iter().enumerate() {
+            let job = match Job::from_value(job) {
+                Ok(job) => job,
+                Err(Error::Parse { value, .. }) => {
+                    problems.push(format!("job {}: {}", i + 1, value));
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
+            match job.output.as_deref() {
+                None => problems.push(format!("job {}: no output", i + 1)),
+                Some("-") => problems.push(format!("job {}: can't write to stdout", i + 1)),
+                Some(output) => {
+                    if let Some(other) = outputs.insert(output.to_string(), i + 1) {
+                        problems.push(format!(
+                            "jobs {} and {} both write {}",
+                            other,
+                            i + 1,
+                            output
+                        ));
+                    }
+                }
+            }
+            jobs.push(job);
+        }
+        if !problems.is_empty() {
+            return Err(Error::parse("manifest", &problems.join("; ")));
+        }
+        Ok(Manifest { jobs })
+    }
+
+    /// Open the manifest file named `filename`.
+    pub fn open(filename: &str) -> Result<Manifest> {
+        let json = fs::read_to_string(platform::path(filename)).map_err(Error::io(filename))?;
+        Manifest::from_json(&json).map_err(|e| match e {
+            Error::Parse { what, value } => Error::Parse {
+                what,
+                value: format!("{}: {}", filename, value),
+            },
+            e => e,
+        })
+    }
+}
+
+#[test]
+fn test_manifest() {
+    let manifest = Manifest::from_json(
+        r#"{"version": 1, "jobs": [
+            {"output": "a.png", "pixels": [40, 30], "center": [-0.5, 0]},
+            {"output": "b.png", "pixels": "40x30", "center": "@-0.75,0.1,z=20", "palette": "fire"}
+        ]}"#,
+    )
+    .unwrap();
+    assert_eq!(manifest.jobs.len(), 2);
+    assert_eq!(manifest.jobs[1].palette.as_deref(), Some("fire"));
+
+    let problems = |json| Manifest::from_json(json).unwrap_err().to_string();
+    assert!(problems(r#"{"jobs": []}"#).contains("no jobs"));
+    assert!(problems(r#"{"jobs": [{"pixels": [1, 1]}]}"#).contains("job 1: no output"));
+    assert!(problems(r#"{"jobs": [{"output": "-"}]}"#).contains("stdout"));
+    let twice = r#"{"jobs": [{"output": "a.png"}, {"output": "b.png"}, {"output": "a.png"}]}"#;
+    assert!(problems(twice).contains("jobs 1 and 3 both write a.png"));
+    // every problem of every job is reported at once
+    let all = problems(r#"{"jobs": [{"output": "a.png", "zoom": 0}, {"zoom": -1}, {}]}"#);
+    assert!(
+        all.contains("job 1: zoom 0") && all.contains("job 2: zoom -1"),
+        "{}",
+        all
+    );
+    assert!(all.contains("job 3: no output"), "{}", all);
+    assert!(problems(r#"{"jobs": [{"output": "a.png", "tilt": 1}]}"#).contains("unknown field"));
+
+    // a later version is read without what this one doesn't know
+    let newer = r#"{"version": 2, "defaults": {}, "jobs": [{"output": "a.png", "tilt": 1}]}"#;
+    assert_eq!(Manifest::from_json(newer).unwrap().jobs.len(), 1);
+}
+
+/// Call `render` with the index of each of `count` jobs, `parallel` at a
+/// time, in the order of the indices, going on whatever it returns. Returns
+/// what it returned for each job.
+pub fn run<F>(count: usize, parallel: usize, render: F) -> Vec<Result<()>>
+where
+    F: Fn(usize) -> Result<()> + Sync,
+{
+    let next = AtomicUsize::new(0);
+    let mut results: Vec<Option<Result<()>>> = (0..count).map(|_| None).collect();
+    let finished = thread::scope(|scope| {
+        let workers: Vec<_> = (0..parallel.clamp(1, count.max(1)))
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut finished = Vec::new();
+                    loop {
+                        let i = next.fetch_add(1, Ordering::Relaxed);
+                        if i >= count {
+                            return finished;
+                        }
+                        finished.push((i, render(i)));
+                    }
+                })
+            })
+            .collect();
+        workers
+            .into_iter()
+            .flat_map(|worker| worker.join().expect("batch worker panicked"))
+            .collect::<Vec<_>>()
+    });
+    for (i, result) in finished {
+        results[i] = Some(result);
+    }
+    results.into_iter().map(Option::unwrap).collect()
+}
+
+#[test]
+fn test_run() {
+    use std::sync::atomic::AtomicBool;
+
+    let running = AtomicUsize::new(0);
+    let overlapped = AtomicBool::new(false);
+    let results = run(7, 3, |i| {
+        if running.fetch_add(1, Ordering::SeqCst) > 0 {
+            overlapped.store(true, Ordering::SeqCst);
+        }
+        thread::sleep(std::time::Duration::from_millis(5));
+        running.fetch_sub(1, Ordering::SeqCst);
+        if i % 3 == 1 {
+            Err(Error::Viewport(format!("job {}", i)))
+        } else {
+            Ok(())
+        }
+    });
+    // the jobs after a failure still run, and the results keep their order
+    let failed: Vec<usize> = (0..7).filter(|&i| results[i].is_err()).collect();
+    assert_eq!(failed, [1, 4]);
+    assert!(results[4]
+        .as_ref()
+        .unwrap_err()
+        .to_string()
+        .contains("job 4"));
+    assert!(overlapped.load(Ordering::SeqCst));
+
+    assert!(run(0, 4, |_| Ok(())).is_empty());
+    assert_eq!(run(2, 0, |_| Ok(())).len(), 2);
+}
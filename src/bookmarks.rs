@@ -0,0 +1,294 @@
+//! The user's own named views, kept between runs in `bookmarks.json` in the
+//! configuration directory (see `platform::config_dir`), so that places
+//! worth coming back to don't have to be copied around:
+//!
+//! ```json
+//! {
+//!   "version": 1,
+//!   "bookmarks": {
+//!     "home": {"center": [-0.5, 0.0], "zoom": 1.0},
+//!     "valley": {"center": [-0.75, 0.1], "zoom": 40.0}
+//!   }
+//! }
+//! ```
+//!
+//! `bookmark add NAME` saves a view there and `--bookmark NAME` renders it
+//! again. Unlike the bookmarks files of `--bookmarks`, which name points to
+//! give views relative to, these keep whole views, their zoom included.
+
+use std::{collections::BTreeMap, fmt, fs};
+
+use num::Complex;
+use serde::{Deserialize, Serialize};
+
+use crate::{anchors, locator::Locator, output, platform, schema, units::Zoom, Error, Result};
+
+/// The name of the file the store is kept in, in the configuration
+/// directory.
+pub const FILE_NAME: &str = "bookmarks.json";
+
+/// A view saved in the store.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Bookmark {
+    /// The center of the view, as its real and imaginary parts.
+    pub center: (f64, f64),
+    /// How much the view is magnified, as for `--zoom`.
+    pub zoom: f64,
+}
+
+impl Bookmark {
+    /// The command line options that ask for the view.
+    pub fn to_args(&self) -> Vec<String> {
+        vec![
+            "--center".to_string(),
+            format!("{},{}", self.center.0, self.center.1),
+            "--zoom".to_string(),
+            self.zoom.to_string(),
+        ]
+    }
+}
+
+impl fmt::Display for Bookmark {
+    /// Write the view as a compact location.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let locator = Locator {
+            center: Complex::new(self.center.0, self.center.1),
+            zoom: Some(Zoom(self.zoom)),
+            rotation: 0.0,
+        };
+        write!(f, "{}", locator)
+    }
+}
+
+/// The store as it is written.
+#[derive(Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+struct File {
+    version: u64,
+    bookmarks: BTreeMap<String, Bookmark>,
+}
+
+/// The views of the store, by name.
+#[derive(Debug, PartialEq)]
+pub struct Store {
+    /// The file the store is read from and saved to.
+    file: String,
+    bookmarks: BTreeMap<String, Bookmark>,
+}
+
+/// Why `bookmark` can't be saved as `name`, if it can't.
+fn problem(name: &str, bookmark: &Bookmark) -> Option<String> {
+    if !anchors::is_name(name) {
+        Some(format!(
+            "{:?} is not a name: letters, digits and _, starting with a letter",
+            name
+        ))
+    } else if !bookmark.center.0.is_finite() || !bookmark.center.1.is_finite() {
+        Some(format!("the center of {} must be finite", name))
+    } else if !(bookmark.zoom > 0.0 && bookmark.zoom.is_finite()) {
+        Some(format!("the zoom of {} must be positive and finite", name))
+    } else {
+        None
+    }
+}
+
+impl Store {
+    /// The file the user's store is kept in.
+    pub fn default_file() -> Result<String> {
+        platform::config_file(FILE_NAME)
+    }
+
+    /// Read the store kept in the file named `file`, which is empty if the
+    /// file doesn't exist yet.
+    pub fn open(file: &str) -> Result<Store> {
+        let path = platform::path(file);
+        let bookmarks = if path.exists() {
+            let json = fs::read_to_string(&path).map_err(Error::io(file))?;
+            let invalid =
+                |e: &dyn fmt::Display| Error::parse("bookmarks", &format!("{}: {}", file, e));
+            let mut value: serde_json::Value =
+                serde_json::from_str(&json).map_err(|e| invalid(&e))?;
+            // stores written before they had versions are the bookmarks alone
+            if value.get("version").is_none() {
+                value = serde_json::json!({ "version": 1, "bookmarks": value });
+            }
+            let version = schema::version("bookmarks", &value)?;
+            if version > schema::VERSION {
+                let mut dropped = Vec::new();
+                schema::drop_unknown::<File>(&mut value, "", &mut dropped);
+                if let Some(serde_json::Value::Object(bookmarks)) = value.get_mut("bookmarks") {
+                    for (name, bookmark) in bookmarks {
+                        let path = format!("bookmarks.{}.", name);
+                        schema::drop_unknown::<Bookmark>(bookmark, &path, &mut dropped);
+                    }
+                }
+                schema::warn_newer("the bookmark store", version, schema::VERSION, &dropped);
+            }
+            let bookmarks = serde_json::from_value::<File>(value)
+                .map_err(|e| invalid(&e))?
+                .bookmarks;
+            if let Some(problem) = bookmarks
+                .iter()
+                .find_map(|(name, bookmark)| problem(name, bookmark))
+            {
+                return Err(Error::parse("bookmarks", &format!("{}: {}", file, problem)));
+            }
+            bookmarks
+        } else {
+            BTreeMap::new()
+        };
+        Ok(Store {
+            file: file.to_string(),
+            bookmarks,
+        })
+    }
+
+    /// The view called `name`, if there is one.
+    pub fn get(&self, name: &str) -> Option<Bookmark> {
+        self.bookmarks.get(name).copied()
+    }
+
+    /// The views, in the order of their names.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, Bookmark)> {
+        self.bookmarks
+            .iter()
+            .map(|(name, &bookmark)| (name.as_str(), bookmark))
+    }
+
+    /// Save `bookmark` as `name`, in place of any view of that name.
+    pub fn add(&mut self, name: &str, bookmark: Bookmark) -> Result<()> {
+        if let Some(problem) = problem(name, &bookmark) {
+            return Err(Error::parse("bookmark", &problem));
+        }
+        self.bookmarks.insert(name.to_string(), bookmark);
+        Ok(())
+    }
+
+    /// Forget the view called `name`. Returns whether there was one.
+    pub fn remove(&mut self, name: &str) -> bool {
+        self.bookmarks.remove(name).is_some()
+    }
+
+    /// Write the store back to its file, creating the directory it goes in
+    /// if need be.
+    pub fn save(&self) -> Result<()> {
+        output::create_parent_dirs(&self.file)?;
+        let file = File {
+            version: schema::VERSION,
+            bookmarks: self.bookmarks.clone(),
+        };
+        let json = serde_json::to_string_pretty(&file).unwrap() + "\n";
+        output::write(&self.file, json.as_bytes())
+    }
+}
+
+#[test]
+fn test_store() {
+    let dir = std::env::temp_dir().join(format!("mandelbrot-bookmarks-{}", std::process::id()));
+    let file = dir.join("config").join(FILE_NAME);
+    let file = file.to_str().unwrap();
+
+    let mut store = Store::open(file).unwrap();
+    assert_eq!(store.iter().count(), 0);
+    let valley = Bookmark {
+        center: (-0.75, 0.1),
+        zoom: 40.0,
+    };
+    store.add("valley", valley).unwrap();
+    store
+        .add(
+            "home",
+            Bookmark {
+                center: (-0.5, 0.0),
+                zoom: 1.0,
+            },
+        )
+        .unwrap();
+    assert!(store.add("2nd", valley).is_err());
+    assert!(store
+        .add(
+            "bad",
+            Bookmark {
+                zoom: 0.0,
+                ..valley
+            }
+        )
+        .is_err());
+    store.save().unwrap();
+
+    let mut store = Store::open(file).unwrap();
+    let names: Vec<&str> = store.iter().map(|(name, _)| name).collect();
+    assert_eq!(names, ["home", "valley"]);
+    assert_eq!(store.get("valley"), Some(valley));
+    assert!(store.remove("home"));
+    assert!(!store.remove("home"));
+    store.save().unwrap();
+    assert_eq!(Store::open(file).unwrap().iter().count(), 1);
+
+    assert!(fs::read_to_string(file).unwrap().contains("\"version\": 1"));
+
+    let bookmarks = |json| r#"{"version": 1, "bookmarks": "#.to_string() + json + "}";
+    fs::write(file, bookmarks(r#"{"x y": {"center": [0, 0], "zoom": 1}}"#)).unwrap();
+    assert!(Store::open(file).is_err());
+    fs::write(file, bookmarks(r#"{"home": {"center": [0, 0]}}"#)).unwrap();
+    assert!(Store::open(file).is_err());
+    fs::write(
+        file,
+        bookmarks(r#"{"home": {"center": [0, 0], "zoom": 1, "tag": "x"}}"#),
+    )
+    .unwrap();
+    assert!(Store::open(file).is_err());
+    fs::write(file, r#"{"home": {"center": [0, 0], "zoom": 1}}"#).unwrap();
+    assert_eq!(Store::open(file).unwrap().iter().count(), 1);
+    // unknown fields are an error, unless a later version wrote them
+    let newer = r#"{"version": 2, "bookmarks": {"home": {"center": [0, 0], "zoom": 1, "tag": "x"}},
+        "folders": {}}"#;
+    fs::write(file, newer).unwrap();
+    assert_eq!(Store::open(file).unwrap().iter().count(), 1);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_bookmark() {
+    let bookmark = Bookmark {
+        center: (-0.75, 0.1),
+        zoom: 40.0,
+    };
+    assert_eq!(
+        bookmark.to_args(),
+        ["--center", "-0.75,0.1", "--zoom", "40"]
+    );
+    assert_eq!(bookmark.to_string(), "@-0.75,0.1,z=4e1");
+    let locator: Locator = bookmark.to_string().parse().unwrap();
+    assert_eq!(locator.zoom, Some(Zoom(40.0)));
+}
+
+/// Replace every `--bookmark NAME` in `args` by the options asking for the
+/// view of that name in the user's store, so that options given after it
+/// override it.
+pub fn expand_bookmarks(args: &[String]) -> Result<Vec<String>> {
+    if !args.iter().any(|arg| arg == "--bookmark") {
+        return Ok(args.to_vec());
+    }
+    let store = Store::open(&Store::default_file()?)?;
+    let mut expanded = Vec::with_capacity(args.len());
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg == "--bookmark" {
+            let name = args
+                .next()
+                .ok_or_else(|| Error::Usage("--bookmark requires a name".to_string()))?;
+            let bookmark = store.get(name).ok_or_else(|| {
+                Error::Usage(format!(
+                    "no bookmark {}, see bookmark list for the ones there are",
+                    name
+                ))
+            })?;
+            expanded.extend(bookmark.to_args());
+        } else {
+            expanded.push(arg.clone());
+        }
+    }
+    Ok(expanded)
+}
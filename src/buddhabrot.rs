@@ -0,0 +1,542 @@
+//! The Buddhabrot: instead of coloring each point by how long it takes to
+//! escape, random points are iterated and the pixels their orbits pass
+//! through are counted. The counts of the escaping orbits make a ghostly
+//! figure, brightest where orbits crowd.
+//!
+//! This has nothing in common with escape-time rendering beyond the
+//! iteration itself: every pixel depends on orbits starting anywhere in the
+//! plane, so the image can't be cut into rows. Instead the points are drawn
+//! in batches, each thread counts the orbits of the batches it takes into a
+//! histogram of its own, and the histograms are added up at the end.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use num::Complex;
+
+use crate::{
+    render::Schedule,
+    sampling::{splitmix64, Rng},
+};
+
+/// The orbits traced for each pixel, unless told otherwise.
+pub const DEFAULT_SAMPLES: usize = 64;
+
+/// The parameters of a Buddhabrot render.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Buddhabrot {
+    /// How many points are drawn for each pixel of the image.
+    pub samples: usize,
+    /// Orbits escaping in fewer iterations than this are left out.
+    pub min_iters: usize,
+    /// Orbits that haven't escaped after this many iterations are taken to
+    /// be inside the set, and left out.
+    pub max_iters: usize,
+    /// Where the sequence of random points starts.
+    pub seed: u64,
+    /// Count the orbits that don't escape instead, for the anti-Buddhabrot.
+    pub anti: bool,
+}
+
+impl Default for Buddhabrot {
+    fn default() -> Buddhabrot {
+        Buddhabrot {
+            samples: DEFAULT_SAMPLES,
+            min_iters: 0,
+            max_iters: 1000,
+            seed: 0,
+            anti: false,
+        }
+    }
+}
+
+/// Iterate `c` at most `max_iters` times, leaving in `orbit` the points it
+/// went through before escaping. Returns whether it escaped.
+fn trace(c: Complex<f64>, max_iters: usize, orbit: &mut Vec<Complex<f64>>) -> bool {
+    orbit.clear();
+    let mut z = Complex { re: 0.0, im: 0.0 };
+    for _ in 0..max_iters {
+        z = z * z + c;
+        if z.norm_sqr() > 4.0 {
+            return true;
+        }
+        orbit.push(z);
+    }
+    false
+}
+
+/// Whether `c` is in the main cardioid or the period 2 bulb, where most of
+/// the points that never escape are: those can be skipped without iterating.
+fn in_main_bulbs(c: Complex<f64>) -> bool {
+    let q = (c.re - 0.25) * (c.re - 0.25) + c.im * c.im;
+    q * (q + (c.re - 0.25)) <= 0.25 * c.im * c.im || (c.re + 1.0).powi(2) + c.im * c.im <= 0.0625
+}
+
+#[test]
+fn test_trace() {
+    let mut orbit = Vec::new();
+    assert!(!trace(Complex { re: -1.0, im: 0.0 }, 100, &mut orbit));
+    assert_eq!(orbit.len(), 100);
+    // 1, 2, 5: escapes on the third iteration
+    assert!(trace(Complex { re: 1.0, im: 0.0 }, 100, &mut orbit));
+    assert_eq!(
+        orbit,
+        [Complex { re: 1.0, im: 0.0 }, Complex { re: 2.0, im: 0.0 }]
+    );
+
+    assert!(in_main_bulbs(Complex { re: 0.0, im: 0.0 }));
+    assert!(in_main_bulbs(Complex { re: -1.0, im: 0.1 }));
+    assert!(!in_main_bulbs(Complex { re: -0.75, im: 0.1 }));
+    assert!(!in_main_bulbs(Complex { re: 0.3, im: 0.0 }));
+}
+
+/// What `count_orbits` counts: which orbits go into which histogram.
+struct Plan<'a> {
+    bounds: (usize, usize),
+    upper_left: Complex<f64>,
+    lower_right: Complex<f64>,
+    /// The smallest and largest escape times of the orbits counted in each
+    /// histogram.
+    channels: &'a [(usize, usize)],
+    /// Count the orbits still captive after the largest escape time of a
+    /// channel instead, up to that many points.
+    anti: bool,
+    /// How many points a batch holds.
+    points: usize,
+    seed: u64,
+    /// Draw the points within the view instead of the whole disk of radius
+    /// 2, for `render_flame`.
+    local: bool,
+}
+
+impl Plan<'_> {
+    /// Count, for every pixel of the view and every channel, how many points
+    /// of the orbits in batch `batch` fall in it, adding to `histograms`,
+    /// which holds the histogram of each channel one after the other.
+    fn accumulate(&self, histograms: &mut [u32], batch: usize) {
+        let (bounds, upper_left) = (self.bounds, self.upper_left);
+        let scale = (
+            bounds.0 as f64 / (self.lower_right.re - upper_left.re),
+            bounds.1 as f64 / (upper_left.im - self.lower_right.im),
+        );
+        let max_iters = self.channels.iter().map(|&(_, max)| max).max().unwrap_or(0);
+        let mut rng = Rng::new(splitmix64(self.seed ^ splitmix64(batch as u64)));
+        let mut orbit = Vec::new();
+        for _ in 0..self.points {
+            // every orbit that escapes starts within 2 of the origin
+            let c = if self.local {
+                Complex {
+                    re: upper_left.re + rng.next_f64() * bounds.0 as f64 / scale.0,
+                    im: upper_left.im - rng.next_f64() * bounds.1 as f64 / scale.1,
+                }
+            } else {
+                Complex {
+                    re: rng.next_f64() * 4.0 - 2.0,
+                    im: rng.next_f64() * 4.0 - 2.0,
+                }
+            };
+            if c.norm_sqr() > 4.0 || (!self.anti && in_main_bulbs(c)) {
+                continue;
+            }
+            let escaped = trace(c, max_iters, &mut orbit);
+            let escape_time = orbit.len() + 1;
+
+            for (channel, &(min, max)) in self.channels.iter().enumerate() {
+                let points = if self.anti {
+                    if escaped && escape_time <= max {
+                        continue;
+                    }
+                    &orbit[..max]
+                } else {
+                    if !escaped || escape_time < min || escape_time > max {
+                        continue;
+                    }
+                    &orbit[..]
+                };
+                let histogram = &mut histograms[channel * bounds.0 * bounds.1..];
+                for z in points {
+                    let column = (z.re - upper_left.re) * scale.0;
+                    let row = (upper_left.im - z.im) * scale.1;
+                    if column >= 0.0 && row >= 0.0 {
+                        let (column, row) = (column as usize, row as usize);
+                        if column < bounds.0 && row < bounds.1 {
+                            histogram[row * bounds.0 + column] += 1;
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Trace the orbits `plan` describes, drawing the points in `bounds.1`
+/// batches that the threads of `schedule` take one at a time, and return
+/// the histogram of each channel, one after the other.
+///
+/// Every thread counts into histograms of its own, which are added up at the
+/// end. Every finished batch counts as a row for `schedule.progress`, and
+/// once `schedule` is stopped the threads stop taking batches.
+fn count_orbits(plan: &Plan, schedule: &Schedule) -> Vec<u32> {
+    let size = plan.channels.len() * plan.bounds.0 * plan.bounds.1;
+    let next_batch = AtomicUsize::new(0);
+    let histograms: Vec<Vec<u32>> = crossbeam::scope(|spawner| {
+        let handles: Vec<_> = (0..schedule.threads.max(1))
+            .map(|_| {
+                spawner.spawn(|_| {
+                    let mut histograms = vec![0; size];
+                    loop {
+                        if schedule.stopped() {
+                            break;
+                        }
+                        let batch = next_batch.fetch_add(1, Ordering::Relaxed);
+                        if batch >= plan.bounds.1 {
+                            break;
+                        }
+                        plan.accumulate(&mut histograms, batch);
+                        if let Some(progress) = schedule.progress {
+                            progress.add_rows(1);
+                        }
+                    }
+                    histograms
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .collect()
+    })
+    .unwrap();
+
+    let mut total = vec![0u32; size];
+    for histogram in histograms {
+        for (sum, count) in total.iter_mut().zip(histogram) {
+            *sum += count;
+        }
+    }
+    total
+}
+
+/// Map the counts of `histogram` to gray levels, the pixel with the most
+/// orbit points through it being white. The square root brings out the
+/// faint parts, which would otherwise be swamped by a few bright ones.
+pub fn tone_map(histogram: &[u32], pixels: &mut [u8]) {
+    let max = histogram.iter().copied().max().unwrap_or(0).max(1) as f64;
+    for (pixel, &count) in pixels.iter_mut().zip(histogram) {
+        *pixel = ((count as f64 / max).sqrt() * 255.0).round() as u8;
+    }
+}
+
+#[test]
+fn test_tone_map() {
+    let mut pixels = [1; 4];
+    tone_map(&[0, 100, 25, 400], &mut pixels);
+    assert_eq!(pixels, [0, 128, 64, 255]);
+    tone_map(&[0, 0], &mut pixels[..2]);
+    assert_eq!(pixels[..2], [0, 0]);
+}
+
+/// Map the counts of `histogram` to gray levels by the logarithm of the
+/// density, as fractal flames are: a pixel a hundred times as crowded as
+/// another is only twice as bright, so the faint filaments show next to the
+/// bright ones without washing out.
+pub fn log_tone_map(histogram: &[u32], pixels: &mut [u8]) {
+    let max = (histogram.iter().copied().max().unwrap_or(0) as f64).ln_1p();
+    for (pixel, &count) in pixels.iter_mut().zip(histogram) {
+        *pixel = match count {
+            0 => 0,
+            count => ((count as f64).ln_1p() / max * 255.0).round() as u8,
+        };
+    }
+}
+
+#[test]
+fn test_log_tone_map() {
+    let mut pixels = [1; 4];
+    log_tone_map(&[0, 1, 10, 120], &mut pixels);
+    assert_eq!(pixels, [0, 37, 128, 255]);
+    log_tone_map(&[0, 0], &mut pixels[..2]);
+    assert_eq!(pixels[..2], [0, 0]);
+}
+
+/// Render the Buddhabrot over the rectangle between `upper_left` and
+/// `lower_right` into `pixels`, whose dimensions are given by `bounds`.
+///
+/// The points are drawn in `bounds.1` batches of `bounds.0 *
+/// buddhabrot.samples` points, which the threads of `schedule` take one at
+/// a time; every finished batch counts as a row for `schedule.progress`.
+/// Each batch has its own seed, so the image doesn't depend on the number
+/// of threads. Once `schedule` is stopped, the threads stop taking
+/// batches and the image shows the orbits traced so far. Checkpoints are
+/// not supported, as the histograms are only added up at the end.
+pub fn render(
+    pixels: &mut [u8],
+    bounds: (usize, usize),
+    upper_left: Complex<f64>,
+    lower_right: Complex<f64>,
+    buddhabrot: &Buddhabrot,
+    schedule: &Schedule,
+) {
+    assert!(pixels.len() == bounds.0 * bounds.1);
+    if pixels.is_empty() {
+        return;
+    }
+
+    let plan = Plan {
+        bounds,
+        upper_left,
+        lower_right,
+        channels: &[(buddhabrot.min_iters, buddhabrot.max_iters)],
+        anti: buddhabrot.anti,
+        points: bounds.0 * buddhabrot.samples,
+        seed: buddhabrot.seed,
+        local: false,
+    };
+    tone_map(&count_orbits(&plan, schedule), pixels);
+}
+
+#[test]
+fn test_render() {
+    let bounds = (60, 40);
+    let (upper_left, lower_right) = (Complex { re: -2.0, im: 1.3 }, Complex { re: 1.0, im: -1.3 });
+    let buddhabrot = Buddhabrot {
+        samples: 4,
+        max_iters: 200,
+        ..Buddhabrot::default()
+    };
+
+    let mut pixels = vec![0; bounds.0 * bounds.1];
+    render(
+        &mut pixels,
+        bounds,
+        upper_left,
+        lower_right,
+        &buddhabrot,
+        &Schedule::new(1),
+    );
+    assert!(pixels.contains(&255));
+    // the figure is (almost) symmetric around the real axis
+    let (top, bottom) = (pixels[8 * bounds.0 + 20], pixels[31 * bounds.0 + 20]);
+    assert!(
+        (top as i32 - bottom as i32).abs() < 64,
+        "{} {}",
+        top,
+        bottom
+    );
+
+    // the same image, whatever the number of threads
+    let mut threaded = vec![0; bounds.0 * bounds.1];
+    render(
+        &mut threaded,
+        bounds,
+        upper_left,
+        lower_right,
+        &buddhabrot,
+        &Schedule::new(3),
+    );
+    assert!(threaded == pixels);
+}
+
+/// Render the exterior of the view between `upper_left` and `lower_right`
+/// into `pixels` the way fractal flames are rendered: a Buddhabrot of the
+/// escaping orbits starting within the view alone, tone mapped by
+/// `log_tone_map`. The orbits of the points near the set are the longest,
+/// so its filaments glow, lined up with the escape time render of the same
+/// view; a higher `buddhabrot.min_iters` leaves out the haze of the quick
+/// orbits further away.
+///
+/// `buddhabrot.samples` points are drawn in each pixel of the view, and
+/// `buddhabrot.anti` is ignored; otherwise this is like `render`.
+pub fn render_flame(
+    pixels: &mut [u8],
+    bounds: (usize, usize),
+    upper_left: Complex<f64>,
+    lower_right: Complex<f64>,
+    buddhabrot: &Buddhabrot,
+    schedule: &Schedule,
+) {
+    assert!(pixels.len() == bounds.0 * bounds.1);
+    if pixels.is_empty() {
+        return;
+    }
+
+    let plan = Plan {
+        bounds,
+        upper_left,
+        lower_right,
+        channels: &[(buddhabrot.min_iters, buddhabrot.max_iters)],
+        anti: false,
+        points: bounds.0 * buddhabrot.samples,
+        seed: buddhabrot.seed,
+        local: true,
+    };
+    log_tone_map(&count_orbits(&plan, schedule), pixels);
+}
+
+#[test]
+fn test_render_flame() {
+    // the seahorse valley, well away from the origin, where the points of
+    // a Buddhabrot of the whole plane are sparse
+    let bounds = (48, 36);
+    let (upper_left, lower_right) = (
+        Complex { re: -0.8, im: 0.2 },
+        Complex {
+            re: -0.7,
+            im: 0.125,
+        },
+    );
+    let buddhabrot = Buddhabrot {
+        samples: 4,
+        max_iters: 300,
+        ..Buddhabrot::default()
+    };
+    let flame = |threads| {
+        let mut pixels = vec![0; bounds.0 * bounds.1];
+        render_flame(
+            &mut pixels,
+            bounds,
+            upper_left,
+            lower_right,
+            &buddhabrot,
+            &Schedule::new(threads),
+        );
+        pixels
+    };
+    let pixels = flame(2);
+    assert!(pixels.contains(&255));
+    // almost every pixel outside the set is lit by its own points at least
+    let lit = pixels.iter().filter(|&&pixel| pixel > 0).count();
+    assert!(lit > bounds.0 * bounds.1 / 2, "{}", lit);
+    assert!(flame(1) == pixels);
+
+    let mut sparse = vec![0; bounds.0 * bounds.1];
+    render(
+        &mut sparse,
+        bounds,
+        upper_left,
+        lower_right,
+        &buddhabrot,
+        &Schedule::new(2),
+    );
+    assert!(sparse.iter().filter(|&&pixel| pixel > 0).count() < lit);
+}
+
+/// The escape times counted in the red, green and blue channels of a
+/// Nebulabrot, unless told otherwise: the slow orbits in red, the fast ones
+/// in blue.
+pub const DEFAULT_CHANNELS: [(usize, usize); 3] = [(0, 5000), (0, 500), (0, 50)];
+
+/// Parse the range of escape times of a Nebulabrot channel, either
+/// `MIN-MAX` or just `MAX`.
+pub fn parse_channel(s: &str) -> Option<(usize, usize)> {
+    let (min, max) = match s.split_once('-') {
+        Some((min, max)) => (min.parse().ok()?, max.parse().ok()?),
+        None => (0, s.parse().ok()?),
+    };
+    if max == 0 || min > max {
+        return None;
+    }
+    Some((min, max))
+}
+
+#[test]
+fn test_parse_channel() {
+    assert_eq!(parse_channel("500"), Some((0, 500)));
+    assert_eq!(parse_channel("20-500"), Some((20, 500)));
+    assert_eq!(parse_channel("500-20"), None);
+    assert_eq!(parse_channel("0"), None);
+    assert_eq!(parse_channel("-5"), None);
+}
+
+/// Render the Nebulabrot over the rectangle between `upper_left` and
+/// `lower_right` into `pixels`, three bytes (red, green and blue) per pixel:
+/// a Buddhabrot for each range of escape times in `channels`, each tone
+/// mapped on its own.
+///
+/// The minimum and maximum iterations of `buddhabrot` are ignored in favor
+/// of those of `channels`; the other arguments are the same as for `render`.
+pub fn render_nebulabrot(
+    pixels: &mut [u8],
+    bounds: (usize, usize),
+    upper_left: Complex<f64>,
+    lower_right: Complex<f64>,
+    buddhabrot: &Buddhabrot,
+    channels: &[(usize, usize); 3],
+    schedule: &Schedule,
+) {
+    assert!(pixels.len() == 3 * bounds.0 * bounds.1);
+    if pixels.is_empty() {
+        return;
+    }
+
+    let plan = Plan {
+        bounds,
+        upper_left,
+        lower_right,
+        channels,
+        anti: buddhabrot.anti,
+        points: bounds.0 * buddhabrot.samples,
+        seed: buddhabrot.seed,
+        local: false,
+    };
+    let histograms = count_orbits(&plan, schedule);
+    let mut channel = vec![0; bounds.0 * bounds.1];
+    for (i, histogram) in histograms.chunks(channel.len()).enumerate() {
+        tone_map(histogram, &mut channel);
+        for (pixel, &value) in pixels.chunks_mut(3).zip(&channel) {
+            pixel[i] = value;
+        }
+    }
+}
+
+#[test]
+fn test_render_variants() {
+    let bounds = (40, 30);
+    let (upper_left, lower_right) = (Complex { re: -2.0, im: 1.3 }, Complex { re: 1.0, im: -1.3 });
+    let buddhabrot = Buddhabrot {
+        samples: 4,
+        max_iters: 100,
+        ..Buddhabrot::default()
+    };
+    let render_gray = |buddhabrot: &Buddhabrot| {
+        let mut pixels = vec![0; bounds.0 * bounds.1];
+        render(
+            &mut pixels,
+            bounds,
+            upper_left,
+            lower_right,
+            buddhabrot,
+            &Schedule::new(2),
+        );
+        pixels
+    };
+
+    // every channel is the Buddhabrot of its range, the same points being
+    // drawn for all of them
+    let channels = [(0, 100), (0, 30), (10, 30)];
+    let mut rgb = vec![0; 3 * bounds.0 * bounds.1];
+    render_nebulabrot(
+        &mut rgb,
+        bounds,
+        upper_left,
+        lower_right,
+        &buddhabrot,
+        &channels,
+        &Schedule::new(2),
+    );
+    for (i, (min_iters, max_iters)) in channels.into_iter().enumerate() {
+        let gray = render_gray(&Buddhabrot {
+            min_iters,
+            max_iters,
+            ..buddhabrot
+        });
+        let channel: Vec<u8> = rgb.iter().skip(i).step_by(3).copied().collect();
+        assert!(channel == gray, "channel {}", i);
+    }
+
+    let anti = render_gray(&Buddhabrot {
+        anti: true,
+        ..buddhabrot
+    });
+    assert!(anti.contains(&255));
+    assert!(anti != render_gray(&buddhabrot));
+}
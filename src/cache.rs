@@ -0,0 +1,367 @@
+//! A cache of rendered tiles on disk, shared by the runs of `serve` and
+//! `explore` and by the processes running at the same time, so that a view
+//! rendered once isn't rendered again.
+//!
+//! Each tile is a file named after a hash of what it depends on, its `Key`:
+//! the view, its size, the fractal, the iterations, the strategy, the
+//! palette and the format it is encoded in. The file starts with the key written out in
+//! full, which is checked on reading, so that two keys with the same hash
+//! can't get each other's tiles. Reading a tile marks it as used; once the
+//! tiles take more than the space they are given, the least recently used
+//! are deleted.
+
+use std::{
+    fs::{self, File},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::SystemTime,
+};
+
+use num::Complex;
+
+use crate::{platform, render::Strategy, Error, Result};
+
+/// How much space the tiles take at most unless told otherwise, in bytes.
+pub const DEFAULT_MAX_BYTES: u64 = 256 << 20;
+
+/// The extension of the files of the tiles.
+const EXTENSION: &str = "tile";
+
+/// What a rendered tile depends on.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Key<'a> {
+    pub bounds: (usize, usize),
+    pub upper_left: Complex<f64>,
+    pub lower_right: Complex<f64>,
+    /// As `--fractal` takes it.
+    pub fractal: &'a str,
+    pub limit: usize,
+    /// As `--strategy` takes it, as adaptive ones interpolate pixels.
+    pub strategy: Strategy,
+    /// A built-in palette as `--palette` takes it, or the `palette_key` of
+    /// the colors of a file, which can change under the same name.
+    pub palette: &'a str,
+    /// How the pixels are encoded: `png`, say, or `gray` for one byte a
+    /// pixel.
+    pub format: &'a str,
+}
+
+impl Key<'_> {
+    /// The key written out in full, with the coordinates to the bit.
+    fn description(&self) -> String {
+        format!(
+            "mandelbrot tile 2 {}x{} {:016x},{:016x} {:016x},{:016x} {} {} {} {} {}",
+            self.bounds.0,
+            self.bounds.1,
+            self.upper_left.re.to_bits(),
+            self.upper_left.im.to_bits(),
+            self.lower_right.re.to_bits(),
+            self.lower_right.im.to_bits(),
+            self.fractal,
+            self.limit,
+            self.strategy,
+            self.palette,
+            self.format
+        )
+    }
+
+    /// The hash of the key, which names its file, see `hash`.
+    fn hash(&self) -> String {
+        hash(self.description().as_bytes())
+    }
+}
+
+/// The hash of `bytes`: 128 bits in hex, from two FNV-1a hashes starting
+/// apart, the same on every platform and in every version.
+fn hash(bytes: &[u8]) -> String {
+    let fnv = |basis: u64| {
+        bytes.iter().fold(basis, |hash, &byte| {
+            (hash ^ byte as u64).wrapping_mul(0x0000_0100_0000_01b3)
+        })
+    };
+    format!(
+        "{:016x}{:016x}",
+        fnv(0xcbf2_9ce4_8422_2325),
+        fnv(0x6c62_272e_07bb_0142)
+    )
+}
+
+/// What stands for the palette whose colors are `lut` in a `Key`: a hash
+/// of the colors, so that tiles colored before a gradient file changed
+/// aren't taken for its new colors.
+pub fn palette_key(lut: &[[u8; 3]]) -> String {
+    format!("lut-{}", hash(lut.as_flattened()))
+}
+
+#[test]
+fn test_key() {
+    let key = Key {
+        bounds: (256, 256),
+        upper_left: Complex::new(-2.5, 2.0),
+        lower_right: Complex::new(1.5, -2.0),
+        fractal: "mandelbrot",
+        limit: 255,
+        strategy: Strategy::Naive,
+        palette: "gray",
+        format: "png",
+    };
+    assert_eq!(key.hash().len(), 32);
+    assert_eq!(key.hash(), key.hash());
+    assert_ne!(key.hash(), Key { limit: 256, ..key }.hash());
+    assert_ne!(
+        key.hash(),
+        Key {
+            strategy: Strategy::Adaptive { max_error: 40 },
+            ..key
+        }
+        .hash()
+    );
+    assert_ne!(
+        key.hash(),
+        Key {
+            format: "gif",
+            ..key
+        }
+        .hash()
+    );
+    assert_ne!(
+        key.hash(),
+        Key {
+            upper_left: Complex::new(-2.5 + f64::EPSILON * 2.0, 2.0),
+            ..key
+        }
+        .hash()
+    );
+    // palettes from files are told apart by their colors
+    let lut = [[0, 0, 0], [255, 128, 0]];
+    assert_eq!(palette_key(&lut), palette_key(&lut));
+    assert_ne!(palette_key(&lut), palette_key(&[[0, 0, 0], [255, 128, 1]]));
+}
+
+/// How many tiles the cache holds, and how much space they take.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Usage {
+    pub tiles: u64,
+    pub bytes: u64,
+}
+
+/// The tiles in a directory.
+pub struct DiskCache {
+    dir: PathBuf,
+    max_bytes: u64,
+    /// The space the tiles take, as far as this process knows: counted once,
+    /// then kept up to date as tiles are added.
+    used: Mutex<Option<u64>>,
+}
+
+impl DiskCache {
+    /// The tiles in `dir`, taking at most `max_bytes`.
+    pub fn new(dir: impl Into<PathBuf>, max_bytes: u64) -> DiskCache {
+        DiskCache {
+            dir: dir.into(),
+            max_bytes,
+            used: Mutex::new(None),
+        }
+    }
+
+    /// The directory the user's tiles are kept in, see `platform::cache_dir`.
+    pub fn default_dir() -> Result<PathBuf> {
+        platform::cache_dir()
+            .map(|dir| dir.join("tiles"))
+            .ok_or_else(|| {
+                Error::Usage(
+                    "can't tell where the cache directory is, set MANDELBROT_CACHE_DIR".to_string(),
+                )
+            })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    fn file(&self, key: &Key) -> PathBuf {
+        self.dir.join(key.hash()).with_extension(EXTENSION)
+    }
+
+    /// The tile for `key`, if it is cached, marking it as used. A tile that
+    /// can't be read is taken not to be cached.
+    pub fn get(&self, key: &Key) -> Option<Vec<u8>> {
+        let file = self.file(key);
+        let contents = fs::read(&file).ok()?;
+        let description = key.description();
+        let tile = contents
+            .strip_prefix(description.as_bytes())?
+            .strip_prefix(b"\n")?;
+        if let Ok(file) = File::options().write(true).open(&file) {
+            let _ = file.set_modified(SystemTime::now());
+        }
+        Some(tile.to_vec())
+    }
+
+    /// Cache `tile` as the tile for `key`, deleting the least recently used
+    /// tiles if they take too much space.
+    pub fn put(&self, key: &Key, tile: &[u8]) -> Result<()> {
+        if self.max_bytes == 0 {
+            return Ok(());
+        }
+        // written aside and renamed, so that other processes never read
+        // half a tile
+        static WRITES: AtomicU64 = AtomicU64::new(0);
+        let file = self.file(key);
+        let partial = file.with_extension(format!(
+            "{}.{}",
+            std::process::id(),
+            WRITES.fetch_add(1, Ordering::Relaxed)
+        ));
+        let name = partial.to_string_lossy().into_owned();
+        fs::create_dir_all(&self.dir).map_err(Error::io(&self.dir.to_string_lossy()))?;
+        let write = || -> io::Result<()> {
+            let mut out = File::create(&partial)?;
+            writeln!(out, "{}", key.description())?;
+            out.write_all(tile)?;
+            fs::rename(&partial, &file)
+        };
+        if let Err(e) = write() {
+            let _ = fs::remove_file(&partial);
+            return Err(Error::io(&name)(e));
+        }
+
+        let mut used = self.used.lock().unwrap();
+        let added = fs::metadata(&file).map_or(0, |metadata| metadata.len());
+        let total = match *used {
+            Some(used) => used + added,
+            None => self.usage()?.bytes,
+        };
+        *used = Some(if total > self.max_bytes {
+            self.evict()?
+        } else {
+            total
+        });
+        Ok(())
+    }
+
+    /// The tile files, with their sizes and when they were last used.
+    fn entries(&self) -> Result<Vec<(PathBuf, u64, SystemTime)>> {
+        let dir = self.dir.to_string_lossy().into_owned();
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(Error::io(&dir)(e)),
+        };
+        let mut tiles = Vec::new();
+        for entry in entries {
+            let path = entry.map_err(Error::io(&dir))?.path();
+            if path
+                .extension()
+                .is_some_and(|extension| extension == EXTENSION)
+            {
+                // deleted by another process since
+                let Ok(metadata) = fs::metadata(&path) else {
+                    continue;
+                };
+                let used = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+                tiles.push((path, metadata.len(), used));
+            }
+        }
+        Ok(tiles)
+    }
+
+    /// Delete the least recently used tiles until those left take at most
+    /// three quarters of the space, so that the next tiles fit without
+    /// deleting more right away. Returns the space they take.
+    fn evict(&self) -> Result<u64> {
+        let mut tiles = self.entries()?;
+        tiles.sort_by_key(|&(_, _, used)| used);
+        let mut total: u64 = tiles.iter().map(|&(_, bytes, _)| bytes).sum();
+        for (path, bytes, _) in tiles {
+            if total <= self.max_bytes / 4 * 3 {
+                break;
+            }
+            match fs::remove_file(&path) {
+                Ok(()) => total -= bytes,
+                Err(e) if e.kind() == io::ErrorKind::NotFound => total -= bytes,
+                Err(e) => return Err(Error::io(&path.to_string_lossy())(e)),
+            }
+        }
+        Ok(total)
+    }
+
+    /// How many tiles are cached, and how much space they take.
+    pub fn usage(&self) -> Result<Usage> {
+        let tiles = self.entries()?;
+        Ok(Usage {
+            tiles: tiles.len() as u64,
+            bytes: tiles.iter().map(|&(_, bytes, _)| bytes).sum(),
+        })
+    }
+
+    /// Delete every tile. Returns how many there were.
+    pub fn clear(&self) -> Result<u64> {
+        let tiles = self.entries()?;
+        for (path, _, _) in &tiles {
+            match fs::remove_file(path) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => {
+                    return Err(Error::io(&path.to_string_lossy())(e))
+                }
+                _ => {}
+            }
+        }
+        *self.used.lock().unwrap() = Some(0);
+        Ok(tiles.len() as u64)
+    }
+}
+
+#[test]
+fn test_disk_cache() {
+    let dir = std::env::temp_dir().join(format!("mandelbrot-cache-{}", std::process::id()));
+    let key = |limit| Key {
+        bounds: (4, 4),
+        upper_left: Complex::new(-2.0, 1.0),
+        lower_right: Complex::new(1.0, -1.0),
+        fractal: "mandelbrot",
+        limit,
+        strategy: Strategy::Naive,
+        palette: "gray",
+        format: "gray",
+    };
+    let cache = DiskCache::new(&dir, 1000);
+    assert_eq!(cache.usage().unwrap(), Usage::default());
+    assert_eq!(cache.get(&key(1)), None);
+    cache.put(&key(1), &[1; 16]).unwrap();
+    assert_eq!(cache.get(&key(1)), Some(vec![1; 16]));
+    assert_eq!(cache.get(&key(2)), None);
+    assert_eq!(cache.usage().unwrap().tiles, 1);
+
+    // another key with the same hash doesn't get the tile
+    fs::copy(cache.file(&key(1)), cache.file(&key(3))).unwrap();
+    assert_eq!(cache.get(&key(3)), None);
+    fs::remove_file(cache.file(&key(3))).unwrap();
+
+    // each tile takes about a hundred bytes, so the oldest go past ten
+    for limit in 10..20 {
+        cache.put(&key(limit), &[0; 16]).unwrap();
+        // file times aren't always finer than this
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        assert!(cache.get(&key(1)).is_some());
+    }
+    let usage = cache.usage().unwrap();
+    assert!(usage.bytes <= 1000, "{:?}", usage);
+    assert!(usage.tiles < 11, "{:?}", usage);
+    // the tile used all along is kept, the oldest of the others aren't
+    assert!(cache.get(&key(1)).is_some());
+    assert!(cache.get(&key(10)).is_none());
+    assert!(cache.get(&key(19)).is_some());
+
+    assert_eq!(cache.clear().unwrap(), usage.tiles);
+    assert_eq!(cache.usage().unwrap(), Usage::default());
+    fs::remove_dir_all(&dir).unwrap();
+
+    // nothing is kept without room for it
+    let none = DiskCache::new(&dir, 0);
+    none.put(&key(1), &[1]).unwrap();
+    assert_eq!(none.get(&key(1)), None);
+}
@@ -0,0 +1,139 @@
+//! Reading and writing complex coordinates through the system clipboard.
+//!
+//! There's no portable clipboard API, so this shells out to the tools each
+//! platform ships with (or that are commonly installed, on Linux), trying them
+//! in turn until one works.
+
+use std::{
+    io::{self, Write},
+    process::{Command, Stdio},
+};
+
+use num::Complex;
+
+use crate::{parse_complex, DecimalSeparator};
+
+/// Commands that print the clipboard contents, in the order they are tried.
+const PASTE_COMMANDS: &[&[&str]] = &[
+    &["pbpaste"],
+    &["wl-paste", "--no-newline"],
+    &["xclip", "-selection", "clipboard", "-out"],
+    &["xsel", "--clipboard", "--output"],
+    &["powershell", "-NoProfile", "-Command", "Get-Clipboard"],
+];
+
+/// Commands that replace the clipboard contents with their input.
+const COPY_COMMANDS: &[&[&str]] = &[
+    &["pbcopy"],
+    &["wl-copy"],
+    &["xclip", "-selection", "clipboard", "-in"],
+    &["xsel", "--clipboard", "--input"],
+    &["clip"],
+];
+
+/// Return the text currently on the clipboard.
+pub fn read() -> io::Result<String> {
+    for command in PASTE_COMMANDS {
+        let output = match Command::new(command[0])
+            .args(&command[1..])
+            .stdin(Stdio::null())
+            .stderr(Stdio::null())
+            .output()
+        {
+            Ok(output) => output,
+            Err(_) => continue,
+        };
+        if output.status.success() {
+            return String::from_utf8(output.stdout)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e));
+        }
+    }
+
+    Err(no_clipboard())
+}
+
+/// Replace the contents of the clipboard with `text`.
+pub fn write(text: &str) -> io::Result<()> {
+    for command in COPY_COMMANDS {
+        let mut child = match Command::new(command[0])
+            .args(&command[1..])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+        {
+            Ok(child) => child,
+            Err(_) => continue,
+        };
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(text.as_bytes())?;
+        }
+        if child.wait()?.success() {
+            return Ok(());
+        }
+    }
+
+    Err(no_clipboard())
+}
+
+fn no_clipboard() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::NotFound,
+        "no clipboard tool found (tried pbpaste, wl-paste, xclip, xsel and powershell)",
+    )
+}
+
+/// Parse a complex number copied from somewhere else, which is usually less
+/// tidy than what is typed on the command line.
+///
+/// Surrounding whitespace and brackets are ignored, and besides the forms
+/// `parse_complex` accepts, `a+bi` and `a-bi` are understood too.
+pub fn parse_point(text: &str, decimal: DecimalSeparator) -> Option<Complex<f64>> {
+    let text = text
+        .trim()
+        .trim_start_matches(['(', '['])
+        .trim_end_matches([')', ']'])
+        .trim();
+
+    let compact: String = text.chars().filter(|c| !c.is_whitespace()).collect();
+    let point = parse_complex(text, decimal).or_else(|| parse_complex(&compact, decimal));
+    if point.is_some() {
+        return point;
+    }
+
+    let imaginary = compact.strip_suffix('i')?;
+    // The sign that starts the imaginary part is the last one that isn't at the
+    // very start or part of an exponent.
+    let is_sign = |&(i, c): &(usize, char)| {
+        (c == '+' || c == '-') && i > 0 && !imaginary[..i].ends_with(['e', 'E'])
+    };
+    let (index, _) = imaginary.char_indices().rfind(is_sign)?;
+    let number = |s: &str| match decimal {
+        DecimalSeparator::Comma => s.replace(',', ".").parse().ok(),
+        _ => s.parse().ok(),
+    };
+    Some(Complex {
+        re: number(&imaginary[..index])?,
+        im: number(&imaginary[index..])?,
+    })
+}
+
+#[test]
+fn test_parse_point() {
+    let point = Some(Complex { re: -0.75, im: 0.1 });
+    assert_eq!(parse_point("-0.75,0.1\n", DecimalSeparator::Auto), point);
+    assert_eq!(parse_point(" (-0.75, 0.1) ", DecimalSeparator::Auto), point);
+    assert_eq!(parse_point("[-0,75;0,1]", DecimalSeparator::Auto), point);
+    assert_eq!(parse_point("-0.75+0.1i", DecimalSeparator::Auto), point);
+    assert_eq!(parse_point("-0.75 + 0.1i", DecimalSeparator::Auto), point);
+    assert_eq!(parse_point("-0,75+0,1i", DecimalSeparator::Comma), point);
+    assert_eq!(
+        parse_point("-7.5e-1-1e-1i", DecimalSeparator::Auto),
+        Some(Complex {
+            re: -0.75,
+            im: -0.1
+        })
+    );
+    assert_eq!(parse_point("hello", DecimalSeparator::Auto), None);
+    assert_eq!(parse_point("-0.75i", DecimalSeparator::Auto), None);
+}
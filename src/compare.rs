@@ -0,0 +1,132 @@
+//! Comparing two renders pixel by pixel, for the `compare` subcommand: to
+//! make sure a faster way of rendering draws the same image, or nearly.
+
+use crate::palette;
+
+/// How much two images differ. The difference of two pixels is the largest
+/// difference of their channels.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Difference {
+    /// The largest difference of two pixels.
+    pub max: u8,
+    /// The average difference, over all the pixels.
+    pub mean: f64,
+    /// How many pixels differ at all.
+    pub differing: usize,
+    /// How many pixels were compared.
+    pub pixels: usize,
+}
+
+impl Difference {
+    /// Whether no two pixels differ by more than `tolerance`.
+    pub fn within(&self, tolerance: u8) -> bool {
+        self.max <= tolerance
+    }
+
+    /// The difference, one `name value` a line.
+    pub fn describe(&self) -> String {
+        let share = 100.0 * self.differing as f64 / self.pixels.max(1) as f64;
+        format!(
+            "max {}\nmean {:.4}\ndiffering {} of {} ({:.2}%)\n",
+            self.max, self.mean, self.differing, self.pixels, share
+        )
+    }
+}
+
+/// The differences of the pixels of `a` and `b`, which have `channels`
+/// bytes a pixel.
+fn pixel_differences<'a>(
+    a: &'a [u8],
+    b: &'a [u8],
+    channels: usize,
+) -> impl Iterator<Item = u8> + 'a {
+    a.chunks(channels).zip(b.chunks(channels)).map(|(a, b)| {
+        a.iter()
+            .zip(b)
+            .map(|(a, b)| a.abs_diff(*b))
+            .max()
+            .unwrap_or(0)
+    })
+}
+
+/// Compare the images `a` and `b`, the same size, with `channels` bytes a
+/// pixel.
+pub fn compare(a: &[u8], b: &[u8], channels: usize) -> Difference {
+    assert_eq!(a.len(), b.len());
+    let (mut max, mut total, mut differing) = (0, 0u64, 0);
+    for difference in pixel_differences(a, b, channels) {
+        max = max.max(difference);
+        total += difference as u64;
+        differing += (difference > 0) as usize;
+    }
+    let pixels = a.len() / channels.max(1);
+    Difference {
+        max,
+        mean: total as f64 / pixels.max(1) as f64,
+        differing,
+        pixels,
+    }
+}
+
+#[test]
+fn test_compare() {
+    let a = [0, 10, 20, 30];
+    assert_eq!(
+        compare(&a, &a, 1),
+        Difference {
+            max: 0,
+            mean: 0.0,
+            differing: 0,
+            pixels: 4
+        }
+    );
+    let difference = compare(&a, &[0, 12, 20, 22], 1);
+    assert_eq!(difference.max, 8);
+    assert_eq!(difference.mean, 2.5);
+    assert_eq!(difference.differing, 2);
+    assert!(difference.within(8) && !difference.within(7));
+    assert_eq!(
+        difference.describe(),
+        "max 8\nmean 2.5000\ndiffering 2 of 4 (50.00%)\n"
+    );
+
+    // the largest difference of the channels of a pixel counts
+    let rgb = compare(&[0, 0, 0, 5, 5, 5], &[1, 3, 0, 5, 5, 5], 3);
+    assert_eq!((rgb.max, rgb.differing, rgb.pixels), (3, 1, 2));
+    assert_eq!(rgb.mean, 1.5);
+}
+
+/// A heatmap of where `a` and `b`, with `channels` bytes a pixel, differ, as
+/// RGB pixels: black where they are the same, then the colors of the `fire`
+/// palette, up to white for the largest difference, however small it is.
+pub fn heatmap(a: &[u8], b: &[u8], channels: usize) -> Vec<u8> {
+    let differences: Vec<u8> = pixel_differences(a, b, channels).collect();
+    let max = differences.iter().copied().max().unwrap_or(0).max(1) as usize;
+    let levels: Vec<u8> = differences
+        .iter()
+        .map(|&difference| (difference as usize * 255 / max) as u8)
+        .collect();
+    let fire = palette::preset("fire").expect("fire is built in");
+    palette::apply(&levels, &fire.lut(256))
+}
+
+#[test]
+fn test_heatmap() {
+    let map = heatmap(&[0, 10, 20], &[0, 11, 22], 1);
+    assert_eq!(map.len(), 9);
+    assert_eq!(map[..3], [0, 0, 0]);
+    // the largest difference is the brightest, even if it's only 2
+    assert_eq!(map[6..], [255, 255, 255]);
+    assert!(map[3] > 0 && map[5] < 255, "{:?}", &map[3..6]);
+}
+
+/// The pixels of the grayscale image `gray` in RGB, to compare it to an
+/// image in color.
+pub fn gray_to_rgb(gray: &[u8]) -> Vec<u8> {
+    gray.iter().flat_map(|&level| [level; 3]).collect()
+}
+
+#[test]
+fn test_gray_to_rgb() {
+    assert_eq!(gray_to_rgb(&[1, 200]), [1, 1, 1, 200, 200, 200]);
+}
@@ -0,0 +1,246 @@
+//! Shell completion scripts, for the `completions` subcommand, written from
+//! the usage message so that every option it lists completes, new ones
+//! included, with no list of them to keep up besides.
+//!
+//! The usage message gives the subcommands as the first word after the
+//! program name on its `Usage:` lines, and the options on lines of their
+//! own starting `  --NAME [VALUE]`, followed by a description two spaces or
+//! more further on. An option is taken to need a value when one is shown.
+
+use std::str::FromStr;
+
+/// A shell completion scripts can be written for.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+impl FromStr for Shell {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "bash" => Ok(Shell::Bash),
+            "zsh" => Ok(Shell::Zsh),
+            "fish" => Ok(Shell::Fish),
+            _ => Err(format!("unknown shell {} (expected bash, zsh or fish)", s)),
+        }
+    }
+}
+
+/// An option of the usage message.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Flag {
+    /// The option, dashes included.
+    pub name: String,
+    /// Whether it is followed by a value.
+    pub takes_value: bool,
+    /// Its description, its lines joined.
+    pub description: String,
+}
+
+/// The subcommands of the usage message `usage` of `program`, in the order
+/// they come.
+pub fn subcommands(usage: &str, program: &str) -> Vec<String> {
+    let mut subcommands: Vec<String> = Vec::new();
+    for line in usage.lines() {
+        let line = line.strip_prefix("Usage:").unwrap_or(line).trim_start();
+        let Some(rest) = line
+            .strip_prefix(program)
+            .and_then(|rest| rest.strip_prefix(' '))
+        else {
+            continue;
+        };
+        let word = rest.split_whitespace().next().unwrap_or("");
+        let is_name = word.starts_with(|c: char| c.is_ascii_lowercase())
+            && word.chars().all(|c| c.is_ascii_lowercase() || c == '-');
+        if is_name && !subcommands.iter().any(|known| known == word) {
+            subcommands.push(word.to_string());
+        }
+    }
+    subcommands
+}
+
+/// The options of the usage message `usage`, in the order they come.
+pub fn flags(usage: &str) -> Vec<Flag> {
+    let mut flags: Vec<Flag> = Vec::new();
+    // the option whose description goes on on the lines below
+    let mut described = None;
+    for line in usage.lines() {
+        if let Some(line) = line.strip_prefix("  --") {
+            let (syntax, description) = line.split_once("  ").unwrap_or((line, ""));
+            let mut words = syntax.split_whitespace();
+            let name = format!("--{}", words.next().unwrap_or(""));
+            described = None;
+            if !flags.iter().any(|flag| flag.name == name) {
+                described = Some(flags.len());
+                flags.push(Flag {
+                    name,
+                    takes_value: words.next().is_some(),
+                    description: description.trim().to_string(),
+                });
+            }
+        } else if let (Some(index), true) = (described, line.starts_with("   ")) {
+            let description = &mut flags[index].description;
+            description.push(' ');
+            description.push_str(line.trim());
+        } else {
+            described = None;
+        }
+    }
+    flags
+}
+
+#[test]
+fn test_usage() {
+    let usage = "Usage: mandelbrot FILE PIXELS [OPTIONS]\n       \
+                 mandelbrot --reproduce MANIFEST.json [FILE]\n       \
+                 mandelbrot export-dzi FILE.dzi PIXELS\n               \
+                 [--palette NAME]\n       \
+                 mandelbrot palette preview NAME\n       \
+                 mandelbrot palette --list-palettes\n\n\
+                 Options:\n  \
+                 --threads N           threads a render uses (default one per CPU)\n  \
+                 --reproduce MANIFEST.json  render the image of a manifest again\n  \
+                 --ascii               draw the explore preview in ASCII\n                        \
+                 instead of braille\n\n                        not the description\n";
+    assert_eq!(subcommands(usage, "mandelbrot"), ["export-dzi", "palette"]);
+    assert_eq!(
+        flags(usage),
+        [
+            Flag {
+                name: "--threads".to_string(),
+                takes_value: true,
+                description: "threads a render uses (default one per CPU)".to_string(),
+            },
+            Flag {
+                name: "--reproduce".to_string(),
+                takes_value: true,
+                description: "render the image of a manifest again".to_string(),
+            },
+            Flag {
+                name: "--ascii".to_string(),
+                takes_value: false,
+                description: "draw the explore preview in ASCII instead of braille".to_string(),
+            },
+        ]
+    );
+}
+
+/// `text` in single quotes for the shells, which take nothing in them
+/// specially but the quote itself.
+fn quoted(text: &str) -> String {
+    format!("'{}'", text.replace('\'', "'\\''"))
+}
+
+/// The completion script for `shell` of `program`, from its usage message
+/// `usage`: subcommands first, options after a dash, and file names
+/// otherwise.
+pub fn script(shell: Shell, program: &str, usage: &str) -> String {
+    let subcommands = subcommands(usage, program);
+    let flags = flags(usage);
+    let function = format!(
+        "_{}",
+        program.replace(|c: char| !c.is_ascii_alphanumeric(), "_")
+    );
+    match shell {
+        Shell::Bash => {
+            let names: Vec<&str> = flags.iter().map(|flag| flag.name.as_str()).collect();
+            format!(
+                "# bash completion for {program}, from `{program} completions bash`\n\
+                 {function}() {{\n    \
+                     local cur=${{COMP_WORDS[COMP_CWORD]}}\n    \
+                     if [[ $cur == -* ]]; then\n        \
+                         COMPREPLY=($(compgen -W {options} -- \"$cur\"))\n    \
+                     elif [[ $COMP_CWORD -eq 1 ]]; then\n        \
+                         COMPREPLY=($(compgen -W {subcommands} -- \"$cur\") $(compgen -f -- \"$cur\"))\n    \
+                     else\n        \
+                         COMPREPLY=($(compgen -f -- \"$cur\"))\n    \
+                     fi\n\
+                 }}\n\
+                 complete -o filenames -F {function} {program}\n",
+                options = quoted(&names.join(" ")),
+                subcommands = quoted(&subcommands.join(" ")),
+            )
+        }
+        Shell::Zsh => {
+            let mut script = format!(
+                "#compdef {program}\n\
+                 # zsh completion for {program}, from `{program} completions zsh`\n\
+                 _arguments \\\n  \
+                 {} \\\n",
+                quoted(&format!(
+                    "1: :_alternative \"subcommands:subcommand:({})\" \"files:file:_files\"",
+                    subcommands.join(" ")
+                ))
+            );
+            for flag in &flags {
+                // brackets and colons end the parts of a specification, and
+                // actions are evaluated
+                let description: String = flag
+                    .description
+                    .chars()
+                    .flat_map(|c| match c {
+                        '[' | ']' | ':' | '\\' | '$' | '`' => vec!['\\', c],
+                        c => vec![c],
+                    })
+                    .collect();
+                let value = if flag.takes_value { ": :_files" } else { "" };
+                script.push_str(&format!(
+                    "  {} \\\n",
+                    quoted(&format!("*{}[{}]{}", flag.name, description, value))
+                ));
+            }
+            script.push_str("  '*:file:_files'\n");
+            script
+        }
+        Shell::Fish => {
+            let mut script =
+                format!("# fish completion for {program}, from `{program} completions fish`\n");
+            for subcommand in &subcommands {
+                script.push_str(&format!(
+                    "complete -c {} -n __fish_use_subcommand -a {}\n",
+                    program,
+                    quoted(subcommand)
+                ));
+            }
+            for flag in &flags {
+                script.push_str(&format!(
+                    "complete -c {} -l {}{} -d {}\n",
+                    program,
+                    &flag.name[2..],
+                    if flag.takes_value { " -r" } else { "" },
+                    quoted(&flag.description)
+                ));
+            }
+            script
+        }
+    }
+}
+
+#[test]
+fn test_script() {
+    let usage = "Usage: mandelbrot FILE [OPTIONS]\n       mandelbrot serve [--listen ADDRESS]\n\
+                 Options:\n  --listen ADDRESS      where serve listens [host:port]\n  \
+                 --ascii               don't draw braille\n";
+    let bash = script(Shell::Bash, "mandelbrot", usage);
+    assert!(bash.contains("compgen -W '--listen --ascii' -- \"$cur\""));
+    assert!(bash.contains("compgen -W 'serve' -- \"$cur\""));
+    assert!(bash.ends_with("complete -o filenames -F _mandelbrot mandelbrot\n"));
+
+    let zsh = script(Shell::Zsh, "mandelbrot", usage);
+    assert!(zsh.starts_with("#compdef mandelbrot\n"));
+    assert!(zsh.contains("'*--listen[where serve listens \\[host\\:port\\]]: :_files' \\\n"));
+    assert!(zsh.contains("'*--ascii[don'\\''t draw braille]' \\\n"));
+
+    let fish = script(Shell::Fish, "mandelbrot", usage);
+    assert!(fish.contains("complete -c mandelbrot -n __fish_use_subcommand -a 'serve'\n"));
+    assert!(
+        fish.contains("complete -c mandelbrot -l listen -r -d 'where serve listens [host:port]'\n")
+    );
+
+    assert_eq!("fish".parse(), Ok(Shell::Fish));
+    assert!("tcsh".parse::<Shell>().is_err());
+}
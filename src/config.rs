@@ -0,0 +1,343 @@
+//! The user's configuration file, `config.toml` in the configuration
+//! directory (see `platform::config_dir`): defaults for the options of
+//! every command, and in a table named after a subcommand, for that
+//! subcommand alone.
+//!
+//! ```toml
+//! threads = 4
+//! palette = "fire"
+//!
+//! [render]
+//! max-iters = "auto"
+//! progressive = true
+//!
+//! [serve]
+//! workers = 2
+//! ```
+//!
+//! As in the sections of a project, the keys are options without their
+//! dashes, with their values as they would be given, `true` for an option
+//! that takes none, and an array for one given several times, so that
+//! `Config::to_args` turns them back into options. Environment variables
+//! set options too, see `env_options`. The settings are layered from the
+//! bottom up: the defaults built into the program, the environment, the
+//! options outside tables, those of the table of the subcommand, then
+//! projects and the command line, each overriding the ones before.
+
+use std::{collections::BTreeMap, fmt, fs};
+
+use serde_json::{Map, Value};
+
+use crate::{platform, schema, toml, Error, Result};
+
+/// The name of the configuration file in the configuration directory.
+pub const FILE_NAME: &str = "config.toml";
+
+/// The tables the file may have: the commands taking options alone, the
+/// render itself being `render`.
+pub const SECTIONS: [&str; 9] = [
+    "render",
+    "explore",
+    "serve",
+    "animate",
+    "coordinator",
+    "location",
+    "export-mesh",
+    "export-dzi",
+    "find",
+];
+
+/// The main settings the program uses when no option sets them, for
+/// `config show --effective`.
+pub const DEFAULTS: [(&str, &str); 9] = [
+    ("fractal", "mandelbrot"),
+    ("max-iters", "255"),
+    ("bailout", "2"),
+    ("strategy", "naive"),
+    ("numeric", "f64"),
+    ("chunk-rows", "8"),
+    ("traversal", "rows"),
+    ("on-exists", "overwrite"),
+    ("disk-cache-size", "256"),
+];
+
+/// The options of a configuration file.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Config {
+    /// The options for every command.
+    pub options: Map<String, Value>,
+    /// The options of each table, by the name of its subcommand.
+    pub sections: BTreeMap<String, Map<String, Value>>,
+}
+
+impl Config {
+    /// Read the configuration file `file`, which sets nothing if it doesn't
+    /// exist.
+    pub fn open(file: &str) -> Result<Config> {
+        let path = platform::path(file);
+        if !path.exists() {
+            return Ok(Config::default());
+        }
+        let text = fs::read_to_string(&path).map_err(Error::io(file))?;
+        Config::from_toml(&text)
+            .map_err(|e| Error::parse("configuration", &format!("{}: {}", file, e)))
+    }
+
+    /// Parse the configuration file `text`.
+    pub fn from_toml(text: &str) -> std::result::Result<Config, String> {
+        let Value::Object(root) = toml::to_json(text)? else {
+            unreachable!("TOML documents are tables")
+        };
+        let mut config = Config::default();
+        for (key, value) in root {
+            match value {
+                Value::Object(table) => {
+                    if !SECTIONS.contains(&key.as_str()) {
+                        return Err(format!(
+                            "unknown table [{}], expected one of {}",
+                            key,
+                            SECTIONS.join(", ")
+                        ));
+                    }
+                    for (option, value) in &table {
+                        check(option, value)?;
+                    }
+                    config.sections.insert(key, table);
+                }
+                value => {
+                    check(&key, &value)?;
+                    config.options.insert(key, value);
+                }
+            }
+        }
+        Ok(config)
+    }
+
+    /// The options for every command.
+    pub fn global_args(&self) -> Vec<String> {
+        to_args(&self.options)
+    }
+
+    /// The options of the table of the subcommand `section`.
+    pub fn section_args(&self, section: &str) -> Vec<String> {
+        self.sections.get(section).map(to_args).unwrap_or_default()
+    }
+
+    /// The options for the subcommand `section`, the ones of its table
+    /// last, so that they override the others.
+    pub fn to_args(&self, section: &str) -> Vec<String> {
+        let mut args = self.global_args();
+        args.extend(self.section_args(section));
+        args
+    }
+}
+
+/// Check that `value` can be given to the option `option`.
+fn check(option: &str, value: &Value) -> std::result::Result<(), String> {
+    let single = |value: &Value| matches!(value, Value::String(_) | Value::Number(_));
+    let valid = match value {
+        Value::Bool(_) => true,
+        Value::Array(values) => values.iter().all(single),
+        value => single(value),
+    };
+    if option.starts_with('-') || option.is_empty() {
+        Err(format!(
+            "{:?} is not an option name, leave the dashes out",
+            option
+        ))
+    } else if !valid {
+        Err(format!(
+            "{} must be a string, a number, a boolean or an array of strings and numbers",
+            option
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+/// The command line options `options` stand for.
+fn to_args(options: &Map<String, Value>) -> Vec<String> {
+    let mut args = Vec::new();
+    for (option, value) in options {
+        let flag = format!("--{}", option);
+        let values = match value {
+            Value::Bool(true) => {
+                args.push(flag);
+                continue;
+            }
+            Value::Array(values) => values.as_slice(),
+            Value::Bool(false) => continue,
+            value => std::slice::from_ref(value),
+        };
+        for value in values {
+            args.push(flag.clone());
+            args.push(match value {
+                Value::String(text) => text.clone(),
+                value => value.to_string(),
+            });
+        }
+    }
+    args
+}
+
+impl fmt::Display for Config {
+    /// Write the configuration back as TOML.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let write_table = |f: &mut fmt::Formatter, options: &Map<String, Value>| {
+            options
+                .iter()
+                .try_for_each(|(option, value)| writeln!(f, "{} = {}", option, value))
+        };
+        write_table(f, &self.options)?;
+        for (name, options) in &self.sections {
+            writeln!(f, "\n[{}]", name)?;
+            write_table(f, options)?;
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn test_config() {
+    let text = r#"
+        threads = 4
+        palette = "fire"  # warm
+        decimal-comma = true
+        ascii = false
+
+        [render]
+        max-iters = "auto"
+        mask = ["a.png", "b.png"]
+
+        [serve]
+        workers = 2
+    "#;
+    let config = Config::from_toml(text).unwrap();
+    assert_eq!(
+        config.to_args("render"),
+        [
+            "--decimal-comma",
+            "--palette",
+            "fire",
+            "--threads",
+            "4",
+            "--mask",
+            "a.png",
+            "--mask",
+            "b.png",
+            "--max-iters",
+            "auto"
+        ]
+    );
+    assert_eq!(config.section_args("serve"), ["--workers", "2"]);
+    assert!(config.section_args("explore").is_empty());
+    assert_eq!(config.global_args().len(), 5);
+
+    // written back, it reads the same
+    assert_eq!(Config::from_toml(&config.to_string()).unwrap(), config);
+
+    assert!(Config::from_toml("[orbit]\nmax-iters = 10")
+        .unwrap_err()
+        .contains("unknown table [orbit]"));
+    assert!(Config::from_toml("--zoom = 2")
+        .unwrap_err()
+        .contains("dashes"));
+    assert!(Config::from_toml("[render]\nview = [[1, 2]]").is_err());
+    assert_eq!(Config::from_toml("").unwrap(), Config::default());
+}
+
+#[test]
+fn test_open_config() {
+    let dir = std::env::temp_dir().join(format!("mandelbrot-config-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let file = dir.join(FILE_NAME).to_string_lossy().into_owned();
+    assert_eq!(Config::open(&file).unwrap(), Config::default());
+    fs::write(&file, "zoom = 3\n[nowhere]\n").unwrap();
+    let error = Config::open(&file).unwrap_err().to_string();
+    assert!(
+        error.contains(&file) && error.contains("nowhere"),
+        "{}",
+        error
+    );
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+/// The prefix of the environment variables that set options, the option
+/// after it in capitals, with underscores for its dashes:
+/// `MANDELBROT_MAX_ITERS=auto` is `--max-iters auto`.
+pub const ENV_PREFIX: &str = "MANDELBROT_";
+
+/// The variables starting with `ENV_PREFIX` that say where the
+/// configuration and the cache are instead, see `platform`.
+const DIRECTORY_VARS: [&str; 2] = ["MANDELBROT_CONFIG_DIR", "MANDELBROT_CACHE_DIR"];
+
+/// The options the environment variables `vars` set, as the name of each
+/// variable with the arguments it stands for, sorted by name. As in the
+/// file, `true` gives an option that takes no value and `false` leaves it
+/// out; empty variables are left out too. Other programs may use the prefix
+/// as well, so variables naming no option, for which `known` is false given
+/// the flag, are left out with a warning.
+pub fn env_options(
+    vars: impl IntoIterator<Item = (String, String)>,
+    known: impl Fn(&str) -> bool,
+) -> Vec<(String, Vec<String>)> {
+    let mut options: Vec<(String, Vec<String>)> = vars
+        .into_iter()
+        .filter(|(name, value)| {
+            name.len() > ENV_PREFIX.len()
+                && name.starts_with(ENV_PREFIX)
+                && !DIRECTORY_VARS.contains(&name.as_str())
+                && !value.is_empty()
+        })
+        .filter_map(|(name, value)| {
+            let option = name[ENV_PREFIX.len()..]
+                .to_ascii_lowercase()
+                .replace('_', "-");
+            if !known(&format!("--{}", option)) {
+                schema::warn(&format!("ignoring {}, which names no option", name));
+                return None;
+            }
+            let value = match value.as_str() {
+                "true" => Value::Bool(true),
+                "false" => Value::Bool(false),
+                _ => Value::String(value),
+            };
+            let args = to_args(&Map::from_iter([(option, value)]));
+            Some((name, args))
+        })
+        .collect();
+    options.sort();
+    options
+}
+
+#[test]
+fn test_env_options() {
+    let vars = [
+        ("MANDELBROT_THREADS", "4"),
+        ("MANDELBROT_MAX_ITERS", "auto"),
+        ("MANDELBROT_DECIMAL_COMMA", "true"),
+        ("MANDELBROT_ASCII", "false"),
+        ("MANDELBROT_PALETTE", ""),
+        ("MANDELBROT_CONFIG_DIR", "/etc/mandelbrot"),
+        ("MANDELBROT_", "x"),
+        ("MANDELBROT_FOO", "1"),
+        ("HOME", "/root"),
+    ]
+    .map(|(name, value)| (name.to_string(), value.to_string()));
+    let args = |args: &[&str]| args.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+    assert_eq!(
+        env_options(vars, |flag| flag != "--foo"),
+        [
+            ("MANDELBROT_ASCII".to_string(), args(&[])),
+            (
+                "MANDELBROT_DECIMAL_COMMA".to_string(),
+                args(&["--decimal-comma"])
+            ),
+            (
+                "MANDELBROT_MAX_ITERS".to_string(),
+                args(&["--max-iters", "auto"])
+            ),
+            ("MANDELBROT_THREADS".to_string(), args(&["--threads", "4"])),
+        ]
+    );
+}
@@ -0,0 +1,302 @@
+//! Contour lines for `--contours`: the lines along which the escape time
+//! crosses given levels, traced by marching squares over the pixels and
+//! written as SVG paths, for pen plotters and print.
+
+use std::{collections::HashMap, fmt::Write as _, str::FromStr};
+
+/// Which escape times lines are drawn at.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Levels {
+    /// Every so many iterations, like the edges of `--bands`.
+    Every(usize),
+    /// These escape times.
+    At(Vec<usize>),
+}
+
+impl FromStr for Levels {
+    type Err = String;
+
+    /// Parse `N` as every N iterations, and `A,B,C` as those escape times; a
+    /// single one is written `A,`.
+    fn from_str(s: &str) -> std::result::Result<Levels, String> {
+        let invalid = || format!("invalid contour levels {}", s);
+        if !s.contains(',') {
+            return match s.parse() {
+                Ok(every) if every > 0 => Ok(Levels::Every(every)),
+                _ => Err(invalid()),
+            };
+        }
+        let levels = s
+            .split(',')
+            .filter(|level| !level.trim().is_empty())
+            .map(|level| match level.trim().parse() {
+                Ok(level) if level > 0 => Ok(level),
+                _ => Err(invalid()),
+            })
+            .collect::<std::result::Result<Vec<usize>, String>>()?;
+        if levels.is_empty() {
+            return Err(invalid());
+        }
+        Ok(Levels::At(levels))
+    }
+}
+
+#[test]
+fn test_parse_levels() {
+    assert_eq!("8".parse(), Ok(Levels::Every(8)));
+    assert_eq!("5,10, 20".parse(), Ok(Levels::At(vec![5, 10, 20])));
+    assert_eq!("20,".parse(), Ok(Levels::At(vec![20])));
+    assert!("0".parse::<Levels>().is_err());
+    assert!(",".parse::<Levels>().is_err());
+    assert!("5,x".parse::<Levels>().is_err());
+}
+
+impl Levels {
+    /// The escape times lines are drawn at, in increasing order, up to the
+    /// highest one the pixels can have.
+    pub fn escape_times(&self) -> Vec<usize> {
+        let mut levels = match self {
+            Levels::Every(every) => (1..).map(|i| i * every).take_while(|&l| l <= 255).collect(),
+            Levels::At(levels) => levels.clone(),
+        };
+        levels.sort_unstable();
+        levels.dedup();
+        levels
+    }
+}
+
+/// The parameters of the contour output.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Contours {
+    pub levels: Levels,
+    /// The color of the lines.
+    pub stroke: [u8; 3],
+    /// How wide the lines are, in pixels.
+    pub width: f64,
+}
+
+impl Default for Contours {
+    fn default() -> Contours {
+        Contours {
+            levels: Levels::Every(8),
+            stroke: [0, 0, 0],
+            width: 1.0,
+        }
+    }
+}
+
+/// The escape time of a pixel produced by `render`, the set being above
+/// every other.
+fn escape_time(pixel: u8) -> f64 {
+    if pixel == 0 {
+        256.0
+    } else {
+        (255 - pixel) as f64
+    }
+}
+
+/// An edge between the centers of two neighbouring pixels: from `(x, y)`
+/// to the right if `across`, down otherwise.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+struct Edge {
+    x: usize,
+    y: usize,
+    across: bool,
+}
+
+/// The lines along which the escape times of `pixels`, a buffer produced by
+/// `render` whose dimensions are given by `bounds`, cross from below
+/// `level` to `level` or more, each as the points it goes through, in
+/// pixel coordinates. A line that closes ends on its first point.
+pub fn trace(pixels: &[u8], bounds: (usize, usize), level: usize) -> Vec<Vec<(f64, f64)>> {
+    assert!(pixels.len() == bounds.0 * bounds.1);
+    let threshold = level as f64 - 0.5;
+    let value = |x: usize, y: usize| escape_time(pixels[y * bounds.0 + x]);
+    let edge = |x, y, across| Edge { x, y, across };
+
+    // the segments crossing each square between four pixel centers
+    let mut segments = Vec::new();
+    for y in 0..bounds.1.saturating_sub(1) {
+        for x in 0..bounds.0.saturating_sub(1) {
+            let corners = [
+                value(x, y),
+                value(x + 1, y),
+                value(x + 1, y + 1),
+                value(x, y + 1),
+            ];
+            let case = corners
+                .iter()
+                .fold(0, |case, &v| case << 1 | (v > threshold) as usize);
+            let (top, right) = (edge(x, y, true), edge(x + 1, y, false));
+            let (bottom, left) = (edge(x, y + 1, true), edge(x, y, false));
+            let center = corners.iter().sum::<f64>() / 4.0 > threshold;
+            match case {
+                0b0001 | 0b1110 => segments.push((left, bottom)),
+                0b0010 | 0b1101 => segments.push((bottom, right)),
+                0b0011 | 0b1100 => segments.push((left, right)),
+                0b0100 | 0b1011 => segments.push((top, right)),
+                0b0110 | 0b1001 => segments.push((top, bottom)),
+                0b0111 | 0b1000 => segments.push((left, top)),
+                // saddles, where the center decides which corners connect
+                0b0101 | 0b1010 if (case == 0b0101) == center => {
+                    segments.extend([(left, top), (bottom, right)])
+                }
+                0b0101 | 0b1010 => segments.extend([(top, right), (left, bottom)]),
+                _ => {}
+            }
+        }
+    }
+
+    // chain the segments meeting at the same edges into lines
+    let mut at: HashMap<Edge, Vec<usize>> = HashMap::new();
+    for (i, &(from, to)) in segments.iter().enumerate() {
+        at.entry(from).or_default().push(i);
+        at.entry(to).or_default().push(i);
+    }
+    let mut used = vec![false; segments.len()];
+    let follow = |line: &mut Vec<Edge>, used: &mut [bool]| {
+        while let Some(&next) = at[line.last().unwrap()].iter().find(|&&i| !used[i]) {
+            used[next] = true;
+            let (from, to) = segments[next];
+            line.push(if from == *line.last().unwrap() {
+                to
+            } else {
+                from
+            });
+        }
+    };
+    let mut lines = Vec::new();
+    for i in 0..segments.len() {
+        if used[i] {
+            continue;
+        }
+        used[i] = true;
+        let mut line = vec![segments[i].0, segments[i].1];
+        follow(&mut line, &mut used);
+        line.reverse();
+        follow(&mut line, &mut used);
+        lines.push(line);
+    }
+
+    let point = |edge: Edge| {
+        let (x, y) = (edge.x, edge.y);
+        let (a, b) = if edge.across {
+            (value(x, y), value(x + 1, y))
+        } else {
+            (value(x, y), value(x, y + 1))
+        };
+        let t = (threshold - a) / (b - a);
+        if edge.across {
+            (x as f64 + t + 0.5, y as f64 + 0.5)
+        } else {
+            (x as f64 + 0.5, y as f64 + t + 0.5)
+        }
+    };
+    lines
+        .into_iter()
+        .map(|line| line.into_iter().map(point).collect())
+        .collect()
+}
+
+#[test]
+fn test_trace() {
+    // escape time 10 in a square of 2x2 pixels, 0 around it
+    let mut pixels = [255; 6 * 6];
+    for (x, y) in [(2, 2), (3, 2), (2, 3), (3, 3)] {
+        pixels[y * 6 + x] = 245;
+    }
+    let lines = trace(&pixels, (6, 6), 10);
+    assert_eq!(lines.len(), 1);
+    let line = &lines[0];
+    // a closed loop around the square, between the centers of the pixels
+    // inside and out
+    assert_eq!(line.first(), line.last());
+    assert_eq!(line.len(), 8 + 1);
+    for &(x, y) in line {
+        assert!(
+            (1.5..=4.5).contains(&x) && (1.5..=4.5).contains(&y),
+            "{:?}",
+            line
+        );
+    }
+    assert!(trace(&pixels, (6, 6), 11).is_empty());
+    assert!(trace(&pixels, (6, 6), 0).is_empty());
+
+    // a line across the image, from edge to edge, is left open, halfway
+    // between escape times 5 and 14
+    let pixels: Vec<u8> = (0..8 * 4)
+        .map(|i| if i % 8 < 4 { 250 } else { 241 })
+        .collect();
+    let lines = trace(&pixels, (8, 4), 10);
+    assert_eq!(lines.len(), 1);
+    assert_eq!(lines[0].len(), 4);
+    assert!(lines[0].iter().all(|&(x, _)| x == 4.0));
+    assert_eq!(
+        lines[0].first().unwrap().1 + lines[0].last().unwrap().1,
+        4.0
+    );
+
+    // the set lies above every escape time
+    let mut pixels = [250; 5 * 5];
+    pixels[12] = 0;
+    assert_eq!(trace(&pixels, (5, 5), 200).len(), 1);
+    assert!(trace(&[0; 4], (2, 2), 1).is_empty());
+    assert!(trace(&[], (0, 0), 1).is_empty());
+}
+
+/// The contour lines of `pixels`, a buffer produced by `render` whose
+/// dimensions are given by `bounds`, as an SVG document: a `<path>` for each
+/// level, in increasing order, stroked as `contours` says.
+pub fn to_svg(pixels: &[u8], bounds: (usize, usize), contours: &Contours) -> String {
+    let (width, height) = bounds;
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" viewBox=\"0 0 {} {}\">\n\
+         <rect width=\"100%\" height=\"100%\" fill=\"white\"/>\n",
+        width, height, width, height
+    );
+    let _ = writeln!(
+        svg,
+        "<g fill=\"none\" stroke=\"#{:02x}{:02x}{:02x}\" stroke-width=\"{}\" \
+         stroke-linecap=\"round\" stroke-linejoin=\"round\">",
+        contours.stroke[0], contours.stroke[1], contours.stroke[2], contours.width
+    );
+    for level in contours.levels.escape_times() {
+        let lines = trace(pixels, bounds, level);
+        if lines.is_empty() {
+            continue;
+        }
+        let mut path = String::new();
+        for line in lines {
+            for (i, (x, y)) in line.iter().enumerate() {
+                let command = if i == 0 { 'M' } else { 'L' };
+                let _ = write!(path, "{}{:.2} {:.2}", command, x, y);
+            }
+        }
+        let _ = writeln!(svg, "<path data-level=\"{}\" d=\"{}\"/>", level, path);
+    }
+    svg.push_str("</g>\n</svg>\n");
+    svg
+}
+
+#[test]
+fn test_to_svg() {
+    let mut pixels = [255; 6 * 6];
+    for (x, y) in [(2, 2), (3, 2), (2, 3), (3, 3)] {
+        pixels[y * 6 + x] = 245;
+    }
+    let contours = Contours {
+        levels: Levels::At(vec![20, 10, 5]),
+        stroke: [255, 0, 128],
+        width: 0.5,
+    };
+    let svg = to_svg(&pixels, (6, 6), &contours);
+    assert!(svg.starts_with("<svg "));
+    assert!(svg.contains("stroke=\"#ff0080\" stroke-width=\"0.5\""));
+    // levels no line crosses are left out
+    assert!(svg.contains("<path data-level=\"5\" d=\"M"));
+    assert!(svg.contains("<path data-level=\"10\" d=\"M"));
+    assert!(svg.find("data-level=\"5\"") < svg.find("data-level=\"10\""));
+    assert!(!svg.contains("data-level=\"20\""));
+    assert_eq!(svg.matches('M').count(), 2);
+    assert!(svg.ends_with("</g>\n</svg>\n"));
+}
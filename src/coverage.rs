@@ -0,0 +1,286 @@
+//! The log of the regions of the plane the user has looked at, rendered or
+//! explored, and the heat map `coverage map` draws of it.
+//!
+//! Like the totals of `tally`, the log is only kept once the user asks for
+//! it with `stats enable`, which starts `visited.log` in the configuration
+//! directory; every render then adds its view to it, and so does every view
+//! `explore` shows. It holds a view a line, as its upper left and lower
+//! right corners:
+//!
+//! ```text
+//! # mandelbrot visited regions, version 1
+//! -2.5,1.5 1.5,-1.5
+//! -0.7475,0.1025 -0.7425,0.0975
+//! ```
+
+use std::{
+    fs::{self, OpenOptions},
+    io::{BufRead, BufReader, Write},
+};
+
+use num::Complex;
+
+use crate::{
+    output,
+    palette::{self, Palette},
+    parse_complex, platform, schema, DecimalSeparator, Error, Result,
+};
+
+/// The name of the file the log is kept in, in the configuration directory.
+pub const FILE_NAME: &str = "visited.log";
+
+/// The first line of the log, before its version.
+const HEADER: &str = "# mandelbrot visited regions, version ";
+
+/// A region looked at, by its corners.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Visit {
+    pub upper_left: Complex<f64>,
+    pub lower_right: Complex<f64>,
+}
+
+/// The version the log starting with `first_line` is written in.
+fn version(first_line: &str) -> Result<u64> {
+    first_line
+        .trim_end()
+        .strip_prefix(HEADER)
+        .and_then(|version| version.parse().ok())
+        .filter(|&version| version >= 1)
+        .ok_or_else(|| Error::parse("visited regions log header", first_line.trim_end()))
+}
+
+/// Start the log in the file named `file`, unless it is already kept.
+pub fn start(file: &str) -> Result<()> {
+    if platform::path(file).exists() {
+        return Ok(());
+    }
+    output::create_parent_dirs(file)?;
+    let header = format!("{}{}\n", HEADER, schema::VERSION);
+    output::write(file, header.as_bytes())
+}
+
+/// Add the region between `upper_left` and `lower_right` to the log in the
+/// file named `file`, if the user keeps it. A log written by a later
+/// version is left alone rather than added lines it may not expect.
+pub fn record(file: &str, upper_left: Complex<f64>, lower_right: Complex<f64>) -> Result<()> {
+    let path = platform::path(file);
+    let Ok(log) = fs::File::open(&path) else {
+        return Ok(());
+    };
+    let mut first_line = String::new();
+    BufReader::new(log)
+        .read_line(&mut first_line)
+        .map_err(Error::io(file))?;
+    if version(&first_line)? > schema::VERSION {
+        return Ok(());
+    }
+    let mut log = OpenOptions::new()
+        .append(true)
+        .open(&path)
+        .map_err(Error::io(file))?;
+    writeln!(
+        log,
+        "{},{} {},{}",
+        upper_left.re, upper_left.im, lower_right.re, lower_right.im
+    )
+    .map_err(Error::io(file))
+}
+
+/// Read the regions in the log kept in the file named `file`, none if it
+/// isn't kept.
+pub fn read(file: &str) -> Result<Vec<Visit>> {
+    let path = platform::path(file);
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let text = fs::read_to_string(&path).map_err(Error::io(file))?;
+    let mut lines = text.lines().enumerate();
+    let version = version(lines.next().map_or("", |(_, line)| line))?;
+    let mut visits = Vec::new();
+    let mut unread = 0;
+    for (i, line) in lines {
+        let corners: Vec<_> = line
+            .split_whitespace()
+            .map(|corner| parse_complex(corner, DecimalSeparator::Point))
+            .collect();
+        match corners[..] {
+            [Some(upper_left), Some(lower_right)] => visits.push(Visit {
+                upper_left,
+                lower_right,
+            }),
+            // later versions may add more after the corners
+            [Some(upper_left), Some(lower_right), ..] if version > schema::VERSION => {
+                visits.push(Visit {
+                    upper_left,
+                    lower_right,
+                })
+            }
+            _ if version > schema::VERSION => unread += 1,
+            _ => {
+                return Err(Error::parse(
+                    "visited regions log",
+                    &format!("{} line {}: {}", file, i + 1, line),
+                ))
+            }
+        }
+    }
+    if version > schema::VERSION {
+        let unread = match unread {
+            0 => vec![],
+            unread => vec![format!("{} lines", unread)],
+        };
+        schema::warn_newer("the visited regions log", version, schema::VERSION, &unread);
+    }
+    Ok(visits)
+}
+
+#[test]
+fn test_log() {
+    let dir = std::env::temp_dir().join(format!("mandelbrot-coverage-{}", std::process::id()));
+    let file = dir.join("config").join(FILE_NAME);
+    let file = file.to_str().unwrap();
+    let (a, b) = (Complex::new(-2.0, 1.5), Complex::new(1.0, -1.5));
+
+    // nothing is kept until asked for
+    record(file, a, b).unwrap();
+    assert!(read(file).unwrap().is_empty());
+
+    start(file).unwrap();
+    record(file, a, b).unwrap();
+    record(file, Complex::new(-0.75, 0.1), Complex::new(-0.7, 0.05)).unwrap();
+    start(file).unwrap();
+    let visits = read(file).unwrap();
+    assert_eq!(visits.len(), 2);
+    assert_eq!(
+        visits[0],
+        Visit {
+            upper_left: a,
+            lower_right: b
+        }
+    );
+
+    fs::write(file, format!("{}1\n-1,1 1,-1 extra\n", HEADER)).unwrap();
+    assert!(read(file).is_err());
+    fs::write(file, "-1,1 1,-1\n").unwrap();
+    assert!(read(file).is_err());
+
+    // a later version's log is read as best we can, and not added to
+    let newer = format!("{}2\n-1,1 1,-1 3\nsomething new\n", HEADER);
+    fs::write(file, &newer).unwrap();
+    assert_eq!(read(file).unwrap().len(), 1);
+    record(file, a, b).unwrap();
+    assert_eq!(fs::read_to_string(file).unwrap(), newer);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+/// How many of `visits` each pixel of the view from `upper_left` to
+/// `lower_right`, whose dimensions are given by `bounds`, was looked at in.
+/// A region smaller than a pixel counts for the pixel it is in.
+pub fn heat(
+    visits: &[Visit],
+    bounds: (usize, usize),
+    upper_left: Complex<f64>,
+    lower_right: Complex<f64>,
+) -> Vec<u32> {
+    let mut heat = vec![0; bounds.0 * bounds.1];
+    let scale = (
+        bounds.0 as f64 / (lower_right.re - upper_left.re),
+        bounds.1 as f64 / (upper_left.im - lower_right.im),
+    );
+    // the pixels from `from` to `to` along an axis `size` pixels long
+    let span = |from: f64, to: f64, size: usize| {
+        let (from, to) = (from.max(0.0), to.min(size as f64));
+        if from >= to {
+            return None;
+        }
+        let first = from.floor() as usize;
+        Some(first..(to.ceil() as usize).max(first + 1).min(size))
+    };
+    for visit in visits {
+        let left = (visit.upper_left.re - upper_left.re) * scale.0;
+        let right = (visit.lower_right.re - upper_left.re) * scale.0;
+        let top = (upper_left.im - visit.upper_left.im) * scale.1;
+        let bottom = (upper_left.im - visit.lower_right.im) * scale.1;
+        let (Some(columns), Some(rows)) =
+            (span(left, right, bounds.0), span(top, bottom, bounds.1))
+        else {
+            continue;
+        };
+        for y in rows {
+            for x in columns.clone() {
+                heat[y * bounds.0 + x] += 1;
+            }
+        }
+    }
+    heat
+}
+
+#[test]
+fn test_heat() {
+    let visit = |(re, im): (f64, f64), (re2, im2): (f64, f64)| Visit {
+        upper_left: Complex::new(re, im),
+        lower_right: Complex::new(re2, im2),
+    };
+    let (upper_left, lower_right) = (Complex::new(0.0, 4.0), Complex::new(4.0, 0.0));
+    let visits = [
+        // the whole view, and more
+        visit((-10.0, 10.0), (10.0, -10.0)),
+        // the upper left quarter
+        visit((0.0, 4.0), (2.0, 2.0)),
+        // much smaller than a pixel, in the lower right one
+        visit((3.5, 0.5), (3.5001, 0.4999)),
+        // outside
+        visit((5.0, 4.0), (6.0, 3.0)),
+    ];
+    #[rustfmt::skip]
+    assert_eq!(
+        heat(&visits, (4, 4), upper_left, lower_right),
+        [
+            2, 2, 1, 1,
+            2, 2, 1, 1,
+            1, 1, 1, 1,
+            1, 1, 1, 2,
+        ]
+    );
+}
+
+/// Draw `heat`, the count of visits of every pixel, over `pixels`, a
+/// grayscale image of the set of the same dimensions, into RGB pixels: gray,
+/// dimmed, where nothing was looked at, and colored by how often it was, on
+/// a logarithmic scale, where it was.
+pub fn map(pixels: &[u8], heat: &[u32]) -> Vec<u8> {
+    let lut = palette::preset("fire")
+        .unwrap_or_else(Palette::gray)
+        .lut(256);
+    let hottest = (*heat.iter().max().unwrap_or(&0) as f64 + 1.0).ln();
+    pixels
+        .iter()
+        .zip(heat)
+        .flat_map(|(&gray, &heat)| {
+            let dimmed = gray / 3;
+            if heat == 0 {
+                return [dimmed; 3];
+            }
+            // from a quarter of the way along the palette, so that places
+            // looked at once still show
+            let t = 0.25 + 0.75 * (heat as f64 + 1.0).ln() / hottest;
+            let color = lut[(t * 255.0).round() as usize];
+            // half as bright in the set, so that it still shows
+            color.map(|channel| (channel as u32 * (gray as u32 + 255) / 510) as u8)
+        })
+        .collect()
+}
+
+#[test]
+fn test_map() {
+    let rgb = map(&[255, 255, 0, 255], &[0, 1, 1, 9]);
+    assert_eq!(rgb.len(), 12);
+    // not looked at: gray, dimmed
+    assert_eq!(rgb[..3], [85, 85, 85]);
+    // looked at more: hotter
+    let brightness = |pixel: &[u8]| pixel.iter().map(|&c| c as u32).sum::<u32>();
+    assert!(brightness(&rgb[9..]) > brightness(&rgb[3..6]));
+    assert_ne!(rgb[3], rgb[4]);
+    // the set shows through
+    assert!(brightness(&rgb[6..9]) < brightness(&rgb[3..6]));
+}
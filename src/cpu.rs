@@ -0,0 +1,286 @@
+//! Picking the escape time kernel for the processor the program runs on.
+//!
+//! The lanes of `kernel::escape_time_periodic_lanes` become SIMD
+//! instructions only where the compiler may use them, and a binary built
+//! for any x86-64 may only count on SSE2. So the kernel is compiled a few
+//! times, for AVX2, AVX-512 and NEON as well as for the baseline, and the
+//! best the processor has is picked the first time it is needed, once for
+//! the process, unless `--force-kernel` picked one before. No build needs
+//! `-C target-cpu=native`, and every kernel computes the same escape times
+//! bit for bit, so the choice only changes how fast they come.
+
+use std::{
+    str::FromStr,
+    sync::atomic::{AtomicU8, Ordering},
+};
+
+use crate::{kernel, Error, Result};
+
+/// A way of computing many escape times.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Kernel {
+    /// One point at a time, `kernel::escape_time_periodic`.
+    Scalar,
+    /// Eight points at a time, in two of the 256-bit registers of AVX2.
+    Avx2,
+    /// Eight points at a time, in one of the 512-bit registers of AVX-512.
+    Avx512,
+    /// Four points at a time, in two of the 128-bit registers of NEON.
+    Neon,
+}
+
+/// The escape times and steps, as `kernel::escape_time_periodic` returns
+/// them, of every point of `points`, stored in `results`, which is as long.
+pub type EscapeTimes = fn(&[(f64, f64)], usize, f64, &mut [(Option<usize>, usize)]);
+
+impl Kernel {
+    /// Every kernel, fastest first.
+    pub const ALL: [Kernel; 4] = [Kernel::Avx512, Kernel::Avx2, Kernel::Neon, Kernel::Scalar];
+
+    /// The name of the kernel, as `--force-kernel` takes it.
+    pub fn name(self) -> &'static str {
+        match self {
+            Kernel::Scalar => "scalar",
+            Kernel::Avx2 => "avx2",
+            Kernel::Avx512 => "avx512",
+            Kernel::Neon => "neon",
+        }
+    }
+
+    /// Whether the processor the program runs on has the instructions of
+    /// the kernel.
+    pub fn is_supported(self) -> bool {
+        match self {
+            Kernel::Scalar => true,
+            #[cfg(target_arch = "x86_64")]
+            Kernel::Avx2 => is_x86_feature_detected!("avx2"),
+            #[cfg(target_arch = "x86_64")]
+            Kernel::Avx512 => is_x86_feature_detected!("avx512f"),
+            #[cfg(target_arch = "aarch64")]
+            Kernel::Neon => std::arch::is_aarch64_feature_detected!("neon"),
+            #[allow(unreachable_patterns)]
+            _ => false,
+        }
+    }
+
+    /// The fastest kernel the processor has.
+    pub fn best() -> Kernel {
+        Kernel::ALL
+            .into_iter()
+            .find(|kernel| kernel.is_supported())
+            .unwrap_or(Kernel::Scalar)
+    }
+
+    /// The function computing escape times with the kernel, or with the
+    /// scalar one if the processor doesn't have its instructions.
+    pub fn escape_times(self) -> EscapeTimes {
+        if !self.is_supported() {
+            return escape_times_scalar;
+        }
+        match self {
+            Kernel::Scalar => escape_times_scalar,
+            #[cfg(target_arch = "x86_64")]
+            Kernel::Avx2 => x86::escape_times_avx2,
+            #[cfg(target_arch = "x86_64")]
+            Kernel::Avx512 => x86::escape_times_avx512,
+            #[cfg(target_arch = "aarch64")]
+            Kernel::Neon => arm::escape_times_neon,
+            #[allow(unreachable_patterns)]
+            _ => escape_times_scalar,
+        }
+    }
+}
+
+impl FromStr for Kernel {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        Kernel::ALL
+            .into_iter()
+            .find(|kernel| kernel.name() == s)
+            .ok_or_else(|| {
+                format!(
+                    "unknown kernel {} (expected scalar, avx2, avx512 or neon)",
+                    s
+                )
+            })
+    }
+}
+
+#[test]
+fn test_kernel() {
+    for kernel in Kernel::ALL {
+        assert_eq!(kernel.name().parse(), Ok(kernel));
+    }
+    assert!("sse".parse::<Kernel>().is_err());
+    assert!(Kernel::Scalar.is_supported());
+    assert!(Kernel::best().is_supported());
+    #[cfg(not(target_arch = "aarch64"))]
+    assert!(!Kernel::Neon.is_supported());
+}
+
+/// The kernel picked, as its index in `Kernel::ALL` plus one, or 0 before
+/// one is.
+static KERNEL: AtomicU8 = AtomicU8::new(0);
+
+/// Compute escape times with `kernel` from now on, rather than the best the
+/// processor has, as `--force-kernel` asks.
+pub fn force(kernel: Kernel) -> Result<()> {
+    if !kernel.is_supported() {
+        return Err(Error::Usage(format!(
+            "this processor can't run the {} kernel",
+            kernel.name()
+        )));
+    }
+    let index = Kernel::ALL.iter().position(|&k| k == kernel).unwrap();
+    KERNEL.store(index as u8 + 1, Ordering::Relaxed);
+    Ok(())
+}
+
+/// The kernel escape times are computed with: the one forced, or else the
+/// best the processor has, picked now if it wasn't yet.
+pub fn kernel() -> Kernel {
+    match KERNEL.load(Ordering::Relaxed) {
+        0 => {
+            let best = Kernel::best();
+            let index = Kernel::ALL.iter().position(|&k| k == best).unwrap();
+            // a kernel forced meanwhile stays
+            let _ =
+                KERNEL.compare_exchange(0, index as u8 + 1, Ordering::Relaxed, Ordering::Relaxed);
+            Kernel::ALL[KERNEL.load(Ordering::Relaxed) as usize - 1]
+        }
+        index => Kernel::ALL[index as usize - 1],
+    }
+}
+
+/// The escape times and steps of every point of `points`, as
+/// `kernel::escape_time_periodic` returns them, stored in `results`, with
+/// the kernel picked.
+pub fn escape_times(
+    points: &[(f64, f64)],
+    limit: usize,
+    bailout: f64,
+    results: &mut [(Option<usize>, usize)],
+) {
+    assert_eq!(points.len(), results.len());
+    kernel().escape_times()(points, limit, bailout, results)
+}
+
+#[test]
+fn test_escape_times() {
+    let points: Vec<(f64, f64)> = (0..203)
+        .map(|i| (-2.1 + 0.013 * i as f64, 1.1 - 0.011 * i as f64))
+        .collect();
+    let expected: Vec<_> = points
+        .iter()
+        .map(|&c| kernel::escape_time_periodic(c, 500, 2.0))
+        .collect();
+    // the kernels the processor doesn't have fall back to the scalar one
+    for kernel in Kernel::ALL {
+        let mut results = vec![(None, 0); points.len()];
+        kernel.escape_times()(&points, 500, 2.0, &mut results);
+        assert_eq!(results, expected, "{}", kernel.name());
+    }
+    let mut results = vec![(None, 0); points.len()];
+    escape_times(&points, 500, 2.0, &mut results);
+    assert_eq!(results, expected);
+}
+
+/// `escape_times` `N` points at a time, the points left over at the end one
+/// at a time.
+#[inline(always)]
+fn escape_times_in_lanes<const N: usize>(
+    points: &[(f64, f64)],
+    limit: usize,
+    bailout: f64,
+    results: &mut [(Option<usize>, usize)],
+) {
+    let mut chunks = points.chunks_exact(N);
+    let mut results = results.chunks_mut(N);
+    for (chunk, results) in (&mut chunks).zip(&mut results) {
+        let c: [(f64, f64); N] = chunk.try_into().unwrap();
+        results.copy_from_slice(&kernel::escape_time_periodic_lanes(c, limit, bailout));
+    }
+    if let Some(results) = results.next() {
+        for (&c, result) in chunks.remainder().iter().zip(results) {
+            *result = kernel::escape_time_periodic(c, limit, bailout);
+        }
+    }
+}
+
+fn escape_times_scalar(
+    points: &[(f64, f64)],
+    limit: usize,
+    bailout: f64,
+    results: &mut [(Option<usize>, usize)],
+) {
+    for (&c, result) in points.iter().zip(results) {
+        *result = kernel::escape_time_periodic(c, limit, bailout);
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+mod x86 {
+    pub(super) fn escape_times_avx2(
+        points: &[(f64, f64)],
+        limit: usize,
+        bailout: f64,
+        results: &mut [(Option<usize>, usize)],
+    ) {
+        #[target_feature(enable = "avx2")]
+        fn avx2(
+            points: &[(f64, f64)],
+            limit: usize,
+            bailout: f64,
+            results: &mut [(Option<usize>, usize)],
+        ) {
+            super::escape_times_in_lanes::<8>(points, limit, bailout, results)
+        }
+        // only returned by `Kernel::escape_times` once the processor said
+        // it has AVX2
+        unsafe { avx2(points, limit, bailout, results) }
+    }
+
+    pub(super) fn escape_times_avx512(
+        points: &[(f64, f64)],
+        limit: usize,
+        bailout: f64,
+        results: &mut [(Option<usize>, usize)],
+    ) {
+        #[target_feature(enable = "avx512f")]
+        fn avx512(
+            points: &[(f64, f64)],
+            limit: usize,
+            bailout: f64,
+            results: &mut [(Option<usize>, usize)],
+        ) {
+            super::escape_times_in_lanes::<8>(points, limit, bailout, results)
+        }
+        // only returned by `Kernel::escape_times` once the processor said
+        // it has AVX-512
+        unsafe { avx512(points, limit, bailout, results) }
+    }
+}
+
+#[cfg(target_arch = "aarch64")]
+mod arm {
+    pub(super) fn escape_times_neon(
+        points: &[(f64, f64)],
+        limit: usize,
+        bailout: f64,
+        results: &mut [(Option<usize>, usize)],
+    ) {
+        #[target_feature(enable = "neon")]
+        fn neon(
+            points: &[(f64, f64)],
+            limit: usize,
+            bailout: f64,
+            results: &mut [(Option<usize>, usize)],
+        ) {
+            super::escape_times_in_lanes::<4>(points, limit, bailout, results)
+        }
+        // only returned by `Kernel::escape_times` once the processor said
+        // it has NEON
+        unsafe { neon(points, limit, bailout, results) }
+    }
+}
@@ -0,0 +1,319 @@
+//! Progressive deepening, for `--progressive`: render the whole image at a
+//! low iteration limit first, then double the limit again and again, only
+//! iterating further the points that haven't escaped yet.
+//!
+//! Every point keeps where its orbit got to, so a deeper pass takes it up
+//! from there instead of starting over from 0: the points that escaped are
+//! done for good, and the others only cost the iterations added. With
+//! `--state`, the orbits are saved to a file, so that a later run can go on
+//! deepening the same image.
+
+use std::{fs, sync::Mutex};
+
+use num::Complex;
+
+use crate::{
+    output, platform,
+    render::{self, Schedule},
+    Error, Result,
+};
+
+/// The limit of the first pass.
+pub const START_LIMIT: usize = 64;
+
+/// How far the orbit of a point has got.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Orbit {
+    pub z: Complex<f64>,
+    /// The derivative of `z` with respect to `c`, which distance estimates
+    /// need.
+    pub dz: Complex<f64>,
+    /// The iterations done.
+    pub count: usize,
+    /// Whether `z` has left the circle of radius 2, after `count` iterations.
+    pub escaped: bool,
+}
+
+impl Orbit {
+    /// Iterate the orbit of `c` until it escapes or `limit` iterations are
+    /// done, counting like `render::escape_time`.
+    pub fn iterate(&mut self, c: Complex<f64>, limit: usize) {
+        while !self.escaped && self.count < limit {
+            if self.z.norm_sqr() > 4.0 {
+                self.escaped = true;
+                return;
+            }
+            self.dz = 2.0 * self.z * self.dz + 1.0;
+            self.z = self.z * self.z + c;
+            self.count += 1;
+        }
+    }
+}
+
+#[test]
+fn test_iterate() {
+    for c in [
+        Complex::new(0.3, 0.5),
+        Complex::new(-0.75, 0.1),
+        Complex::new(0.0, 0.0),
+    ] {
+        let mut orbit = Orbit::default();
+        orbit.iterate(c, 10);
+        orbit.iterate(c, 1000);
+        let escape = render::escape_time(c, 1000);
+        assert_eq!(orbit.escaped.then_some(orbit.count), escape, "{}", c);
+    }
+}
+
+/// The orbits of all the pixels of an image, iterated up to `limit`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Deepening {
+    pub bounds: (usize, usize),
+    pub upper_left: Complex<f64>,
+    pub lower_right: Complex<f64>,
+    /// The limit every orbit has been iterated to, unless it escaped.
+    pub limit: usize,
+    /// The orbits, row by row.
+    pub orbits: Vec<Orbit>,
+}
+
+impl Deepening {
+    /// Start on the view from `upper_left` to `lower_right`, rendered into
+    /// an image whose dimensions are given by `bounds`, with no iterations
+    /// done yet.
+    pub fn new(
+        bounds: (usize, usize),
+        upper_left: Complex<f64>,
+        lower_right: Complex<f64>,
+    ) -> Deepening {
+        Deepening {
+            bounds,
+            upper_left,
+            lower_right,
+            limit: 0,
+            orbits: vec![Orbit::default(); bounds.0 * bounds.1],
+        }
+    }
+
+    /// Iterate the orbits that haven't escaped yet up to `limit`, on the
+    /// threads of `schedule`. Returns false if `schedule` was stopped
+    /// before all the rows were done, leaving `self.limit` as it was.
+    pub fn deepen(&mut self, limit: usize, schedule: &Schedule) -> bool {
+        let width = self.bounds.0;
+        if limit <= self.limit || self.orbits.is_empty() {
+            return true;
+        }
+        let (bounds, upper_left, lower_right) = (self.bounds, self.upper_left, self.lower_right);
+        let rows = schedule.chunk_rows.max(1);
+        let chunks = Mutex::new(self.orbits.chunks_mut(rows * width).enumerate());
+        std::thread::scope(|scope| {
+            for _ in 0..schedule.threads.max(1) {
+                scope.spawn(|| loop {
+                    if schedule.stopped() {
+                        break;
+                    }
+                    let Some((number, chunk)) = chunks.lock().unwrap().next() else {
+                        break;
+                    };
+                    for (i, orbit) in chunk.iter_mut().enumerate() {
+                        let pixel = (i % width, number * rows + i / width);
+                        let c = render::pixel_to_point(bounds, pixel, upper_left, lower_right);
+                        orbit.iterate(c, limit);
+                    }
+                });
+            }
+        });
+        if schedule.stopped() {
+            return false;
+        }
+        self.limit = limit;
+        true
+    }
+
+    /// How many of the orbits haven't escaped yet.
+    pub fn interior(&self) -> usize {
+        self.orbits.iter().filter(|orbit| !orbit.escaped).count()
+    }
+
+    /// The grayscale pixels of the image at the limit reached, as
+    /// `render::render_with_limit` would draw them. Orbits iterated beyond
+    /// it, by a pass that was cancelled, count as not escaped yet.
+    pub fn pixels(&self) -> Vec<u8> {
+        self.orbits
+            .iter()
+            .map(|orbit| {
+                let escape = (orbit.escaped && orbit.count < self.limit).then_some(orbit.count);
+                render::gray_level(escape, self.limit)
+            })
+            .collect()
+    }
+}
+
+/// The first bytes of state files.
+const MAGIC: &[u8; 8] = b"MBSTATE1";
+
+/// The bytes every orbit takes in a state file.
+const ORBIT_BYTES: usize = 40;
+
+impl Deepening {
+    /// Encode the orbits as the contents of a state file: after `MAGIC`,
+    /// the dimensions and the corners of the view, the limit, then for every
+    /// orbit its count, shifted left once with the lowest bit set if it
+    /// escaped, followed by `z` and `dz`: 40 bytes a pixel, little endian.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend((self.bounds.0 as u32).to_le_bytes());
+        bytes.extend((self.bounds.1 as u32).to_le_bytes());
+        for value in [
+            self.upper_left.re,
+            self.upper_left.im,
+            self.lower_right.re,
+            self.lower_right.im,
+        ] {
+            bytes.extend(value.to_le_bytes());
+        }
+        bytes.extend((self.limit as u64).to_le_bytes());
+        for orbit in &self.orbits {
+            bytes.extend(((orbit.count as u64) << 1 | orbit.escaped as u64).to_le_bytes());
+            for value in [orbit.z.re, orbit.z.im, orbit.dz.re, orbit.dz.im] {
+                bytes.extend(value.to_le_bytes());
+            }
+        }
+        bytes
+    }
+
+    /// Decode the contents of a state file written by `to_bytes`, or return
+    /// `None` if they aren't one.
+    pub fn from_bytes(mut bytes: &[u8]) -> Option<Deepening> {
+        fn take<const N: usize>(bytes: &mut &[u8]) -> Option<[u8; N]> {
+            let (head, rest) = bytes.split_first_chunk()?;
+            *bytes = rest;
+            Some(*head)
+        }
+        let f64 = |bytes: &mut &[u8]| take(bytes).map(f64::from_le_bytes);
+        let u64 = |bytes: &mut &[u8]| take(bytes).map(u64::from_le_bytes);
+
+        if take(&mut bytes)? != *MAGIC {
+            return None;
+        }
+        let width = u32::from_le_bytes(take(&mut bytes)?) as usize;
+        let height = u32::from_le_bytes(take(&mut bytes)?) as usize;
+        let upper_left = Complex::new(f64(&mut bytes)?, f64(&mut bytes)?);
+        let lower_right = Complex::new(f64(&mut bytes)?, f64(&mut bytes)?);
+        let limit = u64(&mut bytes)? as usize;
+        let count = width.checked_mul(height)?;
+        if count.checked_mul(ORBIT_BYTES) != Some(bytes.len()) {
+            return None;
+        }
+        let mut orbits = Vec::with_capacity(count);
+        for _ in 0..count {
+            let word = u64(&mut bytes)?;
+            orbits.push(Orbit {
+                count: (word >> 1) as usize,
+                escaped: word & 1 == 1,
+                z: Complex::new(f64(&mut bytes)?, f64(&mut bytes)?),
+                dz: Complex::new(f64(&mut bytes)?, f64(&mut bytes)?),
+            });
+        }
+        Some(Deepening {
+            bounds: (width, height),
+            upper_left,
+            lower_right,
+            limit,
+            orbits,
+        })
+    }
+
+    /// Write the orbits to the state file `filename`.
+    pub fn save(&self, filename: &str) -> Result<()> {
+        output::write(filename, &self.to_bytes())
+    }
+
+    /// Read the orbits from the state file `filename`.
+    pub fn load(filename: &str) -> Result<Deepening> {
+        let bytes = fs::read(platform::path(filename)).map_err(Error::io(filename))?;
+        Deepening::from_bytes(&bytes).ok_or_else(|| Error::parse("state file", filename))
+    }
+}
+
+#[test]
+fn test_state_bytes() {
+    let (upper_left, lower_right) = (Complex::new(-0.76, 0.14), Complex::new(-0.72, 0.11));
+    let mut deepening = Deepening::new((7, 5), upper_left, lower_right);
+    deepening.deepen(100, &Schedule::new(2));
+    let bytes = deepening.to_bytes();
+    assert!(bytes.starts_with(MAGIC));
+    assert_eq!(Deepening::from_bytes(&bytes), Some(deepening.clone()));
+    assert_eq!(bytes.len(), 8 + 8 + 32 + 8 + ORBIT_BYTES * 35);
+
+    assert_eq!(Deepening::from_bytes(&bytes[..bytes.len() - 1]), None);
+    assert_eq!(Deepening::from_bytes(b"MBSTATE2"), None);
+
+    // going on from the state file is the same as never stopping
+    let mut resumed = Deepening::from_bytes(&bytes).unwrap();
+    resumed.deepen(1000, &Schedule::new(2));
+    deepening.deepen(1000, &Schedule::new(3));
+    assert_eq!(resumed, deepening);
+}
+
+/// Deepen `deepening` until it reaches `schedule.limit`: from
+/// `START_LIMIT`, or the limit it's at, doubling it every pass. `snapshot`
+/// is called with it after every pass but the last.
+///
+/// Stops early, without an error, if `schedule` is stopped.
+pub fn progressive(
+    deepening: &mut Deepening,
+    schedule: &Schedule,
+    snapshot: &mut dyn FnMut(&Deepening) -> Result<()>,
+) -> Result<()> {
+    let mut limit = START_LIMIT.max(deepening.limit.saturating_mul(2));
+    loop {
+        limit = limit.min(schedule.limit);
+        if !deepening.deepen(limit, schedule) || limit == schedule.limit {
+            return Ok(());
+        }
+        snapshot(deepening)?;
+        limit = limit.saturating_mul(2);
+    }
+}
+
+#[test]
+fn test_progressive() {
+    let bounds = (41, 30);
+    let (upper_left, lower_right) = (Complex::new(-0.76, 0.14), Complex::new(-0.72, 0.11));
+    let schedule = Schedule {
+        chunk_rows: 3,
+        limit: 1000,
+        ..Schedule::new(4)
+    };
+    let mut deepening = Deepening::new(bounds, upper_left, lower_right);
+    let mut snapshots = Vec::new();
+    progressive(&mut deepening, &schedule, &mut |snapshot| {
+        assert_eq!(snapshot.pixels().len(), bounds.0 * bounds.1);
+        snapshots.push((snapshot.limit, snapshot.interior()));
+        Ok(())
+    })
+    .unwrap();
+    let limits: Vec<usize> = snapshots.iter().map(|&(limit, _)| limit).collect();
+    assert_eq!(limits, [64, 128, 256, 512]);
+    // fewer points are left to iterate every pass
+    assert!(
+        snapshots.windows(2).all(|w| w[0].1 > w[1].1),
+        "{:?}",
+        snapshots
+    );
+    assert_eq!(deepening.limit, 1000);
+
+    // the same as rendering at the last limit from scratch
+    let mut expected = vec![0; bounds.0 * bounds.1];
+    render::render_with_limit(
+        render::Strategy::Naive,
+        &mut expected,
+        bounds,
+        upper_left,
+        lower_right,
+        1000,
+    );
+    assert_eq!(deepening.pixels(), expected);
+    assert!(deepening.interior() > 0);
+}
@@ -0,0 +1,127 @@
+//! The derivative field of a view, for `--derivative`: what distance
+//! estimation and lighting are computed from, written as a float image so
+//! that compositors can do their own shading without iterating the orbits
+//! again.
+//!
+//! Every pixel gets three numbers: the direction of the surface normal,
+//! `z / dz` scaled to length 1, as its real and imaginary parts, and the
+//! distance estimate to the set, in pixels. Points in the set, which have
+//! neither, get zeros.
+
+use std::thread;
+
+use num::Complex;
+
+use crate::render;
+
+/// How far orbits are iterated before the distance is estimated: well past
+/// the radius of 2 where escape times stop, since the estimate is only
+/// accurate for large `z`.
+const BAILOUT: f64 = 1000.0;
+
+/// The derivative of one point: the direction of the normal, and the
+/// distance to the set in pixels.
+pub type Sample = [f32; 3];
+
+/// The derivative sample of the point `c`, whose pixels are `pixel_size`
+/// wide, iterating its orbit at most `limit` times.
+pub fn sample(c: Complex<f64>, limit: usize, pixel_size: f64) -> Sample {
+    let mut z = Complex { re: 0.0, im: 0.0 };
+    let mut dz = Complex { re: 0.0, im: 0.0 };
+    for _ in 0..limit {
+        if z.norm_sqr() > BAILOUT * BAILOUT {
+            let (r, slope) = (z.norm(), dz.norm());
+            if slope == 0.0 {
+                break;
+            }
+            let normal = z / dz;
+            let normal = normal / normal.norm();
+            let distance = 2.0 * r * r.ln() / slope;
+            return [
+                normal.re as f32,
+                normal.im as f32,
+                (distance / pixel_size) as f32,
+            ];
+        }
+        dz = 2.0 * z * dz + 1.0;
+        z = z * z + c;
+    }
+    [0.0; 3]
+}
+
+#[test]
+fn test_sample() {
+    // on the real axis right of the set the normal points right, and the
+    // point is about 0.25 away from the cusp
+    let [re, im, distance] = sample(Complex::new(0.5, 0.0), 1000, 1.0);
+    assert!((re - 1.0).abs() < 1e-6 && im.abs() < 1e-6, "{} {}", re, im);
+    assert!(distance > 0.05 && distance < 0.5, "{}", distance);
+    // measured in pixels
+    let [_, _, pixels] = sample(Complex::new(0.5, 0.0), 1000, 0.01);
+    assert!((pixels - 100.0 * distance).abs() < 1e-3);
+    // the farther, the larger
+    assert!(sample(Complex::new(1.5, 0.0), 1000, 1.0)[2] > distance);
+    assert_eq!(sample(Complex::new(-0.1, 0.1), 1000, 1.0), [0.0; 3]);
+}
+
+/// The derivative field of the view from `upper_left` to `lower_right`, in
+/// an image whose dimensions are given by `bounds`, row by row, computed on
+/// `threads` threads.
+pub fn field(
+    bounds: (usize, usize),
+    upper_left: Complex<f64>,
+    lower_right: Complex<f64>,
+    limit: usize,
+    threads: usize,
+) -> Vec<Sample> {
+    let mut samples = vec![[0.0; 3]; bounds.0 * bounds.1];
+    if samples.is_empty() {
+        return samples;
+    }
+    let pixel_size = (lower_right.re - upper_left.re) / bounds.0 as f64;
+    let rows = bounds.1.div_ceil(threads.max(1));
+    thread::scope(|scope| {
+        for (band, samples) in samples.chunks_mut(rows * bounds.0).enumerate() {
+            scope.spawn(move || {
+                for (i, sample_out) in samples.iter_mut().enumerate() {
+                    let pixel = (i % bounds.0, band * rows + i / bounds.0);
+                    let c = render::pixel_to_point(bounds, pixel, upper_left, lower_right);
+                    *sample_out = sample(c, limit, pixel_size);
+                }
+            });
+        }
+    });
+    samples
+}
+
+/// Encode `samples`, whose dimensions are given by `bounds`, as a color
+/// Portable Float Map: a short text header, then the rows from the bottom
+/// up, three little endian `f32` a pixel.
+pub fn pfm(samples: &[Sample], bounds: (usize, usize)) -> Vec<u8> {
+    // a negative scale says little endian
+    let mut bytes = format!("PF\n{} {}\n-1.0\n", bounds.0, bounds.1).into_bytes();
+    for row in samples.chunks(bounds.0.max(1)).rev() {
+        for value in row.iter().flatten() {
+            bytes.extend(value.to_le_bytes());
+        }
+    }
+    bytes
+}
+
+#[test]
+fn test_field() {
+    let bounds = (8, 6);
+    let (upper_left, lower_right) = (Complex::new(-2.0, 1.5), Complex::new(2.0, -1.5));
+    let samples = field(bounds, upper_left, lower_right, 500, 4);
+    assert_eq!(samples, field(bounds, upper_left, lower_right, 500, 1));
+    // the corner is far out, the middle of the bottom half near the set
+    assert!(samples[0][2] > samples[4 * 8 + 3][2]);
+
+    let bytes = pfm(&samples, bounds);
+    let header = b"PF\n8 6\n-1.0\n";
+    assert!(bytes.starts_with(header));
+    assert_eq!(bytes.len(), header.len() + 8 * 6 * 12);
+    // the last row comes first
+    let first = f32::from_le_bytes(bytes[header.len()..header.len() + 4].try_into().unwrap());
+    assert_eq!(first, samples[5 * 8][0]);
+}
@@ -0,0 +1,896 @@
+//! Rendering one image, or the frames of an animation, on several machines:
+//! a coordinator cuts each frame into bands of rows and hands them out over
+//! HTTP to workers, which render them and send their pixels back.
+//!
+//! The coordinator answers, one connection at a time:
+//!
+//! - `GET /task` with the next band to render, as a query string like
+//!   `band=3&top=384&rows=128&pixels=800x600&upper-left=-2.5,1.5&lower-right=1.5,-1.5&strategy=naive&limit=255&bailout=2`,
+//!   starting with `frame=N&` when there are several frames, with 204 if
+//!   every band is out but some aren't back yet, and with 410 once every
+//!   frame is done;
+//! - `POST /result?band=N`, or `?frame=F&band=N`, with the escape times of
+//!   the band, a byte per pixel;
+//! - `POST /failed?band=N`, or `?frame=F&band=N`, with what went wrong
+//!   rendering it;
+//! - `GET /health` with `ok`.
+//!
+//! Bands are handed out a frame after the other, so frames are done about
+//! in order and only those in progress are held in memory. A band that
+//! isn't back within the lease is handed out again, so a worker dying only
+//! slows the render down, and a band coming back twice is only used once. A
+//! band failing or timing out `MAX_FAILURES` times fails the render.
+
+use std::{
+    collections::HashMap,
+    io::{self, BufRead, BufReader, Read, Write},
+    net::{TcpListener, TcpStream},
+    panic, thread,
+    time::{Duration, Instant},
+};
+
+use num::Complex;
+
+use crate::{
+    error, parse_complex, parse_pair,
+    render::{self, Strategy},
+    server::{self, Response},
+    DecimalSeparator, Error, Result,
+};
+
+/// How many rows a band has.
+pub const DEFAULT_BAND_ROWS: usize = 128;
+
+/// How long a worker has to send a band back, unless told otherwise, before
+/// it is handed out again.
+pub const DEFAULT_LEASE: Duration = Duration::from_secs(60);
+
+/// How many times a band may fail or time out before the render is given
+/// up.
+const MAX_FAILURES: usize = 3;
+
+/// How long a worker waits before asking again when every band is out, or
+/// before trying to reach the coordinator again.
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// How many times in a row a worker tries to reach the coordinator.
+const CONNECT_ATTEMPTS: usize = 20;
+
+/// How long the coordinator stays up once the image is done, to tell the
+/// workers asking for more that there is none.
+const LINGER: Duration = Duration::from_secs(2);
+
+/// How long a connection may take to send its request, or its answer.
+const READ_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// A band of rows of an image, for a worker to render.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Task {
+    /// The number of the frame the band is in, when there are several.
+    pub frame: Option<usize>,
+    pub band: usize,
+    /// The first row of the band.
+    pub top: usize,
+    pub rows: usize,
+    /// The dimensions of the whole image.
+    pub bounds: (usize, usize),
+    /// The corners of the whole image.
+    pub upper_left: Complex<f64>,
+    pub lower_right: Complex<f64>,
+    pub strategy: Strategy,
+    pub limit: usize,
+    pub bailout: f64,
+}
+
+impl Task {
+    /// Write the task as the coordinator sends it.
+    pub fn to_query(&self) -> String {
+        format!(
+            "{}band={}&top={}&rows={}&pixels={}x{}&upper-left={},{}&lower-right={},{}&strategy={}&limit={}&bailout={}",
+            self.frame.map_or(String::new(), |frame| format!("frame={}&", frame)),
+            self.band,
+            self.top,
+            self.rows,
+            self.bounds.0,
+            self.bounds.1,
+            self.upper_left.re,
+            self.upper_left.im,
+            self.lower_right.re,
+            self.lower_right.im,
+            self.strategy,
+            self.limit,
+            self.bailout
+        )
+    }
+
+    /// The target a worker posts the result of the task to, `/result` or
+    /// `/failed`.
+    fn target(&self, path: &str) -> String {
+        match self.frame {
+            Some(frame) => format!("{}?frame={}&band={}", path, frame, self.band),
+            None => format!("{}?band={}", path, self.band),
+        }
+    }
+
+    /// The band in words, `band 3` or `frame 2 band 3`, counting from 1.
+    pub fn name(&self) -> String {
+        band_name(self.frame, self.band)
+    }
+
+    /// Parse a task as the coordinator sends it.
+    pub fn from_query(query: &str) -> Result<Task> {
+        let invalid = || Error::parse("task", query.trim());
+        let mut fields = HashMap::new();
+        for parameter in query.trim().split('&') {
+            let (name, value) = parameter.split_once('=').ok_or_else(invalid)?;
+            fields.insert(name, value);
+        }
+        let field = |name: &str| fields.get(name).copied().ok_or_else(invalid);
+        let number = |name: &str| field(name)?.parse().map_err(|_| invalid());
+        let point =
+            |name: &str| parse_complex(field(name)?, DecimalSeparator::Point).ok_or_else(invalid);
+        let task = Task {
+            frame: match fields.get("frame") {
+                Some(frame) => Some(frame.parse().map_err(|_| invalid())?),
+                None => None,
+            },
+            band: number("band")?,
+            top: number("top")?,
+            rows: number("rows")?,
+            bounds: parse_pair(field("pixels")?, 'x').ok_or_else(invalid)?,
+            upper_left: point("upper-left")?,
+            lower_right: point("lower-right")?,
+            strategy: field("strategy")?.parse().map_err(|_| invalid())?,
+            limit: number("limit")?,
+            bailout: field("bailout")?.parse().map_err(|_| invalid())?,
+        };
+        if task.rows == 0
+            || task.rows > task.bounds.1.saturating_sub(task.top)
+            || !(task.bailout.is_finite() && task.bailout > 0.0)
+        {
+            return Err(invalid());
+        }
+        error::validate_viewport(task.bounds, task.upper_left, task.lower_right)?;
+        Ok(task)
+    }
+
+    /// Render the band on `threads` threads, exactly as `render_parallel`
+    /// renders it as part of the whole image in chunks as tall as the band.
+    pub fn render(&self, threads: usize) -> Vec<u8> {
+        let bounds = (self.bounds.0, self.rows);
+        let upper_left = render::pixel_to_point(
+            self.bounds,
+            (0, self.top),
+            self.upper_left,
+            self.lower_right,
+        );
+        let lower_right = render::pixel_to_point(
+            self.bounds,
+            (self.bounds.0, self.top + self.rows),
+            self.upper_left,
+            self.lower_right,
+        );
+        let mut pixels = vec![0; bounds.0 * bounds.1];
+        render::render_parallel(
+            self.strategy,
+            &mut pixels,
+            bounds,
+            upper_left,
+            lower_right,
+            &render::Schedule {
+                limit: self.limit,
+                bailout: self.bailout,
+                ..render::Schedule::new(threads)
+            },
+        );
+        pixels
+    }
+}
+
+/// `band` of `frame`, in words, counting both from 1.
+fn band_name(frame: Option<usize>, band: usize) -> String {
+    match frame {
+        Some(frame) => format!("frame {} band {}", frame + 1, band + 1),
+        None => format!("band {}", band + 1),
+    }
+}
+
+#[test]
+fn test_task() {
+    let task = Task {
+        frame: None,
+        band: 1,
+        top: 8,
+        rows: 4,
+        bounds: (40, 12),
+        upper_left: Complex::new(-2.5, 1.1),
+        lower_right: Complex::new(1.0, -1.0 / 3.0),
+        strategy: Strategy::BorderTrace,
+        limit: 100,
+        bailout: 2.0,
+    };
+    assert_eq!(Task::from_query(&(task.to_query() + "\n")).unwrap(), task);
+    assert_eq!(task.name(), "band 2");
+    let framed = Task {
+        frame: Some(6),
+        bailout: 1e3,
+        ..task.clone()
+    };
+    assert!(framed.to_query().starts_with("frame=6&band=1&"));
+    assert_eq!(Task::from_query(&framed.to_query()).unwrap(), framed);
+    assert_eq!(framed.name(), "frame 7 band 2");
+    assert_eq!(framed.target("/result"), "/result?frame=6&band=1");
+
+    let query = task.to_query();
+    assert!(Task::from_query(&query.replace("rows=4", "rows=5")).is_err());
+    assert!(Task::from_query(&query.replace("rows=4", "rows=0")).is_err());
+    assert!(Task::from_query(&query.replace("&limit=100", "")).is_err());
+    assert!(Task::from_query(&query.replace("bailout=2", "bailout=0")).is_err());
+    assert!(Task::from_query(&query.replace("band=1&", "frame=x&band=1&")).is_err());
+    assert!(Task::from_query(&query.replace("border-trace", "fast")).is_err());
+    assert!(Task::from_query(&query.replace("1.1", "-2")).is_err());
+    assert!(Task::from_query("done").is_err());
+
+    assert_eq!(task.render(2).len(), 40 * 4);
+}
+
+/// Where a band of the image is.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Band {
+    /// Not handed out yet, or to be handed out again.
+    Waiting,
+    /// Handed out at this instant.
+    Leased(Instant),
+    Done,
+}
+
+/// A frame of the images a coordinator has rendered.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Frame {
+    /// The number of the frame, which tells it apart from the others.
+    pub number: usize,
+    pub upper_left: Complex<f64>,
+    pub lower_right: Complex<f64>,
+    pub limit: usize,
+}
+
+/// The images a coordinator has rendered: one frame for a still image, or
+/// the frames of an animation, all of the same size.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Job {
+    pub bounds: (usize, usize),
+    pub frames: Vec<Frame>,
+    pub strategy: Strategy,
+    pub bailout: f64,
+    /// How many rows each band has, but the last.
+    pub band_rows: usize,
+}
+
+/// Hands out the bands of a job to workers, and puts the pixels they send
+/// back together.
+pub struct Coordinator {
+    job: Job,
+    lease: Duration,
+    /// How many bands each frame has.
+    frame_bands: usize,
+    /// The bands of every frame, one frame after the other.
+    bands: Vec<Band>,
+    /// How many times each band failed or timed out.
+    failures: Vec<usize>,
+    /// The pixels of the frames bands came back for, until they are done.
+    pixels: HashMap<usize, Vec<u8>>,
+    /// How many bands of each frame aren't back yet.
+    frames_left: Vec<usize>,
+    /// The numbers and pixels of the frames done and not taken yet.
+    done: Vec<(usize, Vec<u8>)>,
+    /// How many bands aren't back yet.
+    left: usize,
+    failed: Option<String>,
+}
+
+impl Coordinator {
+    /// Coordinate `job`, handing a band out again if it isn't back within
+    /// `lease`.
+    pub fn new(job: Job, lease: Duration) -> Coordinator {
+        let job = Job {
+            band_rows: job.band_rows.max(1),
+            ..job
+        };
+        let frame_bands = job.bounds.1.div_ceil(job.band_rows);
+        let count = frame_bands * job.frames.len();
+        Coordinator {
+            lease,
+            frame_bands,
+            bands: vec![Band::Waiting; count],
+            failures: vec![0; count],
+            pixels: HashMap::new(),
+            frames_left: vec![frame_bands; job.frames.len()],
+            done: Vec::new(),
+            left: count,
+            failed: None,
+            job,
+        }
+    }
+
+    /// The task of the band at `index` in `bands`.
+    fn task(&self, index: usize) -> Task {
+        let frame = &self.job.frames[index / self.frame_bands];
+        let band = index % self.frame_bands;
+        let top = band * self.job.band_rows;
+        Task {
+            frame: (self.job.frames.len() > 1).then_some(frame.number),
+            band,
+            top,
+            rows: self.job.band_rows.min(self.job.bounds.1 - top),
+            bounds: self.job.bounds,
+            upper_left: frame.upper_left,
+            lower_right: frame.lower_right,
+            strategy: self.job.strategy,
+            limit: frame.limit,
+            bailout: self.job.bailout,
+        }
+    }
+
+    /// Where in `bands` the band a worker gives by the query `query` is:
+    /// `band=N`, or `frame=F&band=N` when there are several frames.
+    fn index(&self, query: &str) -> Option<usize> {
+        let (frame, band) = match query.split_once('&') {
+            Some((frame, band)) if self.job.frames.len() > 1 => {
+                let number: usize = frame.strip_prefix("frame=")?.parse().ok()?;
+                let frame = self
+                    .job
+                    .frames
+                    .iter()
+                    .position(|frame| frame.number == number)?;
+                (frame, band)
+            }
+            None if self.job.frames.len() == 1 => (0, query),
+            _ => return None,
+        };
+        let band: usize = band.strip_prefix("band=")?.parse().ok()?;
+        (band < self.frame_bands).then_some(frame * self.frame_bands + band)
+    }
+
+    /// Whether every band is back, or the render has failed.
+    pub fn finished(&self) -> bool {
+        self.left == 0 || self.failed.is_some()
+    }
+
+    /// The numbers and pixels of the frames done since the last call, in the
+    /// order they were done.
+    pub fn take_done(&mut self) -> Vec<(usize, Vec<u8>)> {
+        std::mem::take(&mut self.done)
+    }
+
+    /// Whether every frame was done, once there is nothing left to do.
+    pub fn into_result(self) -> Result<()> {
+        match self.failed {
+            Some(failure) => Err(Error::Distributed(failure)),
+            None if self.left > 0 => Err(Error::Distributed(format!(
+                "{} of {} bands never came back",
+                self.left,
+                self.bands.len()
+            ))),
+            None => Ok(()),
+        }
+    }
+
+    /// Answer a request from a worker for `target`, the path and query of
+    /// the URL, telling `log` about what the user should know.
+    pub fn handle(
+        &mut self,
+        method: &str,
+        target: &str,
+        body: &[u8],
+        log: &mut dyn FnMut(&str),
+    ) -> Response {
+        let (path, query) = target.split_once('?').unwrap_or((target, ""));
+        match (method, path) {
+            ("GET", "/health") => Response::text(200, "ok"),
+            ("GET", "/task") => self.hand_out(log),
+            ("POST", "/result" | "/failed") => {
+                let Some(index) = self.index(query) else {
+                    return Response::text(400, "no such band");
+                };
+                if path == "/result" {
+                    self.take_result(index, body, log)
+                } else {
+                    let failure = String::from_utf8_lossy(body);
+                    log(&format!(
+                        "{} failed: {}",
+                        self.task(index).name(),
+                        failure.trim()
+                    ));
+                    self.fail(index, failure.trim());
+                    Response::text(200, "ok")
+                }
+            }
+            (_, "/health" | "/task" | "/result" | "/failed") => {
+                Response::text(405, "method not allowed")
+            }
+            _ => Response::text(404, "not found"),
+        }
+    }
+
+    /// Count a failure of the band at `index`, handing it out again unless
+    /// it failed too often.
+    fn fail(&mut self, index: usize, failure: &str) {
+        if self.bands[index] == Band::Done {
+            return;
+        }
+        self.failures[index] += 1;
+        self.bands[index] = Band::Waiting;
+        if self.failures[index] >= MAX_FAILURES {
+            self.failed = Some(format!(
+                "{} failed {} times, last with: {}",
+                self.task(index).name(),
+                MAX_FAILURES,
+                failure
+            ));
+        }
+    }
+
+    fn hand_out(&mut self, log: &mut dyn FnMut(&str)) -> Response {
+        for index in 0..self.bands.len() {
+            if matches!(self.bands[index], Band::Leased(since) if since.elapsed() >= self.lease) {
+                log(&format!(
+                    "{} isn't back in time, handing it out again",
+                    self.task(index).name()
+                ));
+                self.fail(index, "not back in time");
+            }
+        }
+        if self.finished() {
+            return Response::text(410, "done");
+        }
+        match self.bands.iter().position(|&band| band == Band::Waiting) {
+            Some(index) => {
+                self.bands[index] = Band::Leased(Instant::now());
+                Response::text(200, &self.task(index).to_query())
+            }
+            None => Response {
+                status: 204,
+                content_type: "text/plain; charset=utf-8",
+                body: Vec::new(),
+            },
+        }
+    }
+
+    fn take_result(&mut self, index: usize, pixels: &[u8], log: &mut dyn FnMut(&str)) -> Response {
+        let task = self.task(index);
+        let expected = task.bounds.0 * task.rows;
+        if pixels.len() != expected {
+            return Response::text(
+                400,
+                &format!(
+                    "{} has {} pixels, not {}",
+                    task.name(),
+                    pixels.len(),
+                    expected
+                ),
+            );
+        }
+        // rendered twice, the second time after it took too long the first
+        if self.bands[index] == Band::Done {
+            return Response::text(200, "ok");
+        }
+        let frame = index / self.frame_bands;
+        let (width, height) = self.job.bounds;
+        let frame_pixels = self
+            .pixels
+            .entry(frame)
+            .or_insert_with(|| vec![0; width * height]);
+        let start = task.top * width;
+        frame_pixels[start..start + expected].copy_from_slice(pixels);
+        self.bands[index] = Band::Done;
+        self.left -= 1;
+        self.frames_left[frame] -= 1;
+        if self.frames_left[frame] == 0 {
+            let pixels = self.pixels.remove(&frame).unwrap_or_default();
+            self.done.push((self.job.frames[frame].number, pixels));
+        }
+        log(&format!(
+            "{} of {} bands done",
+            self.bands.len() - self.left,
+            self.bands.len()
+        ));
+        Response::text(200, "ok")
+    }
+}
+
+#[test]
+fn test_coordinator() {
+    let job = Job {
+        bounds: (10, 5),
+        frames: vec![Frame {
+            number: 0,
+            upper_left: Complex::new(-2.0, 1.0),
+            lower_right: Complex::new(1.0, -1.0),
+            limit: 50,
+        }],
+        strategy: Strategy::Naive,
+        bailout: 2.0,
+        band_rows: 2,
+    };
+    let mut log = |_: &str| {};
+    let mut coordinator = Coordinator::new(job.clone(), Duration::from_secs(60));
+    let mut tasks = Vec::new();
+    for _ in 0..3 {
+        let response = coordinator.handle("GET", "/task", &[], &mut log);
+        assert_eq!(response.status, 200);
+        tasks.push(Task::from_query(&String::from_utf8(response.body).unwrap()).unwrap());
+    }
+    assert_eq!(
+        tasks
+            .iter()
+            .map(|task| (task.top, task.rows))
+            .collect::<Vec<_>>(),
+        [(0, 2), (2, 2), (4, 1)]
+    );
+    // every band is out
+    assert_eq!(
+        coordinator.handle("GET", "/task", &[], &mut log).status,
+        204
+    );
+
+    let result = |coordinator: &mut Coordinator, band, pixels: &[u8]| {
+        let target = format!("/result?band={}", band);
+        coordinator
+            .handle("POST", &target, pixels, &mut |_| {})
+            .status
+    };
+    assert_eq!(result(&mut coordinator, 0, &[1; 20]), 200);
+    assert_eq!(result(&mut coordinator, 0, &[9; 20]), 200);
+    assert_eq!(result(&mut coordinator, 1, &[2; 19]), 400);
+    let short = coordinator.handle("POST", "/result?band=1", &[2; 19], &mut log);
+    assert_eq!(short.body, b"band 2 has 19 pixels, not 20\n");
+    assert_eq!(result(&mut coordinator, 3, &[2; 20]), 400);
+    let framed = coordinator.handle("POST", "/result?frame=0&band=1", &[2; 20], &mut log);
+    assert_eq!(framed.status, 400);
+    let failed = coordinator.handle("POST", "/failed?band=1", b"out of memory", &mut log);
+    assert_eq!(failed.status, 200);
+    // the failed band is handed out again
+    let response = coordinator.handle("GET", "/task", &[], &mut log);
+    assert!(String::from_utf8(response.body)
+        .unwrap()
+        .starts_with("band=1&"));
+    assert_eq!(result(&mut coordinator, 1, &[2; 20]), 200);
+    assert!(!coordinator.finished());
+    assert_eq!(coordinator.take_done(), []);
+    assert_eq!(result(&mut coordinator, 2, &[3; 10]), 200);
+    assert!(coordinator.finished());
+    let done = coordinator.take_done();
+    assert_eq!(done.len(), 1);
+    let (number, pixels) = &done[0];
+    assert_eq!(*number, 0);
+    assert_eq!(pixels[..20], [1; 20]);
+    assert_eq!(pixels[20..40], [2; 20]);
+    assert_eq!(pixels[40..], [3; 10]);
+    assert_eq!(
+        coordinator.handle("GET", "/task", &[], &mut log).status,
+        410
+    );
+    assert_eq!(
+        coordinator.handle("PUT", "/task", &[], &mut log).status,
+        405
+    );
+    assert_eq!(
+        coordinator.handle("GET", "/other", &[], &mut log).status,
+        404
+    );
+    coordinator.into_result().unwrap();
+
+    // bands not back in time are handed out again, until they have timed
+    // out too often
+    let mut coordinator = Coordinator::new(job.clone(), Duration::ZERO);
+    let mut handed_out = Vec::new();
+    loop {
+        let response = coordinator.handle("GET", "/task", &[], &mut log);
+        if response.status != 200 {
+            assert_eq!(response.status, 410);
+            break;
+        }
+        let query = String::from_utf8(response.body).unwrap();
+        handed_out.push(Task::from_query(&query).unwrap().band);
+    }
+    assert_eq!(handed_out, [0, 0, 0]);
+    let failure = coordinator.into_result().unwrap_err().to_string();
+    assert!(failure.contains("band 1 failed 3 times"), "{}", failure);
+
+    // the frames of an animation are handed out one after the other, each
+    // with its own view and limit, and told apart by their numbers
+    let frames: Vec<Frame> = [3, 4]
+        .map(|number| Frame {
+            number,
+            limit: 10 * number,
+            ..job.frames[0].clone()
+        })
+        .into();
+    let mut coordinator = Coordinator::new(Job { frames, ..job }, Duration::from_secs(60));
+    let mut tasks = Vec::new();
+    for _ in 0..6 {
+        let response = coordinator.handle("GET", "/task", &[], &mut log);
+        tasks.push(Task::from_query(&String::from_utf8(response.body).unwrap()).unwrap());
+    }
+    assert_eq!(
+        tasks
+            .iter()
+            .map(|task| (task.frame, task.band, task.limit))
+            .collect::<Vec<_>>(),
+        [
+            (Some(3), 0, 30),
+            (Some(3), 1, 30),
+            (Some(3), 2, 30),
+            (Some(4), 0, 40),
+            (Some(4), 1, 40),
+            (Some(4), 2, 40)
+        ]
+    );
+    let result = |coordinator: &mut Coordinator, target: &str, pixels: &[u8]| {
+        coordinator
+            .handle("POST", target, pixels, &mut |_| {})
+            .status
+    };
+    assert_eq!(result(&mut coordinator, "/result?band=0", &[1; 20]), 400);
+    assert_eq!(
+        result(&mut coordinator, "/result?frame=5&band=0", &[1; 20]),
+        400
+    );
+    for band in 0..3 {
+        let target = format!("/result?frame=4&band={}", band);
+        let pixels = [4; 20][..tasks[3 + band].rows * 10].to_vec();
+        assert_eq!(result(&mut coordinator, &target, &pixels), 200);
+    }
+    // frame 4 is done before frame 3, and only frame 3 is left in memory
+    assert_eq!(coordinator.take_done(), [(4, vec![4; 50])]);
+    assert_eq!(coordinator.pixels.len(), 0);
+    let short = coordinator.handle("POST", "/result?frame=3&band=2", &[3; 20], &mut log);
+    assert_eq!(short.body, b"frame 4 band 3 has 20 pixels, not 10\n");
+    for (band, rows) in [(0, 2), (1, 2), (2, 1)] {
+        let target = format!("/result?frame=3&band={}", band);
+        assert_eq!(result(&mut coordinator, &target, &vec![3; rows * 10]), 200);
+        assert_eq!(coordinator.pixels.len(), usize::from(band < 2));
+    }
+    assert_eq!(coordinator.take_done(), [(3, vec![3; 50])]);
+    assert!(coordinator.finished());
+    coordinator.into_result().unwrap();
+}
+
+/// Answer one connection of a worker.
+fn answer(
+    coordinator: &mut Coordinator,
+    mut stream: TcpStream,
+    log: &mut dyn FnMut(&str),
+) -> io::Result<()> {
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+    let max_body = (coordinator.job.bounds.0 * coordinator.job.band_rows) as u64;
+    let (method, target, body) = server::read_request(&stream, max_body)?;
+    let response = coordinator.handle(&method, &target, &body, log);
+    server::write_response(&mut stream, &response, "", method == "HEAD")
+}
+
+/// Answer the workers connecting to `listener`, one connection at a time,
+/// until `coordinator` has every band back or has failed, handing each frame
+/// to `done` with its number as soon as it is. Errors on single connections
+/// are told to `log`.
+pub fn coordinate(
+    listener: TcpListener,
+    mut coordinator: Coordinator,
+    log: &mut dyn FnMut(&str),
+    done: &mut dyn FnMut(usize, Vec<u8>) -> Result<()>,
+) -> Result<()> {
+    let address = listener
+        .local_addr()
+        .map_or_else(|_| "listener".to_string(), |address| address.to_string());
+    listener
+        .set_nonblocking(true)
+        .map_err(Error::io(&address))?;
+    let mut finished = None;
+    loop {
+        match listener.accept() {
+            Ok((stream, _)) => {
+                if let Err(e) = answer(&mut coordinator, stream, log) {
+                    log(&format!("error serving a connection: {}", e));
+                }
+            }
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                thread::sleep(Duration::from_millis(10))
+            }
+            Err(e) => {
+                log(&format!("error accepting a connection: {}", e));
+                thread::sleep(Duration::from_millis(10))
+            }
+        }
+        for (number, pixels) in coordinator.take_done() {
+            done(number, pixels)?;
+        }
+        if coordinator.finished() && finished.get_or_insert_with(Instant::now).elapsed() >= LINGER {
+            return coordinator.into_result();
+        }
+    }
+}
+
+/// Make a request to the coordinator at `address`, and return the status
+/// and the body of its answer.
+fn request(address: &str, method: &str, target: &str, body: &[u8]) -> io::Result<(u16, Vec<u8>)> {
+    let mut stream = TcpStream::connect(address)?;
+    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+    write!(
+        stream,
+        "{} {} HTTP/1.1\r\nHost: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        method,
+        target,
+        address,
+        body.len()
+    )?;
+    stream.write_all(body)?;
+    stream.flush()?;
+
+    let mut reader = BufReader::new(stream);
+    let mut status_line = String::new();
+    reader.read_line(&mut status_line)?;
+    let status = status_line
+        .split_whitespace()
+        .nth(1)
+        .and_then(|status| status.parse().ok())
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("not an HTTP response: {:?}", status_line.trim()),
+            )
+        })?;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 || header.trim().is_empty() {
+            break;
+        }
+    }
+    let mut body = Vec::new();
+    reader.read_to_end(&mut body)?;
+    Ok((status, body))
+}
+
+/// Render bands for the coordinator at `address` on `threads` threads until
+/// it has none left, of whichever frames, telling `log` about each. Returns how many were
+/// rendered.
+///
+/// A coordinator that can't be reached is tried again for a while; once
+/// bands were rendered for it, one that is gone is taken to be done.
+pub fn work(address: &str, threads: usize, log: &mut dyn FnMut(&str)) -> Result<usize> {
+    // a coordinator that can't be reached is tried again for a while
+    let ask = |method: &str, target: &str, body: &[u8]| {
+        let mut attempts = 1;
+        loop {
+            match request(address, method, target, body) {
+                Err(_) if attempts < CONNECT_ATTEMPTS => {
+                    attempts += 1;
+                    thread::sleep(POLL_INTERVAL);
+                }
+                answer => return answer,
+            }
+        }
+    };
+    let unreachable = |e: io::Error| {
+        Error::Distributed(format!("can't reach the coordinator at {}: {}", address, e))
+    };
+    let unexpected = |status: u16, body: &[u8]| {
+        Error::Distributed(format!(
+            "the coordinator answered {}: {}",
+            status,
+            String::from_utf8_lossy(body).trim()
+        ))
+    };
+    let gone = format!("the coordinator at {} is gone", address);
+
+    let mut rendered = 0;
+    loop {
+        let (status, body) = match ask("GET", "/task", &[]) {
+            Ok(answer) => answer,
+            Err(e) if rendered == 0 => return Err(unreachable(e)),
+            Err(_) => {
+                log(&gone);
+                return Ok(rendered);
+            }
+        };
+        match status {
+            200 => {}
+            204 => {
+                thread::sleep(POLL_INTERVAL);
+                continue;
+            }
+            410 => return Ok(rendered),
+            _ => return Err(unexpected(status, &body)),
+        }
+        let task = Task::from_query(&String::from_utf8_lossy(&body))?;
+        let started = Instant::now();
+        let (target, body) = match panic::catch_unwind(|| task.render(threads)) {
+            Ok(pixels) => (task.target("/result"), pixels),
+            Err(_) => (task.target("/failed"), b"the render panicked".to_vec()),
+        };
+        let (status, answer) = match ask("POST", &target, &body) {
+            Ok(answer) => answer,
+            Err(e) if rendered == 0 => return Err(unreachable(e)),
+            Err(_) => {
+                log(&gone);
+                return Ok(rendered);
+            }
+        };
+        if status != 200 {
+            return Err(unexpected(status, &answer));
+        }
+        rendered += 1;
+        log(&format!(
+            "{} rendered in {:.1}s",
+            task.name(),
+            started.elapsed().as_secs_f64()
+        ));
+    }
+}
+
+#[test]
+fn test_distributed() {
+    let job = Job {
+        bounds: (60, 45),
+        frames: vec![
+            Frame {
+                number: 0,
+                upper_left: Complex::new(-2.0, 1.2),
+                lower_right: Complex::new(0.8, -1.2),
+                limit: 200,
+            },
+            Frame {
+                number: 1,
+                upper_left: Complex::new(-1.0, 0.5),
+                lower_right: Complex::new(-0.5, 0.1),
+                limit: 300,
+            },
+        ],
+        strategy: Strategy::BorderTrace,
+        bailout: 4.0,
+        band_rows: 8,
+    };
+    let expected: Vec<Vec<u8>> = job
+        .frames
+        .iter()
+        .map(|frame| {
+            let mut pixels = vec![0; job.bounds.0 * job.bounds.1];
+            render::render_parallel(
+                job.strategy,
+                &mut pixels,
+                job.bounds,
+                frame.upper_left,
+                frame.lower_right,
+                &render::Schedule {
+                    chunk_rows: job.band_rows,
+                    limit: frame.limit,
+                    bailout: job.bailout,
+                    ..render::Schedule::new(2)
+                },
+            );
+            pixels
+        })
+        .collect();
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    let coordinator = Coordinator::new(job, DEFAULT_LEASE);
+    let coordinating = thread::spawn(move || {
+        let mut frames = vec![Vec::new(); 2];
+        coordinate(listener, coordinator, &mut |_| {}, &mut |number, pixels| {
+            frames[number] = pixels;
+            Ok(())
+        })
+        .map(|()| frames)
+    });
+    let workers: Vec<_> = (0..2)
+        .map(|_| {
+            let address = address.clone();
+            thread::spawn(move || work(&address, 2, &mut |_| {}).unwrap())
+        })
+        .collect();
+    let frames = coordinating.join().unwrap().unwrap();
+    let rendered: usize = workers.into_iter().map(|w| w.join().unwrap()).sum();
+    assert_eq!(rendered, 12);
+    assert!(frames == expected);
+}
@@ -0,0 +1,123 @@
+//! Dithering, for `--dither`: coloring grayscale pixels with the colors of a
+//! palette before they are rounded to 8 bits, and spreading what rounding
+//! leaves off over neighboring pixels, so that gradients changing by less
+//! than a level from one gray level to the next don't merge into bands.
+
+use std::str::FromStr;
+
+/// How the fractions of the levels are spread.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Dither {
+    /// Rounded up or down by a fixed 8x8 pattern of thresholds, a Bayer
+    /// matrix: a regular texture, the same wherever the image is cut.
+    Ordered,
+    /// The error of each pixel carried over to the pixels right of it and
+    /// below, as Floyd and Steinberg do: no pattern, but noisier.
+    FloydSteinberg,
+}
+
+impl FromStr for Dither {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Dither, String> {
+        match s {
+            "ordered" => Ok(Dither::Ordered),
+            "floyd-steinberg" => Ok(Dither::FloydSteinberg),
+            _ => Err(format!("unknown dithering {}", s)),
+        }
+    }
+}
+
+/// The thresholds of the ordered dither, from 0 to 63.
+const BAYER: [[u8; 8]; 8] = [
+    [0, 32, 8, 40, 2, 34, 10, 42],
+    [48, 16, 56, 24, 50, 18, 58, 26],
+    [12, 44, 4, 36, 14, 46, 6, 38],
+    [60, 28, 52, 20, 62, 30, 54, 22],
+    [3, 35, 11, 43, 1, 33, 9, 41],
+    [51, 19, 59, 27, 49, 17, 57, 25],
+    [15, 47, 7, 39, 13, 45, 5, 37],
+    [63, 31, 55, 23, 61, 29, 53, 21],
+];
+
+/// Color the grayscale `pixels`, whose dimensions are given by `bounds`,
+/// with `levels`, the colors of a palette from gray level 0 to 255 before
+/// rounding, into RGB pixels, three bytes each, rounded as `dither` says.
+pub fn apply(
+    pixels: &[u8],
+    bounds: (usize, usize),
+    levels: &[[f64; 3]],
+    dither: Dither,
+) -> Vec<u8> {
+    let last = levels.len().max(1) - 1;
+    let color = |gray: u8| levels[gray as usize * last / 255];
+    let level = |v: f64| v.round().clamp(0.0, 255.0) as u8;
+    match dither {
+        Dither::Ordered => pixels
+            .iter()
+            .enumerate()
+            .flat_map(|(i, &gray)| {
+                let (x, y) = (i % bounds.0, i / bounds.0);
+                let offset = (BAYER[y % 8][x % 8] as f64 + 0.5) / 64.0 - 0.5;
+                color(gray).map(|v| level(v + offset))
+            })
+            .collect(),
+        Dither::FloydSteinberg => {
+            let width = bounds.0;
+            let mut rgb = Vec::with_capacity(3 * pixels.len());
+            // the errors carried into this row and the next, a pixel of
+            // margin on each side
+            let (mut this, mut next) = (vec![[0.0; 3]; width + 2], vec![[0.0; 3]; width + 2]);
+            for row in pixels.chunks(width.max(1)) {
+                for (x, &gray) in row.iter().enumerate() {
+                    let wanted = color(gray);
+                    for channel in 0..3 {
+                        let v = wanted[channel] + this[x + 1][channel];
+                        let out = level(v);
+                        rgb.push(out);
+                        let error = v - out as f64;
+                        this[x + 2][channel] += error * 7.0 / 16.0;
+                        next[x][channel] += error * 3.0 / 16.0;
+                        next[x + 1][channel] += error * 5.0 / 16.0;
+                        next[x + 2][channel] += error / 16.0;
+                    }
+                }
+                this = std::mem::replace(&mut next, vec![[0.0; 3]; width + 2]);
+            }
+            rgb
+        }
+    }
+}
+
+#[test]
+fn test_dither() {
+    assert_eq!("ordered".parse(), Ok(Dither::Ordered));
+    assert_eq!("floyd-steinberg".parse(), Ok(Dither::FloydSteinberg));
+    assert!("random".parse::<Dither>().is_err());
+
+    // every gray level a quarter of the way from 100 to 101
+    let levels = vec![[100.25, 0.0, 255.0]; 256];
+    let bounds = (16, 16);
+    let pixels = vec![7; 16 * 16];
+    for dither in [Dither::Ordered, Dither::FloydSteinberg] {
+        let rgb = apply(&pixels, bounds, &levels, dither);
+        assert_eq!(rgb.len(), 3 * 256);
+        let red: Vec<u8> = rgb.iter().step_by(3).copied().collect();
+        // a quarter of the pixels round up, on average
+        let up = red.iter().filter(|&&r| r == 101).count();
+        assert!(red.iter().all(|&r| r == 100 || r == 101));
+        assert!((up as f64 - 64.0).abs() <= 4.0, "{:?}: {}", dither, up);
+        // whole levels stay as they are
+        assert!(rgb.iter().skip(1).step_by(3).all(|&g| g == 0));
+        assert!(rgb.iter().skip(2).step_by(3).all(|&b| b == 255));
+    }
+
+    // without fractions, the same as the rounded palette
+    let lut = crate::palette::Palette::gray().lut(256);
+    let whole: Vec<[f64; 3]> = lut.iter().map(|c| c.map(|v| v as f64)).collect();
+    let gray: Vec<u8> = (0..=255).collect();
+    assert_eq!(
+        apply(&gray, (16, 16), &whole, Dither::FloydSteinberg),
+        crate::palette::apply(&gray, &lut)
+    );
+}
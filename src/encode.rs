@@ -0,0 +1,636 @@
+//! Rendering a view straight to the bytes of an encoded image, for callers
+//! like the server and the WASM build that never write to the filesystem.
+//!
+//! PNG and GIF images need the `png` feature; PGM images are simple enough
+//! to write without it, so the bare renderer can still hand out a file.
+
+#[cfg(feature = "png")]
+use std::{borrow::Cow, io};
+use std::{
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use num::Complex;
+
+#[cfg(feature = "png")]
+use crate::{output::OutputSink, share};
+use crate::{
+    palette,
+    render::{self, Strategy},
+    Error, Result,
+};
+
+/// The image formats `Renderer::render_to_encoded_bytes` can produce.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Encoding {
+    #[default]
+    Png,
+    Gif,
+    /// A portable graymap, which RGB images are written as too, by
+    /// averaging their channels.
+    Pgm,
+}
+
+impl FromStr for Encoding {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "png" => Ok(Encoding::Png),
+            "gif" => Ok(Encoding::Gif),
+            "pgm" => Ok(Encoding::Pgm),
+            _ => Err(format!("unknown format {:?}, expected png, gif or pgm", s)),
+        }
+    }
+}
+
+impl Encoding {
+    /// The media type of images in this format.
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Encoding::Png => "image/png",
+            Encoding::Gif => "image/gif",
+            Encoding::Pgm => "image/x-portable-graymap",
+        }
+    }
+}
+
+#[test]
+fn test_parse_encoding() {
+    assert_eq!("gif".parse(), Ok(Encoding::Gif));
+    assert_eq!("pgm".parse(), Ok(Encoding::Pgm));
+    assert_eq!("png".parse(), Ok(Encoding::default()));
+    assert!("jpeg".parse::<Encoding>().is_err());
+    assert_eq!(Encoding::Png.content_type(), "image/png");
+}
+
+/// Options for the encoders. Each applies to one format, and the others
+/// ignore it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EncoderOptions {
+    /// PNG: embed the view, so that `share::read_png_view` can read it back.
+    pub embed_view: bool,
+    /// PGM: write the plain, text format (`P2`) rather than the binary one.
+    pub plain: bool,
+}
+
+impl Default for EncoderOptions {
+    fn default() -> EncoderOptions {
+        EncoderOptions {
+            embed_view: true,
+            plain: false,
+        }
+    }
+}
+
+/// Encode the buffer `pixels`, whose dimensions are given by `bounds`, as
+/// an image in `format`. The pixels are grayscale, one byte each, or RGB,
+/// three bytes each, and show the view from `upper_left` to `lower_right`.
+pub fn encode(
+    pixels: &[u8],
+    bounds: (usize, usize),
+    upper_left: Complex<f64>,
+    lower_right: Complex<f64>,
+    format: Encoding,
+    options: &EncoderOptions,
+) -> Result<Vec<u8>> {
+    let rgb = pixels.len() == 3 * bounds.0 * bounds.1 && !pixels.is_empty();
+    match format {
+        Encoding::Pgm => {
+            let grays: Vec<u8> = if rgb {
+                pixels
+                    .chunks(3)
+                    .map(|p| ((p[0] as u16 + p[1] as u16 + p[2] as u16) / 3) as u8)
+                    .collect()
+            } else {
+                pixels.to_vec()
+            };
+            Ok(pgm(&grays, bounds, options.plain))
+        }
+        #[cfg(feature = "png")]
+        Encoding::Png if options.embed_view => {
+            crate::encode_png(pixels, bounds, upper_left, lower_right)
+        }
+        #[cfg(feature = "png")]
+        Encoding::Png => png(pixels, bounds),
+        #[cfg(feature = "png")]
+        Encoding::Gif => {
+            // a still image is an animation of one frame
+            let mut gif = crate::sequence::Encoder::gif(bounds, 1)?;
+            gif.add_frame(pixels)?;
+            gif.finish()
+        }
+        #[cfg(not(feature = "png"))]
+        Encoding::Png | Encoding::Gif => {
+            let _ = (upper_left, lower_right);
+            Err(Error::Usage(format!(
+                "{:?} images need the png feature",
+                format
+            )))
+        }
+    }
+}
+
+/// Encode `pixels` as a PNG image, with nothing but the pixels in it. They
+/// are grayscale, RGB or RGBA, told apart by how many bytes there are.
+#[cfg(feature = "png")]
+pub fn png(pixels: &[u8], bounds: (usize, usize)) -> Result<Vec<u8>> {
+    use image::{png::PNGEncoder, ColorType};
+
+    let area = bounds.0 * bounds.1;
+    let color = match pixels.len() {
+        0 => ColorType::Gray(8),
+        n if n == 3 * area => ColorType::RGB(8),
+        n if n == 4 * area => ColorType::RGBA(8),
+        _ => ColorType::Gray(8),
+    };
+    let mut png = Vec::new();
+    PNGEncoder::new(&mut png)
+        .encode(pixels, bounds.0 as u32, bounds.1 as u32, color)
+        .map_err(Error::Encode)?;
+    Ok(png)
+}
+
+#[cfg(feature = "png")]
+#[test]
+fn test_png() {
+    use image::{ColorType, GenericImage};
+
+    for (pixels, color) in [
+        (vec![0; 2], ColorType::Gray(8)),
+        (vec![0; 6], ColorType::RGB(8)),
+        (vec![0, 0, 0, 0, 9, 9, 9, 255], ColorType::RGBA(8)),
+    ] {
+        let image = image::load_from_memory(&png(&pixels, (2, 1)).unwrap()).unwrap();
+        assert_eq!((image.color(), image.dimensions()), (color, (2, 1)));
+    }
+}
+
+/// How much compressed data goes in each `IDAT` chunk `png_rows` writes.
+#[cfg(feature = "png")]
+const IDAT_SIZE: usize = 1 << 20;
+
+/// Compressed image data on its way to a sink in `IDAT` chunks. The errors
+/// of the sink are wrapped in `io::Error`s, and `sink_error` unwraps them.
+#[cfg(feature = "png")]
+struct Idat<'a> {
+    sink: &'a mut dyn OutputSink,
+    buffer: Vec<u8>,
+}
+
+#[cfg(feature = "png")]
+impl Idat<'_> {
+    fn write_chunk(&mut self) -> io::Result<()> {
+        if self.buffer.is_empty() {
+            return Ok(());
+        }
+        let chunk = share::png_chunk(b"IDAT", &self.buffer);
+        self.buffer.clear();
+        self.sink.write_all(&chunk).map_err(io::Error::other)
+    }
+}
+
+#[cfg(feature = "png")]
+impl io::Write for Idat<'_> {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        self.buffer.extend_from_slice(data);
+        if self.buffer.len() >= IDAT_SIZE {
+            self.write_chunk()?;
+        }
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.write_chunk()
+    }
+}
+
+/// The error `e` of compressing to an `Idat`: the sink's own, if it failed.
+#[cfg(feature = "png")]
+fn sink_error(e: io::Error) -> Error {
+    match e.get_ref().is_some_and(|inner| inner.is::<Error>()) {
+        true => *e
+            .into_inner()
+            .and_then(|inner| inner.downcast::<Error>().ok())
+            .unwrap(),
+        false => Error::Encode(e),
+    }
+}
+
+/// A PNG image written to a sink as its rows come in, top to bottom, so that
+/// rows can be encoded as soon as they are rendered and neither the pixels
+/// nor the image need to be in memory at once.
+///
+/// The rows are grayscale, one byte a pixel, and colored with a lookup table
+/// on the way, if given, as `palette::apply` does.
+#[cfg(feature = "png")]
+pub struct PngWriter<'a> {
+    zlib: deflate::write::ZlibEncoder<Idat<'a>>,
+    bounds: (usize, usize),
+    lut: Option<&'a [[u8; 3]]>,
+    rows: usize,
+    row: Vec<u8>,
+}
+
+#[cfg(feature = "png")]
+impl<'a> PngWriter<'a> {
+    /// Start a PNG image whose dimensions are given by `bounds` on `sink`,
+    /// in color if there is a `lut`, with each `(keyword, text)` of `text`
+    /// in a `tEXt` chunk.
+    pub fn new(
+        sink: &'a mut dyn OutputSink,
+        bounds: (usize, usize),
+        lut: Option<&'a [[u8; 3]]>,
+        text: &[(&str, &str)],
+    ) -> Result<PngWriter<'a>> {
+        use deflate::{write::ZlibEncoder, Compression};
+
+        let mut header = Vec::with_capacity(13);
+        header.extend((bounds.0 as u32).to_be_bytes());
+        header.extend((bounds.1 as u32).to_be_bytes());
+        // 8 bits, gray or RGB, deflated, filtered by rows, not interlaced
+        header.extend([8, if lut.is_some() { 2 } else { 0 }, 0, 0, 0]);
+        let mut start = share::PNG_SIGNATURE.to_vec();
+        start.extend(share::png_chunk(b"IHDR", &header));
+        for (keyword, text) in text {
+            let data = [keyword.as_bytes(), b"\0", text.as_bytes()].concat();
+            start.extend(share::png_chunk(b"tEXt", &data));
+        }
+        sink.write_all(&start)?;
+
+        let idat = Idat {
+            sink,
+            buffer: Vec::with_capacity(IDAT_SIZE),
+        };
+        Ok(PngWriter {
+            zlib: ZlibEncoder::new(idat, Compression::Fast),
+            bounds,
+            lut,
+            rows: 0,
+            row: Vec::new(),
+        })
+    }
+
+    /// How many rows have been written so far.
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    /// Write the next rows of the image, `pixels` holding any number of
+    /// whole rows.
+    pub fn write_rows(&mut self, pixels: &[u8]) -> Result<()> {
+        use std::io::Write;
+
+        let width = self.bounds.0;
+        if pixels.is_empty() {
+            return Ok(());
+        }
+        if width == 0
+            || !pixels.len().is_multiple_of(width)
+            || self.rows + pixels.len() / width > self.bounds.1
+        {
+            return Err(Error::Encode(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "{} pixels aren't whole rows of the {} left of a {}x{} image",
+                    pixels.len(),
+                    self.bounds.1 - self.rows,
+                    width,
+                    self.bounds.1
+                ),
+            )));
+        }
+        let channels = if self.lut.is_some() { 3 } else { 1 };
+        for levels in pixels.chunks(width) {
+            let colored = match self.lut {
+                Some(lut) => Cow::Owned(palette::apply(levels, lut)),
+                None => Cow::Borrowed(levels),
+            };
+            // the Sub filter: each byte less the one a pixel to its left
+            self.row.clear();
+            self.row.push(1);
+            self.row
+                .extend(colored.iter().enumerate().map(|(i, &byte)| match i {
+                    i if i < channels => byte,
+                    i => byte.wrapping_sub(colored[i - channels]),
+                }));
+            self.zlib.write_all(&self.row).map_err(sink_error)?;
+            self.rows += 1;
+        }
+        Ok(())
+    }
+
+    /// End the image, once every row has been written.
+    pub fn finish(self) -> Result<()> {
+        use std::io::Write;
+
+        if self.rows < self.bounds.1 {
+            return Err(Error::Encode(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                format!("{} of {} rows written", self.rows, self.bounds.1),
+            )));
+        }
+        let mut idat = self.zlib.finish().map_err(sink_error)?;
+        idat.flush().map_err(sink_error)?;
+        idat.sink.write_all(&share::png_chunk(b"IEND", &[]))
+    }
+}
+
+/// Encode the grayscale `pixels`, whose dimensions are given by `bounds`, as
+/// a PNG image written to `sink` with a `PngWriter`, so that neither the
+/// pixels, which may be mapped from a file, nor the image need to fit in
+/// memory. The rows are colored with `lut`, if given, and each
+/// `(keyword, text)` of `text` goes in a `tEXt` chunk.
+#[cfg(feature = "png")]
+pub fn png_rows(
+    sink: &mut dyn OutputSink,
+    pixels: &[u8],
+    bounds: (usize, usize),
+    lut: Option<&[[u8; 3]]>,
+    text: &[(&str, &str)],
+) -> Result<()> {
+    let mut writer = PngWriter::new(sink, bounds, lut, text)?;
+    writer.write_rows(pixels)?;
+    writer.finish()
+}
+
+#[cfg(feature = "png")]
+#[test]
+fn test_png_rows() {
+    use image::GenericImage;
+
+    let bounds = (300, 200);
+    let mut pixels = vec![0; bounds.0 * bounds.1];
+    render::render(
+        &mut pixels,
+        bounds,
+        Complex::new(-2.0, 1.0),
+        Complex::new(1.0, -1.0),
+    );
+    let mut png = Vec::new();
+    png_rows(&mut png, &pixels, bounds, None, &[("Comment", "hi")]).unwrap();
+    let image = image::load_from_memory(&png).unwrap();
+    assert_eq!(image.dimensions(), (300, 200));
+    assert_eq!(image.to_luma().into_raw(), pixels);
+    assert_eq!(share::png_text(&png, "Comment").as_deref(), Some("hi"));
+
+    let lut: Vec<[u8; 3]> = (0..=255).map(|level| [level, 255 - level, 7]).collect();
+    let mut png = Vec::new();
+    png_rows(&mut png, &pixels, bounds, Some(&lut), &[]).unwrap();
+    let image = image::load_from_memory(&png).unwrap();
+    assert_eq!(image.to_rgb().into_raw(), palette::apply(&pixels, &lut));
+}
+
+#[cfg(feature = "png")]
+#[test]
+fn test_png_writer() {
+    use image::GenericImage;
+
+    let bounds = (40, 30);
+    let pixels: Vec<u8> = (0..bounds.0 * bounds.1).map(|i| (i % 251) as u8).collect();
+    let mut png = Vec::new();
+    let mut writer = PngWriter::new(&mut png, bounds, None, &[]).unwrap();
+    // bands of any number of rows
+    for band in [&pixels[..40], &pixels[40..400], &pixels[400..]] {
+        writer.write_rows(band).unwrap();
+    }
+    assert_eq!(writer.rows(), 30);
+    assert!(writer.write_rows(&pixels[..40]).is_err());
+    writer.finish().unwrap();
+    let image = image::load_from_memory(&png).unwrap();
+    assert_eq!(image.dimensions(), (40, 30));
+    assert_eq!(image.to_luma().into_raw(), pixels);
+
+    let mut png = Vec::new();
+    let mut writer = PngWriter::new(&mut png, bounds, None, &[]).unwrap();
+    assert!(writer.write_rows(&pixels[..50]).is_err());
+    writer.write_rows(&pixels[..40]).unwrap();
+    assert!(writer.finish().is_err());
+
+    // the sink's own errors come back as they are
+    struct Full(usize);
+    impl OutputSink for Full {
+        fn write_all(&mut self, contents: &[u8]) -> Result<()> {
+            self.0 = self
+                .0
+                .checked_sub(contents.len())
+                .ok_or(Error::Interrupted("full".to_string()))?;
+            Ok(())
+        }
+    }
+    let mut sink = Full(100);
+    let mut writer = PngWriter::new(&mut sink, bounds, None, &[]).unwrap();
+    writer.write_rows(&pixels).unwrap();
+    assert!(matches!(writer.finish(), Err(Error::Interrupted(_))));
+}
+
+/// Encode the grayscale `pixels` as a PGM image.
+fn pgm(pixels: &[u8], bounds: (usize, usize), plain: bool) -> Vec<u8> {
+    let magic = if plain { "P2" } else { "P5" };
+    let mut pgm = format!("{}\n{} {}\n255\n", magic, bounds.0, bounds.1).into_bytes();
+    if !plain {
+        pgm.extend_from_slice(pixels);
+        return pgm;
+    }
+    for row in pixels.chunks(bounds.0.max(1)) {
+        let levels: Vec<String> = row.iter().map(|level| level.to_string()).collect();
+        pgm.extend_from_slice(levels.join(" ").as_bytes());
+        pgm.push(b'\n');
+    }
+    pgm
+}
+
+#[test]
+fn test_pgm() {
+    assert_eq!(
+        pgm(&[0, 128, 255, 7], (2, 2), false),
+        b"P5\n2 2\n255\n\x00\x80\xff\x07"
+    );
+    assert_eq!(
+        pgm(&[0, 128, 255, 7], (2, 2), true),
+        b"P2\n2 2\n255\n0 128\n255 7\n"
+    );
+}
+
+/// A view to render, and how: everything needed to go from coordinates to
+/// the bytes of an image.
+#[derive(Clone, Debug)]
+pub struct Renderer {
+    pub bounds: (usize, usize),
+    pub upper_left: Complex<f64>,
+    pub lower_right: Complex<f64>,
+    pub strategy: Strategy,
+    pub limit: usize,
+    pub options: EncoderOptions,
+    /// Set from another thread to stop the render after the rows in
+    /// progress.
+    pub cancel: Option<Arc<AtomicBool>>,
+    /// How long the render may take before it stops.
+    pub timeout: Option<Duration>,
+    /// The built-in palette the image is colored with, or `None` (or gray)
+    /// for gray levels.
+    pub palette: Option<&'static str>,
+}
+
+impl Renderer {
+    /// Render the view from `upper_left` to `lower_right` into an image
+    /// whose dimensions are given by `bounds`, with the default strategy,
+    /// iteration limit and encoder options.
+    pub fn new(
+        bounds: (usize, usize),
+        upper_left: Complex<f64>,
+        lower_right: Complex<f64>,
+    ) -> Renderer {
+        Renderer {
+            bounds,
+            upper_left,
+            lower_right,
+            strategy: Strategy::Naive,
+            limit: render::DEFAULT_LIMIT,
+            options: EncoderOptions::default(),
+            cancel: None,
+            timeout: None,
+            palette: None,
+        }
+    }
+
+    /// Render the view to grayscale pixels, one byte each. The rows a
+    /// cancelled or timed out render didn't get to are black.
+    pub fn render(&self) -> Vec<u8> {
+        self.render_until_stopped().0
+    }
+
+    /// Render the view, and tell whether it was done before it was stopped.
+    fn render_until_stopped(&self) -> (Vec<u8>, bool) {
+        let mut pixels = vec![0; self.bounds.0 * self.bounds.1];
+        if self.cancel.is_none() && self.timeout.is_none() {
+            render::render_with_limit(
+                self.strategy,
+                &mut pixels,
+                self.bounds,
+                self.upper_left,
+                self.lower_right,
+                self.limit,
+            );
+            return (pixels, true);
+        }
+        // a chunk of rows at a time, to look at the clock and `cancel` in
+        // between, on the calling thread
+        let schedule = render::Schedule {
+            cancel: self.cancel.as_deref(),
+            deadline: self.timeout.map(|timeout| Instant::now() + timeout),
+            limit: self.limit,
+            ..render::Schedule::new(1)
+        };
+        let done = render::render_parallel(
+            self.strategy,
+            &mut pixels,
+            self.bounds,
+            self.upper_left,
+            self.lower_right,
+            &schedule,
+        );
+        (pixels, done)
+    }
+
+    /// Render the view and encode it as an image in `format`, in memory.
+    /// Returns `Error::Interrupted` if it was cancelled or timed out.
+    pub fn render_to_encoded_bytes(&self, format: Encoding) -> Result<Vec<u8>> {
+        let (pixels, done) = self.render_until_stopped();
+        if !done {
+            let cancelled =
+                (self.cancel.as_ref()).is_some_and(|cancel| cancel.load(Ordering::Relaxed));
+            return Err(Error::Interrupted(match self.timeout {
+                Some(timeout) if !cancelled => {
+                    format!("the render took longer than {} s", timeout.as_secs_f64())
+                }
+                _ => "the render was cancelled".to_string(),
+            }));
+        }
+        let pixels = match self.palette.filter(|&name| name != "gray") {
+            Some(name) => {
+                let palette = palette::preset(name)
+                    .ok_or_else(|| Error::Usage(format!("unknown palette {:?}", name)))?;
+                palette::apply(&pixels, &palette.lut(256))
+            }
+            None => pixels,
+        };
+        encode(
+            &pixels,
+            self.bounds,
+            self.upper_left,
+            self.lower_right,
+            format,
+            &self.options,
+        )
+    }
+}
+
+#[cfg(feature = "png")]
+#[test]
+fn test_render_to_encoded_bytes() {
+    use crate::share;
+
+    let (upper_left, lower_right) = (Complex::new(-2.0, 1.0), Complex::new(1.0, -1.0));
+    let mut renderer = Renderer::new((6, 4), upper_left, lower_right);
+
+    let png = renderer.render_to_encoded_bytes(Encoding::Png).unwrap();
+    let view = share::png_text(&png, share::PNG_KEYWORD)
+        .and_then(|link| share::parse_share_link(&link))
+        .unwrap();
+    assert_eq!(view.upper_left, upper_left);
+    let image = image::load_from_memory(&png).unwrap().to_luma();
+    assert_eq!(image.into_raw(), renderer.render());
+
+    renderer.options.embed_view = false;
+    let plain = renderer.render_to_encoded_bytes(Encoding::Png).unwrap();
+    assert!(share::png_text(&plain, share::PNG_KEYWORD).is_none());
+    assert!(plain.len() < png.len());
+
+    let gif = renderer.render_to_encoded_bytes(Encoding::Gif).unwrap();
+    assert!(gif.starts_with(b"GIF89a"));
+
+    let pgm = renderer.render_to_encoded_bytes(Encoding::Pgm).unwrap();
+    assert!(pgm.starts_with(b"P5\n6 4\n255\n"));
+    assert_eq!(pgm[pgm.len() - 24..], renderer.render()[..]);
+
+    renderer.palette = Some("fire");
+    let colored = renderer.render_to_encoded_bytes(Encoding::Png).unwrap();
+    let lut = palette::preset("fire").unwrap().lut(256);
+    let image = image::load_from_memory(&colored).unwrap().to_rgb();
+    assert_eq!(image.into_raw(), palette::apply(&renderer.render(), &lut));
+    renderer.palette = Some("plaid");
+    assert!(renderer.render_to_encoded_bytes(Encoding::Png).is_err());
+}
+
+#[test]
+fn test_render_stopped() {
+    let (upper_left, lower_right) = (Complex::new(-2.0, 1.0), Complex::new(1.0, -1.0));
+    let mut renderer = Renderer::new((60, 40), upper_left, lower_right);
+    let expected = renderer.render_to_encoded_bytes(Encoding::Pgm).unwrap();
+
+    // with time to spare, the same image
+    renderer.timeout = Some(Duration::from_secs(3600));
+    let cancel = Arc::new(AtomicBool::new(false));
+    renderer.cancel = Some(cancel.clone());
+    assert_eq!(
+        renderer.render_to_encoded_bytes(Encoding::Pgm).unwrap(),
+        expected
+    );
+
+    cancel.store(true, Ordering::Relaxed);
+    let error = renderer.render_to_encoded_bytes(Encoding::Pgm).unwrap_err();
+    assert!(matches!(error, Error::Interrupted(_)));
+    assert!(error.to_string().contains("cancelled"), "{}", error);
+    assert!(renderer.render().iter().all(|&p| p == 0));
+
+    renderer.cancel = None;
+    renderer.timeout = Some(Duration::ZERO);
+    let error = renderer.render_to_encoded_bytes(Encoding::Pgm).unwrap_err();
+    assert!(error.to_string().contains("longer than 0 s"), "{}", error);
+}
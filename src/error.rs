@@ -0,0 +1,235 @@
+//! The errors this crate reports, and the exit codes they map to.
+
+use std::io;
+
+use num::Complex;
+
+use crate::job::{MAX_POINTS, MAX_SIDE};
+
+/// Everything that can go wrong between reading the command line and writing
+/// the last output file.
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// The command line doesn't make sense as a whole.
+    #[error("{0}")]
+    Usage(String),
+
+    /// A value couldn't be parsed.
+    #[error("invalid {what}: {value:?}")]
+    Parse { what: &'static str, value: String },
+
+    /// The region of the complex plane to render isn't a proper rectangle.
+    #[error("invalid viewport: {0}")]
+    Viewport(String),
+
+    /// A file couldn't be read or written.
+    #[error("{path}: {source}")]
+    Io { path: String, source: io::Error },
+
+    /// An image couldn't be encoded.
+    #[error("error encoding the image: {0}")]
+    Encode(io::Error),
+
+    /// An image has no view parameters embedded in it.
+    #[error("{0} has no embedded view parameters")]
+    MissingMetadata(String),
+
+    /// The system clipboard couldn't be used.
+    #[error("clipboard: {0}")]
+    Clipboard(io::Error),
+
+    /// The program was asked to stop before it was done.
+    #[error("{0}")]
+    Interrupted(String),
+
+    /// A color script couldn't be compiled, or failed while coloring.
+    #[error("color script: {0}")]
+    Script(String),
+
+    /// The video encoder couldn't be started, or failed.
+    #[error("video: {0}")]
+    Video(String),
+
+    /// A render couldn't be run in its restricted child process, or it
+    /// failed there.
+    #[error("sandboxed render: {0}")]
+    Sandbox(String),
+
+    /// Two images compared differ by more than they may.
+    #[error("images differ: {0}")]
+    Different(String),
+
+    /// Two projects merged change the same settings differently.
+    #[error("merge conflicts: {0}")]
+    Conflict(String),
+
+    /// Some of the jobs of a batch failed.
+    #[error("batch: {0}")]
+    Batch(String),
+
+    /// A render spread over workers failed, or its coordinator couldn't be
+    /// reached.
+    #[error("distributed render: {0}")]
+    Distributed(String),
+
+    /// `validate` found problems in a file.
+    #[error("{0}")]
+    Invalid(String),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Error {
+    /// Build the `Error::Parse` for `value`, which was meant to be a `what`.
+    pub fn parse(what: &'static str, value: &str) -> Error {
+        Error::Parse {
+            what,
+            value: value.to_string(),
+        }
+    }
+
+    /// Build a closure wrapping an `io::Error` about `path` into an `Error::Io`,
+    /// for use with `map_err`.
+    pub fn io(path: &str) -> impl FnOnce(io::Error) -> Error + '_ {
+        move |source| Error::Io {
+            path: path.to_string(),
+            source,
+        }
+    }
+
+    /// The status the program exits with when it fails with this error, so
+    /// that scripts can tell failures apart.
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            Error::Usage(_) => 2,
+            Error::Parse { .. } => 3,
+            Error::Viewport(_) => 4,
+            Error::Io { .. } => 5,
+            Error::Encode(_) => 6,
+            Error::MissingMetadata(_) => 7,
+            Error::Clipboard(_) => 8,
+            Error::Interrupted(_) => 9,
+            Error::Script(_) => 10,
+            Error::Video(_) => 11,
+            Error::Sandbox(_) => 12,
+            Error::Different(_) => 13,
+            Error::Conflict(_) => 14,
+            Error::Batch(_) => 15,
+            Error::Distributed(_) => 16,
+            Error::Invalid(_) => 17,
+        }
+    }
+}
+
+/// Check that `bounds` and the corners `upper_left` and `lower_right` describe
+/// a non-empty image, of at most `MAX_SIDE` pixels a side and `MAX_POINTS`
+/// in all, of a non-empty rectangle of the complex plane.
+pub fn validate_viewport(
+    bounds: (usize, usize),
+    upper_left: Complex<f64>,
+    lower_right: Complex<f64>,
+) -> Result<()> {
+    let corners = [upper_left.re, upper_left.im, lower_right.re, lower_right.im];
+    let pixels = (bounds.0 as u64).checked_mul(bounds.1 as u64);
+    if bounds.0 == 0 || bounds.1 == 0 {
+        Err(Error::Viewport(format!(
+            "the image has no pixels ({}x{})",
+            bounds.0, bounds.1
+        )))
+    } else if bounds.0 > MAX_SIDE
+        || bounds.1 > MAX_SIDE
+        || pixels.is_none_or(|pixels| pixels > MAX_POINTS)
+    {
+        Err(Error::Viewport(format!(
+            "the image has too many pixels ({}x{}), at most {} a side",
+            bounds.0, bounds.1, MAX_SIDE
+        )))
+    } else if corners.iter().any(|c| !c.is_finite()) {
+        Err(Error::Viewport("the corners must be finite".to_string()))
+    } else if lower_right.re < upper_left.re && lower_right.im > upper_left.im {
+        Err(Error::Viewport(format!(
+            "the corners are swapped, give the upper left one ({}) first",
+            lower_right
+        )))
+    } else if lower_right.re == upper_left.re || lower_right.im == upper_left.im {
+        Err(Error::Viewport(format!(
+            "the corners {} and {} are on a line, the view has no area",
+            upper_left, lower_right
+        )))
+    } else if lower_right.re < upper_left.re {
+        Err(Error::Viewport(format!(
+            "the lower right corner ({}) must be right of the upper left corner ({})",
+            lower_right, upper_left
+        )))
+    } else if lower_right.im > upper_left.im {
+        Err(Error::Viewport(format!(
+            "the lower right corner ({}) must be below the upper left corner ({})",
+            lower_right, upper_left
+        )))
+    } else {
+        Ok(())
+    }
+}
+
+#[test]
+fn test_validate_viewport() {
+    let upper_left = Complex { re: -1.0, im: 1.0 };
+    let lower_right = Complex { re: 1.0, im: -1.0 };
+    assert!(validate_viewport((10, 10), upper_left, lower_right).is_ok());
+    assert!(validate_viewport((0, 10), upper_left, lower_right).is_err());
+    assert!(validate_viewport((MAX_SIDE, MAX_SIDE), upper_left, lower_right).is_ok());
+    assert!(validate_viewport((MAX_SIDE + 1, 1), upper_left, lower_right).is_err());
+    let huge = validate_viewport((1 << 32, 1 << 32), upper_left, lower_right);
+    assert!(huge.unwrap_err().to_string().contains("too many pixels"));
+    let message = |upper_left, lower_right| {
+        validate_viewport((10, 10), upper_left, lower_right)
+            .unwrap_err()
+            .to_string()
+    };
+    assert!(message(lower_right, upper_left).contains("swapped"));
+    assert!(message(upper_left, upper_left).contains("no area"));
+    assert!(message(upper_left, Complex { re: 3.0, im: 1.0 }).contains("no area"));
+    assert!(validate_viewport(
+        (10, 10),
+        upper_left,
+        Complex {
+            re: f64::INFINITY,
+            im: -1.0
+        }
+    )
+    .is_err());
+    // flipped vertically only
+    assert!(validate_viewport(
+        (10, 10),
+        Complex { re: -1.0, im: -1.0 },
+        Complex { re: 1.0, im: 1.0 }
+    )
+    .is_err());
+}
+
+#[test]
+fn test_exit_codes_are_distinct() {
+    let errors = [
+        Error::Usage(String::new()),
+        Error::parse("zoom", "x"),
+        Error::Viewport(String::new()),
+        Error::io("f")(io::Error::other("x")),
+        Error::Encode(io::Error::other("x")),
+        Error::MissingMetadata(String::new()),
+        Error::Clipboard(io::Error::other("x")),
+        Error::Interrupted(String::new()),
+        Error::Script(String::new()),
+        Error::Video(String::new()),
+        Error::Sandbox(String::new()),
+        Error::Different(String::new()),
+        Error::Conflict(String::new()),
+        Error::Batch(String::new()),
+        Error::Distributed(String::new()),
+        Error::Invalid(String::new()),
+    ];
+    let mut codes: Vec<u8> = errors.iter().map(Error::exit_code).collect();
+    codes.sort();
+    codes.dedup();
+    assert_eq!(codes.len(), errors.len());
+    assert!(!codes.contains(&0) && !codes.contains(&1));
+}
@@ -0,0 +1,1095 @@
+//! Exploring the set interactively in the terminal, to find a spot worth a
+//! full quality render.
+//!
+//! The preview is drawn with braille characters, 2x4 dots to a character, or
+//! with plain ASCII for terminals whose fonts lack braille. The terminal side
+//! needs the `tui` feature; the rest is always built so it can be tested.
+
+use std::{fmt, str::FromStr};
+
+use num::Complex;
+
+use crate::{
+    cache::{self, DiskCache},
+    limit::MaxIters,
+    palette::{self, Cycle, Palette, Transfer, PRESETS},
+    render::{self, Strategy},
+    units::Zoom,
+};
+
+/// How the preview is drawn.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Preview {
+    Braille,
+    Ascii,
+}
+
+impl Preview {
+    /// How many pixels of the preview fit in one character, across and down.
+    ///
+    /// Characters are about twice as tall as they are wide, so an ASCII
+    /// character covers two pixels down to keep pixels square.
+    fn pixels_per_char(self) -> (usize, usize) {
+        match self {
+            Preview::Braille => (2, 4),
+            Preview::Ascii => (1, 2),
+        }
+    }
+}
+
+impl Preview {
+    /// Draw `pixels`, whose dimensions are given by `bounds`, as lines of
+    /// characters.
+    pub fn draw(self, pixels: &[u8], bounds: (usize, usize)) -> Vec<String> {
+        match self {
+            Preview::Braille => braille(pixels, bounds),
+            Preview::Ascii => ascii(pixels, bounds),
+        }
+    }
+
+    /// The colors of the characters `draw` draws `pixels` with, line by
+    /// line: the color `lut` gives the average gray level of the pixels
+    /// each stands for.
+    pub fn colors(
+        self,
+        pixels: &[u8],
+        bounds: (usize, usize),
+        lut: &[[u8; 3]],
+    ) -> Vec<Vec<[u8; 3]>> {
+        let (across, down) = self.pixels_per_char();
+        (0..bounds.1)
+            .step_by(down)
+            .map(|top| {
+                (0..bounds.0)
+                    .step_by(across)
+                    .map(|left| {
+                        let cell: Vec<usize> = (top..(top + down).min(bounds.1))
+                            .flat_map(|y| {
+                                (left..(left + across).min(bounds.0))
+                                    .map(move |x| pixels[y * bounds.0 + x] as usize)
+                            })
+                            .collect();
+                        lut[cell.iter().sum::<usize>() / cell.len()]
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+}
+
+#[test]
+fn test_colors() {
+    let lut: Vec<[u8; 3]> = (0..=255).map(|level| [level, 0, 0]).collect();
+    #[rustfmt::skip]
+    let pixels = [
+        0, 0, 255, 255, 10,
+        0, 0, 255, 255, 10,
+        0, 0, 255, 255, 10,
+        0, 0, 255, 255, 10,
+    ];
+    assert_eq!(
+        Preview::Braille.colors(&pixels, (5, 4), &lut),
+        [[[0, 0, 0], [255, 0, 0], [10, 0, 0]]]
+    );
+    assert_eq!(Preview::Ascii.colors(&pixels, (5, 4), &lut).len(), 2);
+}
+
+/// A key the explorer answers.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Key {
+    Char(char),
+    Left,
+    Right,
+    Up,
+    Down,
+    Enter,
+    Esc,
+}
+
+impl FromStr for Key {
+    type Err = String;
+
+    /// Parse `left`, `right`, `up`, `down`, `enter`, `esc`, `space` or a
+    /// single character.
+    fn from_str(s: &str) -> Result<Key, String> {
+        let mut chars = s.chars();
+        match (s, chars.next(), chars.next()) {
+            ("left", ..) => Ok(Key::Left),
+            ("right", ..) => Ok(Key::Right),
+            ("up", ..) => Ok(Key::Up),
+            ("down", ..) => Ok(Key::Down),
+            ("enter", ..) => Ok(Key::Enter),
+            ("esc", ..) => Ok(Key::Esc),
+            ("space", ..) => Ok(Key::Char(' ')),
+            (_, Some(c), None) => Ok(Key::Char(c)),
+            _ => Err(format!("unknown key {}", s)),
+        }
+    }
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Key::Char(' ') => write!(f, "space"),
+            Key::Char(c) => write!(f, "{}", c),
+            Key::Left => write!(f, "←"),
+            Key::Right => write!(f, "→"),
+            Key::Up => write!(f, "↑"),
+            Key::Down => write!(f, "↓"),
+            Key::Enter => write!(f, "enter"),
+            Key::Esc => write!(f, "esc"),
+        }
+    }
+}
+
+/// What a key does in the explorer.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Action {
+    PanLeft,
+    PanRight,
+    PanUp,
+    PanDown,
+    ZoomIn,
+    ZoomOut,
+    SwitchPreview,
+    MoreIterations,
+    FewerIterations,
+    NextPalette,
+    NextTransfer,
+    Keyframe,
+    Readout,
+    Quit,
+}
+
+impl FromStr for Action {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Action, String> {
+        match s {
+            "pan-left" => Ok(Action::PanLeft),
+            "pan-right" => Ok(Action::PanRight),
+            "pan-up" => Ok(Action::PanUp),
+            "pan-down" => Ok(Action::PanDown),
+            "zoom-in" => Ok(Action::ZoomIn),
+            "zoom-out" => Ok(Action::ZoomOut),
+            "switch-preview" => Ok(Action::SwitchPreview),
+            "more-iterations" => Ok(Action::MoreIterations),
+            "fewer-iterations" => Ok(Action::FewerIterations),
+            "next-palette" => Ok(Action::NextPalette),
+            "next-transfer" => Ok(Action::NextTransfer),
+            "keyframe" => Ok(Action::Keyframe),
+            "readout" => Ok(Action::Readout),
+            "quit" => Ok(Action::Quit),
+            _ => Err(format!("unknown explore action {}", s)),
+        }
+    }
+}
+
+/// Which keys do what, the ones bound last first.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Keys(Vec<(Key, Action)>);
+
+impl Default for Keys {
+    fn default() -> Keys {
+        Keys(vec![
+            (Key::Left, Action::PanLeft),
+            (Key::Right, Action::PanRight),
+            (Key::Up, Action::PanUp),
+            (Key::Down, Action::PanDown),
+            (Key::Char('+'), Action::ZoomIn),
+            (Key::Char('='), Action::ZoomIn),
+            (Key::Char('-'), Action::ZoomOut),
+            (Key::Char('b'), Action::SwitchPreview),
+            (Key::Char(']'), Action::MoreIterations),
+            (Key::Char('['), Action::FewerIterations),
+            (Key::Char('p'), Action::NextPalette),
+            (Key::Char('t'), Action::NextTransfer),
+            (Key::Char('k'), Action::Keyframe),
+            (Key::Char('r'), Action::Readout),
+            (Key::Char('q'), Action::Quit),
+            (Key::Esc, Action::Quit),
+            (Key::Enter, Action::Quit),
+        ])
+    }
+}
+
+impl Keys {
+    /// Bind a key to an action as `--bind` does, from `ACTION=KEY`; the key
+    /// stops doing whatever it did before, and the action keeps its other
+    /// keys.
+    pub fn bind(&mut self, binding: &str) -> Result<(), String> {
+        let (action, key) = binding
+            .split_once('=')
+            .ok_or_else(|| format!("expected ACTION=KEY, not {}", binding))?;
+        let (action, key): (Action, Key) = (action.parse()?, key.parse()?);
+        self.0.retain(|&(bound, _)| bound != key);
+        self.0.insert(0, (key, action));
+        Ok(())
+    }
+
+    /// What `key` does, if anything.
+    pub fn action(&self, key: Key) -> Option<Action> {
+        self.0
+            .iter()
+            .find(|&&(bound, _)| bound == key)
+            .map(|&(_, action)| action)
+    }
+
+    /// What the keys do, in short, for the status line: the first key of
+    /// each action.
+    pub fn help(&self) -> String {
+        const GROUPS: [(&[Action], &str); 9] = [
+            (
+                &[
+                    Action::PanLeft,
+                    Action::PanRight,
+                    Action::PanUp,
+                    Action::PanDown,
+                ],
+                "pan",
+            ),
+            (&[Action::ZoomIn, Action::ZoomOut], "zoom"),
+            (&[Action::SwitchPreview], "braille/ascii"),
+            (
+                &[Action::MoreIterations, Action::FewerIterations],
+                "iterations",
+            ),
+            (&[Action::NextPalette], "palette"),
+            (&[Action::NextTransfer], "transfer"),
+            (&[Action::Keyframe], "keyframe"),
+            (&[Action::Readout], "readout"),
+            (&[Action::Quit], "quit"),
+        ];
+        let first_key = |action| {
+            self.0
+                .iter()
+                .find(|&&(_, bound)| bound == action)
+                .map(|(key, _)| key.to_string())
+        };
+        GROUPS
+            .iter()
+            .filter_map(|(actions, what)| {
+                let keys: Vec<String> = actions.iter().filter_map(|&a| first_key(a)).collect();
+                (!keys.is_empty()).then(|| format!("{} {}", keys.join("/"), what))
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+#[test]
+fn test_keys() {
+    let mut keys = Keys::default();
+    assert_eq!(keys.action(Key::Char('=')), Some(Action::ZoomIn));
+    assert_eq!(keys.action(Key::Char('x')), None);
+    assert_eq!(
+        keys.help(),
+        "←/→/↑/↓ pan, +/- zoom, b braille/ascii, ]/[ iterations, p palette, t transfer, \
+         k keyframe, r readout, q quit"
+    );
+
+    keys.bind("zoom-in=i").unwrap();
+    keys.bind("pan-left=h").unwrap();
+    keys.bind("quit=b").unwrap();
+    keys.bind("readout=space").unwrap();
+    assert_eq!(keys.action(Key::Char('i')), Some(Action::ZoomIn));
+    assert_eq!(keys.action(Key::Char('+')), Some(Action::ZoomIn));
+    assert_eq!(keys.action(Key::Char('b')), Some(Action::Quit));
+    assert_eq!(
+        keys.help(),
+        "h/→/↑/↓ pan, i/- zoom, ]/[ iterations, p palette, t transfer, k keyframe, \
+         space readout, b quit"
+    );
+
+    assert_eq!("left".parse(), Ok(Key::Left));
+    assert!(keys.bind("zoom-in").is_err());
+    assert!(keys.bind("spin=x").is_err());
+    assert!(keys.bind("quit=ctrl").is_err());
+}
+
+/// How the explorer is shown and controlled, beyond what it shows.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Settings {
+    pub keys: Keys,
+    /// Draw the preview white on black and the status line in reverse
+    /// video.
+    pub high_contrast: bool,
+    /// Jump to a new zoom at once rather than magnifying the preview on
+    /// screen towards it first.
+    pub reduced_motion: bool,
+    /// Write a line describing each view in words instead of drawing it, on
+    /// the main screen where screen readers follow it.
+    pub screen_reader: bool,
+    /// What the preview starts out rendered and colored with.
+    pub tuning: Tuning,
+    /// The configuration file whose `[explore]` tuning is taken up again
+    /// whenever it changes, if any.
+    pub watch: Option<String>,
+}
+
+/// What the preview is rendered and colored with, which keys and the
+/// configuration file change while it is shown: a new iteration limit needs
+/// the view rendered again, a new palette or transfer function only colors
+/// the pixels rendered already again.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Tuning {
+    pub max_iters: MaxIters,
+    /// The palette as `--palette` takes it, drawn in the colors of the
+    /// terminal; without one, the preview is drawn as it always is.
+    pub palette: Option<String>,
+    /// How escape times are laid over the gray levels or the palette.
+    pub transfer: Transfer,
+}
+
+impl Default for Tuning {
+    fn default() -> Tuning {
+        Tuning {
+            max_iters: MaxIters::Fixed(render::DEFAULT_LIMIT),
+            palette: None,
+            transfer: Transfer::Linear,
+        }
+    }
+}
+
+/// The transfer functions `next_transfer` goes through.
+const TRANSFERS: [Transfer; 4] = [
+    Transfer::Linear,
+    Transfer::Sqrt,
+    Transfer::Cbrt,
+    Transfer::Log,
+];
+
+impl Tuning {
+    /// The tuning of the options `args`, as the configuration file gives
+    /// them: `--max-iters`, `--palette` and `--transfer`, the last of each
+    /// winning. Other options don't change while exploring, and are left
+    /// alone.
+    pub fn from_args(args: &[String]) -> Result<Tuning, String> {
+        let mut tuning = Tuning::default();
+        for pair in args.windows(2) {
+            match pair[0].as_str() {
+                "--max-iters" => tuning.max_iters = pair[1].parse()?,
+                "--palette" => tuning.palette = Some(pair[1].clone()),
+                "--transfer" => tuning.transfer = pair[1].parse()?,
+                _ => {}
+            }
+        }
+        Ok(tuning)
+    }
+
+    /// Double the iteration limit `steps` times from `limit`, what the
+    /// current one comes to for the view, or halve it if `steps` is
+    /// negative.
+    pub fn change_limit(&mut self, limit: usize, steps: i32) {
+        let limit = (limit as f64 * 2f64.powi(steps)).round();
+        self.max_iters = MaxIters::Fixed((limit as usize).max(1));
+    }
+
+    /// Go on to the next built-in palette, from none to the first one other
+    /// than gray, and from the last one back to none.
+    pub fn next_palette(&mut self) {
+        let names: Vec<&str> = PRESETS
+            .iter()
+            .map(|(name, _, _)| *name)
+            .filter(|&name| name != "gray")
+            .collect();
+        let at = (self.palette.as_deref())
+            .and_then(|palette| names.iter().position(|&name| name == palette));
+        self.palette = match at {
+            Some(at) => names.get(at + 1).map(|name| name.to_string()),
+            None => Some(names[0].to_string()),
+        };
+    }
+
+    /// Go on to the next of the transfer functions without formulas.
+    pub fn next_transfer(&mut self) {
+        let at = TRANSFERS
+            .iter()
+            .position(|transfer| *transfer == self.transfer);
+        self.transfer = TRANSFERS[at.map_or(0, |at| (at + 1) % TRANSFERS.len())].clone();
+    }
+
+    fn cycle(&self) -> Cycle {
+        Cycle {
+            transfer: self.transfer.clone(),
+            ..Cycle::default()
+        }
+    }
+
+    /// The gray level each gray level rendered is drawn as, laid out by the
+    /// transfer function.
+    pub fn shades(&self) -> Vec<u8> {
+        let lut = Palette::gray().cycled_lut(256, &self.cycle());
+        lut.iter().map(|color| color[0]).collect()
+    }
+
+    /// The color of each gray level rendered, if there is a palette.
+    pub fn lut(&self) -> crate::Result<Option<Vec<[u8; 3]>>> {
+        match &self.palette {
+            Some(name) => Ok(Some(palette::load(name)?.cycled_lut(256, &self.cycle()))),
+            None => Ok(None),
+        }
+    }
+
+    /// The tuning for the status line, the iteration limit being `limit`.
+    pub fn describe(&self, limit: usize) -> String {
+        let transfer = match self.transfer {
+            Transfer::Linear => "linear",
+            Transfer::Sqrt => "sqrt",
+            Transfer::Cbrt => "cbrt",
+            Transfer::Log => "log",
+            Transfer::Custom(_) => "custom",
+        };
+        format!(
+            "iters {}  {} {}",
+            limit,
+            self.palette.as_deref().unwrap_or("gray"),
+            transfer
+        )
+    }
+}
+
+#[test]
+fn test_tuning() {
+    let args = [
+        "--max-iters",
+        "500",
+        "--mirror",
+        "--palette",
+        "fire",
+        "--transfer",
+        "log",
+    ];
+    let tuning = Tuning::from_args(&args.map(String::from)).unwrap();
+    assert_eq!(tuning.max_iters, MaxIters::Fixed(500));
+    assert_eq!(tuning.palette.as_deref(), Some("fire"));
+    assert_eq!(tuning.transfer, Transfer::Log);
+    assert_eq!(tuning.describe(500), "iters 500  fire log");
+    assert_eq!(Tuning::from_args(&[]).unwrap(), Tuning::default());
+    assert!(Tuning::from_args(&["--max-iters", "none"].map(String::from)).is_err());
+
+    let mut tuning = Tuning::default();
+    tuning.change_limit(255, 1);
+    assert_eq!(tuning.max_iters, MaxIters::Fixed(510));
+    tuning.change_limit(1, -1);
+    assert_eq!(tuning.max_iters, MaxIters::Fixed(1));
+
+    // through every palette and back
+    let mut seen = Vec::new();
+    loop {
+        tuning.next_palette();
+        match &tuning.palette {
+            Some(name) => seen.push(name.clone()),
+            None => break,
+        }
+    }
+    assert_eq!(seen.len(), PRESETS.len() - 1);
+    assert_eq!(seen[0], "fire");
+
+    assert_eq!(tuning.shades(), (0..=255).collect::<Vec<u8>>());
+    assert_eq!(tuning.lut().unwrap(), None);
+    tuning.next_transfer();
+    assert_eq!(tuning.transfer, Transfer::Sqrt);
+    // the gray levels in between darken
+    assert!(tuning.shades()[128] < 128);
+    tuning.palette = Some("fire".to_string());
+    assert_eq!(tuning.lut().unwrap().unwrap().len(), 256);
+    tuning.palette = Some("no such palette".to_string());
+    assert!(tuning.lut().is_err());
+    tuning.transfer = "x^2".parse().unwrap();
+    tuning.next_transfer();
+    assert_eq!(tuning.transfer, Transfer::Linear);
+}
+
+/// How many frames the preview on screen goes through as it zooms.
+pub const ZOOM_FRAMES: usize = 4;
+
+/// `pixels`, whose dimensions are given by `bounds`, magnified `factor`
+/// times about the center, or shrunk if `factor` is below 1, leaving what
+/// was off the image blank.
+pub fn zoomed(pixels: &[u8], bounds: (usize, usize), factor: f64) -> Vec<u8> {
+    let center = (bounds.0 as f64 / 2.0, bounds.1 as f64 / 2.0);
+    let mut out = vec![255; pixels.len()];
+    for (i, pixel) in out.iter_mut().enumerate() {
+        let (x, y) = ((i % bounds.0) as f64 + 0.5, (i / bounds.0) as f64 + 0.5);
+        let (from_x, from_y) = (
+            center.0 + (x - center.0) / factor,
+            center.1 + (y - center.1) / factor,
+        );
+        if from_x >= 0.0 && from_y >= 0.0 {
+            let (from_x, from_y) = (from_x as usize, from_y as usize);
+            if from_x < bounds.0 && from_y < bounds.1 {
+                *pixel = pixels[from_y * bounds.0 + from_x];
+            }
+        }
+    }
+    out
+}
+
+#[test]
+fn test_zoomed() {
+    #[rustfmt::skip]
+    let pixels = [
+        0, 1, 2, 3,
+        4, 5, 6, 7,
+        8, 9, 10, 11,
+        12, 13, 14, 15,
+    ];
+    assert_eq!(zoomed(&pixels, (4, 4), 1.0), pixels);
+    #[rustfmt::skip]
+    assert_eq!(zoomed(&pixels, (4, 4), 2.0), [
+        5, 5, 6, 6,
+        5, 5, 6, 6,
+        9, 9, 10, 10,
+        9, 9, 10, 10,
+    ]);
+    #[rustfmt::skip]
+    assert_eq!(zoomed(&pixels, (4, 4), 0.5), [
+        255, 255, 255, 255,
+        255, 5, 7, 255,
+        255, 13, 15, 255,
+        255, 255, 255, 255,
+    ]);
+}
+
+/// Where the explorer is looking.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Explorer {
+    pub center: Complex<f64>,
+    pub zoom: f64,
+}
+
+/// How much the view moves, as a fraction of its width, per arrow key press.
+const PAN_STEP: f64 = 0.1;
+
+/// How much the view is magnified per `+` key press.
+const ZOOM_STEP: f64 = 1.5;
+
+impl Explorer {
+    /// Move the view by `right` and `up` steps; negative values move it the
+    /// other way.
+    pub fn pan(&mut self, right: f64, up: f64) {
+        let step = PAN_STEP * Zoom(self.zoom).width().0;
+        self.center += Complex {
+            re: right * step,
+            im: up * step,
+        };
+    }
+
+    /// Magnify the view by `steps`, or shrink it if `steps` is negative.
+    pub fn zoom_by(&mut self, steps: i32) {
+        self.zoom *= ZOOM_STEP.powi(steps);
+    }
+
+    /// The command line rendering the current view at full quality.
+    pub fn command_line(&self, program: &str) -> String {
+        format!(
+            "{} mandelbrot.png 1600x1200 --center {},{} --zoom {}",
+            program, self.center.re, self.center.im, self.zoom
+        )
+    }
+
+    /// The dimensions and the corners of a preview of the view filling
+    /// `columns` by `rows` characters.
+    pub fn view(
+        &self,
+        preview: Preview,
+        columns: usize,
+        rows: usize,
+    ) -> ((usize, usize), Complex<f64>, Complex<f64>) {
+        let (across, down) = preview.pixels_per_char();
+        let bounds = (columns * across, rows * down);
+        let (upper_left, lower_right) =
+            render::corners_from_center(self.center, Zoom(self.zoom), bounds);
+        (bounds, upper_left, lower_right)
+    }
+
+    /// Render a preview of the view filling `columns` by `rows` characters,
+    /// iterating at most `limit` times, unless `cache` has its pixels
+    /// already.
+    pub fn preview(
+        &self,
+        preview: Preview,
+        strategy: Strategy,
+        limit: usize,
+        columns: usize,
+        rows: usize,
+        cache: Option<&DiskCache>,
+    ) -> Vec<String> {
+        let (bounds, pixels) = self.pixels(preview, strategy, limit, columns, rows, cache);
+        preview.draw(&pixels, bounds)
+    }
+
+    /// The dimensions and the pixels of the preview `preview` draws.
+    pub fn pixels(
+        &self,
+        preview: Preview,
+        strategy: Strategy,
+        limit: usize,
+        columns: usize,
+        rows: usize,
+        cache: Option<&DiskCache>,
+    ) -> ((usize, usize), Vec<u8>) {
+        let (bounds, upper_left, lower_right) = self.view(preview, columns, rows);
+
+        let schedule = render::Schedule {
+            limit,
+            ..render::Schedule::new(num_cpus::get())
+        };
+        let key = cache::Key {
+            bounds,
+            upper_left,
+            lower_right,
+            fractal: "mandelbrot",
+            limit: schedule.limit,
+            strategy,
+            palette: "gray",
+            format: "gray",
+        };
+        let cached = cache
+            .and_then(|cache| cache.get(&key))
+            .filter(|pixels| pixels.len() == bounds.0 * bounds.1);
+        let pixels = cached.unwrap_or_else(|| {
+            let mut pixels = vec![0; bounds.0 * bounds.1];
+            render::render_parallel(
+                strategy,
+                &mut pixels,
+                bounds,
+                upper_left,
+                lower_right,
+                &schedule,
+            );
+            // a preview is worth showing even if it can't be cached
+            if let Some(cache) = cache {
+                let _ = cache.put(&key, &pixels);
+            }
+            pixels
+        });
+        (bounds, pixels)
+    }
+
+    /// The view in words, for screen readers: where it is, how magnified,
+    /// and how much of its preview `pixels` is in the set. The coordinates
+    /// have as many decimals as the zoom needs to tell views apart.
+    pub fn readout(&self, pixels: &[u8]) -> String {
+        let inside = pixels.iter().filter(|&&pixel| pixel == 0).count();
+        let decimals = self.zoom.log10().max(0.0) as usize + 3;
+        format!(
+            "center {:.*} real, {:.*} imaginary, zoom {:.1} times, {}% in the set",
+            decimals,
+            self.center.re,
+            decimals,
+            self.center.im,
+            self.zoom,
+            (inside as f64 * 100.0 / pixels.len().max(1) as f64).round()
+        )
+    }
+}
+
+#[test]
+fn test_explorer() {
+    let mut explorer = Explorer {
+        center: Complex { re: -0.5, im: 0.0 },
+        zoom: 1.0,
+    };
+    let close = |a: Complex<f64>, b: Complex<f64>| (a - b).norm() < 1e-12;
+    explorer.pan(1.0, -2.0);
+    assert!(close(explorer.center, Complex { re: -0.1, im: -0.8 }));
+    explorer.zoom_by(2);
+    assert_eq!(explorer.zoom, 2.25);
+    // steps shrink with the view
+    explorer.pan(0.0, 2.25);
+    assert!(close(explorer.center, Complex { re: -0.1, im: -0.4 }));
+    explorer.zoom_by(-2);
+    assert_eq!(explorer.zoom, 1.0);
+
+    let limit = render::DEFAULT_LIMIT;
+    let lines = explorer.preview(Preview::Braille, Strategy::Naive, limit, 20, 5, None);
+    assert_eq!(lines.len(), 5);
+    assert!(lines.iter().all(|line| line.chars().count() == 20));
+
+    // the same preview comes out of the cache
+    let dir = std::env::temp_dir().join(format!("mandelbrot-explore-{}", std::process::id()));
+    let cache = DiskCache::new(&dir, cache::DEFAULT_MAX_BYTES);
+    let preview = |cache| explorer.preview(Preview::Braille, Strategy::Naive, limit, 20, 5, cache);
+    assert_eq!(preview(Some(&cache)), lines);
+    assert_eq!(cache.usage().unwrap().tiles, 1);
+    assert_eq!(preview(Some(&cache)), lines);
+    std::fs::remove_dir_all(&dir).unwrap();
+    let (bounds, upper_left, lower_right) = explorer.view(Preview::Braille, 20, 5);
+    assert_eq!(bounds, (40, 20));
+    assert!(close((upper_left + lower_right) / 2.0, explorer.center));
+
+    let explorer = Explorer {
+        center: Complex::new(-0.75, 0.1),
+        zoom: 2500.0,
+    };
+    assert_eq!(
+        explorer.readout(&[0, 0, 255, 17]),
+        "center -0.750000 real, 0.100000 imaginary, zoom 2500.0 times, 50% in the set"
+    );
+}
+
+/// Whether a pixel is drawn, rather than left blank: points in the set and
+/// near it, which take a while to escape.
+fn is_dark(pixel: u8) -> bool {
+    pixel < 224
+}
+
+/// Draw `pixels`, whose dimensions are given by `bounds`, as lines of
+/// braille characters, each standing for 2x4 pixels.
+pub fn braille(pixels: &[u8], bounds: (usize, usize)) -> Vec<String> {
+    // the bit of each dot in a braille character, by row and column
+    const DOTS: [[u32; 2]; 4] = [[0x01, 0x08], [0x02, 0x10], [0x04, 0x20], [0x40, 0x80]];
+
+    let mut lines = Vec::new();
+    for top in (0..bounds.1).step_by(4) {
+        let mut line = String::new();
+        for left in (0..bounds.0).step_by(2) {
+            let mut bits = 0;
+            for (dy, row) in DOTS.iter().enumerate() {
+                for (dx, bit) in row.iter().enumerate() {
+                    let (x, y) = (left + dx, top + dy);
+                    if x < bounds.0 && y < bounds.1 && is_dark(pixels[y * bounds.0 + x]) {
+                        bits |= bit;
+                    }
+                }
+            }
+            line.push(char::from_u32(0x2800 + bits).unwrap());
+        }
+        lines.push(line);
+    }
+    lines
+}
+
+#[test]
+fn test_braille() {
+    #[rustfmt::skip]
+    let pixels = [
+        0, 255, 255, 255,
+        0, 255, 255, 255,
+        0, 255, 255, 0,
+        0, 0, 255, 0,
+    ];
+    assert_eq!(braille(&pixels, (4, 4)), ["⣇⢠"]);
+    assert_eq!(braille(&[255; 6], (3, 2)), ["⠀⠀"]);
+}
+
+/// Draw `pixels`, whose dimensions are given by `bounds`, as lines of ASCII
+/// characters, each standing for two pixels one above the other.
+pub fn ascii(pixels: &[u8], bounds: (usize, usize)) -> Vec<String> {
+    const RAMP: &[u8] = b" .:-=+*#%@";
+
+    let mut lines = Vec::new();
+    for top in (0..bounds.1).step_by(2) {
+        let line = (0..bounds.0)
+            .map(|x| {
+                let below = pixels.get((top + 1) * bounds.0 + x);
+                let pair = [Some(&pixels[top * bounds.0 + x]), below];
+                let darkness: usize = pair.iter().flatten().map(|&&p| 255 - p as usize).sum();
+                let darkness = darkness / pair.iter().flatten().count();
+                RAMP[darkness * (RAMP.len() - 1) / 255] as char
+            })
+            .collect();
+        lines.push(line);
+    }
+    lines
+}
+
+#[test]
+fn test_ascii() {
+    assert_eq!(ascii(&[255, 0, 255, 0], (2, 2)), [" @"]);
+    assert_eq!(ascii(&[255, 0, 128], (3, 1)), [" @="]);
+}
+
+/// Explore the set in the terminal, starting from `explorer`, until the user
+/// quits; returns where they were looking then.
+///
+/// By default arrow keys pan, `+` and `-` zoom, `b` switches between braille
+/// and ASCII, `]` and `[` double and halve the iteration limit, `p` and `t`
+/// go through the palettes and transfer functions, `k` appends the view as
+/// a keyframe to the scene file `scene`, `r` describes the view in words,
+/// and `q`, Escape or Enter quit; `settings` can bind other keys. The tuning
+/// is taken up again from the file `settings` watches whenever it changes.
+/// Every view shown is added to the log of visited regions `visited`, if
+/// given, see `coverage`, and is looked up in `cache` before it is rendered.
+#[cfg(feature = "tui")]
+pub fn run(
+    mut explorer: Explorer,
+    mut preview: Preview,
+    strategy: Strategy,
+    scene: Option<&str>,
+    visited: Option<&str>,
+    cache: Option<&DiskCache>,
+    settings: &Settings,
+) -> std::io::Result<Explorer> {
+    use std::{fs, io::Write, thread, time::Duration};
+
+    use crate::{
+        animation::{self, Keyframe},
+        config::Config,
+        platform,
+    };
+
+    use crossterm::{
+        cursor,
+        event::{self, Event, KeyCode, KeyEventKind},
+        execute, queue,
+        style::{self, Attribute, Color},
+        terminal,
+    };
+
+    /// Puts the terminal back the way it was, even if drawing fails.
+    struct Restore {
+        screen_reader: bool,
+    }
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            if !self.screen_reader {
+                let _ = execute!(
+                    std::io::stdout(),
+                    style::ResetColor,
+                    cursor::Show,
+                    terminal::LeaveAlternateScreen
+                );
+            }
+            let _ = terminal::disable_raw_mode();
+        }
+    }
+
+    /// Draw `lines`, in `colors` if given, and the status line under them.
+    fn draw(
+        out: &mut std::io::Stdout,
+        lines: &[String],
+        colors: Option<&[Vec<[u8; 3]>]>,
+        status: &str,
+        high_contrast: bool,
+    ) -> std::io::Result<()> {
+        if high_contrast {
+            queue!(
+                out,
+                style::SetForegroundColor(Color::White),
+                style::SetBackgroundColor(Color::Black)
+            )?;
+        }
+        queue!(out, terminal::Clear(terminal::ClearType::All))?;
+        for (row, line) in lines.iter().enumerate() {
+            queue!(out, cursor::MoveTo(0, row as u16))?;
+            match colors {
+                Some(colors) => {
+                    for (c, &[r, g, b]) in line.chars().zip(&colors[row]) {
+                        queue!(
+                            out,
+                            style::SetForegroundColor(Color::Rgb { r, g, b }),
+                            style::Print(c)
+                        )?;
+                    }
+                    queue!(out, style::ResetColor)?;
+                }
+                None => queue!(out, style::Print(line))?,
+            }
+        }
+        queue!(out, cursor::MoveTo(0, lines.len() as u16))?;
+        if high_contrast {
+            queue!(
+                out,
+                style::SetAttribute(Attribute::Reverse),
+                style::SetAttribute(Attribute::Bold),
+                style::Print(status),
+                style::SetAttribute(Attribute::Reset)
+            )?;
+        } else {
+            queue!(out, style::Print(status))?;
+        }
+        out.flush()
+    }
+
+    terminal::enable_raw_mode()?;
+    let _restore = Restore {
+        screen_reader: settings.screen_reader,
+    };
+    let mut out = std::io::stdout();
+    if !settings.screen_reader {
+        execute!(out, terminal::EnterAlternateScreen, cursor::Hide)?;
+    }
+
+    /// When `file` was last changed, if it is there.
+    fn modified(file: &str) -> Option<std::time::SystemTime> {
+        fs::metadata(platform::path(file))
+            .and_then(|metadata| metadata.modified())
+            .ok()
+    }
+
+    // shown instead of the help until the next key
+    let mut message = None;
+    let mut last_visited = None;
+    let mut tuning = settings.tuning.clone();
+    let mut last_modified = settings.watch.as_deref().and_then(modified);
+    // the view last rendered and its pixels, which colors alone don't
+    // change
+    let mut rendered = None;
+    loop {
+        let (columns, rows) = terminal::size()?;
+        let (columns, rows) = (columns.max(1) as usize, rows.max(2) as usize);
+        let (bounds, upper_left, lower_right) = explorer.view(preview, columns, rows - 1);
+        let limit = tuning.max_iters.resolve(bounds, upper_left, lower_right);
+        let view = (bounds, upper_left, lower_right, limit);
+        let pixels = match rendered.take() {
+            Some((shown, pixels)) if shown == view => pixels,
+            _ => {
+                explorer
+                    .pixels(preview, strategy, limit, columns, rows - 1, cache)
+                    .1
+            }
+        };
+        let lut = tuning.lut().unwrap_or_else(|e| {
+            message = Some(format!("error: {}", e));
+            tuning.palette = None;
+            None
+        });
+        let shades = tuning.shades();
+        let shaded: Vec<u8> = pixels.iter().map(|&pixel| shades[pixel as usize]).collect();
+        if let Some(visited) = visited {
+            if last_visited != Some((upper_left, lower_right)) {
+                last_visited = Some((upper_left, lower_right));
+                if let Err(e) = crate::coverage::record(visited, upper_left, lower_right) {
+                    message = Some(format!("error: {}", e));
+                }
+            }
+        }
+
+        let note = message.take();
+        let status = format!(
+            "center {},{}  zoom {:.3}  {}  {}",
+            explorer.center.re,
+            explorer.center.im,
+            explorer.zoom,
+            tuning.describe(limit),
+            note.clone().unwrap_or_else(|| settings.keys.help())
+        );
+        let status: String = status.chars().take(columns).collect();
+        if settings.screen_reader {
+            // a line a view, on the main screen, which screen readers read
+            // as it comes
+            let line = note.unwrap_or_else(|| explorer.readout(&pixels));
+            queue!(out, style::Print(line), style::Print("\r\n"))?;
+            out.flush()?;
+        }
+
+        // the preview is drawn in the colors of the palette, but for high
+        // contrast
+        let lut = lut.filter(|_| !settings.high_contrast);
+        let shown = preview;
+        let mut show = |pixels: &[u8]| -> std::io::Result<()> {
+            let lines = shown.draw(pixels, bounds);
+            let colors = (lut.as_ref()).map(|lut| shown.colors(pixels, bounds, lut));
+            draw(
+                &mut out,
+                &lines,
+                colors.as_deref(),
+                &status,
+                settings.high_contrast,
+            )
+        };
+        if !settings.screen_reader {
+            show(&shaded)?;
+        }
+
+        // magnify or shrink the preview on screen towards the next view
+        let mut zoom = |steps: i32| -> std::io::Result<()> {
+            if settings.reduced_motion || settings.screen_reader {
+                return Ok(());
+            }
+            for frame in 1..=ZOOM_FRAMES {
+                let factor = ZOOM_STEP.powf(steps as f64 * frame as f64 / ZOOM_FRAMES as f64);
+                show(&zoomed(&shaded, bounds, factor))?;
+                thread::sleep(Duration::from_millis(20));
+            }
+            Ok(())
+        };
+
+        // wait for a key that changes something, or for the watched file
+        // to; resizes redraw too
+        loop {
+            if !event::poll(Duration::from_millis(250))? {
+                let Some(file) = settings.watch.as_deref() else {
+                    continue;
+                };
+                let now = modified(file);
+                if now.is_none() || now == last_modified {
+                    continue;
+                }
+                last_modified = now;
+                let reloaded = Config::open(file)
+                    .and_then(|config| {
+                        Tuning::from_args(&config.to_args("explore")).map_err(crate::Error::Usage)
+                    })
+                    .and_then(|reloaded| reloaded.lut().map(|_| reloaded));
+                message = Some(match reloaded {
+                    Ok(reloaded) => {
+                        tuning = reloaded;
+                        format!("tuning taken up from {}", file)
+                    }
+                    Err(e) => format!("error: {}", e),
+                });
+                break;
+            }
+            match event::read()? {
+                Event::Key(key) if key.kind != KeyEventKind::Release => {
+                    let key = match key.code {
+                        KeyCode::Char(c) => Key::Char(c),
+                        KeyCode::Left => Key::Left,
+                        KeyCode::Right => Key::Right,
+                        KeyCode::Up => Key::Up,
+                        KeyCode::Down => Key::Down,
+                        KeyCode::Enter => Key::Enter,
+                        KeyCode::Esc => Key::Esc,
+                        _ => continue,
+                    };
+                    match settings.keys.action(key) {
+                        Some(Action::PanLeft) => explorer.pan(-1.0, 0.0),
+                        Some(Action::PanRight) => explorer.pan(1.0, 0.0),
+                        Some(Action::PanUp) => explorer.pan(0.0, 1.0),
+                        Some(Action::PanDown) => explorer.pan(0.0, -1.0),
+                        Some(Action::ZoomIn) => {
+                            zoom(1)?;
+                            explorer.zoom_by(1);
+                        }
+                        Some(Action::ZoomOut) => {
+                            zoom(-1)?;
+                            explorer.zoom_by(-1);
+                        }
+                        Some(Action::SwitchPreview) => {
+                            preview = match preview {
+                                Preview::Braille => Preview::Ascii,
+                                Preview::Ascii => Preview::Braille,
+                            }
+                        }
+                        Some(Action::MoreIterations) => tuning.change_limit(limit, 1),
+                        Some(Action::FewerIterations) => tuning.change_limit(limit, -1),
+                        Some(Action::NextPalette) => tuning.next_palette(),
+                        Some(Action::NextTransfer) => tuning.next_transfer(),
+                        Some(Action::Keyframe) => {
+                            let keyframe = Keyframe::new(explorer.center, explorer.zoom);
+                            message = Some(match scene {
+                                Some(scene) => match animation::append_keyframe(scene, keyframe) {
+                                    Ok(count) => format!("keyframe {} added to {}", count, scene),
+                                    Err(e) => format!("error: {}", e),
+                                },
+                                None => "give --scene FILE to collect keyframes".to_string(),
+                            });
+                        }
+                        Some(Action::Readout) => message = Some(explorer.readout(&pixels)),
+                        Some(Action::Quit) => return Ok(explorer),
+                        None => continue,
+                    }
+                }
+                Event::Resize(..) => {}
+                _ => continue,
+            }
+            break;
+        }
+        rendered = Some((view, pixels));
+    }
+}
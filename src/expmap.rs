@@ -0,0 +1,172 @@
+//! Zoom videos from an exponential map, for `animate --exp-map`.
+//!
+//! When every frame of a scene looks at the same center, zooming in is
+//! moving down an exponential map of the plane around it, see
+//! `render::exp_map_to_point`: a strip going once around the center across,
+//! and in towards it down, each row a little closer than the one above in
+//! logarithm. The strip is rendered once, from the corners of the widest
+//! frame to the pixels at the center of the narrowest, and every frame is
+//! then taken from it by interpolation. Each doubling of the zoom costs as
+//! many rows of the strip, whatever the number of frames it spans, so
+//! smooth videos with many frames per doubling cost a fraction of rendering
+//! every frame.
+
+use num::Complex;
+
+use crate::{
+    animation::Frame,
+    render::{self, Schedule},
+};
+
+/// An exponential map of the plane around a center.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Strip {
+    pub center: Complex<f64>,
+    /// The natural logarithm of the distance of the first row from the
+    /// center.
+    pub log_radius: f64,
+    pub bounds: (usize, usize),
+    /// Grayscale, one byte a pixel, as `render::render` draws them.
+    pub pixels: Vec<u8>,
+}
+
+impl Strip {
+    /// The strip the frames `frames` can be taken from, rendered into
+    /// images whose dimensions are given by `bounds`, not rendered yet: as
+    /// many columns as the corners of the frames need to be as sharp as
+    /// their pixels, and rows from the corners of the widest frame to half a
+    /// pixel from the center of the narrowest. `None` if the frames don't
+    /// all look at the same center, to within half a pixel.
+    pub fn plan(frames: &[Frame], bounds: (usize, usize)) -> Option<Strip> {
+        let first = frames.first()?;
+        let pixel_size = |frame: &Frame| frame.zoom.pixel_size(bounds.0).0;
+        let narrowest = frames.iter().map(pixel_size).fold(f64::INFINITY, f64::min);
+        let widest = frames.iter().map(pixel_size).fold(0.0, f64::max);
+        if frames
+            .iter()
+            .any(|frame| (frame.center - first.center).norm() > narrowest / 2.0)
+        {
+            return None;
+        }
+        // a pixel of the strip at the corners of a frame is one of its
+        // pixels, and smaller further in
+        let half_diagonal = (bounds.0 as f64).hypot(bounds.1 as f64) / 2.0;
+        let columns = (std::f64::consts::TAU * half_diagonal).ceil() as usize;
+        let step = std::f64::consts::TAU / columns as f64;
+        let log_radius = (widest * half_diagonal).ln();
+        let log_inner = (narrowest / 2.0).ln();
+        let rows = ((log_radius - log_inner) / step).ceil() as usize + 1;
+        Some(Strip {
+            center: first.center,
+            log_radius,
+            bounds: (columns, rows),
+            pixels: Vec::new(),
+        })
+    }
+
+    /// Render the strip as `schedule` says, see `render::render_mapped`.
+    /// Returns false if it was stopped before every row was rendered.
+    pub fn render(&mut self, schedule: &Schedule) -> bool {
+        let (columns, center, log_radius) = (self.bounds.0, self.center, self.log_radius);
+        let point = |pixel| render::exp_map_to_point(columns, pixel, center, log_radius);
+        self.pixels = vec![0; self.bounds.0 * self.bounds.1];
+        render::render_mapped(&mut self.pixels, self.bounds, &point, schedule)
+    }
+
+    /// The view from `upper_left` to `lower_right`, whose dimensions are
+    /// given by `bounds`, taken from the strip: each pixel interpolated
+    /// between the four of the strip around its point, the pixels closer to
+    /// the center than the last row taking its gray levels.
+    pub fn reproject(
+        &self,
+        bounds: (usize, usize),
+        upper_left: Complex<f64>,
+        lower_right: Complex<f64>,
+    ) -> Vec<u8> {
+        let (columns, rows) = self.bounds;
+        let step = std::f64::consts::TAU / columns as f64;
+        let at = |x: usize, y: usize| self.pixels[y * columns + x % columns] as f64;
+        let mut pixels = Vec::with_capacity(bounds.0 * bounds.1);
+        for y in 0..bounds.1 {
+            for x in 0..bounds.0 {
+                let offset =
+                    render::pixel_to_point(bounds, (x, y), upper_left, lower_right) - self.center;
+                let across = offset.arg().rem_euclid(std::f64::consts::TAU) / step;
+                let down =
+                    ((self.log_radius - offset.norm().ln()) / step).clamp(0.0, (rows - 1) as f64);
+                let (left, top) = (across.floor(), down.floor());
+                let (dx, dy) = (across - left, down - top);
+                let (left, top) = (left as usize, top as usize);
+                let bottom = (top + 1).min(rows - 1);
+                let upper = at(left, top) * (1.0 - dx) + at(left + 1, top) * dx;
+                let lower = at(left, bottom) * (1.0 - dx) + at(left + 1, bottom) * dx;
+                pixels.push((upper * (1.0 - dy) + lower * dy).round() as u8);
+            }
+        }
+        pixels
+    }
+}
+
+#[test]
+fn test_strip() {
+    use crate::animation::Scene;
+
+    let bounds = (80, 60);
+    let scene = Scene::from_json(
+        r#"{"keyframes": [{"center": [-0.7435, 0.1314], "zoom": 20},
+            {"center": [-0.7435, 0.1314], "zoom": 320}]}"#,
+    )
+    .unwrap();
+    let frames = scene.frames(24, 25);
+    let mut strip = Strip::plan(&frames, bounds).unwrap();
+    // π times the diagonal around, and down 231 rows from the corners of a
+    // frame to half a pixel from its center, and 35 rows for each of the
+    // nearly 4 doublings of the zoom
+    assert_eq!(strip.bounds, (315, 371));
+    assert!(strip.render(&Schedule::new(2)));
+
+    // every frame closer to rendering it than rendering it half a pixel
+    // off, which is how far apart aliasing alone sets them
+    let render = |(upper_left, lower_right)| {
+        let mut pixels = vec![0; bounds.0 * bounds.1];
+        render::render(&mut pixels, bounds, upper_left, lower_right);
+        pixels
+    };
+    let difference = |a: &[u8], b: &[u8]| {
+        let sum: f64 = a
+            .iter()
+            .zip(b)
+            .map(|(&a, &b)| (a as f64 - b as f64).abs())
+            .sum();
+        sum / a.len() as f64
+    };
+    for frame in [&frames[0], &frames[11], &frames[23]] {
+        let (upper_left, lower_right) = frame.corners(bounds);
+        let expected = render((upper_left, lower_right));
+        let pixels = strip.reproject(bounds, upper_left, lower_right);
+        let half = Complex::new(
+            (lower_right.re - upper_left.re) / (2 * bounds.0) as f64,
+            (lower_right.im - upper_left.im) / (2 * bounds.1) as f64,
+        );
+        let shifted = render((upper_left + half, lower_right + half));
+        let (error, alias) = (
+            difference(&pixels, &expected),
+            difference(&shifted, &expected),
+        );
+        assert!(
+            error < alias,
+            "zoom {}: {} vs {}",
+            frame.zoom.0,
+            error,
+            alias
+        );
+    }
+
+    // a center that moves can't be mapped
+    let moving = Scene::from_json(
+        r#"{"keyframes": [{"center": [-0.5, 0], "zoom": 1}, {"center": [-0.75, 0.1], "zoom": 4}]}"#,
+    )
+    .unwrap();
+    assert!(Strip::plan(&moving.frames(4, 25), bounds).is_none());
+    assert!(Strip::plan(&[], bounds).is_none());
+}
@@ -0,0 +1,133 @@
+//! Writing OpenEXR images, for `--layers-exr`: one file holding any number
+//! of float channels, which compositors such as Nuke and Krita group into
+//! layers by the part of their names before the last dot, `exterior.R`
+//! going into the `exterior` layer.
+//!
+//! Only what that needs is written: a single part, uncompressed, one
+//! scanline a chunk, 32-bit float channels.
+
+/// A channel of an image, one value a pixel, row by row.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Channel {
+    pub name: String,
+    pub values: Vec<f32>,
+}
+
+impl Channel {
+    pub fn new(name: &str, values: Vec<f32>) -> Channel {
+        Channel {
+            name: name.to_string(),
+            values,
+        }
+    }
+}
+
+/// Every OpenEXR file starts with this.
+const MAGIC: [u8; 4] = [0x76, 0x2f, 0x31, 0x01];
+
+/// The file format version, 2, with no flags: a single part of scanlines.
+const VERSION: [u8; 4] = [2, 0, 0, 0];
+
+/// The pixel type of 32-bit floats.
+const FLOAT: i32 = 2;
+
+/// Add the header attribute `name`, of type `kind`, to `header`.
+fn attribute(header: &mut Vec<u8>, name: &str, kind: &str, value: &[u8]) {
+    for text in [name, kind] {
+        header.extend(text.as_bytes());
+        header.push(0);
+    }
+    header.extend((value.len() as i32).to_le_bytes());
+    header.extend(value);
+}
+
+/// The bytes of `numbers`, little endian, one after the other.
+fn le_bytes<const N: usize>(numbers: impl IntoIterator<Item = [u8; N]>) -> Vec<u8> {
+    numbers.into_iter().flatten().collect()
+}
+
+/// Encode `channels`, whose dimensions are given by `bounds`, as an OpenEXR
+/// image. The channels are stored in the order of their names, as the format
+/// requires, whatever order they are given in.
+pub fn encode(channels: &[Channel], bounds: (usize, usize)) -> Vec<u8> {
+    let (width, height) = bounds;
+    let mut channels: Vec<&Channel> = channels.iter().collect();
+    channels.sort_by(|a, b| a.name.cmp(&b.name));
+    assert!(channels
+        .iter()
+        .all(|channel| channel.values.len() == width * height));
+
+    let mut list = Vec::new();
+    for channel in &channels {
+        list.extend(channel.name.as_bytes());
+        list.push(0);
+        list.extend(FLOAT.to_le_bytes());
+        // not perceptually linear, three reserved bytes, sampled every pixel
+        list.extend([0; 4]);
+        list.extend(le_bytes([1i32.to_le_bytes(), 1i32.to_le_bytes()]));
+    }
+    list.push(0);
+    let window = le_bytes(
+        [0, 0, width as i32 - 1, height as i32 - 1].map(|corner: i32| corner.to_le_bytes()),
+    );
+
+    let mut exr = [MAGIC, VERSION].concat();
+    attribute(&mut exr, "channels", "chlist", &list);
+    attribute(&mut exr, "compression", "compression", &[0]);
+    attribute(&mut exr, "dataWindow", "box2i", &window);
+    attribute(&mut exr, "displayWindow", "box2i", &window);
+    attribute(&mut exr, "lineOrder", "lineOrder", &[0]);
+    attribute(&mut exr, "pixelAspectRatio", "float", &1f32.to_le_bytes());
+    attribute(&mut exr, "screenWindowCenter", "v2f", &[0; 8]);
+    attribute(&mut exr, "screenWindowWidth", "float", &1f32.to_le_bytes());
+    exr.push(0);
+
+    // a table of where each scanline starts, then the scanlines, each
+    // channel in turn
+    let row_size = 4 * width * channels.len();
+    let rows_start = exr.len() + 8 * height;
+    for y in 0..height {
+        let offset = rows_start + y * (8 + row_size);
+        exr.extend((offset as u64).to_le_bytes());
+    }
+    for y in 0..height {
+        exr.extend((y as i32).to_le_bytes());
+        exr.extend((row_size as i32).to_le_bytes());
+        for channel in &channels {
+            let row = &channel.values[y * width..(y + 1) * width];
+            exr.extend(le_bytes(row.iter().map(|value| value.to_le_bytes())));
+        }
+    }
+    exr
+}
+
+#[test]
+fn test_encode() {
+    let channels = [
+        Channel::new("b.Y", vec![3.0, 4.0]),
+        Channel::new("a.Y", vec![1.0, 2.0]),
+    ];
+    let exr = encode(&channels, (1, 2));
+    assert!(exr.starts_with(&[0x76, 0x2f, 0x31, 0x01, 2, 0, 0, 0]));
+    assert!(exr[8..].starts_with(b"channels\0chlist\0"));
+    // sorted by name
+    let list = &exr[8 + 16 + 4..];
+    assert!(list.starts_with(b"a.Y\0\x02\0\0\0"));
+    assert!(list[4 + 16..].starts_with(b"b.Y\0"));
+    let window = b"dataWindow\0box2i\0\x10\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\x01\0\0\0";
+    assert!(exr.windows(window.len()).any(|found| found == window));
+
+    // the offsets point at the scanlines, which hold `a` then `b`
+    let end = exr.len() - 2 * (8 + 8);
+    let offset = |y: usize| {
+        let at = end - 2 * 8 + 8 * y;
+        u64::from_le_bytes(exr[at..at + 8].try_into().unwrap()) as usize
+    };
+    assert_eq!(offset(0), end);
+    assert_eq!(offset(1), end + 16);
+    let number = |at: usize| f32::from_le_bytes(exr[at..at + 4].try_into().unwrap());
+    assert_eq!(&exr[end..end + 8], b"\0\0\0\0\x08\0\0\0");
+    assert_eq!((number(end + 8), number(end + 12)), (1.0, 3.0));
+    assert_eq!(&exr[end + 16..end + 20], b"\x01\0\0\0");
+    assert_eq!((number(end + 24), number(end + 28)), (2.0, 4.0));
+}
@@ -0,0 +1,222 @@
+//! Extending a render past one of its edges, for the `extend` subcommand:
+//! only the strip of the plane the image grows by is rendered, on the same
+//! grid of pixels, and stitched to the image, so panoramas of the set can be
+//! built a strip at a time without rendering the old pixels again.
+
+use std::str::FromStr;
+
+use num::Complex;
+
+use crate::share::View;
+
+/// The edge of an image to extend it past.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Direction {
+    Left,
+    Right,
+    Up,
+    Down,
+}
+
+impl FromStr for Direction {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Direction, String> {
+        match s {
+            "left" => Ok(Direction::Left),
+            "right" => Ok(Direction::Right),
+            "up" => Ok(Direction::Up),
+            "down" => Ok(Direction::Down),
+            _ => Err(format!(
+                "unknown direction {}, expected left, right, up or down",
+                s
+            )),
+        }
+    }
+}
+
+#[test]
+fn test_parse_direction() {
+    assert_eq!("left".parse(), Ok(Direction::Left));
+    assert_eq!("down".parse(), Ok(Direction::Down));
+    assert!("north".parse::<Direction>().is_err());
+}
+
+/// The strip `amount` pixels wide, or high, that extending `view` past its
+/// `direction` edge exposes, with pixels the same size as those of `view`.
+pub fn strip(view: &View, direction: Direction, amount: usize) -> View {
+    let (width, height) = view.bounds;
+    let (upper_left, lower_right) = (view.upper_left, view.lower_right);
+    let step = Complex {
+        re: (lower_right.re - upper_left.re) / width as f64,
+        im: (upper_left.im - lower_right.im) / height as f64,
+    };
+    let (across, down) = (amount as f64 * step.re, amount as f64 * step.im);
+    let (bounds, upper_left, lower_right) = match direction {
+        Direction::Left => (
+            (amount, height),
+            Complex::new(upper_left.re - across, upper_left.im),
+            Complex::new(upper_left.re, lower_right.im),
+        ),
+        Direction::Right => (
+            (amount, height),
+            Complex::new(lower_right.re, upper_left.im),
+            Complex::new(lower_right.re + across, lower_right.im),
+        ),
+        Direction::Up => (
+            (width, amount),
+            Complex::new(upper_left.re, upper_left.im + down),
+            Complex::new(lower_right.re, upper_left.im),
+        ),
+        Direction::Down => (
+            (width, amount),
+            Complex::new(upper_left.re, lower_right.im),
+            Complex::new(lower_right.re, lower_right.im - down),
+        ),
+    };
+    View {
+        bounds,
+        upper_left,
+        lower_right,
+    }
+}
+
+/// The view of `view` extended by `strip`, as `strip` returns it.
+pub fn extended(view: &View, strip: &View, direction: Direction) -> View {
+    let (bounds, upper_left, lower_right) = match direction {
+        Direction::Left => (
+            (view.bounds.0 + strip.bounds.0, view.bounds.1),
+            strip.upper_left,
+            view.lower_right,
+        ),
+        Direction::Right => (
+            (view.bounds.0 + strip.bounds.0, view.bounds.1),
+            view.upper_left,
+            strip.lower_right,
+        ),
+        Direction::Up => (
+            (view.bounds.0, view.bounds.1 + strip.bounds.1),
+            strip.upper_left,
+            view.lower_right,
+        ),
+        Direction::Down => (
+            (view.bounds.0, view.bounds.1 + strip.bounds.1),
+            view.upper_left,
+            strip.lower_right,
+        ),
+    };
+    View {
+        bounds,
+        upper_left,
+        lower_right,
+    }
+}
+
+/// Stitch the pixels of `strip`, as `strip` returns it, to those of `image`,
+/// whose view is `view`, past its `direction` edge. Both have `channels`
+/// bytes a pixel.
+pub fn stitch(
+    image: &[u8],
+    view: &View,
+    pixels: &[u8],
+    strip: &View,
+    direction: Direction,
+    channels: usize,
+) -> Vec<u8> {
+    match direction {
+        Direction::Up => [pixels, image].concat(),
+        Direction::Down => [image, pixels].concat(),
+        Direction::Left | Direction::Right => {
+            let mut stitched = Vec::with_capacity(image.len() + pixels.len());
+            let rows = image.chunks(view.bounds.0 * channels);
+            let strip_rows = pixels.chunks(strip.bounds.0 * channels);
+            for (row, strip_row) in rows.zip(strip_rows) {
+                let (first, second) = if direction == Direction::Left {
+                    (strip_row, row)
+                } else {
+                    (row, strip_row)
+                };
+                stitched.extend_from_slice(first);
+                stitched.extend_from_slice(second);
+            }
+            stitched
+        }
+    }
+}
+
+#[test]
+fn test_extend() {
+    use crate::render::{render_with_limit, Strategy};
+
+    let render = |view: &View| {
+        let mut pixels = vec![0; view.bounds.0 * view.bounds.1];
+        render_with_limit(
+            Strategy::Naive,
+            &mut pixels,
+            view.bounds,
+            view.upper_left,
+            view.lower_right,
+            255,
+        );
+        pixels
+    };
+    let view = View {
+        bounds: (64, 48),
+        upper_left: Complex::new(-1.0, 0.5),
+        lower_right: Complex::new(0.0, -0.25),
+    };
+    let image = render(&view);
+    for direction in [
+        Direction::Left,
+        Direction::Right,
+        Direction::Up,
+        Direction::Down,
+    ] {
+        let strip = strip(&view, direction, 16);
+        let extended = extended(&view, &strip, direction);
+        let stitched = stitch(&image, &view, &render(&strip), &strip, direction, 1);
+
+        // the same as rendering the larger view at once, but for rounding
+        let expected = render(&extended);
+        assert_eq!(stitched.len(), expected.len(), "{:?}", direction);
+        let different = stitched.iter().zip(&expected).filter(|(a, b)| a != b);
+        assert!(different.count() < expected.len() / 100, "{:?}", direction);
+        // the pixels keep their size
+        let pixel_size = |view: &View| {
+            (
+                (view.lower_right.re - view.upper_left.re) / view.bounds.0 as f64,
+                (view.upper_left.im - view.lower_right.im) / view.bounds.1 as f64,
+            )
+        };
+        let (width, height) = pixel_size(&extended);
+        assert!((width - 1.0 / 64.0).abs() < 1e-15, "{:?}", direction);
+        assert!((height - 0.75 / 48.0).abs() < 1e-15, "{:?}", direction);
+    }
+
+    let strip = strip(&view, Direction::Right, 16);
+    assert_eq!(strip.bounds, (16, 48));
+    assert_eq!(strip.upper_left, Complex::new(0.0, 0.5));
+    assert_eq!(strip.lower_right, Complex::new(0.25, -0.25));
+    assert_eq!(extended(&view, &strip, Direction::Right).bounds, (80, 48));
+
+    // three channels a pixel, side by side
+    let small = View {
+        bounds: (1, 2),
+        ..view
+    };
+    let one = View {
+        bounds: (1, 2),
+        ..strip
+    };
+    assert_eq!(
+        stitch(
+            &[1, 1, 1, 2, 2, 2],
+            &small,
+            &[3, 3, 3, 4, 4, 4],
+            &one,
+            Direction::Left,
+            3
+        ),
+        [3, 3, 3, 1, 1, 1, 4, 4, 4, 2, 2, 2]
+    );
+}
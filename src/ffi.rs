@@ -0,0 +1,293 @@
+//! The renderer, for C and the languages that call C: build with
+//! `cargo build --release --no-default-features --features ffi`, which
+//! leaves `libmandelbrot.so` (`.dylib`, `.dll`) in `target/release`, and
+//! include `include/mandelbrot.h`:
+//!
+//! ```c
+//! MandelbrotRenderer *renderer = mandelbrot_renderer_new(0);
+//! MandelbrotParams params = mandelbrot_params_default();
+//! params.width = 800;
+//! params.height = 600;
+//! uint8_t *pixels = malloc(800 * 600);
+//! if (mandelbrot_render_into_buffer(renderer, &params, pixels, 800 * 600) != MANDELBROT_STATUS_OK)
+//!     fprintf(stderr, "%s\n", mandelbrot_renderer_last_error(renderer));
+//! mandelbrot_renderer_free(renderer);
+//! ```
+//!
+//! The header is generated from this module with
+//! `cbindgen --config cbindgen.toml --output include/mandelbrot.h`.
+//!
+//! No panic crosses into C: one is caught and returned as
+//! `MANDELBROT_STATUS_PANIC`.
+
+use std::{
+    ffi::{c_char, CString},
+    panic::{self, AssertUnwindSafe},
+    ptr, slice,
+};
+
+use num::Complex;
+
+use crate::{
+    error,
+    render::{self, Strategy},
+    units::Zoom,
+};
+
+/// What a call returned.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MandelbrotStatus {
+    Ok = 0,
+    /// A pointer that must not be null was.
+    NullPointer = 1,
+    /// The parameters don't describe a view that can be rendered.
+    InvalidParams = 2,
+    /// The buffer holds fewer bytes than the image has pixels.
+    BufferTooSmall = 3,
+    /// The renderer panicked, which is a bug.
+    Panic = 4,
+}
+
+/// The view to render, centered on `center_re + center_im i` and magnified
+/// `zoom` times as with `--zoom`, into a `width` by `height` image.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MandelbrotParams {
+    pub width: u32,
+    pub height: u32,
+    pub center_re: f64,
+    pub center_im: f64,
+    pub zoom: f64,
+    /// Iterations before a point is taken to be in the set.
+    pub max_iter: u32,
+    /// 0 to compute every pixel on its own, 1 to skip rectangles whose
+    /// border is all the same color, as `--strategy` does.
+    pub strategy: u32,
+}
+
+/// The whole set, as the command line renders it without options, into an
+/// image of no pixels, which the caller sizes.
+#[no_mangle]
+pub extern "C" fn mandelbrot_params_default() -> MandelbrotParams {
+    MandelbrotParams {
+        width: 0,
+        height: 0,
+        center_re: -0.5,
+        center_im: 0.0,
+        zoom: 1.0,
+        max_iter: render::DEFAULT_LIMIT as u32,
+        strategy: 0,
+    }
+}
+
+/// A renderer, kept between renders: the threads it renders with and what
+/// went wrong last.
+pub struct MandelbrotRenderer {
+    threads: usize,
+    last_error: CString,
+}
+
+impl MandelbrotRenderer {
+    /// Render `params` into `buffer`, one byte a pixel.
+    fn render(&mut self, params: &MandelbrotParams, buffer: &mut [u8]) -> MandelbrotStatus {
+        let bounds = (params.width as usize, params.height as usize);
+        let strategy = match params.strategy {
+            0 => Strategy::Naive,
+            1 => Strategy::BorderTrace,
+            strategy => {
+                return self.fail(
+                    MandelbrotStatus::InvalidParams,
+                    &format!("unknown strategy {}", strategy),
+                )
+            }
+        };
+        if !(params.zoom > 0.0 && params.zoom.is_finite()) || params.max_iter == 0 {
+            return self.fail(
+                MandelbrotStatus::InvalidParams,
+                "the zoom must be positive and finite, and max_iter at least 1",
+            );
+        }
+        let center = Complex::new(params.center_re, params.center_im);
+        let (upper_left, lower_right) =
+            render::corners_from_center(center, Zoom(params.zoom), bounds);
+        if let Err(e) = error::validate_viewport(bounds, upper_left, lower_right) {
+            return self.fail(MandelbrotStatus::InvalidParams, &e.to_string());
+        }
+        let len = bounds.0 * bounds.1;
+        if buffer.len() < len {
+            return self.fail(
+                MandelbrotStatus::BufferTooSmall,
+                &format!("{} pixels don't fit in {} bytes", len, buffer.len()),
+            );
+        }
+
+        render::render_parallel(
+            strategy,
+            &mut buffer[..len],
+            bounds,
+            upper_left,
+            lower_right,
+            &render::Schedule {
+                limit: params.max_iter as usize,
+                ..render::Schedule::new(self.threads)
+            },
+        );
+        self.last_error = CString::default();
+        MandelbrotStatus::Ok
+    }
+
+    /// Keep `message` for `mandelbrot_renderer_last_error`, and return
+    /// `status`.
+    fn fail(&mut self, status: MandelbrotStatus, message: &str) -> MandelbrotStatus {
+        self.last_error = CString::new(message.replace('\0', "")).unwrap_or_default();
+        status
+    }
+}
+
+/// A renderer rendering with `threads` threads, or one per CPU if 0. Free it
+/// with `mandelbrot_renderer_free`.
+#[no_mangle]
+pub extern "C" fn mandelbrot_renderer_new(threads: u32) -> *mut MandelbrotRenderer {
+    let threads = match threads {
+        0 => num_cpus::get(),
+        threads => threads as usize,
+    };
+    Box::into_raw(Box::new(MandelbrotRenderer {
+        threads,
+        last_error: CString::default(),
+    }))
+}
+
+/// Free `renderer`. Null is ignored.
+///
+/// # Safety
+///
+/// `renderer` must be null or come from `mandelbrot_renderer_new`, and not
+/// have been freed already.
+#[no_mangle]
+pub unsafe extern "C" fn mandelbrot_renderer_free(renderer: *mut MandelbrotRenderer) {
+    if !renderer.is_null() {
+        drop(Box::from_raw(renderer));
+    }
+}
+
+/// Render the view of `params` into `buffer`, `buffer_len` bytes long, as
+/// one byte a pixel, row after row: black for points in the set to white
+/// for points that escape at once.
+///
+/// Anything but `MANDELBROT_STATUS_OK` leaves `buffer` as it was, except
+/// `MANDELBROT_STATUS_PANIC`, and `mandelbrot_renderer_last_error` tells why.
+///
+/// # Safety
+///
+/// `renderer` must come from `mandelbrot_renderer_new`, `params` point to a
+/// `MandelbrotParams`, and `buffer` to `buffer_len` writable bytes. None of
+/// them may be used by another thread during the call.
+#[no_mangle]
+pub unsafe extern "C" fn mandelbrot_render_into_buffer(
+    renderer: *mut MandelbrotRenderer,
+    params: *const MandelbrotParams,
+    buffer: *mut u8,
+    buffer_len: usize,
+) -> MandelbrotStatus {
+    let Some(renderer) = renderer.as_mut() else {
+        return MandelbrotStatus::NullPointer;
+    };
+    let Some(params) = params.as_ref() else {
+        return renderer.fail(MandelbrotStatus::NullPointer, "params is null");
+    };
+    if buffer.is_null() {
+        return renderer.fail(MandelbrotStatus::NullPointer, "buffer is null");
+    }
+    let buffer = slice::from_raw_parts_mut(buffer, buffer_len);
+    match panic::catch_unwind(AssertUnwindSafe(|| renderer.render(params, buffer))) {
+        Ok(status) => status,
+        Err(_) => renderer.fail(MandelbrotStatus::Panic, "the renderer panicked"),
+    }
+}
+
+/// Why the last call on `renderer` didn't return `MANDELBROT_STATUS_OK`, or an
+/// empty string if it did. The string belongs to `renderer`, and lasts until
+/// the next call on it.
+///
+/// # Safety
+///
+/// `renderer` must be null or come from `mandelbrot_renderer_new`.
+#[no_mangle]
+pub unsafe extern "C" fn mandelbrot_renderer_last_error(
+    renderer: *const MandelbrotRenderer,
+) -> *const c_char {
+    match renderer.as_ref() {
+        Some(renderer) => renderer.last_error.as_ptr(),
+        None => ptr::null(),
+    }
+}
+
+#[test]
+fn test_render_into_buffer() {
+    use std::ffi::CStr;
+
+    let renderer = mandelbrot_renderer_new(2);
+    let params = MandelbrotParams {
+        width: 8,
+        height: 6,
+        ..mandelbrot_params_default()
+    };
+    let last_error = || unsafe { CStr::from_ptr(mandelbrot_renderer_last_error(renderer)) };
+    let mut buffer = vec![7; 50];
+    let status =
+        unsafe { mandelbrot_render_into_buffer(renderer, &params, buffer.as_mut_ptr(), 50) };
+    assert_eq!(status, MandelbrotStatus::Ok);
+    assert!(last_error().is_empty());
+
+    // what `render::render` draws, leaving the rest of the buffer alone
+    let (upper_left, lower_right) =
+        render::corners_from_center(Complex::new(-0.5, 0.0), Zoom::NONE, (8, 6));
+    let mut gray = vec![0; 48];
+    render::render(&mut gray, (8, 6), upper_left, lower_right);
+    assert_eq!(buffer[..48], gray);
+    assert_eq!(buffer[48..], [7, 7]);
+
+    let mut render = |params: &MandelbrotParams, len| unsafe {
+        mandelbrot_render_into_buffer(renderer, params, buffer.as_mut_ptr(), len)
+    };
+    assert_eq!(render(&params, 47), MandelbrotStatus::BufferTooSmall);
+    assert!(last_error().to_str().unwrap().contains("48 pixels"));
+    let invalid = [
+        MandelbrotParams {
+            zoom: 0.0,
+            ..params
+        },
+        MandelbrotParams {
+            zoom: f64::NAN,
+            ..params
+        },
+        MandelbrotParams { width: 0, ..params },
+        MandelbrotParams {
+            max_iter: 0,
+            ..params
+        },
+        MandelbrotParams {
+            strategy: 2,
+            ..params
+        },
+    ];
+    for params in &invalid {
+        assert_eq!(render(params, 50), MandelbrotStatus::InvalidParams);
+        assert!(!last_error().is_empty());
+    }
+    unsafe {
+        assert_eq!(
+            mandelbrot_render_into_buffer(renderer, ptr::null(), buffer.as_mut_ptr(), 50),
+            MandelbrotStatus::NullPointer
+        );
+        assert_eq!(
+            mandelbrot_render_into_buffer(ptr::null_mut(), &params, buffer.as_mut_ptr(), 50),
+            MandelbrotStatus::NullPointer
+        );
+        assert!(mandelbrot_renderer_last_error(ptr::null()).is_null());
+        mandelbrot_renderer_free(renderer);
+        mandelbrot_renderer_free(ptr::null_mut());
+    }
+}
@@ -0,0 +1,300 @@
+//! Looking for interesting views automatically, for wallpapers and animation
+//! targets: random points near the boundary of the set are tried as centers,
+//! and the views around them scored by how varied their escape times are.
+//!
+//! Views inside the set, or far outside it, have all their points escaping
+//! at about the same time, and score low; views full of filaments and
+//! spirals have many different escape times, and score high.
+
+use std::thread;
+
+use num::Complex;
+
+use crate::{
+    limit, render,
+    sampling::{splitmix64, Rng},
+    units::Zoom,
+};
+
+/// How many points a side the grid scoring a view has.
+const GRID: usize = 32;
+
+/// How many random points are tried for every candidate found, at most,
+/// before the search gives up on finding more.
+const ATTEMPTS_PER_CANDIDATE: usize = 1000;
+
+/// The corners of a rectangle the whole set is in.
+const SET_UPPER_LEFT: Complex<f64> = Complex { re: -2.0, im: 1.25 };
+const SET_LOWER_RIGHT: Complex<f64> = Complex { re: 0.5, im: -1.25 };
+
+/// How many cells a side the coarse pass of `random_view` divides the set
+/// into, and the limit it iterates their centers to.
+const COARSE: usize = 64;
+const COARSE_LIMIT: usize = 64;
+
+/// How many views near the boundary `random_view` scores before keeping
+/// the best of them.
+const RANDOM_CANDIDATES: usize = 8;
+
+/// What to look for.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Search {
+    /// How deep the views are.
+    pub zoom: Zoom,
+    /// How many centers are tried.
+    pub candidates: usize,
+    /// How many views are kept, the best first.
+    pub top: usize,
+    /// Where the random centers start; the same seed finds the same views.
+    pub seed: u64,
+    /// How many threads score the candidates.
+    pub threads: usize,
+}
+
+/// A view found, by its center, with its score.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Found {
+    pub center: Complex<f64>,
+    pub score: f64,
+}
+
+/// How many iterations a point needs before it counts as close enough to
+/// the boundary for views magnified `zoom` times around it to show some of
+/// it: the deeper the view, the closer.
+pub fn boundary_escape_time(zoom: Zoom) -> usize {
+    10 + (5.0 * zoom.0.max(1.0).log2()) as usize
+}
+
+/// A random point of the plane whose orbit escapes after at least
+/// `min_escape` iterations, but escapes: a point just outside the set,
+/// close to its boundary. Gives up after `attempts` tries.
+pub fn boundary_point(rng: &mut Rng, min_escape: usize, attempts: usize) -> Option<Complex<f64>> {
+    boundary_point_in(rng, SET_UPPER_LEFT, SET_LOWER_RIGHT, min_escape, attempts)
+}
+
+/// Like `boundary_point`, looking in the rectangle from `upper_left` to
+/// `lower_right` only.
+fn boundary_point_in(
+    rng: &mut Rng,
+    upper_left: Complex<f64>,
+    lower_right: Complex<f64>,
+    min_escape: usize,
+    attempts: usize,
+) -> Option<Complex<f64>> {
+    let limit = min_escape.saturating_mul(8);
+    (0..attempts).find_map(|_| {
+        let c = Complex {
+            re: upper_left.re + rng.next_f64() * (lower_right.re - upper_left.re),
+            im: lower_right.im + rng.next_f64() * (upper_left.im - lower_right.im),
+        };
+        match render::escape_time(c, limit) {
+            Some(escape) if escape >= min_escape => Some(c),
+            _ => None,
+        }
+    })
+}
+
+#[test]
+fn test_boundary_point() {
+    let mut rng = Rng::new(7);
+    let min_escape = boundary_escape_time(Zoom(1000.0));
+    let point = boundary_point(&mut rng, min_escape, 100_000).unwrap();
+    let escape = render::escape_time(point, 8 * min_escape).unwrap();
+    assert!(escape >= min_escape);
+    assert!(boundary_escape_time(Zoom(1e6)) > min_escape);
+}
+
+/// Score the view from `upper_left` to `lower_right` by the entropy, in
+/// bits, of the escape times of a grid of its points, those in the set
+/// counting as an escape time of their own. All the same scores 0.
+pub fn score(upper_left: Complex<f64>, lower_right: Complex<f64>, limit: usize) -> f64 {
+    let grid = (GRID, GRID);
+    let mut counts = vec![0u32; limit + 1];
+    for i in 0..GRID * GRID {
+        let point = render::pixel_to_point(grid, (i % GRID, i / GRID), upper_left, lower_right);
+        counts[render::escape_time(point, limit).unwrap_or(limit)] += 1;
+    }
+    let total = (GRID * GRID) as f64;
+    -counts
+        .iter()
+        .filter(|&&count| count > 0)
+        .map(|&count| {
+            let p = count as f64 / total;
+            p * p.log2()
+        })
+        .sum::<f64>()
+}
+
+#[test]
+fn test_score() {
+    let limit = 255;
+    // inside the main cardioid
+    let inside = score(Complex::new(-0.1, 0.1), Complex::new(0.1, -0.1), limit);
+    assert_eq!(inside, 0.0);
+    // seahorse valley
+    let valley = score(Complex::new(-0.76, 0.14), Complex::new(-0.72, 0.1), limit);
+    // far outside
+    let outside = score(Complex::new(3.0, 3.0), Complex::new(3.1, 2.9), limit);
+    assert!(valley > 3.0, "{}", valley);
+    assert!(outside < 1.0, "{}", outside);
+}
+
+/// Look for the most interesting views `search.zoom` deep: score
+/// `search.candidates` random centers close to the boundary, and return the
+/// best `search.top` of them, the best first, no two overlapping.
+pub fn find(search: &Search) -> Vec<Found> {
+    let min_escape = boundary_escape_time(search.zoom);
+    let mut rng = Rng::new(splitmix64(search.seed));
+    let centers: Vec<Complex<f64>> = (0..search.candidates)
+        .map_while(|_| boundary_point(&mut rng, min_escape, ATTEMPTS_PER_CANDIDATE))
+        .collect();
+
+    // square views, scored at the limit an image 1000 pixels wide would get
+    let bounds = (GRID, GRID);
+    let width = search.zoom.width();
+    let limit = limit::estimate(search.zoom.pixel_size(1000));
+    let chunk = centers.len().div_ceil(search.threads.max(1)).max(1);
+    let mut found: Vec<Found> = thread::scope(|scope| {
+        let workers: Vec<_> = centers
+            .chunks(chunk)
+            .map(|centers| {
+                scope.spawn(move || {
+                    centers
+                        .iter()
+                        .map(|&center| {
+                            let (upper_left, lower_right) =
+                                render::corners_from_center(center, search.zoom, bounds);
+                            Found {
+                                center,
+                                score: score(upper_left, lower_right, limit),
+                            }
+                        })
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        workers
+            .into_iter()
+            .flat_map(|worker| worker.join().unwrap())
+            .collect()
+    });
+
+    found.sort_by(|a, b| b.score.total_cmp(&a.score));
+    let mut best: Vec<Found> = Vec::new();
+    for candidate in found {
+        if best.len() == search.top {
+            break;
+        }
+        let overlaps = best
+            .iter()
+            .any(|view| (view.center - candidate.center).norm() < width.0);
+        if !overlaps {
+            best.push(candidate);
+        }
+    }
+    best
+}
+
+#[test]
+fn test_find() {
+    let search = Search {
+        zoom: Zoom(100.0),
+        candidates: 40,
+        top: 3,
+        seed: 1,
+        threads: 4,
+    };
+    let found = find(&search);
+    assert_eq!(found.len(), 3);
+    assert!(found.windows(2).all(|w| w[0].score >= w[1].score));
+    assert!(found[0].score > 2.0, "{:?}", found);
+    assert!((found[0].center - found[1].center).norm() >= search.zoom.width().0);
+
+    // the seed decides, not the threads
+    let again = find(&Search {
+        threads: 1,
+        ..search.clone()
+    });
+    assert_eq!(again, found);
+}
+
+/// The center of a random view `zoom` deep on the boundary of the set, the
+/// same for the same `seed`.
+///
+/// A quick, coarse pass over the whole set first finds the cells the
+/// boundary goes through, those with points both in the set and out of it
+/// around them, so that the random points are only looked for there; the
+/// best scoring of a few of them is kept.
+pub fn random_view(seed: u64, zoom: Zoom) -> Option<Complex<f64>> {
+    let grid = (COARSE, COARSE);
+    let inside: Vec<bool> = (0..COARSE * COARSE)
+        .map(|i| {
+            let point = render::pixel_to_point(
+                grid,
+                (i % COARSE, i / COARSE),
+                SET_UPPER_LEFT,
+                SET_LOWER_RIGHT,
+            );
+            render::escape_time(point, COARSE_LIMIT).is_none()
+        })
+        .collect();
+    let cells: Vec<(usize, usize)> = (0..COARSE * COARSE)
+        .map(|i| (i % COARSE, i / COARSE))
+        .filter(|&(x, y)| {
+            let neighbors = (x.saturating_sub(1)..(x + 2).min(COARSE))
+                .flat_map(|x| (y.saturating_sub(1)..(y + 2).min(COARSE)).map(move |y| (x, y)));
+            let (mut some_in, mut some_out) = (false, false);
+            for (x, y) in neighbors {
+                some_in |= inside[y * COARSE + x];
+                some_out |= !inside[y * COARSE + x];
+            }
+            some_in && some_out
+        })
+        .collect();
+    if cells.is_empty() {
+        return None;
+    }
+
+    let min_escape = boundary_escape_time(zoom);
+    let limit = limit::estimate(zoom.pixel_size(1000));
+    let mut rng = Rng::new(splitmix64(seed));
+    let mut best: Option<Found> = None;
+    for _ in 0..RANDOM_CANDIDATES {
+        let (x, y) = cells[(rng.next_u64() % cells.len() as u64) as usize];
+        let cell_upper_left = render::pixel_to_point(grid, (x, y), SET_UPPER_LEFT, SET_LOWER_RIGHT);
+        let cell_lower_right =
+            render::pixel_to_point(grid, (x + 1, y + 1), SET_UPPER_LEFT, SET_LOWER_RIGHT);
+        let Some(center) = boundary_point_in(
+            &mut rng,
+            cell_upper_left,
+            cell_lower_right,
+            min_escape,
+            ATTEMPTS_PER_CANDIDATE,
+        ) else {
+            continue;
+        };
+        let (upper_left, lower_right) = render::corners_from_center(center, zoom, (GRID, GRID));
+        let score = score(upper_left, lower_right, limit);
+        if best.is_none_or(|best| score > best.score) {
+            best = Some(Found { center, score });
+        }
+    }
+    best.map(|found| found.center)
+}
+
+#[test]
+fn test_random_view() {
+    let zoom = Zoom(1000.0);
+    let center = random_view(3, zoom).unwrap();
+    assert_eq!(random_view(3, zoom), Some(center));
+    assert_ne!(random_view(4, zoom), Some(center));
+
+    // close to the boundary: the view has points escaping at many times
+    let (upper_left, lower_right) = render::corners_from_center(center, zoom, (GRID, GRID));
+    let score = score(
+        upper_left,
+        lower_right,
+        limit::estimate(zoom.pixel_size(1000)),
+    );
+    assert!(score > 2.0, "{}", score);
+}
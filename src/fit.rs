@@ -0,0 +1,114 @@
+//! Reconciling a view with the shape of the image it is rendered into, for
+//! `--fit`: corners given as numbers needn't have the aspect ratio of the
+//! pixels, and mapping one onto the other as they are stretches the set.
+
+use std::str::FromStr;
+
+use num::Complex;
+
+/// How much the aspect ratios may differ before the stretch shows: a
+/// percent, more than rounding the dimensions of an image to whole pixels
+/// does.
+pub const TOLERANCE: f64 = 0.01;
+
+/// What to do with a view whose aspect ratio isn't that of the image.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Fit {
+    /// Widen the view on the short side, about its center, so that all of it
+    /// shows with more of the plane around it.
+    Letterbox,
+    /// Narrow the view on the long side, about its center, so that it fills
+    /// the image, cutting off its edges.
+    Crop,
+    /// Keep the view, stretching the pixels to fill the image.
+    Stretch,
+}
+
+impl FromStr for Fit {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Fit, String> {
+        match s {
+            "letterbox" => Ok(Fit::Letterbox),
+            "crop" => Ok(Fit::Crop),
+            "stretch" => Ok(Fit::Stretch),
+            _ => Err(format!("unknown fit {}", s)),
+        }
+    }
+}
+
+/// How many times wider than high the pixels of the view from `upper_left`
+/// to `lower_right` are, in an image whose dimensions are given by `bounds`:
+/// 1 when the aspect ratios match.
+pub fn stretch(bounds: (usize, usize), upper_left: Complex<f64>, lower_right: Complex<f64>) -> f64 {
+    let view = (lower_right.re - upper_left.re) / (upper_left.im - lower_right.im);
+    view / (bounds.0 as f64 / bounds.1 as f64)
+}
+
+/// Whether the pixels of the view are stretched by more than `TOLERANCE`.
+pub fn mismatched(
+    bounds: (usize, usize),
+    upper_left: Complex<f64>,
+    lower_right: Complex<f64>,
+) -> bool {
+    stretch(bounds, upper_left, lower_right).ln().abs() > TOLERANCE.ln_1p()
+}
+
+impl Fit {
+    /// The corners of the view from `upper_left` to `lower_right` fitted to
+    /// an image whose dimensions are given by `bounds`.
+    pub fn apply(
+        self,
+        bounds: (usize, usize),
+        upper_left: Complex<f64>,
+        lower_right: Complex<f64>,
+    ) -> (Complex<f64>, Complex<f64>) {
+        let center = (upper_left + lower_right) / 2.0;
+        let (mut width, mut height) = (
+            lower_right.re - upper_left.re,
+            upper_left.im - lower_right.im,
+        );
+        let aspect = bounds.0 as f64 / bounds.1 as f64;
+        // too wide for the image, or too high
+        let wide = width / height > aspect;
+        match (self, wide) {
+            (Fit::Stretch, _) => return (upper_left, lower_right),
+            (Fit::Letterbox, true) | (Fit::Crop, false) => height = width / aspect,
+            (Fit::Letterbox, false) | (Fit::Crop, true) => width = height * aspect,
+        }
+        let half = Complex::new(width / 2.0, -height / 2.0);
+        (center - half, center + half)
+    }
+}
+
+#[test]
+fn test_fit() {
+    assert_eq!("crop".parse(), Ok(Fit::Crop));
+    assert!("fill".parse::<Fit>().is_err());
+
+    // a square view in a 4:3 image
+    let (upper_left, lower_right) = (Complex::new(-2.0, 1.5), Complex::new(1.0, -1.5));
+    let bounds = (400, 300);
+    assert!((stretch(bounds, upper_left, lower_right) - 0.75).abs() < 1e-12);
+    assert!(mismatched(bounds, upper_left, lower_right));
+    assert_eq!(
+        Fit::Letterbox.apply(bounds, upper_left, lower_right),
+        (Complex::new(-2.5, 1.5), Complex::new(1.5, -1.5))
+    );
+    assert_eq!(
+        Fit::Crop.apply(bounds, upper_left, lower_right),
+        (Complex::new(-2.0, 1.125), Complex::new(1.0, -1.125))
+    );
+    assert_eq!(
+        Fit::Stretch.apply(bounds, upper_left, lower_right),
+        (upper_left, lower_right)
+    );
+    for fit in [Fit::Letterbox, Fit::Crop] {
+        let (upper_left, lower_right) = fit.apply(bounds, upper_left, lower_right);
+        assert!(!mismatched(bounds, upper_left, lower_right));
+    }
+
+    // off by less than a pixel
+    let lower_right = Complex::new(2.0, -1.5);
+    assert!(!mismatched((401, 300), upper_left, lower_right));
+}
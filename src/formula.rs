@@ -0,0 +1,533 @@
+//! Fractals of formulas given on the command line: `z*z*z + c*z + c`
+//! instead of the Mandelbrot set's `z*z + c`.
+//!
+//! The formula is parsed once into an `Expression`, which every iteration
+//! evaluates for the current `z` and the point `c` being colored. Points are
+//! colored by escape time, as for the Mandelbrot set, starting from `z = 0`.
+
+use std::{
+    str::FromStr,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+};
+
+use num::Complex;
+
+use crate::{
+    render::{mirror_rows, mirrored_rows, pixel_to_point, Schedule},
+    usage::{self, Usage},
+};
+
+/// The functions a formula can call.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Function {
+    Sin,
+    Cos,
+    Exp,
+    Log,
+    Sqrt,
+    /// The absolute values of the real and imaginary parts, as in the
+    /// Burning Ship's `abs(z)^2 + c`.
+    Abs,
+    Conj,
+}
+
+impl Function {
+    fn named(name: &str) -> Option<Function> {
+        Some(match name {
+            "sin" => Function::Sin,
+            "cos" => Function::Cos,
+            "exp" => Function::Exp,
+            "log" => Function::Log,
+            "sqrt" => Function::Sqrt,
+            "abs" => Function::Abs,
+            "conj" => Function::Conj,
+            _ => return None,
+        })
+    }
+
+    fn apply(self, z: Complex<f64>) -> Complex<f64> {
+        match self {
+            Function::Sin => z.sin(),
+            Function::Cos => z.cos(),
+            Function::Exp => z.exp(),
+            Function::Log => z.ln(),
+            Function::Sqrt => z.sqrt(),
+            Function::Abs => Complex::new(z.re.abs(), z.im.abs()),
+            Function::Conj => z.conj(),
+        }
+    }
+}
+
+/// A parsed formula in `z` and `c`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Expression {
+    Constant(Complex<f64>),
+    Z,
+    C,
+    Negate(Box<Expression>),
+    Add(Box<Expression>, Box<Expression>),
+    Subtract(Box<Expression>, Box<Expression>),
+    Multiply(Box<Expression>, Box<Expression>),
+    Divide(Box<Expression>, Box<Expression>),
+    Power(Box<Expression>, Box<Expression>),
+    Call(Function, Box<Expression>),
+}
+
+impl Expression {
+    /// The value of the expression for `z` and `c`.
+    pub fn eval(&self, z: Complex<f64>, c: Complex<f64>) -> Complex<f64> {
+        match self {
+            Expression::Constant(k) => *k,
+            Expression::Z => z,
+            Expression::C => c,
+            Expression::Negate(a) => -a.eval(z, c),
+            Expression::Add(a, b) => a.eval(z, c) + b.eval(z, c),
+            Expression::Subtract(a, b) => a.eval(z, c) - b.eval(z, c),
+            Expression::Multiply(a, b) => a.eval(z, c) * b.eval(z, c),
+            Expression::Divide(a, b) => a.eval(z, c) / b.eval(z, c),
+            Expression::Power(a, b) => {
+                let base = a.eval(z, c);
+                // whole powers are multiplied out, which is faster and exact
+                // at 0, where powc isn't
+                match **b {
+                    Expression::Constant(k) if k.im == 0.0 && k.re.fract() == 0.0 => {
+                        base.powi(k.re as i32)
+                    }
+                    _ => base.powc(b.eval(z, c)),
+                }
+            }
+            Expression::Call(function, a) => function.apply(a.eval(z, c)),
+        }
+    }
+}
+
+impl Expression {
+    /// Whether the fractal of the formula is symmetric about the real axis:
+    /// conjugating `z` and `c` conjugates its value, as it does without
+    /// numbers off the real axis and without `abs`.
+    pub fn symmetric(&self) -> bool {
+        match self {
+            Expression::Constant(k) => k.im == 0.0,
+            Expression::Z | Expression::C => true,
+            Expression::Negate(a) => a.symmetric(),
+            Expression::Add(a, b)
+            | Expression::Subtract(a, b)
+            | Expression::Multiply(a, b)
+            | Expression::Divide(a, b)
+            | Expression::Power(a, b) => a.symmetric() && b.symmetric(),
+            Expression::Call(function, a) => *function != Function::Abs && a.symmetric(),
+        }
+    }
+}
+
+#[test]
+fn test_symmetric() {
+    let symmetric = |s: &str| s.parse::<Expression>().unwrap().symmetric();
+    assert!(symmetric("z*z + c"));
+    assert!(symmetric("sin(z) / c + conj(z)^2.5"));
+    assert!(!symmetric("z*z + c + 0.3i"));
+    assert!(!symmetric("abs(z)^2 + c"));
+}
+
+#[test]
+fn test_eval() {
+    let z = Complex::new(1.0, 2.0);
+    let c = Complex::new(-0.5, 0.25);
+    let eval = |s: &str| s.parse::<Expression>().unwrap().eval(z, c);
+    assert_eq!(eval("z*z + c"), z * z + c);
+    assert_eq!(eval("z^3 + c*z + c"), z * z * z + c * z + c);
+    assert_eq!(eval("-z^2"), -(z * z));
+    assert_eq!(eval("2^3^2"), Complex::new(512.0, 0.0));
+    assert_eq!(eval("(z - 1) / 2i"), (z - 1.0) / Complex::new(0.0, 2.0));
+    assert_eq!(eval("abs(-z) + conj(c)"), Complex::new(0.5, 1.75));
+    assert_eq!(eval("sin(z) * exp(c)"), z.sin() * c.exp());
+    assert_eq!(eval("z^0.5"), z.powc(Complex::new(0.5, 0.0)));
+}
+
+/// The variables of formulas, and what they stand for.
+const VARIABLES: &[(&str, Expression)] = &[("z", Expression::Z), ("c", Expression::C)];
+
+impl FromStr for Expression {
+    type Err = String;
+
+    /// Parse a formula made of `z`, `c`, numbers, `i`, the operators `+`,
+    /// `-`, `*`, `/` and `^`, parentheses and calls to `sin`, `cos`, `exp`,
+    /// `log`, `sqrt`, `abs` and `conj`.
+    fn from_str(s: &str) -> Result<Expression, String> {
+        Expression::parse_with(s, VARIABLES)
+    }
+}
+
+impl Expression {
+    /// Parse a formula like `from_str` does, in the variables named in
+    /// `variables` instead of `z` and `c`, each standing for the expression
+    /// beside it.
+    pub fn parse_with(s: &str, variables: &[(&str, Expression)]) -> Result<Expression, String> {
+        let mut parser = Parser {
+            chars: s.chars().collect(),
+            pos: 0,
+            variables,
+        };
+        let expression = parser.sum();
+        match expression {
+            Some(expression) if parser.peek().is_none() => Ok(expression),
+            _ => Err(format!(
+                "invalid formula {:?} at character {}",
+                s,
+                parser.pos + 1
+            )),
+        }
+    }
+}
+
+/// A recursive descent parser over the characters of a formula. It stops
+/// where it finds something it can't parse.
+struct Parser<'a> {
+    chars: Vec<char>,
+    pos: usize,
+    variables: &'a [(&'a str, Expression)],
+}
+
+impl Parser<'_> {
+    /// The next character that isn't whitespace, moving `pos` to it.
+    fn peek(&mut self) -> Option<char> {
+        while self.chars.get(self.pos).is_some_and(|c| c.is_whitespace()) {
+            self.pos += 1;
+        }
+        self.chars.get(self.pos).copied()
+    }
+
+    /// The character at `pos`, whitespace included, inside numbers and
+    /// names.
+    fn current(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        let found = self.peek() == Some(c);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    /// Terms added or subtracted.
+    fn sum(&mut self) -> Option<Expression> {
+        let mut sum = self.product()?;
+        loop {
+            if self.eat('+') {
+                sum = Expression::Add(Box::new(sum), Box::new(self.product()?));
+            } else if self.eat('-') {
+                sum = Expression::Subtract(Box::new(sum), Box::new(self.product()?));
+            } else {
+                return Some(sum);
+            }
+        }
+    }
+
+    /// Factors multiplied or divided.
+    fn product(&mut self) -> Option<Expression> {
+        let mut product = self.factor()?;
+        loop {
+            if self.eat('*') {
+                product = Expression::Multiply(Box::new(product), Box::new(self.factor()?));
+            } else if self.eat('/') {
+                product = Expression::Divide(Box::new(product), Box::new(self.factor()?));
+            } else {
+                return Some(product);
+            }
+        }
+    }
+
+    /// A negated factor, or a power, which binds tighter: `-z^2` is
+    /// `-(z^2)`, and `2^3^2` is `2^(3^2)`.
+    fn factor(&mut self) -> Option<Expression> {
+        if self.eat('-') {
+            return Some(Expression::Negate(Box::new(self.factor()?)));
+        }
+        let base = self.atom()?;
+        if self.eat('^') {
+            Some(Expression::Power(Box::new(base), Box::new(self.factor()?)))
+        } else {
+            Some(base)
+        }
+    }
+
+    fn atom(&mut self) -> Option<Expression> {
+        match self.peek()? {
+            '(' => {
+                self.pos += 1;
+                let inner = self.sum()?;
+                self.eat(')').then_some(inner)
+            }
+            c if c.is_ascii_digit() || c == '.' => {
+                let start = self.pos;
+                while let Some(c) = self.current() {
+                    let exponent_sign =
+                        matches!(c, '+' | '-') && matches!(self.chars[self.pos - 1], 'e' | 'E');
+                    if !(c.is_ascii_digit() || matches!(c, '.' | 'e' | 'E') || exponent_sign) {
+                        break;
+                    }
+                    self.pos += 1;
+                }
+                let number: f64 = self.chars[start..self.pos]
+                    .iter()
+                    .collect::<String>()
+                    .parse()
+                    .ok()?;
+                if self.current() == Some('i') {
+                    self.pos += 1;
+                    Some(Expression::Constant(Complex::new(0.0, number)))
+                } else {
+                    Some(Expression::Constant(Complex::new(number, 0.0)))
+                }
+            }
+            c if c.is_ascii_alphabetic() => {
+                let start = self.pos;
+                while self.current().is_some_and(|c| c.is_ascii_alphabetic()) {
+                    self.pos += 1;
+                }
+                let name: String = self.chars[start..self.pos].iter().collect();
+                if let Some((_, variable)) = self.variables.iter().find(|(n, _)| *n == name) {
+                    return Some(variable.clone());
+                }
+                match name.as_str() {
+                    "i" => Some(Expression::Constant(Complex::new(0.0, 1.0))),
+                    _ => {
+                        let function = Function::named(&name).filter(|_| self.eat('('));
+                        let Some(function) = function else {
+                            self.pos = start;
+                            return None;
+                        };
+                        let argument = self.sum()?;
+                        self.eat(')')
+                            .then(|| Expression::Call(function, Box::new(argument)))
+                    }
+                }
+            }
+            _ => None,
+        }
+    }
+}
+
+#[test]
+fn test_parse_expression() {
+    use Expression::*;
+
+    assert_eq!(
+        "z * z + c".parse(),
+        Ok(Add(
+            Box::new(Multiply(Box::new(Z), Box::new(Z))),
+            Box::new(C)
+        ))
+    );
+    assert_eq!(
+        "conj(z)^2".parse(),
+        Ok(Power(
+            Box::new(Call(Function::Conj, Box::new(Z))),
+            Box::new(Constant(Complex::new(2.0, 0.0)))
+        ))
+    );
+    assert_eq!(
+        "invalid formula \"z + w\" at character 5",
+        "z + w".parse::<Expression>().unwrap_err()
+    );
+    for bad in [
+        "", "z +", "(z", "z)", "2z", "1 2", "zz", "tan(z)", "z ** 2", "1e",
+    ] {
+        assert!(bad.parse::<Expression>().is_err(), "{:?}", bad);
+    }
+
+    let in_x = |s: &str| Expression::parse_with(s, &[("x", Z)]);
+    assert_eq!(in_x("sqrt(x)"), Ok(Call(Function::Sqrt, Box::new(Z))));
+    assert!(in_x("z + x").is_err());
+    assert!("x".parse::<Expression>().is_err());
+}
+
+/// The parameters of a formula fractal.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Formula {
+    pub expression: Expression,
+    /// The distance from 0 beyond which a point has escaped.
+    pub bailout: f64,
+}
+
+impl Default for Formula {
+    fn default() -> Formula {
+        Formula {
+            expression: Expression::Add(
+                Box::new(Expression::Multiply(
+                    Box::new(Expression::Z),
+                    Box::new(Expression::Z),
+                )),
+                Box::new(Expression::C),
+            ),
+            bailout: 2.0,
+        }
+    }
+}
+
+/// The number of iterations of `formula` it takes `c` to get further than
+/// `formula.bailout` from 0, starting from `z = 0`, or `None` if it doesn't
+/// within `limit` iterations. An orbit that isn't a number anymore, after a
+/// division by 0 say, has escaped too.
+pub fn escape_time(formula: &Formula, c: Complex<f64>, limit: usize) -> Option<usize> {
+    let bailout = formula.bailout * formula.bailout;
+    let mut z = Complex::new(0.0, 0.0);
+    for i in 0..limit {
+        if z.norm_sqr() > bailout || z.is_nan() {
+            usage::count_iterations(i);
+            return Some(i);
+        }
+        z = formula.expression.eval(z, c);
+    }
+    usage::count_iterations(limit);
+    None
+}
+
+#[test]
+fn test_escape_time() {
+    let mandelbrot = Formula::default();
+    for c in [
+        Complex::new(0.0, 0.0),
+        Complex::new(-1.0, 0.0),
+        Complex::new(1.0, 1.0),
+        Complex::new(-0.75, 0.1),
+    ] {
+        assert_eq!(
+            escape_time(&mandelbrot, c, 255),
+            crate::render::escape_time(c, 255)
+        );
+    }
+
+    let reciprocal = Formula {
+        expression: "1/z".parse().unwrap(),
+        ..Formula::default()
+    };
+    assert_eq!(
+        escape_time(&reciprocal, Complex::new(0.0, 0.0), 10),
+        Some(1)
+    );
+}
+
+/// Render the fractal of `formula` over the rectangle between `upper_left`
+/// and `lower_right` into `pixels`, one grayscale byte per pixel, darker the
+/// longer points take to escape, on the threads of `schedule`.
+///
+/// The threads take `schedule.chunk_rows` rows at a time, as in
+/// `render::render_parallel`, counting them in `schedule.progress` and
+/// stopping when `schedule.stopped()` says so. Like it, they only take the
+/// top half of views symmetric about the real axis when `schedule.mirror`
+/// is set and the formula is symmetric too.
+pub fn render(
+    pixels: &mut [u8],
+    bounds: (usize, usize),
+    upper_left: Complex<f64>,
+    lower_right: Complex<f64>,
+    formula: &Formula,
+    schedule: &Schedule,
+) {
+    assert!(pixels.len() == bounds.0 * bounds.1);
+    if pixels.is_empty() {
+        return;
+    }
+
+    let mirrored = mirrored_rows(bounds, upper_left, lower_right)
+        .filter(|_| schedule.mirror && formula.expression.symmetric());
+    let rows = mirrored.unwrap_or(bounds.1);
+    let next_row = AtomicUsize::new(0);
+    let output = Mutex::new(pixels);
+    let (next_row, output) = (&next_row, &output);
+    crossbeam::scope(|spawner| {
+        for thread in 0..schedule.threads.max(1) {
+            spawner.spawn(move |_| {
+                let mut chunk = Vec::new();
+                loop {
+                    if schedule.stopped() {
+                        break;
+                    }
+                    let top = next_row.fetch_add(schedule.chunk_rows, Ordering::Relaxed);
+                    if top >= rows {
+                        break;
+                    }
+                    let height = schedule.chunk_rows.min(rows - top);
+
+                    chunk.clear();
+                    Usage::measure(schedule.usage, thread, height, || {
+                        for row in top..top + height {
+                            for column in 0..bounds.0 {
+                                let c =
+                                    pixel_to_point(bounds, (column, row), upper_left, lower_right);
+                                chunk.push(match escape_time(formula, c, 255) {
+                                    Some(count) => 255 - count as u8,
+                                    None => 0,
+                                });
+                            }
+                        }
+                    });
+
+                    let mut output = output.lock().unwrap();
+                    output[top * bounds.0..(top + height) * bounds.0].copy_from_slice(&chunk);
+                    let copied = match mirrored {
+                        Some(rows) => mirror_rows(&mut output, bounds, top, height, rows),
+                        None => 0,
+                    };
+                    if let Some(progress) = schedule.progress {
+                        progress.add_rows(height + copied);
+                    }
+                }
+            });
+        }
+    })
+    .unwrap();
+}
+
+#[test]
+fn test_render() {
+    // z*z + c gives the Mandelbrot set
+    let bounds = (40, 30);
+    let (upper_left, lower_right) = (Complex::new(-2.0, 1.2), Complex::new(1.0, -1.2));
+    let mut pixels = vec![7; bounds.0 * bounds.1];
+    render(
+        &mut pixels,
+        bounds,
+        upper_left,
+        lower_right,
+        &Formula::default(),
+        &Schedule {
+            chunk_rows: 4,
+            ..Schedule::new(3)
+        },
+    );
+    let mut expected = vec![0; bounds.0 * bounds.1];
+    crate::render::render(&mut expected, bounds, upper_left, lower_right);
+    assert_eq!(pixels, expected);
+
+    // a formula that isn't symmetric is computed all the way down
+    let formula = Formula {
+        expression: "z*z + c + 0.3i".parse().unwrap(),
+        ..Formula::default()
+    };
+    let render_formula = |mirror| {
+        let mut pixels = vec![0; bounds.0 * bounds.1];
+        let schedule = Schedule {
+            mirror,
+            ..Schedule::new(2)
+        };
+        render(
+            &mut pixels,
+            bounds,
+            upper_left,
+            lower_right,
+            &formula,
+            &schedule,
+        );
+        pixels
+    };
+    let pixels = render_formula(true);
+    assert_eq!(pixels, render_formula(false));
+    assert!(pixels[bounds.0..2 * bounds.0] != pixels[29 * bounds.0..]);
+}
@@ -0,0 +1,194 @@
+//! A glow pass, in which the filaments of the set bleed into their
+//! surroundings, as if they were giving off light.
+//!
+//! The filaments are the pixels that took a long time to escape, so the
+//! escape times are blurred with a kernel and the result is laid over the
+//! image. In the grayscale palette, where those pixels are dark, the glow is
+//! a dark haze gathering where the filaments are dense.
+
+use std::str::FromStr;
+
+/// The shape of the blur spreading the glow.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Kernel {
+    /// A soft glow, fading out smoothly.
+    Gaussian,
+    /// A glow with a sharp tail, brighter close to the filaments.
+    Exponential,
+    /// The same amount of glow all the way to the radius.
+    Box,
+}
+
+impl FromStr for Kernel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Kernel, String> {
+        match s {
+            "gaussian" => Ok(Kernel::Gaussian),
+            "exponential" => Ok(Kernel::Exponential),
+            "box" => Ok(Kernel::Box),
+            _ => Err(format!("unknown glow kernel {}", s)),
+        }
+    }
+}
+
+#[test]
+fn test_parse_kernel() {
+    assert_eq!("gaussian".parse(), Ok(Kernel::Gaussian));
+    assert_eq!("box".parse(), Ok(Kernel::Box));
+    assert!("disc".parse::<Kernel>().is_err());
+}
+
+impl Kernel {
+    /// The weights of the one dimensional kernel, from `-radius` to `radius`,
+    /// adding up to 1. Blurring rows and then columns with them blurs the
+    /// image with the two dimensional kernel.
+    pub fn weights(self, radius: usize) -> Vec<f64> {
+        let r = radius.max(1) as f64;
+        let weights: Vec<f64> = (-(radius as isize)..=radius as isize)
+            .map(|x| {
+                let x = x as f64;
+                match self {
+                    // the radius covers three standard deviations
+                    Kernel::Gaussian => (-4.5 * x * x / (r * r)).exp(),
+                    Kernel::Exponential => (-3.0 * x.abs() / r).exp(),
+                    Kernel::Box => 1.0,
+                }
+            })
+            .collect();
+        let total: f64 = weights.iter().sum();
+        weights.iter().map(|w| w / total).collect()
+    }
+}
+
+#[test]
+fn test_weights() {
+    for kernel in [Kernel::Gaussian, Kernel::Exponential, Kernel::Box] {
+        let weights = kernel.weights(4);
+        assert_eq!(weights.len(), 9);
+        assert!((weights.iter().sum::<f64>() - 1.0).abs() < 1e-12);
+        assert_eq!(weights[0], weights[8]);
+        assert!(weights[4] >= weights[0]);
+    }
+    assert_eq!(Kernel::Box.weights(0), [1.0]);
+}
+
+/// The parameters of the glow pass.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Glow {
+    pub kernel: Kernel,
+    /// How far, in pixels, the glow reaches.
+    pub radius: usize,
+    /// How strong the glow is; 1 lets a pixel surrounded by filaments turn
+    /// as dark as they are.
+    pub strength: f64,
+}
+
+impl Default for Glow {
+    fn default() -> Glow {
+        Glow {
+            kernel: Kernel::Gaussian,
+            radius: 8,
+            strength: 1.0,
+        }
+    }
+}
+
+/// Blur `field`, whose dimensions are given by `bounds`, with the separable
+/// kernel `weights`. Pixels beyond the edges count as copies of the edge.
+pub fn blur(field: &[f64], bounds: (usize, usize), weights: &[f64]) -> Vec<f64> {
+    let radius = weights.len() as isize / 2;
+    let pass = |input: &[f64], step: (isize, isize)| {
+        let mut output = vec![0.0; input.len()];
+        for y in 0..bounds.1 as isize {
+            for x in 0..bounds.0 as isize {
+                let mut sum = 0.0;
+                for (i, weight) in weights.iter().enumerate() {
+                    let offset = i as isize - radius;
+                    let sx = (x + offset * step.0).clamp(0, bounds.0 as isize - 1);
+                    let sy = (y + offset * step.1).clamp(0, bounds.1 as isize - 1);
+                    sum += weight * input[sy as usize * bounds.0 + sx as usize];
+                }
+                output[y as usize * bounds.0 + x as usize] = sum;
+            }
+        }
+        output
+    };
+    let rows = pass(field, (1, 0));
+    pass(&rows, (0, 1))
+}
+
+#[test]
+fn test_blur() {
+    let mut field = vec![0.0; 25];
+    field[12] = 9.0;
+    let blurred = blur(&field, (5, 5), &Kernel::Box.weights(1));
+    for y in 0..5 {
+        for x in 0..5 {
+            let expected = if (1..4).contains(&x) && (1..4).contains(&y) {
+                1.0
+            } else {
+                0.0
+            };
+            assert!((blurred[y * 5 + x] - expected).abs() < 1e-12);
+        }
+    }
+    // a uniform field stays as it is, edges included
+    let blurred = blur(&[2.0; 12], (4, 3), &Kernel::Gaussian.weights(3));
+    assert!(blurred.iter().all(|v| (v - 2.0).abs() < 1e-12));
+}
+
+/// Lay the glow of the filaments over `pixels`, a buffer produced by
+/// `render`, whose dimensions are given by `bounds`.
+pub fn apply(pixels: &mut [u8], bounds: (usize, usize), glow: &Glow) {
+    assert!(pixels.len() == bounds.0 * bounds.1);
+    if pixels.is_empty() {
+        return;
+    }
+
+    // how long each pixel took to escape, from 0 to 1; the interior of the
+    // set isn't a filament and doesn't glow
+    let dwell: Vec<f64> = pixels
+        .iter()
+        .map(|&p| {
+            if p == 0 {
+                0.0
+            } else {
+                (255 - p) as f64 / 255.0
+            }
+        })
+        .collect();
+    let glow_field = blur(&dwell, bounds, &glow.kernel.weights(glow.radius));
+
+    for (pixel, glow_value) in pixels.iter_mut().zip(glow_field) {
+        let glow_value = (glow_value * glow.strength).clamp(0.0, 1.0);
+        *pixel = (*pixel as f64 * (1.0 - glow_value)).round() as u8;
+    }
+}
+
+#[test]
+fn test_apply() {
+    // a dark filament down the middle of a white image
+    let bounds = (9, 3);
+    let mut pixels = vec![255; 27];
+    for row in 0..3 {
+        pixels[row * 9 + 4] = 55;
+    }
+    let glow = Glow {
+        kernel: Kernel::Exponential,
+        radius: 3,
+        strength: 1.0,
+    };
+    let original = pixels.clone();
+    apply(&mut pixels, bounds, &glow);
+
+    // the glow darkens the neighbourhood, less and less further away
+    assert!(pixels[5] < 255);
+    assert!(pixels[5] < pixels[6] && pixels[6] < pixels[7]);
+    assert_eq!(pixels[3], pixels[5]);
+    assert!(pixels[4] <= original[4]);
+    // black stays black
+    let mut black = vec![0; 4];
+    apply(&mut black, (2, 2), &glow);
+    assert_eq!(black, [0; 4]);
+}
@@ -0,0 +1,342 @@
+//! Render jobs described as JSON, e.g. read from stdin in a pipeline.
+//!
+//! A job holds the same parameters as the command line, and is turned back
+//! into command line options by `Job::to_args`, so there is a single place
+//! where parameters are interpreted:
+//!
+//! ```json
+//! {"output": "-", "pixels": [1000, 750], "center": [-0.75, 0.1], "zoom": 20}
+//! ```
+//!
+//! Pairs can be given either as arrays of two numbers or as strings in the
+//! command line syntax, like `"1000x750"` or `"-0.75,0.1"`. The center can
+//! also be a compact location, like `"@-0.75,0.1,z=20"`, see `locator`.
+//!
+//! Jobs may come from untrusted sources, so they are checked before they are
+//! turned into options: numbers must be finite and within the limits below,
+//! and every problem found is reported, not just the first.
+
+use std::io::Read;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    locator::{self, Locator},
+    parse_complex, parse_pair, schema, DecimalSeparator, Error, Result,
+};
+
+/// The largest width or height of an image, of a job or any other render.
+pub const MAX_SIDE: usize = 1 << 16;
+
+/// The most samples a job may ask for per pixel.
+pub const MAX_SAMPLES: usize = 1024;
+
+/// The most points a job may ask to compute: its pixels times its samples;
+/// other renders may have as many pixels.
+pub const MAX_POINTS: u64 = 1 << 32;
+
+/// The longest job description read.
+const MAX_JOB_BYTES: u64 = 1 << 20;
+
+/// A pair of values, as text or as two numbers.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(untagged)]
+pub enum Pair<T> {
+    Text(String),
+    Numbers(T, T),
+}
+
+impl<T: std::fmt::Display> Pair<T> {
+    /// Write the pair the way the command line expects it, with `separator`
+    /// between the two numbers.
+    pub fn to_arg(&self, separator: char) -> String {
+        match self {
+            Pair::Text(text) => text.clone(),
+            Pair::Numbers(a, b) => format!("{}{}{}", a, separator, b),
+        }
+    }
+}
+
+/// The parameters of a render. Every field is optional, and stands for the
+/// command line option of the same name.
+#[derive(Debug, Default, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Job {
+    pub output: Option<String>,
+    pub pixels: Option<Pair<usize>>,
+    pub upper_left: Option<Pair<f64>>,
+    pub lower_right: Option<Pair<f64>>,
+    pub center: Option<Pair<f64>>,
+    pub zoom: Option<f64>,
+    pub from: Option<String>,
+    pub location: Option<String>,
+    pub strategy: Option<String>,
+    pub chunk_rows: Option<usize>,
+    pub samples: Option<usize>,
+    pub jitter: Option<bool>,
+    pub jitter_seed: Option<u64>,
+    pub report: Option<String>,
+    pub on_exists: Option<String>,
+    pub palette: Option<String>,
+}
+
+impl Job {
+    /// Parse a job from its JSON description, and check it.
+    pub fn from_json(json: &str) -> Result<Job> {
+        let value = serde_json::from_str(json).map_err(|e| Error::parse("job", &e.to_string()))?;
+        Job::from_value(value)
+    }
+
+    /// Build a job from its JSON value, and check it.
+    pub fn from_value(mut value: serde_json::Value) -> Result<Job> {
+        let version = schema::take_version("job", &mut value)?;
+        if version > schema::VERSION {
+            let mut dropped = Vec::new();
+            schema::drop_unknown::<Job>(&mut value, "", &mut dropped);
+            schema::warn_newer("the job", version, schema::VERSION, &dropped);
+        }
+        let job: Job =
+            serde_json::from_value(value).map_err(|e| Error::parse("job", &e.to_string()))?;
+        let problems = job.problems();
+        if !problems.is_empty() {
+            return Err(Error::parse("job", &problems.join("; ")));
+        }
+        Ok(job)
+    }
+
+    /// Everything wrong with the job's parameters.
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+
+        let pixels = match &self.pixels {
+            Some(Pair::Numbers(width, height)) => Some((*width, *height)),
+            Some(Pair::Text(text)) => {
+                let pixels = parse_pair::<usize>(text, 'x');
+                if pixels.is_none() {
+                    problems.push(format!("pixels {:?} are not WIDTHxHEIGHT", text));
+                }
+                pixels
+            }
+            None => None,
+        };
+        if let Some((width, height)) = pixels {
+            if width == 0 || height == 0 || width > MAX_SIDE || height > MAX_SIDE {
+                problems.push(format!(
+                    "pixels {}x{} must be from 1 to {} a side",
+                    width, height, MAX_SIDE
+                ));
+            }
+        }
+
+        for (name, point) in [
+            ("upper_left", &self.upper_left),
+            ("lower_right", &self.lower_right),
+            ("center", &self.center),
+        ] {
+            let (re, im) = match point {
+                Some(Pair::Numbers(re, im)) => (*re, *im),
+                // a compact location, which checks its numbers itself
+                Some(Pair::Text(text)) if name == "center" && locator::is_locator(text) => {
+                    if let Err(problem) = text.parse::<Locator>() {
+                        problems.push(format!("center {:?}: {}", text, problem));
+                    }
+                    continue;
+                }
+                Some(Pair::Text(text)) => match parse_complex(text, DecimalSeparator::Auto) {
+                    Some(point) => (point.re, point.im),
+                    None => {
+                        problems.push(format!("{} {:?} is not a point", name, text));
+                        continue;
+                    }
+                },
+                None => continue,
+            };
+            if !re.is_finite() || !im.is_finite() {
+                problems.push(format!("{} ({}, {}) must be finite", name, re, im));
+            }
+        }
+
+        if let Some(zoom) = self.zoom {
+            if !(zoom > 0.0 && zoom.is_finite()) {
+                problems.push(format!("zoom {} must be positive and finite", zoom));
+            }
+        }
+        if self.chunk_rows == Some(0) {
+            problems.push("chunk_rows must be at least 1".to_string());
+        }
+        let samples = self.samples.unwrap_or(1);
+        if samples == 0 || samples > MAX_SAMPLES {
+            problems.push(format!(
+                "samples {} must be from 1 to {}",
+                samples, MAX_SAMPLES
+            ));
+        }
+        if let Some((width, height)) = pixels {
+            let points = (width as u64)
+                .checked_mul(height as u64)
+                .and_then(|pixels| pixels.checked_mul(samples as u64));
+            if points.is_none_or(|points| points > MAX_POINTS) {
+                problems.push(format!(
+                    "{}x{} pixels of {} samples are more than {} points",
+                    width, height, samples, MAX_POINTS
+                ));
+            }
+        }
+
+        for (name, file) in [
+            ("output", &self.output),
+            ("from", &self.from),
+            ("location", &self.location),
+            ("report", &self.report),
+            ("palette", &self.palette),
+        ] {
+            if file.as_deref().is_some_and(|file| file.contains('\0')) {
+                problems.push(format!("{} must not contain NUL characters", name));
+            }
+        }
+        problems
+    }
+
+    /// Turn the job into the command line options that ask for the same
+    /// render.
+    pub fn to_args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        let mut push = |name: &str, value: Option<String>| {
+            if let Some(value) = value {
+                args.push(name.to_string());
+                args.push(value);
+            }
+        };
+
+        push("--output", self.output.clone());
+        push("--pixels", self.pixels.as_ref().map(|p| p.to_arg('x')));
+        push(
+            "--upper-left",
+            self.upper_left.as_ref().map(|p| p.to_arg(',')),
+        );
+        push(
+            "--lower-right",
+            self.lower_right.as_ref().map(|p| p.to_arg(',')),
+        );
+        push("--center", self.center.as_ref().map(|p| p.to_arg(',')));
+        push("--zoom", self.zoom.map(|z| z.to_string()));
+        push("--from", self.from.clone());
+        push("--location", self.location.clone());
+        push("--strategy", self.strategy.clone());
+        push("--chunk-rows", self.chunk_rows.map(|r| r.to_string()));
+        push("--samples", self.samples.map(|s| s.to_string()));
+        push("--jitter-seed", self.jitter_seed.map(|s| s.to_string()));
+        push("--report", self.report.clone());
+        push("--on-exists", self.on_exists.clone());
+        push("--palette", self.palette.clone());
+        if self.jitter == Some(true) {
+            args.push("--jitter".to_string());
+        }
+        args
+    }
+}
+
+#[test]
+fn test_job_to_args() {
+    let job = Job::from_json(
+        r#"{"output": "-", "pixels": [1000, 750], "upper_left": "-1.2,0.35",
+            "lower_right": [-1.0, 0.2], "strategy": "border-trace"}"#,
+    )
+    .unwrap();
+    assert_eq!(
+        job.to_args(),
+        [
+            "--output",
+            "-",
+            "--pixels",
+            "1000x750",
+            "--upper-left",
+            "-1.2,0.35",
+            "--lower-right",
+            "-1,0.2",
+            "--strategy",
+            "border-trace"
+        ]
+    );
+
+    let job = Job::from_json(r#"{"center": [-0.75, 0.1], "zoom": 20}"#).unwrap();
+    assert_eq!(job.to_args(), ["--center", "-0.75,0.1", "--zoom", "20"]);
+
+    let job = Job::from_json(r#"{"center": "@-0.75,0.1,z=2^10"}"#).unwrap();
+    assert_eq!(job.to_args(), ["--center", "@-0.75,0.1,z=2^10"]);
+
+    let job = Job::from_json(r#"{"samples": 4, "jitter": true}"#).unwrap();
+    assert_eq!(job.to_args(), ["--samples", "4", "--jitter"]);
+
+    let job = Job::from_json(r#"{"palette": "fire"}"#).unwrap();
+    assert_eq!(job.to_args(), ["--palette", "fire"]);
+
+    assert!(Job::from_json(r#"{"pixles": "10x10"}"#).is_err());
+    assert!(Job::from_json(r#"{"pixels": [10]}"#).is_err());
+    assert_eq!(Job::from_json("{}").unwrap(), Job::default());
+}
+
+#[test]
+fn test_job_problems() {
+    let job = Job::from_json(r#"{"pixels": "640x480", "center": "-0,75;0,1", "samples": 4}"#);
+    assert!(job.is_ok());
+
+    let problems = |json| Job::from_json(json).unwrap_err().to_string();
+    assert!(problems(r#"{"pixels": [0, 10]}"#).contains("from 1 to 65536"));
+    assert!(problems(r#"{"pixels": "big"}"#).contains("WIDTHxHEIGHT"));
+    assert!(problems(r#"{"center": "NaN,0"}"#).contains("finite"));
+    assert!(problems(r#"{"upper_left": "inf,-inf"}"#).contains("finite"));
+    assert!(problems(r#"{"lower_right": "1,2,3"}"#).contains("not a point"));
+    assert!(problems(r#"{"center": "@0,0,z=-1"}"#).contains("positive"));
+    assert!(problems(r#"{"upper_left": "@0,0"}"#).contains("not a point"));
+    assert!(problems(r#"{"zoom": -1}"#).contains("zoom"));
+    assert!(problems(r#"{"zoom": 1e999}"#).contains("invalid job"));
+    assert!(problems(r#"{"samples": 100000}"#).contains("samples"));
+    assert!(problems(r#"{"chunk_rows": 0}"#).contains("chunk_rows"));
+    assert!(problems(r#"{"output": "a\u0000b"}"#).contains("NUL"));
+    assert!(problems(r#"{"pixels": [65536, 65536], "samples": 2}"#).contains("points"));
+
+    // every problem is reported at once
+    let all = problems(r#"{"pixels": [0, 0], "zoom": 0, "samples": 0}"#);
+    assert_eq!(all.matches("must be").count(), 3, "{}", all);
+}
+
+/// Read a job from the file named `source`, or from stdin if it is `-`.
+pub fn read_job(source: &str) -> Result<Job> {
+    let mut json = String::new();
+    if source == "-" {
+        std::io::stdin()
+            .take(MAX_JOB_BYTES + 1)
+            .read_to_string(&mut json)
+            .map_err(Error::io("stdin"))?;
+    } else {
+        std::fs::File::open(crate::platform::path(source))
+            .and_then(|file| file.take(MAX_JOB_BYTES + 1).read_to_string(&mut json))
+            .map_err(Error::io(source))?;
+    }
+    if json.len() as u64 > MAX_JOB_BYTES {
+        return Err(Error::parse(
+            "job",
+            &format!("{} is longer than {} bytes", source, MAX_JOB_BYTES),
+        ));
+    }
+    Job::from_json(&json)
+}
+
+/// Replace every `--job SOURCE` in `args` by the options the job stands for,
+/// so that options given after it override the job's.
+pub fn expand_jobs(args: &[String]) -> Result<Vec<String>> {
+    let mut expanded = Vec::with_capacity(args.len());
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg == "--job" {
+            let source = args
+                .next()
+                .ok_or_else(|| Error::Usage("--job requires a file name or -".to_string()))?;
+            expanded.extend(read_job(source)?.to_args());
+        } else {
+            expanded.push(arg.clone());
+        }
+    }
+    Ok(expanded)
+}
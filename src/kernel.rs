@@ -0,0 +1,349 @@
+//! The innermost loops of the renderer on their own: plain functions of
+//! `f64`s that neither allocate nor touch any state, so that GPU shaders,
+//! bindings in other languages and other ports can be checked against them
+//! bit-for-bit.
+//!
+//! # Numerical contract
+//!
+//! Every kernel computes in IEEE 754 binary64, rounding to nearest, one
+//! operation at a time in the order its documentation gives: no fused
+//! multiply-adds, no reassociation, no extended precision. Rust never fuses
+//! or reorders floating point operations on its own, so a port that does the
+//! same, such as a shader compiled with contraction off, gets the same
+//! escape times for the same inputs.
+//!
+//! An orbit `z` starts at 0 and, from `z = (x, y)`, goes on to
+//!
+//! ```text
+//! x2 = x * x
+//! y2 = y * y
+//! y' = (x + x) * y + c.1
+//! x' = (x2 - y2) + c.0
+//! ```
+//!
+//! It has escaped once `x2 + y2 > bailout * bailout`, `bailout * bailout`
+//! being rounded once; the escape time is how many steps it took to get
+//! there, from 0, and a point whose orbit hasn't escaped after `limit` steps
+//! is taken to be in the set. Points are pairs `(re, im)`.
+
+/// How close an orbit must come back to an earlier point of itself for
+/// `escape_time_periodic` to take it to be a cycle, squared.
+pub const PERIODICITY_EPSILON: f64 = 1e-20;
+
+/// The escape time of `c`, if its orbit leaves the circle of radius
+/// `bailout` within `limit` steps.
+pub fn escape_time(c: (f64, f64), limit: usize, bailout: f64) -> Option<usize> {
+    let bailout = bailout * bailout;
+    let (mut x, mut y) = (0.0, 0.0);
+    for i in 0..limit {
+        let (x2, y2) = (x * x, y * y);
+        if x2 + y2 > bailout {
+            return Some(i);
+        }
+        y = (x + x) * y + c.1;
+        x = x2 - y2 + c.0;
+    }
+    None
+}
+
+#[test]
+fn test_escape_time() {
+    // values for ports to check themselves against
+    assert_eq!(escape_time((0.3, 0.6), 1000, 2.0), Some(15));
+    assert_eq!(escape_time((-0.75, 0.1), 1000, 2.0), Some(33));
+    assert_eq!(escape_time((-2.5, 1.0), 1000, 2.0), Some(1));
+    assert_eq!(escape_time((0.3, 0.5), 1000, 2.0), None);
+    assert_eq!(escape_time((0.3, 0.6), 1000, 100.0), Some(17));
+    assert_eq!(escape_time((-0.75, 0.1), 1000, 100.0), Some(36));
+    assert_eq!(escape_time((3.0, 0.0), 0, 2.0), None);
+}
+
+/// Like `escape_time`, but stopping early at cycles, as the renderer does:
+/// following Brent, the orbit is saved at every power of two steps, and as
+/// soon as it comes back within `PERIODICITY_EPSILON` of the saved point,
+/// `c` is taken to be in the set. Returns the escape time along with how
+/// many steps were taken.
+///
+/// Once per step, after `z` is updated, `dx = x - sx`, `dy = y - sy` and
+/// `dx * dx + dy * dy < PERIODICITY_EPSILON` are computed from the saved
+/// point `(sx, sy)`.
+pub fn escape_time_periodic(c: (f64, f64), limit: usize, bailout: f64) -> (Option<usize>, usize) {
+    let bailout = bailout * bailout;
+    let (mut x, mut y) = (0.0, 0.0);
+    let mut saved = (x, y);
+    let mut period = 0;
+    let mut next_save = 1;
+    for i in 0..limit {
+        let (x2, y2) = (x * x, y * y);
+        if x2 + y2 > bailout {
+            return (Some(i), i);
+        }
+        y = (x + x) * y + c.1;
+        x = x2 - y2 + c.0;
+
+        let (dx, dy) = (x - saved.0, y - saved.1);
+        if dx * dx + dy * dy < PERIODICITY_EPSILON {
+            return (None, i + 1);
+        }
+        period += 1;
+        if period == next_save {
+            period = 0;
+            next_save *= 2;
+            saved = (x, y);
+        }
+    }
+    (None, limit)
+}
+
+#[test]
+fn test_escape_time_periodic() {
+    // a fixed point, caught at the second step
+    assert_eq!(escape_time_periodic((0.0, 0.0), 1000, 2.0), (None, 1));
+    assert_eq!(escape_time_periodic((-1.0, 0.0), 1000, 2.0).0, None);
+    assert!(escape_time_periodic((-0.1, 0.1), 1000, 2.0).1 < 1000);
+    for i in 0..200 {
+        let c = (-2.0 + 0.015 * i as f64, 0.6 - 0.006 * i as f64);
+        let (escape, steps) = escape_time_periodic(c, 500, 2.0);
+        if escape.is_some() {
+            assert_eq!(escape, escape_time(c, 500, 2.0));
+            assert_eq!(escape, Some(steps));
+        }
+    }
+}
+
+/// `escape_time` for `N` points at once, one in each lane. The lanes are
+/// stepped together, without branching, which the compiler turns into SIMD
+/// instructions where the target has them; every lane comes out as
+/// `escape_time` would for its point alone.
+pub fn escape_time_lanes<const N: usize>(
+    c: [(f64, f64); N],
+    limit: usize,
+    bailout: f64,
+) -> [Option<usize>; N] {
+    let bailout = bailout * bailout;
+    let (mut x, mut y) = ([0.0; N], [0.0; N]);
+    let mut escapes = [None; N];
+    let mut running = N;
+    let mut i = 0;
+    while i < limit && running > 0 {
+        for lane in 0..N {
+            let (x2, y2) = (x[lane] * x[lane], y[lane] * y[lane]);
+            let escaped = x2 + y2 > bailout;
+            if escaped && escapes[lane].is_none() {
+                escapes[lane] = Some(i);
+                running -= 1;
+            }
+            // escaped lanes go on stepping, their results are set
+            let (x0, y0) = (x[lane], y[lane]);
+            y[lane] = (x0 + x0) * y0 + c[lane].1;
+            x[lane] = x2 - y2 + c[lane].0;
+        }
+        i += 1;
+    }
+    escapes
+}
+
+#[test]
+fn test_escape_time_lanes() {
+    let mut c = [(0.0, 0.0); 8];
+    for (i, point) in c.iter_mut().enumerate() {
+        *point = (-2.1 + 0.37 * i as f64, 0.9 - 0.21 * i as f64);
+    }
+    let lanes = escape_time_lanes(c, 300, 2.0);
+    for (point, escape) in c.iter().zip(lanes) {
+        assert_eq!(escape, escape_time(*point, 300, 2.0));
+    }
+    assert_eq!(escape_time_lanes([(0.3, 0.6)], 1000, 100.0), [Some(17)]);
+    assert_eq!(escape_time_lanes::<0>([], 1000, 2.0), []);
+}
+
+/// `escape_time_periodic` for `N` points at once, one in each lane, as
+/// `escape_time_lanes` steps them. Every lane saves its orbit at the same
+/// steps, as they all start together, and comes out as
+/// `escape_time_periodic` would for its point alone.
+#[inline(always)]
+pub fn escape_time_periodic_lanes<const N: usize>(
+    c: [(f64, f64); N],
+    limit: usize,
+    bailout: f64,
+) -> [(Option<usize>, usize); N] {
+    let bailout = bailout * bailout;
+    let (mut x, mut y) = ([0.0; N], [0.0; N]);
+    let (mut saved_x, mut saved_y) = ([0.0; N], [0.0; N]);
+    let mut steps = [limit; N];
+    let mut escaped = [false; N];
+    let mut stopped = [false; N];
+    let mut period = 0;
+    let mut next_save = 1;
+    let mut i = 0;
+    while i < limit {
+        // without branching, so that the lanes are stepped as one: stopped
+        // lanes go on stepping, their results are set
+        for lane in 0..N {
+            let (x0, y0) = (x[lane], y[lane]);
+            let (x2, y2) = (x0 * x0, y0 * y0);
+            let escapes = x2 + y2 > bailout;
+            y[lane] = (x0 + x0) * y0 + c[lane].1;
+            x[lane] = x2 - y2 + c[lane].0;
+            let (dx, dy) = (x[lane] - saved_x[lane], y[lane] - saved_y[lane]);
+            let cycles = dx * dx + dy * dy < PERIODICITY_EPSILON;
+            let stops = !stopped[lane] & (escapes | cycles);
+            steps[lane] = if stops {
+                i + !escapes as usize
+            } else {
+                steps[lane]
+            };
+            escaped[lane] |= stops & escapes;
+            stopped[lane] |= stops;
+        }
+        period += 1;
+        if period == next_save {
+            period = 0;
+            next_save *= 2;
+            saved_x = x;
+            saved_y = y;
+        }
+        i += 1;
+        if stopped.iter().all(|&stopped| stopped) {
+            break;
+        }
+    }
+    std::array::from_fn(|lane| (escaped[lane].then_some(steps[lane]), steps[lane]))
+}
+
+#[test]
+fn test_escape_time_periodic_lanes() {
+    let mut c = [(0.0, 0.0); 8];
+    for (i, point) in c.iter_mut().enumerate() {
+        *point = (-2.1 + 0.37 * i as f64, 0.9 - 0.21 * i as f64);
+    }
+    c[6] = (-1.0, 0.0);
+    let lanes = escape_time_periodic_lanes(c, 300, 2.0);
+    for (point, result) in c.iter().zip(lanes) {
+        assert_eq!(result, escape_time_periodic(*point, 300, 2.0));
+    }
+    assert_eq!(
+        escape_time_periodic_lanes([(0.0, 0.0), (0.3, 0.6)], 1000, 2.0),
+        [(None, 1), (Some(15), 15)]
+    );
+    assert_eq!(escape_time_periodic_lanes::<0>([], 1000, 2.0), []);
+}
+
+/// Fill `orbit` with the orbit of `c`, the reference of `escape_time_delta`,
+/// from 0 on, stopping once it escapes or `orbit` is full. Returns how many
+/// points of it were filled, the last being the first outside the circle
+/// of radius `bailout` if it escaped.
+pub fn reference_orbit(c: (f64, f64), orbit: &mut [(f64, f64)], bailout: f64) -> usize {
+    let bailout = bailout * bailout;
+    let (mut x, mut y) = (0.0, 0.0);
+    for (i, point) in orbit.iter_mut().enumerate() {
+        *point = (x, y);
+        let (x2, y2) = (x * x, y * y);
+        if x2 + y2 > bailout {
+            return i + 1;
+        }
+        y = (x + x) * y + c.1;
+        x = x2 - y2 + c.0;
+    }
+    orbit.len()
+}
+
+/// The escape time of the point `dc` away from the point whose orbit is
+/// `reference`, as `reference_orbit` fills it, computed from the difference
+/// `d` between the two orbits rather than from the point itself. A view
+/// deeper than `f64` can tell points apart in only needs the reference in
+/// high precision, rounded into `reference`; its pixels differ by a `dc`
+/// that `f64` holds well.
+///
+/// `d = (dx, dy)` starts at 0 and `n` at 0. From the `n`th point `(zx, zy)`
+/// of `reference`, each step computes
+///
+/// ```text
+/// x = zx + dx
+/// y = zy + dy
+/// escaped if x * x + y * y > bailout * bailout
+/// rebased if x * x + y * y < dx * dx + dy * dy, or n is the last point:
+///     (dx, dy) = (x, y), n = 0, (zx, zy) = reference[0]
+/// ax = (zx + zx) + dx
+/// ay = (zy + zy) + dy
+/// dx' = (ax * dx - ay * dy) + dc.0
+/// dy' = (ax * dy + ay * dx) + dc.1
+/// ```
+///
+/// with each product rounded on its own, and then `n` goes on to the next
+/// point. Rebasing, which sets `d` to the orbit itself, relative to the
+/// start of the reference at 0, avoids the glitches of perturbation, so one
+/// reference serves a whole view. A `reference` of fewer than two points is
+/// taken to be the orbit of 0, which makes this `escape_time` of `dc`.
+pub fn escape_time_delta(
+    reference: &[(f64, f64)],
+    dc: (f64, f64),
+    limit: usize,
+    bailout: f64,
+) -> Option<usize> {
+    let reference = match reference {
+        [] | [_] => &[(0.0, 0.0); 2],
+        reference => reference,
+    };
+    let bailout = bailout * bailout;
+    let (mut dx, mut dy) = (0.0, 0.0);
+    let mut n = 0;
+    for i in 0..limit {
+        let (zx, zy) = reference[n];
+        let (x, y) = (zx + dx, zy + dy);
+        let z2 = x * x + y * y;
+        if z2 > bailout {
+            return Some(i);
+        }
+        if z2 < dx * dx + dy * dy || n + 1 == reference.len() {
+            (dx, dy) = (x, y);
+            n = 0;
+        }
+        let (zx, zy) = reference[n];
+        let (ax, ay) = (zx + zx + dx, zy + zy + dy);
+        (dx, dy) = (ax * dx - ay * dy + dc.0, ax * dy + ay * dx + dc.1);
+        n += 1;
+    }
+    None
+}
+
+#[test]
+fn test_escape_time_delta() {
+    // with nothing to go from, the same as going from scratch
+    for c in [(0.3, 0.6), (-0.75, 0.1), (-2.5, 1.0), (0.3, 0.5)] {
+        assert_eq!(
+            escape_time_delta(&[], c, 1000, 2.0),
+            escape_time(c, 1000, 2.0)
+        );
+    }
+
+    // around a reference, the same as the direct escape times but for a few
+    // long orbits, which rounding sends elsewhere
+    let center = (-0.743643887037151, 0.13182590420533);
+    let mut orbit = [(0.0, 0.0); 1000];
+    let length = reference_orbit(center, &mut orbit, 2.0);
+    assert_eq!(length, 1000);
+    let (mut same, mut count) = (0, 0);
+    for i in 0..30 {
+        for j in 0..30 {
+            let dc = (1e-6 * (i as f64 - 15.0), 1e-6 * (j as f64 - 15.0));
+            let c = (center.0 + dc.0, center.1 + dc.1);
+            let direct = escape_time(c, 1000, 2.0);
+            let delta = escape_time_delta(&orbit[..length], dc, 1000, 2.0);
+            if direct.is_some_and(|direct| direct < 400) {
+                assert_eq!(direct, delta, "{:?}", c);
+            }
+            same += (direct == delta) as usize;
+            count += 1;
+        }
+    }
+    assert!(same > count * 95 / 100);
+
+    // an escaping reference is rebased on before it runs out
+    let length = reference_orbit((0.3, 0.6), &mut orbit, 2.0);
+    assert_eq!(length, 16);
+    assert_eq!(
+        escape_time_delta(&orbit[..length], (-1.05, -0.5), 1000, 2.0),
+        escape_time((-0.75, 0.1), 1000, 2.0)
+    );
+}
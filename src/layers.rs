@@ -0,0 +1,303 @@
+//! The image split into layers for compositing, for `--layers`: the colored
+//! exterior and interior each on a transparent background, a mask of the
+//! boundary of the set, and the annotations, a crosshair on the center and a
+//! scale bar, on their own.
+//!
+//! The exterior and interior are told apart by the escape times, before the
+//! image is styled, and take their colors from the styled image, so laying
+//! them over each other gives the image back.
+//!
+//! `--layers-exr` writes them all in one OpenEXR file instead, with the
+//! derivative field as floats.
+
+use num::Complex;
+
+use crate::{derivative::Sample, exr::Channel};
+
+/// The names of the layers, which go into their file names, in the order
+/// `Layers::all` gives them.
+pub const NAMES: [&str; 4] = ["exterior", "interior", "boundary", "annotations"];
+
+/// How far from the boundary the mask fades out, in pixels.
+const BOUNDARY_WIDTH: f64 = 2.0;
+
+/// The layers of an image.
+#[derive(Debug, PartialEq)]
+pub struct Layers {
+    /// RGBA, transparent over the set.
+    pub exterior: Vec<u8>,
+    /// RGBA, transparent outside the set.
+    pub interior: Vec<u8>,
+    /// Grayscale, white on the boundary, fading to black `BOUNDARY_WIDTH`
+    /// pixels away from it.
+    pub boundary: Vec<u8>,
+    /// RGBA, white marks on a transparent background.
+    pub annotations: Vec<u8>,
+    /// How long the scale bar is, on the complex plane.
+    pub scale: f64,
+}
+
+/// The level `level` of an sRGB image, from 0 to 255, on the linear scale of
+/// OpenEXR images.
+fn linear(level: u8) -> f32 {
+    let level = level as f32 / 255.0;
+    if level <= 0.04045 {
+        level / 12.92
+    } else {
+        ((level + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+#[test]
+fn test_linear() {
+    assert_eq!(linear(0), 0.0);
+    assert_eq!(linear(255), 1.0);
+    assert!((linear(128) - 0.2158).abs() < 1e-4);
+}
+
+/// The channels of the RGBA `pixels` of the layer `layer`, or of the
+/// default layer if it is empty.
+fn rgba_channels(layer: &str, pixels: &[u8]) -> Vec<Channel> {
+    ["R", "G", "B", "A"]
+        .into_iter()
+        .enumerate()
+        .map(|(i, name)| {
+            let values = pixels.iter().skip(i).step_by(4);
+            let values = if name == "A" {
+                values.map(|&alpha| alpha as f32 / 255.0).collect()
+            } else {
+                values.map(|&level| linear(level)).collect()
+            };
+            let name = match layer {
+                "" => name.to_string(),
+                layer => format!("{}.{}", layer, name),
+            };
+            Channel { name, values }
+        })
+        .collect()
+}
+
+impl Layers {
+    /// The layers, in the order of `NAMES`.
+    pub fn all(&self) -> [&[u8]; 4] {
+        [
+            &self.exterior,
+            &self.interior,
+            &self.boundary,
+            &self.annotations,
+        ]
+    }
+
+    /// The layers, and the derivative field `samples` they were split
+    /// with, as the channels of an OpenEXR image: the image itself is the
+    /// default layer, `R`, `G`, `B` and `A`, the others are `exterior.R` and
+    /// so on, `boundary.Y`, and the normal and the distance of `derivative`
+    /// as its `X`, `Y` and `Z`. Colors are made linear, alpha and the
+    /// boundary mask are not.
+    pub fn exr_channels(&self, samples: &[Sample]) -> Vec<Channel> {
+        // the exterior and the interior don't overlap, so they add up
+        let image: Vec<u8> = self
+            .exterior
+            .iter()
+            .zip(&self.interior)
+            .map(|(outside, inside)| outside + inside)
+            .collect();
+        let mut channels: Vec<Channel> = [
+            ("", &image),
+            ("exterior", &self.exterior),
+            ("interior", &self.interior),
+            ("annotations", &self.annotations),
+        ]
+        .into_iter()
+        .flat_map(|(layer, pixels)| rgba_channels(layer, pixels))
+        .collect();
+        let boundary = self.boundary.iter().map(|&level| level as f32 / 255.0);
+        channels.push(Channel::new("boundary.Y", boundary.collect()));
+        for (i, axis) in ["X", "Y", "Z"].into_iter().enumerate() {
+            let values = samples.iter().map(|sample| sample[i]).collect();
+            channels.push(Channel::new(&format!("derivative.{}", axis), values));
+        }
+        channels
+    }
+}
+
+/// Split the image whose escape times are `escapes`, one gray level a pixel,
+/// and which was styled into `styled`, gray or RGB, into layers. `samples`
+/// is its derivative field, which the boundary mask is drawn from.
+pub fn split(
+    escapes: &[u8],
+    styled: &[u8],
+    samples: &[Sample],
+    bounds: (usize, usize),
+    (upper_left, lower_right): (Complex<f64>, Complex<f64>),
+) -> Layers {
+    let channels = styled.len() / escapes.len().max(1);
+    let (mut exterior, mut interior) = (Vec::new(), Vec::new());
+    for (&escape, color) in escapes.iter().zip(styled.chunks(channels.max(1))) {
+        let rgba = match *color {
+            [gray] => [gray, gray, gray, 255],
+            [r, g, b] => [r, g, b, 255],
+            _ => unreachable!("images are gray or RGB"),
+        };
+        let (inside, outside) = if escape == 0 {
+            (rgba, [0; 4])
+        } else {
+            ([0; 4], rgba)
+        };
+        interior.extend(inside);
+        exterior.extend(outside);
+    }
+    let (annotations, scale) = annotations(bounds, upper_left, lower_right);
+    Layers {
+        exterior,
+        interior,
+        boundary: boundary(escapes, samples, bounds),
+        annotations,
+        scale,
+    }
+}
+
+/// The boundary mask: exterior pixels by their distance estimate, and
+/// interior pixels next to the exterior, which are on the boundary.
+fn boundary(escapes: &[u8], samples: &[Sample], bounds: (usize, usize)) -> Vec<u8> {
+    let (width, height) = bounds;
+    let level = |distance: f64| (255.0 * (1.0 - distance / BOUNDARY_WIDTH).clamp(0.0, 1.0)) as u8;
+    (0..width * height)
+        .map(|i| {
+            if escapes[i] != 0 {
+                return level(samples[i][2] as f64);
+            }
+            let (x, y) = (i % width, i / width);
+            let outside =
+                |x: usize, y: usize| x < width && y < height && escapes[y * width + x] != 0;
+            let beside = outside(x.wrapping_sub(1), y)
+                || outside(x + 1, y)
+                || outside(x, y.wrapping_sub(1))
+                || outside(x, y + 1);
+            if beside {
+                255
+            } else {
+                0
+            }
+        })
+        .collect()
+}
+
+/// The annotations of the view from `upper_left` to `lower_right`: a
+/// crosshair on its center, and in the lower left corner a bar a fifth of
+/// the width of the image long at most, as long as 1, 2 or 5 times a power
+/// of ten on the plane, which is returned too.
+fn annotations(
+    bounds: (usize, usize),
+    upper_left: Complex<f64>,
+    lower_right: Complex<f64>,
+) -> (Vec<u8>, f64) {
+    let (width, height) = bounds;
+    let mut rgba = vec![0; 4 * width * height];
+    // marks off the image, wrapped around below zero, are left out
+    let mut mark = |x: usize, y: usize| {
+        if x < width && y < height {
+            rgba[4 * (y * width + x)..][..4].copy_from_slice(&[255; 4]);
+        }
+    };
+
+    let arm = (width.min(height) / 20).max(2);
+    let (center_x, center_y) = (width / 2, height / 2);
+    for offset in 0..=2 * arm {
+        mark((center_x + offset).wrapping_sub(arm), center_y);
+        mark(center_x, (center_y + offset).wrapping_sub(arm));
+    }
+
+    let pixel_size = (lower_right.re - upper_left.re) / width.max(1) as f64;
+    let longest = pixel_size * (width / 5).max(1) as f64;
+    let power = 10f64.powf(longest.log10().floor());
+    let scale = [5.0, 2.0, 1.0]
+        .into_iter()
+        .map(|step| step * power)
+        .find(|&scale| scale <= longest)
+        .unwrap_or(power);
+    let length = (scale / pixel_size).round() as usize;
+    let (margin, thickness) = (arm, (height / 200).max(1));
+    let bottom = height.saturating_sub(margin);
+    for y in bottom.saturating_sub(thickness)..bottom {
+        for x in margin..margin + length {
+            mark(x, y);
+        }
+    }
+    // ticks at both ends
+    for y in bottom.saturating_sub(thickness + arm / 2)..bottom {
+        mark(margin, y);
+        mark(margin + length.saturating_sub(1), y);
+    }
+    (rgba, scale)
+}
+
+#[test]
+fn test_split() {
+    let bounds = (4, 1);
+    let escapes = [0, 0, 10, 200];
+    let styled = [1, 1, 1, 2, 2, 2, 3, 3, 3, 4, 4, 4];
+    let samples = [[0.0, 0.0, 0.0], [0.0; 3], [1.0, 0.0, 0.5], [1.0, 0.0, 5.0]];
+    let view = (Complex::new(-2.0, 0.5), Complex::new(2.0, -0.5));
+    let layers = split(&escapes, &styled, &samples, bounds, view);
+    assert_eq!(
+        layers.interior,
+        [1, 1, 1, 255, 2, 2, 2, 255, 0, 0, 0, 0, 0, 0, 0, 0]
+    );
+    assert_eq!(
+        layers.exterior,
+        [0, 0, 0, 0, 0, 0, 0, 0, 3, 3, 3, 255, 4, 4, 4, 255]
+    );
+    // inside, far from the exterior, beside it, half a pixel and far out
+    assert_eq!(layers.boundary, [0, 255, 191, 0]);
+    assert_eq!(layers.annotations.len(), 4 * 4);
+    assert_eq!(layers.all()[2], &layers.boundary[..]);
+
+    let channels = layers.exr_channels(&samples);
+    let names: Vec<&str> = channels.iter().map(|channel| &channel.name[..]).collect();
+    assert_eq!(names.len(), 4 * 4 + 1 + 3);
+    assert_eq!(names[..5], ["R", "G", "B", "A", "exterior.R"]);
+    assert_eq!(
+        names[16..],
+        ["boundary.Y", "derivative.X", "derivative.Y", "derivative.Z"]
+    );
+    assert_eq!(channels[3].values, [1.0; 4]);
+    assert_eq!(channels[7].values, [0.0, 0.0, 1.0, 1.0]);
+    assert_eq!(channels[16].values[1], 1.0);
+    assert_eq!(channels[19].values, [0.0, 0.0, 0.5, 5.0]);
+
+    // the interior and exterior add up to the image
+    let gray = split(&escapes, &[9, 8, 7, 6], &samples, bounds, view);
+    for i in 0..4 {
+        let color = gray.interior[4 * i] | gray.exterior[4 * i];
+        assert_eq!(color, [9, 8, 7, 6][i]);
+        assert_eq!(gray.interior[4 * i + 3] ^ gray.exterior[4 * i + 3], 255);
+    }
+}
+
+#[test]
+fn test_annotations() {
+    let bounds = (200, 100);
+    let (rgba, scale) = annotations(bounds, Complex::new(-2.0, 1.0), Complex::new(2.0, -1.0));
+    // a fifth of the width is 0.8, so the bar is 0.5 long, 25 pixels
+    assert_eq!(scale, 0.5);
+    let opaque = |x: usize, y: usize| rgba[4 * (y * bounds.0 + x) + 3] == 255;
+    assert!(opaque(100, 50));
+    assert!(opaque(100, 45) && opaque(105, 50));
+    assert!(!opaque(110, 60));
+    let bar: Vec<usize> = (0..bounds.0).filter(|&x| opaque(x, 94)).collect();
+    assert_eq!(bar.len(), 25, "{:?}", bar);
+    assert!(!opaque(50, 94));
+
+    let (_, deep) = annotations(bounds, Complex::new(0.0, 1e-9), Complex::new(3e-9, 0.0));
+    assert!((deep - 5e-10).abs() < 1e-24, "{}", deep);
+    let (_, tiny) = annotations(bounds, Complex::new(0.0, 1e-9), Complex::new(1.1e-9, 0.0));
+    assert!((tiny - 2e-10).abs() < 1e-24, "{}", tiny);
+    // what render::corners_from_center gives is annotated too
+    let (upper_left, lower_right) = crate::render::corners_from_center(
+        Complex::new(-0.5, 0.0),
+        crate::units::Zoom(1.0),
+        bounds,
+    );
+    assert_eq!(annotations(bounds, upper_left, lower_right).1, 0.5);
+}
@@ -0,0 +1,413 @@
+//! Plotting the Mandelbrot set on PNG images.
+//!
+//! The `mandelbrot` program is a thin command line wrapper around this
+//! library: `render` fills buffers of pixels, and the functions here parse
+//! the command line arguments and write the results.
+//!
+//! Encoding PNG images needs the `png` feature, on by default. Without it the
+//! library is just the renderer, which is what the `wasm` feature wraps for
+//! JavaScript, the `ffi` feature for C and the `python` feature for Python.
+
+use std::str::FromStr;
+
+use num::Complex;
+
+pub use error::{Error, Result};
+
+pub mod anchors;
+pub mod animation;
+pub mod area;
+pub mod art;
+pub mod autopalette;
+pub mod background;
+pub mod bands;
+pub mod batch;
+pub mod bookmarks;
+pub mod buddhabrot;
+pub mod cache;
+pub mod clipboard;
+pub mod compare;
+pub mod completions;
+pub mod config;
+pub mod contour;
+pub mod coverage;
+pub mod cpu;
+pub mod deepen;
+pub mod derivative;
+#[cfg(feature = "png")]
+pub mod distributed;
+pub mod dither;
+pub mod encode;
+pub mod error;
+pub mod explore;
+pub mod expmap;
+pub mod exr;
+pub mod extend;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod find;
+pub mod fit;
+pub mod formula;
+pub mod glow;
+pub mod job;
+pub mod kernel;
+pub mod layers;
+pub mod limit;
+pub mod location;
+pub mod locator;
+#[cfg(feature = "png")]
+pub mod mapped;
+pub mod mask;
+pub mod mesh;
+pub mod mosaic;
+pub mod newton;
+pub mod orbit;
+pub mod output;
+pub mod palette;
+pub mod platform;
+pub mod poly;
+pub mod preview;
+pub mod progress;
+pub mod project;
+pub mod pyramid;
+#[cfg(feature = "python")]
+pub mod python;
+pub mod real;
+pub mod render;
+#[cfg(feature = "png")]
+pub mod report;
+pub mod reproduce;
+pub mod resample;
+pub mod result;
+pub mod sampling;
+#[cfg(all(feature = "sandbox", target_os = "linux"))]
+pub mod sandbox;
+pub mod schema;
+#[cfg(feature = "scripting")]
+pub mod script;
+#[cfg(feature = "png")]
+pub mod sequence;
+#[cfg(feature = "png")]
+pub mod server;
+pub mod service;
+pub mod share;
+pub mod signals;
+pub mod tally;
+#[cfg(feature = "png")]
+pub mod thumbnail;
+pub mod tiles;
+pub mod toml;
+pub mod traversal;
+pub mod units;
+pub mod usage;
+pub mod validate;
+pub mod video;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+pub mod watermark;
+
+/// Parse the string `s` as a coordinate pair, like `"200x300"` or `"1.0,0.4"`
+///
+/// Specifically, `s` should have the form `<left><sep><right>`, where `<sep>` is the
+/// character given by the `separator` argument, and `<left>` and `<right>` both strings
+/// that can be parsed by `T::from_str`. `separator` must be an ASCII character.
+///
+/// If `s` has the proper form, return `Some<(x, y)>`. If it doesn't parse correctly,
+/// return `None`.
+pub fn parse_pair<T: FromStr>(s: &str, separator: char) -> Option<(T, T)> {
+    match s.find(separator) {
+        Some(index) => match (T::from_str(&s[..index]), T::from_str(&s[index + 1..])) {
+            (Ok(l), Ok(r)) => Some((l, r)),
+            _ => None,
+        },
+        None => None,
+    }
+}
+
+#[test]
+fn test_parse_pair() {
+    assert_eq!(parse_pair::<i32>("", ','), None);
+    assert_eq!(parse_pair::<i32>("1,", ','), None);
+    assert_eq!(parse_pair::<f64>("0.1,0.2", ','), Some((0.1, 0.2)));
+    assert_eq!(parse_pair::<f64>("500x", 'x'), None);
+    assert_eq!(parse_pair::<i32>("500x300", 'x'), Some((500, 300)));
+}
+
+#[cfg(test)]
+proptest::proptest! {
+    #[test]
+    fn test_parse_pair_round_trip(
+        a: i32,
+        b: i32,
+        x in proptest::num::f64::NORMAL | proptest::num::f64::ZERO,
+        y in proptest::num::f64::NORMAL | proptest::num::f64::ZERO,
+    ) {
+        proptest::prop_assert_eq!(parse_pair(&format!("{}x{}", a, b), 'x'), Some((a, b)));
+        proptest::prop_assert_eq!(parse_pair(&format!("{},{}", x, y), ','), Some((x, y)));
+    }
+}
+
+/// The character numbers on the command line use to separate the integer
+/// part from the fractional part.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DecimalSeparator {
+    /// Guess from the shape of each value (see `parse_complex`).
+    Auto,
+    /// `-0.75,0.1`: decimal points, a comma between the two components.
+    Point,
+    /// `-0,75;0,1`: decimal commas, the components separated by a semicolon
+    /// or by whitespace.
+    Comma,
+}
+
+impl DecimalSeparator {
+    /// Pick the decimal separator used by a locale name such as `de_DE.UTF-8`
+    /// or `en-US`, looking only at the language part.
+    pub fn for_locale(locale: &str) -> DecimalSeparator {
+        const COMMA_LANGUAGES: &[&str] = &[
+            "bg", "ca", "cs", "da", "de", "el", "es", "et", "eu", "fi", "fr", "gl", "hr", "hu",
+            "id", "is", "it", "lt", "lv", "nb", "nl", "nn", "no", "pl", "pt", "ro", "ru", "sk",
+            "sl", "sr", "sv", "tr", "uk", "vi",
+        ];
+
+        let language = locale
+            .split(['_', '-', '.', '@'])
+            .next()
+            .unwrap_or("")
+            .to_ascii_lowercase();
+        if COMMA_LANGUAGES.contains(&language.as_str()) {
+            DecimalSeparator::Comma
+        } else {
+            DecimalSeparator::Point
+        }
+    }
+}
+
+#[test]
+fn test_decimal_separator_for_locale() {
+    assert_eq!(
+        DecimalSeparator::for_locale("de_DE.UTF-8"),
+        DecimalSeparator::Comma
+    );
+    assert_eq!(
+        DecimalSeparator::for_locale("pt-BR"),
+        DecimalSeparator::Comma
+    );
+    assert_eq!(
+        DecimalSeparator::for_locale("en_US"),
+        DecimalSeparator::Point
+    );
+    assert_eq!(DecimalSeparator::for_locale("C"), DecimalSeparator::Point);
+}
+
+/// Parse a pair of floating point numbers separated by a comma
+/// as a complex number, reading them with the given decimal separator.
+///
+/// With `DecimalSeparator::Comma` the two components are separated by a
+/// semicolon or whitespace (`-0,75;0,1`), or, when there are exactly three
+/// commas, by the middle one (`-0,75,0,1`). Decimal points are rejected, as
+/// they are usually thousands separators in those locales.
+///
+/// `DecimalSeparator::Auto` takes the components apart like this:
+///
+/// - a semicolon means decimal commas, as above;
+/// - a single comma separates the components, as in `-0.75,0.1`;
+/// - three commas and no points mean decimal commas, as in `-0,75,0,1`;
+/// - anything else (e.g. `-0,75,1`) is ambiguous and rejected.
+///
+/// Returns `None` if `s` cannot be read unambiguously.
+pub fn parse_complex(s: &str, decimal: DecimalSeparator) -> Option<Complex<f64>> {
+    let commas = s.matches(',').count();
+    let comma_decimals = match decimal {
+        DecimalSeparator::Point => false,
+        DecimalSeparator::Comma => true,
+        DecimalSeparator::Auto => s.contains(';') || (commas == 3 && !s.contains('.')),
+    };
+
+    if !comma_decimals {
+        return parse_pair(s, ',').map(|(re, im)| Complex { re, im });
+    }
+    if s.contains('.') {
+        return None;
+    }
+
+    let (re, im) = if let Some(index) = s.find(|c: char| c == ';' || c.is_whitespace()) {
+        (&s[..index], &s[index + 1..])
+    } else if commas == 3 {
+        let (index, _) = s.match_indices(',').nth(1)?;
+        (&s[..index], &s[index + 1..])
+    } else {
+        return None;
+    };
+
+    let number = |part: &str| f64::from_str(&part.trim().replace(',', ".")).ok();
+    Some(Complex {
+        re: number(re)?,
+        im: number(im)?,
+    })
+}
+
+#[test]
+fn test_parse_complex() {
+    use DecimalSeparator::*;
+
+    assert_eq!(
+        parse_complex("0.1,0.3", Auto),
+        Some(Complex { re: 0.1, im: 0.3 })
+    );
+    assert_eq!(parse_complex(",0.3", Auto), None);
+
+    let point = Some(Complex { re: -0.75, im: 0.1 });
+    assert_eq!(parse_complex("-0.75,0.1", Auto), point);
+    assert_eq!(parse_complex("-0,75;0,1", Auto), point);
+    assert_eq!(parse_complex("-0,75,0,1", Auto), point);
+    assert_eq!(parse_complex("-0,75,1", Auto), None);
+    assert_eq!(
+        parse_complex("-1,0", Auto),
+        Some(Complex { re: -1.0, im: 0.0 })
+    );
+
+    assert_eq!(parse_complex("-0.75,0.1", Point), point);
+    assert_eq!(parse_complex("-0,75;0,1", Point), None);
+
+    assert_eq!(parse_complex("-0,75;0,1", Comma), point);
+    assert_eq!(parse_complex("-0,75 0,1", Comma), point);
+    assert_eq!(parse_complex("-0,75,0,1", Comma), point);
+    assert_eq!(
+        parse_complex("-1;0", Comma),
+        Some(Complex { re: -1.0, im: 0.0 })
+    );
+    assert_eq!(parse_complex("-0,743643887037151", Comma), None);
+    assert_eq!(parse_complex("-0.75;0.1", Comma), None);
+}
+
+#[cfg(test)]
+proptest::proptest! {
+    #[test]
+    fn test_parse_complex_round_trip(
+        re in -1e6..1e6f64,
+        im in -1e6..1e6f64,
+    ) {
+        use DecimalSeparator::*;
+
+        let point = Some(Complex { re, im });
+        let points = format!("{},{}", re, im);
+        proptest::prop_assert_eq!(parse_complex(&points, Point), point);
+        proptest::prop_assert_eq!(parse_complex(&points, Auto), point);
+        let commas = format!("{};{}", re, im).replace('.', ",");
+        proptest::prop_assert_eq!(parse_complex(&commas, Comma), point);
+        proptest::prop_assert_eq!(parse_complex(&commas, Auto), point);
+        proptest::prop_assert_eq!(parse_complex(&commas, Point), None);
+    }
+}
+
+/// Write the buffer `pixels`, whose dimensions are given by `bounds`, to
+/// the file named `filename`, or to stdout if `filename` is `-`. The pixels
+/// are grayscale, one byte each, or RGB, three bytes each.
+///
+/// The view, given by `upper_left` and `lower_right`, is embedded in the PNG
+/// file so that it can be read back by `share::read_png_view`.
+#[cfg(feature = "png")]
+pub fn write_image(
+    filename: &str,
+    pixels: &[u8],
+    bounds: (usize, usize),
+    upper_left: Complex<f64>,
+    lower_right: Complex<f64>,
+) -> Result<()> {
+    write_png(
+        output::sink(filename).as_mut(),
+        pixels,
+        bounds,
+        upper_left,
+        lower_right,
+    )
+}
+
+/// Write the grayscale `pixels` to `filename` like `write_image` does, but a
+/// row at a time, for images too large to encode in memory; see
+/// `encode::png_rows`. The rows are colored with `lut`, if given.
+#[cfg(feature = "png")]
+pub fn write_image_rows(
+    filename: &str,
+    pixels: &[u8],
+    bounds: (usize, usize),
+    upper_left: Complex<f64>,
+    lower_right: Complex<f64>,
+    lut: Option<&[[u8; 3]]>,
+) -> Result<()> {
+    let mut sink = output::sink(filename);
+    let mut writer = png_writer(sink.as_mut(), bounds, upper_left, lower_right, lut)?;
+    writer.write_rows(pixels)?;
+    writer.finish()
+}
+
+/// Start a PNG image of the view from `upper_left` to `lower_right`, whose
+/// dimensions are given by `bounds`, on `sink`, embedding the view like
+/// `write_image` does; its rows are written as they are rendered, top to
+/// bottom, with `PngWriter::write_rows`. The rows are grayscale, and colored
+/// with `lut`, if given.
+#[cfg(feature = "png")]
+pub fn png_writer<'a>(
+    sink: &'a mut dyn output::OutputSink,
+    bounds: (usize, usize),
+    upper_left: Complex<f64>,
+    lower_right: Complex<f64>,
+    lut: Option<&'a [[u8; 3]]>,
+) -> Result<encode::PngWriter<'a>> {
+    let link = share::share_link(bounds, upper_left, lower_right);
+    encode::PngWriter::new(sink, bounds, lut, &[(share::PNG_KEYWORD, &link)])
+}
+
+/// Read the pixels of the PNG image `filename`, with its dimensions: one
+/// byte a pixel if it is grayscale, three if it is in color.
+#[cfg(feature = "png")]
+pub fn read_image(filename: &str) -> Result<(Vec<u8>, (usize, usize))> {
+    use image::{ColorType, GenericImage};
+
+    let bytes = std::fs::read(platform::path(filename)).map_err(Error::io(filename))?;
+    let image = image::load_from_memory(&bytes).map_err(|e| {
+        Error::io(filename)(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            e.to_string(),
+        ))
+    })?;
+    let (width, height) = image.dimensions();
+    let pixels = match image.color() {
+        ColorType::Gray(_) | ColorType::GrayA(_) => image.to_luma().into_raw(),
+        _ => image.to_rgb().into_raw(),
+    };
+    Ok((pixels, (width as usize, height as usize)))
+}
+
+/// Encode the buffer `pixels` like `write_image` does, and write the PNG
+/// image to `sink`.
+#[cfg(feature = "png")]
+pub fn write_png(
+    sink: &mut dyn output::OutputSink,
+    pixels: &[u8],
+    bounds: (usize, usize),
+    upper_left: Complex<f64>,
+    lower_right: Complex<f64>,
+) -> Result<()> {
+    sink.write_all(&encode_png(pixels, bounds, upper_left, lower_right)?)
+}
+
+/// Encode the buffer `pixels`, whose dimensions are given by `bounds`, as a
+/// PNG image, embedding the view like `write_image` does.
+#[cfg(feature = "png")]
+pub fn encode_png(
+    pixels: &[u8],
+    bounds: (usize, usize),
+    upper_left: Complex<f64>,
+    lower_right: Complex<f64>,
+) -> Result<Vec<u8>> {
+    let mut png = encode::png(pixels, bounds)?;
+    share::add_png_text(
+        &mut png,
+        share::PNG_KEYWORD,
+        &share::share_link(bounds, upper_left, lower_right),
+    );
+
+    Ok(png)
+}
@@ -0,0 +1,184 @@
+//! Choosing the iteration limit for a view, for `--max-iters auto`.
+//!
+//! Deeper views need more iterations before the points near the set escape:
+//! too few and the boundary fills in as black blobs, too many and the time
+//! goes into points that are in the set anyway. The estimate grows with the
+//! logarithm of the pixel size, and sampling the view first refines it to
+//! the escape times actually there.
+
+use std::str::FromStr;
+
+use num::Complex;
+
+use crate::{
+    render::{self, DEFAULT_LIMIT, UNZOOMED_WIDTH},
+    units::{ComplexWidth, PixelSize},
+};
+
+/// The highest limit `auto` picks.
+pub const MAX_AUTO_LIMIT: usize = 1 << 20;
+
+/// How many points a side the grid sampled by `adaptive` has.
+const GRID: usize = 32;
+
+/// The pixel size of the whole set a thousand pixels wide, where the
+/// estimate starts from `DEFAULT_LIMIT`.
+const BASE_PIXEL_SIZE: PixelSize = PixelSize(UNZOOMED_WIDTH / 1000.0);
+
+/// The iteration limit given with `--max-iters`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MaxIters {
+    Fixed(usize),
+    /// Estimated from the pixel size.
+    Auto,
+    /// Estimated from the pixel size, then adjusted to the escape times of
+    /// a sparse grid of points.
+    Adaptive,
+}
+
+impl FromStr for MaxIters {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(MaxIters::Auto),
+            "adaptive" => Ok(MaxIters::Adaptive),
+            _ => match s.parse() {
+                Ok(iterations) if iterations > 0 => Ok(MaxIters::Fixed(iterations)),
+                _ => Err(format!("invalid number of iterations {}", s)),
+            },
+        }
+    }
+}
+
+#[test]
+fn test_parse_max_iters() {
+    assert_eq!("500".parse(), Ok(MaxIters::Fixed(500)));
+    assert_eq!("auto".parse(), Ok(MaxIters::Auto));
+    assert_eq!("adaptive".parse(), Ok(MaxIters::Adaptive));
+    assert!("0".parse::<MaxIters>().is_err());
+    assert!("lots".parse::<MaxIters>().is_err());
+}
+
+impl MaxIters {
+    /// The limit for the view from `upper_left` to `lower_right`, rendered
+    /// into an image whose dimensions are given by `bounds`.
+    pub fn resolve(
+        self,
+        bounds: (usize, usize),
+        upper_left: Complex<f64>,
+        lower_right: Complex<f64>,
+    ) -> usize {
+        let pixel_size = ComplexWidth::between(upper_left, lower_right).pixel_size(bounds.0);
+        match self {
+            MaxIters::Fixed(limit) => limit,
+            MaxIters::Auto => estimate(pixel_size),
+            MaxIters::Adaptive => adapt(estimate(pixel_size), upper_left, lower_right),
+        }
+    }
+}
+
+/// The limit a view whose pixels are `pixel_size` wide likely needs:
+/// `DEFAULT_LIMIT` for the whole set, growing with each tenfold zoom.
+pub fn estimate(pixel_size: PixelSize) -> usize {
+    let depth = (BASE_PIXEL_SIZE.0 / pixel_size.0).log10().max(0.0);
+    if !depth.is_finite() {
+        return MAX_AUTO_LIMIT;
+    }
+    let limit = DEFAULT_LIMIT as f64 * (1.0 + depth).powf(1.25);
+    (limit as usize).clamp(DEFAULT_LIMIT, MAX_AUTO_LIMIT)
+}
+
+#[test]
+fn test_estimate() {
+    assert_eq!(estimate(BASE_PIXEL_SIZE), DEFAULT_LIMIT);
+    assert_eq!(estimate(PixelSize(1.0)), DEFAULT_LIMIT);
+    let shallow = estimate(PixelSize(1e-6));
+    let deep = estimate(PixelSize(1e-12));
+    assert!(
+        DEFAULT_LIMIT < shallow && shallow < deep,
+        "{} {}",
+        shallow,
+        deep
+    );
+    assert_eq!(estimate(PixelSize(0.0)), MAX_AUTO_LIMIT);
+}
+
+/// Adjust the limit `estimate` to the view from `upper_left` to
+/// `lower_right`: sample a grid of points with four times the limit, and
+/// keep enough iterations for nearly all of those that escape.
+///
+/// Views with no points escaping, all inside the set, keep the estimate.
+pub fn adapt(estimate: usize, upper_left: Complex<f64>, lower_right: Complex<f64>) -> usize {
+    let ceiling = estimate.saturating_mul(4).min(MAX_AUTO_LIMIT);
+    let mut escapes = sampled_escapes(ceiling, upper_left, lower_right);
+    if escapes.is_empty() {
+        return estimate;
+    }
+    escapes.sort_unstable();
+    // the slowest few percent are the boundary, which wants some headroom
+    let slow = escapes[escapes.len() * 99 / 100];
+    (slow + slow / 2).clamp(DEFAULT_LIMIT, ceiling)
+}
+
+/// The escape times, within `ceiling`, of the points of a grid over the view
+/// from `upper_left` to `lower_right` that escape.
+fn sampled_escapes(
+    ceiling: usize,
+    upper_left: Complex<f64>,
+    lower_right: Complex<f64>,
+) -> Vec<usize> {
+    let grid = (GRID, GRID);
+    (0..GRID * GRID)
+        .filter_map(|i| {
+            let point = render::pixel_to_point(grid, (i % GRID, i / GRID), upper_left, lower_right);
+            render::escape_time(point, ceiling)
+        })
+        .collect()
+}
+
+/// Whether `limit` is too low for the view from `upper_left` to
+/// `lower_right`: more than one in a hundred points of a grid over it would
+/// be taken to be in the set, but escape within four times the limit, which
+/// shows as black blobs where the boundary should be.
+pub fn under_iterated(limit: usize, upper_left: Complex<f64>, lower_right: Complex<f64>) -> bool {
+    let ceiling = limit.saturating_mul(4).min(MAX_AUTO_LIMIT).max(limit);
+    let escapes = sampled_escapes(ceiling, upper_left, lower_right);
+    escapes.iter().filter(|&&escape| escape >= limit).count() * 100 > GRID * GRID
+}
+
+#[test]
+fn test_under_iterated() {
+    let (upper_left, lower_right) = (Complex::new(-2.0, 1.5), Complex::new(2.0, -1.5));
+    assert!(!under_iterated(DEFAULT_LIMIT, upper_left, lower_right));
+    let center = Complex::new(-0.743643887037151, 0.131825904205330);
+    let (upper_left, lower_right) =
+        render::corners_from_center(center, crate::units::Zoom(1e6), (GRID, GRID));
+    assert!(under_iterated(1000, upper_left, lower_right));
+    assert!(!under_iterated(5000, upper_left, lower_right));
+    // nothing escapes inside the main cardioid
+    let (upper_left, lower_right) = (Complex::new(-0.1, 0.1), Complex::new(0.1, -0.1));
+    assert!(!under_iterated(10, upper_left, lower_right));
+}
+
+#[test]
+fn test_adapt() {
+    // the whole set escapes quickly, so the limit stays low
+    let (upper_left, lower_right) = (Complex::new(-2.0, 1.5), Complex::new(2.0, -1.5));
+    assert_eq!(adapt(1000, upper_left, lower_right), DEFAULT_LIMIT);
+
+    // around the tip of a seahorse tail points take long to escape
+    let center = Complex::new(-0.743643887037151, 0.131825904205330);
+    let (upper_left, lower_right) =
+        render::corners_from_center(center, crate::units::Zoom(1e6), (GRID, GRID));
+    let limit = adapt(DEFAULT_LIMIT, upper_left, lower_right);
+    assert!(
+        limit > DEFAULT_LIMIT && limit <= 4 * DEFAULT_LIMIT,
+        "{}",
+        limit
+    );
+
+    // inside the main cardioid nothing escapes
+    let (upper_left, lower_right) = (Complex::new(-0.1, 0.1), Complex::new(0.1, -0.1));
+    assert_eq!(adapt(700, upper_left, lower_right), 700);
+}
@@ -0,0 +1,203 @@
+//! Location files, as Kalles Fraktaler (`.kfr`) and Fraktaler 3 (`.toml`)
+//! exchange them, so views can be shared with their users.
+//!
+//! Both describe a view by its center and a zoom under which the view is
+//! 4 / zoom high, where our own `--zoom` makes it 4 / zoom wide. Only the
+//! center, the zoom and the iteration limit are read; the rest of the file,
+//! such as the coloring, is ignored.
+
+use std::fs;
+
+use num::Complex;
+
+use crate::{output, platform, render, share::View, units::Zoom, Error, Result};
+
+/// A view, as location files describe it.
+#[derive(Debug, PartialEq)]
+pub struct Location {
+    pub center: Complex<f64>,
+    /// The magnification, relative to a view 4 high.
+    pub zoom: f64,
+    pub iterations: Option<usize>,
+}
+
+impl Location {
+    /// The location of `view`.
+    pub fn from_view(view: &View) -> Location {
+        Location {
+            center: (view.upper_left + view.lower_right) / 2.0,
+            zoom: render::UNZOOMED_WIDTH / (view.upper_left.im - view.lower_right.im),
+            iterations: None,
+        }
+    }
+
+    /// The corners of the location's view in an image whose size is given by
+    /// `bounds`.
+    pub fn corners(&self, bounds: (usize, usize)) -> (Complex<f64>, Complex<f64>) {
+        let zoom = self.zoom * bounds.1 as f64 / bounds.0 as f64;
+        render::corners_from_center(self.center, Zoom(zoom), bounds)
+    }
+}
+
+#[test]
+fn test_location_view() {
+    let location = Location {
+        center: Complex { re: -0.5, im: 0.0 },
+        zoom: 2.0,
+        iterations: None,
+    };
+    let (upper_left, lower_right) = location.corners((200, 100));
+    assert_eq!(upper_left, Complex { re: -2.5, im: 1.0 });
+    assert_eq!(lower_right, Complex { re: 1.5, im: -1.0 });
+
+    let view = View {
+        bounds: (200, 100),
+        upper_left,
+        lower_right,
+    };
+    assert_eq!(Location::from_view(&view), location);
+}
+
+/// Parse a Kalles Fraktaler location, made of `Name: value` lines.
+pub fn parse_kfr(text: &str) -> Option<Location> {
+    let (mut re, mut im, mut zoom, mut iterations) = (None, None, None, None);
+    for line in text.lines() {
+        let Some((name, value)) = line.split_once(':') else {
+            continue;
+        };
+        let value = value.trim();
+        match name.trim() {
+            "Re" => re = value.parse().ok(),
+            "Im" => im = value.parse().ok(),
+            "Zoom" => zoom = value.parse().ok(),
+            "Iterations" => iterations = value.parse().ok(),
+            _ => {}
+        }
+    }
+    Some(Location {
+        center: Complex { re: re?, im: im? },
+        zoom: zoom?,
+        iterations,
+    })
+}
+
+/// Write `location` as a Kalles Fraktaler location.
+pub fn to_kfr(location: &Location) -> String {
+    let mut text = format!(
+        "Re: {}\r\nIm: {}\r\nZoom: {:E}\r\n",
+        location.center.re, location.center.im, location.zoom
+    );
+    if let Some(iterations) = location.iterations {
+        text += &format!("Iterations: {}\r\n", iterations);
+    }
+    text
+}
+
+#[test]
+fn test_kfr() {
+    let text = "Re: -0.743643887037151\r\nIm: 0.131825904205330\r\nZoom: 1.0E5\r\n\
+                Iterations: 5000\r\nIterDiv: 0.010000\r\nColorMethod: 7\r\n";
+    let location = parse_kfr(text).unwrap();
+    assert_eq!(
+        location,
+        Location {
+            center: Complex {
+                re: -0.743643887037151,
+                im: 0.13182590420533
+            },
+            zoom: 1e5,
+            iterations: Some(5000),
+        }
+    );
+    assert_eq!(parse_kfr(&to_kfr(&location)), Some(location));
+    assert_eq!(parse_kfr("Re: 1\nIm: 0\n"), None);
+}
+
+/// Parse a Fraktaler 3 location, the `[location]` table of its TOML files.
+pub fn parse_fraktaler(text: &str) -> Option<Location> {
+    let (mut re, mut im, mut zoom, mut iterations) = (None, None, None, None);
+    let mut table = "";
+    for line in text.lines().map(str::trim) {
+        if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            table = name.trim();
+            continue;
+        }
+        let Some((name, value)) = line.split_once('=') else {
+            continue;
+        };
+        // numbers are strings, to keep their digits beyond what floats hold
+        let value = value.trim().trim_matches('"');
+        match (table, name.trim()) {
+            ("location", "real") => re = value.parse().ok(),
+            ("location", "imag") => im = value.parse().ok(),
+            ("location", "zoom") => zoom = value.parse().ok(),
+            ("bailout", "iterations") => iterations = value.parse().ok(),
+            _ => {}
+        }
+    }
+    Some(Location {
+        center: Complex { re: re?, im: im? },
+        zoom: zoom?,
+        iterations,
+    })
+}
+
+/// Write `location` as a Fraktaler 3 parameter file.
+pub fn to_fraktaler(location: &Location) -> String {
+    let mut text = format!(
+        "[location]\nreal = \"{}\"\nimag = \"{}\"\nzoom = \"{:E}\"\n",
+        location.center.re, location.center.im, location.zoom
+    );
+    if let Some(iterations) = location.iterations {
+        text += &format!("\n[bailout]\niterations = {}\n", iterations);
+    }
+    text
+}
+
+#[test]
+fn test_fraktaler() {
+    let text = "[location]\nreal = \"-1.75\"\nimag = \"0\"\nzoom = \"2E1\"\n\n\
+                [bailout]\niterations = 1024\n\n[render]\nzoom_out_sequence = false\n";
+    let location = parse_fraktaler(text).unwrap();
+    assert_eq!(
+        location,
+        Location {
+            center: Complex { re: -1.75, im: 0.0 },
+            zoom: 20.0,
+            iterations: Some(1024),
+        }
+    );
+    assert_eq!(parse_fraktaler(&to_fraktaler(&location)), Some(location));
+    assert_eq!(
+        parse_fraktaler("real = \"1\"\nimag = \"0\"\nzoom = \"1\"\n"),
+        None
+    );
+}
+
+/// Whether `filename` names a Fraktaler 3 file rather than a Kalles
+/// Fraktaler one, going by its extension.
+fn is_fraktaler(filename: &str) -> bool {
+    filename.to_ascii_lowercase().ends_with(".toml")
+}
+
+/// Read the location file named `filename`, in either format.
+pub fn read(filename: &str) -> Result<Location> {
+    let text = fs::read_to_string(platform::path(filename)).map_err(Error::io(filename))?;
+    let location = if is_fraktaler(filename) {
+        parse_fraktaler(&text)
+    } else {
+        parse_kfr(&text)
+    };
+    location.ok_or_else(|| Error::parse("location file", filename))
+}
+
+/// Write `location` to the file named `filename`, as a Fraktaler 3 file if
+/// it ends in `.toml` and as a Kalles Fraktaler one otherwise.
+pub fn write(filename: &str, location: &Location) -> Result<()> {
+    let text = if is_fraktaler(filename) {
+        to_fraktaler(location)
+    } else {
+        to_kfr(location)
+    };
+    output::write(filename, text.as_bytes())
+}
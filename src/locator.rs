@@ -0,0 +1,245 @@
+//! Compact locations, a whole view in one string that fits on a command
+//! line, in a job, in a bookmarks file or in a share link:
+//!
+//! ```text
+//! @-0.743643887,0.131825904,z=6.4e9,r=30deg
+//! ```
+//!
+//! After `@` come the real and the imaginary part of the center, always with
+//! decimal points, then any of these, separated by commas:
+//!
+//! - `z=ZOOM`, the magnification as `--zoom` gives it, which may also be
+//!   written as a power of two, `z=2^32`;
+//! - `r=ANGLE`, how far the view is turned counterclockwise, in degrees
+//!   unless followed by `deg`, `rad` or `turn`.
+//!
+//! This module is the one place they are read and written.
+
+use std::{f64::consts::TAU, fmt, str::FromStr};
+
+use num::Complex;
+
+use crate::{render, units::Zoom};
+
+/// A view, as a compact location gives it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Locator {
+    pub center: Complex<f64>,
+    /// The magnification, if given.
+    pub zoom: Option<Zoom>,
+    /// How far the view is turned counterclockwise, in turns.
+    pub rotation: f64,
+}
+
+/// Whether `s` looks like a compact location rather than a point.
+pub fn is_locator(s: &str) -> bool {
+    s.trim_start().starts_with('@')
+}
+
+/// Parse a finite number.
+fn number(what: &str, s: &str) -> Result<f64, String> {
+    match s.trim().parse::<f64>() {
+        Ok(value) if value.is_finite() => Ok(value),
+        _ => Err(format!("invalid {} {:?}", what, s.trim())),
+    }
+}
+
+/// Parse a zoom: a positive number, or `2^N`.
+fn zoom(s: &str) -> Result<Zoom, String> {
+    let value = match s.trim().strip_prefix("2^") {
+        Some(exponent) => number("zoom exponent", exponent)?.exp2(),
+        None => number("zoom", s)?,
+    };
+    if value > 0.0 && value.is_finite() {
+        Ok(Zoom(value))
+    } else {
+        Err(format!("zoom {:?} must be positive and finite", s.trim()))
+    }
+}
+
+/// Parse an angle, in turns: degrees, or radians or turns with their unit.
+fn angle(s: &str) -> Result<f64, String> {
+    let s = s.trim();
+    let (value, per_turn) = if let Some(value) = s.strip_suffix("deg") {
+        (value, 360.0)
+    } else if let Some(value) = s.strip_suffix("rad") {
+        (value, TAU)
+    } else if let Some(value) = s.strip_suffix("turn") {
+        (value, 1.0)
+    } else {
+        (s, 360.0)
+    };
+    Ok(number("angle", value)? / per_turn)
+}
+
+impl FromStr for Locator {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Locator, String> {
+        let body = s
+            .trim()
+            .strip_prefix('@')
+            .ok_or_else(|| format!("location {:?} doesn't start with @", s))?;
+        let mut fields = body.split(',');
+        let re = number("real part", fields.next().unwrap_or(""))?;
+        let im = number(
+            "imaginary part",
+            fields
+                .next()
+                .ok_or("a location needs both parts of its center")?,
+        )?;
+        let mut locator = Locator {
+            center: Complex { re, im },
+            zoom: None,
+            rotation: 0.0,
+        };
+        let mut rotated = false;
+        for field in fields {
+            let (key, value) = field
+                .split_once('=')
+                .ok_or_else(|| format!("expected KEY=VALUE, found {:?}", field.trim()))?;
+            match key.trim() {
+                "z" if locator.zoom.is_none() => locator.zoom = Some(zoom(value)?),
+                "r" if !rotated => {
+                    locator.rotation = angle(value)?;
+                    rotated = true;
+                }
+                "z" | "r" => return Err(format!("{} is given twice", key.trim())),
+                key => return Err(format!("unknown location key {:?}", key)),
+            }
+        }
+        Ok(locator)
+    }
+}
+
+impl fmt::Display for Locator {
+    /// Write the locator so that parsing it gives it back.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "@{},{}", self.center.re, self.center.im)?;
+        if let Some(zoom) = self.zoom {
+            write!(f, ",z={:e}", zoom.0)?;
+        }
+        if self.rotation != 0.0 {
+            write!(f, ",r={}deg", self.rotation * 360.0)?;
+        }
+        Ok(())
+    }
+}
+
+impl Locator {
+    /// The corners of the view in an image whose dimensions are given by
+    /// `bounds`, magnified `zoom` times unless the locator says how much.
+    ///
+    /// Views are drawn upright, so turning them is only possible by whole
+    /// turns; returns an error for any other rotation.
+    pub fn corners(
+        &self,
+        bounds: (usize, usize),
+        zoom: Zoom,
+    ) -> Result<(Complex<f64>, Complex<f64>), String> {
+        if self.rotation.fract() != 0.0 {
+            return Err(format!(
+                "{} is turned {}°, but only upright views can be rendered",
+                self,
+                self.rotation * 360.0
+            ));
+        }
+        Ok(render::corners_from_center(
+            self.center,
+            self.zoom.unwrap_or(zoom),
+            bounds,
+        ))
+    }
+}
+
+#[test]
+fn test_parse() {
+    let locator: Locator = "@-0.743643887,0.131825904,z=6.4e9,r=30deg".parse().unwrap();
+    assert_eq!(
+        locator.center,
+        Complex {
+            re: -0.743643887,
+            im: 0.131825904
+        }
+    );
+    assert_eq!(locator.zoom, Some(Zoom(6.4e9)));
+    assert!((locator.rotation - 30.0 / 360.0).abs() < 1e-15);
+
+    let parse = |s: &str| s.parse::<Locator>();
+    let plain = parse("@-0.5,0").unwrap();
+    assert_eq!(plain.center, Complex { re: -0.5, im: 0.0 });
+    assert_eq!((plain.zoom, plain.rotation), (None, 0.0));
+    // spaces, the keys in any order, powers of two and angle units
+    let spaced = parse(" @ -1.25 , 0.5 , r = 0.5turn , z = 2^10 ").unwrap();
+    assert_eq!(spaced.center, Complex { re: -1.25, im: 0.5 });
+    assert_eq!(spaced.zoom, Some(Zoom(1024.0)));
+    assert_eq!(spaced.rotation, 0.5);
+    assert_eq!(parse("@0,0,r=90").unwrap().rotation, 0.25);
+    let radians = parse("@0,0,r=3.141592653589793rad").unwrap();
+    assert!((radians.rotation - 0.5).abs() < 1e-15);
+    assert_eq!(parse("@1e-3,-2E-3,z=1E3").unwrap().zoom, Some(Zoom(1000.0)));
+}
+
+#[test]
+fn test_parse_errors() {
+    let error = |s: &str| s.parse::<Locator>().unwrap_err();
+    assert!(error("-0.5,0").contains("@"));
+    assert!(error("@").contains("real part"));
+    assert!(error("@-0.5").contains("both parts"));
+    assert!(error("@-0,5;0").contains("imaginary part"));
+    assert!(error("@x,0").contains("real part"));
+    assert!(error("@0,NaN").contains("imaginary part"));
+    assert!(error("@0,inf").contains("imaginary part"));
+    assert!(error("@0,0,z").contains("KEY=VALUE"));
+    assert!(error("@0,0,").contains("KEY=VALUE"));
+    assert!(error("@0,0,z=0").contains("positive"));
+    assert!(error("@0,0,z=-4").contains("positive"));
+    assert!(error("@0,0,z=2^2000").contains("positive"));
+    assert!(error("@0,0,z=2^x").contains("zoom exponent"));
+    assert!(error("@0,0,z=big").contains("zoom"));
+    assert!(error("@0,0,z=1,z=2").contains("twice"));
+    assert!(error("@0,0,r=1,r=2").contains("twice"));
+    assert!(error("@0,0,r=30grad").contains("angle"));
+    assert!(error("@0,0,w=1").contains("unknown"));
+}
+
+#[test]
+fn test_display() {
+    for s in [
+        "@-0.743643887,0.131825904,z=6.4e9,r=30deg",
+        "@-0.5,0",
+        "@0.25,-0.001,z=1e0",
+        "@1,2,r=-45deg",
+    ] {
+        let locator: Locator = s.parse().unwrap();
+        assert_eq!(locator.to_string().parse(), Ok(locator), "{}", s);
+    }
+    assert_eq!(
+        "@-0.5,0,z=2^4,r=180deg"
+            .parse::<Locator>()
+            .unwrap()
+            .to_string(),
+        "@-0.5,0,z=1.6e1,r=180deg"
+    );
+    assert!(is_locator(" @0,0"));
+    assert!(!is_locator("0,0"));
+}
+
+#[test]
+fn test_corners() {
+    let bounds = (200, 100);
+    let locator: Locator = "@-0.5,0,z=2".parse().unwrap();
+    let corners = (Complex { re: -1.5, im: 0.5 }, Complex { re: 0.5, im: -0.5 });
+    assert_eq!(locator.corners(bounds, Zoom(8.0)), Ok(corners));
+    // without a zoom of its own, it takes the one given
+    let unzoomed: Locator = "@-0.5,0".parse().unwrap();
+    assert_eq!(unzoomed.corners(bounds, Zoom(2.0)), Ok(corners));
+    // whole turns leave the view as it is
+    let turned: Locator = "@-0.5,0,z=2,r=360".parse().unwrap();
+    assert_eq!(turned.corners(bounds, Zoom::NONE), Ok(corners));
+    let tilted: Locator = "@-0.5,0,z=2,r=30deg".parse().unwrap();
+    assert!(tilted
+        .corners(bounds, Zoom::NONE)
+        .unwrap_err()
+        .contains("30"));
+}
@@ -1,199 +1,5144 @@
-use std::{env, fs::File, str::FromStr};
+use std::{
+    borrow::Cow,
+    collections::BTreeMap,
+    env,
+    net::TcpListener,
+    path::Path,
+    process::ExitCode,
+    sync::atomic::{AtomicBool, Ordering},
+    thread,
+    time::{Duration, Instant},
+};
 
-use image::{png::PNGEncoder, ColorType};
 use num::Complex;
 
-fn main() {
-    let args: Vec<String> = env::args().collect();
+#[cfg(all(feature = "sandbox", target_os = "linux"))]
+use mandelbrot::sandbox;
+#[cfg(feature = "scripting")]
+use mandelbrot::script::ColorScript;
+use mandelbrot::{
+    anchors::{self, Anchors},
+    animation::{self, Scene},
+    area, art,
+    art::Art,
+    autopalette::{self, Choice},
+    background::{self, Background},
+    bands,
+    bands::Bands,
+    batch,
+    bookmarks::{self, Bookmark, Store},
+    buddhabrot,
+    buddhabrot::Buddhabrot,
+    cache::{self, DiskCache},
+    clipboard, compare,
+    completions::{self, Shell},
+    config::{self, Config},
+    contour::{self, Contours},
+    coverage,
+    cpu::{self, Kernel},
+    deepen::{self, Deepening},
+    derivative,
+    distributed::{self, Coordinator},
+    dither::{self, Dither},
+    encode, encode_png, error,
+    explore::{self, Explorer},
+    expmap::Strip,
+    exr,
+    extend::{self, Direction},
+    find, fit,
+    fit::Fit,
+    formula,
+    formula::Formula,
+    glow,
+    glow::Glow,
+    job, layers,
+    limit::{self, MaxIters},
+    location,
+    locator::{self, Locator},
+    mapped::{MappedBuffer, Pixels},
+    mask, mesh, mosaic,
+    mosaic::Mosaic,
+    newton,
+    newton::Newton,
+    orbit, output,
+    output::OnExists,
+    palette,
+    palette::Palette,
+    parse_pair, platform,
+    poly::Polynomial,
+    preview,
+    progress::Progress,
+    project::{self, Project},
+    pyramid::{self, Layout},
+    read_image,
+    real::Numeric,
+    render,
+    render::Fractal,
+    render::Strategy,
+    report,
+    reproduce::{self, Manifest},
+    resample,
+    result::{self, Outcome},
+    sampling::{self, Sampling, TemporalAccumulator, Variance},
+    schema, sequence,
+    sequence::Format,
+    server, service,
+    service::Priority,
+    share, signals,
+    tally::{self, Tally},
+    traversal::Traversal,
+    units::{ComplexWidth, Zoom},
+    usage::{self, Cost, Usage},
+    validate, video,
+    video::Video,
+    watermark::{self, Watermark},
+    write_image, write_image_rows, DecimalSeparator, Error, Result,
+};
 
-    if args.len() != 5 {
-        eprintln!("Usage: {} FILE PIXELS UPPERLEFT LOWERRIGHT", args[0]);
-        eprintln!(
-            "Example: {} mandel.png 1000x750 -1.20,0.35 -1.0,0.2",
-            args[0]
+fn main() -> ExitCode {
+    // `env::args` would panic on arguments that aren't valid Unicode, like
+    // file names with unpaired surrogates on Windows
+    let args = env::args_os().map(|arg| arg.into_string());
+    let args: Vec<String> = match args.collect() {
+        Ok(args) => args,
+        Err(arg) => {
+            let error = Error::Usage(format!("argument {:?} is not valid Unicode", arg));
+            eprintln!("error: {}", error);
+            return ExitCode::from(error.exit_code());
+        }
+    };
+
+    // filled in by renders with --result json, or left out if the command
+    // line doesn't get as far as saying so
+    let mut outcome = None;
+    let result = run(&args, &mut outcome);
+    if outcome.is_none() && args.windows(2).any(|pair| pair == ["--result", "json"]) {
+        outcome = Some(Outcome::default());
+    }
+    if let Some(outcome) = outcome {
+        print!("{}", outcome.to_json(result.as_ref().err()));
+    }
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            if args.iter().any(|arg| arg == "--service") {
+                service::log(Priority::Error, &error.to_string(), &[]);
+            } else {
+                eprintln!("error: {}", error);
+            }
+            if let Error::Usage(_) = error {
+                usage(&args[0]);
+            }
+            ExitCode::from(error.exit_code())
+        }
+    }
+}
+
+/// Do everything the command line `args` asks for, noting what a render did
+/// in `outcome` if it is given `--result json`.
+fn run(args: &[String], outcome: &mut Option<Outcome>) -> Result<()> {
+    match args.get(1).map(String::as_str) {
+        #[cfg(all(feature = "sandbox", target_os = "linux"))]
+        Some(sandbox::SUBCOMMAND) => return sandbox::run(),
+        Some("project") => return project_command(&args[2..]),
+        Some("bookmark") => return bookmark_command(&args[2..]),
+        Some("batch") => return batch_command(&args[0], &args[2..]),
+        Some("stats") => return stats_command(&args[2..]),
+        Some("cache") => return cache_command(&args[2..]),
+        Some("config") => return config_command(&args[2..]),
+        Some("completions") => return completions_command(&args[0], &args[2..]),
+        Some("validate") => return validate_command(&args[2..]),
+        Some("coverage") => return coverage_command(&args[2..]),
+        Some("worker") => return worker_command(&args[2..]),
+        _ => {}
+    }
+    let args = &reproduce_args(args)?;
+    let args = &with_config(args)?;
+    let args = &bookmarks::expand_bookmarks(&project::expand_projects(args)?)?;
+    match args.get(1).map(String::as_str) {
+        Some("explore") => {
+            let options = parse_options(&args[2..])?;
+            return explore(&args[0], &options);
+        }
+        Some("serve") => {
+            let options = parse_options(&args[2..])?;
+            return serve(&options);
+        }
+        Some("animate") => {
+            let options = parse_options(&args[2..])?;
+            return animate(&options);
+        }
+        Some("coordinator") => {
+            let options = parse_options(&args[2..])?;
+            return coordinate(&options);
+        }
+        Some("location") => {
+            let options = parse_options(&args[2..])?;
+            return export_location(&options);
+        }
+        Some("export-mesh") => {
+            let options = parse_options(&args[2..])?;
+            return export_mesh(&options);
+        }
+        Some("export-dzi") => {
+            let options = parse_options(&args[2..])?;
+            return export_dzi(&options);
+        }
+        Some("palette") => return palette_command(&args[2..]),
+        Some("convert-view") => return convert_view(&args[2..]),
+        Some("extend") => return extend_image(&args[2..]),
+        Some("compare") => return compare_images(&args[2..]),
+        Some("orbit") => return orbit_command(&args[2..]),
+        Some("find") => {
+            let options = parse_options(&args[2..])?;
+            return find_views(&options);
+        }
+        _ => {}
+    }
+
+    let args = job::expand_jobs(&args[1..])?;
+    let mut options = parse_options(&args)?;
+    let mut unreported = Outcome::default();
+    let outcome = if options.result_json {
+        outcome.insert(Outcome::default())
+    } else {
+        &mut unreported
+    };
+    if options.list_palettes {
+        return list_palettes(&options);
+    }
+    if options.preview_dir.is_some() {
+        return Err(Error::Usage(
+            "--preview-dir goes with --list-palettes".to_string(),
+        ));
+    }
+    let output = options
+        .output
+        .clone()
+        .ok_or_else(|| Error::Usage("missing output file".to_string()))?;
+    if options.manifest && output == "-" {
+        return Err(Error::Usage(
+            "--manifest goes next to the image, so FILE can't be -".to_string(),
+        ));
+    }
+    if options.result_json && output == "-" {
+        return Err(Error::Usage(
+            "--result json writes to stdout, so FILE can't be -".to_string(),
+        ));
+    }
+    if platform::is_terminal(&output) {
+        return Err(Error::Usage(format!(
+            "refusing to write a PNG image to the terminal ({}); redirect stdout or give a file name",
+            output
+        )));
+    }
+    let output = match output::resolve(&output, options.on_exists) {
+        Some(output) => output,
+        None => {
+            log(
+                &options,
+                Priority::Info,
+                &format!("{} already exists, skipping", output),
+            );
+            outcome.skipped = true;
+            return Ok(());
+        }
+    };
+    if options.mkdir {
+        output::create_parent_dirs(&output)?;
+        if let Some(report_file) = &options.report {
+            output::create_parent_dirs(report_file)?;
+        }
+        if let Some(stats_file) = &options.stats_json {
+            output::create_parent_dirs(stats_file)?;
+        }
+        if let Some(derivative_file) = &options.derivative {
+            output::create_parent_dirs(derivative_file)?;
+        }
+        if let Some(variance_file) = &options.variance {
+            output::create_parent_dirs(variance_file)?;
+        }
+        if let Some(cost_file) = &options.cost_map {
+            output::create_parent_dirs(cost_file)?;
+        }
+        if let Some(exr_file) = &options.layers_exr {
+            output::create_parent_dirs(exr_file)?;
+        }
+        if let Some(mask_file) = &options.mask {
+            output::create_parent_dirs(mask_file)?;
+        }
+        if let Some(state_file) = &options.state {
+            output::create_parent_dirs(state_file)?;
+        }
+    }
+
+    let started = Instant::now();
+    let share::View {
+        bounds,
+        upper_left,
+        lower_right,
+    } = view(&options)?;
+    error::validate_viewport(bounds, upper_left, lower_right)?;
+    options.max_iters = max_iters(&options)?;
+    let limit = options
+        .max_iters
+        .map(|max_iters| max_iters.resolve(bounds, upper_left, lower_right));
+    if let Some(limit) = limit {
+        if !matches!(options.max_iters, Some(MaxIters::Fixed(_))) {
+            log(
+                &options,
+                Priority::Info,
+                &format!("rendering with {} iterations", limit),
+            );
+        }
+        options.buddhabrot.max_iters = limit;
+    }
+    if options.buddhabrot.min_iters > options.buddhabrot.max_iters {
+        return Err(Error::Usage(
+            "--min-iters is larger than --max-iters".to_string(),
+        ));
+    }
+    if (options.progressive || options.state.is_some())
+        && (options.fractal != Fractal::Mandelbrot
+            || options.color_script.is_some()
+            || options.strategy != Strategy::Naive
+            || options.sampling.samples > 1)
+    {
+        return Err(Error::Usage(
+            "--progressive and --state render the mandelbrot fractal a pixel at a time, \
+             without --color-script, --strategy or --samples"
+                .to_string(),
+        ));
+    }
+    if options.fractal == Fractal::Mandelbrot && options.formula.bailout < render::DEFAULT_BAILOUT {
+        return Err(Error::Usage(
+            "--bailout is at least 2 for the mandelbrot fractal, or points outside the set \
+             never escape"
+                .to_string(),
+        ));
+    }
+    if options.formula.bailout != render::DEFAULT_BAILOUT
+        && (options.progressive || options.state.is_some())
+    {
+        return Err(Error::Usage(
+            "--progressive and --state keep orbits escaping at a radius of 2, without --bailout"
+                .to_string(),
+        ));
+    }
+    // the samples of the mandelbrot fractal, the others averaging theirs in
+    // their own way
+    let supersampled = options.fractal == Fractal::Mandelbrot
+        && options.color_script.is_none()
+        && options.sampling.samples > 1;
+    if options.variance.is_some() && !supersampled {
+        return Err(Error::Usage(
+            "--variance maps how the points of --samples 2 or more vary, for the mandelbrot \
+             fractal without --color-script"
+                .to_string(),
+        ));
+    }
+    if options.cost_map.is_some()
+        && (options.fractal != Fractal::Mandelbrot
+            || options.color_script.is_some()
+            || options.progressive
+            || options.state.is_some())
+    {
+        return Err(Error::Usage(
+            "--cost-map maps the iterations of the mandelbrot fractal, without --color-script, \
+             --progressive or --state"
+                .to_string(),
+        ));
+    }
+    if options.render_scale > 1.0
+        && (options.sampling.samples > 1
+            && !matches!(
+                options.fractal,
+                Fractal::Buddhabrot
+                    | Fractal::AntiBuddhabrot
+                    | Fractal::Nebulabrot
+                    | Fractal::Flame
+            )
+            || options.progressive
+            || options.state.is_some()
+            || options.low_memory)
+    {
+        return Err(Error::Usage(
+            "--render-scale is an alternative to --samples, rendering the whole image at once \
+             without --progressive, --state or --low-memory"
+                .to_string(),
+        ));
+    }
+    // the dimensions of the image rendered, shrunk to `bounds` once it is
+    let render_bounds = resample::scaled_bounds(bounds, options.render_scale);
+    error::validate_viewport(render_bounds, upper_left, lower_right)?;
+    if options.derivative.is_some() && options.fractal != Fractal::Mandelbrot {
+        return Err(Error::Usage(
+            "--derivative is only computed for the mandelbrot fractal".to_string(),
+        ));
+    }
+    if (options.layers || options.layers_exr.is_some())
+        && (options.fractal != Fractal::Mandelbrot
+            || options.color_script.is_some()
+            || options.art.is_some()
+            || options.mosaic.is_some()
+            || options.contours.is_some()
+            || output == "-")
+    {
+        return Err(Error::Usage(
+            "--layers and --layers-exr split images of the mandelbrot fractal written to a \
+             file, without --color-script, --art, --mosaic or --contours"
+                .to_string(),
+        ));
+    }
+    if options.traversal != Traversal::Rows
+        && (options.fractal != Fractal::Mandelbrot
+            || options.color_script.is_some()
+            || options.progressive
+            || options.state.is_some()
+            || options.sampling.samples > 1
+            || options.numeric != Numeric::F64)
+    {
+        return Err(Error::Usage(
+            "--traversal renders the mandelbrot fractal one f64 point per pixel, without \
+             --color-script, --progressive, --state, --samples or --numeric"
+                .to_string(),
+        ));
+    }
+    if options.numeric != Numeric::F64
+        && (options.fractal != Fractal::Mandelbrot
+            || options.color_script.is_some()
+            || options.progressive
+            || options.state.is_some()
+            || options.sampling.samples > 1)
+    {
+        return Err(Error::Usage(
+            "--numeric only applies to the mandelbrot fractal, one point per pixel, without \
+             --color-script, --progressive or --state"
+                .to_string(),
+        ));
+    }
+    if options.low_memory
+        && (!matches!(options.fractal, Fractal::Mandelbrot | Fractal::Formula)
+            || options.color_script.is_some()
+            || options.progressive
+            || options.state.is_some()
+            || options.bands.is_some()
+            || options.glow.is_some()
+            || options.background.is_some()
+            || options.transparent
+            || options.mask.is_some()
+            || options.art.is_some()
+            || options.mosaic.is_some()
+            || options.contours.is_some()
+            || options.layers
+            || options.layers_exr.is_some()
+            || options.derivative.is_some()
+            || options.report.is_some()
+            || options.dither.is_some()
+            || options.watermark.is_some())
+    {
+        return Err(Error::Usage(
+            "--low-memory renders the mandelbrot or formula fractal, colored by --palette at \
+             most, without --color-script, --progressive, --state or the options that need the \
+             whole image at once"
+                .to_string(),
+        ));
+    }
+    if (options.transparent || options.mask.is_some())
+        && (!matches!(options.fractal, Fractal::Mandelbrot | Fractal::Formula)
+            || options.color_script.is_some()
+            || options.art.is_some()
+            || options.mosaic.is_some())
+    {
+        return Err(Error::Usage(
+            "--transparent and --mask separate the set of the mandelbrot or formula fractal, \
+             without --color-script, --art or --mosaic"
+                .to_string(),
+        ));
+    }
+    if options.transparent && (options.layers || options.layers_exr.is_some()) {
+        return Err(Error::Usage(
+            "--layers and --layers-exr have transparent layers of their own, without \
+             --transparent"
+                .to_string(),
+        ));
+    }
+    if options.color_script.is_some() && options.fractal != Fractal::Mandelbrot {
+        return Err(Error::Usage(
+            "--color-script only colors the mandelbrot fractal".to_string(),
+        ));
+    }
+    #[cfg(feature = "scripting")]
+    let color_script = match &options.color_script {
+        Some(file) => Some(ColorScript::read(file)?),
+        None => None,
+    };
+    let rgb = matches!(options.fractal, Fractal::Nebulabrot | Fractal::Newton)
+        || options.color_script.is_some();
+    if rgb
+        && (options.bands.is_some()
+            || options.glow.is_some()
+            || options.palette.is_some()
+            || options.background.is_some()
+            || options.art.is_some()
+            || options.mosaic.is_some()
+            || options.contours.is_some()
+            || options.report.is_some())
+    {
+        return Err(Error::Usage(
+            "--bands, --glow, --palette, --background, --art, --mosaic, --contours and \
+             --report need a grayscale fractal"
+                .to_string(),
+        ));
+    }
+    if options.palette.is_none()
+        && (options.palette_cycle != palette::Cycle::default() || options.dither.is_some())
+    {
+        return Err(Error::Usage(
+            "--transfer, --palette-offset, --palette-period, --palette-blend and --dither go \
+             with --palette"
+                .to_string(),
+        ));
+    }
+    if (options.palette.is_some() || options.background.is_some())
+        && (options.art.is_some() || options.mosaic.is_some())
+    {
+        return Err(Error::Usage(
+            "--art and --mosaic draw in black and white, without --palette or --background"
+                .to_string(),
+        ));
+    }
+    if options.contours.is_some()
+        && (options.bands.is_some()
+            || options.glow.is_some()
+            || options.palette.is_some()
+            || options.background.is_some()
+            || options.transparent
+            || options.art.is_some()
+            || options.mosaic.is_some()
+            || !(output == "-" || output.to_ascii_lowercase().ends_with(".svg")))
+    {
+        return Err(Error::Usage(
+            "--contours traces the escape times into an SVG file, FILE.svg, without --bands, \
+             --glow, --palette, --background, --transparent, --art or --mosaic"
+                .to_string(),
+        ));
+    }
+    if options.watermark.is_some()
+        && (options.art.is_some() || options.mosaic.is_some() || options.contours.is_some())
+    {
+        return Err(Error::Usage(
+            "--watermark stamps images, without --art, --mosaic or --contours".to_string(),
+        ));
+    }
+    // read before rendering, so that a bad file is found out at once
+    let palette = match &options.palette {
+        Some(name) => Some(autopalette::choose(name)?),
+        None => None,
+    };
+    let backdrop = match &options.background {
+        Some(background) => Some(background.pixels(bounds)?),
+        None => None,
+    };
+    let parsed = Instant::now();
+
+    let channels = if rgb { 3 } else { 1 };
+    let len = channels * render_bounds.0 * render_bounds.1;
+    let mut pixels = if options.low_memory {
+        Pixels::Mapped(MappedBuffer::new(&pixels_file(&output), len)?)
+    } else {
+        Pixels::Owned(vec![0; len])
+    };
+    // what isn't rendered keeps its color
+    if options.fill != [0, 0, 0] {
+        let fill = fill_pixel(options.fill, channels);
+        for pixel in pixels.chunks_mut(channels) {
+            pixel.copy_from_slice(&fill);
+        }
+    }
+
+    let progress = Progress::new(render_bounds.1);
+    let checkpoint_file = checkpoint_file(&output);
+    let view = (bounds, upper_left, lower_right);
+    let render_view = (render_bounds, upper_left, lower_right);
+    let checkpoint =
+        |pixels: &[u8]| match write_pixels(&options, &checkpoint_file, pixels, render_view) {
+            Ok(()) => log(
+                &options,
+                Priority::Info,
+                &format!("checkpoint written to {}", checkpoint_file),
+            ),
+            Err(e) => log(
+                &options,
+                Priority::Error,
+                &format!("error writing the checkpoint: {}", e),
+            ),
+        };
+    let cancel = AtomicBool::new(false);
+    let threads = options.threads.unwrap_or_else(num_cpus::get);
+    let usage = Usage::new(threads);
+    let variance = (supersampled && (options.variance.is_some() || options.stats_json.is_some()))
+        .then(|| Variance::new(bounds));
+    let cost = options.cost_map.as_ref().map(|_| Cost::new(render_bounds));
+    let schedule = render::Schedule {
+        chunk_rows: options.chunk_rows,
+        traversal: options.traversal,
+        progress: Some(&progress),
+        checkpoint: Some(&checkpoint),
+        cancel: Some(&cancel),
+        deadline: options.timeout.map(|timeout| Instant::now() + timeout),
+        sampling: options.sampling,
+        limit: limit.unwrap_or(render::DEFAULT_LIMIT),
+        numeric: options.numeric,
+        bailout: options.formula.bailout,
+        usage: options.stats_json.as_ref().map(|_| &usage),
+        variance: variance.as_ref(),
+        cost: cost.as_ref(),
+        mirror: options.mirror,
+        ..render::Schedule::new(threads)
+    };
+    if options.fit.is_none() && fit::mismatched(bounds, upper_left, lower_right) {
+        warn(
+            &options,
+            outcome,
+            result::ASPECT_MISMATCH,
+            format!(
+                "the corners don't have the aspect ratio of the image, so its pixels are \
+                 stretched {:.2} times across; --fit letterbox or crop keeps them square, \
+                 --fit stretch says it is meant",
+                fit::stretch(bounds, upper_left, lower_right)
+            ),
+        );
+    }
+    if !options
+        .numeric
+        .resolves(upper_left, lower_right, render_bounds.0)
+    {
+        warn(
+            &options,
+            outcome,
+            result::PRECISION_LIMIT_HIT,
+            format!(
+                "the view is too deep for --numeric {}: neighboring pixels are the same point",
+                options.numeric
+            ),
+        );
+    }
+    if options.fractal == Fractal::Mandelbrot
+        && limit::under_iterated(schedule.limit, upper_left, lower_right)
+    {
+        warn(
+            &options,
+            outcome,
+            result::UNDER_ITERATION_DETECTED,
+            format!(
+                "{} iterations leave points near the boundary in the set; try --max-iters \
+                 adaptive",
+                schedule.limit
+            ),
         );
-        std::process::exit(1);
     }
 
-    let bounds: (usize, usize) = parse_pair(&args[2], 'x').expect("error parsing image dimensions");
-    let upper_left = parse_complex(&args[3]).expect("error parsing the upper left corner point");
-    let lower_right = parse_complex(&args[4]).expect("error parsing the lower right corner point");
+    signals::install();
+    signals::install_interrupt();
+    if options.service {
+        signals::install_terminate();
+        service::log(
+            Priority::Info,
+            &format!("rendering {}", output),
+            &[
+                ("FILE", output.clone()),
+                ("PIXELS", format!("{}x{}", render_bounds.0, render_bounds.1)),
+                ("UPPER_LEFT", upper_left.to_string()),
+                ("LOWER_RIGHT", lower_right.to_string()),
+            ],
+        );
+        notify(&format!("READY=1\nSTATUS=rendering {}", output));
+    }
+    let done = AtomicBool::new(false);
+    #[cfg(feature = "scripting")]
+    let mut scripted = Ok(());
+    let mut deepened = Ok(());
+    thread::scope(|scope| {
+        scope.spawn(|| {
+            let mut ticks = 0;
+            while !done.load(Ordering::Relaxed) {
+                thread::sleep(Duration::from_millis(100));
+                ticks += 1;
+                if signals::take_progress_request() {
+                    log(&options, Priority::Info, &progress.summary());
+                }
+                if signals::take_checkpoint_request() {
+                    progress.request_checkpoint();
+                }
+                if options.service && ticks % 10 == 0 {
+                    notify(&format!("STATUS={}", progress.summary()));
+                }
+                if signals::take_terminate_request() {
+                    service::log(
+                        Priority::Warning,
+                        "SIGTERM received, finishing the rows in progress",
+                        &[],
+                    );
+                    notify("STOPPING=1");
+                    cancel.store(true, Ordering::Relaxed);
+                }
+                if signals::take_interrupt_request() {
+                    log(
+                        &options,
+                        Priority::Warning,
+                        "interrupted, finishing the rows in progress; Ctrl-C again quits at once",
+                    );
+                    cancel.store(true, Ordering::Relaxed);
+                }
+            }
+        });
 
-    let mut pixels = vec![0; bounds.0 * bounds.1];
+        let _done = Done(&done);
+        match options.fractal {
+            #[cfg(feature = "scripting")]
+            Fractal::Mandelbrot if color_script.is_some() => {
+                scripted = mandelbrot::script::render(
+                    &mut pixels,
+                    render_bounds,
+                    upper_left,
+                    lower_right,
+                    color_script.as_ref().unwrap(),
+                    options.buddhabrot.max_iters,
+                    &schedule,
+                );
+            }
+            Fractal::Mandelbrot if options.progressive || options.state.is_some() => {
+                let view = (bounds, upper_left, lower_right);
+                deepened = deepen_orbits(
+                    &options,
+                    &output,
+                    view,
+                    palette.as_ref(),
+                    &schedule,
+                    outcome,
+                )
+                .map(|deepened| pixels = Pixels::Owned(deepened));
+            }
+            Fractal::Mandelbrot => {
+                render::render_parallel(
+                    options.strategy,
+                    &mut pixels,
+                    render_bounds,
+                    upper_left,
+                    lower_right,
+                    &schedule,
+                );
+            }
+            Fractal::Buddhabrot | Fractal::AntiBuddhabrot => buddhabrot::render(
+                &mut pixels,
+                render_bounds,
+                upper_left,
+                lower_right,
+                &Buddhabrot {
+                    seed: options.sampling.seed,
+                    anti: options.fractal == Fractal::AntiBuddhabrot,
+                    ..options.buddhabrot
+                },
+                &schedule,
+            ),
+            Fractal::Flame => buddhabrot::render_flame(
+                &mut pixels,
+                render_bounds,
+                upper_left,
+                lower_right,
+                &Buddhabrot {
+                    seed: options.sampling.seed,
+                    ..options.buddhabrot
+                },
+                &schedule,
+            ),
+            Fractal::Nebulabrot => buddhabrot::render_nebulabrot(
+                &mut pixels,
+                render_bounds,
+                upper_left,
+                lower_right,
+                &Buddhabrot {
+                    seed: options.sampling.seed,
+                    ..options.buddhabrot
+                },
+                &options.channels,
+                &schedule,
+            ),
+            Fractal::Newton => newton::render(
+                &mut pixels,
+                render_bounds,
+                upper_left,
+                lower_right,
+                &options.newton,
+                &schedule,
+            ),
+            Fractal::Formula => formula::render(
+                &mut pixels,
+                render_bounds,
+                upper_left,
+                lower_right,
+                &options.formula,
+                &schedule,
+            ),
+        }
+    });
+    #[cfg(feature = "scripting")]
+    scripted?;
+    signals::restore_interrupt();
+    deepened?;
+    let rendered = Instant::now();
+
+    if cancel.load(Ordering::Relaxed) {
+        write_pixels(&options, &checkpoint_file, &pixels, render_view)?;
+        outcome.wrote(&checkpoint_file);
+        return Err(Error::Interrupted(format!(
+            "stopped after {}, what was rendered is in {}; {}",
+            progress.summary(),
+            checkpoint_file,
+            resume_hint(&options)
+        )));
+    }
+    if render_bounds != bounds {
+        pixels = Pixels::Owned(resample::resample(
+            &pixels,
+            render_bounds,
+            bounds,
+            channels,
+            resample::Filter::Lanczos,
+        ));
+    }
+    if let Some(timeout) = options.timeout {
+        if schedule
+            .deadline
+            .is_some_and(|deadline| rendered >= deadline)
+        {
+            write_pixels(&options, &output, &pixels, view)?;
+            outcome.wrote(&output);
+            return Err(Error::Interrupted(format!(
+                "timed out after {} s, what was rendered by then is in {}",
+                timeout.as_secs_f64(),
+                output
+            )));
+        }
+    }
 
-    let threads = num_cpus::get();
-    let rows_per_band = bounds.1 / threads + 1;
-    {
-        let bands: Vec<&mut [u8]> = pixels.chunks_mut(rows_per_band * bounds.0).collect();
-        crossbeam::scope(|spawner| {
-            for (i, band) in bands.into_iter().enumerate() {
-                let top = rows_per_band * i;
-                let height = band.len() / bounds.0;
-                let band_bounds = (bounds.0, height);
-                let band_upper_left = pixel_to_point(bounds, (0, top), upper_left, lower_right);
-                let band_lower_right =
-                    pixel_to_point(bounds, (bounds.0, top + height), upper_left, lower_right);
-
-                spawner.spawn(move |_| {
-                    render(band, band_bounds, band_upper_left, band_lower_right);
+    // the report works from the escape times, so styling goes to a copy
+    let mut image = Cow::Borrowed(&pixels[..]);
+    if let Some(style) = &options.bands {
+        bands::apply(image.to_mut(), bounds, style);
+    }
+    if let Some(glow) = &options.glow {
+        glow::apply(image.to_mut(), bounds, glow);
+    }
+    // with --low-memory, colored a row at a time as it is written
+    let lut = palette
+        .as_ref()
+        .map(|palette| palette.lut_for(&image, &options.palette_cycle));
+    if let (Some(palette), Some(dither)) = (&palette, options.dither) {
+        let levels = palette.levels_for(&image, &options.palette_cycle);
+        image = Cow::Owned(dither::apply(&image, bounds, &levels, dither));
+    } else if let (Some(lut), false) = (&lut, options.low_memory) {
+        image = Cow::Owned(palette::apply(&image, lut));
+    }
+    if let Some(backdrop) = &backdrop {
+        image = Cow::Owned(background::apply(&pixels, &image, backdrop));
+    }
+    if options.transparent {
+        image = Cow::Owned(mask::transparent(&pixels, &image));
+    }
+    // SVG or text written instead of a PNG image, and the size of the image
+    let svg = output.to_ascii_lowercase().ends_with(".svg");
+    let mut document = None;
+    let mut image_bounds = bounds;
+    if let Some(art) = &options.art {
+        let drawing = art::draw(&image, bounds, art, options.sampling.seed);
+        if svg {
+            document = Some(drawing.to_svg());
+        } else {
+            image = Cow::Owned(drawing.rasterize());
+        }
+    }
+    if let Some(mosaic) = &options.mosaic {
+        match &mosaic.source {
+            mosaic::Source::Glyphs(glyphs) => {
+                let cell = mosaic.cell.unwrap_or((8, 16));
+                document = Some(if svg {
+                    mosaic::to_svg(&image, bounds, cell, glyphs)
+                } else {
+                    mosaic::to_text(&image, bounds, cell, glyphs)
                 });
             }
-        })
-        .unwrap();
+            mosaic::Source::Tiles(files) => {
+                let tiles = mosaic::read_tiles(files)?;
+                let cell = mosaic.cell.unwrap_or(tiles[0].size);
+                let (tiled, size) = mosaic::to_image(&image, bounds, cell, &tiles);
+                image = Cow::Owned(tiled);
+                image_bounds = size;
+            }
+        }
+    }
+    if let Some(contours) = &options.contours {
+        document = Some(contour::to_svg(&pixels, bounds, contours));
     }
+    if let Some(mark) = &options.watermark {
+        let lines = watermark::lines(upper_left, lower_right, mark.text.as_deref());
+        let logo = match &mark.logo {
+            Some(file) => Some(watermark::read_logo(file)?),
+            None => None,
+        };
+        let logo = logo.as_ref().map(|(pixels, size)| (&pixels[..], *size));
+        watermark::stamp(image.to_mut(), image_bounds, &lines, logo, mark.corner);
+    }
+    let styled = Instant::now();
+    // what --manifest records and --reproduce checks
+    let digest = (options.manifest || options.reproduce.is_some()).then(|| match &document {
+        Some(document) => reproduce::digest(document.as_bytes()),
+        None => reproduce::digest(&image),
+    });
 
-    write_image(&args[1], &pixels, bounds).expect("error writing the PNG file")
-}
+    match document {
+        Some(document) => output::write(&output, document.as_bytes())?,
+        None if options.low_memory => write_image_rows(
+            &output,
+            &image,
+            image_bounds,
+            upper_left,
+            lower_right,
+            lut.as_deref(),
+        )?,
+        None => write_image(&output, &image, image_bounds, upper_left, lower_right)?,
+    }
+    outcome.wrote(&output);
+    if let Some(digest) = digest {
+        let manifest = Manifest {
+            version: schema::VERSION,
+            program: reproduce::program(),
+            output: output.clone(),
+            args: recorded_args(&args),
+            seeds: reproduce::Seeds {
+                jitter_seed: options.sampling.seed,
+                seed: options.seed,
+            },
+            pixels: [bounds.0, bounds.1],
+            upper_left: [upper_left.re, upper_left.im],
+            lower_right: [lower_right.re, lower_right.im],
+            max_iters: schedule.limit,
+            digest,
+        };
+        if options.manifest {
+            let manifest_file = reproduce::manifest_file(&output);
+            output::write(&manifest_file, manifest.to_json().as_bytes())?;
+            outcome.wrote(&manifest_file);
+        }
+        if let Some(file) = &options.reproduce {
+            let recorded = Manifest::open(file)?;
+            if recorded.digest != manifest.digest {
+                let rendered_by = if recorded.program == manifest.program {
+                    String::new()
+                } else {
+                    format!(", rendered by {}", recorded.program)
+                };
+                return Err(Error::Different(format!(
+                    "{} doesn't have the pixels recorded in {}{}",
+                    output, file, rendered_by
+                )));
+            }
+        }
+    }
+    if let Some(mask_file) = &options.mask {
+        let mask = mask::png(&pixels, bounds, upper_left, lower_right)?;
+        output::write(mask_file, &mask)?;
+        outcome.wrote(mask_file);
+    }
+    if let (Some(variance_file), Some(variance)) = (&options.variance, &variance) {
+        let gray = sampling::variance_gray(&variance.values());
+        write_image(variance_file, &gray, bounds, upper_left, lower_right)?;
+        outcome.wrote(variance_file);
+    }
+    if let (Some(cost_file), Some(cost)) = (&options.cost_map, &cost) {
+        let fire = palette::preset("fire").expect("fire is built in");
+        let heatmap = palette::apply(&usage::cost_levels(&cost.values()), &fire.lut(256));
+        write_image(cost_file, &heatmap, render_bounds, upper_left, lower_right)?;
+        outcome.wrote(cost_file);
+    }
+    let encoded = Instant::now();
 
-/// try to determine if `c` is in the Mandlebrot set, using at most `limit`
-/// iterations to decide.
-///
-/// If `c` is not a member, returns `Some(i)`, where `i` is the number of
-/// iterations it tok for `c` to leave the circle of radius 2 centered on the origin.
-/// If `c` seems to be a member (more precisely, if we reached the iteration limit without
-/// being able to prove that `c` is not a member), return `None`.
-fn escape_time(c: Complex<f64>, limit: usize) -> Option<usize> {
-    let mut z = Complex { re: 0.0, im: 0.0 };
-    for i in 0..limit {
-        if z.norm_sqr() > 4.0 {
-            return Some(i);
+    if options.copy_center {
+        let center = (upper_left + lower_right) / 2.0;
+        clipboard::write(&format!("{},{}", center.re, center.im)).map_err(Error::Clipboard)?;
+    }
+
+    if options.derivative.is_some() || options.layers || options.layers_exr.is_some() {
+        let samples = derivative::field(
+            bounds,
+            upper_left,
+            lower_right,
+            schedule.limit,
+            schedule.threads,
+        );
+        if let Some(derivative_file) = &options.derivative {
+            output::write(derivative_file, &derivative::pfm(&samples, bounds))?;
+            outcome.wrote(derivative_file);
+        }
+        let split = (options.layers || options.layers_exr.is_some())
+            .then(|| layers::split(&pixels, &image, &samples, bounds, (upper_left, lower_right)));
+        if let (true, Some(split)) = (options.layers, &split) {
+            for (name, layer) in layers::NAMES.iter().zip(split.all()) {
+                let mut png = encode_png(layer, bounds, upper_left, lower_right)?;
+                if *name == "annotations" {
+                    share::add_png_text(&mut png, "Scale", &split.scale.to_string());
+                }
+                let file = output::with_suffix(&output, &format!("-{}", name));
+                output::write(&file, &png)?;
+                outcome.wrote(&file);
+            }
+        }
+        if let (Some(exr_file), Some(split)) = (&options.layers_exr, &split) {
+            let exr = exr::encode(&split.exr_channels(&samples), bounds);
+            output::write(exr_file, &exr)?;
+            outcome.wrote(exr_file);
+        }
+    }
+
+    if let Some(report_file) = &options.report {
+        let report = report::Report {
+            image_file: &output,
+            bounds,
+            upper_left,
+            lower_right,
+            pixels: &pixels,
+            timings: vec![
+                ("parse", parsed - started),
+                ("render", rendered - parsed),
+                ("style", styled - rendered),
+                ("encode", encoded - styled),
+            ],
+        };
+        report::write_report(report_file, &report)?;
+        outcome.wrote(report_file);
+    }
+
+    if let Some(stats_file) = &options.stats_json {
+        // orbits of buddhabrots and of deepened renders aren't counted
+        let measured = match options.fractal {
+            Fractal::Mandelbrot => !options.progressive && options.state.is_none(),
+            Fractal::Formula | Fractal::Newton => true,
+            Fractal::Buddhabrot
+            | Fractal::AntiBuddhabrot
+            | Fractal::Nebulabrot
+            | Fractal::Flame => false,
+        };
+        let escape_times = matches!(options.fractal, Fractal::Mandelbrot | Fractal::Formula);
+        let render_time = rendered - parsed;
+        let stats = usage::Stats {
+            version: schema::VERSION,
+            wall_seconds: started.elapsed().as_secs_f64(),
+            render_seconds: render_time.as_secs_f64(),
+            pixels: render_bounds.0 * render_bounds.1,
+            pixels_per_second: (render_bounds.0 * render_bounds.1) as f64
+                / render_time.as_secs_f64().max(f64::EPSILON),
+            iterations: measured.then(|| usage.iterations()),
+            kernel: cpu::kernel().name(),
+            interior_percent: (escape_times && !rgb).then(|| usage::interior_percent(&pixels)),
+            peak_memory_bytes: platform::peak_memory(),
+            threads: if measured {
+                usage.threads(render_time)
+            } else {
+                Vec::new()
+            },
+            variance: variance.as_ref().map(|variance| {
+                usage::VarianceStats::new(&variance.values(), options.sampling.samples)
+            }),
+        };
+        output::write(stats_file, stats.to_json().as_bytes())?;
+        outcome.wrote(stats_file);
+    }
+    outcome.stats = Some(result::Summary {
+        wall_seconds: started.elapsed().as_secs_f64(),
+        render_seconds: (rendered - parsed).as_secs_f64(),
+        pixels: render_bounds.0 * render_bounds.1,
+        max_iters: schedule.limit,
+        interior_percent: (matches!(options.fractal, Fractal::Mandelbrot | Fractal::Formula)
+            && !rgb)
+            .then(|| usage::interior_percent(&pixels)),
+    });
+
+    // the running totals and the log of visited regions, if the user keeps
+    // them
+    if let Ok(visited) = platform::config_file(coverage::FILE_NAME) {
+        if let Err(e) = coverage::record(&visited, upper_left, lower_right) {
+            log(
+                &options,
+                Priority::Warning,
+                &format!("error logging the view: {}", e),
+            );
+        }
+    }
+    if let Ok(tally_file) = Tally::default_file() {
+        let render = tally::Render {
+            fractal: &options.fractal.to_string(),
+            pixels: (render_bounds.0 * render_bounds.1) as u64,
+            cpu_seconds: (rendered - parsed).as_secs_f64() * threads as f64,
+            zoom: ComplexWidth::between(upper_left, lower_right).zoom().0,
+        };
+        if let Err(e) = tally::record(&tally_file, &render) {
+            log(
+                &options,
+                Priority::Warning,
+                &format!("error keeping the stats: {}", e),
+            );
         }
-        z = z * z + c;
     }
 
-    None
+    if options.service {
+        service::log(
+            Priority::Info,
+            &format!("rendered {}", output),
+            &[
+                ("FILE", output.clone()),
+                (
+                    "RENDER_SECONDS",
+                    format!("{:.3}", (rendered - parsed).as_secs_f64()),
+                ),
+            ],
+        );
+        notify("STOPPING=1");
+    }
+
+    Ok(())
 }
 
-/// Parse the string `s` as a coordinate pair, like `"200x300"` or `"1.0,0.4"`
-///
-/// Specifically, `s` should have the form `<left><sep><right>`, where `<sep>` is the
-/// character given by the `separator` argument, and `<left>` and `<right>` both strings
-/// that can be parsed by `T::from_str`. `separator` must be an ASCII character.
-///
-/// If `s` has the proper form, return `Some<(x, y)>`. If it doesn't parse correctly,
-/// return `None`.
-fn parse_pair<T: FromStr>(s: &str, separator: char) -> Option<(T, T)> {
-    match s.find(separator) {
-        Some(index) => match (T::from_str(&s[..index]), T::from_str(&s[index + 1..])) {
-            (Ok(l), Ok(r)) => Some((l, r)),
-            _ => None,
-        },
-        None => None,
+/// Tells the progress loop of a render that the render is over when
+/// dropped, also by a panicking render thread, which `thread::scope` only
+/// passes on once the loop has stopped.
+struct Done<'a>(&'a AtomicBool);
+
+impl Drop for Done<'_> {
+    fn drop(&mut self) {
+        self.0.store(true, Ordering::Relaxed);
     }
 }
 
 #[test]
-fn test_parse_pair() {
-    assert_eq!(parse_pair::<i32>("", ','), None);
-    assert_eq!(parse_pair::<i32>("1,", ','), None);
-    assert_eq!(parse_pair::<f64>("0.1,0.2", ','), Some((0.1, 0.2)));
-    assert_eq!(parse_pair::<f64>("500x", 'x'), None);
-    assert_eq!(parse_pair::<i32>("500x300", 'x'), Some((500, 300)));
-}
-
-/// Parse a pair of floating point numbers separated by a comma
-/// as a complex number
-fn parse_complex(s: &str) -> Option<Complex<f64>> {
-    match parse_pair(s, ',') {
-        Some((re, im)) => Some(Complex { re, im }),
-        None => None,
+fn test_done() {
+    let done = AtomicBool::new(false);
+    let rendered = std::panic::catch_unwind(|| {
+        thread::scope(|scope| {
+            scope.spawn(|| {
+                while !done.load(Ordering::Relaxed) {
+                    thread::sleep(Duration::from_millis(1));
+                }
+            });
+            let _done = Done(&done);
+            panic!("a render thread panicked");
+        })
+    });
+    assert!(rendered.is_err());
+}
+
+/// Write the unstyled `pixels` of `view` to `filename`, a row at a time with
+/// `--low-memory`.
+fn write_pixels(
+    options: &Options,
+    filename: &str,
+    pixels: &[u8],
+    (bounds, upper_left, lower_right): ((usize, usize), Complex<f64>, Complex<f64>),
+) -> Result<()> {
+    if options.low_memory {
+        write_image_rows(filename, pixels, bounds, upper_left, lower_right, None)
+    } else {
+        write_image(filename, pixels, bounds, upper_left, lower_right)
+    }
+}
+
+/// `color` as one pixel of an image with `channels` bytes a pixel: its
+/// luma in grayscale.
+fn fill_pixel(color: [u8; 3], channels: usize) -> Vec<u8> {
+    match channels {
+        1 => {
+            let [r, g, b] = color.map(u32::from);
+            vec![((299 * r + 587 * g + 114 * b + 500) / 1000) as u8]
+        }
+        _ => color.to_vec(),
     }
 }
 
 #[test]
-fn test_parse_complex() {
-    assert_eq!(parse_complex("0.1,0.3"), Some(Complex { re: 0.1, im: 0.3 }));
-    assert_eq!(parse_complex(",0.3"), None);
+fn test_fill_pixel() {
+    assert_eq!(fill_pixel([255, 0, 255], 3), [255, 0, 255]);
+    assert_eq!(fill_pixel([255, 255, 255], 1), [255]);
+    assert_eq!(fill_pixel([255, 0, 255], 1), [105]);
 }
 
-/// Given the row and the column of a pixel in the output image, return
-/// the corresponding point on the complex plane.
-///
-/// `bounds` is a pair giving the width and the height of the image in pixels.
-/// `pixel` is a (column,row) pair inidicating a particular pixel in that image.
-/// The `upper_left` and `lower_right` parameters are points on the complex plane
-/// designating the area our image covers.
-fn pixel_to_point(
-    bounds: (usize, usize),
-    pixel: (usize, usize),
-    upper_left: Complex<f64>,
-    lower_right: Complex<f64>,
-) -> Complex<f64> {
-    let (width, height) = (
-        lower_right.re - upper_left.re,
-        upper_left.im - lower_right.im,
-    );
+/// How to go on with the render the options describe once it was stopped:
+/// from the orbits saved with `--state`, if it was rendered with it or could
+/// have been, or from the start.
+fn resume_hint(options: &Options) -> String {
+    let deepens = options.fractal == Fractal::Mandelbrot
+        && options.color_script.is_none()
+        && options.strategy == Strategy::Naive
+        && options.sampling.samples <= 1
+        && options.numeric == Numeric::F64;
+    match &options.state {
+        Some(state) => format!(
+            "run the same command again to go on from the orbits saved in {}",
+            state
+        ),
+        None if deepens => "run the same command again to render it all, adding --state STATE \
+                            to save the orbits when stopped and go on from them the next time"
+            .to_string(),
+        None => "run the same command again to render it all".to_string(),
+    }
+}
+
+/// Warn the user about `message`, and note it in `outcome` as `code`.
+fn warn(options: &Options, outcome: &mut Outcome, code: &'static str, message: String) {
+    log(options, Priority::Warning, &format!("warning: {}", message));
+    outcome.warn(code, message);
+}
+
+/// Read the options `args`, and compute escape times from then on with the
+/// kernel they force, if any.
+fn parse_options(args: &[String]) -> Result<Options> {
+    let options = parse_args(args).map_err(Error::Usage)?;
+    if let Some(kernel) = options.kernel {
+        cpu::force(kernel)?;
+    }
+    Ok(options)
+}
+
+/// Tell the user about `message`, through the journal in service mode.
+fn log(options: &Options, priority: Priority, message: &str) {
+    if options.service {
+        service::log(priority, message, &[]);
+    } else {
+        eprintln!("{}", message);
+    }
+}
+
+/// Notify the service manager of `state`, if there is one; failing to do so
+/// is logged but doesn't stop the render.
+fn notify(state: &str) {
+    if let Err(e) = service::notify(state) {
+        service::log(
+            Priority::Warning,
+            &format!("error notifying the service manager: {}", e),
+            &[],
+        );
+    }
+}
+
+/// Render the mandelbrot fractal for `--progressive` and `--state`, by
+/// deepening the orbits of the pixels of `view`, given by its bounds and
+/// corners: going on from those in the state file if it exists, writing a
+/// snapshot of every pass if progressive, and saving them to the state file
+/// when done, or stopped.
+fn deepen_orbits(
+    options: &Options,
+    output: &str,
+    (bounds, upper_left, lower_right): ((usize, usize), Complex<f64>, Complex<f64>),
+    palette: Option<&Choice>,
+    schedule: &render::Schedule,
+    outcome: &mut Outcome,
+) -> Result<Vec<u8>> {
+    let mut deepening = match &options.state {
+        Some(file) if platform::path(file).exists() => {
+            let deepening = Deepening::load(file)?;
+            if (
+                deepening.bounds,
+                deepening.upper_left,
+                deepening.lower_right,
+            ) != (bounds, upper_left, lower_right)
+            {
+                return Err(Error::Usage(format!(
+                    "{} holds the orbits of another view",
+                    file
+                )));
+            }
+            log(
+                options,
+                Priority::Info,
+                &format!("going on from {} iterations in {}", deepening.limit, file),
+            );
+            deepening
+        }
+        _ => Deepening::new(bounds, upper_left, lower_right),
+    };
+
+    let deepened = if options.progressive {
+        deepen::progressive(&mut deepening, schedule, &mut |snapshot| {
+            let mut image = snapshot.pixels();
+            if let Some(palette) = palette {
+                image = palette::apply(&image, &palette.lut_for(&image, &options.palette_cycle));
+            }
+            let file = snapshot_file(output, snapshot.limit);
+            write_image(&file, &image, bounds, upper_left, lower_right)?;
+            outcome.wrote(&file);
+            log(
+                options,
+                Priority::Info,
+                &format!(
+                    "{} iterations, {} pixels still inside, written to {}",
+                    snapshot.limit,
+                    snapshot.interior(),
+                    file
+                ),
+            );
+            Ok(())
+        })
+    } else {
+        deepening.deepen(schedule.limit, schedule);
+        Ok(())
+    };
+    if let Some(file) = &options.state {
+        deepening.save(file)?;
+        outcome.wrote(file);
+    }
+    deepened?;
+    Ok(deepening.pixels())
+}
+
+/// The file a checkpoint of the render written to `filename` goes to: the
+/// same name, with `.checkpoint` before the extension. Checkpoints of images
+/// written to stdout or to a device like `NUL` go to
+/// `mandelbrot.checkpoint.png` instead.
+fn checkpoint_file(filename: &str) -> String {
+    if output::is_special(filename) {
+        return "mandelbrot.checkpoint.png".to_string();
+    }
+    output::with_suffix(filename, ".checkpoint")
+}
 
-    Complex {
-        re: upper_left.re + pixel.0 as f64 * width / bounds.0 as f64,
-        im: upper_left.im - pixel.1 as f64 * height / bounds.1 as f64,
-        // pixel.1 increases as we go down, the imaginary component increases as we go up
+/// The file the pixels of `filename` are kept in while it renders with
+/// `--low-memory`, next to it like its checkpoint.
+fn pixels_file(filename: &str) -> String {
+    if output::is_special(filename) {
+        return "mandelbrot.pixels".to_string();
     }
+    format!("{}.pixels", filename)
 }
 
 #[test]
-fn test_pixel_to_point() {
+fn test_checkpoint_file() {
     assert_eq!(
-        pixel_to_point(
-            (100, 200),
-            (25, 175),
-            Complex { re: -1.0, im: 1.0 },
-            Complex { re: 1.0, im: -1.0 }
-        ),
-        Complex {
-            re: -0.5,
-            im: -0.75
-        }
+        checkpoint_file("out/mandel.png"),
+        "out/mandel.checkpoint.png"
     );
+    assert_eq!(checkpoint_file("./mandel"), "./mandel.checkpoint");
+    assert_eq!(checkpoint_file("-"), "mandelbrot.checkpoint.png");
+}
+
+/// The file the snapshot at `limit` iterations of a `--progressive` render
+/// written to `filename` goes to: the same name, with `-LIMIT` before the
+/// extension, or `mandelbrot-LIMIT.png` like checkpoints.
+fn snapshot_file(filename: &str, limit: usize) -> String {
+    let suffix = format!("-{}", limit);
+    if output::is_special(filename) {
+        return format!("mandelbrot{}.png", suffix);
+    }
+    output::with_suffix(filename, &suffix)
+}
+
+/// The usage message, with `{program}` standing for the name the program
+/// is run as, and `{anchors}` and `{palettes}` for the names of the
+/// built-in anchors and palettes. `completions` reads the subcommands and
+/// options from it as well.
+const USAGE: &str = "\
+Usage: {program} FILE PIXELS UPPERLEFT LOWERRIGHT [OPTIONS]
+       {program} FILE PIXELS --center CENTER [--zoom ZOOM] [OPTIONS]
+       {program} FILE [PIXELS] --from IMAGE.png [OPTIONS]
+       {program} FILE PIXELS --random-viewport [--seed N] [--zoom ZOOM] [OPTIONS]
+       {program} --reproduce MANIFEST.json [FILE]
+       {program} explore [--center CENTER] [--zoom ZOOM] [--from IMAGE.png] [--ascii]
+               [--scene SCENE.json] [--bind ACTION=KEY] [--high-contrast]
+               [--reduced-motion] [--screen-reader]
+       {program} animate FILE PIXELS --scene SCENE.json [--frames N]
+               [--format png|gif|apng] [--fps N] [--exp-map] [--temporal WEIGHT]
+       {program} animate --video FILE --pixels PIXELS --scene SCENE.json [--fps N]
+               [--codec CODEC] [--frames N] [--exp-map] [--temporal WEIGHT]
+       {program} serve [--listen ADDRESS] [--workers N] [--cache-tiles N] [--timeout SECS]
+               [--max-iters N] [--palette NAME] [--sandbox]
+       {program} location FILE.kfr|FILE.toml PIXELS VIEW...
+       {program} export-mesh FILE.obj|FILE.stl|FILE.ply PIXELS VIEW... [--decimate N]
+               [--height-scale SCALE] [--max-iters N]
+       {program} export-dzi FILE.dzi|DIR PIXELS VIEW... [--tile-size N] [--base-url URL]
+               [--palette NAME] [--max-iters N] [--strategy STRATEGY]
+       {program} project save FILE.mandel VIEW... [FRACTAL AND COLORING OPTIONS...]
+               [--scene SCENE.json] [--bookmarks FILE]
+       {program} project migrate FILE.mandel
+       {program} project diff A.mandel B.mandel
+       {program} bookmark add NAME VIEW...
+       {program} bookmark list | remove NAME
+       {program} project merge BASE.mandel A.mandel B.mandel OUT.mandel
+       {program} convert-view --pixels PIXELS --from-corners UL LR | --from-center CENTER
+               [--zoom ZOOM]
+       {program} find [FILE PIXELS] --zoom ZOOM [--top N] [--candidates N] [--seed N]
+       {program} extend IN.png left|right|up|down PIXELS OUT.png [--max-iters N]
+               [--bailout RADIUS] [--palette NAME] [--transfer FUNCTION]
+               [--palette-offset TURNS] [--palette-period LENGTH]
+       {program} compare A.png B.png [--tolerance N] [--diff DIFF.png]
+       {program} orbit RE,IM [--max-iters N] [--bailout RADIUS] [--out PLOT.svg|PLOT.png]
+       {program} batch MANIFEST.json [--jobs N]
+       {program} stats enable | show | disable
+       {program} stats area [FILE.json|-] [PIXELS] [VIEW...] [--samples N] [--jitter-seed N]
+       {program} cache stats | clear
+       {program} config show [--effective [SUBCOMMAND] OPTIONS...]
+       {program} completions bash|zsh|fish
+       {program} validate SCENE|PALETTE|PROJECT|config.toml [--pixels PIXELS] [--frames N]
+       {program} coordinator FILE PIXELS VIEW... [--listen ADDRESS] [--lease SECS]
+       {program} coordinator FILE PIXELS --scene SCENE.json [--frames N] [--fps N]
+               [--listen ADDRESS] [--lease SECS]
+       {program} worker ADDRESS [--threads N]
+       {program} coverage map FILE.png [PIXELS] [VIEW...]
+       {program} palette preview NAME|FILE --out FILE.png [--pixels PIXELS]
+       {program} palette --list-palettes [--preview-dir DIR]
+Example: {program} mandel.png 1000x750 -1.20,0.35 -1.0,0.2
+
+FILE may be - to write the image to stdout, or NUL (or /dev/null) to only
+render it. The positional arguments can also be given as --output,
+--pixels, --upper-left and --lower-right.
+
+Points can be given relative to a named anchor, as NAME or NAME + RE,IM;
+the built-in anchors are {anchors}.
+
+Options:
+  --job JOB.json        read options from a JSON job file, or from stdin if
+                        JOB.json is -; options after it override the job's
+  --project FILE.mandel read the view, fractal, coloring, scene and bookmarks
+                        of a project file; options after it override them
+  --center CENTER       center the view on CENTER, or on the point on the
+                        clipboard if CENTER is `clipboard`; a compact
+                        location like @-0.75,0.1,z=20 also sets the zoom,
+                        and can stand for both corners too
+  --zoom ZOOM           magnify the view centered on CENTER (default 1,
+                        which is 4 wide)
+  --bookmarks FILE      read named points from FILE, one NAME RE,IM a line
+  --bookmark NAME       render the view saved as NAME with bookmark add
+  --copy-center         copy the center of the view to the clipboard
+  --from IMAGE.png      render the view of an image written earlier again
+  --location FILE.kfr   render the view of a Kalles Fraktaler (.kfr) or
+                        Fraktaler 3 (.toml) location file, with its
+                        iterations unless --max-iters is given
+  --random-viewport     center the view on a random point near the boundary
+                        of the set, chosen by --seed, --zoom deep
+  --fit FIT             when the corners don't have the aspect ratio of the
+                        image, letterbox or crop the view to keep pixels
+                        square, or stretch them without a warning
+  --report REPORT.html  also write an HTML report of the render
+  --stats-json FILE     also write the time, speed, iterations, thread
+                        use and peak memory of the render as JSON
+  --cost-map FILE.png   also write a heatmap of the iterations spent on every
+                        pixel, samples included
+  --result json         write the status, files written, warnings and
+                        main figures of the render to stdout as JSON at
+                        the end, even if it fails
+  --manifest            also write FILE.manifest.json, the options, seeds and
+                        a digest of the pixels, for --reproduce
+  --reproduce MANIFEST.json  render the image of a manifest again, to FILE
+                        if given, failing if its pixels differ
+  --derivative FILE.pfm also write the derivative field, the direction of the
+                        normal and the distance to the set in pixels, as a
+                        float image, for lighting in other programs
+  --layers              also write FILE-exterior.png, FILE-interior.png,
+                        FILE-boundary.png and FILE-annotations.png, layers
+                        for compositing
+  --layers-exr FILE.exr also write the layers and the derivative field as
+                        one layered OpenEXR image
+  --low-memory          keep the pixels in FILE.pixels, mapped into memory,
+                        and write FILE a row at a time, for images larger
+                        than memory
+  --on-exists POLICY    if FILE exists: overwrite it (the default), skip the
+                        render, or increment to the first free FILE-N.png;
+                        animate and coordinator apply it to each frame
+  --no-mkdir            don't create the directories FILE goes in
+  --decimal-comma       read corner points as -0,75;0,1
+  --decimal-point       read corner points as -0.75,0.1
+  --locale LOCALE       use the decimal separator of LOCALE, e.g. de_DE
+  --fractal FRACTAL     mandelbrot (the default), buddhabrot,
+                        anti-buddhabrot, nebulabrot, flame, newton or formula
+  --poly POLYNOMIAL     the polynomial of the newton fractal (default z^3-1)
+  --formula FORMULA     iterate FORMULA in z and c instead of z*z + c, e.g.
+                        \"z*z*z + c*z + c\" or \"sin(z) * c\"
+  --bailout RADIUS      how far from 0 an orbit escapes (default 2); larger
+                        radii smooth --color-script's escape times
+  --strategy STRATEGY   naive (the default), border-trace or adaptive
+  --max-error N         the gray levels the corners of a cell of --strategy
+                        adaptive may be apart for it to be interpolated
+                        (default 0)
+  --no-mirror           compute both halves of views centered on the real
+                        axis instead of mirroring the top one
+  --numeric TYPE        compute in f32, f64 (the default) or dd, double-double
+                        for views too deep for f64, down to a zoom of 1e15
+  --force-kernel NAME   compute escape times with scalar, avx2, avx512 or neon
+                        rather than the fastest the processor has
+  --chunk-rows ROWS     rows a thread renders at a time (default 8)
+  --traversal ORDER     rows (the default), or blocks along a hilbert or
+                        morton curve
+  --samples N           average N jittered points per pixel (default 1)
+                        or trace N orbits per pixel of a buddhabrot (default 64)
+  --jitter              give every pixel random points of its own rather
+                        than the pattern of --samples
+  --jitter-seed SEED    where the pattern of --samples starts (default 0)
+  --variance FILE.png   also write how much the points of --samples vary in
+                        every pixel, white where they disagree the most
+  --render-scale SCALE  render SCALE times as many pixels across and down,
+                        then shrink the image, at most 8 (default 1)
+  --min-iters N         leave out buddhabrot orbits escaping sooner (default 0)
+  --max-iters N         iterations before a point is taken to be inside the
+                        set (default 255, or 1000 for buddhabrots and color
+                        scripts); auto estimates it from the zoom, and
+                        adaptive from a sample of the view too
+  --progressive         render at 64 iterations first, then double the limit
+                        until --max-iters, only iterating the pixels still
+                        inside further, and write FILE-N.png after each pass
+  --state STATE         save where the orbit of every pixel got to in STATE,
+                        and go on from there if it exists, to render the
+                        same view again with a higher --max-iters
+  --red-iters RANGE     the escape times, MAX or MIN-MAX, of the nebulabrot
+                        orbits counted in red (default 5000); --green-iters
+                        (500) and --blue-iters (50) likewise
+  --color-script FILE   color the set with the Rhai function color(e) in
+                        FILE (needs the scripting feature)
+  --bands ITERATIONS    draw contour bands ITERATIONS escape times wide
+  --band-outline GRAY   the gray level of the band outlines (default 0)
+  --band-outline-width N  0, 1 (the default) or 2 pixels
+  --glow RADIUS         let the filaments glow RADIUS pixels around them
+  --glow-strength S     how strong the glow is (default 1)
+  --glow-kernel KERNEL  gaussian (the default), exponential or box
+  --palette FILE        color the image with the gradient in FILE: .json,
+                        .toml, UltraFractal .ugr or GIMP .ggr, or with a
+                        built-in palette: {palettes};
+                        auto, auto:hues or auto:NAME fit the stops of a
+                        palette to the gray levels of each image
+  --transfer FUNCTION   map escape times to the gradient of --palette
+                        linearly (the default), by sqrt, cbrt or log, or
+                        by a formula in x, from 0 to 1, like \"x^0.7\"
+  --palette-offset TURNS  shift the gradient of --palette along by TURNS
+  --palette-period LENGTH  repeat the gradient every LENGTH of the gray
+                        levels (default 1), or log:LENGTH to repeat it
+                        on a logarithmic scale of escape times
+  --palette-blend BLEND mix the colors of --palette in srgb, as they are
+                        stored (the default), or in linear light
+  --dither DITHER       round the colors of --palette to 8 bits with ordered
+                        or floyd-steinberg dithering instead of banding
+  --background COLOR|IMAGE  show #rrggbb or the PNG image IMAGE, stretched
+                        to fit, where points escape, keeping the set
+  --transparent         write RGBA, with the set transparent
+  --mask FILE           write a black and white mask of the set to FILE
+  --list-palettes       list the built-in palettes instead of rendering
+  --preview-dir DIR     with --list-palettes, write a preview of each to DIR
+  --art STYLE           redraw the image as stipple dots or hatch strokes;
+                        written as SVG if FILE ends in .svg
+  --art-spacing N       the size of the stipple cells and the distance
+                        between strokes, in pixels (default 6)
+  --mosaic PIECES       rebuild the image from glyphs, e.g. \" .:#\", lightest
+                        first, written as text (or SVG if FILE ends in .svg),
+                        or from tile images, e.g. a.png,b.png
+  --mosaic-cell WxH     the pixels each piece stands for (default 8x16 for
+                        glyphs, the size of the tiles for tiles)
+  --contours LEVELS     trace contour lines every LEVELS iterations, or at
+                        the escape times A,B,C, into the SVG file FILE
+  --contour-stroke COLOR  the #rrggbb color of the lines (default #000000)
+  --contour-width W     how wide the lines are, in pixels (default 1)
+  --watermark           stamp the view and zoom onto a corner of the image
+  --watermark-text TEXT  add the line TEXT to the stamp
+  --watermark-logo FILE.png  add the PNG image FILE.png above the text
+  --watermark-corner CORNER  top-left, top-right, bottom-left or
+                        bottom-right (the default)
+  --ascii               draw the explore preview in ASCII instead of braille
+  --bind ACTION=KEY     make KEY do ACTION in explore: pan-left, pan-right,
+                        pan-up, pan-down, zoom-in, zoom-out, switch-preview,
+                        more-iterations, fewer-iterations, next-palette,
+                        next-transfer, keyframe, readout or quit; KEY is a
+                        character, left, right, up, down, enter, esc or space
+  --high-contrast       draw explore white on black, the status line reversed
+  --reduced-motion      jump to each zoom in explore instead of animating it
+  --screen-reader       write each explore view as a line of words instead
+  --scene SCENE.json    the keyframes explore adds to with k, and animate
+                        renders; .json or .toml, see the README
+  --frames N            frames animate renders between keyframes without
+                        times (default 30)
+  --exp-map             have animate render a zoom about a fixed center once,
+                        as an exponential map, and take every frame from it
+  --format FORMAT       how animate writes the frames: png, a file each
+                        (default), or gif or apng, one looping image
+  --fps N               frames a second of animations and videos (default 25)
+  --temporal WEIGHT     have animate blend each frame into the ones before,
+                        counting for WEIGHT, from 0 to 1, and them the rest
+  --video FILE          have ffmpeg encode the frames of animate into FILE
+  --codec CODEC         the codec ffmpeg uses for --video (default libx264)
+  --top N               views find keeps (default 5)
+  --candidates N        centers find tries (default 200)
+  --seed N              where the random choices of find and
+                        --random-viewport start (default 0)
+  --tolerance N         how much two pixels compare lets differ (default 0)
+  --diff DIFF.png       where compare writes a heatmap of the differences
+  --decimate N          pixels between the points of export-mesh (default 1)
+  --height-scale SCALE  how high export-mesh raises the set, as a fraction
+                        of the width of the mesh (default 0.2)
+  --tile-size N         how wide the tiles of export-dzi are (default 256)
+  --base-url URL        where the IIIF tiles of export-dzi will be served from
+  --listen ADDRESS      where serve and coordinator listen (default 127.0.0.1:8080)
+  --workers N           requests serve handles at a time (default one per CPU)
+  --cache-tiles N       tiles serve keeps in memory (default 1024)
+  --sandbox             serve renders in a child process that can't open
+                        files or sockets (needs Linux and the sandbox feature)
+  --disk-cache-size MB  space serve and explore keep renders in on disk,
+                        0 for none (default 256)
+  --threads N           threads a render uses (default one per CPU)
+  --jobs N              images batch renders at a time, sharing the CPUs
+                        (default 1)
+  --timeout SECS        stop a render after SECS seconds, writing the rows
+                        done by then to FILE; serve answers 503 instead
+  --lease SECS          how long coordinator waits for a band from a worker
+                        before handing it out again (default 60)
+  --fill #RRGGBB        the color of the pixels a render stopped by Ctrl-C,
+                        SIGTERM or --timeout didn't get to (default #000000)
+  --service             run as a systemd service: notify readiness, log to
+                        the journal, and on SIGTERM stop after the rows in
+                        progress, writing what was rendered as a checkpoint
+
+Options can also be set by environment variables such as MANDELBROT_THREADS=4
+or MANDELBROT_DECIMAL_COMMA=true, which the configuration file overrides.
+
+On Unix, send SIGUSR1 to print the progress of a render and SIGUSR2 to
+write what is rendered so far to FILE with .checkpoint before the extension.
+Ctrl-C stops a render after the rows in progress, writing what was rendered
+as a checkpoint; pressing it again quits at once.
+
+Exit status: 0 on success, 2 for usage errors, 3 for values that can't be
+parsed, 4 for invalid viewports, 5 for file errors, 6 for encoding errors,
+7 for images without view parameters, 8 for clipboard errors, 9 when
+interrupted or timed out, 10 when a color script fails, 11 when the
+video encoder fails, 12 when a serve --sandbox render fails, 13 when
+compare finds images differing too much or --reproduce different pixels,
+14 when project merge finds conflicts, 15 when jobs of a batch fail, 16
+when a distributed render fails and 17 when validate finds problems.
+";
+
+/// The usage message of the program `program`.
+fn usage_text(program: &str) -> String {
+    let anchors: Vec<&str> = anchors::PRESETS.iter().map(|(name, _)| *name).collect();
+    let palettes: Vec<&str> = palette::PRESETS.iter().map(|(name, _, _)| *name).collect();
+    USAGE
+        .replace("{program}", program)
+        .replace("{anchors}", &anchors.join(", "))
+        .replace("{palettes}", &palettes.join(", "))
+}
+
+/// Print the usage message.
+fn usage(program: &str) {
+    eprint!("{}", usage_text(program));
 }
 
-/// Render a rectangle of the Mandelbrot set into a buffer of pixels.
+/// Work out the dimensions of the image and the corners of the view it covers
+/// from the options.
 ///
-/// The `bounds` argument gives the width and the height of the buffer `pixels`,
-/// which holds one grayscale pizel per byte. The `upper_left` and `lower_right`
-/// arguments specify points on the complex plane corresponding to the upper-left
-/// and lower-right corners of the pixel buffer.
-fn render(
-    pixels: &mut [u8],
+/// Fails with `Error::Usage` if they don't describe a view, e.g. if both the
+/// corners and the center are given.
+fn view(options: &Options) -> Result<share::View> {
+    let from = match &options.from {
+        Some(file) => Some(share::read_png_view(file)?),
+        None => None,
+    };
+    let bounds = match (&options.pixels, &from) {
+        (Some(pixels), _) => {
+            parse_pair(pixels, 'x').ok_or_else(|| Error::parse("image dimensions", pixels))?
+        }
+        (None, Some(view)) => view.bounds,
+        (None, None) => return Err(Error::Usage("missing image dimensions".to_string())),
+    };
+
+    let point = |what, s: &str| point(what, s, options);
+    let corners = (&options.upper_left, &options.lower_right);
+    let random = options.random_viewport;
+    let (upper_left, lower_right) =
+        match (&from, &options.center, corners, &options.location, random) {
+            (Some(view), None, (None, None), None, false) => (view.upper_left, view.lower_right),
+            (None, None, (Some(upper_left), Some(lower_right)), None, false) => (
+                point("upper left corner point", upper_left)?,
+                point("lower right corner point", lower_right)?,
+            ),
+            (None, Some(center), (None, None), None, false) if locator::is_locator(center) => {
+                locator_corners(center, bounds, options)?
+            }
+            // a compact location given in place of the corners
+            (None, None, (Some(at), None), None, false) if locator::is_locator(at) => {
+                locator_corners(at, bounds, options)?
+            }
+            (None, Some(center), (None, None), None, false) => render::corners_from_center(
+                center_point(center, options)?,
+                Zoom(options.zoom),
+                bounds,
+            ),
+            (None, None, (None, None), Some(file), false) => location::read(file)?.corners(bounds),
+            (None, None, (None, None), None, true) => {
+                let center =
+                    find::random_view(options.seed, Zoom(options.zoom)).ok_or_else(|| {
+                        Error::Viewport("found no point near the boundary".to_string())
+                    })?;
+                log(
+                    options,
+                    Priority::Info,
+                    &format!("random viewport centered on {},{}", center.re, center.im),
+                );
+                render::corners_from_center(center, Zoom(options.zoom), bounds)
+            }
+            _ => {
+                return Err(Error::Usage(
+                    "give either both corners, --center, --from, --location or --random-viewport"
+                        .to_string(),
+                ))
+            }
+        };
+
+    let (upper_left, lower_right) = match options.fit {
+        Some(fit) => {
+            error::validate_viewport(bounds, upper_left, lower_right)?;
+            fit.apply(bounds, upper_left, lower_right)
+        }
+        None => (upper_left, lower_right),
+    };
+
+    Ok(share::View {
+        bounds,
+        upper_left,
+        lower_right,
+    })
+}
+
+/// The iteration limit the options ask for: `--max-iters`, or else that of
+/// the `--location` file, if it gives one.
+fn max_iters(options: &Options) -> Result<Option<MaxIters>> {
+    match (options.max_iters, &options.location) {
+        (None, Some(file)) => Ok(location::read(file)?.iterations.map(MaxIters::Fixed)),
+        (max_iters, _) => Ok(max_iters),
+    }
+}
+
+#[test]
+fn test_max_iters() {
+    let dir = std::env::temp_dir().join(format!("mandelbrot-kfr-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let file = dir.join("deep.kfr").to_str().unwrap().to_string();
+    let spot = location::Location {
+        center: Complex::new(-0.75, 0.1),
+        zoom: 1e6,
+        iterations: Some(5000),
+    };
+    location::write(&file, &spot).unwrap();
+
+    let mut options = parse_args(&["--location".to_string(), file.clone()]).unwrap();
+    assert_eq!(max_iters(&options).unwrap(), Some(MaxIters::Fixed(5000)));
+    options.max_iters = Some(MaxIters::Fixed(300));
+    assert_eq!(max_iters(&options).unwrap(), Some(MaxIters::Fixed(300)));
+    location::write(
+        &file,
+        &location::Location {
+            iterations: None,
+            ..spot
+        },
+    )
+    .unwrap();
+    options.max_iters = None;
+    assert_eq!(max_iters(&options).unwrap(), None);
+    assert_eq!(max_iters(&Options::default()).unwrap(), None);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+/// Parse the compact location `s`, see `locator`.
+fn parse_locator(s: &str) -> Result<Locator> {
+    s.parse().map_err(|_| Error::parse("location", s))
+}
+
+/// The corners of the view the compact location `s` gives, in an image
+/// whose dimensions are given by `bounds`, magnified `--zoom` times unless
+/// it says how much.
+fn locator_corners(
+    s: &str,
     bounds: (usize, usize),
-    upper_left: Complex<f64>,
-    lower_right: Complex<f64>,
-) {
-    assert!(pixels.len() == bounds.0 * bounds.1);
+    options: &Options,
+) -> Result<(Complex<f64>, Complex<f64>)> {
+    parse_locator(s)?
+        .corners(bounds, Zoom(options.zoom))
+        .map_err(Error::Viewport)
+}
+
+/// Parse the `--center` option `center`, reading the clipboard if it is
+/// `clipboard`, and taking the center of compact locations.
+fn center_point(center: &str, options: &Options) -> Result<Complex<f64>> {
+    if locator::is_locator(center) {
+        Ok(parse_locator(center)?.center)
+    } else if center == "clipboard" {
+        let text = clipboard::read().map_err(Error::Clipboard)?;
+        clipboard::parse_point(&text, options.decimal)
+            .ok_or_else(|| Error::parse("center point on the clipboard", &text))
+    } else {
+        point("center point", center, options)
+    }
+}
 
-    for row in 0..bounds.1 {
-        for column in 0..bounds.0 {
-            let point = pixel_to_point(bounds, (column, row), upper_left, lower_right);
+/// Parse the point `s`, given by its coordinates or relative to an anchor,
+/// reading the bookmarks file of the options, or the bookmarks of their
+/// project, if there is one.
+fn point(what: &'static str, s: &str, options: &Options) -> Result<Complex<f64>> {
+    let anchors = match (&options.bookmarks, &options.project) {
+        (Some(file), _) => Anchors::read(file)?,
+        (None, Some(file)) => Project::open(file)?.anchors(),
+        (None, None) => Anchors::default(),
+    };
+    anchors
+        .resolve(s, options.decimal)
+        .ok_or_else(|| Error::parse(what, s))
+}
 
-            // if escape_time says that point belongs to the set, render colors
-            // the corresponding pixel black (0). Otherwise, render assigns darker colors
-            // to the numbers that tool longer to escape the circle.
-            pixels[row * bounds.0 + column] = {
-                match escape_time(point, 255) {
-                    Some(count) => 255 - count as u8,
-                    None => 0,
-                }
+/// Run the `explore` subcommand: browse the set in the terminal, starting
+/// from the view given by `--center` and `--zoom` or by `--from`, and print
+/// where the user ended up.
+fn explore(program: &str, options: &Options) -> Result<()> {
+    if options.output.is_some() {
+        return Err(Error::Usage(
+            "explore takes no positional arguments".to_string(),
+        ));
+    }
+    let start = match (&options.from, &options.center) {
+        (Some(file), None) => {
+            let view = share::read_png_view(file)?;
+            Explorer {
+                center: (view.upper_left + view.lower_right) / 2.0,
+                zoom: ComplexWidth::between(view.upper_left, view.lower_right)
+                    .zoom()
+                    .0,
             }
         }
+        (None, center) => Explorer {
+            center: match center {
+                Some(center) => center_point(center, options)?,
+                None => Complex { re: -0.5, im: 0.0 },
+            },
+            zoom: match center {
+                Some(center) if locator::is_locator(center) => parse_locator(center)?
+                    .zoom
+                    .map_or(options.zoom, |zoom| zoom.0),
+                _ => options.zoom,
+            },
+        },
+        _ => return Err(Error::Usage("give either --center or --from".to_string())),
+    };
+
+    #[cfg(feature = "tui")]
+    {
+        let preview = if options.ascii {
+            mandelbrot::explore::Preview::Ascii
+        } else {
+            mandelbrot::explore::Preview::Braille
+        };
+        let visited = platform::config_file(coverage::FILE_NAME).ok();
+        // what the keys and the configuration file tune starts out as the
+        // options say
+        let settings = explore::Settings {
+            tuning: explore::Tuning {
+                max_iters: (options.max_iters).unwrap_or(MaxIters::Fixed(render::DEFAULT_LIMIT)),
+                palette: options.palette.clone(),
+                transfer: options.palette_cycle.transfer.clone(),
+            },
+            watch: platform::config_file(config::FILE_NAME).ok(),
+            ..options.explore.clone()
+        };
+        let end = mandelbrot::explore::run(
+            start,
+            preview,
+            options.strategy,
+            options.scene.as_deref(),
+            visited.as_deref(),
+            disk_cache(options).as_ref(),
+            &settings,
+        )
+        .map_err(Error::io("terminal"))?;
+        println!(
+            "center {},{} zoom {}",
+            end.center.re, end.center.im, end.zoom
+        );
+        println!("{}", end.command_line(program));
+        Ok(())
+    }
+    #[cfg(not(feature = "tui"))]
+    {
+        let _ = (program, start);
+        Err(Error::Usage(
+            "explore needs the tui feature: cargo build --features tui".to_string(),
+        ))
     }
 }
 
-/// Write the buffer `pixels`, whose dimensions are given by `bounds`, to
-/// the file named `filename`.
-fn write_image(
-    filename: &str,
-    pixels: &[u8],
-    bounds: (usize, usize),
-) -> Result<(), std::io::Error> {
-    let output = File::create(filename)?;
-
-    let encoder = PNGEncoder::new(output);
-    encoder.encode(
-        &pixels,
-        bounds.0 as u32,
-        bounds.1 as u32,
-        ColorType::Gray(8),
-    )?;
+/// Where `animate` sends the frames it renders.
+enum Frames {
+    /// A PNG file each.
+    Files,
+    Animation(sequence::Encoder),
+    Video(Video),
+}
 
-    Ok(())
+/// The dimensions of the frames of the scene given by `--scene`, or by the
+/// project of `--project`, and the frames, for `command` to render.
+fn scene_frames(
+    options: &Options,
+    command: &str,
+) -> Result<((usize, usize), Vec<animation::Frame>)> {
+    let pixels = options
+        .pixels
+        .as_deref()
+        .ok_or_else(|| Error::Usage("missing image dimensions".to_string()))?;
+    let bounds = parse_pair(pixels, 'x').ok_or_else(|| Error::parse("image dimensions", pixels))?;
+    let (scene, scene_file) = match (&options.scene, &options.project) {
+        (Some(file), _) => (Scene::read(file)?, file),
+        (None, Some(file)) => {
+            let scene = Project::open(file)?.scene.ok_or_else(|| {
+                Error::Usage(format!("{} has no scene, and {} needs one", file, command))
+            })?;
+            (scene, file)
+        }
+        (None, None) => return Err(Error::Usage(format!("{} needs --scene", command))),
+    };
+    let frames = scene.frames(options.frames, options.fps);
+    if frames.is_empty() {
+        return Err(Error::Usage(format!("{} has no keyframes", scene_file)));
+    }
+    Ok((bounds, frames))
+}
+
+/// The palette the frames `frames` are colored with: that of `--palette`,
+/// or gray if they rotate it, or none.
+fn frame_palette(options: &Options, frames: &[animation::Frame]) -> Result<Option<Choice>> {
+    Ok(match &options.palette {
+        Some(name) => Some(autopalette::choose(name)?),
+        // rotating the palette needs one, even if it's gray
+        None if frames.iter().any(|frame| frame.rotation != 0.0) => {
+            Some(Choice::Fixed(Palette::gray()))
+        }
+        None => None,
+    })
+}
+
+/// Apply `--bands` and `--glow` to the escape times `pixels` of a frame of
+/// `bounds` pixels, then color it with `palette`, rotated by `rotation`
+/// turns, if there is one.
+fn style_frame(
+    options: &Options,
+    palette: Option<&Choice>,
+    mut pixels: Vec<u8>,
+    bounds: (usize, usize),
+    rotation: f64,
+) -> Vec<u8> {
+    if let Some(style) = &options.bands {
+        bands::apply(&mut pixels, bounds, style);
+    }
+    if let Some(glow) = &options.glow {
+        glow::apply(&mut pixels, bounds, glow);
+    }
+    match palette {
+        Some(palette) => {
+            let lut = palette::rotate(&palette.lut_for(&pixels, &options.palette_cycle), rotation);
+            palette::apply(&pixels, &lut)
+        }
+        None => pixels,
+    }
+}
+
+/// Run the `animate` subcommand: render every frame of the scene given by
+/// `--scene`, or by the project of `--project`, to FILE, with the number of the frame before the extension, or
+/// as one animated image with `--format gif` or `--format apng`, or piped
+/// into ffmpeg with `--video`.
+fn animate(options: &Options) -> Result<()> {
+    let output = match (&options.output, &options.video) {
+        (Some(output), None) => output.as_str(),
+        (None, Some(video)) => video.as_str(),
+        (Some(_), Some(_)) => {
+            return Err(Error::Usage(
+                "animate writes either FILE or --video, not both".to_string(),
+            ))
+        }
+        (None, None) => return Err(Error::Usage("missing output file".to_string())),
+    };
+    if options.video.is_some() && options.format != Format::Png {
+        return Err(Error::Usage(
+            "--video and --format can't be used together".to_string(),
+        ));
+    }
+    let (bounds, frames) = scene_frames(options, "animate")?;
+    let palette = frame_palette(options, &frames)?;
+    // a sequence of PNG files has `on_exists` apply to each frame instead
+    let sequence = options.video.is_none() && options.format == Format::Png;
+    let output = match output::resolve(output, options.on_exists) {
+        _ if sequence => output.to_string(),
+        Some(output) => output,
+        None => {
+            log(
+                options,
+                Priority::Info,
+                &format!("{} already exists, skipping", output),
+            );
+            return Ok(());
+        }
+    };
+    let output = output.as_str();
+    if options.mkdir {
+        output::create_parent_dirs(output)?;
+    }
+    let mut sink = match options.format {
+        Format::Png if options.video.is_some() => Frames::Video(Video::start(
+            output,
+            bounds,
+            palette.is_some(),
+            options.fps,
+            &options.codec,
+        )?),
+        Format::Png => Frames::Files,
+        Format::Gif => Frames::Animation(sequence::Encoder::gif(bounds, options.fps)?),
+        Format::Apng => {
+            Frames::Animation(sequence::Encoder::apng(bounds, frames.len(), options.fps))
+        }
+    };
+
+    let strip = if options.exp_map {
+        if options.numeric != Numeric::F64 || options.sampling.samples > 1 {
+            return Err(Error::Usage(
+                "--exp-map renders one f64 point per pixel, without --samples or --numeric"
+                    .to_string(),
+            ));
+        }
+        let mut strip = Strip::plan(&frames, bounds).ok_or_else(|| {
+            Error::Usage(
+                "--exp-map needs every frame of the scene to look at the same center".to_string(),
+            )
+        })?;
+        // gray levels depend on the limit, so the strip has that of the
+        // deepest frame for all of them
+        let limit = frames
+            .iter()
+            .map(|frame| frame.limit(options.max_iters, bounds))
+            .max()
+            .unwrap_or(render::DEFAULT_LIMIT);
+        let start = Instant::now();
+        strip.render(&render::Schedule {
+            chunk_rows: options.chunk_rows,
+            limit,
+            bailout: options.formula.bailout,
+            ..render::Schedule::new(num_cpus::get())
+        });
+        log(
+            options,
+            Priority::Info,
+            &format!(
+                "exponential map of {}x{} pixels rendered in {:.1} s",
+                strip.bounds.0,
+                strip.bounds.1,
+                start.elapsed().as_secs_f64()
+            ),
+        );
+        Some(strip)
+    } else {
+        None
+    };
+    let mut accumulator = options.temporal.map(TemporalAccumulator::new);
+
+    for (number, frame) in frames.iter().enumerate() {
+        let (upper_left, lower_right) = frame.corners(bounds);
+        error::validate_viewport(bounds, upper_left, lower_right)?;
+        let file = if sequence {
+            let file = output::with_suffix(output, &format!("-{:04}", number + 1));
+            let resolved = output::resolve(&file, options.on_exists);
+            if resolved.is_none() {
+                log(
+                    options,
+                    Priority::Info,
+                    &format!("{} already exists, skipping", file),
+                );
+                // the frames after it are blended with it all the same
+                if accumulator.is_none() {
+                    continue;
+                }
+            }
+            resolved
+        } else {
+            None
+        };
+        let mut pixels = match &strip {
+            Some(strip) => strip.reproject(bounds, upper_left, lower_right),
+            None => {
+                let mut pixels = vec![0; bounds.0 * bounds.1];
+                render::render_parallel(
+                    options.strategy,
+                    &mut pixels,
+                    bounds,
+                    upper_left,
+                    lower_right,
+                    &render::Schedule {
+                        chunk_rows: options.chunk_rows,
+                        sampling: options.sampling.for_frame(number as u64),
+                        limit: frame.limit(options.max_iters, bounds),
+                        numeric: options.numeric,
+                        bailout: options.formula.bailout,
+                        ..render::Schedule::new(num_cpus::get())
+                    },
+                );
+                pixels
+            }
+        };
+        if let Some(accumulator) = &mut accumulator {
+            pixels = accumulator.add(&pixels);
+            if sequence && file.is_none() {
+                continue;
+            }
+        }
+        let pixels = style_frame(options, palette.as_ref(), pixels, bounds, frame.rotation);
+
+        match &mut sink {
+            Frames::Files => {}
+            Frames::Animation(animation) => animation.add_frame(&pixels)?,
+            Frames::Video(video) => video.add_frame(&pixels)?,
+        }
+        let Some(file) = file else {
+            log(
+                options,
+                Priority::Info,
+                &format!("frame {} of {} encoded", number + 1, frames.len()),
+            );
+            continue;
+        };
+        write_image(&file, &pixels, bounds, upper_left, lower_right)?;
+        log(
+            options,
+            Priority::Info,
+            &format!(
+                "frame {} of {} written to {}",
+                number + 1,
+                frames.len(),
+                file
+            ),
+        );
+    }
+    match sink {
+        Frames::Files => return Ok(()),
+        Frames::Animation(animation) => output::write(output, &animation.finish()?)?,
+        Frames::Video(video) => video.finish()?,
+    }
+    log(
+        options,
+        Priority::Info,
+        &format!("animation written to {}", output),
+    );
+    Ok(())
+}
+
+#[test]
+fn test_animate_temporal() {
+    let dir = std::env::temp_dir().join(format!("mandelbrot-temporal-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let scene = dir.join("dive.toml").to_str().unwrap().to_string();
+    std::fs::write(
+        &scene,
+        "[[keyframes]]\ncenter = [-0.5, 0]\nzoom = 1\n\n\
+         [[keyframes]]\ncenter = [-0.75, 0.1]\nzoom = 4\n",
+    )
+    .unwrap();
+    let frames = |name: &str, temporal: Option<f64>| {
+        let output = dir.join(name).to_str().unwrap().to_string();
+        let mut options = parse_args(&["--scene".to_string(), scene.clone()]).unwrap();
+        options.output = Some(output.clone());
+        options.pixels = Some("24x16".to_string());
+        options.frames = 3;
+        options.temporal = temporal;
+        animate(&options).unwrap();
+        (1..=4)
+            .map(|number| {
+                read_image(&output::with_suffix(&output, &format!("-{:04}", number)))
+                    .unwrap()
+                    .0
+            })
+            .collect::<Vec<_>>()
+    };
+    let plain = frames("plain.png", None);
+    assert_ne!(plain[0], plain[3]);
+    // each frame counting for all of it leaves them as they are
+    assert_eq!(frames("all.png", Some(1.0)), plain);
+    // and counting for nothing keeps the first
+    assert!(frames("none.png", Some(0.0))
+        .iter()
+        .all(|frame| *frame == plain[0]));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+/// Run the `location` subcommand: write the view the options describe to a
+/// location file for Kalles Fraktaler or Fraktaler 3, instead of rendering it.
+fn export_location(options: &Options) -> Result<()> {
+    let file = options
+        .output
+        .as_deref()
+        .ok_or_else(|| Error::Usage("missing location file".to_string()))?;
+    let view = view(options)?;
+    error::validate_viewport(view.bounds, view.upper_left, view.lower_right)?;
+    location::write(file, &location::Location::from_view(&view))
+}
+
+/// Run the `export-mesh` subcommand: write the smooth escape times of the
+/// view the options describe as the height field of a solid, to an OBJ, STL
+/// or PLY file, going by the extension.
+fn export_mesh(options: &Options) -> Result<()> {
+    let file = options
+        .output
+        .as_deref()
+        .ok_or_else(|| Error::Usage("missing mesh file".to_string()))?;
+    let format = mesh::Format::from_filename(file).ok_or_else(|| {
+        Error::Usage(format!(
+            "can't tell the mesh format of {}, expected .obj, .stl or .ply",
+            file
+        ))
+    })?;
+    let view = view(options)?;
+    error::validate_viewport(view.bounds, view.upper_left, view.lower_right)?;
+    let grid = mesh::grid(view.bounds, options.decimate);
+    if grid.0 < 2 || grid.1 < 2 {
+        return Err(Error::Usage(format!(
+            "a mesh needs at least 2x2 points, and --decimate {} leaves {}x{}",
+            options.decimate, grid.0, grid.1
+        )));
+    }
+    let limit = options.max_iters.unwrap_or(MaxIters::Auto).resolve(
+        view.bounds,
+        view.upper_left,
+        view.lower_right,
+    );
+    let heights = mesh::heights(
+        view.bounds,
+        view.upper_left,
+        view.lower_right,
+        limit,
+        options.decimate,
+        num_cpus::get(),
+    );
+    let solid = mesh::solid(&heights, grid, options.height_scale);
+    output::write(file, &solid.encode(format))
+}
+
+/// Run the `export-dzi` subcommand: render the view at every level of a
+/// tile pyramid, in the layout `pyramid::Layout::from_filename` picks for
+/// the output, with the tiles spread over a thread per CPU and cached on
+/// disk like those of `serve`.
+fn export_dzi(options: &Options) -> Result<()> {
+    let file = options
+        .output
+        .as_deref()
+        .ok_or_else(|| Error::Usage("missing pyramid file or directory".to_string()))?;
+    if file == "-" {
+        return Err(Error::Usage(
+            "export-dzi writes a directory of tiles, so FILE can't be -".to_string(),
+        ));
+    }
+    // a directory of tiles is named without the slash, for `increment` to
+    // give the next free name
+    let file = match output::resolve(file.trim_end_matches(['/', '\\']), options.on_exists) {
+        Some(file) => file,
+        None => {
+            log(
+                options,
+                Priority::Info,
+                &format!("{} already exists, skipping", file),
+            );
+            return Ok(());
+        }
+    };
+    let file = file.as_str();
+    let layout = Layout::from_filename(file);
+    let view = view(options)?;
+    error::validate_viewport(view.bounds, view.upper_left, view.lower_right)?;
+    let lut = match &options.palette {
+        Some(name) => match autopalette::choose(name)? {
+            Choice::Fixed(palette) => Some(palette.lut(256)),
+            Choice::Auto(_) => {
+                return Err(Error::Usage(
+                    "export-dzi colors each tile alone, so it needs a fixed palette".to_string(),
+                ))
+            }
+        },
+        None => None,
+    };
+    // built-in palettes are cached by name, those of files by their colors
+    let palette_key = match (&options.palette, &lut) {
+        (Some(name), Some(lut)) => match palette::preset_name(name) {
+            Some(name) => name.to_string(),
+            None => cache::palette_key(lut),
+        },
+        _ => "gray".to_string(),
+    };
+    let limit = options.max_iters.unwrap_or(MaxIters::Auto).resolve(
+        view.bounds,
+        view.upper_left,
+        view.lower_right,
+    );
+    let tile_dir = layout.tile_dir(file);
+    let tiles = pyramid::tiles(layout, view.bounds, options.tile_size);
+    let disk_cache = disk_cache(options);
+    let next_tile = std::sync::atomic::AtomicUsize::new(0);
+    let render_tile = |tile: &pyramid::Tile| -> Result<()> {
+        let (upper_left, lower_right) = mandelbrot::tiles::corners(
+            tile.level,
+            (tile.origin, tile.bounds),
+            view.upper_left,
+            view.lower_right,
+        );
+        let mut renderer = encode::Renderer::new(tile.bounds, upper_left, lower_right);
+        renderer.strategy = options.strategy;
+        renderer.limit = limit;
+        let key = cache::Key {
+            bounds: tile.bounds,
+            upper_left,
+            lower_right,
+            fractal: "mandelbrot",
+            limit,
+            strategy: options.strategy,
+            palette: &palette_key,
+            format: encode::Encoding::Png.content_type(),
+        };
+        let cached = disk_cache.as_ref().and_then(|cache| cache.get(&key));
+        let encoded = match cached {
+            Some(encoded) => encoded,
+            None => {
+                let pixels = renderer.render();
+                let pixels = match &lut {
+                    Some(lut) => palette::apply(&pixels, lut),
+                    None => pixels,
+                };
+                let encoded = encode::encode(
+                    &pixels,
+                    tile.bounds,
+                    upper_left,
+                    lower_right,
+                    encode::Encoding::Png,
+                    &renderer.options,
+                )?;
+                if let Some(disk_cache) = &disk_cache {
+                    disk_cache.put(&key, &encoded)?;
+                }
+                encoded
+            }
+        };
+        let path = format!("{}/{}", tile_dir, tile.path);
+        output::create_parent_dirs(&path)?;
+        output::write(&path, &encoded)
+    };
+    let workers = num_cpus::get().min(tiles.len()).max(1);
+    thread::scope(|scope| {
+        let handles: Vec<_> = (0..workers)
+            .map(|_| {
+                scope.spawn(|| {
+                    while let Some(tile) = tiles.get(next_tile.fetch_add(1, Ordering::Relaxed)) {
+                        render_tile(tile)?;
+                    }
+                    Ok(())
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .try_for_each(|handle| handle.join().expect("tile threads don't panic"))
+    })?;
+
+    // the tiles are under the directory's name, relative to the viewer's
+    // page unless the URL they will be served from is given
+    let name = Path::new(&tile_dir)
+        .file_name()
+        .map_or(tile_dir.clone(), |name| name.to_string_lossy().into_owned());
+    let id = match &options.base_url {
+        Some(base_url) => format!("{}/{}", base_url, name),
+        None => name,
+    };
+    let descriptor_file = layout.descriptor_file(file);
+    output::create_parent_dirs(&descriptor_file)?;
+    output::write(
+        &descriptor_file,
+        pyramid::descriptor(layout, view.bounds, options.tile_size, &id).as_bytes(),
+    )?;
+    log(
+        options,
+        Priority::Info,
+        &format!("wrote {} tiles for {}", tiles.len(), descriptor_file),
+    );
+    Ok(())
+}
+
+/// Run the `project` subcommand: `save FILE.mandel OPTIONS...` writes the
+/// project of the options, `migrate FILE.mandel` writes a project back in
+/// the current version of the format, `diff A B` prints how two projects
+/// differ and `merge BASE A B OUT` merges two projects changed from BASE.
+fn project_command(args: &[String]) -> Result<()> {
+    match args {
+        [command, file, options @ ..] if command == "save" => {
+            Project::from_args(options)?.save(file)
+        }
+        [command, file] if command == "migrate" => {
+            let project = Project::open(file)?;
+            project.save(file)?;
+            eprintln!("{} is at version {}", file, project::VERSION);
+            Ok(())
+        }
+        [command, a, b] if command == "diff" => {
+            for difference in project::diff(&Project::open(a)?, &Project::open(b)?) {
+                println!("{}", difference);
+            }
+            Ok(())
+        }
+        [command, base, a, b, out] if command == "merge" => {
+            let (base, a, b) = (Project::open(base)?, Project::open(a)?, Project::open(b)?);
+            project::merge(&base, &a, &b)?.save(out)
+        }
+        _ => Err(Error::Usage(
+            "expected project save FILE.mandel OPTIONS..., migrate FILE.mandel, \
+             diff A.mandel B.mandel or merge BASE.mandel A.mandel B.mandel OUT.mandel"
+                .to_string(),
+        )),
+    }
+}
+
+/// Run the `batch` subcommand: render every job of the manifest `args[0]`,
+/// `--jobs` of them at a time. Each job's status is printed as it finishes,
+/// and a job failing doesn't stop the others.
+fn batch_command(program: &str, args: &[String]) -> Result<()> {
+    let (file, parallel) = match args {
+        [file] => (file, 1),
+        [file, option, value] if option == "--jobs" => match value.parse() {
+            Ok(jobs) if jobs > 0 => (file, jobs),
+            _ => return Err(Error::Usage(format!("invalid number of jobs {}", value))),
+        },
+        _ => {
+            return Err(Error::Usage(
+                "expected batch MANIFEST.json [--jobs N]".to_string(),
+            ))
+        }
+    };
+    let manifest = batch::Manifest::open(file)?;
+    let count = manifest.jobs.len();
+
+    // the images rendered at the same time share the CPUs
+    let threads = (num_cpus::get() / parallel.min(count)).max(1);
+    let results = batch::run(count, parallel, |i| {
+        let job = &manifest.jobs[i];
+        let output = job.output.as_deref().unwrap_or_default();
+        let started = Instant::now();
+        let mut args = vec![
+            program.to_string(),
+            "--threads".to_string(),
+            threads.to_string(),
+        ];
+        args.extend(job.to_args());
+        let result = run(&args, &mut None);
+        match &result {
+            Ok(()) => eprintln!(
+                "[{}/{}] {}: done in {:.1}s",
+                i + 1,
+                count,
+                output,
+                started.elapsed().as_secs_f64()
+            ),
+            Err(e) => eprintln!("[{}/{}] {}: error: {}", i + 1, count, output, e),
+        }
+        result
+    });
+
+    let failed: Vec<&str> = manifest
+        .jobs
+        .iter()
+        .zip(&results)
+        .filter(|(_, result)| result.is_err())
+        .map(|(job, _)| job.output.as_deref().unwrap_or_default())
+        .collect();
+    if !failed.is_empty() {
+        return Err(Error::Batch(format!(
+            "{} of {} jobs failed: {}",
+            failed.len(),
+            count,
+            failed.join(", ")
+        )));
+    }
+    eprintln!("{} jobs rendered", count);
+    Ok(())
+}
+
+/// Run the `coordinator` subcommand: have the workers connecting render the
+/// image the options describe in bands, or every frame of the scene of
+/// `--scene`, and write what they send back.
+fn coordinate(options: &Options) -> Result<()> {
+    let output = options
+        .output
+        .as_deref()
+        .ok_or_else(|| Error::Usage("missing output file".to_string()))?;
+    if options.timeout.is_some() {
+        return Err(Error::Usage(
+            "coordinator has no deadline; --lease SECS is how long a worker has for a band"
+                .to_string(),
+        ));
+    }
+    if options.video.is_some()
+        || options.format != Format::Png
+        || options.temporal.is_some()
+        || options.exp_map
+    {
+        return Err(Error::Usage(
+            "coordinator writes the frames of a scene to PNG files, without --video, --format, \
+             --temporal or --exp-map"
+                .to_string(),
+        ));
+    }
+    if options.fractal != Fractal::Mandelbrot
+        || options.color_script.is_some()
+        || options.progressive
+        || options.state.is_some()
+        || options.sampling.samples > 1
+        || options.numeric != Numeric::F64
+        || options.low_memory
+    {
+        return Err(Error::Usage(
+            "coordinator renders the mandelbrot fractal one f64 point per pixel, without \
+             --color-script, --progressive, --state, --samples, --numeric or --low-memory"
+                .to_string(),
+        ));
+    }
+    if options.formula.bailout < render::DEFAULT_BAILOUT {
+        return Err(Error::Usage(
+            "--bailout is at least 2 for the mandelbrot fractal, or points outside the set \
+             never escape"
+                .to_string(),
+        ));
+    }
+
+    // a still image is frame 0, the frames of a scene go to FILE-0001.png
+    // and on; each frame with its file and the rotation of its palette
+    let mut frames = Vec::new();
+    let (bounds, palette) = match &options.scene {
+        Some(_) => {
+            let (bounds, scene) = scene_frames(options, "coordinator")?;
+            for (number, frame) in scene.iter().enumerate() {
+                let (upper_left, lower_right) = frame.corners(bounds);
+                error::validate_viewport(bounds, upper_left, lower_right)?;
+                let file = output::with_suffix(output, &format!("-{:04}", number + 1));
+                let Some(file) = output::resolve(&file, options.on_exists) else {
+                    log(
+                        options,
+                        Priority::Info,
+                        &format!("{} already exists, skipping", file),
+                    );
+                    continue;
+                };
+                let limit = frame.limit(options.max_iters, bounds);
+                frames.push((
+                    distributed::Frame {
+                        number,
+                        upper_left,
+                        lower_right,
+                        limit,
+                    },
+                    file,
+                    frame.rotation,
+                ));
+            }
+            (bounds, frame_palette(options, &scene)?)
+        }
+        None => {
+            let share::View {
+                bounds,
+                upper_left,
+                lower_right,
+            } = view(options)?;
+            error::validate_viewport(bounds, upper_left, lower_right)?;
+            match output::resolve(output, options.on_exists) {
+                Some(file) => {
+                    let limit = options
+                        .max_iters
+                        .map_or(render::DEFAULT_LIMIT, |max_iters| {
+                            max_iters.resolve(bounds, upper_left, lower_right)
+                        });
+                    let frame = distributed::Frame {
+                        number: 0,
+                        upper_left,
+                        lower_right,
+                        limit,
+                    };
+                    frames.push((frame, file, 0.0));
+                }
+                None => log(
+                    options,
+                    Priority::Info,
+                    &format!("{} already exists, skipping", output),
+                ),
+            }
+            (bounds, frame_palette(options, &[])?)
+        }
+    };
+    if frames.is_empty() {
+        return Ok(());
+    }
+    let listen = options.listen.as_deref().unwrap_or(DEFAULT_LISTEN);
+    let listener = TcpListener::bind(listen).map_err(Error::io(listen))?;
+    let address = listener.local_addr().map_err(Error::io(listen))?;
+    log(
+        options,
+        Priority::Info,
+        &format!("waiting for workers on {} to render {}", address, output),
+    );
+
+    let coordinator = Coordinator::new(
+        distributed::Job {
+            bounds,
+            frames: frames.iter().map(|(frame, _, _)| frame.clone()).collect(),
+            strategy: options.strategy,
+            bailout: options.formula.bailout,
+            band_rows: distributed::DEFAULT_BAND_ROWS,
+        },
+        options.lease.unwrap_or(distributed::DEFAULT_LEASE),
+    );
+    let count = frames.len();
+    let mut written = 0;
+    distributed::coordinate(
+        listener,
+        coordinator,
+        &mut |message| log(options, Priority::Info, message),
+        &mut |number, pixels| {
+            let Some((frame, file, rotation)) =
+                frames.iter().find(|(frame, _, _)| frame.number == number)
+            else {
+                return Ok(());
+            };
+            let pixels = style_frame(options, palette.as_ref(), pixels, bounds, *rotation);
+            if options.mkdir {
+                output::create_parent_dirs(file)?;
+            }
+            write_image(file, &pixels, bounds, frame.upper_left, frame.lower_right)?;
+            written += 1;
+            let message = match options.scene {
+                Some(_) => format!(
+                    "frame {} ({} of {}) written to {}",
+                    number + 1,
+                    written,
+                    count,
+                    file
+                ),
+                None => format!("written to {}", file),
+            };
+            log(options, Priority::Info, &message);
+            Ok(())
+        },
+    )
+}
+
+/// Run the `worker` subcommand: render bands of rows for the coordinator at
+/// `args[0]` until it has none left.
+fn worker_command(args: &[String]) -> Result<()> {
+    let (address, threads) = match args {
+        [address] => (address, num_cpus::get()),
+        [address, option, value] if option == "--threads" => match value.parse() {
+            Ok(threads) if threads > 0 => (address, threads),
+            _ => return Err(Error::Usage(format!("invalid number of threads {}", value))),
+        },
+        _ => {
+            return Err(Error::Usage(
+                "expected worker ADDRESS [--threads N]".to_string(),
+            ))
+        }
+    };
+    let address = address.strip_prefix("http://").unwrap_or(address);
+    let address = address.trim_end_matches('/');
+    eprintln!("working for {}", address);
+    let rendered = distributed::work(address, threads, &mut |message| eprintln!("{}", message))?;
+    eprintln!("{} bands rendered", rendered);
+    Ok(())
+}
+
+/// Run the `stats` subcommand: `enable` starts keeping running totals of the
+/// renders and the log of the regions visited, `show` prints the totals and
+/// `disable` stops keeping them, forgetting them. `area` measures the set
+/// instead, see `area_stats`.
+fn stats_command(args: &[String]) -> Result<()> {
+    if let [command, options @ ..] = args {
+        if command == "area" {
+            return area_stats(options);
+        }
+    }
+    let file = Tally::default_file()?;
+    let visited = platform::config_file(coverage::FILE_NAME)?;
+    match args {
+        [command] if command == "enable" => {
+            if Tally::open(&file)?.is_none() {
+                Tally::default().save(&file)?;
+            }
+            coverage::start(&visited)?;
+            eprintln!("keeping stats in {} and {}", file, visited);
+            Ok(())
+        }
+        [command] if command == "show" => match Tally::open(&file)? {
+            Some(tally) => {
+                print!("{}", tally);
+                Ok(())
+            }
+            None => Err(Error::Usage(
+                "no stats are kept, stats enable starts keeping them".to_string(),
+            )),
+        },
+        [command] if command == "disable" => {
+            for file in [&file, &visited] {
+                let path = platform::path(file);
+                if path.exists() {
+                    std::fs::remove_file(&path).map_err(Error::io(file))?;
+                }
+            }
+            eprintln!("no longer keeping stats");
+            Ok(())
+        }
+        _ => Err(Error::Usage(
+            "expected stats enable, show, disable or area".to_string(),
+        )),
+    }
+}
+
+/// Run `stats area [FILE.json] [PIXELS] [VIEW...]`: estimate the area of the
+/// set within the view, the whole set by default, with `--samples` random
+/// points in every pixel, and write what `area::measure` finds as JSON to
+/// FILE.json, or to stdout if it is `-` or left out.
+fn area_stats(args: &[String]) -> Result<()> {
+    let mut options = parse_options(args)?;
+    options.pixels.get_or_insert_with(|| "1000x750".to_string());
+    let whole_set = [&options.upper_left, &options.lower_right, &options.center]
+        .iter()
+        .all(|point| point.is_none())
+        && options.from.is_none()
+        && options.location.is_none();
+    if whole_set {
+        options.center = Some("-0.75,0".to_string());
+    }
+    let share::View {
+        bounds,
+        upper_left,
+        lower_right,
+    } = view(&options)?;
+    error::validate_viewport(bounds, upper_left, lower_right)?;
+    let limit =
+        options
+            .max_iters
+            .unwrap_or(MaxIters::Auto)
+            .resolve(bounds, upper_left, lower_right);
+
+    let stats = area::measure(
+        bounds,
+        upper_left,
+        lower_right,
+        limit,
+        options.sampling.samples,
+        options.sampling.seed,
+        options.threads.unwrap_or_else(num_cpus::get),
+    );
+    let json = serde_json::to_string_pretty(&stats).unwrap() + "\n";
+    match options.output.as_deref() {
+        None | Some("-") => {
+            print!("{}", json);
+            Ok(())
+        }
+        Some(file) => output::write(file, json.as_bytes()),
+    }
+}
+
+/// Run the `cache` subcommand: `stats` prints how many renders the disk
+/// cache holds and how much space they take, `clear` deletes them.
+fn cache_command(args: &[String]) -> Result<()> {
+    let cache = DiskCache::new(DiskCache::default_dir()?, 0);
+    match args {
+        [command] if command == "stats" => {
+            let usage = cache.usage()?;
+            println!(
+                "{} renders, {:.1} MB in {}",
+                usage.tiles,
+                usage.bytes as f64 / (1 << 20) as f64,
+                cache.dir().display()
+            );
+            Ok(())
+        }
+        [command] if command == "clear" => {
+            let cleared = cache.clear()?;
+            eprintln!("deleted {} renders from {}", cleared, cache.dir().display());
+            Ok(())
+        }
+        _ => Err(Error::Usage("expected cache stats or clear".to_string())),
+    }
+}
+
+/// Run the `config` subcommand: `config show` prints the user's
+/// configuration file, and `config show --effective ARGS...` the settings
+/// the command line `ARGS` would run with, and where each comes from.
+fn config_command(args: &[String]) -> Result<()> {
+    let file = platform::config_file(config::FILE_NAME)?;
+    match args {
+        [command] if command == "show" => {
+            println!("# {}", file);
+            print!("{}", Config::open(&file)?);
+            Ok(())
+        }
+        [command, flag, args @ ..] if command == "show" && flag == "--effective" => {
+            for (option, value, source) in effective_settings(&file, env_vars(), args)? {
+                println!("{:<20} {:<24} {}", option, value, source);
+            }
+            Ok(())
+        }
+        _ => Err(Error::Usage(
+            "expected config show [--effective [SUBCOMMAND] OPTIONS...]".to_string(),
+        )),
+    }
+}
+
+/// Print the completion script of the program `program` for the shell
+/// `args` names.
+fn completions_command(program: &str, args: &[String]) -> Result<()> {
+    let [shell] = args else {
+        return Err(Error::Usage(
+            "expected completions bash|zsh|fish".to_string(),
+        ));
+    };
+    let shell: Shell = shell.parse().map_err(Error::Usage)?;
+    let program = Path::new(program)
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or("mandelbrot");
+    print!(
+        "{}",
+        completions::script(shell, program, &usage_text(program))
+    );
+    Ok(())
+}
+
+#[test]
+fn test_completions() {
+    let usage = usage_text("mandelbrot");
+    let subcommands = completions::subcommands(&usage, "mandelbrot");
+    for subcommand in ["explore", "serve", "config", "completions", "worker"] {
+        assert!(
+            subcommands.iter().any(|known| known == subcommand),
+            "{}",
+            subcommand
+        );
+    }
+    // the options of the usage message are those the arguments take, with
+    // a value where they take one
+    let flags = completions::flags(&usage);
+    assert!(flags.len() > 100);
+    for flag in &flags {
+        match parse_args(std::slice::from_ref(&flag.name)) {
+            Err(e) if e.starts_with("unknown option") => {}
+            _ => assert_eq!(takes_value(&flag.name), flag.takes_value, "{}", flag.name),
+        }
+    }
+}
+
+/// where its options go in `args`, or `None` for the subcommands it doesn't
+/// apply to, which take arguments before their options.
+fn config_section(args: &[String]) -> Option<(&str, usize)> {
+    match args.get(1).map(String::as_str) {
+        Some("palette" | "convert-view" | "extend" | "compare" | "orbit") => None,
+        Some(subcommand) if subcommand != "render" && config::SECTIONS.contains(&subcommand) => {
+            Some((subcommand, 2))
+        }
+        _ => Some(("render", 1)),
+    }
+}
+
+/// `args` with the options of the environment and of the user's
+/// configuration file for its command before its own, which override them.
+fn with_config(args: &[String]) -> Result<Vec<String>> {
+    // manifests have the options of the configuration already
+    if args.iter().any(|arg| arg == "--reproduce") {
+        return Ok(args.to_vec());
+    }
+    let Some((section, at)) = config_section(args) else {
+        return Ok(args.to_vec());
+    };
+    let mut options = Vec::new();
+    for (var, var_options) in config::env_options(env_vars(), is_option) {
+        parse_args(&var_options).map_err(|e| Error::Usage(format!("{}, in {}", e, var)))?;
+        options.extend(var_options);
+    }
+    if let Ok(file) = platform::config_file(config::FILE_NAME) {
+        let file_options = Config::open(&file)?.to_args(section);
+        parse_args(&file_options).map_err(|e| Error::Usage(format!("{}, in {}", e, file)))?;
+        options.extend(file_options);
+    }
+    let mut expanded = args[..at].to_vec();
+    expanded.extend(options);
+    expanded.extend_from_slice(&args[at..]);
+    Ok(expanded)
+}
+
+/// The environment variables of the process, but those that aren't valid
+/// Unicode, as no option is.
+fn env_vars() -> Vec<(String, String)> {
+    env::vars_os()
+        .filter_map(|(name, value)| Some((name.into_string().ok()?, value.into_string().ok()?)))
+        .collect()
+}
+
+/// Replace `--reproduce MANIFEST [FILE]` in `args` by the options recorded
+/// in the manifest, writing to FILE rather than to the file recorded if it is
+/// given. `--reproduce MANIFEST` stays at the end, for the render to check
+/// its pixels against the manifest.
+fn reproduce_args(args: &[String]) -> Result<Vec<String>> {
+    let Some(at) = args.iter().position(|arg| arg == "--reproduce") else {
+        return Ok(args.to_vec());
+    };
+    let file = args
+        .get(at + 1)
+        .ok_or_else(|| Error::Usage("--reproduce requires a manifest".to_string()))?;
+    let others: Vec<&String> = args[1..at].iter().chain(&args[at + 2..]).collect();
+    let output = match others[..] {
+        [] => None,
+        [output] if !output.starts_with("--") => Some(output),
+        _ => {
+            return Err(Error::Usage(
+                "--reproduce takes the options of the manifest, and no others but FILE".to_string(),
+            ))
+        }
+    };
+    let manifest = Manifest::open(file)?;
+    let mut expanded = vec![args[0].clone()];
+    expanded.extend(manifest.to_args(output.unwrap_or(&manifest.output)));
+    expanded.extend(["--reproduce".to_string(), file.clone()]);
+    Ok(expanded)
+}
+
+/// The options of the render `args`, without the output file and
+/// `--manifest`, as a manifest records them.
+fn recorded_args(args: &[String]) -> Vec<String> {
+    let mut recorded = Vec::new();
+    let mut output = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--manifest" => {}
+            "--output" | "--out" => {
+                args.next();
+            }
+            option if option.starts_with("--") => {
+                recorded.push(arg.clone());
+                if takes_value(option) {
+                    recorded.extend(args.next().cloned());
+                }
+            }
+            _ if output.is_none() => output = Some(arg),
+            _ => recorded.push(arg.clone()),
+        }
+    }
+    recorded
+}
+
+#[test]
+fn test_recorded_args() {
+    let args: Vec<String> = [
+        "--samples",
+        "4",
+        "dust.png",
+        "--manifest",
+        "80x60",
+        "--jitter",
+        "-2,1.5",
+        "2,-1.5",
+    ]
+    .iter()
+    .map(|s| s.to_string())
+    .collect();
+    assert_eq!(
+        recorded_args(&args),
+        ["--samples", "4", "80x60", "--jitter", "-2,1.5", "2,-1.5"]
+    );
+    let args: Vec<String> = ["--output", "a.png", "--pixels", "8x6"]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+    assert_eq!(recorded_args(&args), ["--pixels", "8x6"]);
+}
+
+/// Whether `parse_args` takes the argument after `option` as its value.
+fn takes_value(option: &str) -> bool {
+    matches!(parse_args(&[option.to_string()]), Err(e) if e.contains("requires"))
+}
+
+/// Whether the flag `option`, such as `--threads`, is one of the options.
+fn is_option(option: &str) -> bool {
+    !matches!(parse_args(&[option.to_string()]), Err(e) if e.starts_with("unknown option"))
+}
+
+/// The settings the command line `args` would run with, given the
+/// configuration file `file` and the environment variables `vars`: the name
+/// of each option set, its value, and where it comes from, the defaults
+/// built into the program, a variable, the file, its table for the
+/// subcommand, the projects, bookmarks and jobs given, or the command line
+/// itself.
+fn effective_settings(
+    file: &str,
+    vars: Vec<(String, String)>,
+    args: &[String],
+) -> Result<Vec<(String, String, String)>> {
+    let mut command = vec![String::new()];
+    command.extend_from_slice(args);
+    let (section, at) = config_section(&command).ok_or_else(|| {
+        Error::Usage(format!("the configuration doesn't apply to {}", command[1]))
+    })?;
+    let config = Config::open(file)?;
+    let mut layers = config::env_options(vars, is_option);
+    layers.extend([
+        (config::FILE_NAME.to_string(), config.global_args()),
+        (
+            format!("{} [{}]", config::FILE_NAME, section),
+            config.section_args(section),
+        ),
+    ]);
+    // the command line, split where projects, bookmarks and jobs expand
+    let mut given = Vec::new();
+    let mut args = command[at..].iter();
+    while let Some(arg) = args.next() {
+        let expands = match arg.as_str() {
+            "--project" | "--bookmark" => true,
+            "--job" => section == "render",
+            _ => false,
+        };
+        if !expands {
+            given.push(arg.clone());
+            continue;
+        }
+        let name = args
+            .next()
+            .ok_or_else(|| Error::Usage(format!("{} requires a value", arg)))?;
+        let pair = [arg.clone(), name.clone()];
+        let mut expanded =
+            bookmarks::expand_bookmarks(&project::expand_projects(&job::expand_jobs(&pair)?)?)?;
+        // the project's bookmarks still come from `--project`
+        if arg == "--project" {
+            given.extend(expanded.drain(..2));
+        }
+        layers.push(("command line".to_string(), std::mem::take(&mut given)));
+        layers.push((format!("{} {}", &arg[2..], name), expanded));
+    }
+    layers.push(("command line".to_string(), given));
+    let all: Vec<String> = layers.iter().flat_map(|(_, args)| args.clone()).collect();
+    parse_args(&all).map_err(Error::Usage)?;
+
+    let mut settings: BTreeMap<String, (String, String)> = config::DEFAULTS
+        .iter()
+        .map(|(option, value)| {
+            (
+                option.to_string(),
+                (value.to_string(), "built in".to_string()),
+            )
+        })
+        .collect();
+    let mut positional = ["output", "pixels", "upper-left", "lower-right"].into_iter();
+    for (source, args) in &layers {
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let (option, value) = match arg.strip_prefix("--") {
+                Some(option) if takes_value(arg) => {
+                    (option, args.next().cloned().unwrap_or_default())
+                }
+                Some(option) => (option, "true".to_string()),
+                None => (positional.next().unwrap_or("argument"), arg.clone()),
+            };
+            let option = if option == "out" { "output" } else { option };
+            settings.insert(option.to_string(), (value, source.clone()));
+        }
+    }
+    Ok(settings
+        .into_iter()
+        .map(|(option, (value, source))| (option, value, source))
+        .collect())
+}
+
+#[test]
+fn test_effective_settings() {
+    let dir = std::env::temp_dir().join(format!("mandelbrot-effective-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let file = dir.join(config::FILE_NAME).to_string_lossy().into_owned();
+    std::fs::write(
+        &file,
+        "max-iters = 500\npalette = \"fire\"\n[render]\nzoom = 4\n[serve]\nworkers = 2\n",
+    )
+    .unwrap();
+    let project = dir.join("p.mandel").to_string_lossy().into_owned();
+    let args = |args: &[&str]| args.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+    Project::from_args(&args(&["--zoom", "20", "--bailout", "4"]))
+        .unwrap()
+        .save(&project)
+        .unwrap();
+
+    // another program's variable is no option of ours
+    let vars = [
+        ("MANDELBROT_THREADS", "3"),
+        ("MANDELBROT_PALETTE", "ocean"),
+        ("MANDELBROT_FOO", "1"),
+    ]
+    .map(|(name, value)| (name.to_string(), value.to_string()));
+    let settings = effective_settings(
+        &file,
+        vars.to_vec(),
+        &args(&[
+            "out.png",
+            "--project",
+            &project,
+            "--bailout",
+            "8",
+            "--progressive",
+        ]),
+    )
+    .unwrap();
+    let setting = |name: &str| {
+        settings
+            .iter()
+            .find(|(option, _, _)| option == name)
+            .map(|(_, value, source)| (value.as_str(), source.as_str()))
+    };
+    assert_eq!(setting("output"), Some(("out.png", "command line")));
+    assert_eq!(setting("max-iters"), Some(("500", "config.toml")));
+    assert_eq!(setting("palette"), Some(("fire", "config.toml")));
+    assert_eq!(setting("threads"), Some(("3", "MANDELBROT_THREADS")));
+    assert_eq!(setting("foo"), None);
+    assert_eq!(
+        setting("zoom"),
+        Some(("20", format!("project {}", project).as_str()))
+    );
+    assert_eq!(setting("bailout"), Some(("8", "command line")));
+    assert_eq!(setting("progressive"), Some(("true", "command line")));
+    assert_eq!(setting("strategy"), Some(("naive", "built in")));
+    assert_eq!(setting("workers"), None);
+
+    let settings =
+        effective_settings(&file, Vec::new(), &args(&["serve", "--workers", "3"])).unwrap();
+    assert!(settings.contains(&("workers".into(), "3".into(), "command line".into())));
+    assert!(!settings.iter().any(|(option, _, _)| option == "zoom"));
+
+    assert!(effective_settings(&file, Vec::new(), &args(&["orbit", "0,0"])).is_err());
+    assert!(effective_settings(&file, Vec::new(), &args(&["out.png", "--zoom"])).is_err());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+/// Run the `validate` subcommand: `validate FILE [OPTIONS]` checks the
+/// scene, palette, project or configuration file FILE without rendering it,
+/// prints what it found, and fails if anything is wrong. Scenes, and the
+/// scenes of projects, are checked as `animate` would render them with the
+/// options given.
+fn validate_command(args: &[String]) -> Result<()> {
+    let [file, rest @ ..] = args else {
+        return Err(Error::Usage(
+            "validate takes a scene, palette, project or configuration file".to_string(),
+        ));
+    };
+    let mut problems = Vec::new();
+    let mut options = parse_args(rest).map_err(Error::Usage)?;
+    if options.output.is_some() {
+        return Err(Error::Usage(
+            "validate takes a single file, give the size of the frames with --pixels".to_string(),
+        ));
+    }
+    let mut scene = None;
+    match validate::Kind::of(file)? {
+        validate::Kind::Scene => match Scene::read(file) {
+            Ok(read) => scene = Some(read),
+            Err(e) => problems.push(e.to_string()),
+        },
+        validate::Kind::Palette => match palette::read(file) {
+            Ok(palette) => println!("{}: a palette of {} stops", file, palette.stops().len()),
+            Err(e) => problems.push(e.to_string()),
+        },
+        validate::Kind::Project => match Project::open(file) {
+            Ok(project) => {
+                // the options given override the project's, as with --project
+                let mut args = project.to_args();
+                args.extend_from_slice(rest);
+                match parse_args(&args) {
+                    Ok(parsed) => options = parsed,
+                    Err(e) => problems.push(e),
+                }
+                scene = project.scene;
+            }
+            Err(e) => problems.push(e.to_string()),
+        },
+        validate::Kind::Config => match Config::open(file) {
+            Ok(config) => {
+                for section in config::SECTIONS {
+                    if let Err(e) = parse_args(&config.to_args(section)) {
+                        problems.push(format!("[{}]: {}", section, e));
+                    }
+                }
+            }
+            Err(e) => problems.push(e.to_string()),
+        },
+    }
+    if let Some(name) = &options.palette {
+        if let Err(e) = autopalette::choose(name) {
+            problems.push(e.to_string());
+        }
+    }
+
+    if let Some(scene) = &scene {
+        let pixels = options.pixels.as_deref().unwrap_or("1000x750");
+        let bounds =
+            parse_pair(pixels, 'x').ok_or_else(|| Error::parse("image dimensions", pixels))?;
+        let plan = validate::Plan {
+            bounds,
+            per_keyframe: options.frames,
+            fps: options.fps,
+            numeric: options.numeric,
+            max_iters: options.max_iters,
+        };
+        let (frames, found) = validate::check_scene(scene, &plan);
+        let total = scene.frames(plan.per_keyframe, plan.fps).len();
+        println!(
+            "{}: {} keyframes, {} frames of {}x{}",
+            file,
+            scene.keyframes.len(),
+            total,
+            bounds.0,
+            bounds.1
+        );
+        if found.is_empty() && !frames.is_empty() {
+            let threads = options.threads.unwrap_or_else(num_cpus::get);
+            let seconds = validate::estimate_seconds(&frames, bounds, options.numeric, threads);
+            println!(
+                "about {} to render with {} thread{}, before coloring and encoding",
+                validate::duration(seconds),
+                threads,
+                if threads == 1 { "" } else { "s" }
+            );
+        }
+        problems.extend(found);
+    }
+
+    for problem in &problems {
+        println!("{}: {}", file, problem);
+    }
+    match problems.len() {
+        0 => {
+            println!("{}: ok", file);
+            Ok(())
+        }
+        1 => Err(Error::Invalid(format!("1 problem in {}", file))),
+        count => Err(Error::Invalid(format!("{} problems in {}", count, file))),
+    }
+}
+
+/// The disk cache `serve` and `explore` keep renders in, unless
+/// `--disk-cache-size` is 0 or there is nowhere to keep it.
+fn disk_cache(options: &Options) -> Option<DiskCache> {
+    if options.disk_cache_size == 0 {
+        return None;
+    }
+    let dir = DiskCache::default_dir().ok()?;
+    Some(DiskCache::new(dir, options.disk_cache_size << 20))
+}
+
+/// Run the `coverage` subcommand: `map FILE.png` renders the whole set,
+/// or the view given, with a heat map of the regions visited over it.
+fn coverage_command(args: &[String]) -> Result<()> {
+    let [command, args @ ..] = args else {
+        return Err(Error::Usage("expected coverage map FILE.png".to_string()));
+    };
+    if command != "map" {
+        return Err(Error::Usage(format!(
+            "unknown coverage command {}, expected map",
+            command
+        )));
+    }
+    let mut options = parse_options(args)?;
+    let output = options
+        .output
+        .clone()
+        .ok_or_else(|| Error::Usage("coverage map requires an output file".to_string()))?;
+    options.pixels.get_or_insert_with(|| "1000x750".to_string());
+    let whole_set = [&options.upper_left, &options.lower_right, &options.center]
+        .iter()
+        .all(|point| point.is_none())
+        && options.from.is_none()
+        && options.location.is_none();
+    if whole_set {
+        options.center = Some("-0.75,0".to_string());
+    }
+    let share::View {
+        bounds,
+        upper_left,
+        lower_right,
+    } = view(&options)?;
+    error::validate_viewport(bounds, upper_left, lower_right)?;
+
+    let visits = coverage::read(&platform::config_file(coverage::FILE_NAME)?)?;
+    if visits.is_empty() {
+        eprintln!("no regions visited yet; stats enable starts logging them");
+    }
+    let mut pixels = vec![0; bounds.0 * bounds.1];
+    render::render_parallel(
+        options.strategy,
+        &mut pixels,
+        bounds,
+        upper_left,
+        lower_right,
+        &render::Schedule::new(options.threads.unwrap_or_else(num_cpus::get)),
+    );
+    let heat = coverage::heat(&visits, bounds, upper_left, lower_right);
+    let rgb = coverage::map(&pixels, &heat);
+    write_image(&output, &rgb, bounds, upper_left, lower_right)
+}
+
+/// Run the `bookmark` subcommand: `add NAME VIEW...` saves the view of the
+/// options in the user's store as NAME, `list` prints the views saved, as
+/// lines of a bookmarks file, and `remove NAME` forgets one.
+fn bookmark_command(args: &[String]) -> Result<()> {
+    let file = Store::default_file()?;
+    let mut store = Store::open(&file)?;
+    match args {
+        [command, name, options @ ..] if command == "add" => {
+            let mut options = parse_args(options).map_err(Error::Usage)?;
+            if options.output.is_some() {
+                return Err(Error::Usage(
+                    "bookmark add takes the view as options, e.g. --center CENTER --zoom ZOOM"
+                        .to_string(),
+                ));
+            }
+            let bookmark = match &options.center {
+                // kept as given, rather than through the corners
+                Some(center) if !locator::is_locator(center) => {
+                    let center = center_point(center, &options)?;
+                    Bookmark {
+                        center: (center.re, center.im),
+                        zoom: options.zoom,
+                    }
+                }
+                _ => {
+                    // only the center and the zoom are kept, whatever the size
+                    if options.pixels.is_none() && options.from.is_none() {
+                        options.pixels = Some("1x1".to_string());
+                    }
+                    let view = view(&options)?;
+                    error::validate_viewport(view.bounds, view.upper_left, view.lower_right)?;
+                    let center = (view.upper_left + view.lower_right) / 2.0;
+                    Bookmark {
+                        center: (center.re, center.im),
+                        zoom: ComplexWidth::between(view.upper_left, view.lower_right)
+                            .zoom()
+                            .0,
+                    }
+                }
+            };
+            store.add(name, bookmark)?;
+            store.save()?;
+            eprintln!("{} saved as {} in {}", bookmark, name, file);
+            Ok(())
+        }
+        [command] if command == "list" => {
+            for (name, bookmark) in store.iter() {
+                println!("{} {}", name, bookmark);
+            }
+            Ok(())
+        }
+        [command, name] if command == "remove" => {
+            if !store.remove(name) {
+                return Err(Error::Usage(format!("no bookmark {} in {}", name, file)));
+            }
+            store.save()
+        }
+        _ => Err(Error::Usage(
+            "expected bookmark add NAME VIEW..., bookmark list or bookmark remove NAME".to_string(),
+        )),
+    }
+}
+
+/// Run the `convert-view` subcommand: print the view given by `args` in
+/// every form, corners, center and zoom, width, so that coordinates can be
+/// carried between this program, other renderers and published locations.
+///
+/// Besides the options selecting a view, `--from-corners UL LR` stands for
+/// `--upper-left UL --lower-right LR` and `--from-center CENTER` for
+/// `--center CENTER`.
+fn convert_view(args: &[String]) -> Result<()> {
+    let mut translated = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--from-corners" => {
+                let (Some(upper_left), Some(lower_right)) = (args.next(), args.next()) else {
+                    return Err(Error::Usage(
+                        "--from-corners requires two corner points".to_string(),
+                    ));
+                };
+                translated.extend([
+                    "--upper-left".to_string(),
+                    upper_left.clone(),
+                    "--lower-right".to_string(),
+                    lower_right.clone(),
+                ]);
+            }
+            "--from-center" => translated.push("--center".to_string()),
+            _ => translated.push(arg.clone()),
+        }
+    }
+    let options = parse_args(&translated).map_err(Error::Usage)?;
+    if options.output.is_some() {
+        return Err(Error::Usage(
+            "convert-view takes the view as options, e.g. --from-corners UL LR".to_string(),
+        ));
+    }
+    let view = view(&options)?;
+    error::validate_viewport(view.bounds, view.upper_left, view.lower_right)?;
+    print!("{}", view.describe());
+    Ok(())
+}
+
+/// Run the `find` subcommand: look for the most interesting views `--zoom`
+/// deep and print their centers, rendering each to a file of its own if FILE
+/// is given.
+fn find_views(options: &Options) -> Result<()> {
+    let found = find::find(&find::Search {
+        zoom: Zoom(options.zoom),
+        candidates: options.candidates,
+        top: options.top,
+        seed: options.seed,
+        threads: options.threads.unwrap_or_else(num_cpus::get),
+    });
+    if found.is_empty() {
+        return Err(Error::Viewport(format!(
+            "found no views {} deep, try more --candidates",
+            options.zoom
+        )));
+    }
+    let Some(output) = &options.output else {
+        for view in &found {
+            println!("{},{}\t{:.3}", view.center.re, view.center.im, view.score);
+        }
+        return Ok(());
+    };
+
+    let pixels = options
+        .pixels
+        .as_deref()
+        .ok_or_else(|| Error::Usage("missing image dimensions".to_string()))?;
+    let bounds = parse_pair(pixels, 'x').ok_or_else(|| Error::parse("image dimensions", pixels))?;
+    let palette = match &options.palette {
+        Some(name) => Some(autopalette::choose(name)?),
+        None => None,
+    };
+    if options.mkdir {
+        output::create_parent_dirs(output)?;
+    }
+    for (number, view) in found.iter().enumerate() {
+        let (upper_left, lower_right) =
+            render::corners_from_center(view.center, Zoom(options.zoom), bounds);
+        error::validate_viewport(bounds, upper_left, lower_right)?;
+        let mut pixels = vec![0; bounds.0 * bounds.1];
+        render::render_parallel(
+            options.strategy,
+            &mut pixels,
+            bounds,
+            upper_left,
+            lower_right,
+            &render::Schedule {
+                chunk_rows: options.chunk_rows,
+                sampling: options.sampling,
+                numeric: options.numeric,
+                bailout: options.formula.bailout,
+                limit: options.max_iters.unwrap_or(MaxIters::Auto).resolve(
+                    bounds,
+                    upper_left,
+                    lower_right,
+                ),
+                ..render::Schedule::new(num_cpus::get())
+            },
+        );
+        if let Some(palette) = &palette {
+            pixels = palette::apply(&pixels, &palette.lut_for(&pixels, &options.palette_cycle));
+        }
+        let file = output::with_suffix(output, &format!("-{}", number + 1));
+        write_image(&file, &pixels, bounds, upper_left, lower_right)?;
+        println!(
+            "{},{}\t{:.3}\t{}",
+            view.center.re, view.center.im, view.score, file
+        );
+    }
+    Ok(())
+}
+
+/// Run the `extend` subcommand: `extend IN.png DIRECTION PIXELS OUT.png`
+/// renders the strip PIXELS wide, or high, past the DIRECTION edge of the
+/// image IN.png, whose view is embedded in it, and writes the image with the
+/// strip stitched on to OUT.png.
+///
+/// Only the strip is rendered, with the options that follow, which should be
+/// those the image was rendered with: `--max-iters`, `--bailout` and, for
+/// images in color, `--palette`.
+fn extend_image(args: &[String]) -> Result<()> {
+    let [input, direction, amount, output, rest @ ..] = args else {
+        return Err(Error::Usage(
+            "extend takes IN.png DIRECTION PIXELS OUT.png".to_string(),
+        ));
+    };
+    let direction: Direction = direction.parse().map_err(Error::Usage)?;
+    let amount = amount
+        .parse()
+        .ok()
+        .filter(|&amount: &usize| amount > 0)
+        .ok_or_else(|| Error::parse("strip size", amount))?;
+    let options = parse_options(rest)?;
+    if options.output.is_some() {
+        return Err(Error::Usage(
+            "extend takes no more positional arguments after OUT.png".to_string(),
+        ));
+    }
+
+    let view = share::read_png_view(input)?;
+    let (image, bounds) = read_image(input)?;
+    if bounds != view.bounds {
+        return Err(Error::Usage(format!(
+            "{} is {}x{}, but its embedded view is {}x{}",
+            input, bounds.0, bounds.1, view.bounds.0, view.bounds.1
+        )));
+    }
+    let channels = image.len() / (bounds.0 * bounds.1).max(1);
+    let palette = match &options.palette {
+        Some(name) => match autopalette::choose(name)? {
+            Choice::Fixed(palette) => Some(palette),
+            Choice::Auto(_) => {
+                return Err(Error::Usage(
+                    "extend can't fit a palette to the strip alone, give a fixed one".to_string(),
+                ))
+            }
+        },
+        None => None,
+    };
+    if (channels == 3) != palette.is_some() {
+        return Err(Error::Usage(format!(
+            "{} is {}, so extend needs {}",
+            input,
+            if channels == 3 {
+                "in color"
+            } else {
+                "grayscale"
+            },
+            if channels == 3 {
+                "the --palette it was colored with"
+            } else {
+                "no --palette"
+            }
+        )));
+    }
+
+    let strip = extend::strip(&view, direction, amount);
+    let extended = extend::extended(&view, &strip, direction);
+    error::validate_viewport(extended.bounds, extended.upper_left, extended.lower_right)?;
+    let mut pixels = vec![0; strip.bounds.0 * strip.bounds.1];
+    render::render_parallel(
+        Strategy::Naive,
+        &mut pixels,
+        strip.bounds,
+        strip.upper_left,
+        strip.lower_right,
+        &render::Schedule {
+            sampling: options.sampling,
+            bailout: options.formula.bailout,
+            // as the whole image would have had it
+            limit: options
+                .max_iters
+                .map_or(render::DEFAULT_LIMIT, |max_iters| {
+                    max_iters.resolve(view.bounds, view.upper_left, view.lower_right)
+                }),
+            ..render::Schedule::new(num_cpus::get())
+        },
+    );
+    if let Some(palette) = &palette {
+        pixels = palette::apply(&pixels, &palette.cycled_lut(256, &options.palette_cycle));
+    }
+    let stitched = extend::stitch(&image, &view, &pixels, &strip, direction, channels);
+    if options.mkdir {
+        output::create_parent_dirs(output)?;
+    }
+    write_image(
+        output,
+        &stitched,
+        extended.bounds,
+        extended.upper_left,
+        extended.lower_right,
+    )
+}
+
+/// Run the `orbit` subcommand: `orbit RE,IM` prints the orbit of the point,
+/// step by step, and how it ends, and plots it to the output file, SVG or
+/// PNG, if given.
+fn orbit_command(args: &[String]) -> Result<()> {
+    let [c, rest @ ..] = args else {
+        return Err(Error::Usage("orbit takes a point RE,IM".to_string()));
+    };
+    let options = parse_args(rest).map_err(Error::Usage)?;
+    let c = center_point(c, &options)?;
+    let limit = match options.max_iters {
+        None => render::DEFAULT_LIMIT,
+        Some(MaxIters::Fixed(limit)) => limit,
+        Some(_) => {
+            return Err(Error::Usage(
+                "orbit takes a fixed number of --max-iters".to_string(),
+            ))
+        }
+    };
+    let bailout = options.formula.bailout;
+    let orbit = orbit::trace(c, limit, bailout);
+    print!("{}", orbit);
+
+    let Some(file) = &options.output else {
+        return Ok(());
+    };
+    let bounds = match &options.pixels {
+        Some(pixels) => {
+            parse_pair(pixels, 'x').ok_or_else(|| Error::parse("image dimensions", pixels))?
+        }
+        None => (400, 400),
+    };
+    if options.mkdir {
+        output::create_parent_dirs(file)?;
+    }
+    let drawing = orbit.drawing(bounds, bailout);
+    if file.to_ascii_lowercase().ends_with(".svg") {
+        drawing.write_svg(file)
+    } else {
+        let (upper_left, lower_right) = orbit::frame(bounds, bailout);
+        write_image(file, &drawing.rasterize(), bounds, upper_left, lower_right)
+    }
+}
+
+/// Run the `compare` subcommand: `compare A.png B.png` prints how much the
+/// two images differ, writes a heatmap of the differences to `--diff` if
+/// given, and fails if two pixels differ by more than `--tolerance`.
+fn compare_images(args: &[String]) -> Result<()> {
+    let [a, b, rest @ ..] = args else {
+        return Err(Error::Usage("compare takes A.png B.png".to_string()));
+    };
+    let options = parse_args(rest).map_err(Error::Usage)?;
+    if options.output.is_some() {
+        return Err(Error::Usage(
+            "compare takes two images, and options after them".to_string(),
+        ));
+    }
+    let ((mut a_pixels, bounds), (mut b_pixels, b_bounds)) = (read_image(a)?, read_image(b)?);
+    if bounds != b_bounds {
+        return Err(Error::Different(format!(
+            "{} is {}x{}, {} is {}x{}",
+            a, bounds.0, bounds.1, b, b_bounds.0, b_bounds.1
+        )));
+    }
+    // a grayscale image is compared to one in color as gray RGB
+    if a_pixels.len() < b_pixels.len() {
+        a_pixels = compare::gray_to_rgb(&a_pixels);
+    } else if b_pixels.len() < a_pixels.len() {
+        b_pixels = compare::gray_to_rgb(&b_pixels);
+    }
+    let channels = a_pixels.len() / (bounds.0 * bounds.1).max(1);
+
+    let difference = compare::compare(&a_pixels, &b_pixels, channels);
+    print!("{}", difference.describe());
+    if let Some(diff) = &options.diff {
+        if options.mkdir {
+            output::create_parent_dirs(diff)?;
+        }
+        let heatmap = compare::heatmap(&a_pixels, &b_pixels, channels);
+        output::write(diff, &encode::png(&heatmap, bounds)?)?;
+    }
+    if !difference.within(options.tolerance) {
+        return Err(Error::Different(format!(
+            "pixels differ by up to {}, more than the tolerance of {}",
+            difference.max, options.tolerance
+        )));
+    }
+    Ok(())
+}
+
+/// Run the `palette` subcommand: `palette preview NAME --out FILE` draws the
+/// preview of a palette, built in or from a file, and `palette
+/// --list-palettes` lists the built-in ones.
+fn palette_command(args: &[String]) -> Result<()> {
+    if args.first().map(String::as_str) != Some("preview") {
+        let options = parse_args(args).map_err(Error::Usage)?;
+        if !options.list_palettes {
+            return Err(Error::Usage(
+                "palette takes preview NAME --out FILE, or --list-palettes".to_string(),
+            ));
+        }
+        return list_palettes(&options);
+    }
+    let name = args
+        .get(1)
+        .ok_or_else(|| Error::Usage("missing palette name or file".to_string()))?;
+    let options = parse_options(&args[2..])?;
+    let file = options
+        .output
+        .as_deref()
+        .ok_or_else(|| Error::Usage("missing preview file, give --out FILE".to_string()))?;
+    write_preview(&palette::load(name)?, file, &options)
+}
+
+/// Print the names of the built-in palettes, and with `--preview-dir` write
+/// the preview of each to `NAME.png` in that directory.
+fn list_palettes(options: &Options) -> Result<()> {
+    for (name, _, _) in palette::PRESETS {
+        let Some(dir) = &options.preview_dir else {
+            println!("{}", name);
+            continue;
+        };
+        let file = Path::new(dir).join(format!("{}.png", name));
+        let file = file.to_string_lossy();
+        write_preview(&palette::preset(name).unwrap(), &file, options)?;
+        println!("{}\t{}", name, file);
+    }
+    Ok(())
+}
+
+/// Write the preview of `palette` to `file`, `--pixels` in size.
+fn write_preview(palette: &Palette, file: &str, options: &Options) -> Result<()> {
+    let bounds = match &options.pixels {
+        Some(pixels) => {
+            parse_pair(pixels, 'x').ok_or_else(|| Error::parse("image dimensions", pixels))?
+        }
+        None => preview::DEFAULT_BOUNDS,
+    };
+    if options.mkdir {
+        output::create_parent_dirs(file)?;
+    }
+    let rgb = preview::preview(palette, bounds);
+    output::write(file, &encode::png(&rgb, bounds)?)
+}
+
+/// The address `serve` listens on unless told otherwise.
+const DEFAULT_LISTEN: &str = "127.0.0.1:8080";
+
+/// Run the `serve` subcommand: answer HTTP requests for renders and tiles
+/// until killed, or until `SIGTERM`, after answering those in progress.
+fn serve(options: &Options) -> Result<()> {
+    if options.output.is_some() {
+        return Err(Error::Usage(
+            "serve takes no positional arguments".to_string(),
+        ));
+    }
+    // every image is colored alone, so it takes a palette of fixed colors
+    let palette = match &options.palette {
+        Some(name) => Some(palette::preset_name(name).ok_or_else(|| {
+            Error::Usage(format!(
+                "serve colors with a built-in palette, not {}; see --list-palettes",
+                name
+            ))
+        })?),
+        None => None,
+    };
+    let listen = options.listen.as_deref().unwrap_or(DEFAULT_LISTEN);
+    let listener = TcpListener::bind(listen).map_err(Error::io(listen))?;
+    let address = listener.local_addr().map_err(Error::io(listen))?;
+
+    log(
+        options,
+        Priority::Info,
+        &format!("listening on http://{}", address),
+    );
+    signals::install_terminate();
+    if options.service {
+        notify(&format!("READY=1\nSTATUS=listening on {}", address));
+    }
+    let terminated = || {
+        if !signals::take_terminate_request() {
+            return false;
+        }
+        log(
+            options,
+            Priority::Info,
+            "SIGTERM received, answering the requests in progress",
+        );
+        if options.service {
+            notify("STOPPING=1");
+        }
+        true
+    };
+    server::serve(
+        listener,
+        &server::Config {
+            workers: options.workers.unwrap_or_else(num_cpus::get),
+            cache_tiles: options.cache_tiles,
+            strategy: options.strategy,
+            max_iters: options.max_iters,
+            palette,
+            timeout: options.timeout,
+            disk_cache: disk_cache(options).map(|cache| cache.dir().to_path_buf()),
+            disk_cache_bytes: options.disk_cache_size << 20,
+            sandbox: options
+                .sandbox
+                .then(std::env::current_exe)
+                .transpose()
+                .map_err(Error::io("the program"))?,
+        },
+        terminated,
+    )
+}
+
+#[test]
+fn test_serve_palette() {
+    // checked before listening
+    let options = Options {
+        palette: Some("fire.json".to_string()),
+        listen: Some("invalid address".to_string()),
+        ..Options::default()
+    };
+    let error = serve(&options).unwrap_err().to_string();
+    assert!(error.contains("built-in palette"), "{}", error);
+}
+
+/// Options given on the command line as `--name value` pairs, or as
+/// positional arguments for the first four.
+#[derive(Debug, PartialEq)]
+struct Options {
+    /// The PNG file to write, or `-` for stdout.
+    output: Option<String>,
+    /// The dimensions of the image, as `WIDTHxHEIGHT`.
+    pixels: Option<String>,
+    /// The corners of the view, unparsed since reading them depends on
+    /// `decimal`, which may come later on the command line.
+    upper_left: Option<String>,
+    lower_right: Option<String>,
+    /// Where to write an HTML report of the render, if anywhere.
+    report: Option<String>,
+    /// Where to write the statistics of the render as JSON, if anywhere.
+    stats_json: Option<String>,
+    /// A PNG file to write the iterations spent on every pixel to.
+    cost_map: Option<String>,
+    /// Whether to write what the render did as JSON to stdout at the end.
+    result_json: bool,
+    /// Whether to write a manifest to render the image again from next to
+    /// it.
+    manifest: bool,
+    /// The manifest the image is rendered again from, whose pixels it must
+    /// have, if it is.
+    reproduce: Option<String>,
+    /// How long a render may take before it stops, if it may stop.
+    timeout: Option<Duration>,
+    /// How long `coordinator` waits for a band before handing it out again,
+    /// if not `distributed::DEFAULT_LEASE`.
+    lease: Option<Duration>,
+    /// The color of the pixels a stopped render didn't get to.
+    fill: [u8; 3],
+    /// How to read the decimal separator in the corner points.
+    decimal: DecimalSeparator,
+    /// What to render.
+    fractal: Fractal,
+    /// The algorithm used to fill the pixels.
+    strategy: Strategy,
+    /// Whether to compute only the top half of views symmetric about the
+    /// real axis, and mirror it.
+    mirror: bool,
+    /// The numbers escape times are computed in.
+    numeric: Numeric,
+    /// The escape time kernel to use rather than the best the processor has.
+    kernel: Option<Kernel>,
+    /// Whether to render at doubling limits, writing a snapshot after each.
+    progressive: bool,
+    /// A PFM file to write the derivative field of the view to.
+    derivative: Option<String>,
+    /// A PNG file to write how much the samples of every pixel vary to.
+    variance: Option<String>,
+    /// How many times larger than the image, across and down, it is
+    /// rendered before it is shrunk.
+    render_scale: f64,
+    /// Whether to also write the image split into layers for compositing.
+    layers: bool,
+    /// An OpenEXR file to write the layers and the derivative field to.
+    layers_exr: Option<String>,
+    /// Whether to keep the pixels in a memory-mapped file and encode them a
+    /// row at a time.
+    low_memory: bool,
+    /// The file the orbits of the pixels are saved to, and read back from.
+    state: Option<String>,
+    /// The center of the view, used instead of its corners. `clipboard`
+    /// stands for the point on the clipboard.
+    center: Option<String>,
+    /// How much the view centered on `center` is magnified.
+    zoom: f64,
+    /// A file of named points, which points can be given relative to.
+    bookmarks: Option<String>,
+    /// The project file the options were read from, for its bookmarks and
+    /// its scene.
+    project: Option<String>,
+    /// Whether to copy the center of the view to the clipboard when done.
+    copy_center: bool,
+    /// A PNG file written earlier whose view to render again.
+    from: Option<String>,
+    /// A Kalles Fraktaler or Fraktaler 3 location file whose view to render.
+    location: Option<String>,
+    /// Whether to center the view on a random point near the boundary.
+    random_viewport: bool,
+    /// How corners are fitted to the aspect ratio of the image, if they are.
+    fit: Option<Fit>,
+    /// How many rows a thread renders each time it picks up work.
+    chunk_rows: usize,
+    /// The order threads render the pixels in.
+    traversal: Traversal,
+    /// How many points are averaged for each pixel, and where.
+    sampling: Sampling,
+    /// The parameters of `Fractal::Buddhabrot`, but for the seed, which is
+    /// `sampling`'s.
+    buddhabrot: Buddhabrot,
+    /// The iteration limit given with `--max-iters`, if any, for every
+    /// fractal; without it each has a default of its own.
+    max_iters: Option<MaxIters>,
+    /// The escape times counted in the red, green and blue channels of
+    /// `Fractal::Nebulabrot`.
+    channels: [(usize, usize); 3],
+    /// The polynomial of `Fractal::Newton`.
+    newton: Newton,
+    /// The formula of `Fractal::Formula`.
+    formula: Formula,
+    /// The Rhai script coloring the Mandelbrot set, if any.
+    color_script: Option<String>,
+    /// The contour bands the image is drawn in, if any.
+    bands: Option<Bands>,
+    /// The glow laid over the image, if any.
+    glow: Option<Glow>,
+    /// The file of the palette the image is colored with, if any.
+    palette: Option<String>,
+    /// What shows where points escape, instead of their colors.
+    background: Option<Background>,
+    /// Whether to write RGBA, with the set transparent.
+    transparent: bool,
+    /// Where to write a 1-bit mask of the set, if anywhere.
+    mask: Option<String>,
+    /// How many pixels apart the points of `export-mesh` are.
+    decimate: usize,
+    /// How high `export-mesh` makes the set, as a fraction of the width.
+    height_scale: f64,
+    /// How wide the tiles of `export-dzi` are.
+    tile_size: usize,
+    /// The URL the tiles of an IIIF pyramid are under, without its
+    /// directory.
+    base_url: Option<String>,
+    /// How the gradient of the palette is shifted and repeated.
+    palette_cycle: palette::Cycle,
+    /// How the colors of the palette are dithered down to 8 bits, if they
+    /// are.
+    dither: Option<Dither>,
+    /// Whether to list the built-in palettes instead of rendering.
+    list_palettes: bool,
+    /// The directory `--list-palettes` writes a preview of each palette to,
+    /// if any.
+    preview_dir: Option<String>,
+    /// How the image is redrawn as dots or strokes, if it is.
+    art: Option<Art>,
+    /// The glyphs or tiles the image is rebuilt from, if any.
+    mosaic: Option<Mosaic>,
+    /// The contour lines the image is traced into, if it is.
+    contours: Option<Contours>,
+    /// What is stamped onto a corner of the image, if anything.
+    watermark: Option<Watermark>,
+    /// What to do if `output` already exists.
+    on_exists: OnExists,
+    /// Whether to create the directories the output files go in.
+    mkdir: bool,
+    /// Whether `explore` draws its preview in ASCII rather than braille.
+    ascii: bool,
+    /// The keys of `explore`, and how it shows the views.
+    explore: explore::Settings,
+    /// The scene file `explore` adds keyframes to and `animate` renders.
+    scene: Option<String>,
+    /// How many frames `animate` renders from each keyframe to the next.
+    frames: usize,
+    /// How `animate` writes the frames.
+    format: Format,
+    /// How many frames a second animated images and videos play.
+    fps: u16,
+    /// The video file `animate` encodes the frames into, if any.
+    video: Option<String>,
+    /// Whether `animate` takes its frames from one exponential map.
+    exp_map: bool,
+    /// How much each frame of `animate` counts for when blended into the
+    /// ones before, if they are.
+    temporal: Option<f64>,
+    /// The codec ffmpeg encodes videos with.
+    codec: String,
+    /// How many views `find` keeps.
+    top: usize,
+    /// How many centers `find` tries.
+    candidates: usize,
+    /// Where the random choices of `find` and `--random-viewport` start.
+    seed: u64,
+    /// How much two pixels `compare` may differ by.
+    tolerance: u8,
+    /// Where `compare` writes the heatmap of the differences, if anywhere.
+    diff: Option<String>,
+    /// The address `serve` listens on.
+    listen: Option<String>,
+    /// How many requests `serve` handles at the same time; one per CPU by
+    /// default.
+    workers: Option<usize>,
+    /// How many tiles `serve` keeps in memory.
+    cache_tiles: usize,
+    /// Whether `serve` renders in restricted child processes.
+    sandbox: bool,
+    /// How many megabytes `serve` and `explore` keep renders in on disk.
+    disk_cache_size: u64,
+    /// How many threads a render uses; one per CPU by default.
+    threads: Option<usize>,
+    /// Whether to run as a systemd service: notify the service manager, log
+    /// to the journal and drain on `SIGTERM`.
+    service: bool,
+}
+
+impl Default for Options {
+    fn default() -> Options {
+        Options {
+            output: None,
+            pixels: None,
+            upper_left: None,
+            lower_right: None,
+            report: None,
+            stats_json: None,
+            cost_map: None,
+            result_json: false,
+            manifest: false,
+            reproduce: None,
+            timeout: None,
+            lease: None,
+            fill: [0, 0, 0],
+            decimal: DecimalSeparator::Auto,
+            fractal: Fractal::Mandelbrot,
+            strategy: Strategy::Naive,
+            mirror: true,
+            numeric: Numeric::F64,
+            kernel: None,
+            progressive: false,
+            derivative: None,
+            variance: None,
+            render_scale: 1.0,
+            layers: false,
+            layers_exr: None,
+            low_memory: false,
+            state: None,
+            center: None,
+            zoom: 1.0,
+            bookmarks: None,
+            project: None,
+            copy_center: false,
+            random_viewport: false,
+            fit: None,
+            from: None,
+            location: None,
+            chunk_rows: render::DEFAULT_CHUNK_ROWS,
+            traversal: Traversal::Rows,
+            sampling: Sampling::NONE,
+            buddhabrot: Buddhabrot::default(),
+            max_iters: None,
+            channels: buddhabrot::DEFAULT_CHANNELS,
+            newton: Newton::default(),
+            formula: Formula::default(),
+            color_script: None,
+            bands: None,
+            glow: None,
+            palette: None,
+            background: None,
+            transparent: false,
+            mask: None,
+            decimate: 1,
+            height_scale: mesh::DEFAULT_HEIGHT_SCALE,
+            tile_size: pyramid::DEFAULT_TILE_SIZE,
+            base_url: None,
+            palette_cycle: palette::Cycle::default(),
+            dither: None,
+            list_palettes: false,
+            preview_dir: None,
+            art: None,
+            mosaic: None,
+            contours: None,
+            watermark: None,
+            on_exists: OnExists::Overwrite,
+            mkdir: true,
+            ascii: false,
+            explore: explore::Settings::default(),
+            scene: None,
+            frames: 30,
+            exp_map: false,
+            temporal: None,
+            format: Format::default(),
+            fps: 25,
+            video: None,
+            codec: video::DEFAULT_CODEC.to_string(),
+            top: 5,
+            candidates: 200,
+            seed: 0,
+            tolerance: 0,
+            diff: None,
+            listen: None,
+            workers: None,
+            cache_tiles: 1024,
+            sandbox: false,
+            disk_cache_size: cache::DEFAULT_MAX_BYTES >> 20,
+            threads: None,
+            service: false,
+        }
+    }
+}
+
+/// Parse the command line arguments (without the program name). Positional
+/// arguments stand for `--output`, `--pixels`, `--upper-left` and
+/// `--lower-right`, in that order, and take precedence over them.
+///
+/// Returns an error message if an option is unknown or is missing its value.
+fn parse_args(args: &[String]) -> std::result::Result<Options, String> {
+    let mut positional = Vec::new();
+    let mut options = Options::default();
+    // applied once --strategy is known, wherever it comes
+    let mut max_error = None;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--output" | "--out" => {
+                let value = args.next().ok_or("--output requires a file name or -")?;
+                options.output = Some(value.clone());
+            }
+            "--pixels" => {
+                let value = args.next().ok_or("--pixels requires dimensions")?;
+                options.pixels = Some(value.clone());
+            }
+            "--upper-left" => {
+                let value = args.next().ok_or("--upper-left requires a point")?;
+                options.upper_left = Some(value.clone());
+            }
+            "--lower-right" => {
+                let value = args.next().ok_or("--lower-right requires a point")?;
+                options.lower_right = Some(value.clone());
+            }
+            "--report" => {
+                let value = args.next().ok_or("--report requires a file name")?;
+                options.report = Some(value.clone());
+            }
+            "--timeout" => {
+                let value = args
+                    .next()
+                    .ok_or("--timeout requires a number of seconds")?;
+                options.timeout = match value.parse::<f64>() {
+                    Ok(seconds) if seconds > 0.0 && seconds.is_finite() => {
+                        Some(Duration::from_secs_f64(seconds))
+                    }
+                    _ => return Err(format!("invalid timeout {}", value)),
+                };
+            }
+            "--lease" => {
+                let value = args.next().ok_or("--lease requires a number of seconds")?;
+                options.lease = match value.parse::<f64>() {
+                    Ok(seconds) if seconds > 0.0 && seconds.is_finite() => {
+                        Some(Duration::from_secs_f64(seconds))
+                    }
+                    _ => return Err(format!("invalid lease {}", value)),
+                };
+            }
+            "--fill" => {
+                let value = args.next().ok_or("--fill requires a color")?;
+                options.fill = palette::parse_hex(value)?;
+            }
+            "--stats-json" => {
+                let value = args.next().ok_or("--stats-json requires a file name")?;
+                options.stats_json = Some(value.clone());
+            }
+            "--cost-map" => {
+                let value = args.next().ok_or("--cost-map requires a file name")?;
+                options.cost_map = Some(value.clone());
+            }
+            "--result" => {
+                let value = args.next().ok_or("--result requires a format")?;
+                if value != "json" {
+                    return Err(format!("unknown result format {}, expected json", value));
+                }
+                options.result_json = true;
+            }
+            "--manifest" => options.manifest = true,
+            "--reproduce" => {
+                let value = args.next().ok_or("--reproduce requires a manifest")?;
+                options.reproduce = Some(value.clone());
+            }
+            "--decimal-comma" => options.decimal = DecimalSeparator::Comma,
+            "--decimal-point" => options.decimal = DecimalSeparator::Point,
+            "--fractal" => {
+                let value = args.next().ok_or("--fractal requires a fractal name")?;
+                options.fractal = value.parse()?;
+            }
+            "--poly" => {
+                let value = args.next().ok_or("--poly requires a polynomial")?;
+                let polynomial: Polynomial = value.parse()?;
+                if polynomial.degree() == 0 {
+                    return Err(format!("{} has no roots to converge to", value));
+                }
+                options.newton.polynomial = polynomial;
+            }
+            "--formula" => {
+                let value = args.next().ok_or("--formula requires a formula")?;
+                options.formula.expression = value.parse()?;
+                options.fractal = Fractal::Formula;
+            }
+            "--bailout" => {
+                let value = args.next().ok_or("--bailout requires a radius")?;
+                options.formula.bailout = match value.parse() {
+                    Ok(bailout) if bailout > 0.0 => bailout,
+                    _ => return Err(format!("invalid bailout {}", value)),
+                };
+            }
+            "--strategy" => {
+                let value = args.next().ok_or("--strategy requires a strategy name")?;
+                options.strategy = value.parse()?;
+            }
+            "--max-error" => {
+                let value = args
+                    .next()
+                    .ok_or("--max-error requires a number of gray levels")?;
+                max_error = Some(
+                    value
+                        .parse()
+                        .map_err(|_| format!("invalid maximum error {}", value))?,
+                );
+            }
+            "--no-mirror" => options.mirror = false,
+            "--numeric" => {
+                let value = args.next().ok_or("--numeric requires a number type")?;
+                options.numeric = value.parse()?;
+            }
+            "--force-kernel" => {
+                let value = args.next().ok_or("--force-kernel requires a kernel name")?;
+                options.kernel = Some(value.parse()?);
+            }
+            "--center" => {
+                let value = args.next().ok_or("--center requires a point")?;
+                options.center = Some(value.clone());
+            }
+            "--zoom" => {
+                let value = args.next().ok_or("--zoom requires a magnification")?;
+                options.zoom = match value.parse() {
+                    Ok(zoom) if zoom > 0.0 => zoom,
+                    _ => return Err(format!("invalid zoom {}", value)),
+                };
+            }
+            "--bookmarks" => {
+                let value = args.next().ok_or("--bookmarks requires a file name")?;
+                options.bookmarks = Some(value.clone());
+            }
+            "--project" => {
+                let value = args.next().ok_or("--project requires a file name")?;
+                options.project = Some(value.clone());
+            }
+            "--copy-center" => options.copy_center = true,
+            "--random-viewport" => options.random_viewport = true,
+            "--fit" => {
+                let value = args.next().ok_or("--fit requires a fit")?;
+                options.fit = Some(value.parse()?);
+            }
+            "--progressive" => options.progressive = true,
+            "--derivative" => {
+                let value = args.next().ok_or("--derivative requires a file name")?;
+                options.derivative = Some(value.clone());
+            }
+            "--variance" => {
+                let value = args.next().ok_or("--variance requires a file name")?;
+                options.variance = Some(value.clone());
+            }
+            "--render-scale" => {
+                let value = args.next().ok_or("--render-scale requires a scale")?;
+                options.render_scale = match value.parse::<f64>() {
+                    Ok(scale) if (1.0..=resample::MAX_SCALE).contains(&scale) => scale,
+                    _ => return Err(format!("invalid render scale {}", value)),
+                };
+            }
+            "--layers" => options.layers = true,
+            "--low-memory" => options.low_memory = true,
+            "--layers-exr" => {
+                let value = args.next().ok_or("--layers-exr requires a file name")?;
+                options.layers_exr = Some(value.clone());
+            }
+            "--state" => {
+                let value = args.next().ok_or("--state requires a file name")?;
+                options.state = Some(value.clone());
+            }
+            "--on-exists" => {
+                let value = args.next().ok_or("--on-exists requires a policy")?;
+                options.on_exists = value.parse()?;
+            }
+            "--no-mkdir" => options.mkdir = false,
+            "--ascii" => options.ascii = true,
+            "--bind" => {
+                let value = args.next().ok_or("--bind requires ACTION=KEY")?;
+                options.explore.keys.bind(value)?;
+            }
+            "--high-contrast" => options.explore.high_contrast = true,
+            "--reduced-motion" => options.explore.reduced_motion = true,
+            "--screen-reader" => options.explore.screen_reader = true,
+            "--list-palettes" => options.list_palettes = true,
+            "--preview-dir" => {
+                let value = args.next().ok_or("--preview-dir requires a directory")?;
+                options.preview_dir = Some(value.clone());
+            }
+            "--color-script" => {
+                let value = args.next().ok_or("--color-script requires a file name")?;
+                if !cfg!(feature = "scripting") {
+                    return Err(
+                        "--color-script needs the scripting feature: cargo build --features scripting"
+                            .to_string(),
+                    );
+                }
+                options.color_script = Some(value.clone());
+            }
+            "--palette" => {
+                let value = args
+                    .next()
+                    .ok_or("--palette requires a palette name or file")?;
+                options.palette = Some(value.clone());
+            }
+            "--background" => {
+                let value = args
+                    .next()
+                    .ok_or("--background requires a color or an image file")?;
+                options.background = Some(value.parse()?);
+            }
+            "--transparent" => options.transparent = true,
+            "--decimate" => {
+                let value = args
+                    .next()
+                    .ok_or("--decimate requires a number of pixels")?;
+                options.decimate = match value.parse() {
+                    Ok(pixels) if pixels > 0 => pixels,
+                    _ => return Err(format!("invalid number of pixels {}", value)),
+                };
+            }
+            "--height-scale" => {
+                let value = args.next().ok_or("--height-scale requires a number")?;
+                options.height_scale = match value.parse::<f64>() {
+                    Ok(scale) if scale.is_finite() && scale >= 0.0 => scale,
+                    _ => return Err(format!("invalid height scale {}", value)),
+                };
+            }
+            "--tile-size" => {
+                let value = args
+                    .next()
+                    .ok_or("--tile-size requires a number of pixels")?;
+                options.tile_size = match value.parse() {
+                    Ok(pixels) if pixels > 0 => pixels,
+                    _ => return Err(format!("invalid number of pixels {}", value)),
+                };
+            }
+            "--base-url" => {
+                let value = args.next().ok_or("--base-url requires a URL")?;
+                options.base_url = Some(value.trim_end_matches('/').to_string());
+            }
+            "--mask" => {
+                let value = args.next().ok_or("--mask requires a file name")?;
+                options.mask = Some(value.clone());
+            }
+            "--transfer" => {
+                let value = args
+                    .next()
+                    .ok_or("--transfer requires a transfer function")?;
+                options.palette_cycle.transfer = value.parse()?;
+            }
+            "--palette-offset" => {
+                let value = args
+                    .next()
+                    .ok_or("--palette-offset requires a number of turns")?;
+                options.palette_cycle.offset = match value.parse::<f64>() {
+                    Ok(offset) if offset.is_finite() => offset,
+                    _ => return Err(format!("invalid palette offset {}", value)),
+                };
+            }
+            "--palette-period" => {
+                let value = args.next().ok_or("--palette-period requires a length")?;
+                options.palette_cycle.period = value.parse()?;
+            }
+            "--palette-blend" => {
+                let value = args.next().ok_or("--palette-blend requires a blend")?;
+                options.palette_cycle.blend = value.parse()?;
+            }
+            "--dither" => {
+                let value = args.next().ok_or("--dither requires a dithering")?;
+                options.dither = Some(value.parse()?);
+            }
+            "--scene" => {
+                let value = args.next().ok_or("--scene requires a file name")?;
+                options.scene = Some(value.clone());
+            }
+            "--top" => {
+                let value = args.next().ok_or("--top requires a number of views")?;
+                options.top = match value.parse() {
+                    Ok(top) if top > 0 => top,
+                    _ => return Err(format!("invalid number of views {}", value)),
+                };
+            }
+            "--candidates" => {
+                let value = args
+                    .next()
+                    .ok_or("--candidates requires a number of points")?;
+                options.candidates = match value.parse() {
+                    Ok(candidates) if candidates > 0 => candidates,
+                    _ => return Err(format!("invalid number of candidates {}", value)),
+                };
+            }
+            "--seed" => {
+                let value = args.next().ok_or("--seed requires a number")?;
+                options.seed = value
+                    .parse()
+                    .map_err(|_| format!("invalid seed {}", value))?;
+            }
+            "--tolerance" => {
+                let value = args.next().ok_or("--tolerance requires a gray level")?;
+                options.tolerance = value
+                    .parse()
+                    .map_err(|_| format!("invalid tolerance {}, expected 0 to 255", value))?;
+            }
+            "--diff" => {
+                let value = args.next().ok_or("--diff requires a file name")?;
+                options.diff = Some(value.clone());
+            }
+            "--frames" => {
+                let value = args.next().ok_or("--frames requires a number of frames")?;
+                options.frames = match value.parse() {
+                    Ok(frames) if frames > 0 => frames,
+                    _ => return Err(format!("invalid number of frames {}", value)),
+                };
+            }
+            "--exp-map" => options.exp_map = true,
+            "--temporal" => {
+                let value = args.next().ok_or("--temporal requires a weight")?;
+                options.temporal = match value.parse() {
+                    Ok(weight) if (0.0..=1.0).contains(&weight) => Some(weight),
+                    _ => return Err(format!("invalid weight {} (expected 0 to 1)", value)),
+                };
+            }
+            "--format" => {
+                let value = args.next().ok_or("--format requires png, gif or apng")?;
+                options.format = value.parse()?;
+            }
+            "--video" => {
+                let value = args.next().ok_or("--video requires a file name")?;
+                options.video = Some(value.clone());
+            }
+            "--codec" => {
+                let value = args.next().ok_or("--codec requires an ffmpeg codec name")?;
+                options.codec = value.clone();
+            }
+            "--fps" => {
+                let value = args.next().ok_or("--fps requires a frame rate")?;
+                options.fps = match value.parse() {
+                    Ok(fps) if fps > 0 => fps,
+                    _ => return Err(format!("invalid frame rate {}", value)),
+                };
+            }
+            "--listen" => {
+                let value = args.next().ok_or("--listen requires an address")?;
+                options.listen = Some(value.clone());
+            }
+            "--workers" => {
+                let value = args
+                    .next()
+                    .ok_or("--workers requires a number of threads")?;
+                options.workers = match value.parse() {
+                    Ok(workers) if workers > 0 => Some(workers),
+                    _ => return Err(format!("invalid number of workers {}", value)),
+                };
+            }
+            "--cache-tiles" => {
+                let value = args
+                    .next()
+                    .ok_or("--cache-tiles requires a number of tiles")?;
+                options.cache_tiles = value
+                    .parse()
+                    .map_err(|_| format!("invalid number of tiles {}", value))?;
+            }
+            "--sandbox" => {
+                if !cfg!(all(feature = "sandbox", target_os = "linux")) {
+                    return Err(
+                        "--sandbox needs Linux and the sandbox feature: cargo build --features sandbox"
+                            .to_string(),
+                    );
+                }
+                options.sandbox = true;
+            }
+            "--disk-cache-size" => {
+                let value = args
+                    .next()
+                    .ok_or("--disk-cache-size requires a size in megabytes")?;
+                options.disk_cache_size = match value.parse::<u64>() {
+                    Ok(size)
+                        if size
+                            .checked_shl(20)
+                            .is_some_and(|bytes| bytes >> 20 == size) =>
+                    {
+                        size
+                    }
+                    _ => return Err(format!("invalid disk cache size {}", value)),
+                };
+            }
+            "--threads" => {
+                let value = args
+                    .next()
+                    .ok_or("--threads requires a number of threads")?;
+                options.threads = match value.parse() {
+                    Ok(threads) if threads > 0 => Some(threads),
+                    _ => return Err(format!("invalid number of threads {}", value)),
+                };
+            }
+            "--service" => options.service = true,
+            "--from" => {
+                let value = args.next().ok_or("--from requires a PNG file")?;
+                options.from = Some(value.clone());
+            }
+            "--location" => {
+                let value = args.next().ok_or("--location requires a location file")?;
+                options.location = Some(value.clone());
+            }
+            "--chunk-rows" => {
+                let value = args
+                    .next()
+                    .ok_or("--chunk-rows requires a number of rows")?;
+                options.chunk_rows = match value.parse() {
+                    Ok(rows) if rows > 0 => rows,
+                    _ => return Err(format!("invalid number of rows {}", value)),
+                };
+            }
+            "--traversal" => {
+                let value = args.next().ok_or("--traversal requires an order")?;
+                options.traversal = value.parse()?;
+            }
+            "--samples" => {
+                let value = args
+                    .next()
+                    .ok_or("--samples requires a number of samples")?;
+                options.sampling.samples = match value.parse() {
+                    Ok(samples) if (1..=job::MAX_SAMPLES).contains(&samples) => samples,
+                    _ => {
+                        return Err(format!(
+                            "invalid number of samples {} (expected 1 to {})",
+                            value,
+                            job::MAX_SAMPLES
+                        ))
+                    }
+                };
+                options.buddhabrot.samples = options.sampling.samples;
+            }
+            "--min-iters" => {
+                let value = args
+                    .next()
+                    .ok_or("--min-iters requires a number of iterations")?;
+                options.buddhabrot.min_iters = value
+                    .parse()
+                    .map_err(|_| format!("invalid number of iterations {}", value))?;
+            }
+            "--max-iters" => {
+                let value = args
+                    .next()
+                    .ok_or("--max-iters requires a number of iterations")?;
+                let max_iters = value.parse()?;
+                if let MaxIters::Fixed(iterations) = max_iters {
+                    options.buddhabrot.max_iters = iterations;
+                }
+                options.max_iters = Some(max_iters);
+            }
+            "--jitter" => options.sampling.jitter = true,
+            "--jitter-seed" => {
+                let value = args.next().ok_or("--jitter-seed requires a number")?;
+                options.sampling.seed = value
+                    .parse()
+                    .map_err(|_| format!("invalid seed {}", value))?;
+            }
+            "--bands" => {
+                let value = args
+                    .next()
+                    .ok_or("--bands requires a number of iterations")?;
+                options.bands.get_or_insert_with(Bands::default).width = match value.parse() {
+                    Ok(width) if width > 0 => width,
+                    _ => return Err(format!("invalid band width {}", value)),
+                };
+            }
+            "--band-outline" => {
+                let value = args.next().ok_or("--band-outline requires a gray level")?;
+                options.bands.get_or_insert_with(Bands::default).outline = value
+                    .parse()
+                    .map_err(|_| format!("invalid gray level {}", value))?;
+            }
+            "--band-outline-width" => {
+                let value = args
+                    .next()
+                    .ok_or("--band-outline-width requires a number of pixels")?;
+                options
+                    .bands
+                    .get_or_insert_with(Bands::default)
+                    .outline_width = match value.parse() {
+                    Ok(width) if width <= 2 => width,
+                    _ => return Err(format!("invalid outline width {}", value)),
+                };
+            }
+            "--glow" => {
+                let value = args.next().ok_or("--glow requires a radius in pixels")?;
+                options.glow.get_or_insert_with(Glow::default).radius = value
+                    .parse()
+                    .map_err(|_| format!("invalid glow radius {}", value))?;
+            }
+            "--glow-strength" => {
+                let value = args.next().ok_or("--glow-strength requires a number")?;
+                options.glow.get_or_insert_with(Glow::default).strength = match value.parse() {
+                    Ok(strength) if strength >= 0.0 => strength,
+                    _ => return Err(format!("invalid glow strength {}", value)),
+                };
+            }
+            "--glow-kernel" => {
+                let value = args.next().ok_or("--glow-kernel requires a kernel name")?;
+                options.glow.get_or_insert_with(Glow::default).kernel = value.parse()?;
+            }
+            "--art" => {
+                let value = args.next().ok_or("--art requires a style name")?;
+                options.art.get_or_insert_with(Art::default).kind = value.parse()?;
+            }
+            "--art-spacing" => {
+                let value = args
+                    .next()
+                    .ok_or("--art-spacing requires a number of pixels")?;
+                options.art.get_or_insert_with(Art::default).spacing = match value.parse() {
+                    Ok(spacing) if spacing > 0 => spacing,
+                    _ => return Err(format!("invalid art spacing {}", value)),
+                };
+            }
+            flag @ ("--red-iters" | "--green-iters" | "--blue-iters") => {
+                let value = args
+                    .next()
+                    .ok_or_else(|| format!("{} requires a range of iterations", flag))?;
+                let channel = match flag {
+                    "--red-iters" => 0,
+                    "--green-iters" => 1,
+                    _ => 2,
+                };
+                options.channels[channel] = buddhabrot::parse_channel(value)
+                    .ok_or_else(|| format!("invalid range of iterations {}", value))?;
+            }
+            "--mosaic" => {
+                let value = args
+                    .next()
+                    .ok_or("--mosaic requires glyphs or tile images")?;
+                options.mosaic.get_or_insert_with(Mosaic::default).source = value.parse()?;
+            }
+            "--mosaic-cell" => {
+                let value = args.next().ok_or("--mosaic-cell requires dimensions")?;
+                options.mosaic.get_or_insert_with(Mosaic::default).cell =
+                    match parse_pair(value, 'x') {
+                        Some((width, height)) if width > 0 && height > 0 => Some((width, height)),
+                        _ => return Err(format!("invalid mosaic cell {}", value)),
+                    };
+            }
+            "--locale" => {
+                let value = args.next().ok_or("--locale requires a locale name")?;
+                options.decimal = DecimalSeparator::for_locale(value);
+            }
+            "--contours" => {
+                let value = args.next().ok_or("--contours requires levels")?;
+                options
+                    .contours
+                    .get_or_insert_with(Contours::default)
+                    .levels = value.parse()?;
+            }
+            "--contour-stroke" => {
+                let value = args.next().ok_or("--contour-stroke requires a color")?;
+                options
+                    .contours
+                    .get_or_insert_with(Contours::default)
+                    .stroke = palette::parse_hex(value)?;
+            }
+            "--watermark" => {
+                options.watermark.get_or_insert_with(Watermark::default);
+            }
+            "--watermark-text" => {
+                let value = args.next().ok_or("--watermark-text requires text")?;
+                options
+                    .watermark
+                    .get_or_insert_with(Watermark::default)
+                    .text = Some(value.clone());
+            }
+            "--watermark-logo" => {
+                let value = args.next().ok_or("--watermark-logo requires a PNG image")?;
+                options
+                    .watermark
+                    .get_or_insert_with(Watermark::default)
+                    .logo = Some(value.clone());
+            }
+            "--watermark-corner" => {
+                let value = args.next().ok_or("--watermark-corner requires a corner")?;
+                options
+                    .watermark
+                    .get_or_insert_with(Watermark::default)
+                    .corner = value.parse()?;
+            }
+            "--contour-width" => {
+                let value = args.next().ok_or("--contour-width requires a width")?;
+                options.contours.get_or_insert_with(Contours::default).width = match value.parse() {
+                    Ok(width) if width > 0.0 && f64::is_finite(width) => width,
+                    _ => return Err(format!("invalid contour width {}", value)),
+                };
+            }
+            flag if flag.starts_with("--") => return Err(format!("unknown option {}", flag)),
+            _ => positional.push(arg.clone()),
+        }
+    }
+
+    if let Some(max_error) = max_error {
+        match &mut options.strategy {
+            Strategy::Adaptive { max_error: error } => *error = max_error,
+            _ => return Err("--max-error goes with --strategy adaptive".to_string()),
+        }
+    }
+
+    let slots = [
+        &mut options.output,
+        &mut options.pixels,
+        &mut options.upper_left,
+        &mut options.lower_right,
+    ];
+    if positional.len() > slots.len() {
+        return Err("too many arguments".to_string());
+    }
+    for (slot, value) in slots.into_iter().zip(positional) {
+        *slot = Some(value);
+    }
+
+    Ok(options)
+}
+
+#[test]
+fn test_parse_args() {
+    let args: Vec<String> = ["out.png", "10x10", "--report", "r.html", "-1,1", "1,-1"]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+    let options = parse_args(&args).unwrap();
+    assert_eq!(options.output.as_deref(), Some("out.png"));
+    assert_eq!(options.pixels.as_deref(), Some("10x10"));
+    assert_eq!(options.upper_left.as_deref(), Some("-1,1"));
+    assert_eq!(options.lower_right.as_deref(), Some("1,-1"));
+    assert_eq!(options.report.as_deref(), Some("r.html"));
+    assert_eq!(options.decimal, DecimalSeparator::Auto);
+
+    let args: Vec<String> = ["--pixels", "10x10", "--output", "-", "out.png"]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+    let options = parse_args(&args).unwrap();
+    assert_eq!(options.output.as_deref(), Some("out.png"));
+    assert_eq!(options.pixels.as_deref(), Some("10x10"));
+    assert!(parse_args(&vec!["x".to_string(); 5]).is_err());
+
+    assert!(!options.layers);
+    assert!(parse_args(&["--layers".to_string()]).unwrap().layers);
+    assert!(
+        parse_args(&["--low-memory".to_string()])
+            .unwrap()
+            .low_memory
+    );
+    assert!(options.mirror);
+    assert!(!parse_args(&["--no-mirror".to_string()]).unwrap().mirror);
+    let args: Vec<String> = ["--bind", "zoom-in=i", "--high-contrast", "--reduced-motion"]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+    let settings = parse_args(&args).unwrap().explore;
+    assert_eq!(
+        settings.keys.action(explore::Key::Char('i')),
+        Some(explore::Action::ZoomIn)
+    );
+    assert!(settings.high_contrast && settings.reduced_motion && !settings.screen_reader);
+    assert!(parse_args(&["--bind".to_string(), "zoom-in".to_string()]).is_err());
+    assert_eq!(options.traversal, Traversal::Rows);
+    let args = ["--traversal".to_string(), "hilbert".to_string()];
+    assert_eq!(parse_args(&args).unwrap().traversal, Traversal::Hilbert);
+    let args = ["--layers-exr".to_string(), "l.exr".to_string()];
+    assert_eq!(
+        parse_args(&args).unwrap().layers_exr.as_deref(),
+        Some("l.exr")
+    );
+
+    let args = ["--background".to_string(), "#000080".to_string()];
+    assert_eq!(
+        parse_args(&args).unwrap().background,
+        Some(Background::Color([0, 0, 128]))
+    );
+    assert!(parse_args(&["--background".to_string(), "#00".to_string()]).is_err());
+    assert!(!options.transparent);
+    assert!(
+        parse_args(&["--transparent".to_string()])
+            .unwrap()
+            .transparent
+    );
+    assert_eq!(options.decimate, 1);
+    let args = ["--decimate", "4", "--height-scale", "0.5"].map(String::from);
+    let parsed = parse_args(&args).unwrap();
+    assert_eq!((parsed.decimate, parsed.height_scale), (4, 0.5));
+    assert!(parse_args(&["--decimate", "0"].map(String::from)).is_err());
+    assert!(parse_args(&["--height-scale", "-1"].map(String::from)).is_err());
+    assert_eq!(options.tile_size, 256);
+    let args = [
+        "--tile-size",
+        "512",
+        "--base-url",
+        "https://example.com/tiles/",
+    ];
+    let parsed = parse_args(&args.map(String::from)).unwrap();
+    assert_eq!(parsed.tile_size, 512);
+    assert_eq!(
+        parsed.base_url.as_deref(),
+        Some("https://example.com/tiles")
+    );
+    assert!(parse_args(&["--tile-size", "0"].map(String::from)).is_err());
+    let args = ["--mask".to_string(), "set.png".to_string()];
+    assert_eq!(parse_args(&args).unwrap().mask.as_deref(), Some("set.png"));
+
+    let args = ["--on-exists".to_string(), "increment".to_string()];
+    assert_eq!(parse_args(&args).unwrap().on_exists, OnExists::Increment);
+    assert!(parse_args(&["--on-exists".to_string(), "x".to_string()]).is_err());
+    let args: Vec<String> = ["--samples", "9", "--jitter-seed", "4", "--jitter"]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+    assert_eq!(
+        parse_args(&args).unwrap().sampling,
+        Sampling {
+            samples: 9,
+            seed: 4,
+            jitter: true,
+        }
+    );
+    assert!(parse_args(&["--samples".to_string(), "0".to_string()]).is_err());
+    let args = ["--samples".to_string(), (job::MAX_SAMPLES + 1).to_string()];
+    assert!(parse_args(&args).is_err());
+    let args = ["--samples".to_string(), usize::MAX.to_string()];
+    assert!(parse_args(&args).is_err());
+    let args: Vec<String> = ["--glow-kernel", "box", "--glow", "3"]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+    assert_eq!(
+        parse_args(&args).unwrap().glow,
+        Some(Glow {
+            kernel: glow::Kernel::Box,
+            radius: 3,
+            strength: 1.0
+        })
+    );
+    let args: Vec<String> = ["--band-outline-width", "2", "--bands", "16"]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+    assert_eq!(
+        parse_args(&args).unwrap().bands,
+        Some(Bands {
+            width: 16,
+            outline: 0,
+            outline_width: 2
+        })
+    );
+    assert!(parse_args(&["--band-outline-width".to_string(), "3".to_string()]).is_err());
+    let args = ["--art".to_string(), "hatch".to_string()];
+    assert_eq!(
+        parse_args(&args).unwrap().art,
+        Some(Art {
+            kind: art::Kind::Hatch,
+            spacing: 6
+        })
+    );
+    assert!(parse_args(&["--art-spacing".to_string(), "0".to_string()]).is_err());
+    let args: Vec<String> = ["--contour-width", "0.5", "--contours", "5,10"]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+    assert_eq!(
+        parse_args(&args).unwrap().contours,
+        Some(Contours {
+            levels: contour::Levels::At(vec![5, 10]),
+            stroke: [0, 0, 0],
+            width: 0.5
+        })
+    );
+    assert!(parse_args(&["--contour-stroke".to_string(), "red".to_string()]).is_err());
+    let args: Vec<String> = ["--mosaic-cell", "4x8", "--mosaic", " .#"]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+    assert_eq!(
+        parse_args(&args).unwrap().mosaic,
+        Some(Mosaic {
+            source: mosaic::Source::Glyphs(vec![' ', '.', '#']),
+            cell: Some((4, 8))
+        })
+    );
+    assert!(parse_args(&["--mosaic-cell".to_string(), "0x8".to_string()]).is_err());
+    let args: Vec<String> = [
+        "--fractal",
+        "buddhabrot",
+        "--samples",
+        "20",
+        "--max-iters",
+        "500",
+    ]
+    .iter()
+    .map(|s| s.to_string())
+    .collect();
+    let options = parse_args(&args).unwrap();
+    assert_eq!(options.fractal, Fractal::Buddhabrot);
+    assert_eq!(
+        options.buddhabrot,
+        Buddhabrot {
+            samples: 20,
+            max_iters: 500,
+            ..Buddhabrot::default()
+        }
+    );
+    assert!(parse_args(&["--max-iters".to_string(), "0".to_string()]).is_err());
+    let options = parse_args(&["--max-iters".to_string(), "auto".to_string()]).unwrap();
+    assert_eq!(options.max_iters, Some(MaxIters::Auto));
+    assert_eq!(options.buddhabrot, Buddhabrot::default());
+    let args: Vec<String> = ["--fractal", "nebulabrot", "--green-iters", "20-200"]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+    let options = parse_args(&args).unwrap();
+    assert_eq!(options.fractal, Fractal::Nebulabrot);
+    assert_eq!(options.channels, [(0, 5000), (20, 200), (0, 50)]);
+    assert!(parse_args(&["--red-iters".to_string(), "9-3".to_string()]).is_err());
+    let args: Vec<String> = ["--fractal", "newton", "--poly", "z^4 - 1"]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+    let options = parse_args(&args).unwrap();
+    assert_eq!(options.fractal, Fractal::Newton);
+    assert_eq!(options.newton.polynomial.degree(), 4);
+    assert!(parse_args(&["--poly".to_string(), "3".to_string()]).is_err());
+    assert!(parse_args(&["--poly".to_string(), "z^".to_string()]).is_err());
+    let args: Vec<String> = ["--formula", "sin(z) * c", "--bailout", "50"]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+    let options = parse_args(&args).unwrap();
+    assert_eq!(options.fractal, Fractal::Formula);
+    assert_eq!(options.formula.expression, "sin(z)*c".parse().unwrap());
+    assert_eq!(options.formula.bailout, 50.0);
+    assert!(parse_args(&["--formula".to_string(), "z*w".to_string()]).is_err());
+    assert!(parse_args(&["--bailout".to_string(), "-1".to_string()]).is_err());
+    let args: Vec<String> = ["--scene", "dive.json", "--frames", "12"]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+    let options = parse_args(&args).unwrap();
+    assert_eq!(options.scene.as_deref(), Some("dive.json"));
+    assert_eq!(options.frames, 12);
+    assert!(parse_args(&["--frames".to_string(), "0".to_string()]).is_err());
+    assert!(parse_args(&["--exp-map".to_string()]).unwrap().exp_map);
+    let args: Vec<String> = ["--temporal", "0.25"]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+    assert_eq!(parse_args(&args).unwrap().temporal, Some(0.25));
+    for weight in ["1.5", "-0.1", "NaN", "x"] {
+        assert!(parse_args(&["--temporal".to_string(), weight.to_string()]).is_err());
+    }
+    let args: Vec<String> = ["--format", "gif", "--fps", "12"]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+    let options = parse_args(&args).unwrap();
+    assert_eq!((options.format, options.fps), (Format::Gif, 12));
+    let args: Vec<String> = ["--video", "dive.mp4", "--codec", "libx265"]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+    let options = parse_args(&args).unwrap();
+    assert_eq!(options.video.as_deref(), Some("dive.mp4"));
+    assert_eq!(options.codec, "libx265");
+    assert_eq!(parse_args(&[]).unwrap().codec, "libx264");
+    assert!(parse_args(&["--format".to_string(), "mp4".to_string()]).is_err());
+    assert!(parse_args(&["--fps".to_string(), "0".to_string()]).is_err());
+    let args: Vec<String> = [
+        "--list-palettes",
+        "--preview-dir",
+        "previews",
+        "--out",
+        "a.png",
+    ]
+    .iter()
+    .map(|s| s.to_string())
+    .collect();
+    let options = parse_args(&args).unwrap();
+    assert!(options.list_palettes);
+    assert_eq!(options.preview_dir.as_deref(), Some("previews"));
+    assert_eq!(options.output.as_deref(), Some("a.png"));
+    let args: Vec<String> = ["--top", "3", "--candidates", "50", "--seed", "9"]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+    let options = parse_args(&args).unwrap();
+    assert_eq!((options.top, options.candidates, options.seed), (3, 50, 9));
+    assert!(parse_args(&["--top".to_string(), "0".to_string()]).is_err());
+    let args: Vec<String> = ["--tolerance", "2", "--diff", "d.png"]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+    let options = parse_args(&args).unwrap();
+    assert_eq!(
+        (options.tolerance, options.diff.as_deref()),
+        (2, Some("d.png"))
+    );
+    assert!(parse_args(&["--tolerance".to_string(), "256".to_string()]).is_err());
+    let args = ["--palette".to_string(), "fire.ggr".to_string()];
+    assert_eq!(
+        parse_args(&args).unwrap().palette.as_deref(),
+        Some("fire.ggr")
+    );
+    let args: Vec<String> = ["--palette-offset", "-0.25", "--palette-period", "log:0.5"]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+    let cycle = parse_args(&args).unwrap().palette_cycle;
+    assert_eq!(cycle.offset, -0.25);
+    assert_eq!((cycle.period.length, cycle.period.log), (0.5, true));
+    assert!(parse_args(&["--palette-offset".to_string(), "inf".to_string()]).is_err());
+    assert!(parse_args(&["--palette-period".to_string(), "0".to_string()]).is_err());
+    let args = ["--palette-blend".to_string(), "linear".to_string()];
+    assert_eq!(
+        parse_args(&args).unwrap().palette_cycle.blend,
+        palette::Blend::Linear
+    );
+    assert!(
+        parse_args(&["--result".to_string(), "json".to_string()])
+            .unwrap()
+            .result_json
+    );
+    assert!(parse_args(&["--result".to_string(), "xml".to_string()]).is_err());
+    let args: Vec<String> = ["--manifest", "--reproduce", "a.manifest.json"]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+    let options = parse_args(&args).unwrap();
+    assert!(options.manifest);
+    assert_eq!(options.reproduce.as_deref(), Some("a.manifest.json"));
+    let args = ["--force-kernel".to_string(), "avx2".to_string()];
+    assert_eq!(parse_args(&args).unwrap().kernel, Some(Kernel::Avx2));
+    assert!(parse_args(&["--force-kernel".to_string(), "sse".to_string()]).is_err());
+    let args: Vec<String> = ["--max-error", "6", "--strategy", "adaptive"]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+    assert_eq!(
+        parse_args(&args).unwrap().strategy,
+        Strategy::Adaptive { max_error: 6 }
+    );
+    assert!(parse_args(&args[..2]).is_err());
+    let args = ["--fit".to_string(), "crop".to_string()];
+    assert_eq!(parse_args(&args).unwrap().fit, Some(Fit::Crop));
+    assert!(parse_args(&["--fit".to_string(), "fill".to_string()]).is_err());
+    let args: Vec<String> = ["--watermark-corner", "top-left", "--watermark-text", "me"]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+    assert_eq!(
+        parse_args(&args).unwrap().watermark,
+        Some(Watermark {
+            corner: watermark::Corner::TopLeft,
+            text: Some("me".to_string()),
+            logo: None,
+        })
+    );
+    assert_eq!(
+        parse_args(&["--watermark".to_string()]).unwrap().watermark,
+        Some(Watermark::default())
+    );
+    assert!(parse_args(&["--watermark-corner".to_string(), "middle".to_string()]).is_err());
+    let args = ["--dither".to_string(), "ordered".to_string()];
+    assert_eq!(parse_args(&args).unwrap().dither, Some(Dither::Ordered));
+    assert!(parse_args(&["--dither".to_string(), "noise".to_string()]).is_err());
+    let args = ["--transfer".to_string(), "x^0.5".to_string()];
+    assert!(matches!(
+        parse_args(&args).unwrap().palette_cycle.transfer,
+        palette::Transfer::Custom(_)
+    ));
+    assert!(parse_args(&["--transfer".to_string(), "tan".to_string()]).is_err());
+    let args = ["--color-script".to_string(), "c.rhai".to_string()];
+    assert_eq!(parse_args(&args).is_ok(), cfg!(feature = "scripting"));
+    let args = ["--bookmarks".to_string(), "b.txt".to_string()];
+    assert_eq!(
+        parse_args(&args).unwrap().bookmarks.as_deref(),
+        Some("b.txt")
+    );
+    let args = ["--project".to_string(), "p.mandel".to_string()];
+    assert_eq!(
+        parse_args(&args).unwrap().project.as_deref(),
+        Some("p.mandel")
+    );
+    let args = ["--workers".to_string(), "3".to_string()];
+    assert_eq!(parse_args(&args).unwrap().workers, Some(3));
+    assert!(parse_args(&["--workers".to_string(), "0".to_string()]).is_err());
+    let sandboxed = parse_args(&["--sandbox".to_string()]).map(|options| options.sandbox);
+    assert_eq!(
+        sandboxed.is_ok_and(|sandbox| sandbox),
+        cfg!(all(feature = "sandbox", target_os = "linux"))
+    );
+    let args = ["--threads".to_string(), "2".to_string()];
+    assert_eq!(parse_args(&args).unwrap().threads, Some(2));
+    assert_eq!(parse_args(&[]).unwrap().disk_cache_size, 256);
+    let args = ["--disk-cache-size".to_string(), "0".to_string()];
+    assert_eq!(parse_args(&args).unwrap().disk_cache_size, 0);
+    assert!(parse_args(&["--disk-cache-size".to_string(), "-1".to_string()]).is_err());
+    let huge = (u64::MAX >> 10).to_string();
+    assert!(parse_args(&["--disk-cache-size".to_string(), huge]).is_err());
+    assert!(parse_args(&["--threads".to_string(), "0".to_string()]).is_err());
+
+    let args = ["--locale".to_string(), "fr_FR".to_string()];
+    assert_eq!(parse_args(&args).unwrap().decimal, DecimalSeparator::Comma);
+    let args = ["--decimal-comma".to_string()];
+    assert_eq!(parse_args(&args).unwrap().decimal, DecimalSeparator::Comma);
+
+    assert!(parse_args(&["--report".to_string()]).is_err());
+    let args = ["--stats-json".to_string(), "stats.json".to_string()];
+    assert_eq!(
+        parse_args(&args).unwrap().stats_json.as_deref(),
+        Some("stats.json")
+    );
+    assert!(parse_args(&["--stats-json".to_string()]).is_err());
+    let args = ["--cost-map".to_string(), "cost.png".to_string()];
+    assert_eq!(
+        parse_args(&args).unwrap().cost_map.as_deref(),
+        Some("cost.png")
+    );
+    assert!(parse_args(&["--cost-map".to_string()]).is_err());
+    let args = ["--variance".to_string(), "noise.png".to_string()];
+    assert_eq!(
+        parse_args(&args).unwrap().variance.as_deref(),
+        Some("noise.png")
+    );
+    assert!(parse_args(&["--variance".to_string()]).is_err());
+    let args = ["--render-scale".to_string(), "2.5".to_string()];
+    assert_eq!(parse_args(&args).unwrap().render_scale, 2.5);
+    for scale in ["0.5", "9", "NaN"] {
+        let args = ["--render-scale".to_string(), scale.to_string()];
+        assert!(parse_args(&args).is_err(), "{}", scale);
+    }
+    let args = ["--timeout".to_string(), "1.5".to_string()];
+    assert_eq!(
+        parse_args(&args).unwrap().timeout,
+        Some(Duration::from_millis(1500))
+    );
+    assert!(parse_args(&["--timeout".to_string(), "0".to_string()]).is_err());
+    let args = ["--lease".to_string(), "90".to_string()];
+    assert_eq!(
+        parse_args(&args).unwrap().lease,
+        Some(Duration::from_secs(90))
+    );
+    assert!(parse_args(&["--lease".to_string(), "inf".to_string()]).is_err());
+    let args = ["--fill".to_string(), "#ff00ff".to_string()];
+    assert_eq!(parse_args(&args).unwrap().fill, [255, 0, 255]);
+    assert!(parse_args(&["--fill".to_string(), "magenta".to_string()]).is_err());
+    assert!(parse_args(&["--bogus".to_string()]).is_err());
 }
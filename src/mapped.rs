@@ -0,0 +1,123 @@
+//! Pixel buffers in memory-mapped files, for `--low-memory`: the operating
+//! system pages the rows in and out as they are rendered and encoded, so an
+//! image larger than memory can be rendered as long as it fits on disk.
+
+use std::{
+    fs::{self, OpenOptions},
+    mem::ManuallyDrop,
+    ops::{Deref, DerefMut},
+};
+
+use memmap2::MmapMut;
+
+use crate::{platform, Error, Result};
+
+/// A buffer of bytes kept in a file, which is removed when the buffer is
+/// dropped.
+pub struct MappedBuffer {
+    map: ManuallyDrop<MmapMut>,
+    len: usize,
+    filename: String,
+}
+
+impl MappedBuffer {
+    /// A buffer of `len` zeros, kept in the new file `filename`.
+    pub fn new(filename: &str, len: usize) -> Result<MappedBuffer> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(platform::path(filename))
+            .map_err(Error::io(filename))?;
+        let mapped = file
+            // mapping nothing is an error
+            .set_len(len.max(1) as u64)
+            // the file was just created here, and nothing else should
+            // change it while it is mapped
+            .and_then(|()| unsafe { MmapMut::map_mut(&file) });
+        match mapped {
+            Ok(map) => Ok(MappedBuffer {
+                map: ManuallyDrop::new(map),
+                len,
+                filename: filename.to_string(),
+            }),
+            Err(e) => {
+                drop(file);
+                let _ = fs::remove_file(platform::path(filename));
+                Err(Error::io(filename)(e))
+            }
+        }
+    }
+}
+
+impl Deref for MappedBuffer {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.map[..self.len]
+    }
+}
+
+impl DerefMut for MappedBuffer {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        &mut self.map[..self.len]
+    }
+}
+
+impl Drop for MappedBuffer {
+    fn drop(&mut self) {
+        // unmapped first, or Windows won't remove the file; the map isn't
+        // used again
+        unsafe { ManuallyDrop::drop(&mut self.map) };
+        let _ = fs::remove_file(platform::path(&self.filename));
+    }
+}
+
+/// The pixels of a render, in memory or, with `--low-memory`, in a file.
+pub enum Pixels {
+    Owned(Vec<u8>),
+    Mapped(MappedBuffer),
+}
+
+impl Deref for Pixels {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            Pixels::Owned(pixels) => pixels,
+            Pixels::Mapped(pixels) => pixels,
+        }
+    }
+}
+
+impl DerefMut for Pixels {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        match self {
+            Pixels::Owned(pixels) => pixels,
+            Pixels::Mapped(pixels) => pixels,
+        }
+    }
+}
+
+#[test]
+fn test_mapped_buffer() {
+    let dir = std::env::temp_dir().join(format!("mandelbrot-mapped-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let file = dir.join("pixels");
+    let filename = file.to_str().unwrap();
+
+    let mut pixels = Pixels::Mapped(MappedBuffer::new(filename, 5).unwrap());
+    assert_eq!(&pixels[..], [0; 5]);
+    pixels[1..3].copy_from_slice(&[7, 8]);
+    assert_eq!(&pixels[..], [0, 7, 8, 0, 0]);
+    assert_eq!(fs::read(&file).unwrap(), [0, 7, 8, 0, 0]);
+    // the file is new every time
+    assert!(MappedBuffer::new(filename, 5).is_err());
+    drop(pixels);
+    assert!(!file.exists());
+
+    let empty = MappedBuffer::new(filename, 0).unwrap();
+    assert!(empty.is_empty());
+    drop(empty);
+    fs::remove_dir_all(&dir).unwrap();
+}
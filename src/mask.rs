@@ -0,0 +1,105 @@
+//! Separating the set from the rest of an image, for `--transparent` and
+//! `--mask`: renders with a transparent set, or a black and white mask of
+//! it, composite over other pictures in image editors.
+
+/// The image `image`, gray or RGB, whose escape times are `escapes`, one
+/// gray level a pixel, as RGBA, with the set transparent and the points that
+/// escape opaque.
+pub fn transparent(escapes: &[u8], image: &[u8]) -> Vec<u8> {
+    let channels = image.len() / escapes.len().max(1);
+    let mut rgba = Vec::with_capacity(4 * escapes.len());
+    for (&escape, color) in escapes.iter().zip(image.chunks(channels.max(1))) {
+        let alpha = if escape == 0 { 0 } else { 255 };
+        match *color {
+            [gray] => rgba.extend([gray, gray, gray, alpha]),
+            [r, g, b] => rgba.extend([r, g, b, alpha]),
+            _ => rgba.extend([0, 0, 0, alpha]),
+        }
+    }
+    rgba
+}
+
+#[test]
+fn test_transparent() {
+    let escapes = [0, 10];
+    assert_eq!(transparent(&escapes, &[1, 2]), [1, 1, 1, 0, 2, 2, 2, 255]);
+    assert_eq!(
+        transparent(&escapes, &[1, 2, 3, 4, 5, 6]),
+        [1, 2, 3, 0, 4, 5, 6, 255]
+    );
+}
+
+/// The mask of the set in an image whose escape times are `escapes` and
+/// whose dimensions are given by `bounds`, one bit a pixel, set for the
+/// points of the set: row by row, each row starting on a new byte, the
+/// leftmost pixel in the highest bit.
+pub fn bits(escapes: &[u8], bounds: (usize, usize)) -> Vec<u8> {
+    let mut bits = Vec::with_capacity(bounds.0.div_ceil(8) * bounds.1);
+    for row in escapes.chunks(bounds.0.max(1)) {
+        for pixels in row.chunks(8) {
+            let byte = pixels
+                .iter()
+                .enumerate()
+                .filter(|(_, &escape)| escape == 0)
+                .fold(0, |byte, (i, _)| byte | 0x80 >> i);
+            bits.push(byte);
+        }
+    }
+    bits
+}
+
+#[test]
+fn test_bits() {
+    let escapes = [0, 1, 0, 0, 1, 1, 1, 1, 0, 1, 1, 0];
+    assert_eq!(bits(&escapes, (12, 1)), [0b1011_0000, 0b1001_0000]);
+    assert_eq!(
+        bits(&escapes, (3, 4)),
+        [0b1010_0000, 0b1000_0000, 0b0010_0000, 0b0010_0000]
+    );
+}
+
+/// Encode the mask of the set in an image whose escape times are `escapes`
+/// as a 1-bit PNG image, white for the set and black elsewhere, embedding
+/// the view like `write_image` does.
+#[cfg(feature = "png")]
+pub fn png(
+    escapes: &[u8],
+    bounds: (usize, usize),
+    upper_left: num::Complex<f64>,
+    lower_right: num::Complex<f64>,
+) -> crate::Result<Vec<u8>> {
+    use image::{png::PNGEncoder, ColorType};
+
+    use crate::{share, Error};
+
+    let mut png = Vec::new();
+    PNGEncoder::new(&mut png)
+        .encode(
+            &bits(escapes, bounds),
+            bounds.0 as u32,
+            bounds.1 as u32,
+            ColorType::Gray(1),
+        )
+        .map_err(Error::Encode)?;
+    let link = share::share_link(bounds, upper_left, lower_right);
+    share::add_png_text(&mut png, share::PNG_KEYWORD, &link);
+    Ok(png)
+}
+
+#[cfg(feature = "png")]
+#[test]
+fn test_png() {
+    use image::GenericImage;
+
+    let escapes = [0, 1, 0, 0, 1, 1, 1, 1, 0, 1, 1, 0];
+    let view = (num::Complex::new(-2.0, 1.0), num::Complex::new(1.0, -1.0));
+    let png = png(&escapes, (3, 4), view.0, view.1).unwrap();
+    let image = image::load_from_memory(&png).unwrap();
+    // the bit depth, in the header
+    assert_eq!(png[24], 1);
+    assert_eq!(image.dimensions(), (3, 4));
+    let white: Vec<bool> = image.to_luma().pixels().map(|p| p[0] == 255).collect();
+    let set: Vec<bool> = escapes.iter().map(|&escape| escape == 0).collect();
+    assert_eq!(white, set);
+    assert!(crate::share::png_text(&png, crate::share::PNG_KEYWORD).is_some());
+}
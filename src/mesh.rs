@@ -0,0 +1,403 @@
+//! Height fields of the set as 3D meshes, for the `export-mesh` subcommand:
+//! the smooth escape time of each point is its height, and the mesh is a
+//! closed solid, with walls and a bottom, ready to be 3D printed or imported
+//! into Blender.
+
+use std::{str::FromStr, thread};
+
+use num::Complex;
+
+use crate::render;
+
+/// How far from 0 an orbit has to get for its smooth escape time, which is
+/// only accurate far out.
+const SMOOTH_BAILOUT: f64 = 256.0;
+
+/// How thick the solid is under its lowest point, as a fraction of its
+/// width, so that it holds together when printed.
+const BASE: f64 = 0.02;
+
+/// The default height of the highest point above the base, as a fraction
+/// of the width of the mesh.
+pub const DEFAULT_HEIGHT_SCALE: f64 = 0.2;
+
+/// The file formats meshes are written in.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    /// Wavefront OBJ, as text.
+    Obj,
+    /// Binary STL, the format of slicers for 3D printers.
+    Stl,
+    /// Binary little endian PLY.
+    Ply,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Format, String> {
+        match s {
+            "obj" => Ok(Format::Obj),
+            "stl" => Ok(Format::Stl),
+            "ply" => Ok(Format::Ply),
+            _ => Err(format!(
+                "unknown mesh format {}, expected obj, stl or ply",
+                s
+            )),
+        }
+    }
+}
+
+impl Format {
+    /// The format a file is in, going by the extension of its name.
+    pub fn from_filename(filename: &str) -> Option<Format> {
+        let (_, extension) = filename.rsplit_once('.')?;
+        extension.to_ascii_lowercase().parse().ok()
+    }
+}
+
+#[test]
+fn test_parse_format() {
+    assert_eq!("stl".parse(), Ok(Format::Stl));
+    assert!("fbx".parse::<Format>().is_err());
+    assert_eq!(Format::from_filename("set.OBJ"), Some(Format::Obj));
+    assert_eq!(Format::from_filename("dir.ply/set"), None);
+    assert_eq!(Format::from_filename("set"), None);
+}
+
+/// The smooth escape time of `c`, if it escapes within `limit` iterations:
+/// the escape time less how far past the bailout the orbit went, so that
+/// neighboring points don't jump from one step to the next.
+fn smooth_escape(c: Complex<f64>, limit: usize) -> Option<f64> {
+    let mut z = Complex::new(0.0, 0.0);
+    for i in 0..limit {
+        let norm_sqr = z.norm_sqr();
+        if norm_sqr > SMOOTH_BAILOUT * SMOOTH_BAILOUT {
+            let smooth = i as f64 + 1.0 - (norm_sqr.ln() / 2.0).log2();
+            return Some(smooth.max(0.0));
+        }
+        z = z * z + c;
+    }
+    None
+}
+
+#[test]
+fn test_smooth_escape() {
+    assert_eq!(smooth_escape(Complex::new(-1.0, 0.0), 1000), None);
+    // smooth across the points where the escape time steps
+    let steps: Vec<f64> = (0..50)
+        .map(|i| smooth_escape(Complex::new(0.3 + 0.002 * i as f64, 0.0), 1000).unwrap())
+        .collect();
+    assert!(steps.windows(2).all(|pair| pair[0] >= pair[1]));
+    assert!(steps.windows(2).all(|pair| pair[0] - pair[1] < 1.0));
+}
+
+/// How many points across and down a mesh has that takes every
+/// `decimate`th pixel of an image whose dimensions are given by `bounds`,
+/// starting with the first.
+pub fn grid(bounds: (usize, usize), decimate: usize) -> (usize, usize) {
+    let decimate = decimate.max(1);
+    let points = |pixels: usize| pixels.saturating_sub(1) / decimate + (pixels > 0) as usize;
+    (points(bounds.0), points(bounds.1))
+}
+
+/// The heights of the points `grid` gives, row by row, computed on
+/// `threads` threads and iterating at most `limit` times. They go from 0,
+/// for the points that escape first, to 1, for the set, on a logarithmic
+/// scale of smooth escape times, which otherwise shoot up near the set.
+pub fn heights(
+    bounds: (usize, usize),
+    upper_left: Complex<f64>,
+    lower_right: Complex<f64>,
+    limit: usize,
+    decimate: usize,
+    threads: usize,
+) -> Vec<f64> {
+    let decimate = decimate.max(1);
+    let columns = grid(bounds, decimate).0;
+    let mut escapes = vec![None; columns * grid(bounds, decimate).1];
+    if escapes.is_empty() {
+        return Vec::new();
+    }
+    let rows = (escapes.len() / columns).div_ceil(threads.max(1));
+    thread::scope(|scope| {
+        for (band, escapes) in escapes.chunks_mut(rows * columns).enumerate() {
+            scope.spawn(move || {
+                for (i, escape) in escapes.iter_mut().enumerate() {
+                    let point = (i % columns, band * rows + i / columns);
+                    let pixel = (decimate * point.0, decimate * point.1);
+                    let c = render::pixel_to_point(bounds, pixel, upper_left, lower_right);
+                    *escape = smooth_escape(c, limit);
+                }
+            });
+        }
+    });
+
+    let (lowest, highest) = escapes
+        .iter()
+        .flatten()
+        .fold((f64::INFINITY, 0.0f64), |(low, high), &escape| {
+            (low.min(escape), high.max(escape))
+        });
+    let range = (1.0 + highest - lowest).ln();
+    escapes
+        .iter()
+        .map(|escape| match escape {
+            Some(escape) if range > 0.0 => (1.0 + escape - lowest).ln() / range,
+            Some(_) => 0.0,
+            None => 1.0,
+        })
+        .collect()
+}
+
+#[test]
+fn test_heights() {
+    let bounds = (21, 11);
+    let (upper_left, lower_right) = (Complex::new(-2.0, 1.0), Complex::new(1.0, -1.0));
+    assert_eq!(grid(bounds, 1), (21, 11));
+    assert_eq!(grid(bounds, 5), (5, 3));
+    assert_eq!(grid((0, 3), 5), (0, 1));
+    let heights = heights(bounds, upper_left, lower_right, 500, 5, 2);
+    assert_eq!(heights.len(), 5 * 3);
+    assert!(heights.iter().all(|height| (0.0..=1.0).contains(height)));
+    assert!(heights.contains(&0.0));
+    // the middle of the row in the middle, -0.5, is in the set
+    assert_eq!(heights[5 + 2], 1.0);
+}
+
+/// A mesh of triangles, each listing the indices of its vertices
+/// counterclockwise seen from outside.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Mesh {
+    pub vertices: Vec<[f32; 3]>,
+    pub triangles: Vec<[u32; 3]>,
+}
+
+/// The indices of the points around the edge of a grid whose dimensions
+/// are `grid`, going around it from the upper left corner, clockwise seen
+/// from above.
+fn rim(grid: (usize, usize)) -> Vec<usize> {
+    let (columns, rows) = grid;
+    let index = |x: usize, y: usize| y * columns + x;
+    let mut rim: Vec<usize> = (0..columns - 1).map(|x| index(x, 0)).collect();
+    rim.extend((0..rows - 1).map(|y| index(columns - 1, y)));
+    rim.extend((1..columns).rev().map(|x| index(x, rows - 1)));
+    rim.extend((1..rows).rev().map(|y| index(0, y)));
+    rim
+}
+
+/// The solid whose top is the height field `heights`, of points laid out in
+/// `grid`, at least 2 by 2, row by row, as `heights` gives them. The points
+/// are 1 apart, the first row at the back, and the highest point is
+/// `height_scale` times the width above the base.
+pub fn solid(heights: &[f64], grid: (usize, usize), height_scale: f64) -> Mesh {
+    let (columns, rows) = grid;
+    assert!(columns >= 2 && rows >= 2 && heights.len() == columns * rows);
+    let width = (columns - 1) as f64;
+    let mut vertices: Vec<[f32; 3]> = heights
+        .iter()
+        .enumerate()
+        .map(|(i, height)| {
+            let (x, y) = (i % columns, rows - 1 - i / columns);
+            let z = width * (BASE + height * height_scale);
+            [x as f32, y as f32, z as f32]
+        })
+        .collect();
+    let mut triangles = Vec::new();
+    for y in 0..rows - 1 {
+        for x in 0..columns - 1 {
+            let corner = (y * columns + x) as u32;
+            let (right, below) = (corner + 1, corner + columns as u32);
+            triangles.push([corner, below, right]);
+            triangles.push([right, below, below + 1]);
+        }
+    }
+
+    // walls down from the rim, and a bottom fanning out from its middle
+    let rim = rim(grid);
+    let bottom = vertices.len();
+    let floor: Vec<[f32; 3]> = rim
+        .iter()
+        .map(|&i| [vertices[i][0], vertices[i][1], 0.0])
+        .collect();
+    vertices.extend(floor);
+    let middle = vertices.len() as u32;
+    vertices.push([width as f32 / 2.0, (rows - 1) as f32 / 2.0, 0.0]);
+    for k in 0..rim.len() {
+        let next = (k + 1) % rim.len();
+        let (top, top_next) = (rim[k] as u32, rim[next] as u32);
+        let (low, low_next) = ((bottom + k) as u32, (bottom + next) as u32);
+        triangles.push([top, top_next, low]);
+        triangles.push([top_next, low_next, low]);
+        triangles.push([middle, low, low_next]);
+    }
+    Mesh {
+        vertices,
+        triangles,
+    }
+}
+
+#[test]
+fn test_solid() {
+    use std::collections::HashMap;
+
+    let heights = [0.0, 0.5, 1.0, 0.25, 0.75, 1.0];
+    let mesh = solid(&heights, (3, 2), 1.0);
+    assert_eq!(mesh.vertices.len(), 6 + 6 + 1);
+    assert_eq!(mesh.triangles.len(), 2 * 2 + 3 * 6);
+    // the first row at the back, the highest point twice the width up
+    assert_eq!(mesh.vertices[0], [0.0, 1.0, 0.04]);
+    assert_eq!(mesh.vertices[5], [2.0, 0.0, 2.04]);
+
+    // closed: every edge is gone along once each way
+    let mut edges = HashMap::new();
+    for triangle in &mesh.triangles {
+        for k in 0..3 {
+            *edges
+                .entry((triangle[k], triangle[(k + 1) % 3]))
+                .or_insert(0) += 1;
+        }
+    }
+    for (&(a, b), &count) in &edges {
+        assert_eq!(count, 1);
+        assert_eq!(edges.get(&(b, a)), Some(&1));
+    }
+
+    // facing out: the volume is positive, and what it should be
+    let volume: f64 = mesh
+        .triangles
+        .iter()
+        .map(|triangle| {
+            let [a, b, c] = triangle.map(|i| mesh.vertices[i as usize].map(f64::from));
+            let cross = [
+                b[1] * c[2] - b[2] * c[1],
+                b[2] * c[0] - b[0] * c[2],
+                b[0] * c[1] - b[1] * c[0],
+            ];
+            (a[0] * cross[0] + a[1] * cross[1] + a[2] * cross[2]) / 6.0
+        })
+        .sum();
+    let mut expected = 0.0;
+    for [a, b, c] in &mesh.triangles[..4] {
+        let z: f64 = [a, b, c]
+            .iter()
+            .map(|&&i| mesh.vertices[i as usize][2] as f64)
+            .sum();
+        // each triangle of the top covers half a square
+        expected += z / 3.0 / 2.0;
+    }
+    assert!((volume - expected).abs() < 1e-5, "{} {}", volume, expected);
+}
+
+impl Mesh {
+    /// The mesh in `format`.
+    pub fn encode(&self, format: Format) -> Vec<u8> {
+        match format {
+            Format::Obj => self.to_obj(),
+            Format::Stl => self.to_stl(),
+            Format::Ply => self.to_ply(),
+        }
+    }
+
+    /// The mesh as a Wavefront OBJ file.
+    pub fn to_obj(&self) -> Vec<u8> {
+        let mut obj = String::from("# the Mandelbrot set as a height field\n");
+        for [x, y, z] in &self.vertices {
+            obj.push_str(&format!("v {} {} {}\n", x, y, z));
+        }
+        for [a, b, c] in &self.triangles {
+            obj.push_str(&format!("f {} {} {}\n", a + 1, b + 1, c + 1));
+        }
+        obj.into_bytes()
+    }
+
+    /// The mesh as a binary STL file: an 80 byte header, the number of
+    /// triangles, then each with its normal and its corners.
+    pub fn to_stl(&self) -> Vec<u8> {
+        let mut stl = vec![0; 80];
+        let header = b"the Mandelbrot set as a height field";
+        stl[..header.len()].copy_from_slice(header);
+        stl.extend((self.triangles.len() as u32).to_le_bytes());
+        for triangle in &self.triangles {
+            let [a, b, c] = triangle.map(|i| self.vertices[i as usize]);
+            let (u, v) = (
+                [0, 1, 2].map(|k| b[k] - a[k]),
+                [0, 1, 2].map(|k| c[k] - a[k]),
+            );
+            let normal = [
+                u[1] * v[2] - u[2] * v[1],
+                u[2] * v[0] - u[0] * v[2],
+                u[0] * v[1] - u[1] * v[0],
+            ];
+            let length = normal
+                .iter()
+                .map(|n| n * n)
+                .sum::<f32>()
+                .sqrt()
+                .max(f32::MIN_POSITIVE);
+            for value in normal
+                .map(|n| n / length)
+                .iter()
+                .chain(a.iter().chain(&b).chain(&c))
+            {
+                stl.extend(value.to_le_bytes());
+            }
+            // no attributes
+            stl.extend([0, 0]);
+        }
+        stl
+    }
+
+    /// The mesh as a binary little endian PLY file.
+    pub fn to_ply(&self) -> Vec<u8> {
+        let mut ply = format!(
+            "ply\nformat binary_little_endian 1.0\n\
+             comment the Mandelbrot set as a height field\n\
+             element vertex {}\nproperty float x\nproperty float y\nproperty float z\n\
+             element face {}\nproperty list uchar int vertex_indices\nend_header\n",
+            self.vertices.len(),
+            self.triangles.len()
+        )
+        .into_bytes();
+        for value in self.vertices.iter().flatten() {
+            ply.extend(value.to_le_bytes());
+        }
+        for triangle in &self.triangles {
+            ply.push(3);
+            for &index in triangle {
+                ply.extend((index as i32).to_le_bytes());
+            }
+        }
+        ply
+    }
+}
+
+#[test]
+fn test_encode() {
+    let mesh = Mesh {
+        vertices: vec![[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.5]],
+        triangles: vec![[0, 1, 2]],
+    };
+    let obj = String::from_utf8(mesh.encode(Format::Obj)).unwrap();
+    assert!(obj.ends_with("v 0 1 0.5\nf 1 2 3\n"));
+
+    let stl = mesh.encode(Format::Stl);
+    assert_eq!(stl.len(), 80 + 4 + 50);
+    assert_eq!(&stl[80..84], &1u32.to_le_bytes());
+    let number = |at: usize| f32::from_le_bytes(stl[at..at + 4].try_into().unwrap());
+    // the normal, up and back
+    let normal = [number(84), number(88), number(92)];
+    assert!((normal[1] + 0.4472136).abs() < 1e-6 && (normal[2] - 0.8944272).abs() < 1e-6);
+    assert_eq!(number(84 + 12 + 4 * 8), 0.5);
+
+    let ply = mesh.encode(Format::Ply);
+    let header_end = b"end_header\n";
+    let at = ply
+        .windows(header_end.len())
+        .position(|window| window == header_end)
+        .unwrap()
+        + header_end.len();
+    assert!(ply.starts_with(b"ply\nformat binary_little_endian 1.0\n"));
+    assert_eq!(ply.len() - at, 3 * 12 + 1 + 3 * 4);
+    assert_eq!(&ply[ply.len() - 13..ply.len() - 8], [3, 0, 0, 0, 0]);
+}
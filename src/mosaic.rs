@@ -0,0 +1,265 @@
+//! Mosaics: the image rebuilt from pieces chosen by how dark each cell of it
+//! is, either glyphs, for typographic art, or small tile images.
+//!
+//! Glyphs are given from the lightest to the darkest, like ` .:-=+*#%@`,
+//! and make a text, or an SVG poster of it. Tiles are sorted by their own
+//! brightness, so they can be given in any order, and make an image.
+
+use std::{fmt::Write as _, str::FromStr};
+
+#[cfg(feature = "png")]
+use crate::{platform, Error, Result};
+
+/// Where the pieces of a mosaic come from.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Source {
+    /// Characters, from the lightest to the darkest.
+    Glyphs(Vec<char>),
+    /// The names of image files, all the same size.
+    Tiles(Vec<String>),
+}
+
+/// The parameters of a mosaic.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Mosaic {
+    pub source: Source,
+    /// The width and height, in pixels of the render, each piece stands for;
+    /// by default 8x16 for glyphs, which are about twice as tall as wide, and
+    /// the size of the tiles for tiles.
+    pub cell: Option<(usize, usize)>,
+}
+
+impl Default for Mosaic {
+    fn default() -> Mosaic {
+        Mosaic {
+            source: Source::Glyphs(" .:-=+*#%@".chars().collect()),
+            cell: None,
+        }
+    }
+}
+
+impl FromStr for Source {
+    type Err = String;
+
+    /// Parse the argument of `--mosaic`: image files separated by commas if
+    /// it ends in `.png`, glyphs otherwise.
+    fn from_str(s: &str) -> std::result::Result<Source, String> {
+        if s.to_ascii_lowercase().ends_with(".png") {
+            Ok(Source::Tiles(s.split(',').map(str::to_string).collect()))
+        } else if s.chars().count() >= 2 {
+            Ok(Source::Glyphs(s.chars().collect()))
+        } else {
+            Err(format!("a mosaic needs at least two glyphs, not {:?}", s))
+        }
+    }
+}
+
+#[test]
+fn test_parse_source() {
+    assert_eq!(" .#".parse(), Ok(Source::Glyphs(vec![' ', '.', '#'])));
+    assert_eq!(
+        "a.png,b.PNG".parse(),
+        Ok(Source::Tiles(vec![
+            "a.png".to_string(),
+            "b.PNG".to_string()
+        ]))
+    );
+    assert!("#".parse::<Source>().is_err());
+}
+
+/// The average darkness, between 0 (white) and 1 (black), of every cell
+/// `cell` pixels in size of `pixels`, whose dimensions are given by `bounds`,
+/// with the number of columns and rows of cells. Cells cut by the right or
+/// the bottom edge are left out.
+pub fn cells(
+    pixels: &[u8],
+    bounds: (usize, usize),
+    cell: (usize, usize),
+) -> (Vec<f64>, (usize, usize)) {
+    assert!(pixels.len() == bounds.0 * bounds.1);
+    let cell = (cell.0.max(1), cell.1.max(1));
+    let grid = (bounds.0 / cell.0, bounds.1 / cell.1);
+    let mut darkness = Vec::with_capacity(grid.0 * grid.1);
+    for row in 0..grid.1 {
+        for column in 0..grid.0 {
+            let mut sum = 0;
+            for y in row * cell.1..(row + 1) * cell.1 {
+                let start = y * bounds.0 + column * cell.0;
+                sum += pixels[start..start + cell.0]
+                    .iter()
+                    .map(|&p| p as usize)
+                    .sum::<usize>();
+            }
+            darkness.push(1.0 - sum as f64 / (255 * cell.0 * cell.1) as f64);
+        }
+    }
+    (darkness, grid)
+}
+
+#[test]
+fn test_cells() {
+    let pixels = [0, 0, 255, 255, 255, 0, 255, 255, 9];
+    let (darkness, grid) = cells(&pixels, (3, 3), (2, 1));
+    assert_eq!(grid, (1, 3));
+    assert_eq!(darkness, [1.0, 0.0, 0.0]);
+}
+
+/// Which of `count` pieces, sorted from the lightest to the darkest, stands
+/// for a cell of darkness `darkness`.
+fn choose(darkness: f64, count: usize) -> usize {
+    ((darkness * count as f64) as usize).min(count - 1)
+}
+
+/// The mosaic of `pixels`, whose dimensions are given by `bounds`, in
+/// `glyphs`, as lines of text.
+pub fn to_text(
+    pixels: &[u8],
+    bounds: (usize, usize),
+    cell: (usize, usize),
+    glyphs: &[char],
+) -> String {
+    let (darkness, grid) = cells(pixels, bounds, cell);
+    let mut text = String::new();
+    for row in darkness.chunks(grid.0.max(1)).take(grid.1) {
+        text.extend(row.iter().map(|&d| glyphs[choose(d, glyphs.len())]));
+        text.push('\n');
+    }
+    text
+}
+
+#[test]
+fn test_to_text() {
+    let pixels = [0, 128, 255, 0];
+    assert_eq!(
+        to_text(&pixels, (2, 2), (1, 1), &[' ', '+', '#']),
+        "#+\n #\n"
+    );
+    assert_eq!(to_text(&pixels, (2, 2), (2, 2), &[' ', '#']), "#\n");
+}
+
+/// The mosaic of `pixels` in `glyphs` as an SVG poster: black monospaced
+/// text on white, each glyph as large as the cell it stands for.
+pub fn to_svg(
+    pixels: &[u8],
+    bounds: (usize, usize),
+    cell: (usize, usize),
+    glyphs: &[char],
+) -> String {
+    let text = to_text(pixels, bounds, cell, glyphs);
+    let lines: Vec<&str> = text.lines().collect();
+    let (width, height) = (bounds.0 / cell.0.max(1) * cell.0, lines.len() * cell.1);
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" viewBox=\"0 0 {} {}\">\n\
+         <rect width=\"100%\" height=\"100%\" fill=\"white\"/>\n\
+         <g font-family=\"monospace\" font-size=\"{}\" xml:space=\"preserve\">\n",
+        width, height, width, height, cell.1
+    );
+    for (row, line) in lines.iter().enumerate() {
+        let escaped = line
+            .replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;");
+        let _ = writeln!(
+            svg,
+            "<text x=\"0\" y=\"{}\" textLength=\"{}\">{}</text>",
+            (row + 1) * cell.1,
+            width,
+            escaped
+        );
+    }
+    svg.push_str("</g>\n</svg>\n");
+    svg
+}
+
+#[test]
+fn test_to_svg() {
+    let svg = to_svg(&[0, 255], (2, 1), (1, 1), &[' ', '<']);
+    assert!(svg.contains("<text x=\"0\" y=\"1\" textLength=\"2\">&lt; </text>"));
+    assert!(svg.ends_with("</svg>\n"));
+}
+
+/// A grayscale tile image.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Tile {
+    pub size: (usize, usize),
+    pub pixels: Vec<u8>,
+}
+
+impl Tile {
+    /// The average gray level of the tile.
+    pub fn brightness(&self) -> usize {
+        self.pixels.iter().map(|&p| p as usize).sum::<usize>() / self.pixels.len().max(1)
+    }
+}
+
+/// Read the tiles in the image files `files`, sorted from the lightest to
+/// the darkest. They must all be the same size.
+#[cfg(feature = "png")]
+pub fn read_tiles(files: &[String]) -> Result<Vec<Tile>> {
+    let mut tiles = Vec::new();
+    for file in files {
+        let bytes = std::fs::read(platform::path(file)).map_err(Error::io(file))?;
+        let image = image::load_from_memory(&bytes)
+            .map_err(|e| {
+                Error::io(file)(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    e.to_string(),
+                ))
+            })?
+            .to_luma();
+        let size = image.dimensions();
+        tiles.push(Tile {
+            size: (size.0 as usize, size.1 as usize),
+            pixels: image.into_raw(),
+        });
+    }
+    if let Some(first) = tiles.first() {
+        if let Some((file, tile)) = files.iter().zip(&tiles).find(|(_, t)| t.size != first.size) {
+            return Err(Error::Usage(format!(
+                "mosaic tiles must all be the same size: {} is {}x{}, {} is {}x{}",
+                files[0], first.size.0, first.size.1, file, tile.size.0, tile.size.1
+            )));
+        }
+    }
+    tiles.sort_by_key(|tile| std::cmp::Reverse(tile.brightness()));
+    Ok(tiles)
+}
+
+/// The mosaic of `pixels`, whose dimensions are given by `bounds`, in
+/// `tiles`, sorted from the lightest to the darkest, with the dimensions of
+/// the image it makes.
+pub fn to_image(
+    pixels: &[u8],
+    bounds: (usize, usize),
+    cell: (usize, usize),
+    tiles: &[Tile],
+) -> (Vec<u8>, (usize, usize)) {
+    let (darkness, grid) = cells(pixels, bounds, cell);
+    let tile_size = tiles[0].size;
+    let size = (grid.0 * tile_size.0, grid.1 * tile_size.1);
+    let mut image = vec![0; size.0 * size.1];
+    for (i, &d) in darkness.iter().enumerate() {
+        let tile = &tiles[choose(d, tiles.len())];
+        let (left, top) = (i % grid.0 * tile_size.0, i / grid.0 * tile_size.1);
+        for (y, line) in tile.pixels.chunks(tile_size.0).enumerate() {
+            let start = (top + y) * size.0 + left;
+            image[start..start + tile_size.0].copy_from_slice(line);
+        }
+    }
+    (image, size)
+}
+
+#[test]
+fn test_to_image() {
+    let light = Tile {
+        size: (2, 1),
+        pixels: vec![255, 200],
+    };
+    let dark = Tile {
+        size: (2, 1),
+        pixels: vec![0, 50],
+    };
+    let (image, size) = to_image(&[0, 255, 255, 0], (2, 2), (1, 1), &[light, dark]);
+    assert_eq!(size, (4, 2));
+    assert_eq!(image, [0, 50, 255, 200, 255, 200, 0, 50]);
+}
@@ -0,0 +1,229 @@
+//! Newton fractals: every point of the plane is the start of Newton's
+//! method for finding a root of a polynomial, and is colored by the root it
+//! ends up at, darker the longer it takes to get there.
+//!
+//! Points don't escape here, they converge, so this has a classification of
+//! its own instead of `render::escape_time`, and writes color pixels, three
+//! bytes each.
+
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Mutex,
+};
+
+use num::Complex;
+
+use crate::{
+    poly::Polynomial,
+    render::pixel_to_point,
+    render::Schedule,
+    usage::{self, Usage},
+};
+
+/// How close, as a squared distance, two iterations have to be for Newton's
+/// method to be considered converged.
+const CONVERGED: f64 = 1e-18;
+
+/// The parameters of a Newton fractal.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Newton {
+    pub polynomial: Polynomial,
+    /// Points that haven't converged after this many iterations are black.
+    pub max_iters: usize,
+}
+
+impl Default for Newton {
+    fn default() -> Newton {
+        Newton {
+            polynomial: "z^3 - 1".parse().unwrap(),
+            max_iters: 64,
+        }
+    }
+}
+
+/// Run Newton's method for `polynomial` from `z`, at most `max_iters` times.
+///
+/// Returns the index in `roots` of the root it converges to, and the number
+/// of iterations that took, or `None` if it didn't converge to any of them.
+pub fn converge(
+    polynomial: &Polynomial,
+    roots: &[Complex<f64>],
+    mut z: Complex<f64>,
+    max_iters: usize,
+) -> Option<(usize, usize)> {
+    for i in 0..max_iters {
+        let (p, dp) = polynomial.eval(z);
+        if dp.norm_sqr() == 0.0 {
+            usage::count_iterations(i);
+            return None;
+        }
+        let step = p / dp;
+        z -= step;
+        if step.norm_sqr() < CONVERGED {
+            usage::count_iterations(i + 1);
+            let (root, distance) = roots
+                .iter()
+                .map(|root| (root - z).norm_sqr())
+                .enumerate()
+                .min_by(|a, b| a.1.total_cmp(&b.1))?;
+            return (distance < 1e-6).then_some((root, i + 1));
+        }
+    }
+    usage::count_iterations(max_iters);
+    None
+}
+
+#[test]
+fn test_converge() {
+    let polynomial: Polynomial = "z^3 - 1".parse().unwrap();
+    let roots = polynomial.roots();
+    let one = roots.iter().position(|r| (r - 1.0).norm() < 1e-9).unwrap();
+
+    let (root, iterations) = converge(&polynomial, &roots, Complex::new(2.0, 0.0), 64).unwrap();
+    assert_eq!(root, one);
+    assert!(iterations < 10);
+    // the derivative vanishes at 0
+    assert_eq!(
+        converge(&polynomial, &roots, Complex::new(0.0, 0.0), 64),
+        None
+    );
+    assert_eq!(
+        converge(&polynomial, &roots, Complex::new(2.0, 0.0), 1),
+        None
+    );
+}
+
+/// The color of a point converging to root `root` of `roots` roots in
+/// `iterations` of at most `max_iters`: a hue for every root, darker the
+/// more iterations it took.
+pub fn color(root: usize, roots: usize, iterations: usize, max_iters: usize) -> [u8; 3] {
+    let hue = root as f64 / roots.max(1) as f64 * 6.0;
+    let shade = 1.0 - (iterations as f64 / max_iters.max(1) as f64).sqrt();
+    // a fully saturated color of that hue
+    let x = 1.0 - (hue % 2.0 - 1.0).abs();
+    let (r, g, b) = match hue as usize {
+        0 => (1.0, x, 0.0),
+        1 => (x, 1.0, 0.0),
+        2 => (0.0, 1.0, x),
+        3 => (0.0, x, 1.0),
+        4 => (x, 0.0, 1.0),
+        _ => (1.0, 0.0, x),
+    };
+    [r, g, b].map(|c: f64| (c * shade * 255.0).round() as u8)
+}
+
+#[test]
+fn test_color() {
+    assert_eq!(color(0, 3, 0, 64), [255, 0, 0]);
+    assert_eq!(color(1, 3, 0, 64), [0, 255, 0]);
+    assert_eq!(color(2, 3, 0, 64), [0, 0, 255]);
+    assert_eq!(color(0, 3, 16, 64), [128, 0, 0]);
+    assert_eq!(color(0, 3, 64, 64), [0, 0, 0]);
+}
+
+/// Render the Newton fractal of `newton` over the rectangle between
+/// `upper_left` and `lower_right` into `pixels`, three bytes (red, green
+/// and blue) per pixel, on the threads of `schedule`.
+///
+/// The threads take `schedule.chunk_rows` rows at a time, as in
+/// `render::render_parallel`, counting them in `schedule.progress` and
+/// stopping when `schedule.stopped()` says so.
+pub fn render(
+    pixels: &mut [u8],
+    bounds: (usize, usize),
+    upper_left: Complex<f64>,
+    lower_right: Complex<f64>,
+    newton: &Newton,
+    schedule: &Schedule,
+) {
+    assert!(pixels.len() == 3 * bounds.0 * bounds.1);
+    if pixels.is_empty() {
+        return;
+    }
+
+    let roots = newton.polynomial.roots();
+    let next_row = AtomicUsize::new(0);
+    let output = Mutex::new(pixels);
+    let (roots, next_row, output) = (&roots, &next_row, &output);
+    crossbeam::scope(|spawner| {
+        for thread in 0..schedule.threads.max(1) {
+            spawner.spawn(move |_| {
+                let mut chunk = Vec::new();
+                loop {
+                    if schedule.stopped() {
+                        break;
+                    }
+                    let top = next_row.fetch_add(schedule.chunk_rows, Ordering::Relaxed);
+                    if top >= bounds.1 {
+                        break;
+                    }
+                    let height = schedule.chunk_rows.min(bounds.1 - top);
+
+                    chunk.clear();
+                    Usage::measure(schedule.usage, thread, height, || {
+                        for row in top..top + height {
+                            for column in 0..bounds.0 {
+                                let z =
+                                    pixel_to_point(bounds, (column, row), upper_left, lower_right);
+                                chunk.extend(
+                                    match converge(&newton.polynomial, roots, z, newton.max_iters) {
+                                        Some((root, iterations)) => {
+                                            color(root, roots.len(), iterations, newton.max_iters)
+                                        }
+                                        None => [0; 3],
+                                    },
+                                );
+                            }
+                        }
+                    });
+
+                    let mut output = output.lock().unwrap();
+                    output[3 * top * bounds.0..3 * (top + height) * bounds.0]
+                        .copy_from_slice(&chunk);
+                    if let Some(progress) = schedule.progress {
+                        progress.add_rows(height);
+                    }
+                }
+            });
+        }
+    })
+    .unwrap();
+}
+
+#[test]
+fn test_render() {
+    let bounds = (30, 20);
+    let (upper_left, lower_right) = (Complex::new(-1.5, 1.0), Complex::new(1.5, -1.0));
+    let newton = Newton::default();
+    let mut pixels = vec![7; 3 * bounds.0 * bounds.1];
+    render(
+        &mut pixels,
+        bounds,
+        upper_left,
+        lower_right,
+        &newton,
+        &Schedule {
+            chunk_rows: 3,
+            ..Schedule::new(2)
+        },
+    );
+
+    // three basins, and the point near 1 has the color of that root
+    let mut colors: Vec<[u8; 3]> = pixels
+        .chunks(3)
+        .map(|p| [p[0], p[1], p[2]].map(|c| (c > 0) as u8))
+        .collect();
+    colors.sort();
+    colors.dedup();
+    assert!(colors.len() >= 3, "{:?}", colors);
+    let near_one = 3 * (10 * bounds.0 + 25);
+    let roots = newton.polynomial.roots();
+    let one = roots.iter().position(|r| (r - 1.0).norm() < 1e-9).unwrap();
+    let expected = color(one, 3, 1, newton.max_iters);
+    let pixel = &pixels[near_one..near_one + 3];
+    assert!(
+        (0..3).all(|i| (pixel[i] > 0) == (expected[i] > 0)),
+        "{:?}",
+        pixel
+    );
+}
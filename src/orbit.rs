@@ -0,0 +1,237 @@
+//! The orbit of a single point, for the `orbit` subcommand: every `z` it
+//! goes through, with the derivative `dz/dc` along it, and how it ends,
+//! escaping, caught in a cycle, or neither within the limit. It is what to
+//! look at when a formula, the periodicity check or a perturbation
+//! reference misbehaves at a point.
+//!
+//! The orbit is iterated as `render` iterates it: it escapes at the same
+//! step as `escape_time` says, and cycles are found the way
+//! `kernel::escape_time_periodic` finds them, then measured.
+
+use std::{f64::consts::PI, fmt};
+
+use num::Complex;
+
+use crate::{art::Drawing, kernel::PERIODICITY_EPSILON};
+
+/// A point of an orbit.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Step {
+    pub z: Complex<f64>,
+    /// The derivative of `z` with respect to `c`.
+    pub dz: Complex<f64>,
+}
+
+/// How an orbit ends.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Fate {
+    /// It left the circle of the bailout radius at this step.
+    Escapes(usize),
+    /// It came back to where it was `period` steps before, at step `at`.
+    Cycles { period: usize, at: usize },
+    /// Neither, within the limit: the point is taken to be in the set.
+    Stays,
+}
+
+/// The orbit of `c`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Orbit {
+    pub c: Complex<f64>,
+    /// From `z = 0`, up to where the orbit ends.
+    pub steps: Vec<Step>,
+    pub fate: Fate,
+}
+
+/// Follow the orbit of `c` for at most `limit` steps, until it leaves the
+/// circle of radius `bailout` or cycles.
+pub fn trace(c: Complex<f64>, limit: usize, bailout: f64) -> Orbit {
+    let mut z = Complex::new(0.0, 0.0);
+    let mut dz = Complex::new(0.0, 0.0);
+    let mut steps = Vec::new();
+    // saved at every power of two steps, as for the renderer
+    let mut saved = z;
+    let mut since_saved = 0;
+    let mut next_save = 1;
+    for i in 0..limit {
+        steps.push(Step { z, dz });
+        if z.norm_sqr() > bailout * bailout {
+            return Orbit {
+                c,
+                steps,
+                fate: Fate::Escapes(i),
+            };
+        }
+        dz = 2.0 * z * dz + 1.0;
+        z = z * z + c;
+
+        if (z - saved).norm_sqr() < PERIODICITY_EPSILON {
+            // a multiple of the period: find the period itself
+            let period = (1..=since_saved + 1)
+                .find(|&p| (z - steps[i + 1 - p].z).norm_sqr() < PERIODICITY_EPSILON)
+                .unwrap_or(since_saved + 1);
+            steps.push(Step { z, dz });
+            return Orbit {
+                c,
+                steps,
+                fate: Fate::Cycles { period, at: i + 1 },
+            };
+        }
+        since_saved += 1;
+        if since_saved == next_save {
+            since_saved = 0;
+            next_save *= 2;
+            saved = z;
+        }
+    }
+    Orbit {
+        c,
+        steps,
+        fate: Fate::Stays,
+    }
+}
+
+#[test]
+fn test_trace() {
+    use crate::kernel;
+
+    // the escape step is the renderer's
+    for c in [(0.3, 0.6), (-0.75, 0.1), (-2.5, 1.0)] {
+        let orbit = trace(Complex::new(c.0, c.1), 1000, 2.0);
+        let Fate::Escapes(step) = orbit.fate else {
+            panic!("{:?} doesn't escape", c);
+        };
+        assert_eq!(Some(step), kernel::escape_time(c, 1000, 2.0));
+        assert_eq!(orbit.steps.len(), step + 1);
+        assert!(orbit.steps[step].z.norm() > 2.0);
+    }
+
+    // 0 is a fixed point, -1 goes 0, -1, 0, ..., and the center of the
+    // period 3 bulb on the real axis comes back every third step
+    let fate = |re, im| trace(Complex::new(re, im), 1000, 2.0).fate;
+    assert_eq!(fate(0.0, 0.0), Fate::Cycles { period: 1, at: 1 });
+    assert!(matches!(fate(-1.0, 0.0), Fate::Cycles { period: 2, .. }));
+    assert!(matches!(
+        fate(-1.754877666246693, 0.0),
+        Fate::Cycles { period: 3, .. }
+    ));
+    // an attracting cycle that takes a while to settle
+    assert!(matches!(fate(-0.12, 0.74), Fate::Cycles { period: 3, .. }));
+    assert_eq!(trace(Complex::new(-0.12, 0.74), 5, 2.0).fate, Fate::Stays);
+
+    // the derivative follows the orbit: dz/dc is 1, then 2c + 1, ...
+    let c = Complex::new(0.1, 0.2);
+    let steps = trace(c, 3, 2.0).steps;
+    assert_eq!(steps[1].dz, Complex::new(1.0, 0.0));
+    assert_eq!(steps[2].dz, 2.0 * c + 1.0);
+}
+
+impl fmt::Display for Orbit {
+    /// Write the orbit for people to read: a line a step, and how it ends.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "orbit of {},{}", self.c.re, self.c.im)?;
+        writeln!(
+            f,
+            "{:>6}  {:<24} {:<24} {:<12} {:<24} dz/dc im",
+            "step", "re", "im", "|z|", "dz/dc re"
+        )?;
+        for (i, step) in self.steps.iter().enumerate() {
+            writeln!(
+                f,
+                "{:>6}  {:<24} {:<24} {:<12.6e} {:<24} {}",
+                i,
+                step.z.re,
+                step.z.im,
+                step.z.norm(),
+                step.dz.re,
+                step.dz.im
+            )?;
+        }
+        match self.fate {
+            Fate::Escapes(step) => writeln!(f, "escapes at step {}", step),
+            Fate::Cycles { period, at } => writeln!(
+                f,
+                "in the set: cycles with period {}, found at step {}",
+                period, at
+            ),
+            Fate::Stays => writeln!(
+                f,
+                "in the set as far as {} steps show, without a cycle",
+                self.steps.len()
+            ),
+        }
+    }
+}
+
+#[test]
+fn test_display_orbit() {
+    let text = trace(Complex::new(-1.0, 0.0), 100, 2.0).to_string();
+    let lines: Vec<&str> = text.lines().collect();
+    assert_eq!(lines[0], "orbit of -1,0");
+    assert!(lines[1].contains("|z|") && lines[1].contains("dz/dc"));
+    assert!(lines[3].trim_start().starts_with("1  -1 "), "{}", lines[3]);
+    assert_eq!(
+        lines.last().unwrap(),
+        &"in the set: cycles with period 2, found at step 3"
+    );
+    let text = trace(Complex::new(1.0, 1.0), 100, 2.0).to_string();
+    assert!(text.ends_with("escapes at step 2\n"), "{}", text);
+}
+
+/// The corners of the plot `Orbit::drawing` draws into an image `bounds` in
+/// size, whose smaller side spans the circle of radius `bailout` with a
+/// margin.
+pub fn frame(bounds: (usize, usize), bailout: f64) -> (Complex<f64>, Complex<f64>) {
+    let pixel = 2.2 * bailout / bounds.0.min(bounds.1).max(1) as f64;
+    let (half_width, half_height) = (bounds.0 as f64 * pixel / 2.0, bounds.1 as f64 * pixel / 2.0);
+    (
+        Complex::new(-half_width, half_height),
+        Complex::new(half_width, -half_height),
+    )
+}
+
+impl Orbit {
+    /// The orbit drawn on the plane: a dot at every step, joined in order,
+    /// over the axes and the circle of radius `bailout`, into an image
+    /// `bounds` in size framed as `frame` says.
+    pub fn drawing(&self, bounds: (usize, usize), bailout: f64) -> Drawing {
+        let (upper_left, lower_right) = frame(bounds, bailout);
+        let scale = bounds.0 as f64 / (lower_right.re - upper_left.re);
+        let to_pixel = |z: Complex<f64>| {
+            (
+                (z.re - upper_left.re) * scale,
+                (upper_left.im - z.im) * scale,
+            )
+        };
+        let (width, height) = (bounds.0 as f64, bounds.1 as f64);
+        let origin = to_pixel(Complex::new(0.0, 0.0));
+        let mut strokes = vec![
+            ((0.0, origin.1), (width, origin.1)),
+            ((origin.0, 0.0), (origin.0, height)),
+        ];
+        let circle = |i: usize| to_pixel(Complex::from_polar(bailout, i as f64 * PI / 32.0));
+        strokes.extend((0..64).map(|i| (circle(i), circle(i + 1))));
+        let dots: Vec<(f64, f64)> = self.steps.iter().map(|step| to_pixel(step.z)).collect();
+        strokes.extend(dots.windows(2).map(|pair| (pair[0], pair[1])));
+        Drawing {
+            bounds,
+            dot_radius: 2.0,
+            dots,
+            strokes,
+        }
+    }
+}
+
+#[test]
+fn test_drawing() {
+    let (upper_left, lower_right) = frame((400, 200), 2.0);
+    assert_eq!(upper_left, Complex::new(-4.4, 2.2));
+    assert_eq!(lower_right, Complex::new(4.4, -2.2));
+
+    let drawing = trace(Complex::new(-1.0, 0.0), 100, 2.0).drawing((100, 100), 2.0);
+    // 0, -1, 0, -1: four dots and three strokes, after the axes and the
+    // circle
+    assert_eq!(drawing.dots.len(), 4);
+    assert_eq!(drawing.strokes.len(), 2 + 64 + 3);
+    assert_eq!(drawing.dots[0], (50.0, 50.0));
+    assert!((drawing.dots[1].0 - (50.0 - 100.0 / 4.4)).abs() < 1e-9);
+}
@@ -0,0 +1,233 @@
+//! Deciding which file an image is written to, so that batch runs behave
+//! predictably when files already exist or directories don't, and the sinks
+//! every kind of output goes through once it is encoded.
+
+use std::{fs, io::Write, path::Path, str::FromStr};
+
+use crate::{platform, Error, Result};
+
+/// What to do when the output file already exists.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OnExists {
+    /// Replace it.
+    Overwrite,
+    /// Don't render at all.
+    Skip,
+    /// Write to the first free name with `-1`, `-2`, ... before the extension.
+    Increment,
+}
+
+impl FromStr for OnExists {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<OnExists, String> {
+        match s {
+            "overwrite" => Ok(OnExists::Overwrite),
+            "skip" => Ok(OnExists::Skip),
+            "increment" => Ok(OnExists::Increment),
+            _ => Err(format!("unknown --on-exists policy {}", s)),
+        }
+    }
+}
+
+#[test]
+fn test_parse_on_exists() {
+    assert_eq!("skip".parse(), Ok(OnExists::Skip));
+    assert_eq!("increment".parse(), Ok(OnExists::Increment));
+    assert!("rename".parse::<OnExists>().is_err());
+}
+
+/// Return whether `filename` isn't an ordinary file: `-` for stdout, or a
+/// device like `NUL` on Windows or `/dev/null` elsewhere.
+pub fn is_special(filename: &str) -> bool {
+    let device = if cfg!(windows) {
+        platform::windows_device(filename).is_some()
+    } else {
+        filename.starts_with("/dev/")
+    };
+    filename == "-" || device
+}
+
+/// Insert `suffix` into `filename` before its extension, if it has one.
+pub fn with_suffix(filename: &str, suffix: &str) -> String {
+    match filename.rsplit_once('.') {
+        Some((stem, extension)) if !extension.contains(['/', '\\']) => {
+            format!("{}{}.{}", stem, suffix, extension)
+        }
+        _ => format!("{}{}", filename, suffix),
+    }
+}
+
+#[test]
+fn test_with_suffix() {
+    assert_eq!(with_suffix("out/mandel.png", "-2"), "out/mandel-2.png");
+    assert_eq!(with_suffix("./mandel", "-2"), "./mandel-2");
+    assert_eq!(with_suffix("out.d/mandel", "-2"), "out.d/mandel-2");
+}
+
+/// Apply `on_exists` to the output file `filename`: return the file to write
+/// the image to, or `None` if the render should be skipped.
+pub fn resolve(filename: &str, on_exists: OnExists) -> Option<String> {
+    let path = platform::path(filename);
+    if is_special(filename) || !path.exists() {
+        return Some(filename.to_string());
+    }
+    match on_exists {
+        OnExists::Overwrite => Some(filename.to_string()),
+        OnExists::Skip => None,
+        OnExists::Increment => (1..)
+            .map(|n| with_suffix(filename, &format!("-{}", n)))
+            .find(|candidate| !platform::path(candidate).exists()),
+    }
+}
+
+#[test]
+fn test_resolve() {
+    let dir = std::env::temp_dir().join(format!("mandelbrot-resolve-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let file = dir.join("out.png");
+    let file = file.to_str().unwrap();
+    let _ = fs::remove_file(file);
+
+    assert_eq!(resolve(file, OnExists::Skip).as_deref(), Some(file));
+    fs::write(file, b"").unwrap();
+    assert_eq!(resolve(file, OnExists::Overwrite).as_deref(), Some(file));
+    assert_eq!(resolve(file, OnExists::Skip), None);
+    let first = with_suffix(file, "-1");
+    assert_eq!(resolve(file, OnExists::Increment), Some(first.clone()));
+    fs::write(&first, b"").unwrap();
+    assert_eq!(
+        resolve(file, OnExists::Increment),
+        Some(with_suffix(file, "-2"))
+    );
+    assert_eq!(resolve("-", OnExists::Skip).as_deref(), Some("-"));
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+/// Somewhere encoded output goes: a file, stdout, a buffer in memory or an
+/// HTTP response. Encoders produce bytes and hand them to a sink, so images,
+/// documents and reports all leave the program the same way.
+///
+/// There's no sink for object stores like S3: they need an HTTP client and
+/// TLS, which this crate doesn't have. Write to stdout and pipe it into
+/// their command line tools instead.
+pub trait OutputSink {
+    /// Write all of `contents` to the sink.
+    fn write_all(&mut self, contents: &[u8]) -> Result<()>;
+}
+
+/// The file named by `filename`, created when it is first written to; what
+/// is written after goes after it.
+pub struct FileSink {
+    filename: String,
+    file: Option<fs::File>,
+}
+
+impl FileSink {
+    pub fn new(filename: &str) -> FileSink {
+        FileSink {
+            filename: filename.to_string(),
+            file: None,
+        }
+    }
+}
+
+impl OutputSink for FileSink {
+    fn write_all(&mut self, contents: &[u8]) -> Result<()> {
+        let file = match &mut self.file {
+            Some(file) => file,
+            None => {
+                let file = platform::create(&self.filename).map_err(Error::io(&self.filename))?;
+                self.file.insert(file)
+            }
+        };
+        file.write_all(contents).map_err(Error::io(&self.filename))
+    }
+}
+
+/// The standard output.
+pub struct StdoutSink;
+
+impl OutputSink for StdoutSink {
+    fn write_all(&mut self, contents: &[u8]) -> Result<()> {
+        let mut stdout = std::io::stdout().lock();
+        stdout
+            .write_all(contents)
+            .and_then(|()| stdout.flush())
+            .map_err(Error::io("stdout"))
+    }
+}
+
+/// A buffer in memory, which collects everything written to it.
+impl OutputSink for Vec<u8> {
+    fn write_all(&mut self, contents: &[u8]) -> Result<()> {
+        self.extend_from_slice(contents);
+        Ok(())
+    }
+}
+
+/// The sink for `filename` on the command line: stdout if it is `-`, and
+/// the file of that name otherwise.
+pub fn sink(filename: &str) -> Box<dyn OutputSink> {
+    if filename == "-" {
+        Box::new(StdoutSink)
+    } else {
+        Box::new(FileSink::new(filename))
+    }
+}
+
+/// Write `contents` to the file named `filename`, or to stdout if
+/// `filename` is `-`.
+pub fn write(filename: &str, contents: &[u8]) -> Result<()> {
+    sink(filename).write_all(contents)
+}
+
+#[test]
+fn test_sinks() {
+    let mut buffer = Vec::new();
+    OutputSink::write_all(&mut buffer, b"ab").unwrap();
+    OutputSink::write_all(&mut buffer, b"cd").unwrap();
+    assert_eq!(buffer, b"abcd");
+
+    let dir = std::env::temp_dir().join(format!("mandelbrot-sink-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let file = dir.join("out.txt");
+    write(file.to_str().unwrap(), b"hello").unwrap();
+    assert_eq!(fs::read(&file).unwrap(), b"hello");
+    // a sink writes one file, however many writes it takes
+    let mut file_sink = sink(file.to_str().unwrap());
+    file_sink.write_all(b"good").unwrap();
+    file_sink.write_all(b"bye").unwrap();
+    drop(file_sink);
+    assert_eq!(fs::read(&file).unwrap(), b"goodbye");
+
+    let missing = dir.join("no").join("such").join("dir.txt");
+    let error = sink(missing.to_str().unwrap()).write_all(b"").unwrap_err();
+    assert!(matches!(error, Error::Io { .. }), "{:?}", error);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+/// Create the directories the file named `filename` goes in, if they don't
+/// exist yet.
+pub fn create_parent_dirs(filename: &str) -> Result<()> {
+    if is_special(filename) {
+        return Ok(());
+    }
+    match platform::path(filename).parent() {
+        Some(parent) if parent != Path::new("") => {
+            fs::create_dir_all(parent).map_err(Error::io(&parent.to_string_lossy()))
+        }
+        _ => Ok(()),
+    }
+}
+
+#[test]
+fn test_create_parent_dirs() {
+    let dir = std::env::temp_dir().join(format!("mandelbrot-mkdir-{}", std::process::id()));
+    let file = dir.join("a").join("b").join("out.png");
+    create_parent_dirs(file.to_str().unwrap()).unwrap();
+    assert!(dir.join("a").join("b").is_dir());
+    create_parent_dirs("out.png").unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+}
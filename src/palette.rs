@@ -0,0 +1,993 @@
+//! Palettes: gradients of colors the gray levels of the image are mapped to.
+//!
+//! A palette is a list of stops, colors at positions between 0 (the gray
+//! level of points in the set, black) and 1 (points escaping at once, white),
+//! and a way to interpolate between them. It is turned into a lookup table
+//! once, before coloring any pixel.
+//!
+//! Palettes are read from files, going by their extension:
+//!
+//! - `.json`: `{"interpolation": "linear", "stops": [[0, "#000000"],
+//!   [0.5, "#ff8000"], [1, [255, 255, 255]]]}`, colors given as `#rrggbb` or
+//!   as three levels;
+//! - `.toml`: the same keys, `interpolation = "smooth"` and
+//!   `stops = [[0, "#000000"], [1, "#ffffff"]]`;
+//! - `.ugr`: UltraFractal gradients, of which the first one is used;
+//! - `.ggr`: GIMP gradients, whose segments are approximated as linear, in
+//!   RGB.
+//!
+//! A few palettes are built in, and can be given by name instead of a file:
+//! see `PRESETS`.
+
+use std::{fs, str::FromStr};
+
+use serde::Deserialize;
+
+use num::Complex;
+
+use crate::{formula::Expression, platform, toml, Error, Result};
+
+/// How colors are interpolated between two stops.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Interpolation {
+    /// The color of the stop before, up to the next stop: flat bands.
+    Constant,
+    /// Straight from one color to the next.
+    #[default]
+    Linear,
+    /// Easing in and out of every stop, so there are no visible kinks.
+    Smooth,
+}
+
+impl FromStr for Interpolation {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Interpolation, String> {
+        match s {
+            "constant" => Ok(Interpolation::Constant),
+            "linear" => Ok(Interpolation::Linear),
+            "smooth" => Ok(Interpolation::Smooth),
+            _ => Err(format!("unknown interpolation {}", s)),
+        }
+    }
+}
+
+/// How colors are mixed between two stops.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Blend {
+    /// The levels of the colors as they are stored, gamma encoded in sRGB:
+    /// the mixes come out darker than the light of the two colors would.
+    #[default]
+    Srgb,
+    /// The light of the colors, decoding sRGB first and encoding the mix
+    /// back, which keeps gradients between dark and bright colors even.
+    Linear,
+}
+
+impl FromStr for Blend {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Blend, String> {
+        match s {
+            "srgb" => Ok(Blend::Srgb),
+            "linear" => Ok(Blend::Linear),
+            _ => Err(format!("unknown palette blend {}", s)),
+        }
+    }
+}
+
+/// The light of the sRGB level `level`, from 0 to 1.
+fn srgb_to_linear(level: u8) -> f64 {
+    let v = level as f64 / 255.0;
+    if v <= 0.04045 {
+        v / 12.92
+    } else {
+        ((v + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// The sRGB level of light `v`, from 0 to 1, unrounded.
+fn linear_to_srgb(v: f64) -> f64 {
+    let v = if v <= 0.0031308 {
+        v * 12.92
+    } else {
+        1.055 * v.powf(1.0 / 2.4) - 0.055
+    };
+    v.clamp(0.0, 1.0) * 255.0
+}
+
+#[test]
+fn test_srgb() {
+    for level in 0..=255 {
+        assert_eq!(linear_to_srgb(srgb_to_linear(level)).round() as u8, level);
+    }
+    // half the light of white is much lighter than level 128
+    assert_eq!(linear_to_srgb(0.5).round(), 188.0);
+    assert!((srgb_to_linear(128) - 0.2158).abs() < 1e-4);
+    assert_eq!("linear".parse(), Ok(Blend::Linear));
+    assert!("lab".parse::<Blend>().is_err());
+}
+
+/// A color at a position of a palette.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Stop {
+    pub position: f64,
+    pub color: [u8; 3],
+}
+
+/// A gradient of colors.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Palette {
+    /// At least one, sorted by position.
+    stops: Vec<Stop>,
+    interpolation: Interpolation,
+}
+
+impl Palette {
+    /// The palette of `stops`, in any order, whose positions must be between
+    /// 0 and 1.
+    pub fn new(
+        mut stops: Vec<Stop>,
+        interpolation: Interpolation,
+    ) -> std::result::Result<Palette, String> {
+        if stops.is_empty() {
+            return Err("a palette needs at least one stop".to_string());
+        }
+        if let Some(stop) = stops.iter().find(|s| !(0.0..=1.0).contains(&s.position)) {
+            return Err(format!(
+                "stop positions must be between 0 and 1, not {}",
+                stop.position
+            ));
+        }
+        stops.sort_by(|a, b| a.position.total_cmp(&b.position));
+        Ok(Palette {
+            stops,
+            interpolation,
+        })
+    }
+
+    /// From black to white, which leaves the image as it is.
+    pub fn gray() -> Palette {
+        Palette {
+            stops: vec![
+                Stop {
+                    position: 0.0,
+                    color: [0; 3],
+                },
+                Stop {
+                    position: 1.0,
+                    color: [255; 3],
+                },
+            ],
+            interpolation: Interpolation::Linear,
+        }
+    }
+
+    pub fn stops(&self) -> &[Stop] {
+        &self.stops
+    }
+
+    /// The color at position `t`, between 0 and 1. Before the first stop and
+    /// after the last, the color is theirs.
+    pub fn color_at(&self, t: f64) -> [u8; 3] {
+        self.blended_color_at(t, Blend::Srgb)
+    }
+
+    /// Like `color_at`, mixing the colors of the stops around `t` as `blend`
+    /// says.
+    pub fn blended_color_at(&self, t: f64, blend: Blend) -> [u8; 3] {
+        self.levels_at(t, blend).map(|level| level.round() as u8)
+    }
+
+    /// Like `blended_color_at`, without rounding the levels of the color to
+    /// whole numbers.
+    pub fn levels_at(&self, t: f64, blend: Blend) -> [f64; 3] {
+        let levels = |color: [u8; 3]| color.map(|level| level as f64);
+        let after = self.stops.partition_point(|stop| stop.position <= t);
+        if after == 0 {
+            return levels(self.stops[0].color);
+        }
+        if after == self.stops.len() {
+            return levels(self.stops[after - 1].color);
+        }
+        let (from, to) = (self.stops[after - 1], self.stops[after]);
+        let mut s = (t - from.position) / (to.position - from.position);
+        match self.interpolation {
+            Interpolation::Constant => return levels(from.color),
+            Interpolation::Linear => {}
+            Interpolation::Smooth => s = s * s * (3.0 - 2.0 * s),
+        }
+        let mut color = [0.0; 3];
+        for (i, level) in color.iter_mut().enumerate() {
+            *level = match blend {
+                Blend::Srgb => {
+                    let (a, b) = (from.color[i] as f64, to.color[i] as f64);
+                    a + (b - a) * s
+                }
+                Blend::Linear => {
+                    let (a, b) = (srgb_to_linear(from.color[i]), srgb_to_linear(to.color[i]));
+                    linear_to_srgb(a + (b - a) * s)
+                }
+            };
+        }
+        color
+    }
+
+    /// The colors at `resolution` evenly spaced positions from 0 to 1.
+    pub fn lut(&self, resolution: usize) -> Vec<[u8; 3]> {
+        self.cycled_lut(resolution, &Cycle::default())
+    }
+
+    /// Like `lut`, with the gradient laid over the positions as `cycle`
+    /// says, and its colors mixed as `cycle.blend` says. The first
+    /// position, the set, keeps the color at 0.
+    pub fn cycled_lut(&self, resolution: usize, cycle: &Cycle) -> Vec<[u8; 3]> {
+        self.cycled_levels(resolution, cycle)
+            .into_iter()
+            .map(|levels| levels.map(|level| level.round() as u8))
+            .collect()
+    }
+
+    /// Like `cycled_lut`, without rounding the levels of the colors to whole
+    /// numbers, for `dither`.
+    pub fn cycled_levels(&self, resolution: usize, cycle: &Cycle) -> Vec<[f64; 3]> {
+        let last = resolution.saturating_sub(1).max(1) as f64;
+        (0..resolution)
+            .map(|i| match i {
+                0 => self.levels_at(0.0, Blend::Srgb),
+                _ => self.levels_at(cycle.position(i as f64 / last), cycle.blend),
+            })
+            .collect()
+    }
+}
+
+#[test]
+fn test_palette() {
+    let stop = |position, color| Stop { position, color };
+    let palette = Palette::new(
+        vec![
+            stop(1.0, [255, 255, 255]),
+            stop(0.0, [0, 0, 0]),
+            stop(0.5, [200, 100, 0]),
+        ],
+        Interpolation::Linear,
+    )
+    .unwrap();
+    assert_eq!(palette.color_at(0.0), [0, 0, 0]);
+    assert_eq!(palette.color_at(0.25), [100, 50, 0]);
+    assert_eq!(palette.color_at(0.5), [200, 100, 0]);
+    assert_eq!(palette.color_at(2.0), [255, 255, 255]);
+    assert_eq!(palette.lut(3), [[0, 0, 0], [200, 100, 0], [255, 255, 255]]);
+    assert_eq!(palette.blended_color_at(0.25, Blend::Linear), [146, 71, 0]);
+
+    let constant = Palette {
+        interpolation: Interpolation::Constant,
+        ..palette.clone()
+    };
+    assert_eq!(constant.color_at(0.49), [0, 0, 0]);
+    let smooth = Palette {
+        interpolation: Interpolation::Smooth,
+        ..palette
+    };
+    assert_eq!(smooth.color_at(0.125), [31, 16, 0]);
+
+    assert_eq!(Palette::gray().lut(256)[77], [77; 3]);
+    // mixing light, the middle of a gradient from black is lighter
+    let linear = Cycle {
+        blend: Blend::Linear,
+        ..Cycle::default()
+    };
+    let lut = Palette::gray().cycled_lut(3, &linear);
+    assert_eq!(lut, [[0; 3], [188; 3], [255; 3]]);
+    assert!(Palette::new(Vec::new(), Interpolation::Linear).is_err());
+    assert!(Palette::new(vec![stop(1.5, [0; 3])], Interpolation::Linear).is_err());
+}
+
+/// How long one repeat of a gradient is, for `--palette-period`: `LENGTH`, a
+/// share of the gray levels, or `log:LENGTH`, a share of them on a
+/// logarithmic scale of escape times.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Period {
+    pub length: f64,
+    /// Whether repeats cover more escape times the longer they are, so that
+    /// the few points escaping soon and the many escaping late both get the
+    /// whole gradient.
+    pub log: bool,
+}
+
+impl Default for Period {
+    fn default() -> Period {
+        Period {
+            length: 1.0,
+            log: false,
+        }
+    }
+}
+
+impl FromStr for Period {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Period, String> {
+        let (length, log) = match s.strip_prefix("log:") {
+            Some(length) => (length, true),
+            None => (s, false),
+        };
+        match length.parse::<f64>() {
+            Ok(length) if length > 0.0 && length.is_finite() => Ok(Period { length, log }),
+            _ => Err(format!(
+                "invalid palette period {}, expected a positive LENGTH or log:LENGTH",
+                s
+            )),
+        }
+    }
+}
+
+#[test]
+fn test_parse_period() {
+    assert_eq!("1".parse(), Ok(Period::default()));
+    assert_eq!(
+        "log:0.25".parse(),
+        Ok(Period {
+            length: 0.25,
+            log: true
+        })
+    );
+    assert!("0".parse::<Period>().is_err());
+    assert!("log:".parse::<Period>().is_err());
+    assert!("-2".parse::<Period>().is_err());
+}
+
+/// How escape times are mapped to positions in the gradient, for
+/// `--transfer`: both as a share of the iteration limit, from 0 to 1. Linear
+/// leaves them as they are, which spreads the colors evenly over escape
+/// times even though most points of a view escape within a narrow range of
+/// them; the others give the early escape times more of the gradient.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum Transfer {
+    #[default]
+    Linear,
+    Sqrt,
+    Cbrt,
+    Log,
+    /// A formula in `x`, as `--formula` takes them, whose real part is the
+    /// position.
+    Custom(Expression),
+}
+
+impl FromStr for Transfer {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Transfer, String> {
+        match s {
+            "linear" => Ok(Transfer::Linear),
+            "sqrt" => Ok(Transfer::Sqrt),
+            "cbrt" => Ok(Transfer::Cbrt),
+            "log" => Ok(Transfer::Log),
+            _ => match Expression::parse_with(s, &[("x", Expression::Z)]) {
+                Ok(expression) => Ok(Transfer::Custom(expression)),
+                Err(message) => Err(format!(
+                    "unknown transfer function {}, expected linear, sqrt, cbrt, log \
+                     or a formula in x ({})",
+                    s, message
+                )),
+            },
+        }
+    }
+}
+
+impl Transfer {
+    /// The position of escape time `x`, both between 0 and 1.
+    pub fn apply(&self, x: f64) -> f64 {
+        let position = match self {
+            Transfer::Linear => x,
+            Transfer::Sqrt => x.sqrt(),
+            Transfer::Cbrt => x.cbrt(),
+            Transfer::Log => (255.0 * x).ln_1p() / 256f64.ln(),
+            Transfer::Custom(expression) => {
+                expression
+                    .eval(Complex::new(x, 0.0), Complex::new(0.0, 0.0))
+                    .re
+            }
+        };
+        // formulas can leave the gradient, or give NaN
+        if position.is_nan() {
+            0.0
+        } else {
+            position.clamp(0.0, 1.0)
+        }
+    }
+}
+
+#[test]
+fn test_transfer() {
+    let parse = |s: &str| s.parse::<Transfer>().unwrap();
+    assert_eq!(parse("linear"), Transfer::default());
+    assert_eq!(parse("linear").apply(0.25), 0.25);
+    assert_eq!(parse("sqrt").apply(0.25), 0.5);
+    assert_eq!(parse("cbrt").apply(0.125), 0.5);
+    for transfer in ["linear", "sqrt", "cbrt", "log", "x^2"] {
+        let transfer = parse(transfer);
+        assert_eq!(transfer.apply(0.0), 0.0, "{:?}", transfer);
+        assert!((transfer.apply(1.0) - 1.0).abs() < 1e-12, "{:?}", transfer);
+    }
+    // the early escape times get most of the gradient
+    assert!(parse("log").apply(16.0 / 255.0) > 0.5);
+    assert_eq!(parse("x^2").apply(0.5), 0.25);
+    assert_eq!(parse("1 - x").apply(0.25), 0.75);
+    // clamped to the gradient
+    assert_eq!(parse("2*x").apply(0.75), 1.0);
+    assert_eq!(parse("log(x - 1)").apply(0.5), 0.0);
+    assert!("tan(x)".parse::<Transfer>().is_err());
+    assert!("z"
+        .parse::<Transfer>()
+        .unwrap_err()
+        .contains("formula in x"));
+}
+
+/// How the gradient of a palette is laid over the gray levels, for
+/// `--transfer`, `--palette-offset` and `--palette-period`: escape times
+/// mapped through `transfer`, then shifted along by `offset`, in turns of
+/// the gradient, and repeated every `period`. Its colors are mixed as
+/// `blend` says, for `--palette-blend`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Cycle {
+    pub offset: f64,
+    pub period: Period,
+    pub transfer: Transfer,
+    pub blend: Blend,
+}
+
+impl Cycle {
+    /// The position in the gradient of gray level `t`, between 0 and 1.
+    pub fn position(&self, t: f64) -> f64 {
+        // gray levels go down as escape times go up
+        let t = 1.0 - self.transfer.apply(1.0 - t);
+        let t = if self.period.log {
+            // escape times from 0 to 255, spread out where they are small
+            1.0 - (256.0 - 255.0 * t).ln() / 256f64.ln()
+        } else {
+            t
+        };
+        let turns = self.offset + t / self.period.length;
+        let position = turns.rem_euclid(1.0);
+        // every repeat ends on the last color rather than the first
+        if position == 0.0 && turns > self.offset {
+            1.0
+        } else {
+            position
+        }
+    }
+}
+
+#[test]
+fn test_cycle() {
+    let lut = Palette::gray().cycled_lut(256, &Cycle::default());
+    assert_eq!(lut, Palette::gray().lut(256));
+    assert_eq!(lut[255], [255; 3]);
+
+    let twice = Cycle {
+        period: "0.5".parse().unwrap(),
+        ..Cycle::default()
+    };
+    assert_eq!(twice.position(0.25), 0.5);
+    assert_eq!(twice.position(0.5), 1.0);
+    assert_eq!(twice.position(0.75), 0.5);
+    assert_eq!(twice.position(1.0), 1.0);
+    let shifted = Cycle {
+        offset: 0.25,
+        ..twice.clone()
+    };
+    assert_eq!(shifted.position(0.25), 0.75);
+    assert_eq!(shifted.position(0.5), 0.25);
+    // the set keeps its color
+    let lut = Palette::gray().cycled_lut(256, &shifted);
+    assert_eq!(lut[0], [0; 3]);
+    assert_eq!(
+        lut[1],
+        Palette::gray().color_at(shifted.position(1.0 / 255.0))
+    );
+
+    // on a logarithmic scale, most of the gradient goes to the first few
+    // escape times, which are the lightest gray levels
+    let log = Cycle {
+        period: "log:1".parse().unwrap(),
+        ..Cycle::default()
+    };
+    assert_eq!(log.position(0.0), 0.0);
+    assert!((log.position(1.0) - 1.0).abs() < 1e-12);
+    assert!(log.position(0.5) < 0.2, "{}", log.position(0.5));
+    assert!(log.position(1.0 - 16.0 / 255.0) < 0.5);
+    // the transfer function comes first
+    let sqrt = Cycle {
+        transfer: Transfer::Sqrt,
+        ..twice
+    };
+    assert_eq!(sqrt.position(0.75), 1.0);
+    assert_eq!(sqrt.position(1.0), 1.0);
+    assert_eq!(Cycle::default().position(0.75), 0.75);
+}
+
+/// A color as palette files give it: `#rrggbb`, or three levels.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Color {
+    Hex(String),
+    Levels([u8; 3]),
+}
+
+impl Color {
+    fn levels(&self) -> std::result::Result<[u8; 3], String> {
+        match self {
+            Color::Levels(levels) => Ok(*levels),
+            Color::Hex(hex) => parse_hex(hex),
+        }
+    }
+}
+
+/// Parse a color written `#rrggbb`.
+pub fn parse_hex(hex: &str) -> std::result::Result<[u8; 3], String> {
+    let digits = hex
+        .strip_prefix('#')
+        .filter(|d| d.len() == 6 && d.is_ascii())
+        .ok_or_else(|| format!("invalid color {:?}", hex))?;
+    let mut levels = [0; 3];
+    for (i, level) in levels.iter_mut().enumerate() {
+        *level = u8::from_str_radix(&digits[2 * i..2 * i + 2], 16)
+            .map_err(|_| format!("invalid color {:?}", hex))?;
+    }
+    Ok(levels)
+}
+
+/// A palette as `.json` and `.toml` files describe it.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Description {
+    #[serde(default)]
+    interpolation: Interpolation,
+    stops: Vec<(f64, Color)>,
+}
+
+impl Description {
+    fn palette(&self) -> std::result::Result<Palette, String> {
+        let mut stops = Vec::new();
+        for (position, color) in &self.stops {
+            stops.push(Stop {
+                position: *position,
+                color: color.levels()?,
+            });
+        }
+        Palette::new(stops, self.interpolation)
+    }
+}
+
+/// Parse a palette described in JSON.
+pub fn parse_json(text: &str) -> std::result::Result<Palette, String> {
+    let description: Description = serde_json::from_str(text).map_err(|e| e.to_string())?;
+    description.palette()
+}
+
+/// Parse a palette described in TOML, see `toml::to_json`.
+pub fn parse_toml(text: &str) -> std::result::Result<Palette, String> {
+    let description: Description =
+        serde_json::from_value(toml::to_json(text)?).map_err(|e| e.to_string())?;
+    description.palette()
+}
+
+#[test]
+fn test_parse_json_and_toml() {
+    let expected = Palette::new(
+        vec![
+            Stop {
+                position: 0.0,
+                color: [0, 0, 0],
+            },
+            Stop {
+                position: 0.5,
+                color: [255, 128, 0],
+            },
+            Stop {
+                position: 1.0,
+                color: [255, 255, 255],
+            },
+        ],
+        Interpolation::Smooth,
+    );
+    assert_eq!(
+        parse_json(
+            r##"{"interpolation": "smooth",
+                 "stops": [[0, "#000000"], [0.5, "#FF8000"], [1, [255, 255, 255]]]}"##
+        ),
+        expected
+    );
+    assert_eq!(
+        parse_toml(
+            "# sunset\ninterpolation = \"smooth\"\nstops = [\n  [0, \"#000000\"], # night\n  \
+             [0.5, \"#ff8000\"],\n  [1, [255, 255, 255]],\n]\n"
+        ),
+        expected
+    );
+    assert_eq!(
+        parse_json(r##"{"stops": [[0, "#000000"]]}"##)
+            .unwrap()
+            .interpolation,
+        Interpolation::Linear
+    );
+
+    assert!(parse_json(r##"{"stops": [[0, "#00000"]]}"##).is_err());
+    assert!(parse_json(r##"{"stops": [[0, "#000000"]], "name": "x"}"##).is_err());
+    assert!(parse_json(r##"{"stops": []}"##).is_err());
+    assert!(parse_toml("stops [[0, \"#000000\"]]").is_err());
+}
+
+/// Parse the first gradient of an UltraFractal `.ugr` file, whose stops are
+/// `index=N color=C` with indices from 0 to 399 and colors as decimal BGR.
+pub fn parse_ugr(text: &str) -> std::result::Result<Palette, String> {
+    let mut stops = Vec::new();
+    let mut smooth = false;
+    for line in text.lines() {
+        let line = line.trim();
+        if line == "}" && !stops.is_empty() {
+            break;
+        }
+        let (mut index, mut color) = (None, None);
+        for field in line.split_whitespace() {
+            match field.split_once('=') {
+                Some(("index", value)) => index = value.parse::<i64>().ok(),
+                Some(("color", value)) => color = value.parse::<u32>().ok(),
+                Some(("smooth", value)) => smooth = value == "yes",
+                _ => {}
+            }
+        }
+        if let (Some(index), Some(color)) = (index, color) {
+            stops.push(Stop {
+                position: index.rem_euclid(400) as f64 / 400.0,
+                color: [color as u8, (color >> 8) as u8, (color >> 16) as u8],
+            });
+        }
+    }
+    let interpolation = if smooth {
+        Interpolation::Smooth
+    } else {
+        Interpolation::Linear
+    };
+    Palette::new(stops, interpolation)
+}
+
+#[test]
+fn test_parse_ugr() {
+    let ugr = "Fire {\ngradient:\n  title=\"Fire\" smooth=no\n  index=0 color=0\n  \
+               index=200 color=33023\n  index=399 color=16777215\nopacity:\n  smooth=no\n}\n\
+               Other {\ngradient:\n  index=0 color=255\n}\n";
+    let palette = parse_ugr(ugr).unwrap();
+    assert_eq!(palette.interpolation, Interpolation::Linear);
+    assert_eq!(
+        palette.stops(),
+        [
+            Stop {
+                position: 0.0,
+                color: [0, 0, 0]
+            },
+            Stop {
+                position: 0.5,
+                color: [255, 128, 0]
+            },
+            Stop {
+                position: 0.9975,
+                color: [255, 255, 255]
+            },
+        ]
+    );
+    assert!(parse_ugr("Empty {\n}\n").is_err());
+}
+
+/// Parse a GIMP `.ggr` gradient. Every segment becomes a stop at each end,
+/// and one at its middle point with the color halfway between, which is
+/// exact for linear segments in RGB and close enough for the others.
+pub fn parse_ggr(text: &str) -> std::result::Result<Palette, String> {
+    let mut lines = text.lines();
+    if lines.next().map(str::trim) != Some("GIMP Gradient") {
+        return Err("not a GIMP gradient".to_string());
+    }
+    let mut lines = lines.skip_while(|line| line.starts_with("Name:"));
+    let count: usize = lines
+        .next()
+        .and_then(|line| line.trim().parse().ok())
+        .ok_or("missing the number of segments")?;
+
+    let mut stops = Vec::new();
+    for _ in 0..count {
+        let line = lines.next().ok_or("missing segments")?;
+        let values: Vec<f64> = line
+            .split_whitespace()
+            .map(str::parse)
+            .collect::<std::result::Result<_, _>>()
+            .map_err(|_| format!("invalid segment {:?}", line))?;
+        if values.len() < 11 {
+            return Err(format!("invalid segment {:?}", line));
+        }
+        let level = |v: f64| (v.clamp(0.0, 1.0) * 255.0).round() as u8;
+        let left = [level(values[3]), level(values[4]), level(values[5])];
+        let right = [level(values[7]), level(values[8]), level(values[9])];
+        let middle = [0, 1, 2].map(|i| (left[i] as u16 + right[i] as u16).div_ceil(2) as u8);
+        for (position, color) in [(values[0], left), (values[1], middle), (values[2], right)] {
+            stops.push(Stop { position, color });
+        }
+    }
+    Palette::new(stops, Interpolation::Linear)
+}
+
+#[test]
+fn test_parse_ggr() {
+    let ggr = "GIMP Gradient\nName: Two\n2\n\
+               0 0.25 0.5 0 0 0 1 1 0 0 1 0 0\n\
+               0.5 0.75 1 1 0 0 1 1 1 1 1 0 0 0 0\n";
+    let palette = parse_ggr(ggr).unwrap();
+    assert_eq!(palette.stops().len(), 6);
+    assert_eq!(palette.color_at(0.25), [128, 0, 0]);
+    assert_eq!(palette.color_at(1.0), [255, 255, 255]);
+    assert!(parse_ggr("GIMP Gradient\nName: x\n2\n0 0.5 1 0 0 0 1 1 1 1 1 0 0\n").is_err());
+    assert!(parse_ggr("not a gradient").is_err());
+}
+
+/// A built-in palette: its name, interpolation, and stops as positions and
+/// colors.
+pub type Preset = (&'static str, Interpolation, &'static [(f64, [u8; 3])]);
+
+/// The palettes built in.
+pub const PRESETS: &[Preset] = &[
+    (
+        "gray",
+        Interpolation::Linear,
+        &[(0.0, [0; 3]), (1.0, [255; 3])],
+    ),
+    (
+        "fire",
+        Interpolation::Smooth,
+        &[
+            (0.0, [0, 0, 0]),
+            (0.3, [128, 0, 0]),
+            (0.6, [255, 128, 0]),
+            (0.85, [255, 230, 64]),
+            (1.0, [255, 255, 255]),
+        ],
+    ),
+    (
+        "ocean",
+        Interpolation::Smooth,
+        &[
+            (0.0, [0, 0, 16]),
+            (0.4, [0, 48, 128]),
+            (0.75, [32, 160, 200]),
+            (1.0, [230, 250, 255]),
+        ],
+    ),
+    (
+        "forest",
+        Interpolation::Linear,
+        &[
+            (0.0, [8, 16, 8]),
+            (0.35, [24, 80, 32]),
+            (0.7, [150, 170, 60]),
+            (1.0, [250, 240, 200]),
+        ],
+    ),
+    (
+        "twilight",
+        Interpolation::Smooth,
+        &[
+            (0.0, [16, 0, 32]),
+            (0.3, [96, 32, 128]),
+            (0.6, [224, 96, 96]),
+            (0.85, [255, 200, 120]),
+            (1.0, [255, 255, 240]),
+        ],
+    ),
+    (
+        "bands",
+        Interpolation::Constant,
+        &[
+            (0.0, [0, 0, 0]),
+            (0.2, [60, 20, 100]),
+            (0.4, [200, 60, 60]),
+            (0.6, [240, 180, 40]),
+            (0.8, [250, 250, 200]),
+        ],
+    ),
+    // matplotlib's perceptually uniform colormaps, which get lighter at an
+    // even pace and read the same to color blind eyes, sampled every tenth
+    // of the way, or every quarter for cividis, which hardly bends
+    (
+        "viridis",
+        Interpolation::Linear,
+        &[
+            (0.0, [68, 1, 84]),
+            (0.1, [72, 37, 117]),
+            (0.2, [65, 68, 135]),
+            (0.3, [53, 95, 141]),
+            (0.4, [42, 120, 142]),
+            (0.5, [33, 145, 140]),
+            (0.6, [34, 168, 132]),
+            (0.7, [68, 191, 112]),
+            (0.8, [122, 209, 81]),
+            (0.9, [189, 223, 38]),
+            (1.0, [253, 231, 37]),
+        ],
+    ),
+    (
+        "magma",
+        Interpolation::Linear,
+        &[
+            (0.0, [0, 0, 4]),
+            (0.1, [20, 14, 54]),
+            (0.2, [59, 15, 112]),
+            (0.3, [100, 26, 128]),
+            (0.4, [140, 41, 129]),
+            (0.5, [183, 55, 121]),
+            (0.6, [222, 73, 104]),
+            (0.7, [247, 112, 92]),
+            (0.8, [254, 159, 109]),
+            (0.9, [254, 207, 146]),
+            (1.0, [252, 253, 191]),
+        ],
+    ),
+    (
+        "inferno",
+        Interpolation::Linear,
+        &[
+            (0.0, [0, 0, 4]),
+            (0.1, [22, 11, 57]),
+            (0.2, [66, 10, 104]),
+            (0.3, [106, 23, 110]),
+            (0.4, [147, 38, 103]),
+            (0.5, [188, 55, 84]),
+            (0.6, [221, 81, 58]),
+            (0.7, [243, 120, 25]),
+            (0.8, [252, 165, 10]),
+            (0.9, [246, 215, 70]),
+            (1.0, [252, 255, 164]),
+        ],
+    ),
+    (
+        "cividis",
+        Interpolation::Linear,
+        &[
+            (0.0, [0, 34, 78]),
+            (0.25, [65, 77, 107]),
+            (0.5, [124, 123, 120]),
+            (0.75, [188, 175, 111]),
+            (1.0, [254, 232, 56]),
+        ],
+    ),
+];
+
+/// The built-in palette called `name`, if there is one.
+pub fn preset(name: &str) -> Option<Palette> {
+    PRESETS
+        .iter()
+        .find(|(preset, _, _)| *preset == name)
+        .map(|(_, interpolation, stops)| Palette {
+            stops: stops
+                .iter()
+                .map(|&(position, color)| Stop { position, color })
+                .collect(),
+            interpolation: *interpolation,
+        })
+}
+
+/// The name of the built-in palette called `name`, if there is one, as it
+/// is kept for as long as the program runs.
+pub fn preset_name(name: &str) -> Option<&'static str> {
+    PRESETS
+        .iter()
+        .map(|(preset, _, _)| *preset)
+        .find(|preset| *preset == name)
+}
+
+#[test]
+fn test_presets() {
+    assert_eq!(preset("gray"), Some(Palette::gray()));
+    assert!(preset("plaid").is_none());
+    let name = String::from("fire");
+    assert_eq!(preset_name(&name), Some("fire"));
+    assert_eq!(preset_name("plaid"), None);
+    for (name, interpolation, stops) in PRESETS {
+        let stops = stops
+            .iter()
+            .map(|&(position, color)| Stop { position, color })
+            .collect();
+        // built the way palette files are, they are valid
+        assert_eq!(Palette::new(stops, *interpolation).ok(), preset(name));
+    }
+    // the perceptually uniform ones only ever get lighter
+    for name in ["viridis", "magma", "inferno", "cividis"] {
+        let lightness: Vec<f64> = preset(name)
+            .unwrap()
+            .lut(64)
+            .iter()
+            .map(|&[r, g, b]| {
+                0.2126 * srgb_to_linear(r) + 0.7152 * srgb_to_linear(g) + 0.0722 * srgb_to_linear(b)
+            })
+            .collect();
+        assert!(
+            lightness.windows(2).all(|pair| pair[0] < pair[1]),
+            "{}",
+            name
+        );
+    }
+}
+
+/// The built-in palette called `name`, or else the palette in the file of
+/// that name.
+pub fn load(name: &str) -> Result<Palette> {
+    match preset(name) {
+        Some(palette) => Ok(palette),
+        None => read(name),
+    }
+}
+
+/// Read the palette in the file named `filename`, in the format its
+/// extension says.
+pub fn read(filename: &str) -> Result<Palette> {
+    let text = fs::read_to_string(platform::path(filename)).map_err(Error::io(filename))?;
+    let extension = filename
+        .rsplit_once('.')
+        .map(|(_, extension)| extension.to_ascii_lowercase());
+    let palette = match extension.as_deref() {
+        Some("json") => parse_json(&text),
+        Some("toml") => parse_toml(&text),
+        Some("ugr") => parse_ugr(&text),
+        Some("ggr") => parse_ggr(&text),
+        _ => Err("palette files must be .json, .toml, .ugr or .ggr".to_string()),
+    };
+    palette.map_err(|message| Error::Parse {
+        what: "palette",
+        value: format!("{}: {}", filename, message),
+    })
+}
+
+/// Color the grayscale `pixels` with `lut`, the colors of a palette from
+/// gray level 0 to 255, into RGB pixels, three bytes each.
+pub fn apply(pixels: &[u8], lut: &[[u8; 3]]) -> Vec<u8> {
+    let last = lut.len().max(1) - 1;
+    pixels
+        .iter()
+        .flat_map(|&gray| lut[gray as usize * last / 255])
+        .collect()
+}
+
+#[test]
+fn test_apply() {
+    let lut = Palette::gray().lut(256);
+    assert_eq!(
+        apply(&[0, 77, 255], &lut),
+        [0, 0, 0, 77, 77, 77, 255, 255, 255]
+    );
+    let coarse = [[1, 2, 3], [4, 5, 6]];
+    assert_eq!(
+        apply(&[0, 127, 128, 255], &coarse),
+        [1, 2, 3, 1, 2, 3, 1, 2, 3, 4, 5, 6]
+    );
+}
+
+/// `lut` rotated by `turns` of its length, wrapping around, so that the
+/// color of every gray level is the one `turns` further along the palette.
+/// Rotating a little more every frame cycles the colors of an animation.
+pub fn rotate(lut: &[[u8; 3]], turns: f64) -> Vec<[u8; 3]> {
+    let mut rotated = lut.to_vec();
+    if !lut.is_empty() {
+        let shift = (turns.rem_euclid(1.0) * lut.len() as f64).round() as usize;
+        rotated.rotate_left(shift % lut.len());
+    }
+    rotated
+}
+
+#[test]
+fn test_rotate() {
+    let lut = [[0, 0, 0], [1, 1, 1], [2, 2, 2], [3, 3, 3]];
+    assert_eq!(rotate(&lut, 0.0), lut);
+    assert_eq!(rotate(&lut, 0.25)[0], [1, 1, 1]);
+    assert_eq!(rotate(&lut, 1.5)[0], [2, 2, 2]);
+    assert_eq!(rotate(&lut, -0.25)[0], [3, 3, 3]);
+    assert!(rotate(&[], 0.5).is_empty());
+}
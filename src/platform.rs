@@ -0,0 +1,168 @@
+//! Papering over the ways file names differ between platforms, Windows in
+//! particular.
+
+use std::{borrow::Cow, fs::File, io, path::PathBuf};
+
+/// The names Windows reserves for devices, in any case and with any
+/// extension: `nul.png` is the null device, not a file.
+const WINDOWS_DEVICES: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL", "CONIN$", "CONOUT$", "COM1", "COM2", "COM3", "COM4", "COM5",
+    "COM6", "COM7", "COM8", "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8",
+    "LPT9",
+];
+
+/// Return the Windows device `filename` stands for, in upper case, or `None`
+/// if it is an ordinary file name.
+pub fn windows_device(filename: &str) -> Option<&'static str> {
+    let name = filename.rsplit(['/', '\\']).next().unwrap_or(filename);
+    let stem = name.split('.').next().unwrap_or(name).trim_end();
+    WINDOWS_DEVICES
+        .iter()
+        .find(|device| device.eq_ignore_ascii_case(stem))
+        .copied()
+}
+
+#[test]
+fn test_windows_device() {
+    assert_eq!(windows_device("NUL"), Some("NUL"));
+    assert_eq!(windows_device("con"), Some("CON"));
+    assert_eq!(windows_device("out\\Con.png"), Some("CON"));
+    assert_eq!(windows_device("conout$"), Some("CONOUT$"));
+    assert_eq!(windows_device("lpt1 .txt"), Some("LPT1"));
+    assert_eq!(windows_device("console.png"), None);
+    assert_eq!(windows_device("com10"), None);
+    assert_eq!(windows_device("con/out.png"), None);
+}
+
+/// Return whether writing to the file named `filename` would put binary data
+/// on a terminal: `-` when stdout is one, or the Windows console device.
+pub fn is_terminal(filename: &str) -> bool {
+    use std::io::IsTerminal;
+
+    if filename == "-" {
+        return io::stdout().is_terminal();
+    }
+    cfg!(windows) && matches!(windows_device(filename), Some("CON" | "CONOUT$"))
+}
+
+/// Use backslashes throughout a verbatim (`\\?\`) path, since Windows takes
+/// the rest of such a path literally and `/` isn't a separator there.
+fn verbatim_separators(filename: &str) -> Cow<'_, str> {
+    match filename.strip_prefix(r"\\?\") {
+        Some(rest) if rest.contains('/') => Cow::Owned(format!(r"\\?\{}", rest.replace('/', "\\"))),
+        _ => Cow::Borrowed(filename),
+    }
+}
+
+#[test]
+fn test_verbatim_separators() {
+    assert_eq!(
+        verbatim_separators(r"\\?\C:\images/out.png"),
+        r"\\?\C:\images\out.png"
+    );
+    assert_eq!(
+        verbatim_separators(r"\\?\UNC\server\share/out.png"),
+        r"\\?\UNC\server\share\out.png"
+    );
+    assert_eq!(verbatim_separators("out/mandel.png"), "out/mandel.png");
+    assert_eq!(
+        verbatim_separators(r"\\server\share\out.png"),
+        r"\\server\share\out.png"
+    );
+}
+
+/// The path of the file named `filename` on the command line.
+pub fn path(filename: &str) -> PathBuf {
+    if cfg!(windows) {
+        PathBuf::from(verbatim_separators(filename).as_ref())
+    } else {
+        PathBuf::from(filename)
+    }
+}
+
+/// Create the file named `filename` on the command line.
+pub fn create(filename: &str) -> io::Result<File> {
+    File::create(path(filename))
+}
+
+/// The directory the program keeps what it remembers between runs in:
+/// `MANDELBROT_CONFIG_DIR` if it is set, and otherwise `mandelbrot` in the
+/// user's configuration directory, `%APPDATA%` on Windows,
+/// `~/Library/Application Support` on macOS and `$XDG_CONFIG_HOME` or
+/// `~/.config` elsewhere. `None` if the environment doesn't say where that
+/// is.
+pub fn config_dir() -> Option<PathBuf> {
+    let var = |name| std::env::var_os(name).filter(|value| !value.is_empty());
+    if let Some(dir) = var("MANDELBROT_CONFIG_DIR") {
+        return Some(PathBuf::from(dir));
+    }
+    let base = if cfg!(windows) {
+        PathBuf::from(var("APPDATA")?)
+    } else if cfg!(target_os = "macos") {
+        PathBuf::from(var("HOME")?).join("Library/Application Support")
+    } else {
+        match var("XDG_CONFIG_HOME") {
+            Some(dir) => PathBuf::from(dir),
+            None => PathBuf::from(var("HOME")?).join(".config"),
+        }
+    };
+    Some(base.join("mandelbrot"))
+}
+
+/// The file named `name` in `config_dir`.
+pub fn config_file(name: &str) -> crate::Result<String> {
+    let dir = config_dir().ok_or_else(|| {
+        crate::Error::Usage(
+            "can't tell where the configuration directory is, set MANDELBROT_CONFIG_DIR"
+                .to_string(),
+        )
+    })?;
+    Ok(dir.join(name).to_string_lossy().into_owned())
+}
+
+/// The directory the program keeps what it can compute again in:
+/// `MANDELBROT_CACHE_DIR` if it is set, and otherwise `mandelbrot` in the
+/// user's cache directory, `%LOCALAPPDATA%` on Windows, `~/Library/Caches`
+/// on macOS and `$XDG_CACHE_HOME` or `~/.cache` elsewhere. `None` if the
+/// environment doesn't say where that is.
+pub fn cache_dir() -> Option<PathBuf> {
+    let var = |name| std::env::var_os(name).filter(|value| !value.is_empty());
+    if let Some(dir) = var("MANDELBROT_CACHE_DIR") {
+        return Some(PathBuf::from(dir));
+    }
+    let base = if cfg!(windows) {
+        PathBuf::from(var("LOCALAPPDATA")?)
+    } else if cfg!(target_os = "macos") {
+        PathBuf::from(var("HOME")?).join("Library/Caches")
+    } else {
+        match var("XDG_CACHE_HOME") {
+            Some(dir) => PathBuf::from(dir),
+            None => PathBuf::from(var("HOME")?).join(".cache"),
+        }
+    };
+    Some(base.join("mandelbrot"))
+}
+
+/// The most memory the process has held at once, in bytes, where the
+/// platform tells: Linux does, in `/proc/self/status`.
+pub fn peak_memory() -> Option<u64> {
+    if !cfg!(target_os = "linux") {
+        return None;
+    }
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|line| line.starts_with("VmHWM:"))?;
+    let kilobytes: u64 = line["VmHWM:".len()..]
+        .trim()
+        .trim_end_matches("kB")
+        .trim()
+        .parse()
+        .ok()?;
+    Some(kilobytes * 1024)
+}
+
+#[test]
+fn test_peak_memory() {
+    let peak = peak_memory();
+    assert_eq!(peak.is_some(), cfg!(target_os = "linux"));
+    assert!(peak.is_none_or(|bytes| bytes > 0));
+}
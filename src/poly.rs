@@ -0,0 +1,248 @@
+//! Polynomials with complex coefficients, as written on the command line:
+//! `z^3 - 1`, `2z^4 + 0.5*z - i` or `(1+2i)z^2 - 3`.
+
+use std::str::FromStr;
+
+use num::Complex;
+
+/// A polynomial in `z`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Polynomial {
+    /// The coefficient of `z^k` at index `k`, without zeros at the end.
+    coefficients: Vec<Complex<f64>>,
+}
+
+impl Polynomial {
+    /// The polynomial with the given coefficients, lowest power first.
+    pub fn new(coefficients: &[Complex<f64>]) -> Polynomial {
+        let mut coefficients = coefficients.to_vec();
+        while coefficients.last() == Some(&Complex::new(0.0, 0.0)) {
+            coefficients.pop();
+        }
+        Polynomial { coefficients }
+    }
+
+    /// The highest power of `z` with a coefficient other than 0; 0 for
+    /// constants, including 0 itself.
+    pub fn degree(&self) -> usize {
+        self.coefficients.len().saturating_sub(1)
+    }
+
+    /// The value of the polynomial and of its derivative at `z`.
+    pub fn eval(&self, z: Complex<f64>) -> (Complex<f64>, Complex<f64>) {
+        let zero = Complex::new(0.0, 0.0);
+        self.coefficients
+            .iter()
+            .rev()
+            .fold((zero, zero), |(p, dp), &a| (p * z + a, dp * z + p))
+    }
+
+    /// All the roots of the polynomial, as many as its degree, found with the
+    /// Durand-Kerner method.
+    pub fn roots(&self) -> Vec<Complex<f64>> {
+        let degree = self.degree();
+        if degree == 0 {
+            return Vec::new();
+        }
+        let leading = self.coefficients[degree];
+        let monic = Polynomial::new(
+            &self
+                .coefficients
+                .iter()
+                .map(|&a| a / leading)
+                .collect::<Vec<_>>(),
+        );
+
+        // start from points spread on a circle, off the real axis
+        let seed = Complex::new(0.4, 0.9);
+        let mut roots: Vec<Complex<f64>> = (0..degree).map(|k| seed.powu(k as u32)).collect();
+        for _ in 0..500 {
+            let mut moved = 0.0f64;
+            for i in 0..degree {
+                let mut denominator = Complex::new(1.0, 0.0);
+                for j in 0..degree {
+                    if i != j {
+                        denominator *= roots[i] - roots[j];
+                    }
+                }
+                let step = monic.eval(roots[i]).0 / denominator;
+                roots[i] -= step;
+                moved = moved.max(step.norm());
+            }
+            if moved < 1e-14 {
+                break;
+            }
+        }
+        roots
+    }
+}
+
+#[test]
+fn test_eval_and_roots() {
+    // z^3 - 1
+    let p = Polynomial::new(&[
+        Complex::new(-1.0, 0.0),
+        Complex::new(0.0, 0.0),
+        Complex::new(0.0, 0.0),
+        Complex::new(1.0, 0.0),
+    ]);
+    assert_eq!(p.degree(), 3);
+    assert_eq!(
+        p.eval(Complex::new(2.0, 0.0)),
+        (Complex::new(7.0, 0.0), Complex::new(12.0, 0.0))
+    );
+
+    let roots = p.roots();
+    assert_eq!(roots.len(), 3);
+    for root in &roots {
+        assert!(p.eval(*root).0.norm() < 1e-9, "{}", root);
+    }
+    assert!(roots.iter().any(|r| (r - 1.0).norm() < 1e-9));
+
+    assert_eq!(Polynomial::new(&[Complex::new(0.0, 0.0)]).degree(), 0);
+    assert!(Polynomial::new(&[Complex::new(5.0, 0.0)])
+        .roots()
+        .is_empty());
+}
+
+impl FromStr for Polynomial {
+    type Err = String;
+
+    /// Parse a sum of terms, each an optional coefficient followed by `z` or
+    /// `z^N`, or a constant. A coefficient is a number, a number followed by
+    /// `i`, `i` alone, or a constant in parentheses; a `*` may separate it
+    /// from `z`.
+    fn from_str(s: &str) -> Result<Polynomial, String> {
+        let chars: Vec<char> = s.chars().filter(|c| !c.is_whitespace()).collect();
+        let error = || format!("invalid polynomial {:?}", s);
+        if chars.is_empty() {
+            return Err(error());
+        }
+
+        let mut coefficients = Vec::new();
+        let mut pos = 0;
+        while pos < chars.len() {
+            let sign = match chars[pos] {
+                '+' => 1.0,
+                '-' => -1.0,
+                _ if pos == 0 => 1.0,
+                _ => return Err(error()),
+            };
+            if matches!(chars[pos], '+' | '-') {
+                pos += 1;
+            }
+
+            let coefficient = coefficient(&chars, &mut pos).ok_or_else(error)?;
+            if coefficient.is_some() && chars.get(pos) == Some(&'*') {
+                pos += 1;
+                if chars.get(pos) != Some(&'z') {
+                    return Err(error());
+                }
+            }
+            let power = if chars.get(pos) == Some(&'z') {
+                pos += 1;
+                if chars.get(pos) == Some(&'^') {
+                    pos += 1;
+                    let start = pos;
+                    while chars.get(pos).is_some_and(char::is_ascii_digit) {
+                        pos += 1;
+                    }
+                    let digits: String = chars[start..pos].iter().collect();
+                    digits
+                        .parse::<usize>()
+                        .ok()
+                        .filter(|&n| n <= 64)
+                        .ok_or_else(error)?
+                } else {
+                    1
+                }
+            } else if coefficient.is_some() {
+                0
+            } else {
+                return Err(error());
+            };
+
+            if coefficients.len() <= power {
+                coefficients.resize(power + 1, Complex::new(0.0, 0.0));
+            }
+            coefficients[power] += coefficient.unwrap_or(Complex::new(1.0, 0.0)) * sign;
+        }
+
+        Ok(Polynomial::new(&coefficients))
+    }
+}
+
+/// Parse the coefficient starting at `chars[*pos]`, if there is one, moving
+/// `pos` past it. Returns `None` if there is one but it is malformed.
+fn coefficient(chars: &[char], pos: &mut usize) -> Option<Option<Complex<f64>>> {
+    match chars.get(*pos) {
+        Some('(') => {
+            let close = *pos + chars[*pos..].iter().position(|&c| c == ')')?;
+            let inner: String = chars[*pos + 1..close].iter().collect();
+            let constant: Polynomial = inner.parse().ok()?;
+            if constant.degree() > 0 {
+                return None;
+            }
+            *pos = close + 1;
+            Some(Some(
+                constant
+                    .coefficients
+                    .first()
+                    .copied()
+                    .unwrap_or(Complex::new(0.0, 0.0)),
+            ))
+        }
+        Some('i') => {
+            *pos += 1;
+            Some(Some(Complex::new(0.0, 1.0)))
+        }
+        Some(c) if c.is_ascii_digit() || *c == '.' => {
+            let start = *pos;
+            while let Some(&c) = chars.get(*pos) {
+                let exponent_sign = matches!(c, '+' | '-') && matches!(chars[*pos - 1], 'e' | 'E');
+                if !(c.is_ascii_digit() || matches!(c, '.' | 'e' | 'E') || exponent_sign) {
+                    break;
+                }
+                *pos += 1;
+            }
+            let number: f64 = chars[start..*pos].iter().collect::<String>().parse().ok()?;
+            if chars.get(*pos) == Some(&'i') {
+                *pos += 1;
+                Some(Some(Complex::new(0.0, number)))
+            } else {
+                Some(Some(Complex::new(number, 0.0)))
+            }
+        }
+        _ => Some(None),
+    }
+}
+
+#[test]
+fn test_parse_polynomial() {
+    let c = |re, im| Complex::new(re, im);
+    assert_eq!(
+        "z^3-1".parse(),
+        Ok(Polynomial::new(&[
+            c(-1.0, 0.0),
+            c(0.0, 0.0),
+            c(0.0, 0.0),
+            c(1.0, 0.0)
+        ]))
+    );
+    assert_eq!(
+        "2z^2 + 0.5*z - i".parse(),
+        Ok(Polynomial::new(&[c(0.0, -1.0), c(0.5, 0.0), c(2.0, 0.0)]))
+    );
+    assert_eq!(
+        "(1+2i)z^2 - 3 + z^2 - 1e-1z".parse(),
+        Ok(Polynomial::new(&[c(-3.0, 0.0), c(-0.1, 0.0), c(2.0, 2.0)]))
+    );
+    assert_eq!(
+        "-z + 2iz".parse(),
+        Ok(Polynomial::new(&[c(0.0, 0.0), c(-1.0, 2.0)]))
+    );
+
+    for bad in ["", "z^", "z^x", "2*", "z z", "(z)z", "z^999", "w"] {
+        assert!(bad.parse::<Polynomial>().is_err(), "{:?}", bad);
+    }
+}
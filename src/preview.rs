@@ -0,0 +1,65 @@
+//! Previews of palettes, to choose one without rendering full images: a strip
+//! of the gradient above a small render of the set colored with it.
+
+use num::Complex;
+
+use crate::{
+    palette::{self, Palette},
+    render,
+    units::Zoom,
+};
+
+/// The view of the sample render: seahorse valley, which has every gray
+/// level somewhere.
+const SWATCH_CENTER: Complex<f64> = Complex {
+    re: -0.7436,
+    im: 0.1318,
+};
+const SWATCH_ZOOM: Zoom = Zoom(60.0);
+
+/// The dimensions of previews unless told otherwise.
+pub const DEFAULT_BOUNDS: (usize, usize) = (512, 256);
+
+/// Draw the preview of `palette` into an RGB image, three bytes a pixel,
+/// whose dimensions are given by `bounds`: the gradient from the color of
+/// points in the set on the left to that of points escaping at once on the
+/// right, in the top quarter, and the sample render below it.
+pub fn preview(palette: &Palette, bounds: (usize, usize)) -> Vec<u8> {
+    let strip = (bounds.1 / 4).max(1).min(bounds.1);
+    let last = bounds.0.saturating_sub(1).max(1) as f64;
+    let gradient: Vec<u8> = (0..bounds.0)
+        .flat_map(|x| palette.color_at(x as f64 / last))
+        .collect();
+    let mut rgb = gradient.repeat(strip);
+
+    let swatch = (bounds.0, bounds.1 - strip);
+    if swatch.0 > 0 && swatch.1 > 0 {
+        let (upper_left, lower_right) =
+            render::corners_from_center(SWATCH_CENTER, SWATCH_ZOOM, swatch);
+        let mut pixels = vec![0; swatch.0 * swatch.1];
+        render::render(&mut pixels, swatch, upper_left, lower_right);
+        rgb.extend(palette::apply(&pixels, &palette.lut(256)));
+    }
+    rgb
+}
+
+#[test]
+fn test_preview() {
+    let fire = palette::preset("fire").unwrap();
+    let bounds = (16, 8);
+    let rgb = preview(&fire, bounds);
+    assert_eq!(rgb.len(), 3 * 16 * 8);
+    // the strip, two rows, goes from black to white
+    assert_eq!(rgb[..3], [0, 0, 0]);
+    assert_eq!(rgb[3 * 15..3 * 16], [255, 255, 255]);
+    assert_eq!(rgb[..3 * 16], rgb[3 * 16..3 * 32]);
+    // the swatch has colors of the palette other than its ends
+    let lut = fire.lut(256);
+    assert!(rgb[3 * 32..]
+        .chunks(3)
+        .all(|color| lut.contains(&[color[0], color[1], color[2]])));
+    assert!(rgb[3 * 32..].chunks(3).any(|color| color != [0, 0, 0]));
+
+    assert_eq!(preview(&fire, (4, 1)).len(), 12);
+    assert!(preview(&fire, (0, 4)).is_empty());
+}
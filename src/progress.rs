@@ -0,0 +1,114 @@
+//! Keeping track of how far along a render is.
+
+use std::{
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+    time::{Duration, Instant},
+};
+
+/// Counts the rows of an image that have been rendered so far. It is shared
+/// between the rendering threads and whoever wants to know how they're doing.
+pub struct Progress {
+    total_rows: usize,
+    rows_done: AtomicUsize,
+    started: Instant,
+    checkpoint_requested: AtomicBool,
+}
+
+impl Progress {
+    /// Start tracking the render of an image `total_rows` rows high.
+    pub fn new(total_rows: usize) -> Progress {
+        Progress {
+            total_rows,
+            rows_done: AtomicUsize::new(0),
+            started: Instant::now(),
+            checkpoint_requested: AtomicBool::new(false),
+        }
+    }
+
+    /// Record that `rows` more rows are done.
+    pub fn add_rows(&self, rows: usize) {
+        self.rows_done.fetch_add(rows, Ordering::Relaxed);
+    }
+
+    pub fn rows_done(&self) -> usize {
+        self.rows_done.load(Ordering::Relaxed)
+    }
+
+    /// The part of the image rendered so far, between 0 and 1.
+    pub fn fraction(&self) -> f64 {
+        if self.total_rows == 0 {
+            return 1.0;
+        }
+        self.rows_done() as f64 / self.total_rows as f64
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    /// Estimate how long until the render is done, assuming the remaining rows
+    /// take as long as the ones done so far. Returns `None` until a row is done.
+    pub fn eta(&self) -> Option<Duration> {
+        eta(self.elapsed(), self.fraction())
+    }
+
+    /// Ask the rendering threads to write a checkpoint as soon as they can.
+    pub fn request_checkpoint(&self) {
+        self.checkpoint_requested.store(true, Ordering::Relaxed);
+    }
+
+    /// Return whether a checkpoint was requested since the last call.
+    pub fn take_checkpoint_request(&self) -> bool {
+        self.checkpoint_requested.swap(false, Ordering::Relaxed)
+    }
+
+    /// Describe the progress in one line, for humans.
+    pub fn summary(&self) -> String {
+        let eta = match self.eta() {
+            Some(eta) => format!("{:.1} s", eta.as_secs_f64()),
+            None => "unknown".to_string(),
+        };
+        format!(
+            "{}/{} rows ({:.1}%), {:.1} s elapsed, ETA {}",
+            self.rows_done(),
+            self.total_rows,
+            100.0 * self.fraction(),
+            self.elapsed().as_secs_f64(),
+            eta
+        )
+    }
+}
+
+/// Estimate the time left when `fraction` of the work took `elapsed`.
+fn eta(elapsed: Duration, fraction: f64) -> Option<Duration> {
+    if fraction <= 0.0 {
+        return None;
+    }
+    Some(elapsed.mul_f64((1.0 - fraction).max(0.0) / fraction))
+}
+
+#[test]
+fn test_eta() {
+    assert_eq!(eta(Duration::from_secs(10), 0.0), None);
+    assert_eq!(
+        eta(Duration::from_secs(10), 0.25),
+        Some(Duration::from_secs(30))
+    );
+    assert_eq!(eta(Duration::from_secs(10), 1.0), Some(Duration::ZERO));
+}
+
+#[test]
+fn test_progress() {
+    let progress = Progress::new(200);
+    assert_eq!(progress.fraction(), 0.0);
+    progress.add_rows(50);
+    progress.add_rows(50);
+    assert_eq!(progress.rows_done(), 100);
+    assert_eq!(progress.fraction(), 0.5);
+    assert!(progress.summary().starts_with("100/200 rows (50.0%)"));
+
+    assert!(!progress.take_checkpoint_request());
+    progress.request_checkpoint();
+    assert!(progress.take_checkpoint_request());
+    assert!(!progress.take_checkpoint_request());
+}
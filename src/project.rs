@@ -0,0 +1,817 @@
+//! Project files, `.mandel`: one versioned JSON document holding the view,
+//! the fractal, the coloring, the keyframes of an animation and bookmarks,
+//! instead of options, configuration files and sidecars spread around.
+//!
+//! ```json
+//! {
+//!   "version": 1,
+//!   "view": {"pixels": [1000, 750], "center": [-0.75, 0.1], "zoom": 20},
+//!   "fractal": {"max-iters": "1000"},
+//!   "coloring": {"palette": "fire", "transfer": "sqrt"},
+//!   "scene": {"keyframes": [{"center": [-0.75, 0.1], "zoom": 1}]},
+//!   "bookmarks": {"spot": [-0.7435, 0.1314]}
+//! }
+//! ```
+//!
+//! The view is written like a job's, see `job`. The fractal and the coloring
+//! are the command line options they stand for, without their dashes, with
+//! their values as they would be given, so that `Project::to_args` turns them
+//! back into options and there is still a single place where parameters are
+//! interpreted. The scene is an animation scene, see `animation`.
+//!
+//! `diff` compares two projects setting by setting, and `merge` combines two
+//! projects changed separately from a common one.
+//!
+//! Every project says which version of the format it is written in. Projects
+//! written in earlier versions are migrated to the current one as they are
+//! opened, and written back in it when saved. Projects written in later
+//! versions are opened as best we can, see `schema`: what this version
+//! doesn't know is left out, with a warning.
+
+use std::{collections::BTreeMap, fs};
+
+use num::Complex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    anchors::{self, Anchors},
+    animation::Scene,
+    job::{Job, Pair},
+    output, parse_complex, parse_pair, platform, schema, DecimalSeparator, Error, Result,
+};
+
+/// The version of the format projects are written in.
+pub const VERSION: u64 = 1;
+
+/// A change to the format: turns a project of the version before into one
+/// of the version after, which it sets.
+type Migration = fn(&mut serde_json::Map<String, Value>) -> std::result::Result<(), String>;
+
+/// The migrations from each version to the next, the first from version 1
+/// to version 2. Version 1 is the first, so there are none yet.
+const MIGRATIONS: &[Migration] = &[];
+
+/// The options the `fractal` section may hold.
+pub const FRACTAL_OPTIONS: [&str; 5] = ["fractal", "max-iters", "bailout", "formula", "poly"];
+
+/// The options the `coloring` section may hold, in the order the program
+/// applies them: bands, glow, palette, background.
+pub const COLORING_OPTIONS: [&str; 11] = [
+    "bands",
+    "band-outline",
+    "band-outline-width",
+    "glow",
+    "glow-strength",
+    "glow-kernel",
+    "palette",
+    "transfer",
+    "palette-offset",
+    "palette-period",
+    "background",
+];
+
+/// The part of the plane a project shows, and the size of its image.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct View {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pixels: Option<Pair<usize>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub upper_left: Option<Pair<f64>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lower_right: Option<Pair<f64>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub center: Option<Pair<f64>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub zoom: Option<f64>,
+}
+
+impl View {
+    /// The job of the view alone.
+    fn job(&self) -> Job {
+        Job {
+            pixels: self.pixels.clone(),
+            upper_left: self.upper_left.clone(),
+            lower_right: self.lower_right.clone(),
+            center: self.center.clone(),
+            zoom: self.zoom,
+            ..Job::default()
+        }
+    }
+}
+
+/// Everything about a render but where it is written.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Project {
+    pub version: u64,
+    #[serde(default)]
+    pub view: View,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub fractal: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub coloring: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scene: Option<Scene>,
+    /// Named points, for `--bookmarks`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub bookmarks: BTreeMap<String, (f64, f64)>,
+}
+
+impl Default for Project {
+    fn default() -> Project {
+        Project {
+            version: VERSION,
+            view: View::default(),
+            fractal: BTreeMap::new(),
+            coloring: BTreeMap::new(),
+            scene: None,
+            bookmarks: BTreeMap::new(),
+        }
+    }
+}
+
+/// Bring the project `value`, in any earlier version of the format, to the
+/// current one with `migrations`. Later versions are left as they are.
+fn migrate_with(value: Value, migrations: &[Migration]) -> Result<Value> {
+    let Value::Object(mut project) = value else {
+        return Err(Error::parse("project", "expected a JSON object"));
+    };
+    let version = match project.get("version") {
+        Some(version) => version
+            .as_u64()
+            .filter(|&version| version >= 1)
+            .ok_or_else(|| Error::parse("project", &format!("version {}", version)))?,
+        None => return Err(Error::parse("project", "missing version")),
+    };
+    if version > migrations.len() as u64 + 1 {
+        return Ok(Value::Object(project));
+    }
+    for (from, migration) in (version..).zip(&migrations[version as usize - 1..]) {
+        migration(&mut project).map_err(|problem| {
+            Error::parse(
+                "project",
+                &format!("migrating from version {}: {}", from, problem),
+            )
+        })?;
+        project.insert("version".to_string(), Value::from(from + 1));
+    }
+    Ok(Value::Object(project))
+}
+
+/// Bring the project `value`, in any earlier version of the format, to the
+/// current one.
+pub fn migrate(value: Value) -> Result<Value> {
+    migrate_with(value, MIGRATIONS)
+}
+
+#[test]
+fn test_migrate() {
+    use serde_json::json;
+
+    let project = json!({"version": 1, "coloring": {"palette": "fire"}});
+    assert_eq!(migrate(project.clone()).unwrap(), project);
+    assert!(migrate(json!({"coloring": {}})).is_err());
+    assert!(migrate(json!({"version": 0})).is_err());
+    assert!(migrate(json!({"version": "1"})).is_err());
+    assert!(migrate(json!([1])).is_err());
+    let newer = json!({"version": 2, "lights": []});
+    assert_eq!(migrate(newer.clone()).unwrap(), newer);
+
+    // migrations run one after the other, from the version of the project
+    let migrations: &[Migration] = &[
+        |project| {
+            let palette = project.remove("palette").ok_or("no palette")?;
+            project.insert("coloring".to_string(), json!({ "palette": palette }));
+            Ok(())
+        },
+        |project| {
+            project.insert("view".to_string(), json!({"zoom": 2}));
+            Ok(())
+        },
+    ];
+    let old = json!({"version": 1, "palette": "fire"});
+    assert_eq!(
+        migrate_with(old, migrations).unwrap(),
+        json!({"version": 3, "coloring": {"palette": "fire"}, "view": {"zoom": 2}})
+    );
+    let failed = migrate_with(json!({"version": 1}), migrations).unwrap_err();
+    assert!(failed.to_string().contains("from version 1: no palette"));
+    assert_eq!(
+        migrate_with(json!({"version": 2}), migrations).unwrap(),
+        json!({"version": 3, "view": {"zoom": 2}})
+    );
+}
+
+impl Project {
+    /// Parse a project from its JSON description, in any version of the
+    /// format, and check it.
+    pub fn from_json(json: &str) -> Result<Project> {
+        let value =
+            serde_json::from_str(json).map_err(|e| Error::parse("project", &e.to_string()))?;
+        Project::from_value(value)
+    }
+
+    /// Build a project from its JSON value, in any version of the format,
+    /// and check it.
+    pub fn from_value(value: Value) -> Result<Project> {
+        let mut value = migrate(value)?;
+        let version = schema::version("project", &value)?;
+        if version > VERSION {
+            let mut dropped = Vec::new();
+            schema::drop_unknown::<Project>(&mut value, "", &mut dropped);
+            if let Some(view) = value.get_mut("view") {
+                schema::drop_unknown::<View>(view, "view.", &mut dropped);
+            }
+            if let Some(scene) = value.get_mut("scene") {
+                Scene::drop_unknown(scene, "scene.", &mut dropped);
+            }
+            for (section, allowed) in [
+                ("fractal", &FRACTAL_OPTIONS[..]),
+                ("coloring", &COLORING_OPTIONS[..]),
+            ] {
+                if let Some(Value::Object(options)) = value.get_mut(section) {
+                    options.retain(|name, _| {
+                        let keep = allowed.contains(&name.as_str());
+                        if !keep {
+                            dropped.push(format!("{}.{}", section, name));
+                        }
+                        keep
+                    });
+                }
+            }
+            schema::warn_newer("the project", version, VERSION, &dropped);
+        }
+        let project: Project =
+            serde_json::from_value(value).map_err(|e| Error::parse("project", &e.to_string()))?;
+        // points may be given relative to the bookmarks
+        let anchors = project.anchors();
+        let resolve = |point: &Option<Pair<f64>>| match point {
+            Some(Pair::Text(text)) => match anchors.resolve(text, DecimalSeparator::Auto) {
+                Some(point) => Some(Pair::Numbers(point.re, point.im)),
+                None => Some(Pair::Text(text.clone())),
+            },
+            point => point.clone(),
+        };
+        let view = Job {
+            upper_left: resolve(&project.view.upper_left),
+            lower_right: resolve(&project.view.lower_right),
+            center: resolve(&project.view.center),
+            ..project.view.job()
+        };
+        let mut problems = view.problems();
+        for (section, options, allowed) in [
+            ("fractal", &project.fractal, &FRACTAL_OPTIONS[..]),
+            ("coloring", &project.coloring, &COLORING_OPTIONS[..]),
+        ] {
+            for name in options.keys() {
+                if !allowed.contains(&name.as_str()) {
+                    problems.push(format!("{} has no option {}", section, name));
+                }
+            }
+        }
+        for (name, point) in &project.bookmarks {
+            if !anchors::is_name(name) {
+                problems.push(format!("bookmark name {:?} is not a name", name));
+            }
+            if !point.0.is_finite() || !point.1.is_finite() {
+                problems.push(format!("bookmark {} must be finite", name));
+            }
+        }
+        if !problems.is_empty() {
+            return Err(Error::parse("project", &problems.join("; ")));
+        }
+        let scene = project.scene.map(Scene::checked).transpose()?;
+        Ok(Project { scene, ..project })
+    }
+
+    /// Open the project file named `filename`.
+    pub fn open(filename: &str) -> Result<Project> {
+        let json = fs::read_to_string(platform::path(filename)).map_err(Error::io(filename))?;
+        Project::from_json(&json).map_err(|e| match e {
+            Error::Parse { what, value } => Error::Parse {
+                what,
+                value: format!("{}: {}", filename, value),
+            },
+            e => e,
+        })
+    }
+
+    /// The project as JSON, in the current version of the format.
+    pub fn to_json(&self) -> String {
+        let project = Project {
+            version: VERSION,
+            ..self.clone()
+        };
+        serde_json::to_string_pretty(&project).unwrap() + "\n"
+    }
+
+    /// Save the project to the file named `filename`, in the current version
+    /// of the format.
+    pub fn save(&self, filename: &str) -> Result<()> {
+        output::write(filename, self.to_json().as_bytes())
+    }
+
+    /// Turn the view, the fractal and the coloring of the project into the
+    /// command line options that ask for them.
+    pub fn to_args(&self) -> Vec<String> {
+        let mut args = self.view.job().to_args();
+        for (name, value) in self.fractal.iter().chain(&self.coloring) {
+            args.push(format!("--{}", name));
+            args.push(value.clone());
+        }
+        args
+    }
+
+    /// The project of the options `args`: the view, fractal and coloring
+    /// options, and the scene and the bookmarks of `--scene` and
+    /// `--bookmarks`, read from their files. Anything else is an error
+    /// rather than left out.
+    pub fn from_args(args: &[String]) -> Result<Project> {
+        let mut project = Project::default();
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let name = arg.strip_prefix("--").unwrap_or("");
+            let section = if FRACTAL_OPTIONS.contains(&name) {
+                Some(&mut project.fractal)
+            } else if COLORING_OPTIONS.contains(&name) {
+                Some(&mut project.coloring)
+            } else {
+                None
+            };
+            let known = section.is_some()
+                || ["pixels", "upper-left", "lower-right", "center", "zoom"].contains(&name)
+                || ["scene", "bookmarks"].contains(&name);
+            if !known {
+                return Err(Error::Usage(format!(
+                    "a project keeps the view, the fractal, the coloring, --scene and \
+                     --bookmarks, not {}",
+                    arg
+                )));
+            }
+            let value = args
+                .next()
+                .ok_or_else(|| Error::Usage(format!("{} requires a value", arg)))?
+                .clone();
+            let view = &mut project.view;
+            match name {
+                "pixels" => view.pixels = Some(Pair::Text(value)),
+                "upper-left" => view.upper_left = Some(Pair::Text(value)),
+                "lower-right" => view.lower_right = Some(Pair::Text(value)),
+                "center" => view.center = Some(Pair::Text(value)),
+                "zoom" => {
+                    view.zoom = Some(value.parse().map_err(|_| Error::parse("zoom", &value))?)
+                }
+                "scene" => project.scene = Some(Scene::read(&value)?),
+                "bookmarks" => {
+                    for (name, point) in Anchors::read(&value)?.bookmarks() {
+                        project.bookmarks.insert(name.clone(), (point.re, point.im));
+                    }
+                }
+                _ => {
+                    section.unwrap().insert(name.to_string(), value);
+                }
+            }
+        }
+        Ok(project)
+    }
+
+    /// The bookmarks of the project, as anchors for points.
+    pub fn anchors(&self) -> Anchors {
+        Anchors::new(
+            self.bookmarks
+                .iter()
+                .map(|(name, &(re, im))| (name.clone(), Complex::new(re, im)))
+                .collect(),
+        )
+    }
+}
+
+#[test]
+fn test_project() {
+    let json = r#"{
+        "version": 1,
+        "view": {"pixels": [1000, 750], "center": "spot - 0.0065,0.0314", "zoom": 20},
+        "fractal": {"max-iters": "1000"},
+        "coloring": {"palette": "fire", "transfer": "sqrt"},
+        "scene": {"keyframes": [{"center": [-0.75, 0.1], "zoom": 1}]},
+        "bookmarks": {"spot": [-0.7435, 0.1314]}
+    }"#;
+    let project = Project::from_json(json).unwrap();
+    assert_eq!(
+        project.to_args(),
+        [
+            "--pixels",
+            "1000x750",
+            "--center",
+            "spot - 0.0065,0.0314",
+            "--zoom",
+            "20",
+            "--max-iters",
+            "1000",
+            "--palette",
+            "fire",
+            "--transfer",
+            "sqrt"
+        ]
+    );
+    assert_eq!(project.scene.as_ref().unwrap().keyframes.len(), 1);
+    assert_eq!(
+        project.anchors().get("spot"),
+        Some(Complex::new(-0.7435, 0.1314))
+    );
+    // saved and opened again, the same
+    assert_eq!(Project::from_json(&project.to_json()).unwrap(), project);
+    assert_eq!(
+        Project::from_json(r#"{"version": 1}"#).unwrap(),
+        Project::default()
+    );
+
+    let problems = |json| Project::from_json(json).unwrap_err().to_string();
+    assert!(problems(r#"{"version": 1, "view": {"zoom": -1}}"#).contains("zoom"));
+    assert!(problems(r#"{"version": 1, "view": {"center": "spot"}}"#).contains("not a point"));
+    assert!(problems(r#"{"version": 1, "coloring": {"output": "x"}}"#).contains("no option"));
+    assert!(problems(r#"{"version": 1, "bookmarks": {"1st": [0, 0]}}"#).contains("not a name"));
+    assert!(problems(r#"{"version": 1, "extra": 1}"#).contains("unknown field"));
+    let scene = r#"{"version": 1, "scene": {"keyframes": [{"center": [0, 0], "zoom": 0}]}}"#;
+    assert!(problems(scene).contains("keyframe 1"));
+
+    // a later version is read without what this one doesn't know
+    let newer = r#"{
+        "version": 2, "lights": [],
+        "view": {"center": [-0.5, 0], "tilt": 30},
+        "coloring": {"palette": "fire", "shading": "phong"},
+        "scene": {"keyframes": [{"center": [0, 0], "zoom": 1, "roll": 5}]}
+    }"#;
+    let project = Project::from_json(newer).unwrap();
+    assert_eq!(
+        project.to_args(),
+        ["--center", "-0.5,0", "--palette", "fire"]
+    );
+    assert_eq!(project.scene.unwrap().keyframes.len(), 1);
+}
+
+#[test]
+fn test_from_args() {
+    let args = |args: &[&str]| -> Vec<String> { args.iter().map(|s| s.to_string()).collect() };
+    let project = Project::from_args(&args(&[
+        "--pixels",
+        "640x480",
+        "--center",
+        "seahorse",
+        "--zoom",
+        "50",
+        "--fractal",
+        "formula",
+        "--formula",
+        "z*z*z + c",
+        "--glow",
+        "3",
+    ]))
+    .unwrap();
+    assert_eq!(project.view.pixels, Some(Pair::Text("640x480".to_string())));
+    assert_eq!(project.view.zoom, Some(50.0));
+    assert_eq!(project.fractal.len(), 2);
+    assert_eq!(project.coloring["glow"], "3");
+    let again = Project::from_args(&project.to_args()).unwrap();
+    assert_eq!(again, project);
+
+    assert!(Project::from_args(&args(&["--output", "x.png"])).is_err());
+    assert!(Project::from_args(&args(&["x.png"])).is_err());
+    assert!(Project::from_args(&args(&["--palette"])).is_err());
+    assert!(Project::from_args(&args(&["--zoom", "big"])).is_err());
+}
+
+/// Replace every `--project FILE` in `args` by itself followed by the
+/// options the project stands for, so that options given after it override
+/// the project's, and the program still knows the project for its bookmarks
+/// and its scene.
+pub fn expand_projects(args: &[String]) -> Result<Vec<String>> {
+    let mut expanded = Vec::with_capacity(args.len());
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        expanded.push(arg.clone());
+        if arg == "--project" {
+            let file = args
+                .next()
+                .ok_or_else(|| Error::Usage("--project requires a file name".to_string()))?;
+            expanded.push(file.clone());
+            expanded.extend(Project::open(file)?.to_args());
+        }
+    }
+    Ok(expanded)
+}
+
+impl Project {
+    /// The project as a JSON value written the same way whatever way it was
+    /// given in: in the current version of the format, and with the pairs
+    /// of the view as numbers where they are numbers, so that `"20x10"` and
+    /// `[20, 10]` are the same.
+    fn normalized(&self) -> Value {
+        let numbers = |point: &Option<Pair<f64>>| match point {
+            Some(Pair::Text(text)) => match parse_complex(text, DecimalSeparator::Auto) {
+                Some(point) => Some(Pair::Numbers(point.re, point.im)),
+                None => point.clone(),
+            },
+            point => point.clone(),
+        };
+        let pixels = match &self.view.pixels {
+            Some(Pair::Text(text)) => match parse_pair(text, 'x') {
+                Some((width, height)) => Some(Pair::Numbers(width, height)),
+                None => self.view.pixels.clone(),
+            },
+            pixels => pixels.clone(),
+        };
+        let project = Project {
+            version: VERSION,
+            view: View {
+                pixels,
+                upper_left: numbers(&self.view.upper_left),
+                lower_right: numbers(&self.view.lower_right),
+                center: numbers(&self.view.center),
+                zoom: self.view.zoom,
+            },
+            ..self.clone()
+        };
+        serde_json::to_value(project).unwrap()
+    }
+}
+
+/// The path of the field `key` of the value at `path`, like
+/// `coloring.palette`.
+fn field(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        key.to_string()
+    } else {
+        format!("{}.{}", path, key)
+    }
+}
+
+/// Whether `value` is an array of objects, like keyframes, whose elements
+/// are compared one by one rather than as a whole, like the numbers of a
+/// point.
+fn is_list(value: Option<&Value>) -> bool {
+    match value {
+        Some(Value::Array(values)) => values.iter().all(Value::is_object),
+        _ => false,
+    }
+}
+
+/// `value` as it is written in differences and conflicts.
+fn describe(value: &Option<Value>) -> String {
+    match value {
+        Some(value) => value.to_string(),
+        None => "nothing".to_string(),
+    }
+}
+
+/// A setting two projects differ in.
+#[derive(Debug, PartialEq)]
+pub struct Difference {
+    /// Where it is, like `view.zoom` or `scene.keyframes[2].center`.
+    pub path: String,
+    /// Its value in the first project, if it has one.
+    pub old: Option<Value>,
+    /// Its value in the second project, if it has one.
+    pub new: Option<Value>,
+}
+
+impl std::fmt::Display for Difference {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match (&self.old, &self.new) {
+            (None, new) => write!(f, "{}: added {}", self.path, describe(new)),
+            (old, None) => write!(f, "{}: removed {}", self.path, describe(old)),
+            (old, new) => write!(f, "{}: {} -> {}", self.path, describe(old), describe(new)),
+        }
+    }
+}
+
+fn diff_values(path: &str, old: Option<&Value>, new: Option<&Value>, out: &mut Vec<Difference>) {
+    if old == new {
+        return;
+    }
+    match (old, new) {
+        (Some(Value::Object(old)), Some(Value::Object(new))) => {
+            let keys: std::collections::BTreeSet<_> = old.keys().chain(new.keys()).collect();
+            for key in keys {
+                diff_values(&field(path, key), old.get(key), new.get(key), out);
+            }
+        }
+        (Some(Value::Array(old_values)), Some(Value::Array(new_values)))
+            if is_list(old) && is_list(new) =>
+        {
+            for i in 0..old_values.len().max(new_values.len()) {
+                let path = format!("{}[{}]", path, i);
+                diff_values(&path, old_values.get(i), new_values.get(i), out);
+            }
+        }
+        _ => out.push(Difference {
+            path: path.to_string(),
+            old: old.cloned(),
+            new: new.cloned(),
+        }),
+    }
+}
+
+/// What changes from the project `old` to the project `new`, setting by
+/// setting, in the order of the format: the fields of the view, each option,
+/// each keyframe and each bookmark on its own. How the projects are written,
+/// their versions, the order of their fields and whether pairs are arrays or
+/// text make no difference.
+pub fn diff(old: &Project, new: &Project) -> Vec<Difference> {
+    let mut differences = Vec::new();
+    diff_values(
+        "",
+        Some(&old.normalized()),
+        Some(&new.normalized()),
+        &mut differences,
+    );
+    differences
+}
+
+#[test]
+fn test_diff() {
+    let old = Project::from_json(
+        r#"{"version": 1, "view": {"pixels": "640x480", "zoom": 20},
+            "coloring": {"palette": "fire", "glow": "3"},
+            "scene": {"keyframes": [{"center": [0, 0], "zoom": 1}, {"center": [0, 0], "zoom": 2}]}}"#,
+    )
+    .unwrap();
+    let new = Project::from_json(
+        r#"{"version": 1, "view": {"pixels": [640, 480], "zoom": 40},
+            "coloring": {"palette": "ocean", "bands": "8"},
+            "scene": {"keyframes": [{"center": [0, 0], "zoom": 1}, {"center": [0, 0.5], "zoom": 2},
+                                    {"center": [0, 0], "zoom": 4}]},
+            "bookmarks": {"spot": [0.25, 0]}}"#,
+    )
+    .unwrap();
+    let differences: Vec<String> = diff(&old, &new).iter().map(|d| d.to_string()).collect();
+    assert_eq!(
+        differences,
+        [
+            "bookmarks: added {\"spot\":[0.25,0.0]}",
+            "coloring.bands: added \"8\"",
+            "coloring.glow: removed \"3\"",
+            "coloring.palette: \"fire\" -> \"ocean\"",
+            "scene.keyframes[1].center: [0.0,0.0] -> [0.0,0.5]",
+            "scene.keyframes[2]: added {\"center\":[0.0,0.0],\"zoom\":4.0}",
+            "view.zoom: 20.0 -> 40.0",
+        ]
+    );
+    assert!(diff(&new, &new).is_empty());
+}
+
+/// A setting two projects merged changed from their common ancestor in
+/// different ways.
+#[derive(Debug, PartialEq)]
+pub struct Conflict {
+    /// Where it is, like `coloring.palette`.
+    pub path: String,
+    /// Its value in the first project, if it has one.
+    pub ours: Option<Value>,
+    /// Its value in the second project, if it has one.
+    pub theirs: Option<Value>,
+}
+
+impl std::fmt::Display for Conflict {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{}: {} or {}",
+            self.path,
+            describe(&self.ours),
+            describe(&self.theirs)
+        )
+    }
+}
+
+fn merge_values(
+    path: &str,
+    base: Option<&Value>,
+    ours: Option<&Value>,
+    theirs: Option<&Value>,
+    conflicts: &mut Vec<Conflict>,
+) -> Option<Value> {
+    if ours == theirs || theirs == base {
+        return ours.cloned();
+    }
+    if ours == base {
+        return theirs.cloned();
+    }
+    let empty = serde_json::Map::new();
+    match (base, ours, theirs) {
+        (base, Some(Value::Object(ours)), Some(Value::Object(theirs))) => {
+            let base = match base {
+                Some(Value::Object(base)) => base,
+                _ => &empty,
+            };
+            let keys: std::collections::BTreeSet<_> = ours.keys().chain(theirs.keys()).collect();
+            let mut merged = serde_json::Map::new();
+            for key in keys {
+                let path = field(path, key);
+                let value = merge_values(
+                    &path,
+                    base.get(key),
+                    ours.get(key),
+                    theirs.get(key),
+                    conflicts,
+                );
+                if let Some(value) = value {
+                    merged.insert(key.clone(), value);
+                }
+            }
+            Some(Value::Object(merged))
+        }
+        // keyframes edited on both sides but neither added nor removed
+        (
+            Some(Value::Array(base_values)),
+            Some(Value::Array(our_values)),
+            Some(Value::Array(their_values)),
+        ) if is_list(base)
+            && is_list(ours)
+            && is_list(theirs)
+            && base_values.len() == our_values.len()
+            && base_values.len() == their_values.len() =>
+        {
+            let merged = (0..base_values.len()).filter_map(|i| {
+                let path = format!("{}[{}]", path, i);
+                merge_values(
+                    &path,
+                    base_values.get(i),
+                    our_values.get(i),
+                    their_values.get(i),
+                    conflicts,
+                )
+            });
+            Some(Value::Array(merged.collect()))
+        }
+        _ => {
+            conflicts.push(Conflict {
+                path: path.to_string(),
+                ours: ours.cloned(),
+                theirs: theirs.cloned(),
+            });
+            ours.cloned()
+        }
+    }
+}
+
+/// Merge the projects `ours` and `theirs`, both changed from the project
+/// `base`: every setting changed on one side only is taken from that side,
+/// and settings changed the same way on both are taken once. A setting
+/// changed differently on both sides is a conflict, and so is a list of
+/// keyframes one side adds to or removes from while the other changes it.
+pub fn merge(base: &Project, ours: &Project, theirs: &Project) -> Result<Project> {
+    let mut conflicts = Vec::new();
+    let merged = merge_values(
+        "",
+        Some(&base.normalized()),
+        Some(&ours.normalized()),
+        Some(&theirs.normalized()),
+        &mut conflicts,
+    );
+    if !conflicts.is_empty() {
+        let conflicts: Vec<String> = conflicts.iter().map(Conflict::to_string).collect();
+        return Err(Error::Conflict(conflicts.join("; ")));
+    }
+    Project::from_value(merged.unwrap_or_default())
+}
+
+#[test]
+fn test_merge() {
+    let project = |json: &str| Project::from_json(json).unwrap();
+    let base = project(
+        r#"{"version": 1, "view": {"zoom": 20}, "coloring": {"palette": "fire"},
+            "scene": {"keyframes": [{"center": [0, 0], "zoom": 1}, {"center": [0, 0], "zoom": 2}]}}"#,
+    );
+    // one retouches the palette and the first keyframe, the other the glow
+    // and the second keyframe
+    let ours = project(
+        r#"{"version": 1, "view": {"zoom": 20}, "coloring": {"palette": "ocean"},
+            "scene": {"keyframes": [{"center": [0, 0.5], "zoom": 1}, {"center": [0, 0], "zoom": 2}]}}"#,
+    );
+    let theirs = project(
+        r#"{"version": 1, "view": {"zoom": 20}, "coloring": {"palette": "fire", "glow": "2"},
+            "scene": {"keyframes": [{"center": [0, 0], "zoom": 1}, {"center": [0, 0], "zoom": 3}]}}"#,
+    );
+    let merged = merge(&base, &ours, &theirs).unwrap();
+    assert_eq!(merged.coloring["palette"], "ocean");
+    assert_eq!(merged.coloring["glow"], "2");
+    let keyframes = &merged.scene.as_ref().unwrap().keyframes;
+    assert_eq!(keyframes[0].center, (0.0, 0.5));
+    assert_eq!(keyframes[1].zoom, 3.0);
+    assert_eq!(merge(&base, &ours, &base).unwrap(), ours);
+    assert_eq!(merge(&base, &ours, &ours).unwrap(), ours);
+
+    let conflicting =
+        project(r#"{"version": 1, "view": {"zoom": 30}, "coloring": {"palette": "gray"}}"#);
+    let error = merge(&base, &ours, &conflicting).unwrap_err().to_string();
+    assert!(
+        error.contains("coloring.palette: \"ocean\" or \"gray\""),
+        "{}",
+        error
+    );
+    assert!(error.contains("scene:"), "{}", error);
+    assert!(!error.contains("view.zoom"), "{}", error);
+}
@@ -0,0 +1,236 @@
+//! Multi-resolution tile pyramids, for `export-dzi`: the view rendered at
+//! its full size and at every half of it down to a single tile, each level
+//! cut into tiles, in the layout viewers such as OpenSeadragon and Leaflet
+//! read from plain files, with no server rendering them.
+//!
+//! A DeepZoom pyramid is a `.dzi` file describing the image, with the tiles
+//! in a directory beside it named after it, `deep_files/LEVEL/COL_ROW.png`,
+//! level 0 being a single pixel. An IIIF pyramid is a directory with the
+//! `info.json` of a level 0 Image API 2 service, and the tiles at the paths
+//! viewers ask such a service for, `X,Y,W,H/SIZE,/0/default.png`.
+
+/// The width and height of tiles unless told otherwise.
+pub const DEFAULT_TILE_SIZE: usize = 256;
+
+/// How the tiles of a pyramid are laid out.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Layout {
+    DeepZoom,
+    Iiif,
+}
+
+impl Layout {
+    /// The layout of the pyramid written to `file`: DeepZoom for a `.dzi`
+    /// file, and IIIF for anything else, a directory.
+    pub fn from_filename(file: &str) -> Layout {
+        if file.to_ascii_lowercase().ends_with(".dzi") {
+            Layout::DeepZoom
+        } else {
+            Layout::Iiif
+        }
+    }
+
+    /// The directory the tiles of the pyramid written to `file` go in.
+    pub fn tile_dir(self, file: &str) -> String {
+        match self {
+            Layout::DeepZoom => format!("{}_files", &file[..file.len() - ".dzi".len()]),
+            Layout::Iiif => file.trim_end_matches(['/', '\\']).to_string(),
+        }
+    }
+
+    /// The file describing the pyramid written to `file`.
+    pub fn descriptor_file(self, file: &str) -> String {
+        match self {
+            Layout::DeepZoom => file.to_string(),
+            Layout::Iiif => format!("{}/info.json", self.tile_dir(file)),
+        }
+    }
+}
+
+#[test]
+fn test_layout() {
+    let deep = Layout::from_filename("out/deep.DZI");
+    assert_eq!(deep, Layout::DeepZoom);
+    assert_eq!(deep.tile_dir("out/deep.DZI"), "out/deep_files");
+    assert_eq!(deep.descriptor_file("out/deep.DZI"), "out/deep.DZI");
+    let iiif = Layout::from_filename("out/deep/");
+    assert_eq!(iiif, Layout::Iiif);
+    assert_eq!(iiif.tile_dir("out/deep/"), "out/deep");
+    assert_eq!(iiif.descriptor_file("out/deep/"), "out/deep/info.json");
+}
+
+/// A tile of a pyramid.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Tile {
+    /// Where the tile goes, relative to the directory of the tiles.
+    pub path: String,
+    /// The dimensions of the whole level the tile is part of.
+    pub level: (usize, usize),
+    /// The pixel of the level at the upper left corner of the tile.
+    pub origin: (usize, usize),
+    pub bounds: (usize, usize),
+}
+
+/// The dimensions of an image whose dimensions are given by `bounds` shrunk
+/// `scale` times, rounding up.
+fn shrunk(bounds: (usize, usize), scale: usize) -> (usize, usize) {
+    (bounds.0.div_ceil(scale), bounds.1.div_ceil(scale))
+}
+
+/// How many times an image whose dimensions are given by `bounds` is shrunk
+/// at each level of its pyramid, from the full size down: by powers of two
+/// down to a pixel for DeepZoom, and down to a single tile `tile_size`
+/// pixels wide for IIIF.
+pub fn scales(layout: Layout, bounds: (usize, usize), tile_size: usize) -> Vec<usize> {
+    let smallest = match layout {
+        Layout::DeepZoom => 1,
+        Layout::Iiif => tile_size.max(1),
+    };
+    let mut scales = vec![1];
+    let mut scale = 1;
+    while shrunk(bounds, scale).0.max(shrunk(bounds, scale).1) > smallest {
+        scale *= 2;
+        scales.push(scale);
+    }
+    scales
+}
+
+/// Every tile of the pyramid of an image whose dimensions are given by
+/// `bounds`, `tile_size` pixels wide, level by level from the full size
+/// down.
+pub fn tiles(layout: Layout, bounds: (usize, usize), tile_size: usize) -> Vec<Tile> {
+    let scales = scales(layout, bounds, tile_size);
+    let mut tiles = Vec::new();
+    for (i, &scale) in scales.iter().enumerate() {
+        let level = shrunk(bounds, scale);
+        for (origin, tile_bounds) in crate::tiles::layout(level, tile_size) {
+            let path = match layout {
+                Layout::DeepZoom => format!(
+                    "{}/{}_{}.png",
+                    scales.len() - 1 - i,
+                    origin.0 / tile_size,
+                    origin.1 / tile_size
+                ),
+                // as OpenSeadragon asks for them: the level in one tile is
+                // the full region
+                Layout::Iiif if level.0 < tile_size && level.1 < tile_size => {
+                    format!("full/{},/0/default.png", level.0)
+                }
+                Layout::Iiif => {
+                    let (x, y) = (origin.0 * scale, origin.1 * scale);
+                    let region = (
+                        (tile_size * scale).min(bounds.0 - x),
+                        (tile_size * scale).min(bounds.1 - y),
+                    );
+                    format!(
+                        "{},{},{},{}/{},/0/default.png",
+                        x,
+                        y,
+                        region.0,
+                        region.1,
+                        region.0.div_ceil(scale)
+                    )
+                }
+            };
+            tiles.push(Tile {
+                path,
+                level,
+                origin,
+                bounds: tile_bounds,
+            });
+        }
+    }
+    tiles
+}
+
+#[test]
+fn test_tiles() {
+    assert_eq!(
+        scales(Layout::DeepZoom, (600, 300), 256),
+        [1, 2, 4, 8, 16, 32, 64, 128, 256, 512, 1024]
+    );
+    assert_eq!(scales(Layout::Iiif, (600, 300), 256), [1, 2, 4]);
+    assert_eq!(scales(Layout::Iiif, (200, 100), 256), [1]);
+
+    let deep = tiles(Layout::DeepZoom, (600, 300), 256);
+    // 3x2 tiles at full size, 2x1 at half and one for each level below
+    assert_eq!(deep.len(), 6 + 2 + 9);
+    assert_eq!(
+        deep[5],
+        Tile {
+            path: "10/2_1.png".to_string(),
+            level: (600, 300),
+            origin: (512, 256),
+            bounds: (88, 44),
+        }
+    );
+    assert_eq!(deep[7].path, "9/1_0.png");
+    assert_eq!(deep[7].bounds, (44, 150));
+    assert_eq!(deep[16].path, "0/0_0.png");
+    assert_eq!(deep[16].bounds, (1, 1));
+
+    let iiif = tiles(Layout::Iiif, (600, 300), 256);
+    let paths: Vec<&str> = iiif.iter().map(|tile| tile.path.as_str()).collect();
+    assert_eq!(
+        paths,
+        [
+            "0,0,256,256/256,/0/default.png",
+            "256,0,256,256/256,/0/default.png",
+            "512,0,88,256/88,/0/default.png",
+            "0,256,256,44/256,/0/default.png",
+            "256,256,256,44/256,/0/default.png",
+            "512,256,88,44/88,/0/default.png",
+            "0,0,512,300/256,/0/default.png",
+            "512,0,88,300/44,/0/default.png",
+            "full/150,/0/default.png",
+        ]
+    );
+}
+
+/// The file describing the pyramid of an image whose dimensions are given
+/// by `bounds`, with tiles `tile_size` pixels wide: the XML of a `.dzi`
+/// file, or the JSON of an IIIF `info.json` for the service at `id`, the
+/// URL the tiles are under.
+pub fn descriptor(layout: Layout, bounds: (usize, usize), tile_size: usize, id: &str) -> String {
+    match layout {
+        Layout::DeepZoom => format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+             <Image xmlns=\"http://schemas.microsoft.com/deepzoom/2008\" \
+             Format=\"png\" Overlap=\"0\" TileSize=\"{}\">\n  \
+             <Size Width=\"{}\" Height=\"{}\"/>\n\
+             </Image>\n",
+            tile_size, bounds.0, bounds.1
+        ),
+        Layout::Iiif => {
+            let info = serde_json::json!({
+                "@context": "http://iiif.io/api/image/2/context.json",
+                "@id": id,
+                "protocol": "http://iiif.io/api/image",
+                "width": bounds.0,
+                "height": bounds.1,
+                "profile": ["http://iiif.io/api/image/2/level0.json"],
+                "tiles": [{
+                    "width": tile_size,
+                    "scaleFactors": scales(layout, bounds, tile_size),
+                }],
+            });
+            serde_json::to_string_pretty(&info).expect("descriptors are plain values") + "\n"
+        }
+    }
+}
+
+#[test]
+fn test_descriptor() {
+    let dzi = descriptor(Layout::DeepZoom, (600, 300), 256, "");
+    assert!(dzi.contains("TileSize=\"256\""));
+    assert!(dzi.contains("<Size Width=\"600\" Height=\"300\"/>"));
+
+    let info: serde_json::Value =
+        serde_json::from_str(&descriptor(Layout::Iiif, (600, 300), 256, "deep")).unwrap();
+    assert_eq!(info["@id"], "deep");
+    assert_eq!(info["width"], 600);
+    assert_eq!(
+        info["tiles"][0]["scaleFactors"],
+        serde_json::json!([1, 2, 4])
+    );
+}
@@ -0,0 +1,113 @@
+//! The renderer, for Python: build with `maturin develop --release`, which
+//! reads `pyproject.toml`, and
+//!
+//! ```python
+//! import mandelbrot
+//! import matplotlib.pyplot as plt
+//! counts = mandelbrot.render(800, 600, center=(-0.75, 0.1), zoom=20, max_iters=1000)
+//! plt.imshow(counts, cmap="twilight")
+//! ```
+//!
+//! Unlike the images of the rest of the crate, which are gray levels, the
+//! array holds the iteration counts themselves, for Python to color and
+//! analyse as it likes.
+
+// the wrappers `pyfunction` writes convert errors to what they already are
+#![allow(clippy::useless_conversion)]
+
+use std::thread;
+
+use num::Complex;
+use numpy::{IntoPyArray, PyArray2, PyArrayMethods};
+use pyo3::{exceptions::PyValueError, prelude::*};
+
+use crate::{error, render, units::Zoom};
+
+/// How many iterations each pixel of the view from `upper_left` to
+/// `lower_right`, whose dimensions are `bounds`, took to escape, row after
+/// row, or `limit` for the points taken to be in the set.
+fn iterations(
+    bounds: (usize, usize),
+    upper_left: Complex<f64>,
+    lower_right: Complex<f64>,
+    limit: usize,
+    threads: usize,
+) -> Vec<u32> {
+    let mut counts = vec![0; bounds.0 * bounds.1];
+    if counts.is_empty() {
+        return counts;
+    }
+    let rows_per_band = bounds.1.div_ceil(threads.max(1));
+    thread::scope(|scope| {
+        for (band, rows) in counts.chunks_mut(rows_per_band * bounds.0).enumerate() {
+            scope.spawn(move || {
+                for (i, count) in rows.iter_mut().enumerate() {
+                    let pixel = (i % bounds.0, band * rows_per_band + i / bounds.0);
+                    let point = render::pixel_to_point(bounds, pixel, upper_left, lower_right);
+                    *count = render::escape_time(point, limit).unwrap_or(limit) as u32;
+                }
+            });
+        }
+    });
+    counts
+}
+
+#[test]
+fn test_iterations() {
+    let bounds = (9, 7);
+    let (upper_left, lower_right) =
+        render::corners_from_center(Complex::new(-0.5, 0.0), Zoom::NONE, bounds);
+    let counts = iterations(bounds, upper_left, lower_right, 100, 3);
+    assert_eq!(counts, iterations(bounds, upper_left, lower_right, 100, 1));
+    assert_eq!(counts.len(), 63);
+    // some points are in the set, the corners escape at once
+    assert!(counts.contains(&100));
+    assert!(counts[0] < 3 && counts[62] < 3);
+    assert!(iterations((0, 4), upper_left, lower_right, 100, 2).is_empty());
+}
+
+/// Render the view centered on `center`, magnified `zoom` times as with
+/// `--zoom`, into a `height` by `width` array of the iterations each pixel
+/// took to escape, `max_iters` for the points taken to be in the set.
+#[pyfunction]
+#[pyo3(
+    name = "render",
+    signature = (width, height, center = (-0.5, 0.0), zoom = 1.0, max_iters = 255)
+)]
+fn render_counts<'py>(
+    py: Python<'py>,
+    width: usize,
+    height: usize,
+    center: (f64, f64),
+    zoom: f64,
+    max_iters: usize,
+) -> PyResult<Bound<'py, PyArray2<u32>>> {
+    if !(zoom > 0.0 && zoom.is_finite()) {
+        return Err(PyValueError::new_err(format!(
+            "the zoom must be positive, not {}",
+            zoom
+        )));
+    }
+    if max_iters == 0 || max_iters > u32::MAX as usize {
+        return Err(PyValueError::new_err(format!(
+            "invalid number of iterations {}",
+            max_iters
+        )));
+    }
+    let bounds = (width, height);
+    let center = Complex::new(center.0, center.1);
+    let (upper_left, lower_right) = render::corners_from_center(center, Zoom(zoom), bounds);
+    error::validate_viewport(bounds, upper_left, lower_right)
+        .map_err(|e| PyValueError::new_err(e.to_string()))?;
+
+    // other Python threads go on while the cores are busy
+    let counts = py
+        .allow_threads(|| iterations(bounds, upper_left, lower_right, max_iters, num_cpus::get()));
+    counts.into_pyarray_bound(py).reshape([height, width])
+}
+
+/// The `mandelbrot` Python module.
+#[pymodule]
+fn mandelbrot(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_function(wrap_pyfunction!(render_counts, module)?)
+}
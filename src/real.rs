@@ -0,0 +1,374 @@
+//! The numbers the escape times can be computed in, for `--numeric`.
+//!
+//! `f64` is the default. `f32` is faster, and good enough for shallow views;
+//! `DoubleDouble` keeps about 106 bits, as the unevaluated sum of two `f64`,
+//! which lets views go a hundred times deeper before neighboring pixels
+//! collapse onto the same point. The corners of the view are still `f64`,
+//! so it stops helping around a zoom of 1e15, where they collapse too.
+
+use std::{
+    fmt,
+    ops::{Add, Mul, Neg, Sub},
+    str::FromStr,
+};
+
+use num::Complex;
+
+use crate::{
+    render::{gray_level, Schedule},
+    usage,
+};
+
+/// What the orbits need of a number type.
+pub trait Real:
+    Copy + PartialOrd + Send + Sync + Add<Output = Self> + Sub<Output = Self> + Mul<Output = Self>
+{
+    fn from_f64(x: f64) -> Self;
+    fn to_f64(self) -> f64;
+}
+
+impl Real for f32 {
+    fn from_f64(x: f64) -> f32 {
+        x as f32
+    }
+
+    fn to_f64(self) -> f64 {
+        self as f64
+    }
+}
+
+impl Real for f64 {
+    fn from_f64(x: f64) -> f64 {
+        x
+    }
+
+    fn to_f64(self) -> f64 {
+        self
+    }
+}
+
+/// A number kept as the sum of two `f64`, the second smaller than half a
+/// unit in the last place of the first.
+#[derive(Clone, Copy, Debug, Default, PartialEq, PartialOrd)]
+pub struct DoubleDouble {
+    hi: f64,
+    lo: f64,
+}
+
+/// `a + b` and the rounding error of the sum, exactly.
+fn two_sum(a: f64, b: f64) -> DoubleDouble {
+    let hi = a + b;
+    let b_part = hi - a;
+    let lo = (a - (hi - b_part)) + (b - b_part);
+    DoubleDouble { hi, lo }
+}
+
+/// Like `two_sum`, for `a` at least as large as `b`.
+fn quick_two_sum(a: f64, b: f64) -> DoubleDouble {
+    let hi = a + b;
+    DoubleDouble {
+        hi,
+        lo: b - (hi - a),
+    }
+}
+
+impl Add for DoubleDouble {
+    type Output = DoubleDouble;
+
+    fn add(self, other: DoubleDouble) -> DoubleDouble {
+        let sum = two_sum(self.hi, other.hi);
+        quick_two_sum(sum.hi, sum.lo + self.lo + other.lo)
+    }
+}
+
+impl Neg for DoubleDouble {
+    type Output = DoubleDouble;
+
+    fn neg(self) -> DoubleDouble {
+        DoubleDouble {
+            hi: -self.hi,
+            lo: -self.lo,
+        }
+    }
+}
+
+impl Sub for DoubleDouble {
+    type Output = DoubleDouble;
+
+    fn sub(self, other: DoubleDouble) -> DoubleDouble {
+        self + -other
+    }
+}
+
+impl Mul for DoubleDouble {
+    type Output = DoubleDouble;
+
+    fn mul(self, other: DoubleDouble) -> DoubleDouble {
+        let product = self.hi * other.hi;
+        // the fused multiply-add gives the rounding error of the product
+        let error = self.hi.mul_add(other.hi, -product);
+        quick_two_sum(product, error + (self.hi * other.lo + self.lo * other.hi))
+    }
+}
+
+impl Real for DoubleDouble {
+    fn from_f64(x: f64) -> DoubleDouble {
+        DoubleDouble { hi: x, lo: 0.0 }
+    }
+
+    fn to_f64(self) -> f64 {
+        self.hi + self.lo
+    }
+}
+
+#[test]
+fn test_double_double() {
+    let one = DoubleDouble::from_f64(1.0);
+    let tiny = DoubleDouble::from_f64(1e-20);
+    // lost in f64, kept here
+    assert_eq!(1.0 + 1e-20 - 1.0, 0.0);
+    assert_eq!(((one + tiny) - one).to_f64(), 1e-20);
+
+    let third = DoubleDouble::from_f64(1.0 / 3.0);
+    let square = third * third;
+    assert!((square.to_f64() - 1.0 / 9.0).abs() < 1e-17);
+    assert!(one > tiny && -one < tiny);
+}
+
+/// The number type escape times are computed in.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Numeric {
+    F32,
+    #[default]
+    F64,
+    DoubleDouble,
+}
+
+impl FromStr for Numeric {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Numeric, String> {
+        match s {
+            "f32" => Ok(Numeric::F32),
+            "f64" => Ok(Numeric::F64),
+            "dd" => Ok(Numeric::DoubleDouble),
+            _ => Err(format!(
+                "unknown numeric type {}, expected f32, f64 or dd",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Numeric {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Numeric::F32 => "f32",
+            Numeric::F64 => "f64",
+            Numeric::DoubleDouble => "dd",
+        })
+    }
+}
+
+impl Numeric {
+    /// Whether the pixels of the view from `upper_left` to `lower_right`,
+    /// `width` pixels across, are still told apart in these numbers: in `f32`
+    /// and `f64`, a pixel has to be wider than the gap between neighboring
+    /// numbers around the corners, and in `DoubleDouble`, whose corners are
+    /// still `f64`, the whole view a few such gaps wide.
+    pub fn resolves(
+        self,
+        upper_left: Complex<f64>,
+        lower_right: Complex<f64>,
+        width: usize,
+    ) -> bool {
+        let magnitude = [upper_left.re, upper_left.im, lower_right.re, lower_right.im]
+            .iter()
+            .fold(f64::MIN_POSITIVE, |magnitude, x| magnitude.max(x.abs()));
+        let view = lower_right.re - upper_left.re;
+        match self {
+            Numeric::F32 => view / width as f64 > 2.0 * f32::EPSILON as f64 * magnitude,
+            Numeric::F64 => view / width as f64 > 2.0 * f64::EPSILON * magnitude,
+            Numeric::DoubleDouble => view > 16.0 * f64::EPSILON * magnitude,
+        }
+    }
+}
+
+#[test]
+fn test_resolves() {
+    use crate::{render::corners_from_center, units::Zoom};
+
+    let view = |zoom| corners_from_center(Complex::new(-0.75, 0.1), Zoom(zoom), (1000, 750));
+    let resolves = |numeric: Numeric, zoom| {
+        let (upper_left, lower_right) = view(zoom);
+        numeric.resolves(upper_left, lower_right, 1000)
+    };
+    assert!(resolves(Numeric::F32, 1e2));
+    assert!(!resolves(Numeric::F32, 1e5));
+    // as the README says: f64 to about 1e13, dd to about 1e15
+    assert!(resolves(Numeric::F64, 1e12));
+    assert!(!resolves(Numeric::F64, 1e14));
+    assert!(resolves(Numeric::DoubleDouble, 1e14));
+    assert!(!resolves(Numeric::DoubleDouble, 1e16));
+}
+
+#[test]
+fn test_parse_numeric() {
+    assert_eq!("f32".parse(), Ok(Numeric::F32));
+    assert_eq!("dd".parse(), Ok(Numeric::DoubleDouble));
+    assert_eq!("f64".parse(), Ok(Numeric::default()));
+    assert!("f128".parse::<Numeric>().is_err());
+}
+
+/// Like `render::escape_time_with_bailout`, computing in `R`, without the
+/// detection of cycles, which depends on the precision.
+pub fn escape_time<R: Real>(c: (R, R), limit: usize, bailout: f64) -> Option<usize> {
+    let bailout = R::from_f64(bailout * bailout);
+    let (mut re, mut im) = (R::from_f64(0.0), R::from_f64(0.0));
+    for i in 0..limit {
+        let (re2, im2) = (re * re, im * im);
+        if re2 + im2 > bailout {
+            usage::count_iterations(i);
+            return Some(i);
+        }
+        im = (re + re) * im + c.1;
+        re = re2 - im2 + c.0;
+    }
+    usage::count_iterations(limit);
+    None
+}
+
+#[test]
+fn test_escape_time() {
+    for c in [
+        Complex::new(0.3, 0.5),
+        Complex::new(-0.75, 0.1),
+        Complex::new(-2.5, 1.0),
+    ] {
+        let expected = crate::render::escape_time(c, 1000);
+        assert_eq!(escape_time((c.re, c.im), 1000, 2.0), expected);
+        let dd = (DoubleDouble::from_f64(c.re), DoubleDouble::from_f64(c.im));
+        assert_eq!(escape_time(dd, 1000, 2.0), expected);
+        assert_eq!(
+            escape_time(dd, 1000, 100.0),
+            crate::render::escape_time_with_bailout(c, 1000, 100.0)
+        );
+    }
+    assert_eq!(escape_time((0.0f32, 0.0f32), 1000, 2.0), None);
+}
+
+/// Render the rows of the view from `upper_left` to `lower_right`, in an
+/// image whose dimensions are given by `bounds`, that `pixels` holds,
+/// starting with row `top`, computing in `schedule.numeric` up to
+/// `schedule.limit` iterations.
+///
+/// Every point is computed in `numeric` from the corners and its offset
+/// from them, so that the points of neighboring pixels stay apart even
+/// where the corners and the offsets on their own can't tell them apart.
+pub fn render_rows(
+    pixels: &mut [u8],
+    bounds: (usize, usize),
+    top: usize,
+    upper_left: Complex<f64>,
+    lower_right: Complex<f64>,
+    schedule: &Schedule,
+) {
+    let render = match schedule.numeric {
+        Numeric::F32 => render_rows_in::<f32>,
+        Numeric::F64 => render_rows_in::<f64>,
+        Numeric::DoubleDouble => render_rows_in::<DoubleDouble>,
+    };
+    render(pixels, bounds, top, upper_left, lower_right, schedule);
+}
+
+fn render_rows_in<R: Real>(
+    pixels: &mut [u8],
+    bounds: (usize, usize),
+    top: usize,
+    upper_left: Complex<f64>,
+    lower_right: Complex<f64>,
+    schedule: &Schedule,
+) {
+    let (limit, bailout) = (schedule.limit, schedule.bailout);
+    let step = (
+        (lower_right.re - upper_left.re) / bounds.0 as f64,
+        (upper_left.im - lower_right.im) / bounds.1 as f64,
+    );
+    let (left, upper) = (R::from_f64(upper_left.re), R::from_f64(upper_left.im));
+    let mut costs = Vec::new();
+    for (i, pixel) in pixels.iter_mut().enumerate() {
+        let (column, row) = (i % bounds.0, top + i / bounds.0);
+        let c = (
+            left + R::from_f64(column as f64 * step.0),
+            upper - R::from_f64(row as f64 * step.1),
+        );
+        let (count, cost) = usage::counting(|| escape_time(c, limit, bailout));
+        *pixel = gray_level(count, limit);
+        costs.push(cost);
+    }
+    if let Some(cost) = schedule.cost {
+        cost.set_block((0, top), bounds.0, &costs);
+    }
+}
+
+#[test]
+fn test_render_rows() {
+    let bounds = (40, 30);
+    let center = Complex::new(-0.743643887037151, 0.131825904205330);
+
+    // shallow, f64 and dd draw what `render` does, and f32 nearly
+    let (upper_left, lower_right) =
+        crate::render::corners_from_center(center, crate::units::Zoom(100.0), bounds);
+    let mut expected = vec![0; bounds.0 * bounds.1];
+    crate::render::render(&mut expected, bounds, upper_left, lower_right);
+    let mut pixels = vec![0; bounds.0 * bounds.1];
+    for numeric in [Numeric::F64, Numeric::DoubleDouble] {
+        let schedule = Schedule {
+            numeric,
+            ..Schedule::new(1)
+        };
+        render_rows(&mut pixels, bounds, 0, upper_left, lower_right, &schedule);
+        assert_eq!(pixels, expected, "{:?}", numeric);
+    }
+    // one row, the tenth, on its own
+    let mut row = vec![0; bounds.0];
+    let schedule = Schedule {
+        numeric: Numeric::F32,
+        ..Schedule::new(1)
+    };
+    render_rows(&mut row, bounds, 10, upper_left, lower_right, &schedule);
+    let close = row
+        .iter()
+        .zip(&expected[10 * bounds.0..11 * bounds.0])
+        .filter(|(a, b)| a.abs_diff(**b) <= 1)
+        .count();
+    assert!(close > bounds.0 * 3 / 4, "{:?}", row);
+
+    // deep, neighboring points in f64 are the same point, in pairs or more
+    let (upper_left, lower_right) =
+        crate::render::corners_from_center(center, crate::units::Zoom(1e15), bounds);
+    let distinct = |numeric| {
+        let mut row = vec![0; bounds.0];
+        let schedule = Schedule {
+            numeric,
+            limit: 5000,
+            ..Schedule::new(1)
+        };
+        render_rows(
+            &mut row,
+            bounds,
+            bounds.1 / 2,
+            upper_left,
+            lower_right,
+            &schedule,
+        );
+        row.dedup();
+        row.len()
+    };
+    assert!(
+        distinct(Numeric::DoubleDouble) > distinct(Numeric::F64),
+        "{} {}",
+        distinct(Numeric::DoubleDouble),
+        distinct(Numeric::F64)
+    );
+}
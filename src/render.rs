@@ -0,0 +1,1725 @@
+//! Turning a region of the complex plane into grayscale pixels.
+
+use std::{
+    fmt,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Mutex,
+    },
+    time::Instant,
+};
+
+use num::Complex;
+
+use crate::{
+    cpu, kernel,
+    progress::Progress,
+    real::{self, Numeric},
+    sampling::{render_supersampled, Sampling, Variance},
+    traversal::{self, Traversal},
+    units::Zoom,
+    usage::{self, Cost, Usage},
+};
+
+/// How far from 0 an orbit has to get to escape unless told otherwise: the
+/// smallest radius no orbit comes back from.
+pub const DEFAULT_BAILOUT: f64 = 2.0;
+
+/// try to determine if `c` is in the Mandlebrot set, using at most `limit`
+/// iterations to decide.
+///
+/// If `c` is not a member, returns `Some(i)`, where `i` is the number of
+/// iterations it tok for `c` to leave the circle of radius 2 centered on the origin.
+/// If `c` seems to be a member (more precisely, if we reached the iteration limit without
+/// being able to prove that `c` is not a member), return `None`.
+///
+/// Points inside the set usually settle into a cycle, which would otherwise always
+/// cost `limit` iterations. Following Brent's cycle detection, `z` is saved at
+/// every power of two iterations and, as soon as the orbit comes back to the saved
+/// value, the search stops and `None` is returned.
+pub fn escape_time(c: Complex<f64>, limit: usize) -> Option<usize> {
+    escape_time_with_bailout(c, limit, DEFAULT_BAILOUT)
+}
+
+/// Like `escape_time`, with orbits escaping once they leave the circle of
+/// radius `bailout` instead, which must be at least 2. Larger radii take a
+/// few more iterations, and leave the orbits far enough out for smooth
+/// coloring to be accurate.
+///
+/// The loop is `kernel::escape_time_periodic`, whose arithmetic ports can
+/// match bit-for-bit.
+pub fn escape_time_with_bailout(c: Complex<f64>, limit: usize, bailout: f64) -> Option<usize> {
+    let (escape, iterations) = kernel::escape_time_periodic((c.re, c.im), limit, bailout);
+    usage::count_iterations(iterations);
+    escape
+}
+
+#[test]
+fn test_escape_time() {
+    fn naive_escape_time(c: Complex<f64>, limit: usize) -> Option<usize> {
+        let mut z = Complex { re: 0.0, im: 0.0 };
+        for i in 0..limit {
+            if z.norm_sqr() > 4.0 {
+                return Some(i);
+            }
+            z = z * z + c;
+        }
+        None
+    }
+
+    for row in 0..60 {
+        for column in 0..80 {
+            let c = pixel_to_point(
+                (80, 60),
+                (column, row),
+                Complex { re: -2.0, im: 1.2 },
+                Complex { re: 0.6, im: -1.2 },
+            );
+            assert_eq!(escape_time(c, 255), naive_escape_time(c, 255), "{}", c);
+        }
+    }
+
+    // interior points in cycles bail out long before the limit is reached
+    assert_eq!(escape_time(Complex { re: 0.0, im: 0.0 }, usize::MAX), None);
+    assert_eq!(escape_time(Complex { re: -1.0, im: 0.0 }, usize::MAX), None);
+    assert_eq!(escape_time(Complex { re: -0.1, im: 0.1 }, usize::MAX), None);
+}
+
+#[test]
+fn test_escape_time_with_bailout() {
+    let c = Complex { re: 0.3, im: 0.6 };
+    let escape = escape_time(c, 1000).unwrap();
+    // farther out takes longer, but not much: orbits grow fast out there
+    let far = escape_time_with_bailout(c, 1000, 1000.0).unwrap();
+    assert!(far > escape && far < escape + 5, "{} {}", escape, far);
+    assert_eq!(
+        escape_time_with_bailout(c, 1000, DEFAULT_BAILOUT),
+        Some(escape)
+    );
+    assert_eq!(
+        escape_time_with_bailout(Complex { re: -1.0, im: 0.0 }, 1000, 1000.0),
+        None
+    );
+}
+
+/// Given the row and the column of a pixel in the output image, return
+/// the corresponding point on the complex plane.
+///
+/// `bounds` is a pair giving the width and the height of the image in pixels.
+/// `pixel` is a (column,row) pair inidicating a particular pixel in that image.
+/// The `upper_left` and `lower_right` parameters are points on the complex plane
+/// designating the area our image covers.
+pub fn pixel_to_point(
+    bounds: (usize, usize),
+    pixel: (usize, usize),
+    upper_left: Complex<f64>,
+    lower_right: Complex<f64>,
+) -> Complex<f64> {
+    let (width, height) = (
+        lower_right.re - upper_left.re,
+        upper_left.im - lower_right.im,
+    );
+
+    Complex {
+        re: upper_left.re + pixel.0 as f64 * width / bounds.0 as f64,
+        im: upper_left.im - pixel.1 as f64 * height / bounds.1 as f64,
+        // pixel.1 increases as we go down, the imaginary component increases as we go up
+    }
+}
+
+#[test]
+fn test_pixel_to_point() {
+    assert_eq!(
+        pixel_to_point(
+            (100, 200),
+            (25, 175),
+            Complex { re: -1.0, im: 1.0 },
+            Complex { re: 1.0, im: -1.0 }
+        ),
+        Complex {
+            re: -0.5,
+            im: -0.75
+        }
+    );
+}
+
+#[cfg(test)]
+proptest::proptest! {
+    #[test]
+    fn test_pixel_to_point_corners_and_order(
+        width in 1..2000usize,
+        height in 1..2000usize,
+        left in -4.0..4.0f64,
+        top in -4.0..4.0f64,
+        size in 1e-9..4.0f64,
+        x in 0..2000usize,
+        y in 0..2000usize,
+    ) {
+        let bounds = (width, height);
+        let upper_left = Complex::new(left, top);
+        let lower_right = Complex::new(left + size, top - size * height as f64 / width as f64);
+        let point = |pixel| pixel_to_point(bounds, pixel, upper_left, lower_right);
+
+        // the upper left pixel is at the upper left corner, and one past the
+        // lower right one at the lower right corner
+        proptest::prop_assert_eq!(point((0, 0)), upper_left);
+        let corner = point(bounds);
+        proptest::prop_assert!((corner - lower_right).norm() <= 1e-12 * (1.0 + left.abs() + top.abs()));
+
+        // right is further along the real axis, down further down the
+        // imaginary one
+        let (x, y) = (x % width, y % height);
+        let (here, right, down) = (point((x, y)), point((x + 1, y)), point((x, y + 1)));
+        proptest::prop_assert!(right.re > here.re && right.im == here.im);
+        proptest::prop_assert!(down.im < here.im && down.re == here.re);
+    }
+}
+
+/// Given the column and the row of a pixel of an exponential map of the
+/// plane around `center`, `columns` pixels wide, return the point it shows.
+///
+/// Columns go once around `center`, counterclockwise from the right, and
+/// rows go in towards it: the natural logarithm of the distance of row 0
+/// from `center` is `log_radius`, and each row is closer by the angle
+/// between two columns, in logarithm, so that pixels are square on the plane
+/// wherever they are. Zooming in by a factor `k` is moving `ln k` times
+/// `columns / 2π` rows down.
+pub fn exp_map_to_point(
+    columns: usize,
+    pixel: (usize, usize),
+    center: Complex<f64>,
+    log_radius: f64,
+) -> Complex<f64> {
+    let step = std::f64::consts::TAU / columns as f64;
+    let radius = (log_radius - pixel.1 as f64 * step).exp();
+    center + Complex::from_polar(radius, pixel.0 as f64 * step)
+}
+
+#[test]
+fn test_exp_map_to_point() {
+    let center = Complex { re: -0.5, im: 0.25 };
+    let close = |a: Complex<f64>, b: Complex<f64>| (a - b).norm() < 1e-12;
+    assert!(close(
+        exp_map_to_point(4, (0, 0), center, 0.0),
+        Complex { re: 0.5, im: 0.25 }
+    ));
+    assert!(close(
+        exp_map_to_point(4, (1, 0), center, 0.0),
+        Complex { re: -0.5, im: 1.25 }
+    ));
+    // a quarter turn in, the radius shrinks by e^(π/2)
+    let point = exp_map_to_point(4, (2, 1), center, 2.0);
+    assert!(close(
+        point,
+        center - (2.0 - std::f64::consts::FRAC_PI_2).exp()
+    ));
+}
+
+/// The width, on the complex plane, of a view with a zoom of 1.
+pub const UNZOOMED_WIDTH: f64 = 4.0;
+
+/// Return the upper left and lower right corners of the view centered on
+/// `center`, magnified `zoom` times, for an image whose width and height in
+/// pixels are given by `bounds`.
+///
+/// Without magnification the view is `UNZOOMED_WIDTH` wide, and its height
+/// follows from the aspect ratio of `bounds`, so pixels stay square.
+pub fn corners_from_center(
+    center: Complex<f64>,
+    zoom: Zoom,
+    bounds: (usize, usize),
+) -> (Complex<f64>, Complex<f64>) {
+    let width = zoom.width().0;
+    let height = width * bounds.1 as f64 / bounds.0 as f64;
+    let half = Complex {
+        re: width / 2.0,
+        im: -height / 2.0,
+    };
+    (center - half, center + half)
+}
+
+#[test]
+fn test_corners_from_center() {
+    assert_eq!(
+        corners_from_center(Complex { re: -0.5, im: 0.0 }, Zoom(2.0), (200, 100)),
+        (Complex { re: -1.5, im: 0.5 }, Complex { re: 0.5, im: -0.5 })
+    );
+}
+
+/// Render a rectangle of the Mandelbrot set into a buffer of pixels.
+///
+/// The `bounds` argument gives the width and the height of the buffer `pixels`,
+/// which holds one grayscale pizel per byte. The `upper_left` and `lower_right`
+/// arguments specify points on the complex plane corresponding to the upper-left
+/// and lower-right corners of the pixel buffer.
+pub fn render(
+    pixels: &mut [u8],
+    bounds: (usize, usize),
+    upper_left: Complex<f64>,
+    lower_right: Complex<f64>,
+) {
+    render_with_limit(
+        Strategy::Naive,
+        pixels,
+        bounds,
+        upper_left,
+        lower_right,
+        DEFAULT_LIMIT,
+    );
+}
+
+/// The iteration limit of grayscale renders unless told otherwise: one
+/// escape time per gray level.
+pub const DEFAULT_LIMIT: usize = 255;
+
+/// The gray level of a point that escaped after `count` iterations, out of
+/// at most `limit`, or that is taken to be in the set if `count` is `None`.
+///
+/// Points in the set are black (0), and the longer the others take to
+/// escape, the darker they are. With the default limit every escape time has
+/// a gray level of its own; higher limits share them out evenly.
+pub fn gray_level(count: Option<usize>, limit: usize) -> u8 {
+    match count {
+        Some(count) => 255 - (count * 255 / limit.max(1)) as u8,
+        None => 0,
+    }
+}
+
+#[test]
+fn test_gray_level() {
+    assert_eq!(gray_level(None, 255), 0);
+    assert_eq!(gray_level(Some(0), 255), 255);
+    assert_eq!(gray_level(Some(10), 255), 245);
+    assert_eq!(gray_level(Some(500), 1000), 128);
+}
+
+/// Compute the grayscale value of a single pixel, see `gray_level`.
+fn render_pixel(
+    bounds: (usize, usize),
+    pixel: (usize, usize),
+    upper_left: Complex<f64>,
+    lower_right: Complex<f64>,
+    limit: usize,
+    bailout: f64,
+) -> u8 {
+    let point = pixel_to_point(bounds, pixel, upper_left, lower_right);
+    gray_level(escape_time_with_bailout(point, limit, bailout), limit)
+}
+
+/// The available algorithms to fill a buffer of pixels.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Strategy {
+    /// Compute every pixel on its own, see `render`.
+    Naive,
+    /// Skip rectangles whose border is all the same color, see `render_border_trace`.
+    BorderTrace,
+    /// Interpolate cells whose corners are at most `max_error` gray levels
+    /// apart, see `render_adaptive`.
+    Adaptive { max_error: u8 },
+}
+
+impl FromStr for Strategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Strategy, String> {
+        match s {
+            "naive" => Ok(Strategy::Naive),
+            "border-trace" => Ok(Strategy::BorderTrace),
+            "adaptive" => Ok(Strategy::Adaptive { max_error: 0 }),
+            _ => match s.strip_prefix("adaptive:").map(str::parse) {
+                Some(Ok(max_error)) => Ok(Strategy::Adaptive { max_error }),
+                _ => Err(format!("unknown render strategy {}", s)),
+            },
+        }
+    }
+}
+
+impl fmt::Display for Strategy {
+    /// Write the strategy as `--strategy` takes it, with the maximum error
+    /// of `Strategy::Adaptive` after a colon if it has one, `adaptive:4`, so
+    /// that the strategies sent to workers keep it.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Strategy::Naive => f.write_str("naive"),
+            Strategy::BorderTrace => f.write_str("border-trace"),
+            Strategy::Adaptive { max_error: 0 } => f.write_str("adaptive"),
+            Strategy::Adaptive { max_error } => write!(f, "adaptive:{}", max_error),
+        }
+    }
+}
+
+#[test]
+fn test_parse_strategy() {
+    assert_eq!("naive".parse(), Ok(Strategy::Naive));
+    assert_eq!("border-trace".parse(), Ok(Strategy::BorderTrace));
+    assert!("fast".parse::<Strategy>().is_err());
+    assert!("adaptive:300".parse::<Strategy>().is_err());
+    for strategy in [
+        Strategy::BorderTrace,
+        Strategy::Adaptive { max_error: 0 },
+        Strategy::Adaptive { max_error: 4 },
+    ] {
+        assert_eq!(strategy.to_string().parse(), Ok(strategy));
+    }
+}
+
+/// The available images, each with a renderer of its own.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Fractal {
+    /// The Mandelbrot set, colored by escape time.
+    Mandelbrot,
+    /// The density of escaping orbits, see `buddhabrot::render`.
+    Buddhabrot,
+    /// The density of the orbits that don't escape.
+    AntiBuddhabrot,
+    /// Three Buddhabrots of different escape times in the red, green and
+    /// blue channels, see `buddhabrot::render_nebulabrot`.
+    Nebulabrot,
+    /// The density of the escaping orbits starting within the view, tone
+    /// mapped like a fractal flame, see `buddhabrot::render_flame`.
+    Flame,
+    /// The basins of Newton's method for a polynomial, see `newton::render`.
+    Newton,
+    /// The escape times of a formula given on the command line, see
+    /// `formula::render`.
+    Formula,
+}
+
+impl FromStr for Fractal {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Fractal, String> {
+        match s {
+            "mandelbrot" => Ok(Fractal::Mandelbrot),
+            "buddhabrot" => Ok(Fractal::Buddhabrot),
+            "anti-buddhabrot" => Ok(Fractal::AntiBuddhabrot),
+            "nebulabrot" => Ok(Fractal::Nebulabrot),
+            "flame" => Ok(Fractal::Flame),
+            "newton" => Ok(Fractal::Newton),
+            "formula" => Ok(Fractal::Formula),
+            _ => Err(format!("unknown fractal {}", s)),
+        }
+    }
+}
+
+impl fmt::Display for Fractal {
+    /// Write the fractal as `--fractal` takes it.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Fractal::Mandelbrot => "mandelbrot",
+            Fractal::Buddhabrot => "buddhabrot",
+            Fractal::AntiBuddhabrot => "anti-buddhabrot",
+            Fractal::Nebulabrot => "nebulabrot",
+            Fractal::Flame => "flame",
+            Fractal::Newton => "newton",
+            Fractal::Formula => "formula",
+        })
+    }
+}
+
+#[test]
+fn test_parse_fractal() {
+    assert_eq!("mandelbrot".parse(), Ok(Fractal::Mandelbrot));
+    assert_eq!("buddhabrot".parse(), Ok(Fractal::Buddhabrot));
+    assert_eq!("nebulabrot".parse(), Ok(Fractal::Nebulabrot));
+    assert_eq!("formula".parse(), Ok(Fractal::Formula));
+    assert_eq!("flame".parse(), Ok(Fractal::Flame));
+    assert!("julia".parse::<Fractal>().is_err());
+    assert_eq!(
+        Fractal::AntiBuddhabrot.to_string().parse(),
+        Ok(Fractal::AntiBuddhabrot)
+    );
+}
+
+/// Render a rectangle of the Mandelbrot set into `pixels` using `strategy`.
+///
+/// The arguments are the same as for `render`.
+pub fn render_with(
+    strategy: Strategy,
+    pixels: &mut [u8],
+    bounds: (usize, usize),
+    upper_left: Complex<f64>,
+    lower_right: Complex<f64>,
+) {
+    render_with_limit(
+        strategy,
+        pixels,
+        bounds,
+        upper_left,
+        lower_right,
+        DEFAULT_LIMIT,
+    );
+}
+
+/// Render a rectangle of the Mandelbrot set into `pixels` using `strategy`,
+/// giving up on points that haven't escaped after `limit` iterations.
+///
+/// The other arguments are the same as for `render`.
+pub fn render_with_limit(
+    strategy: Strategy,
+    pixels: &mut [u8],
+    bounds: (usize, usize),
+    upper_left: Complex<f64>,
+    lower_right: Complex<f64>,
+    limit: usize,
+) {
+    render_with_bailout(
+        strategy,
+        pixels,
+        bounds,
+        upper_left,
+        lower_right,
+        limit,
+        DEFAULT_BAILOUT,
+    );
+}
+
+/// Like `render_with_limit`, with orbits escaping once they leave the
+/// circle of radius `bailout`, see `escape_time_with_bailout`.
+///
+/// The set being symmetric about the real axis, so are views centered on
+/// it: only their top half is computed, and mirrored into the bottom half,
+/// see `mirrored_rows`.
+pub fn render_with_bailout(
+    strategy: Strategy,
+    pixels: &mut [u8],
+    bounds: (usize, usize),
+    upper_left: Complex<f64>,
+    lower_right: Complex<f64>,
+    limit: usize,
+    bailout: f64,
+) {
+    render_costed(
+        strategy,
+        pixels,
+        None,
+        bounds,
+        upper_left,
+        lower_right,
+        limit,
+        bailout,
+    );
+}
+
+/// `render_with_bailout`, keeping the iterations spent on every pixel in
+/// `costs`, if given, see `usage::Cost`.
+#[allow(clippy::too_many_arguments)]
+fn render_costed(
+    strategy: Strategy,
+    pixels: &mut [u8],
+    mut costs: Option<&mut [u32]>,
+    bounds: (usize, usize),
+    upper_left: Complex<f64>,
+    lower_right: Complex<f64>,
+    limit: usize,
+    bailout: f64,
+) {
+    assert!(pixels.len() == bounds.0 * bounds.1);
+
+    let rows = mirrored_rows(bounds, upper_left, lower_right).unwrap_or(bounds.1);
+    let top = (bounds.0, rows);
+    match strategy {
+        Strategy::Naive => {
+            // a row at a time, for the kernel of the processor to take the
+            // points several at once
+            let mut points = vec![(0.0, 0.0); bounds.0];
+            let mut results = vec![(None, 0); bounds.0];
+            for row in 0..rows {
+                for (column, point) in points.iter_mut().enumerate() {
+                    let c = pixel_to_point(bounds, (column, row), upper_left, lower_right);
+                    *point = (c.re, c.im);
+                }
+                cpu::escape_times(&points, limit, bailout, &mut results);
+                let start = row * bounds.0;
+                for (column, &(escape, steps)) in results.iter().enumerate() {
+                    pixels[start + column] = gray_level(escape, limit);
+                    if let Some(costs) = costs.as_deref_mut() {
+                        costs[start + column] = steps as u32;
+                    }
+                }
+                usage::count_iterations(results.iter().map(|&(_, steps)| steps).sum());
+            }
+        }
+        Strategy::BorderTrace => {
+            let mut tracer = BorderTracer {
+                done: vec![false; top.0 * top.1],
+                pixels: &mut pixels[..top.0 * top.1],
+                costs: costs.map(|costs| &mut costs[..top.0 * top.1]),
+                bounds: top,
+                upper_left,
+                lower_right: pixel_to_point(bounds, top, upper_left, lower_right),
+                limit,
+                bailout,
+            };
+            tracer.subdivide((0, 0), top);
+        }
+        Strategy::Adaptive { max_error } => {
+            let mut refiner = Refiner {
+                done: vec![false; top.0 * top.1],
+                pixels: &mut pixels[..top.0 * top.1],
+                costs: costs.map(|costs| &mut costs[..top.0 * top.1]),
+                bounds: top,
+                upper_left,
+                lower_right: pixel_to_point(bounds, top, upper_left, lower_right),
+                limit,
+                bailout,
+                max_error,
+            };
+            refiner.refine_grid();
+        }
+    }
+    if rows < bounds.1 {
+        mirror_rows(pixels, bounds, 0, rows, rows);
+    }
+}
+
+/// Rectangles with a side this short or shorter are always computed pixel
+/// by pixel, as tracing their border would save next to nothing.
+const MIN_TRACED_SIDE: usize = 4;
+
+/// Render a rectangle of the Mandelbrot set using rectangle subdivision.
+///
+/// The regions of the Mandelbrot set that escape after the same number of
+/// iterations are connected, so when all the pixels on the border of a rectangle
+/// have the same color, all the pixels inside it have that color too. The image
+/// is recursively split in halves, and only the halves whose border isn't uniform
+/// are looked into further.
+///
+/// The arguments are the same as for `render`, and so is the output, except
+/// for the rare filament that is thinner than a pixel and slips between the
+/// border pixels of a rectangle: those are filled over.
+pub fn render_border_trace(
+    pixels: &mut [u8],
+    bounds: (usize, usize),
+    upper_left: Complex<f64>,
+    lower_right: Complex<f64>,
+) {
+    render_with_limit(
+        Strategy::BorderTrace,
+        pixels,
+        bounds,
+        upper_left,
+        lower_right,
+        DEFAULT_LIMIT,
+    );
+}
+
+/// The state of `render_border_trace`, remembering which pixels have already
+/// been computed so that the edges shared by two rectangles are only done once.
+struct BorderTracer<'a> {
+    pixels: &'a mut [u8],
+    costs: Option<&'a mut [u32]>,
+    done: Vec<bool>,
+    bounds: (usize, usize),
+    upper_left: Complex<f64>,
+    lower_right: Complex<f64>,
+    limit: usize,
+    bailout: f64,
+}
+
+impl BorderTracer<'_> {
+    fn pixel(&mut self, column: usize, row: usize) -> u8 {
+        let index = row * self.bounds.0 + column;
+        if !self.done[index] {
+            let (gray, cost) = usage::counting(|| {
+                render_pixel(
+                    self.bounds,
+                    (column, row),
+                    self.upper_left,
+                    self.lower_right,
+                    self.limit,
+                    self.bailout,
+                )
+            });
+            self.pixels[index] = gray;
+            if let Some(costs) = self.costs.as_deref_mut() {
+                costs[index] = cost;
+            }
+            self.done[index] = true;
+        }
+        self.pixels[index]
+    }
+
+    /// Fill the rectangle with the given upper left corner and size.
+    fn subdivide(&mut self, (left, top): (usize, usize), (width, height): (usize, usize)) {
+        if width == 0 || height == 0 {
+            return;
+        }
+        let (right, bottom) = (left + width - 1, top + height - 1);
+
+        if width <= MIN_TRACED_SIDE || height <= MIN_TRACED_SIDE {
+            for row in top..=bottom {
+                for column in left..=right {
+                    self.pixel(column, row);
+                }
+            }
+            return;
+        }
+
+        let color = self.pixel(left, top);
+        let mut uniform = true;
+        for column in left..=right {
+            uniform &= self.pixel(column, top) == color;
+            uniform &= self.pixel(column, bottom) == color;
+        }
+        for row in top..=bottom {
+            uniform &= self.pixel(left, row) == color;
+            uniform &= self.pixel(right, row) == color;
+        }
+
+        if uniform {
+            for row in top + 1..bottom {
+                let start = row * self.bounds.0;
+                self.pixels[start + left + 1..start + right].fill(color);
+                self.done[start + left + 1..start + right].fill(true);
+            }
+        } else if width >= height {
+            let half = width / 2;
+            self.subdivide((left, top), (half, height));
+            self.subdivide((left + half, top), (width - half, height));
+        } else {
+            let half = height / 2;
+            self.subdivide((left, top), (width, half));
+            self.subdivide((left, top + half), (width, height - half));
+        }
+    }
+}
+
+#[test]
+fn test_render_border_trace() {
+    let viewports = [
+        // the whole set
+        (Complex { re: -2.0, im: 1.2 }, Complex { re: 0.6, im: -1.2 }),
+        // the README sample
+        (
+            Complex { re: -1.2, im: 0.35 },
+            Complex { re: -1.0, im: 0.2 },
+        ),
+        // seahorse valley
+        (
+            Complex {
+                re: -0.76,
+                im: 0.12,
+            },
+            Complex {
+                re: -0.73,
+                im: 0.09,
+            },
+        ),
+        // nowhere near the set
+        (Complex { re: 1.0, im: 2.0 }, Complex { re: 2.0, im: 1.0 }),
+    ];
+    let bounds = (161, 117);
+
+    for (upper_left, lower_right) in viewports {
+        let mut naive = vec![0; bounds.0 * bounds.1];
+        render(&mut naive, bounds, upper_left, lower_right);
+        let mut traced = vec![0; bounds.0 * bounds.1];
+        render_border_trace(&mut traced, bounds, upper_left, lower_right);
+        assert!(naive == traced, "{} {}", upper_left, lower_right);
+    }
+}
+
+/// The side of the cells `Strategy::Adaptive` starts from, in pixels between
+/// their corners.
+const ADAPTIVE_CELL: usize = 16;
+
+/// Cells this many pixels across or down, corners included, or fewer, are
+/// always computed pixel by pixel, having next to nothing inside.
+const MIN_REFINED_SIDE: usize = 3;
+
+/// Render a rectangle of the Mandelbrot set by adaptive refinement, after
+/// Mariani and Silver.
+///
+/// The image is cut into a grid of cells `ADAPTIVE_CELL` pixels a side,
+/// sharing their edges. The corners of each, and its center, are computed;
+/// if their gray levels are at most `max_error` apart, the rest of the cell
+/// is interpolated between its corners, and otherwise it is split into four
+/// and each looked into the same way. With `max_error` 0 only cells of a
+/// single gray level are filled, and the output is that of `render` but for
+/// the features that slip between the points computed, which get fewer as
+/// the cells shrink; larger errors skip more of the gradients outside the
+/// set, blurring them a little.
+///
+/// The other arguments are the same as for `render`.
+pub fn render_adaptive(
+    pixels: &mut [u8],
+    bounds: (usize, usize),
+    upper_left: Complex<f64>,
+    lower_right: Complex<f64>,
+    limit: usize,
+    max_error: u8,
+) {
+    render_with_limit(
+        Strategy::Adaptive { max_error },
+        pixels,
+        bounds,
+        upper_left,
+        lower_right,
+        limit,
+    );
+}
+
+/// The first and last pixels of the cells of `render_adaptive` along a
+/// side `length` pixels long, each starting where the one before ends.
+fn spans(length: usize) -> Vec<(usize, usize)> {
+    let last = length.saturating_sub(1);
+    let mut spans = vec![(0, ADAPTIVE_CELL.min(last))];
+    while let Some(&(_, end)) = spans.last().filter(|&&(_, end)| end < last) {
+        spans.push((end, (end + ADAPTIVE_CELL).min(last)));
+    }
+    spans
+}
+
+#[test]
+fn test_spans() {
+    assert_eq!(spans(1), [(0, 0)]);
+    assert_eq!(spans(10), [(0, 9)]);
+    assert_eq!(spans(40), [(0, 16), (16, 32), (32, 39)]);
+    assert_eq!(spans(33), [(0, 16), (16, 32)]);
+}
+
+/// The state of `render_adaptive`, remembering which pixels have been
+/// computed, rather than interpolated, so that the corners shared by cells
+/// are only done once.
+struct Refiner<'a> {
+    pixels: &'a mut [u8],
+    costs: Option<&'a mut [u32]>,
+    done: Vec<bool>,
+    bounds: (usize, usize),
+    upper_left: Complex<f64>,
+    lower_right: Complex<f64>,
+    limit: usize,
+    bailout: f64,
+    max_error: u8,
+}
+
+impl Refiner<'_> {
+    fn pixel(&mut self, column: usize, row: usize) -> u8 {
+        let index = row * self.bounds.0 + column;
+        if !self.done[index] {
+            let (gray, cost) = usage::counting(|| {
+                render_pixel(
+                    self.bounds,
+                    (column, row),
+                    self.upper_left,
+                    self.lower_right,
+                    self.limit,
+                    self.bailout,
+                )
+            });
+            self.pixels[index] = gray;
+            if let Some(costs) = self.costs.as_deref_mut() {
+                costs[index] = cost;
+            }
+            self.done[index] = true;
+        }
+        self.pixels[index]
+    }
+
+    /// Fill the whole image, a cell of the grid at a time.
+    fn refine_grid(&mut self) {
+        for (top, bottom) in spans(self.bounds.1) {
+            for (left, right) in spans(self.bounds.0) {
+                self.refine((left, top), (right, bottom));
+            }
+        }
+    }
+
+    /// Fill the cell from the upper left pixel `(left, top)` to the lower
+    /// right one `(right, bottom)`, both included.
+    fn refine(&mut self, (left, top): (usize, usize), (right, bottom): (usize, usize)) {
+        if right - left < MIN_REFINED_SIDE || bottom - top < MIN_REFINED_SIDE {
+            for row in top..=bottom {
+                for column in left..=right {
+                    self.pixel(column, row);
+                }
+            }
+            return;
+        }
+
+        let corners = [
+            self.pixel(left, top),
+            self.pixel(right, top),
+            self.pixel(left, bottom),
+            self.pixel(right, bottom),
+        ];
+        let (middle, center) = ((left + right) / 2, (top + bottom) / 2);
+        let levels = corners.into_iter().chain([self.pixel(middle, center)]);
+        let (lowest, highest) = levels.fold((u8::MAX, 0), |(lowest, highest), level| {
+            (lowest.min(level), highest.max(level))
+        });
+
+        if highest - lowest <= self.max_error {
+            let [upper_left, upper_right, lower_left, lower_right] = corners.map(f64::from);
+            let (width, height) = ((right - left) as f64, (bottom - top) as f64);
+            for row in top..=bottom {
+                let y = (row - top) as f64 / height;
+                let start = upper_left + (lower_left - upper_left) * y;
+                let end = upper_right + (lower_right - upper_right) * y;
+                for column in left..=right {
+                    let index = row * self.bounds.0 + column;
+                    if !self.done[index] {
+                        let x = (column - left) as f64 / width;
+                        self.pixels[index] = (start + (end - start) * x).round() as u8;
+                    }
+                }
+            }
+        } else {
+            self.refine((left, top), (middle, center));
+            self.refine((middle, top), (right, center));
+            self.refine((left, center), (middle, bottom));
+            self.refine((middle, center), (right, bottom));
+        }
+    }
+}
+
+#[test]
+fn test_render_adaptive() {
+    let viewports = [
+        // the whole set
+        (Complex { re: -2.0, im: 1.2 }, Complex { re: 0.6, im: -1.2 }),
+        // seahorse valley
+        (
+            Complex {
+                re: -0.76,
+                im: 0.12,
+            },
+            Complex {
+                re: -0.73,
+                im: 0.09,
+            },
+        ),
+        // nowhere near the set
+        (Complex { re: 1.0, im: 2.0 }, Complex { re: 2.0, im: 1.0 }),
+    ];
+    let bounds = (161, 117);
+
+    for (upper_left, lower_right) in viewports {
+        let mut exact = vec![0; bounds.0 * bounds.1];
+        render(&mut exact, bounds, upper_left, lower_right);
+        for max_error in [0, 8] {
+            let mut refined = vec![0; bounds.0 * bounds.1];
+            render_adaptive(
+                &mut refined,
+                bounds,
+                upper_left,
+                lower_right,
+                DEFAULT_LIMIT,
+                max_error,
+            );
+            let errors: Vec<u8> = exact
+                .iter()
+                .zip(&refined)
+                .map(|(&a, &b)| a.abs_diff(b))
+                .collect();
+            let wrong = errors.iter().filter(|&&error| error > max_error).count();
+            let mean = errors.iter().map(|&error| error as f64).sum::<f64>() / errors.len() as f64;
+            assert!(
+                wrong < exact.len() / 100 && mean < 1.0,
+                "{} {} with {}: {} wrong, {} on average",
+                upper_left,
+                lower_right,
+                max_error,
+                wrong,
+                mean
+            );
+        }
+    }
+
+    // far from the set, most pixels are interpolated
+    let (upper_left, lower_right) = viewports[2];
+    let mut refined = vec![0; bounds.0 * bounds.1];
+    let mut costs = vec![0; bounds.0 * bounds.1];
+    let mut refiner = Refiner {
+        pixels: &mut refined,
+        costs: Some(&mut costs),
+        done: vec![false; bounds.0 * bounds.1],
+        bounds,
+        upper_left,
+        lower_right,
+        limit: DEFAULT_LIMIT,
+        bailout: DEFAULT_BAILOUT,
+        max_error: 8,
+    };
+    refiner.refine_grid();
+    let computed = refiner.done.iter().filter(|&&done| done).count();
+    assert!(computed < bounds.0 * bounds.1 / 10, "{}", computed);
+    // and cost nothing
+    let done = refiner.done;
+    assert!(costs
+        .iter()
+        .zip(&done)
+        .all(|(&cost, &done)| (cost > 0) == done));
+}
+
+/// The number of rows `render_parallel` hands out at a time, unless told
+/// otherwise.
+pub const DEFAULT_CHUNK_ROWS: usize = 8;
+
+/// A function receiving the whole buffer of pixels of a render in progress.
+pub type Checkpoint<'a> = dyn Fn(&[u8]) + Sync + 'a;
+
+/// How `render_parallel` spreads the work over threads, and who it keeps
+/// informed along the way.
+pub struct Schedule<'a> {
+    pub threads: usize,
+    /// How many rows a thread takes from the queue at a time.
+    pub chunk_rows: usize,
+    /// The order the threads go through the pixels in: blocks along a curve
+    /// instead of `chunk_rows` rows at a time, if not `Traversal::Rows`.
+    pub traversal: Traversal,
+    /// Where to count the rows done, if anywhere.
+    pub progress: Option<&'a Progress>,
+    /// Called with all the pixels, those not rendered yet being 0, whenever
+    /// `progress` asks for a checkpoint.
+    pub checkpoint: Option<&'a Checkpoint<'a>>,
+    /// Once this is set, the threads finish the chunk they are working on and
+    /// stop, leaving the rest of the pixels alone.
+    pub cancel: Option<&'a AtomicBool>,
+    /// Once this passes, the threads stop as if `cancel` had been set.
+    pub deadline: Option<Instant>,
+    /// How many points each pixel averages. With more than one, every pixel
+    /// is computed, whatever the strategy.
+    pub sampling: Sampling,
+    /// Iterations before a point is taken to be in the set.
+    pub limit: usize,
+    /// The numbers the escape times are computed in. With anything but
+    /// `f64`, every pixel is computed, whatever the strategy.
+    pub numeric: Numeric,
+    /// How far from 0 orbits have to get to escape, see
+    /// `escape_time_with_bailout`.
+    pub bailout: f64,
+    /// Where to measure the work of every thread, if anywhere.
+    pub usage: Option<&'a Usage>,
+    /// Where to keep the variance of the points of every pixel, if anywhere,
+    /// when `sampling` averages several.
+    pub variance: Option<&'a Variance>,
+    /// Where to keep the iterations spent on every pixel, if anywhere.
+    pub cost: Option<&'a Cost>,
+    /// Compute only the top half of views symmetric about the real axis,
+    /// and mirror it, see `mirrored_rows`. Formulas are only mirrored when
+    /// their fractals are symmetric too.
+    pub mirror: bool,
+}
+
+impl<'a> Schedule<'a> {
+    /// Use `threads` threads, the default chunk size, and report to no one.
+    pub fn new(threads: usize) -> Schedule<'a> {
+        Schedule {
+            threads,
+            chunk_rows: DEFAULT_CHUNK_ROWS,
+            traversal: Traversal::Rows,
+            progress: None,
+            checkpoint: None,
+            cancel: None,
+            deadline: None,
+            sampling: Sampling::NONE,
+            limit: DEFAULT_LIMIT,
+            numeric: Numeric::F64,
+            bailout: DEFAULT_BAILOUT,
+            usage: None,
+            variance: None,
+            cost: None,
+            mirror: true,
+        }
+    }
+
+    /// Whether the threads should stop: `cancel` is set, or `deadline` has
+    /// passed.
+    pub fn stopped(&self) -> bool {
+        self.cancel
+            .is_some_and(|cancel| cancel.load(Ordering::Relaxed))
+            || self
+                .deadline
+                .is_some_and(|deadline| Instant::now() >= deadline)
+    }
+}
+
+/// How many rows from the top of the view between `upper_left` and
+/// `lower_right`, an image of `bounds` pixels, have to be computed when the
+/// view is symmetric about the real axis, the others being the mirror images
+/// of some of them, or `None` if it isn't.
+///
+/// Row `r` is at the same distance from the axis as row `bounds.1 - r`, so
+/// the first row, on the top edge, is alone, and with an even height the
+/// middle row lies on the axis.
+pub fn mirrored_rows(
+    bounds: (usize, usize),
+    upper_left: Complex<f64>,
+    lower_right: Complex<f64>,
+) -> Option<usize> {
+    let height = upper_left.im - lower_right.im;
+    let symmetric = (upper_left.im + lower_right.im).abs() <= height * 1e-12;
+    (symmetric && bounds.1 > 2).then_some(bounds.1 / 2 + 1)
+}
+
+#[test]
+fn test_mirrored_rows() {
+    let (upper_left, lower_right) = (Complex { re: -2.0, im: 1.2 }, Complex { re: 0.6, im: -1.2 });
+    assert_eq!(mirrored_rows((97, 61), upper_left, lower_right), Some(31));
+    assert_eq!(mirrored_rows((97, 60), upper_left, lower_right), Some(31));
+    assert_eq!(mirrored_rows((97, 2), upper_left, lower_right), None);
+    let lower_right = Complex { re: 0.6, im: -1.0 };
+    assert_eq!(mirrored_rows((97, 61), upper_left, lower_right), None);
+}
+
+/// Copy `height` rows of `pixels`, an image `bounds` in size, from row
+/// `top` on, into the rows mirroring them about the real axis, see
+/// `mirrored_rows`, leaving alone the first `rows` rows, which are computed.
+/// Returns how many rows were copied.
+pub fn mirror_rows(
+    pixels: &mut [u8],
+    bounds: (usize, usize),
+    top: usize,
+    height: usize,
+    rows: usize,
+) -> usize {
+    let mut copied = 0;
+    for row in top.max(1)..top + height {
+        let mirror = bounds.1 - row;
+        if mirror >= rows {
+            pixels.copy_within(row * bounds.0..(row + 1) * bounds.0, mirror * bounds.0);
+            copied += 1;
+        }
+    }
+    copied
+}
+
+/// Render a rectangle of the Mandelbrot set into `pixels` on several threads.
+///
+/// The image is cut into chunks of `schedule.chunk_rows` rows, which the threads
+/// take one at a time, by bumping a shared row counter, until none are left.
+/// Rows near the set take far longer than rows away from it, so this keeps every
+/// thread busy until the end, where giving each thread a fixed band would leave
+/// most of them waiting for the slowest one. Smaller chunks balance the load
+/// better, larger ones leave more room for `Strategy::BorderTrace` to skip pixels.
+///
+/// Each chunk is rendered aside and then copied into `pixels`, so that a
+/// checkpoint always sees whole chunks.
+///
+/// With another `schedule.traversal`, the threads take blocks along its
+/// curve instead, see `render_blocks`.
+///
+/// When the view is symmetric about the real axis and `schedule.mirror` is
+/// set, the threads only take the rows of the top half, and every chunk is
+/// mirrored into the bottom half as it is copied, see `mirrored_rows`.
+/// Supersampled pixels are all computed, as their points aren't symmetric.
+///
+/// The other arguments are the same as for `render_with`. Returns false if
+/// `schedule` was stopped before every row was rendered.
+pub fn render_parallel(
+    strategy: Strategy,
+    pixels: &mut [u8],
+    bounds: (usize, usize),
+    upper_left: Complex<f64>,
+    lower_right: Complex<f64>,
+    schedule: &Schedule,
+) -> bool {
+    assert!(pixels.len() == bounds.0 * bounds.1);
+    if pixels.is_empty() {
+        return true;
+    }
+    if schedule.traversal != Traversal::Rows
+        && schedule.sampling.samples <= 1
+        && schedule.numeric == Numeric::F64
+    {
+        return render_blocks(strategy, pixels, bounds, upper_left, lower_right, schedule);
+    }
+
+    // the rows computed: whole chunks, cut where they would be without
+    // mirroring
+    let mirrored = mirrored_rows(bounds, upper_left, lower_right)
+        .filter(|_| schedule.mirror && schedule.sampling.samples <= 1)
+        .map(|rows| (rows.div_ceil(schedule.chunk_rows) * schedule.chunk_rows).min(bounds.1));
+    let rows = mirrored.unwrap_or(bounds.1);
+    let next_row = AtomicUsize::new(0);
+    let output = Mutex::new(pixels);
+    let (next_row, output) = (&next_row, &output);
+    crossbeam::scope(|spawner| {
+        for thread in 0..schedule.threads {
+            spawner.spawn(move |_| {
+                let (mut chunk, mut costs) = (Vec::new(), Vec::new());
+                loop {
+                    if schedule.stopped() {
+                        break;
+                    }
+                    let top = next_row.fetch_add(schedule.chunk_rows, Ordering::Relaxed);
+                    if top >= rows {
+                        break;
+                    }
+                    let height = schedule.chunk_rows.min(bounds.1 - top);
+
+                    let chunk_upper_left =
+                        pixel_to_point(bounds, (0, top), upper_left, lower_right);
+                    let chunk_lower_right =
+                        pixel_to_point(bounds, (bounds.0, top + height), upper_left, lower_right);
+                    chunk.resize(height * bounds.0, 0);
+                    Usage::measure(schedule.usage, thread, height, || {
+                        if schedule.sampling.samples > 1 {
+                            render_supersampled(
+                                &mut chunk,
+                                (bounds.0, height),
+                                top,
+                                chunk_upper_left,
+                                chunk_lower_right,
+                                schedule,
+                            );
+                        } else if schedule.numeric != Numeric::F64 {
+                            real::render_rows(
+                                &mut chunk,
+                                bounds,
+                                top,
+                                upper_left,
+                                lower_right,
+                                schedule,
+                            );
+                        } else {
+                            costs.clear();
+                            costs.resize(chunk.len(), 0);
+                            render_costed(
+                                strategy,
+                                &mut chunk,
+                                schedule.cost.map(|_| &mut costs[..]),
+                                (bounds.0, height),
+                                chunk_upper_left,
+                                chunk_lower_right,
+                                schedule.limit,
+                                schedule.bailout,
+                            );
+                            if let Some(cost) = schedule.cost {
+                                cost.set_block((0, top), bounds.0, &costs);
+                            }
+                        }
+                    });
+
+                    let mut output = output.lock().unwrap();
+                    output[top * bounds.0..(top + height) * bounds.0].copy_from_slice(&chunk);
+                    let copied = match mirrored {
+                        Some(rows) => mirror_rows(&mut output, bounds, top, height, rows),
+                        None => 0,
+                    };
+                    if let Some(progress) = schedule.progress {
+                        progress.add_rows(height + copied);
+                        if progress.take_checkpoint_request() {
+                            if let Some(checkpoint) = schedule.checkpoint {
+                                checkpoint(&output);
+                            }
+                        }
+                    }
+                }
+            });
+        }
+    })
+    .unwrap();
+    // every chunk taken from the queue is rendered
+    next_row.load(Ordering::Relaxed) >= rows
+}
+
+/// Render into `pixels`, whose dimensions are given by `bounds`, the point
+/// `point` gives for each pixel rather than a rectangle of the plane, such
+/// as those of `exp_map_to_point`, on several threads.
+///
+/// The threads take `schedule.chunk_rows` rows at a time like they do in
+/// `render_parallel`, computing every pixel with `schedule.limit` and
+/// `schedule.bailout`, in `f64`; the other settings of `schedule` but
+/// `progress`, `cancel` and `deadline` are ignored. Returns false if it was
+/// stopped before every row was rendered.
+pub fn render_mapped(
+    pixels: &mut [u8],
+    bounds: (usize, usize),
+    point: &(dyn Fn((usize, usize)) -> Complex<f64> + Sync),
+    schedule: &Schedule,
+) -> bool {
+    assert!(pixels.len() == bounds.0 * bounds.1);
+    let next_row = AtomicUsize::new(0);
+    let output = Mutex::new(pixels);
+    let (next_row, output) = (&next_row, &output);
+    crossbeam::scope(|spawner| {
+        for _ in 0..schedule.threads {
+            spawner.spawn(move |_| {
+                let mut chunk = Vec::new();
+                while !schedule.stopped() {
+                    let top = next_row.fetch_add(schedule.chunk_rows, Ordering::Relaxed);
+                    if top >= bounds.1 {
+                        break;
+                    }
+                    let height = schedule.chunk_rows.min(bounds.1 - top);
+                    chunk.clear();
+                    for y in top..top + height {
+                        chunk.extend((0..bounds.0).map(|x| {
+                            let c = point((x, y));
+                            let count =
+                                escape_time_with_bailout(c, schedule.limit, schedule.bailout);
+                            gray_level(count, schedule.limit)
+                        }));
+                    }
+                    output.lock().unwrap()[top * bounds.0..(top + height) * bounds.0]
+                        .copy_from_slice(&chunk);
+                    if let Some(progress) = schedule.progress {
+                        progress.add_rows(height);
+                    }
+                }
+            });
+        }
+    })
+    .unwrap();
+    next_row.load(Ordering::Relaxed) >= bounds.1
+}
+
+#[test]
+fn test_render_mapped() {
+    // mapped onto a rectangle, the same as rendering it
+    let bounds = (60, 40);
+    let (upper_left, lower_right) = (Complex { re: -2.0, im: 1.0 }, Complex { re: 1.0, im: -1.0 });
+    let mut expected = vec![0; bounds.0 * bounds.1];
+    render(&mut expected, bounds, upper_left, lower_right);
+    let mut pixels = vec![1; bounds.0 * bounds.1];
+    let point = |pixel| pixel_to_point(bounds, pixel, upper_left, lower_right);
+    assert!(render_mapped(
+        &mut pixels,
+        bounds,
+        &point,
+        &Schedule {
+            chunk_rows: 7,
+            ..Schedule::new(3)
+        }
+    ));
+    assert_eq!(pixels, expected);
+}
+
+/// `render_parallel` a block at a time, in the order of `schedule.traversal`.
+/// Only one point a pixel, in `f64`, is computed this way; supersampled and
+/// other numbers always go by rows.
+///
+/// Progress is still counted in rows: a row is done once as many pixels
+/// are as there are in a row, wherever they are.
+fn render_blocks(
+    strategy: Strategy,
+    pixels: &mut [u8],
+    bounds: (usize, usize),
+    upper_left: Complex<f64>,
+    lower_right: Complex<f64>,
+    schedule: &Schedule,
+) -> bool {
+    let blocks = traversal::blocks(bounds, schedule.traversal);
+    let next_block = AtomicUsize::new(0);
+    // the pixels, and how many of them are done
+    let output = Mutex::new((pixels, 0));
+    let (blocks, next_block, output) = (&blocks, &next_block, &output);
+    crossbeam::scope(|spawner| {
+        for thread in 0..schedule.threads {
+            spawner.spawn(move |_| {
+                let (mut block, mut costs) = (Vec::new(), Vec::new());
+                while !schedule.stopped() {
+                    let number = next_block.fetch_add(1, Ordering::Relaxed);
+                    let Some(&((x, y), (width, height))) = blocks.get(number) else {
+                        break;
+                    };
+                    let block_upper_left = pixel_to_point(bounds, (x, y), upper_left, lower_right);
+                    let block_lower_right =
+                        pixel_to_point(bounds, (x + width, y + height), upper_left, lower_right);
+                    block.resize(width * height, 0);
+                    costs.clear();
+                    costs.resize(block.len(), 0);
+                    Usage::measure(schedule.usage, thread, height, || {
+                        render_costed(
+                            strategy,
+                            &mut block,
+                            schedule.cost.map(|_| &mut costs[..]),
+                            (width, height),
+                            block_upper_left,
+                            block_lower_right,
+                            schedule.limit,
+                            schedule.bailout,
+                        );
+                    });
+                    if let Some(cost) = schedule.cost {
+                        cost.set_block((x, y), width, &costs);
+                    }
+
+                    let mut output = output.lock().unwrap();
+                    let (pixels, done) = &mut *output;
+                    for (row, line) in block.chunks(width).enumerate() {
+                        let start = (y + row) * bounds.0 + x;
+                        pixels[start..start + width].copy_from_slice(line);
+                    }
+                    let rows_before = *done / bounds.0;
+                    *done += width * height;
+                    if let Some(progress) = schedule.progress {
+                        progress.add_rows(*done / bounds.0 - rows_before);
+                        if progress.take_checkpoint_request() {
+                            if let Some(checkpoint) = schedule.checkpoint {
+                                checkpoint(pixels);
+                            }
+                        }
+                    }
+                }
+            });
+        }
+    })
+    .unwrap();
+    next_block.load(Ordering::Relaxed) >= blocks.len()
+}
+
+#[test]
+fn test_render_blocks() {
+    let bounds = (97, 61);
+    let (upper_left, lower_right) = (Complex { re: -2.0, im: 1.2 }, Complex { re: 0.6, im: -1.2 });
+    let mut expected = vec![0; bounds.0 * bounds.1];
+    render(&mut expected, bounds, upper_left, lower_right);
+
+    for traversal in [Traversal::Hilbert, Traversal::Morton] {
+        let progress = Progress::new(bounds.1);
+        let schedule = Schedule {
+            traversal,
+            progress: Some(&progress),
+            ..Schedule::new(3)
+        };
+        let mut pixels = vec![0; bounds.0 * bounds.1];
+        let done = render_parallel(
+            Strategy::BorderTrace,
+            &mut pixels,
+            bounds,
+            upper_left,
+            lower_right,
+            &schedule,
+        );
+        assert!(done);
+        // the same but for rounding, the blocks having corners of their own
+        let different = pixels.iter().zip(&expected).filter(|(a, b)| a != b);
+        assert!(different.count() < pixels.len() / 100, "{:?}", traversal);
+        assert_eq!(progress.rows_done(), bounds.1);
+    }
+
+    // stopped after the first block
+    let cancel = AtomicBool::new(false);
+    let first_block = |pixels: &[u8]| {
+        cancel.store(true, Ordering::Relaxed);
+        assert!(pixels[..16].iter().all(|&gray| gray != 0));
+    };
+    let progress = Progress::new(bounds.1);
+    progress.request_checkpoint();
+    let schedule = Schedule {
+        traversal: Traversal::Hilbert,
+        progress: Some(&progress),
+        checkpoint: Some(&first_block),
+        cancel: Some(&cancel),
+        ..Schedule::new(1)
+    };
+    let mut pixels = vec![0; bounds.0 * bounds.1];
+    let done = render_parallel(
+        Strategy::Naive,
+        &mut pixels,
+        bounds,
+        upper_left,
+        lower_right,
+        &schedule,
+    );
+    assert!(!done);
+    assert_eq!(progress.rows_done(), 16 * 16 / bounds.0);
+    assert!(pixels[16..bounds.0].iter().all(|&gray| gray == 0));
+}
+
+#[test]
+fn test_render_parallel() {
+    let bounds = (97, 61);
+    let (upper_left, lower_right) = (Complex { re: -2.0, im: 1.2 }, Complex { re: 0.6, im: -1.2 });
+    let mut expected = vec![0; bounds.0 * bounds.1];
+    render(&mut expected, bounds, upper_left, lower_right);
+
+    for (threads, chunk_rows) in [(1, 1), (3, 7), (4, 100)] {
+        let progress = Progress::new(bounds.1);
+        let schedule = Schedule {
+            chunk_rows,
+            progress: Some(&progress),
+            ..Schedule::new(threads)
+        };
+        let mut pixels = vec![0; bounds.0 * bounds.1];
+        render_parallel(
+            Strategy::Naive,
+            &mut pixels,
+            bounds,
+            upper_left,
+            lower_right,
+            &schedule,
+        );
+        assert!(
+            pixels == expected,
+            "{} threads, {} rows",
+            threads,
+            chunk_rows
+        );
+        assert_eq!(progress.rows_done(), bounds.1);
+    }
+
+    // every iteration run is counted, on the thread that ran it, every row
+    // being computed without mirroring
+    let usage = Usage::new(3);
+    let schedule = Schedule {
+        chunk_rows: 5,
+        usage: Some(&usage),
+        mirror: false,
+        ..Schedule::new(3)
+    };
+    let mut pixels = vec![0; bounds.0 * bounds.1];
+    render_parallel(
+        Strategy::Naive,
+        &mut pixels,
+        bounds,
+        upper_left,
+        lower_right,
+        &schedule,
+    );
+    let threads = usage.threads(std::time::Duration::from_secs(1));
+    assert_eq!(threads.iter().map(|t| t.rows).sum::<u64>(), bounds.1 as u64);
+    let iterations: u64 = threads.iter().map(|t| t.iterations).sum();
+    assert_eq!(iterations, usage.iterations());
+    assert!(iterations > (bounds.0 * bounds.1) as u64);
+}
+
+#[test]
+fn test_render_parallel_cost() {
+    let bounds = (97, 61);
+    let (upper_left, lower_right) = (Complex { re: -2.0, im: 1.2 }, Complex { re: 0.6, im: -1.2 });
+    let two = Sampling {
+        samples: 2,
+        ..Sampling::NONE
+    };
+    let settings = [
+        (
+            Strategy::Naive,
+            Traversal::Rows,
+            Sampling::NONE,
+            Numeric::F64,
+        ),
+        (
+            Strategy::BorderTrace,
+            Traversal::Rows,
+            Sampling::NONE,
+            Numeric::F64,
+        ),
+        (
+            Strategy::Naive,
+            Traversal::Hilbert,
+            Sampling::NONE,
+            Numeric::F64,
+        ),
+        (Strategy::Naive, Traversal::Rows, two, Numeric::F64),
+        (
+            Strategy::Naive,
+            Traversal::Rows,
+            Sampling::NONE,
+            Numeric::DoubleDouble,
+        ),
+    ];
+    for (strategy, traversal, sampling, numeric) in settings {
+        // every iteration is spent on some pixel
+        let (usage, cost) = (Usage::new(2), Cost::new(bounds));
+        let schedule = Schedule {
+            chunk_rows: 5,
+            traversal,
+            sampling,
+            numeric,
+            usage: Some(&usage),
+            cost: Some(&cost),
+            ..Schedule::new(2)
+        };
+        let mut pixels = vec![0; bounds.0 * bounds.1];
+        render_parallel(
+            strategy,
+            &mut pixels,
+            bounds,
+            upper_left,
+            lower_right,
+            &schedule,
+        );
+        let costs = cost.values();
+        let spent: u64 = costs.iter().map(|&cost| cost as u64).sum();
+        assert_eq!(spent, usage.iterations(), "{:?}", strategy);
+        // far more near the set than far from it
+        let max = costs.iter().copied().max().unwrap();
+        assert!(costs[bounds.0 - 1] < max / 10, "{:?}", strategy);
+    }
+}
+
+#[test]
+fn test_render_parallel_mirrored() {
+    let bounds = (80, 45);
+    let (upper_left, lower_right) = (Complex { re: -2.0, im: 1.2 }, Complex { re: 0.6, im: -1.2 });
+    let render_rows = |mirror, strategy| {
+        let progress = Progress::new(bounds.1);
+        let usage = Usage::new(2);
+        let schedule = Schedule {
+            chunk_rows: 4,
+            progress: Some(&progress),
+            usage: Some(&usage),
+            mirror,
+            ..Schedule::new(2)
+        };
+        let mut pixels = vec![0; bounds.0 * bounds.1];
+        render_parallel(
+            strategy,
+            &mut pixels,
+            bounds,
+            upper_left,
+            lower_right,
+            &schedule,
+        );
+        assert_eq!(progress.rows_done(), bounds.1);
+        let rows = usage.threads(std::time::Duration::from_secs(1));
+        (pixels, rows.iter().map(|t| t.rows).sum::<u64>())
+    };
+
+    // the top half, in whole chunks, is computed as it would be without
+    // mirroring, and the bottom half is its mirror image
+    let (whole, rows) = render_rows(false, Strategy::Naive);
+    assert_eq!(rows, 45);
+    let (mirrored, rows) = render_rows(true, Strategy::Naive);
+    assert_eq!(rows, 24);
+    assert_eq!(mirrored[..24 * bounds.0], whole[..24 * bounds.0]);
+    for row in 1..bounds.1 {
+        let mirror = bounds.1 - row;
+        assert_eq!(
+            mirrored[row * bounds.0..(row + 1) * bounds.0],
+            mirrored[mirror * bounds.0..(mirror + 1) * bounds.0]
+        );
+    }
+    assert!(render_rows(true, Strategy::BorderTrace).0 == mirrored);
+
+    // a view off the axis is computed all the way down
+    let mut pixels = vec![0; bounds.0 * bounds.1];
+    let lower_right = Complex { re: 0.6, im: -1.0 };
+    render_parallel(
+        Strategy::Naive,
+        &mut pixels,
+        bounds,
+        upper_left,
+        lower_right,
+        &Schedule::new(2),
+    );
+    let mut expected = vec![0; bounds.0 * bounds.1];
+    render(&mut expected, bounds, upper_left, lower_right);
+    assert!(pixels == expected);
+}
+
+#[test]
+fn test_render_parallel_supersampled() {
+    let bounds = (40, 30);
+    let (upper_left, lower_right) = (Complex { re: -2.0, im: 1.2 }, Complex { re: 0.6, im: -1.2 });
+    for jitter in [false, true] {
+        let sampling = Sampling {
+            samples: 4,
+            seed: 3,
+            jitter,
+        };
+        let schedule = Schedule {
+            chunk_rows: 7,
+            sampling,
+            ..Schedule::new(2)
+        };
+        let mut expected = vec![0; bounds.0 * bounds.1];
+        render_supersampled(&mut expected, bounds, 0, upper_left, lower_right, &schedule);
+
+        // chunks sample the same points as the whole image would
+        let mut pixels = vec![0; bounds.0 * bounds.1];
+        render_parallel(
+            Strategy::BorderTrace,
+            &mut pixels,
+            bounds,
+            upper_left,
+            lower_right,
+            &schedule,
+        );
+        assert!(pixels == expected, "{}", jitter);
+    }
+}
+
+#[test]
+fn test_render_parallel_checkpoint() {
+    let bounds = (40, 30);
+    let progress = Progress::new(bounds.1);
+    progress.request_checkpoint();
+    let checkpoints = Mutex::new(Vec::new());
+    let checkpoint = |pixels: &[u8]| checkpoints.lock().unwrap().push(pixels.to_vec());
+    let schedule = Schedule {
+        chunk_rows: 10,
+        progress: Some(&progress),
+        checkpoint: Some(&checkpoint),
+        mirror: false,
+        ..Schedule::new(1)
+    };
+
+    let mut pixels = vec![0; bounds.0 * bounds.1];
+    render_parallel(
+        Strategy::Naive,
+        &mut pixels,
+        bounds,
+        Complex { re: -2.0, im: 1.2 },
+        Complex { re: 0.6, im: -1.2 },
+        &schedule,
+    );
+
+    // the checkpoint came after the first chunk, and has nothing else, the
+    // view not being mirrored
+    let checkpoints = checkpoints.into_inner().unwrap();
+    assert_eq!(checkpoints.len(), 1);
+    assert_eq!(checkpoints[0][..400], pixels[..400]);
+    assert!(checkpoints[0][400..].iter().all(|&p| p == 0));
+}
+
+#[test]
+fn test_render_parallel_cancel() {
+    let bounds = (40, 30);
+    let cancel = AtomicBool::new(true);
+    let schedule = Schedule {
+        cancel: Some(&cancel),
+        ..Schedule::new(2)
+    };
+
+    let mut pixels = vec![7; bounds.0 * bounds.1];
+    let (upper_left, lower_right) = (Complex { re: -2.0, im: 1.2 }, Complex { re: 0.6, im: -1.2 });
+    let done = render_parallel(
+        Strategy::Naive,
+        &mut pixels,
+        bounds,
+        upper_left,
+        lower_right,
+        &schedule,
+    );
+    assert!(!done);
+    assert!(pixels.iter().all(|&p| p == 7));
+
+    // a deadline that has passed stops it the same way
+    let schedule = Schedule {
+        deadline: Some(Instant::now()),
+        ..Schedule::new(2)
+    };
+    assert!(schedule.stopped());
+    let done = render_parallel(
+        Strategy::Naive,
+        &mut pixels,
+        bounds,
+        upper_left,
+        lower_right,
+        &schedule,
+    );
+    assert!(!done);
+    assert!(pixels.iter().all(|&p| p == 7));
+    let later = Schedule {
+        deadline: Some(Instant::now() + std::time::Duration::from_secs(3600)),
+        ..Schedule::new(2)
+    };
+    assert!(!later.stopped());
+    let done = render_parallel(
+        Strategy::Naive,
+        &mut pixels,
+        bounds,
+        upper_left,
+        lower_right,
+        &later,
+    );
+    assert!(done);
+    assert!(pixels.iter().any(|&p| p != 7));
+}
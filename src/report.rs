@@ -0,0 +1,252 @@
+//! Self-contained HTML reports describing a finished render.
+//!
+//! A report embeds a thumbnail of the image as a data URI, so the single
+//! `.html` file can be archived or mailed around on its own.
+
+use std::time::Duration;
+
+use image::{png::PNGEncoder, ColorType};
+use num::Complex;
+
+use crate::{share::share_link, Error, Result};
+
+/// The longest side, in pixels, of the thumbnail embedded in a report.
+const THUMBNAIL_SIZE: usize = 320;
+
+/// Everything a report needs to know about a render.
+pub struct Report<'a> {
+    /// The file the full size image was written to.
+    pub image_file: &'a str,
+    pub bounds: (usize, usize),
+    pub upper_left: Complex<f64>,
+    pub lower_right: Complex<f64>,
+    /// The rendered grayscale pixels, one byte per pixel.
+    pub pixels: &'a [u8],
+    /// How long each phase of the run took, in the order they happened.
+    pub timings: Vec<(&'static str, Duration)>,
+}
+
+/// Summary statistics of a rendered image.
+#[derive(Debug, PartialEq)]
+pub struct Stats {
+    pub pixels: usize,
+    /// Pixels that were rendered black, i.e. that seem to belong to the set.
+    pub interior: usize,
+    /// Fewest and most iterations it took an exterior pixel to escape.
+    pub min_escape: Option<usize>,
+    pub max_escape: Option<usize>,
+    pub mean_escape: Option<f64>,
+}
+
+/// Compute the statistics of a buffer of pixels produced by `render`.
+///
+/// `render` stores `255 - i` for a point that escaped after `i` iterations
+/// and `0` for points that didn't escape, so this works backwards from that.
+pub fn stats(pixels: &[u8]) -> Stats {
+    let escapes: Vec<usize> = pixels
+        .iter()
+        .filter(|&&p| p != 0)
+        .map(|&p| 255 - p as usize)
+        .collect();
+
+    Stats {
+        pixels: pixels.len(),
+        interior: pixels.len() - escapes.len(),
+        min_escape: escapes.iter().copied().min(),
+        max_escape: escapes.iter().copied().max(),
+        mean_escape: if escapes.is_empty() {
+            None
+        } else {
+            Some(escapes.iter().sum::<usize>() as f64 / escapes.len() as f64)
+        },
+    }
+}
+
+#[test]
+fn test_stats() {
+    let stats = stats(&[0, 255, 250, 0]);
+    assert_eq!(stats.pixels, 4);
+    assert_eq!(stats.interior, 2);
+    assert_eq!(stats.min_escape, Some(0));
+    assert_eq!(stats.max_escape, Some(5));
+    assert_eq!(stats.mean_escape, Some(2.5));
+}
+
+/// Shrink the grayscale `pixels`, whose dimensions are given by `bounds`, so
+/// that neither side is longer than `max_side`, averaging the pixels that are
+/// folded together.
+///
+/// Returns the new pixels together with their dimensions.
+pub fn thumbnail(
+    pixels: &[u8],
+    bounds: (usize, usize),
+    max_side: usize,
+) -> (Vec<u8>, (usize, usize)) {
+    let factor = bounds.0.max(bounds.1).div_ceil(max_side);
+    let factor = factor.max(1);
+    let small = ((bounds.0 / factor).max(1), (bounds.1 / factor).max(1));
+
+    let mut out = Vec::with_capacity(small.0 * small.1);
+    for row in 0..small.1 {
+        for column in 0..small.0 {
+            let mut sum = 0;
+            let mut count = 0;
+            for y in row * factor..((row + 1) * factor).min(bounds.1) {
+                for x in column * factor..((column + 1) * factor).min(bounds.0) {
+                    sum += pixels[y * bounds.0 + x] as usize;
+                    count += 1;
+                }
+            }
+            out.push((sum / count.max(1)) as u8);
+        }
+    }
+
+    (out, small)
+}
+
+#[test]
+fn test_thumbnail() {
+    let pixels = [0, 4, 10, 10, 8, 4, 20, 20];
+    assert_eq!(thumbnail(&pixels, (4, 2), 2), (vec![4, 15], (2, 1)));
+    assert_eq!(thumbnail(&pixels, (4, 2), 10), (pixels.to_vec(), (4, 2)));
+}
+
+/// Encode `data` as standard, padded base64.
+pub fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let b = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let n = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+
+    out
+}
+
+#[test]
+fn test_base64() {
+    assert_eq!(base64(b""), "");
+    assert_eq!(base64(b"f"), "Zg==");
+    assert_eq!(base64(b"fo"), "Zm8=");
+    assert_eq!(base64(b"foo"), "Zm9v");
+    assert_eq!(base64(b"foobar"), "Zm9vYmFy");
+}
+
+/// Escape the characters that have a special meaning in HTML.
+fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Render `report` as a self-contained HTML document.
+pub fn to_html(report: &Report) -> Result<String> {
+    let (small, small_bounds) = thumbnail(report.pixels, report.bounds, THUMBNAIL_SIZE);
+    let mut png = Vec::new();
+    PNGEncoder::new(&mut png)
+        .encode(
+            &small,
+            small_bounds.0 as u32,
+            small_bounds.1 as u32,
+            ColorType::Gray(8),
+        )
+        .map_err(Error::Encode)?;
+
+    let stats = stats(report.pixels);
+    let link = escape_html(&share_link(
+        report.bounds,
+        report.upper_left,
+        report.lower_right,
+    ));
+    let optional = |value: Option<String>| value.unwrap_or_else(|| "-".to_string());
+
+    let mut timings = String::new();
+    let mut total = Duration::default();
+    for (phase, duration) in &report.timings {
+        timings += &format!(
+            "<tr><th>{}</th><td>{:.3} s</td></tr>\n",
+            phase,
+            duration.as_secs_f64()
+        );
+        total += *duration;
+    }
+    timings += &format!(
+        "<tr><th>total</th><td>{:.3} s</td></tr>\n",
+        total.as_secs_f64()
+    );
+
+    Ok(format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>Mandelbrot render: {file}</title>
+<style>
+body {{ font-family: sans-serif; margin: 2em; }}
+table {{ border-collapse: collapse; margin-bottom: 1.5em; }}
+th, td {{ text-align: left; padding: 0.2em 1em 0.2em 0; }}
+</style>
+</head>
+<body>
+<h1>{file}</h1>
+<img src="data:image/png;base64,{thumbnail}" width="{thumb_width}" height="{thumb_height}" alt="thumbnail">
+<h2>Parameters</h2>
+<table>
+<tr><th>pixels</th><td>{width}x{height}</td></tr>
+<tr><th>upper left</th><td>{ul_re},{ul_im}</td></tr>
+<tr><th>lower right</th><td>{lr_re},{lr_im}</td></tr>
+</table>
+<h2>Statistics</h2>
+<table>
+<tr><th>pixels</th><td>{pixels}</td></tr>
+<tr><th>interior pixels</th><td>{interior} ({interior_pct:.2}%)</td></tr>
+<tr><th>min escape iterations</th><td>{min}</td></tr>
+<tr><th>max escape iterations</th><td>{max}</td></tr>
+<tr><th>mean escape iterations</th><td>{mean}</td></tr>
+</table>
+<h2>Timing</h2>
+<table>
+{timings}</table>
+<h2>Share</h2>
+<p><a href="{link}">{link}</a></p>
+</body>
+</html>
+"#,
+        file = escape_html(report.image_file),
+        thumbnail = base64(&png),
+        thumb_width = small_bounds.0,
+        thumb_height = small_bounds.1,
+        width = report.bounds.0,
+        height = report.bounds.1,
+        ul_re = report.upper_left.re,
+        ul_im = report.upper_left.im,
+        lr_re = report.lower_right.re,
+        lr_im = report.lower_right.im,
+        pixels = stats.pixels,
+        interior = stats.interior,
+        interior_pct = 100.0 * stats.interior as f64 / stats.pixels.max(1) as f64,
+        min = optional(stats.min_escape.map(|v| v.to_string())),
+        max = optional(stats.max_escape.map(|v| v.to_string())),
+        mean = optional(stats.mean_escape.map(|v| format!("{:.2}", v))),
+        timings = timings,
+        link = link,
+    ))
+}
+
+/// Write the HTML report for `report` to the file named `filename`.
+pub fn write_report(filename: &str, report: &Report) -> Result<()> {
+    crate::output::write(filename, to_html(report)?.as_bytes())
+}
@@ -0,0 +1,199 @@
+//! Rendering an image again pixel for pixel, for `--manifest` and
+//! `--reproduce`. Nothing the program draws at random is left to chance: the
+//! points of the Buddhabrots, jittered samples, stipples and random
+//! viewports all come from seeds, the same on every machine whatever the
+//! number of threads. A manifest written next to the image records them with
+//! the other options it was rendered with, once the configuration file,
+//! projects, bookmarks and jobs are expanded, and a digest of its pixels:
+//!
+//! ```json
+//! {
+//!   "version": 1,
+//!   "program": "mandelbrot 0.1.0",
+//!   "output": "dust.png",
+//!   "args": ["800x600", "-2,1.5", "2,-1.5", "--fractal", "buddhabrot"],
+//!   "seeds": {"jitter_seed": 0, "seed": 0},
+//!   "pixels": [800, 600],
+//!   "upper_left": [-2.0, 1.5],
+//!   "lower_right": [2.0, -1.5],
+//!   "max_iters": 1000,
+//!   "digest": "5b1c0e9d2f6a47e18c03d9b2a4f7e6c1"
+//! }
+//! ```
+//!
+//! `--reproduce` renders with the options of a manifest alone, its seeds
+//! given explicitly, and fails if the pixels don't come out the same.
+
+use std::{fmt, fs};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{platform, schema, Error, Result};
+
+/// The seeds of the random choices of a render.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Seeds {
+    /// The seed of `--jitter-seed`, for samples, Buddhabrots and stipples.
+    pub jitter_seed: u64,
+    /// The seed of `--seed`, for random viewports.
+    pub seed: u64,
+}
+
+/// How an image was rendered.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Manifest {
+    pub version: u64,
+    /// The name and version of the program that rendered it.
+    pub program: String,
+    pub output: String,
+    /// The options, and the positional arguments after the output.
+    pub args: Vec<String>,
+    pub seeds: Seeds,
+    /// The view, as the options resolved it.
+    pub pixels: [usize; 2],
+    pub upper_left: [f64; 2],
+    pub lower_right: [f64; 2],
+    pub max_iters: usize,
+    /// The digest of the pixels written, or of the text of an SVG or text
+    /// file, see `digest`.
+    pub digest: String,
+}
+
+/// The name and version of this program, as manifests record it.
+pub fn program() -> String {
+    format!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"))
+}
+
+/// The manifest written next to the image `output`: `dust.png` has
+/// `dust.manifest.json`.
+pub fn manifest_file(output: &str) -> String {
+    let stem = match output.rfind('.') {
+        Some(dot) if !output[dot..].contains(['/', '\\']) => &output[..dot],
+        _ => output,
+    };
+    format!("{}.manifest.json", stem)
+}
+
+#[test]
+fn test_manifest_file() {
+    assert_eq!(manifest_file("dust.png"), "dust.manifest.json");
+    assert_eq!(manifest_file("out/dust"), "out/dust.manifest.json");
+    assert_eq!(manifest_file("v1.0/dust"), "v1.0/dust.manifest.json");
+}
+
+/// The digest of `bytes`: 128 bits in hex, from two FNV-1a hashes starting
+/// apart, like the names of the disk cache.
+pub fn digest(bytes: &[u8]) -> String {
+    let fnv = |basis: u64| {
+        bytes.iter().fold(basis, |hash, &byte| {
+            (hash ^ byte as u64).wrapping_mul(0x0000_0100_0000_01b3)
+        })
+    };
+    format!(
+        "{:016x}{:016x}",
+        fnv(0xcbf2_9ce4_8422_2325),
+        fnv(0x6c62_272e_07bb_0142)
+    )
+}
+
+#[test]
+fn test_digest() {
+    assert_eq!(digest(b""), "cbf29ce4842223256c62272e07bb0142");
+    assert_eq!(digest(&[1, 2, 3]).len(), 32);
+    assert_ne!(digest(&[1, 2, 3]), digest(&[1, 3, 2]));
+}
+
+impl Manifest {
+    /// Read the manifest `file`.
+    pub fn open(file: &str) -> Result<Manifest> {
+        let json = fs::read_to_string(platform::path(file)).map_err(Error::io(file))?;
+        let invalid = |e: &dyn fmt::Display| Error::parse("manifest", &format!("{}: {}", file, e));
+        let mut value: serde_json::Value = serde_json::from_str(&json).map_err(|e| invalid(&e))?;
+        let version = schema::version("manifest", &value)?;
+        if version > schema::VERSION {
+            let mut dropped = Vec::new();
+            schema::drop_unknown::<Manifest>(&mut value, "", &mut dropped);
+            if let Some(seeds) = value.get_mut("seeds") {
+                schema::drop_unknown::<Seeds>(seeds, "seeds.", &mut dropped);
+            }
+            schema::warn_newer("the manifest", version, schema::VERSION, &dropped);
+        }
+        serde_json::from_value(value).map_err(|e| invalid(&e))
+    }
+
+    /// The manifest as JSON.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("manifests are plain values") + "\n"
+    }
+
+    /// The command line arguments, but the program name, that render the
+    /// image again, into `output`: the options recorded, with the seeds
+    /// after them.
+    pub fn to_args(&self, output: &str) -> Vec<String> {
+        let mut args = vec![output.to_string()];
+        args.extend(self.args.iter().cloned());
+        args.extend([
+            "--jitter-seed".to_string(),
+            self.seeds.jitter_seed.to_string(),
+            "--seed".to_string(),
+            self.seeds.seed.to_string(),
+        ]);
+        args
+    }
+}
+
+#[test]
+fn test_manifest() {
+    let manifest = Manifest {
+        version: schema::VERSION,
+        program: program(),
+        output: "dust.png".to_string(),
+        args: vec![
+            "80x60".to_string(),
+            "--fractal".to_string(),
+            "flame".to_string(),
+        ],
+        seeds: Seeds {
+            jitter_seed: 7,
+            seed: 0,
+        },
+        pixels: [80, 60],
+        upper_left: [-2.0, 1.5],
+        lower_right: [2.0, -1.5],
+        max_iters: 1000,
+        digest: digest(b"pixels"),
+    };
+    assert_eq!(
+        manifest.to_args("again.png"),
+        [
+            "again.png",
+            "80x60",
+            "--fractal",
+            "flame",
+            "--jitter-seed",
+            "7",
+            "--seed",
+            "0"
+        ]
+    );
+
+    let dir = std::env::temp_dir().join(format!("mandelbrot-reproduce-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let file = dir.join("dust.manifest.json");
+    let file = file.to_str().unwrap();
+    fs::write(file, manifest.to_json()).unwrap();
+    assert_eq!(Manifest::open(file).unwrap(), manifest);
+
+    // newer versions may add fields
+    let mut value: serde_json::Value = serde_json::from_str(&manifest.to_json()).unwrap();
+    value["version"] = (schema::VERSION + 1).into();
+    value["seeds"]["noise_seed"] = 3.into();
+    fs::write(file, value.to_string()).unwrap();
+    assert_eq!(Manifest::open(file).unwrap().seeds, manifest.seeds);
+
+    fs::write(file, "{\"version\": 1}").unwrap();
+    assert!(Manifest::open(file).is_err());
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,147 @@
+//! What a render did, for scripts, for `--result json`: written to stdout as
+//! one JSON object when the program ends, whether the render succeeded or
+//! not, so that pipelines don't have to scrape the messages meant for
+//! people.
+//!
+//! ```json
+//! {
+//!   "version": 1,
+//!   "status": "ok",
+//!   "exit_code": 0,
+//!   "error": null,
+//!   "outputs": ["dive.png", "dive-stats.json"],
+//!   "warnings": [{"code": "under-iteration-detected", "message": "..."}],
+//!   "stats": {"wall_seconds": 1.2, "render_seconds": 1.1, "pixels": 480000,
+//!             "max_iters": 255, "interior_percent": 23.5}
+//! }
+//! ```
+//!
+//! The fields are always there, `null` when they don't apply, and keep their
+//! names from one version to the next; new ones may be added. `status` is
+//! `ok`, `skipped` when `--on-exists skip` found the output there already,
+//! or `error`, with `exit_code` and `error` saying which.
+
+use serde::Serialize;
+
+use crate::{schema, Error};
+
+/// The codes of the warnings, which scripts can match on.
+pub const PRECISION_LIMIT_HIT: &str = "precision-limit-hit";
+pub const UNDER_ITERATION_DETECTED: &str = "under-iteration-detected";
+pub const ASPECT_MISMATCH: &str = "aspect-mismatch";
+
+/// Something about a render that didn't stop it, but makes the image less
+/// than it could be.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Warning {
+    pub code: &'static str,
+    pub message: String,
+}
+
+/// The main figures of a render.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Summary {
+    pub wall_seconds: f64,
+    pub render_seconds: f64,
+    pub pixels: usize,
+    pub max_iters: usize,
+    /// For escape time fractals in gray levels.
+    pub interior_percent: Option<f64>,
+}
+
+/// What a render did, gathered as it goes.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Outcome {
+    /// Every file written, in order.
+    pub outputs: Vec<String>,
+    pub warnings: Vec<Warning>,
+    pub stats: Option<Summary>,
+    /// Whether the render was skipped, its output being there already.
+    pub skipped: bool,
+}
+
+#[derive(Serialize)]
+struct Envelope<'a> {
+    version: u64,
+    status: &'static str,
+    exit_code: u8,
+    error: Option<String>,
+    outputs: &'a [String],
+    warnings: &'a [Warning],
+    stats: &'a Option<Summary>,
+}
+
+impl Outcome {
+    /// Note that `file` was written.
+    pub fn wrote(&mut self, file: &str) {
+        self.outputs.push(file.to_string());
+    }
+
+    /// Note the warning `code`, explained by `message`.
+    pub fn warn(&mut self, code: &'static str, message: String) {
+        self.warnings.push(Warning { code, message });
+    }
+
+    /// The JSON object for scripts, with how the render ended, `error` if it
+    /// failed.
+    pub fn to_json(&self, error: Option<&Error>) -> String {
+        let status = match (error, self.skipped) {
+            (Some(_), _) => "error",
+            (None, true) => "skipped",
+            (None, false) => "ok",
+        };
+        let envelope = Envelope {
+            version: schema::VERSION,
+            status,
+            exit_code: error.map_or(0, Error::exit_code),
+            error: error.map(Error::to_string),
+            outputs: &self.outputs,
+            warnings: &self.warnings,
+            stats: &self.stats,
+        };
+        serde_json::to_string_pretty(&envelope).expect("outcomes are plain values") + "\n"
+    }
+}
+
+#[test]
+fn test_outcome() {
+    use serde_json::{json, Value};
+
+    let mut outcome = Outcome::default();
+    outcome.wrote("a.png");
+    outcome.warn(PRECISION_LIMIT_HIT, "too deep".to_string());
+    outcome.stats = Some(Summary {
+        wall_seconds: 1.5,
+        render_seconds: 1.0,
+        pixels: 12,
+        max_iters: 255,
+        interior_percent: None,
+    });
+    let envelope: Value = serde_json::from_str(&outcome.to_json(None)).unwrap();
+    assert_eq!(
+        envelope,
+        json!({
+            "version": 1,
+            "status": "ok",
+            "exit_code": 0,
+            "error": null,
+            "outputs": ["a.png"],
+            "warnings": [{"code": "precision-limit-hit", "message": "too deep"}],
+            "stats": {"wall_seconds": 1.5, "render_seconds": 1.0, "pixels": 12,
+                      "max_iters": 255, "interior_percent": null},
+        })
+    );
+
+    let error = Error::Viewport("upside down".to_string());
+    let envelope: Value = serde_json::from_str(&Outcome::default().to_json(Some(&error))).unwrap();
+    assert_eq!(envelope["status"], "error");
+    assert_eq!(envelope["exit_code"], 4);
+    assert!(envelope["error"].as_str().unwrap().contains("upside down"));
+    assert_eq!(envelope["stats"], Value::Null);
+
+    let skipped = Outcome {
+        skipped: true,
+        ..Outcome::default()
+    };
+    assert!(skipped.to_json(None).contains("\"status\": \"skipped\""));
+}
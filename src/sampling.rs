@@ -0,0 +1,419 @@
+//! Supersampling: averaging several points inside each pixel, to smooth the
+//! jagged edges a single point per pixel leaves.
+//!
+//! The points follow a low-discrepancy pattern shifted by an offset drawn
+//! from a seed. Animations should use a different seed for every frame
+//! (`Sampling::for_frame`), so that the pattern jitters from frame to frame:
+//! edges that would crawl with a fixed pattern turn into fine noise, which
+//! blending consecutive frames with a `TemporalAccumulator` then averages out.
+//!
+//! With `jitter`, every pixel gets points of its own instead, drawn at random
+//! from the seed and the position of the pixel: the noise that leaves is
+//! finer than the patterns a shared set of offsets makes along filaments.
+
+use std::sync::Mutex;
+
+use num::Complex;
+
+use crate::{
+    render::{escape_time_with_bailout, gray_level, Schedule},
+    usage,
+};
+
+/// How a render samples its pixels.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Sampling {
+    /// How many points are averaged for each pixel; 1 samples the corner of
+    /// every pixel, like `render::render` does.
+    pub samples: usize,
+    /// Where the pattern of points starts.
+    pub seed: u64,
+    /// Whether every pixel draws random points of its own rather than
+    /// following the pattern.
+    pub jitter: bool,
+}
+
+impl Sampling {
+    /// One sample per pixel.
+    pub const NONE: Sampling = Sampling {
+        samples: 1,
+        seed: 0,
+        jitter: false,
+    };
+
+    /// The sampling for frame `frame` of an animation: the same number of
+    /// samples, with the pattern moved.
+    pub fn for_frame(self, frame: u64) -> Sampling {
+        Sampling {
+            seed: splitmix64(self.seed ^ splitmix64(frame)),
+            ..self
+        }
+    }
+
+    /// The offsets, within a pixel, of the points sampled, each coordinate
+    /// between 0 and 1.
+    pub fn pattern(&self) -> Vec<(f64, f64)> {
+        // the R2 sequence, which spreads points more evenly than a grid does
+        // for any number of them
+        const G: f64 = 1.324_717_957_244_746;
+        let (a1, a2) = (1.0 / G, 1.0 / (G * G));
+
+        let shift = splitmix64(self.seed);
+        let shift = (
+            (shift >> 32) as f64 / (1u64 << 32) as f64,
+            (shift & 0xffff_ffff) as f64 / (1u64 << 32) as f64,
+        );
+        (0..self.samples)
+            .map(|i| {
+                let i = i as f64;
+                ((shift.0 + i * a1).fract(), (shift.1 + i * a2).fract())
+            })
+            .collect()
+    }
+}
+
+#[test]
+fn test_pattern() {
+    let sampling = Sampling {
+        samples: 16,
+        seed: 7,
+        jitter: false,
+    };
+    let pattern = sampling.pattern();
+    assert_eq!(pattern.len(), 16);
+    assert!(pattern
+        .iter()
+        .all(|&(x, y)| (0.0..1.0).contains(&x) && (0.0..1.0).contains(&y)));
+    // every quarter of the pixel gets some of the points
+    for quarter in [(0.0, 0.0), (0.5, 0.0), (0.0, 0.5), (0.5, 0.5)] {
+        assert!(pattern.iter().any(|&(x, y)| x >= quarter.0
+            && x < quarter.0 + 0.5
+            && y >= quarter.1
+            && y < quarter.1 + 0.5));
+    }
+
+    assert_eq!(sampling.pattern(), pattern);
+    assert_ne!(sampling.for_frame(1).pattern(), pattern);
+    assert_ne!(sampling.for_frame(1), sampling.for_frame(2));
+}
+
+/// A 64 bit mixing function (from SplitMix64), turning seeds that are close
+/// together into unrelated ones.
+pub fn splitmix64(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// A small, fast pseudorandom number generator (SplitMix64). It is nowhere
+/// near good enough for cryptography, but plenty for sampling, and the same
+/// seed gives the same numbers everywhere.
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        Rng(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        let value = splitmix64(self.0);
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        value
+    }
+
+    /// A number between 0 (included) and 1 (excluded).
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+#[test]
+fn test_rng() {
+    let mut rng = Rng::new(42);
+    let numbers: Vec<f64> = (0..1000).map(|_| rng.next_f64()).collect();
+    assert!(numbers.iter().all(|x| (0.0..1.0).contains(x)));
+    let mean = numbers.iter().sum::<f64>() / numbers.len() as f64;
+    assert!((mean - 0.5).abs() < 0.05);
+
+    let mut again = Rng::new(42);
+    assert_eq!(again.next_f64(), numbers[0]);
+    assert_ne!(Rng::new(43).next_u64(), Rng::new(42).next_u64());
+}
+
+/// The variance of the gray levels of the points averaged in every pixel of
+/// a supersampled image, filled in as its rows are rendered. Where it is
+/// high, the points of a pixel disagree, and its average is noisy.
+pub struct Variance {
+    width: usize,
+    values: Mutex<Vec<f32>>,
+}
+
+impl Variance {
+    /// Room for an image whose dimensions are given by `bounds`.
+    pub fn new(bounds: (usize, usize)) -> Variance {
+        Variance {
+            width: bounds.0,
+            values: Mutex::new(vec![0.0; bounds.0 * bounds.1]),
+        }
+    }
+
+    /// Keep the variances of the rows starting at row `first_row`.
+    fn set_rows(&self, first_row: usize, rows: &[f32]) {
+        let start = first_row * self.width;
+        self.values.lock().unwrap()[start..start + rows.len()].copy_from_slice(rows);
+    }
+
+    /// The variance of every pixel, row by row.
+    pub fn values(&self) -> Vec<f32> {
+        self.values.lock().unwrap().clone()
+    }
+}
+
+/// The standard deviations of the pixels of `variance` as gray levels, twice
+/// as bright as they are deep, so that an image of them is white where the
+/// points of a pixel disagree the most.
+pub fn variance_gray(variance: &[f32]) -> Vec<u8> {
+    variance
+        .iter()
+        .map(|&variance| (2.0 * variance.sqrt()).round().min(255.0) as u8)
+        .collect()
+}
+
+#[test]
+fn test_variance() {
+    let variance = Variance::new((2, 3));
+    variance.set_rows(1, &[4.0, 100.0, 16256.25, 20000.0]);
+    assert_eq!(variance.values(), [0.0, 0.0, 4.0, 100.0, 16256.25, 20000.0]);
+    assert_eq!(variance_gray(&variance.values()), [0, 0, 4, 20, 255, 255]);
+}
+
+/// Render a rectangle of the Mandelbrot set into `pixels`, averaging the
+/// points of the pattern of `schedule.sampling` in each pixel, or random
+/// points with its `jitter`, giving up on points that haven't escaped after
+/// `schedule.limit` iterations. Their variance goes to `schedule.variance`,
+/// and the iterations they took to `schedule.cost`, if anywhere.
+///
+/// `first_row` is the row of the whole image the first row of `pixels` is,
+/// so that the random points of a pixel don't depend on how the image was
+/// cut into chunks. The other arguments are the same as for
+/// `render::render`.
+pub fn render_supersampled(
+    pixels: &mut [u8],
+    bounds: (usize, usize),
+    first_row: usize,
+    upper_left: Complex<f64>,
+    lower_right: Complex<f64>,
+    schedule: &Schedule,
+) {
+    assert!(pixels.len() == bounds.0 * bounds.1);
+
+    let (sampling, limit) = (&schedule.sampling, schedule.limit);
+    let mut pattern = sampling.pattern();
+    let step = Complex {
+        re: (lower_right.re - upper_left.re) / bounds.0 as f64,
+        im: (upper_left.im - lower_right.im) / bounds.1 as f64,
+    };
+    let (mut variances, mut costs) = (Vec::new(), Vec::new());
+    for row in 0..bounds.1 {
+        for column in 0..bounds.0 {
+            if sampling.jitter {
+                let pixel = ((first_row + row) * bounds.0 + column) as u64;
+                let mut rng = Rng::new(splitmix64(sampling.seed ^ splitmix64(pixel)));
+                for offset in &mut pattern {
+                    *offset = (rng.next_f64(), rng.next_f64());
+                }
+            }
+            let ((total, squares), cost) = usage::counting(|| {
+                let (mut total, mut squares) = (0, 0);
+                for &(dx, dy) in &pattern {
+                    let point = Complex {
+                        re: upper_left.re + (column as f64 + dx) * step.re,
+                        im: upper_left.im - (row as f64 + dy) * step.im,
+                    };
+                    let gray = gray_level(
+                        escape_time_with_bailout(point, limit, schedule.bailout),
+                        limit,
+                    ) as usize;
+                    total += gray;
+                    squares += gray * gray;
+                }
+                (total, squares)
+            });
+            costs.push(cost);
+            let count = pattern.len().max(1);
+            pixels[row * bounds.0 + column] = (total / count) as u8;
+            if schedule.variance.is_some() {
+                // of a sample, dividing by one less than the points
+                let mean = total as f64 / count as f64;
+                let spread = squares as f64 - total as f64 * mean;
+                variances.push((spread / (count - 1).max(1) as f64) as f32);
+            }
+        }
+    }
+    if let Some(variance) = schedule.variance {
+        variance.set_rows(first_row, &variances);
+    }
+    if let Some(cost) = schedule.cost {
+        cost.set_block((0, first_row), bounds.0, &costs);
+    }
+}
+
+#[test]
+fn test_render_supersampled() {
+    let bounds = (40, 30);
+    let (upper_left, lower_right) = (Complex { re: -2.0, im: 1.2 }, Complex { re: 0.6, im: -1.2 });
+    let mut pixels = vec![0; bounds.0 * bounds.1];
+    let sampling = Sampling {
+        samples: 8,
+        seed: 1,
+        jitter: false,
+    };
+    render_supersampled(
+        &mut pixels,
+        bounds,
+        0,
+        upper_left,
+        lower_right,
+        &Schedule {
+            sampling,
+            ..Schedule::new(1)
+        },
+    );
+
+    // deep inside the set everything is black, far outside almost white
+    assert_eq!(pixels[15 * bounds.0 + 28], 0);
+    assert!(pixels[0] > 250);
+    // and the edges get shades that a single sample can't give
+    let mut single = vec![0; bounds.0 * bounds.1];
+    crate::render::render(&mut single, bounds, upper_left, lower_right);
+    assert_ne!(pixels, single);
+
+    // where they do, the points of a pixel vary
+    let variance = Variance::new(bounds);
+    let mut again = vec![0; bounds.0 * bounds.1];
+    render_supersampled(
+        &mut again,
+        bounds,
+        0,
+        upper_left,
+        lower_right,
+        &Schedule {
+            sampling,
+            variance: Some(&variance),
+            ..Schedule::new(1)
+        },
+    );
+    assert_eq!(again, pixels);
+    let variance = variance.values();
+    assert_eq!(variance[15 * bounds.0 + 28], 0.0);
+    assert!(variance.iter().any(|&variance| variance > 100.0));
+}
+
+#[test]
+fn test_render_jittered() {
+    let bounds = (40, 30);
+    let (upper_left, lower_right) = (Complex { re: -2.0, im: 1.2 }, Complex { re: 0.6, im: -1.2 });
+    let sampling = Sampling {
+        samples: 8,
+        seed: 1,
+        jitter: true,
+    };
+    let render = |sampling: &Sampling| {
+        let mut pixels = vec![0; bounds.0 * bounds.1];
+        render_supersampled(
+            &mut pixels,
+            bounds,
+            0,
+            upper_left,
+            lower_right,
+            &Schedule {
+                sampling: *sampling,
+                ..Schedule::new(1)
+            },
+        );
+        pixels
+    };
+    let pixels = render(&sampling);
+    assert_eq!(pixels[15 * bounds.0 + 28], 0);
+    assert!(pixels[0] > 250);
+    assert_eq!(render(&sampling), pixels);
+    assert_ne!(
+        render(&Sampling {
+            seed: 2,
+            ..sampling
+        }),
+        pixels
+    );
+    assert_ne!(
+        render(&Sampling {
+            jitter: false,
+            ..sampling
+        }),
+        pixels
+    );
+
+    // the bottom half on its own gets the same points
+    let middle = crate::render::pixel_to_point(bounds, (0, 15), upper_left, lower_right);
+    let mut half = vec![0; bounds.0 * 15];
+    render_supersampled(
+        &mut half,
+        (bounds.0, 15),
+        15,
+        middle,
+        lower_right,
+        &Schedule {
+            sampling,
+            ..Schedule::new(1)
+        },
+    );
+    assert_eq!(half, pixels[15 * bounds.0..]);
+}
+
+/// Blends the frames of an animation as they are rendered, each frame
+/// counting for `weight` and the ones before it for the rest: the longer ago
+/// a frame was, the less it counts.
+///
+/// With the sampling pattern jittered every frame, this accumulates samples
+/// over time, at the price of some ghosting where the image moves fast.
+pub struct TemporalAccumulator {
+    weight: f64,
+    history: Vec<f64>,
+}
+
+impl TemporalAccumulator {
+    /// `weight` is between 0 (the first frame stays forever) and 1 (no
+    /// blending at all).
+    pub fn new(weight: f64) -> TemporalAccumulator {
+        TemporalAccumulator {
+            weight: weight.clamp(0.0, 1.0),
+            history: Vec::new(),
+        }
+    }
+
+    /// Blend `frame` into the history, and return the blended frame. A frame
+    /// of a different size than the last one starts over.
+    pub fn add(&mut self, frame: &[u8]) -> Vec<u8> {
+        if self.history.len() != frame.len() {
+            self.history = frame.iter().map(|&p| p as f64).collect();
+        } else {
+            for (old, &new) in self.history.iter_mut().zip(frame) {
+                *old += (new as f64 - *old) * self.weight;
+            }
+        }
+        self.history.iter().map(|&p| p.round() as u8).collect()
+    }
+}
+
+#[test]
+fn test_temporal_accumulator() {
+    let mut accumulator = TemporalAccumulator::new(0.5);
+    assert_eq!(accumulator.add(&[0, 200]), [0, 200]);
+    assert_eq!(accumulator.add(&[100, 100]), [50, 150]);
+    assert_eq!(accumulator.add(&[50, 150]), [50, 150]);
+    assert_eq!(accumulator.add(&[7]), [7]);
+
+    let mut none = TemporalAccumulator::new(1.0);
+    none.add(&[0]);
+    assert_eq!(none.add(&[9]), [9]);
+}
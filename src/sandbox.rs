@@ -0,0 +1,379 @@
+//! Rendering in a restricted child process, for `serve --sandbox`.
+//!
+//! A public server renders whatever views anyone asks for, so a bug in
+//! rendering or encoding is a bug anyone can reach. With `--sandbox` each
+//! render runs in a child process instead: the program itself, run again
+//! as `mandelbrot sandboxed-render` with an empty environment. The child
+//! installs a seccomp filter first thing, then reads the job from its stdin
+//! and writes the encoded image to its stdout, and the server answers with
+//! that. The filter only lets through the system calls a render needs:
+//! reading and writing the pipes it was given, managing memory, starting
+//! threads and exiting. Opening files and sockets, and running programs,
+//! fail with `EPERM`, so a compromised render can neither read nor write the
+//! filesystem, nor reach the network.
+//!
+//! Starting a process for every render costs a millisecond or two; tiles
+//! asked for again come from the server's cache without one.
+
+use std::{
+    collections::HashMap,
+    io::{self, Read, Write},
+    path::Path,
+    process::{Command, Stdio},
+    time::Duration,
+};
+
+use num::Complex;
+
+use crate::{
+    cpu::{self, Kernel},
+    encode::{Encoding, Renderer},
+    error, palette, parse_complex, parse_pair,
+    render::Strategy,
+    DecimalSeparator, Error, Result,
+};
+
+/// The subcommand the child process is run with.
+pub const SUBCOMMAND: &str = "sandboxed-render";
+
+/// A render, as the server sends it to the child process.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Job {
+    pub bounds: (usize, usize),
+    pub upper_left: Complex<f64>,
+    pub lower_right: Complex<f64>,
+    pub strategy: Strategy,
+    pub limit: usize,
+    pub timeout: Option<Duration>,
+    pub palette: Option<&'static str>,
+    pub format: Encoding,
+    /// The kernel the server computes escape times with, so that the child
+    /// uses the one forced too.
+    pub kernel: Kernel,
+}
+
+impl Job {
+    /// The job of rendering what `renderer` would, encoded in `format`.
+    pub fn new(renderer: &Renderer, format: Encoding) -> Job {
+        Job {
+            bounds: renderer.bounds,
+            upper_left: renderer.upper_left,
+            lower_right: renderer.lower_right,
+            strategy: renderer.strategy,
+            limit: renderer.limit,
+            timeout: renderer.timeout,
+            palette: renderer.palette,
+            format,
+            kernel: cpu::kernel(),
+        }
+    }
+
+    /// Write the job as the child reads it, a query string like the tasks
+    /// of `distributed`.
+    pub fn to_query(&self) -> String {
+        let mut query = format!(
+            "pixels={}x{}&upper-left={},{}&lower-right={},{}&strategy={}&limit={}&format={}&kernel={}",
+            self.bounds.0,
+            self.bounds.1,
+            self.upper_left.re,
+            self.upper_left.im,
+            self.lower_right.re,
+            self.lower_right.im,
+            self.strategy,
+            self.limit,
+            match self.format {
+                Encoding::Png => "png",
+                Encoding::Gif => "gif",
+                Encoding::Pgm => "pgm",
+            },
+            self.kernel.name(),
+        );
+        if let Some(timeout) = self.timeout {
+            query.push_str(&format!("&timeout={}", timeout.as_secs_f64()));
+        }
+        if let Some(palette) = self.palette {
+            query.push_str(&format!("&palette={}", palette));
+        }
+        query
+    }
+
+    /// Parse a job as the child reads it.
+    pub fn from_query(query: &str) -> Result<Job> {
+        let invalid = || Error::parse("sandboxed job", query.trim());
+        let mut fields = HashMap::new();
+        for parameter in query.trim().split('&') {
+            let (name, value) = parameter.split_once('=').ok_or_else(invalid)?;
+            fields.insert(name, value);
+        }
+        let field = |name: &str| fields.get(name).copied().ok_or_else(invalid);
+        let point =
+            |name: &str| parse_complex(field(name)?, DecimalSeparator::Point).ok_or_else(invalid);
+        let timeout = match fields.get("timeout") {
+            Some(seconds) => Some(
+                seconds
+                    .parse()
+                    .ok()
+                    .and_then(|seconds| Duration::try_from_secs_f64(seconds).ok())
+                    .ok_or_else(invalid)?,
+            ),
+            None => None,
+        };
+        let palette = match fields.get("palette") {
+            Some(name) => Some(palette::preset_name(name).ok_or_else(invalid)?),
+            None => None,
+        };
+        let job = Job {
+            bounds: parse_pair(field("pixels")?, 'x').ok_or_else(invalid)?,
+            upper_left: point("upper-left")?,
+            lower_right: point("lower-right")?,
+            strategy: field("strategy")?.parse().map_err(|_| invalid())?,
+            limit: field("limit")?.parse().map_err(|_| invalid())?,
+            timeout,
+            palette,
+            format: field("format")?.parse().map_err(|_| invalid())?,
+            kernel: field("kernel")?.parse().map_err(|_| invalid())?,
+        };
+        error::validate_viewport(job.bounds, job.upper_left, job.lower_right)?;
+        Ok(job)
+    }
+
+    /// Render and encode the image, in this process.
+    fn render(&self) -> Result<Vec<u8>> {
+        let mut renderer = Renderer::new(self.bounds, self.upper_left, self.lower_right);
+        renderer.strategy = self.strategy;
+        renderer.limit = self.limit;
+        renderer.timeout = self.timeout;
+        renderer.palette = self.palette;
+        renderer.render_to_encoded_bytes(self.format)
+    }
+}
+
+#[test]
+fn test_job() {
+    let job = Job {
+        bounds: (40, 12),
+        upper_left: Complex::new(-2.5, 1.1),
+        lower_right: Complex::new(1.0, -1.0 / 3.0),
+        strategy: Strategy::BorderTrace,
+        limit: 100,
+        timeout: Some(Duration::from_millis(1500)),
+        palette: Some("magma"),
+        format: Encoding::Gif,
+        kernel: Kernel::Scalar,
+    };
+    assert_eq!(Job::from_query(&(job.to_query() + "\n")).unwrap(), job);
+    let untimed = Job {
+        timeout: None,
+        palette: None,
+        ..job.clone()
+    };
+    assert_eq!(Job::from_query(&untimed.to_query()).unwrap(), untimed);
+
+    let query = job.to_query();
+    assert!(Job::from_query(&query.replace("&strategy=border-trace", "")).is_err());
+    assert!(Job::from_query(&query.replace("border-trace", "fast")).is_err());
+    assert!(Job::from_query(&query.replace("gif", "bmp")).is_err());
+    assert!(Job::from_query(&query.replace("magma", "plaid")).is_err());
+    assert!(Job::from_query(&query.replace("1.1", "-2")).is_err());
+    assert!(Job::from_query("").is_err());
+}
+
+/// Render and encode what `renderer` would, in `format`, in a child process
+/// running `program`, this program, restricted.
+pub fn render(program: &Path, renderer: &Renderer, format: Encoding) -> Result<Vec<u8>> {
+    let mut child = Command::new(program)
+        .arg(SUBCOMMAND)
+        .env_clear()
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| Error::Sandbox(format!("can't run {}: {}", program.display(), e)))?;
+    // the child reads all of the job before it writes anything
+    let mut stdin = child.stdin.take().unwrap();
+    let sent = stdin.write_all(Job::new(renderer, format).to_query().as_bytes());
+    drop(stdin);
+    let output = child
+        .wait_with_output()
+        .map_err(|e| Error::Sandbox(e.to_string()))?;
+    if output.status.success() {
+        return Ok(output.stdout);
+    }
+    sent.map_err(|e| Error::Sandbox(format!("can't send the job: {}", e)))?;
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let message = stderr.trim().trim_start_matches("error: ").to_string();
+    Err(match output.status.code() {
+        Some(code) if code == Error::Interrupted(String::new()).exit_code() as i32 => {
+            Error::Interrupted(message)
+        }
+        Some(_) => Error::Sandbox(message),
+        None => Error::Sandbox(format!("the render process died: {}", output.status)),
+    })
+}
+
+/// Run the `sandboxed-render` subcommand: restrict this process, then
+/// render the job read from stdin, and write the image to stdout.
+pub fn run() -> Result<()> {
+    restrict().map_err(|e| Error::Sandbox(format!("can't install the filter: {}", e)))?;
+    let mut query = String::new();
+    io::stdin()
+        .read_to_string(&mut query)
+        .map_err(Error::io("stdin"))?;
+    let job = Job::from_query(&query)?;
+    cpu::force(job.kernel)?;
+    let encoded = job.render()?;
+    let mut stdout = io::stdout().lock();
+    stdout.write_all(&encoded).map_err(Error::io("stdout"))?;
+    stdout.flush().map_err(Error::io("stdout"))
+}
+
+/// The `AUDIT_ARCH_*` value of `seccomp_data.arch` for the system calls of
+/// the architecture the program was built for; the others are refused, or
+/// their numbers would mean other calls.
+#[cfg(target_arch = "x86_64")]
+const AUDIT_ARCH: u32 = 0xc000_003e;
+#[cfg(target_arch = "aarch64")]
+const AUDIT_ARCH: u32 = 0xc000_00b7;
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+compile_error!("the sandbox feature is for x86-64 and AArch64 Linux only");
+
+/// The system calls a restricted process may make: reading its job,
+/// writing its image or error, allocating memory, starting the thread a
+/// render with a timeout runs on, and exiting, also by panicking.
+const ALLOWED: &[libc::c_long] = &[
+    libc::SYS_read,
+    libc::SYS_write,
+    libc::SYS_writev,
+    libc::SYS_close,
+    libc::SYS_brk,
+    libc::SYS_mmap,
+    libc::SYS_munmap,
+    libc::SYS_mremap,
+    libc::SYS_mprotect,
+    libc::SYS_madvise,
+    libc::SYS_futex,
+    libc::SYS_clone,
+    libc::SYS_clone3,
+    libc::SYS_set_robust_list,
+    libc::SYS_rseq,
+    libc::SYS_sched_yield,
+    libc::SYS_getrandom,
+    libc::SYS_clock_gettime,
+    libc::SYS_rt_sigaction,
+    libc::SYS_rt_sigprocmask,
+    libc::SYS_rt_sigreturn,
+    libc::SYS_sigaltstack,
+    libc::SYS_getpid,
+    libc::SYS_gettid,
+    libc::SYS_tgkill,
+    libc::SYS_exit,
+    libc::SYS_exit_group,
+];
+
+/// Keep this process, from now on, from making any system call but the
+/// `ALLOWED` ones, which fail with `EPERM` instead, and from gaining
+/// privileges by running set-user-ID programs. There is no undoing it.
+pub fn restrict() -> io::Result<()> {
+    use libc::{
+        sock_filter, sock_fprog, BPF_ABS, BPF_JEQ, BPF_JMP, BPF_K, BPF_LD, BPF_RET, BPF_W, EPERM,
+        SECCOMP_RET_ALLOW, SECCOMP_RET_ERRNO, SECCOMP_RET_KILL_PROCESS,
+    };
+
+    let statement = |code: u32, k: u32| sock_filter {
+        code: code as u16,
+        jt: 0,
+        jf: 0,
+        k,
+    };
+    // skip the next instruction unless the accumulator is `k`
+    let unless_equal = |k: u32| sock_filter {
+        code: (BPF_JMP | BPF_JEQ | BPF_K) as u16,
+        jt: 0,
+        jf: 1,
+        k,
+    };
+    // where `struct seccomp_data` has `nr` and `arch`
+    let (nr, arch) = (0, 4);
+    let mut filter = vec![
+        statement(BPF_LD | BPF_W | BPF_ABS, arch),
+        // skip the next instruction if the accumulator is `AUDIT_ARCH`
+        sock_filter {
+            code: (BPF_JMP | BPF_JEQ | BPF_K) as u16,
+            jt: 1,
+            jf: 0,
+            k: AUDIT_ARCH,
+        },
+        statement(BPF_RET | BPF_K, SECCOMP_RET_KILL_PROCESS),
+        statement(BPF_LD | BPF_W | BPF_ABS, nr),
+    ];
+    for &call in ALLOWED {
+        filter.push(unless_equal(call as u32));
+        filter.push(statement(BPF_RET | BPF_K, SECCOMP_RET_ALLOW));
+    }
+    filter.push(statement(BPF_RET | BPF_K, SECCOMP_RET_ERRNO | EPERM as u32));
+    let program = sock_fprog {
+        len: filter.len() as u16,
+        filter: filter.as_mut_ptr(),
+    };
+
+    // the filter is only read during the calls, and copied by the kernel
+    unsafe {
+        if libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) != 0
+            || libc::prctl(
+                libc::PR_SET_SECCOMP,
+                libc::SECCOMP_MODE_FILTER,
+                &program as *const sock_fprog,
+            ) != 0
+        {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+/// Restrict the test process run again by `test_restrict`, and check what
+/// it can still do.
+#[cfg(test)]
+fn restricted_checks() {
+    use std::{fs::File, net::TcpListener};
+
+    restrict().unwrap();
+    let refused = |e: io::Error| assert_eq!(e.raw_os_error(), Some(libc::EPERM), "{}", e);
+    refused(File::open("Cargo.toml").unwrap_err());
+    refused(File::create("sandboxed").unwrap_err());
+    refused(TcpListener::bind("127.0.0.1:0").unwrap_err());
+    refused(Command::new("true").status().unwrap_err());
+    let job = Job {
+        bounds: (300, 200),
+        upper_left: Complex::new(-2.0, 1.0),
+        lower_right: Complex::new(1.0, -1.0),
+        strategy: Strategy::BorderTrace,
+        limit: 255,
+        timeout: Some(Duration::from_secs(60)),
+        palette: None,
+        format: Encoding::Pgm,
+        kernel: cpu::kernel(),
+    };
+    assert!(job.render().unwrap().starts_with(b"P5\n300 200\n255\n"));
+}
+
+#[test]
+fn test_restrict() {
+    // restricting the test process itself would break the harness, so the
+    // checks run in a copy of it
+    if std::env::var_os("MANDELBROT_TEST_RESTRICTED").is_some() {
+        return restricted_checks();
+    }
+    let output = Command::new(std::env::current_exe().unwrap())
+        .args(["--exact", "sandbox::test_restrict", "--nocapture"])
+        .env("MANDELBROT_TEST_RESTRICTED", "1")
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        output.status.success() && stdout.contains("1 passed"),
+        "{}{}",
+        stdout,
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
@@ -0,0 +1,148 @@
+//! The versions of the formats parameters are written in, and reading what
+//! newer versions of the program wrote as best we can.
+//!
+//! Everything the program writes parameters into says which version of its
+//! format it is: the view embedded in images, projects, scenes, the bookmark
+//! store and statistics. Newer versions may add fields; a document of a
+//! version newer than we know has the fields we don't know dropped, with a
+//! warning, and the rest is used, so that images and projects made with a
+//! newer install still render on an older one. Unknown fields in documents
+//! of the versions we know are still errors, being most likely typos.
+
+use serde::{
+    de::{self, Visitor},
+    forward_to_deserialize_any, Deserialize, Deserializer,
+};
+use serde_json::Value;
+
+use crate::{Error, Result};
+
+/// The version of the parameters this program writes, and the newest it
+/// reads in full. Projects have versions of their own, see `project`.
+pub const VERSION: u64 = 1;
+
+/// Tell the user about something that isn't an error, but that they might
+/// not expect.
+pub fn warn(message: &str) {
+    eprintln!("warning: {}", message);
+}
+
+/// A deserializer which only finds out the names of the fields of a struct.
+struct FieldNames<'a>(&'a mut &'static [&'static str]);
+
+impl<'de> Deserializer<'de> for FieldNames<'_> {
+    type Error = de::value::Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, _: V) -> std::result::Result<V::Value, Self::Error> {
+        Err(de::Error::custom("not a struct"))
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _: &'static str,
+        fields: &'static [&'static str],
+        _: V,
+    ) -> std::result::Result<V::Value, Self::Error> {
+        *self.0 = fields;
+        Err(de::Error::custom("only the fields are wanted"))
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf
+        option unit unit_struct newtype_struct seq tuple tuple_struct map enum identifier
+        ignored_any
+    }
+}
+
+/// The names of the fields of the struct `T`, as it is written.
+pub fn fields<'de, T: Deserialize<'de>>() -> &'static [&'static str] {
+    let mut fields: &'static [&'static str] = &[];
+    let _ = T::deserialize(FieldNames(&mut fields));
+    fields
+}
+
+/// The version the document `value` says it is written in, and 1 if it
+/// doesn't say, having been written before documents said.
+pub fn version(what: &'static str, value: &Value) -> Result<u64> {
+    match value.get("version") {
+        None => Ok(1),
+        Some(version) => version
+            .as_u64()
+            .filter(|&version| version >= 1)
+            .ok_or_else(|| Error::parse(what, &format!("version {}", version))),
+    }
+}
+
+/// Like `version`, but taking the version out of `value`, for documents
+/// whose fields don't include it.
+pub fn take_version(what: &'static str, value: &mut Value) -> Result<u64> {
+    let version = self::version(what, value)?;
+    if let Value::Object(object) = value {
+        object.remove("version");
+    }
+    Ok(version)
+}
+
+/// Drop the fields of the JSON object `value` the struct `T` doesn't have,
+/// adding their names, after `path`, to `dropped`.
+pub fn drop_unknown<'de, T: Deserialize<'de>>(
+    value: &mut Value,
+    path: &str,
+    dropped: &mut Vec<String>,
+) {
+    let known = fields::<T>();
+    if let Value::Object(object) = value {
+        object.retain(|name, _| {
+            let keep = known.contains(&name.as_str());
+            if !keep {
+                dropped.push(format!("{}{}", path, name));
+            }
+            keep
+        });
+    }
+}
+
+/// Warn that the `what` in version `version`, newer than `current`, is
+/// read as best we can, without the fields `dropped`.
+pub fn warn_newer(what: &str, version: u64, current: u64, dropped: &[String]) {
+    let mut message = format!(
+        "{} is in version {} of its format, newer than this program's {}",
+        what, version, current
+    );
+    if dropped.is_empty() {
+        message += "; reading it as best it can";
+    } else {
+        message += &format!("; ignoring {}", dropped.join(", "));
+    }
+    warn(&message);
+}
+
+#[test]
+fn test_fields() {
+    #[derive(Deserialize)]
+    #[allow(dead_code)]
+    struct Example {
+        a: u8,
+        #[serde(rename = "bee")]
+        b: Option<u8>,
+    }
+    assert_eq!(fields::<Example>(), ["a", "bee"]);
+    assert!(fields::<u8>().is_empty());
+
+    let mut value = serde_json::json!({"a": 1, "c": 2, "d": 3});
+    let mut dropped = Vec::new();
+    drop_unknown::<Example>(&mut value, "x.", &mut dropped);
+    assert_eq!(value, serde_json::json!({"a": 1}));
+    assert_eq!(dropped, ["x.c", "x.d"]);
+
+    assert_eq!(version("job", &serde_json::json!({})).unwrap(), 1);
+    assert_eq!(
+        version("job", &serde_json::json!({"version": 3})).unwrap(),
+        3
+    );
+    assert!(version("job", &serde_json::json!({"version": 0})).is_err());
+    assert!(version("job", &serde_json::json!({"version": "2"})).is_err());
+    let mut value = serde_json::json!({"version": 2, "a": 1});
+    assert_eq!(take_version("job", &mut value).unwrap(), 2);
+    assert_eq!(value, serde_json::json!({"a": 1}));
+}
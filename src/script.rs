@@ -0,0 +1,317 @@
+//! Coloring the Mandelbrot set with a script, so that new coloring schemes
+//! don't need a new build of the crate.
+//!
+//! The script is written in [Rhai](https://rhai.rs) and defines a function
+//! `color`, which gets what is known about how a point escaped and returns
+//! its red, green and blue levels, from 0 to 255:
+//!
+//! ```text
+//! fn color(e) {
+//!     if e.inside { return [0, 0, 0]; }
+//!     let t = e.smooth / e.max_iters;
+//!     [255 * t, 255 * t.sqrt(), 128 + 127 * (e.trap * 10.0).sin()]
+//! }
+//! ```
+//!
+//! The argument is a map of:
+//!
+//! - `inside`: whether the point is taken to be in the set;
+//! - `smooth`: the number of iterations it took to escape, smoothed into a
+//!   continuous value, or `max_iters` for points inside;
+//! - `magnitude`: `|z|` at the last iteration;
+//! - `trap`: the closest the orbit came to 0, for orbit trap coloring;
+//! - `max_iters`: the iteration limit.
+
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Mutex,
+};
+
+use num::Complex;
+use rhai::{Array, Dynamic, Engine, Map, Scope, AST};
+
+use crate::{
+    platform,
+    render::{pixel_to_point, Schedule},
+    usage::{self, Usage},
+    Error, Result,
+};
+
+/// How far from 0 an orbit has to get to escape, at least. Far beyond 2, so
+/// that the smoothed iteration count is smooth.
+const BAILOUT: f64 = 256.0;
+
+/// What is known about how a point escaped, or didn't.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Escape {
+    pub inside: bool,
+    pub smooth: f64,
+    pub magnitude: f64,
+    pub trap: f64,
+}
+
+/// Iterate `z * z + c` from 0 at most `max_iters` times and describe how
+/// `c` escaped from the circle of radius `bailout`, or `BAILOUT` if that is
+/// larger.
+///
+/// The smoothed count doesn't depend on the radius, once it is large enough:
+/// the larger, the closer the count gets to the exact one.
+pub fn escape(c: Complex<f64>, max_iters: usize, bailout: f64) -> Escape {
+    let bailout = bailout.max(BAILOUT);
+    let mut z = Complex::new(0.0, 0.0);
+    let mut trap = f64::INFINITY;
+    for i in 0..max_iters {
+        z = z * z + c;
+        let magnitude = z.norm();
+        trap = trap.min(magnitude);
+        if magnitude > bailout {
+            usage::count_iterations(i + 1);
+            return Escape {
+                inside: false,
+                smooth: (i as f64 + 1.0 - magnitude.ln().log2()).max(0.0),
+                magnitude,
+                trap,
+            };
+        }
+    }
+    usage::count_iterations(max_iters);
+    Escape {
+        inside: true,
+        smooth: max_iters as f64,
+        magnitude: z.norm(),
+        trap,
+    }
+}
+
+#[test]
+fn test_escape() {
+    let inside = escape(Complex::new(-1.0, 0.0), 100, 2.0);
+    assert!(inside.inside);
+    assert_eq!(inside.smooth, 100.0);
+    assert_eq!(inside.trap, 0.0);
+
+    let near = escape(Complex::new(0.3, 0.6), 1000, 2.0);
+    let far = escape(Complex::new(1.0, 1.0), 1000, 2.0);
+    assert!(!near.inside && !far.inside);
+    assert!(far.smooth < near.smooth);
+    assert!(far.magnitude > BAILOUT);
+
+    // a larger radius takes more iterations to the same smoothed count
+    let farther = escape(Complex::new(0.3, 0.6), 1000, 1e6);
+    assert!(farther.magnitude > 1e6);
+    assert!((farther.smooth - near.smooth).abs() < 0.01, "{:?}", farther);
+}
+
+/// A compiled color script.
+pub struct ColorScript {
+    engine: Engine,
+    ast: AST,
+}
+
+impl ColorScript {
+    /// Compile `source`, which must define `color`.
+    pub fn compile(source: &str) -> Result<ColorScript> {
+        let mut engine = Engine::new();
+        // a script is only there to compute colors
+        engine.set_max_operations(100_000);
+        engine.set_max_call_levels(32);
+        let ast = engine
+            .compile(source)
+            .map_err(|e| Error::Script(e.to_string()))?;
+        if !ast
+            .iter_functions()
+            .any(|f| f.name == "color" && f.params.len() == 1)
+        {
+            return Err(Error::Script(
+                "the script has no function color(e)".to_string(),
+            ));
+        }
+        Ok(ColorScript { engine, ast })
+    }
+
+    /// Read and compile the script in the file named `filename`.
+    pub fn read(filename: &str) -> Result<ColorScript> {
+        let source =
+            std::fs::read_to_string(platform::path(filename)).map_err(Error::io(filename))?;
+        ColorScript::compile(&source)
+    }
+
+    /// The color the script gives a point that escaped as `escape` says.
+    pub fn color(&self, escape: &Escape, max_iters: usize) -> Result<[u8; 3]> {
+        let mut e = Map::new();
+        e.insert("inside".into(), escape.inside.into());
+        e.insert("smooth".into(), escape.smooth.into());
+        e.insert("magnitude".into(), escape.magnitude.into());
+        e.insert("trap".into(), escape.trap.into());
+        e.insert("max_iters".into(), (max_iters as f64).into());
+
+        let result: Dynamic = self
+            .engine
+            .call_fn(&mut Scope::new(), &self.ast, "color", (e,))
+            .map_err(|e| Error::Script(e.to_string()))?;
+        let invalid = || {
+            Error::Script(format!(
+                "color must return [red, green, blue], not {}",
+                result
+            ))
+        };
+        let levels: Array = result.clone().try_cast().ok_or_else(invalid)?;
+        if levels.len() != 3 {
+            return Err(invalid());
+        }
+        let mut rgb = [0; 3];
+        for (level, value) in rgb.iter_mut().zip(levels) {
+            let value = match value.as_int() {
+                Ok(int) => int as f64,
+                Err(_) => value.as_float().map_err(|_| invalid())?,
+            };
+            *level = value.round().clamp(0.0, 255.0) as u8;
+        }
+        Ok(rgb)
+    }
+}
+
+#[test]
+fn test_color_script() {
+    let script = ColorScript::compile(
+        "fn color(e) { if e.inside { [0, 0, 0] } else { [e.smooth, 300, -5.4] } }",
+    )
+    .unwrap();
+    let escape = Escape {
+        inside: false,
+        smooth: 12.4,
+        magnitude: 300.0,
+        trap: 0.5,
+    };
+    assert_eq!(script.color(&escape, 100).unwrap(), [12, 255, 0]);
+    let inside = Escape {
+        inside: true,
+        ..escape
+    };
+    assert_eq!(script.color(&inside, 100).unwrap(), [0, 0, 0]);
+
+    assert!(ColorScript::compile("fn colour(e) { [0, 0, 0] }").is_err());
+    assert!(ColorScript::compile("fn color(e) { [0, 0, 0 }").is_err());
+    for bad in ["[1, 2]", "\"red\"", "[1, 2, \"3\"]", "loop {}"] {
+        let script = ColorScript::compile(&format!("fn color(e) {{ {} }}", bad)).unwrap();
+        assert!(script.color(&escape, 100).is_err(), "{}", bad);
+    }
+}
+
+/// Render the Mandelbrot set over the rectangle between `upper_left` and
+/// `lower_right` into `pixels`, three bytes (red, green and blue) per pixel
+/// colored by `script`, on the threads of `schedule`.
+///
+/// The threads take `schedule.chunk_rows` rows at a time, as in
+/// `render::render_parallel`, counting them in `schedule.progress` and
+/// stopping when `schedule.stopped()` says so. The first error the script runs
+/// into stops the render and is returned.
+pub fn render(
+    pixels: &mut [u8],
+    bounds: (usize, usize),
+    upper_left: Complex<f64>,
+    lower_right: Complex<f64>,
+    script: &ColorScript,
+    max_iters: usize,
+    schedule: &Schedule,
+) -> Result<()> {
+    assert!(pixels.len() == 3 * bounds.0 * bounds.1);
+    if pixels.is_empty() {
+        return Ok(());
+    }
+
+    let next_row = AtomicUsize::new(0);
+    let output = Mutex::new(pixels);
+    let failure = Mutex::new(None);
+    let (next_row, output, failure) = (&next_row, &output, &failure);
+    crossbeam::scope(|spawner| {
+        for thread in 0..schedule.threads.max(1) {
+            spawner.spawn(move |_| {
+                let mut chunk = Vec::new();
+                loop {
+                    if schedule.stopped() {
+                        break;
+                    }
+                    let top = next_row.fetch_add(schedule.chunk_rows, Ordering::Relaxed);
+                    if top >= bounds.1 {
+                        break;
+                    }
+                    let height = schedule.chunk_rows.min(bounds.1 - top);
+
+                    chunk.clear();
+                    let colored = Usage::measure(schedule.usage, thread, height, || {
+                        for row in top..top + height {
+                            for column in 0..bounds.0 {
+                                let c =
+                                    pixel_to_point(bounds, (column, row), upper_left, lower_right);
+                                let escape = escape(c, max_iters, schedule.bailout);
+                                chunk.extend(script.color(&escape, max_iters)?);
+                            }
+                        }
+                        Ok(())
+                    });
+                    if let Err(e) = colored {
+                        failure.lock().unwrap().get_or_insert(e);
+                        // let the other threads stop too
+                        next_row.store(bounds.1, Ordering::Relaxed);
+                        return;
+                    }
+
+                    let mut output = output.lock().unwrap();
+                    output[3 * top * bounds.0..3 * (top + height) * bounds.0]
+                        .copy_from_slice(&chunk);
+                    if let Some(progress) = schedule.progress {
+                        progress.add_rows(height);
+                    }
+                }
+            });
+        }
+    })
+    .unwrap();
+
+    let failure = failure.lock().unwrap().take();
+    match failure {
+        Some(e) => Err(e),
+        None => Ok(()),
+    }
+}
+
+#[test]
+fn test_render() {
+    let script = ColorScript::compile(
+        "fn color(e) { if e.inside { [0, 0, 0] } else { [255, e.smooth, 0] } }",
+    )
+    .unwrap();
+    let bounds = (12, 8);
+    let (upper_left, lower_right) = (Complex::new(-2.0, 1.0), Complex::new(1.0, -1.0));
+    let mut pixels = vec![7; 3 * bounds.0 * bounds.1];
+    render(
+        &mut pixels,
+        bounds,
+        upper_left,
+        lower_right,
+        &script,
+        100,
+        &Schedule {
+            chunk_rows: 3,
+            ..Schedule::new(2)
+        },
+    )
+    .unwrap();
+    // -0.25 is inside, the corner isn't
+    let center = 3 * (4 * bounds.0 + 7);
+    assert_eq!(pixels[center..center + 3], [0, 0, 0]);
+    assert_eq!(pixels[0], 255);
+
+    let broken = ColorScript::compile("fn color(e) { e.nope + 1 }").unwrap();
+    assert!(render(
+        &mut pixels,
+        bounds,
+        upper_left,
+        lower_right,
+        &broken,
+        100,
+        &Schedule::new(2),
+    )
+    .is_err());
+}
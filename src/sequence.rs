@@ -0,0 +1,266 @@
+//! Writing the frames of an animation as one animated image, a GIF or an
+//! APNG, rather than as a PNG file per frame.
+//!
+//! GIF images have at most 256 colors a frame: grayscale frames fit exactly,
+//! and colored frames are quantized to a palette of their own. APNG images
+//! keep every frame as it is, at the cost of larger files.
+
+use std::{borrow::Cow, cell::RefCell, io, io::Write, rc::Rc, str::FromStr};
+
+use gif::{Frame, Repeat, SetParameter};
+use image::{png::PNGEncoder, ColorType};
+
+use crate::{share, Error, Result};
+
+/// How the frames of an animation are written.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Format {
+    /// A PNG file per frame.
+    #[default]
+    Png,
+    /// One looping animated GIF.
+    Gif,
+    /// One looping animated PNG.
+    Apng,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "png" => Ok(Format::Png),
+            "gif" => Ok(Format::Gif),
+            "apng" => Ok(Format::Apng),
+            _ => Err(format!("unknown format {:?}, expected png, gif or apng", s)),
+        }
+    }
+}
+
+#[test]
+fn test_parse_format() {
+    assert_eq!("gif".parse(), Ok(Format::Gif));
+    assert_eq!("apng".parse(), Ok(Format::Apng));
+    assert_eq!("png".parse(), Ok(Format::default()));
+    assert!("mp4".parse::<Format>().is_err());
+}
+
+/// The bytes the GIF encoder writes, which are still there once it is
+/// dropped.
+#[derive(Clone, Default)]
+struct Buffer(Rc<RefCell<Vec<u8>>>);
+
+impl Write for Buffer {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(data);
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// An animated image being encoded, one frame at a time.
+pub struct Encoder {
+    bounds: (usize, usize),
+    fps: u16,
+    kind: Kind,
+}
+
+enum Kind {
+    Gif(gif::Encoder<Buffer>, Buffer),
+    Apng {
+        png: Vec<u8>,
+        frames: usize,
+        written: usize,
+        sequence: u32,
+    },
+}
+
+impl Encoder {
+    /// Start an animated GIF whose frames are given by `bounds`, played
+    /// `fps` frames a second.
+    pub fn gif(bounds: (usize, usize), fps: u16) -> Result<Encoder> {
+        let side = |pixels: usize| {
+            u16::try_from(pixels)
+                .map_err(|_| Error::Viewport(format!("GIF images are at most {0}x{0}", u16::MAX)))
+        };
+        let (width, height) = (side(bounds.0)?, side(bounds.1)?);
+        let grays: Vec<u8> = (0..=255).flat_map(|level| [level; 3]).collect();
+        let buffer = Buffer::default();
+        let mut gif =
+            gif::Encoder::new(buffer.clone(), width, height, &grays).map_err(Error::Encode)?;
+        gif.set(Repeat::Infinite).map_err(Error::Encode)?;
+        Ok(Encoder {
+            bounds,
+            fps: fps.max(1),
+            kind: Kind::Gif(gif, buffer),
+        })
+    }
+
+    /// Start an animated PNG of `frames` frames whose dimensions are given
+    /// by `bounds`, played `fps` frames a second.
+    pub fn apng(bounds: (usize, usize), frames: usize, fps: u16) -> Encoder {
+        Encoder {
+            bounds,
+            fps: fps.max(1),
+            kind: Kind::Apng {
+                png: Vec::new(),
+                frames,
+                written: 0,
+                sequence: 0,
+            },
+        }
+    }
+
+    /// Add the frame `pixels`, grayscale, one byte a pixel, or RGB, three
+    /// bytes a pixel. All the frames of an APNG image must be the same kind.
+    pub fn add_frame(&mut self, pixels: &[u8]) -> Result<()> {
+        let bounds = self.bounds;
+        let rgb = pixels.len() == 3 * bounds.0 * bounds.1 && !pixels.is_empty();
+        match &mut self.kind {
+            Kind::Gif(gif, _) => {
+                let (width, height) = (bounds.0 as u16, bounds.1 as u16);
+                let mut frame = if rgb {
+                    Frame::from_rgb(width, height, pixels)
+                } else {
+                    // the global palette is every gray level, in order
+                    Frame {
+                        width,
+                        height,
+                        buffer: Cow::Borrowed(pixels),
+                        ..Frame::default()
+                    }
+                };
+                frame.delay = (100.0 / self.fps as f64).round().max(1.0) as u16;
+                gif.write_frame(&frame).map_err(Error::Encode)
+            }
+            Kind::Apng {
+                png,
+                frames,
+                written,
+                sequence,
+            } => {
+                assert!(
+                    *written < *frames,
+                    "more frames than the APNG was started with"
+                );
+                let color = if rgb {
+                    ColorType::RGB(8)
+                } else {
+                    ColorType::Gray(8)
+                };
+                let mut encoded = Vec::new();
+                PNGEncoder::new(&mut encoded)
+                    .encode(pixels, bounds.0 as u32, bounds.1 as u32, color)
+                    .map_err(Error::Encode)?;
+                let chunks = share::png_chunks(&encoded).expect("the encoder writes PNG images");
+
+                if *written == 0 {
+                    png.extend_from_slice(share::PNG_SIGNATURE);
+                    for (_, data) in chunks.iter().filter(|(kind, _)| *kind == b"IHDR") {
+                        png.extend(share::png_chunk(b"IHDR", data));
+                    }
+                    let mut control = Vec::new();
+                    control.extend_from_slice(&(*frames as u32).to_be_bytes());
+                    // loop forever
+                    control.extend_from_slice(&0u32.to_be_bytes());
+                    png.extend(share::png_chunk(b"acTL", &control));
+                }
+
+                let mut control = Vec::new();
+                control.extend_from_slice(&sequence.to_be_bytes());
+                control.extend_from_slice(&(bounds.0 as u32).to_be_bytes());
+                control.extend_from_slice(&(bounds.1 as u32).to_be_bytes());
+                // at the upper left corner, shown for 1/fps seconds, replacing
+                // the frame before
+                control.extend_from_slice(&[0; 8]);
+                control.extend_from_slice(&1u16.to_be_bytes());
+                control.extend_from_slice(&self.fps.to_be_bytes());
+                control.extend_from_slice(&[0, 0]);
+                png.extend(share::png_chunk(b"fcTL", &control));
+                *sequence += 1;
+
+                for (_, data) in chunks.iter().filter(|(kind, _)| *kind == b"IDAT") {
+                    if *written == 0 {
+                        png.extend(share::png_chunk(b"IDAT", data));
+                    } else {
+                        let mut frame_data = sequence.to_be_bytes().to_vec();
+                        frame_data.extend_from_slice(data);
+                        png.extend(share::png_chunk(b"fdAT", &frame_data));
+                        *sequence += 1;
+                    }
+                }
+                *written += 1;
+                Ok(())
+            }
+        }
+    }
+
+    /// Finish the image and return it.
+    pub fn finish(self) -> Result<Vec<u8>> {
+        match self.kind {
+            Kind::Gif(gif, buffer) => {
+                // the encoder writes the trailer when it is dropped
+                drop(gif);
+                Ok(buffer.0.take())
+            }
+            Kind::Apng {
+                mut png,
+                frames,
+                written,
+                ..
+            } => {
+                assert_eq!(
+                    written, frames,
+                    "fewer frames than the APNG was started with"
+                );
+                png.extend(share::png_chunk(b"IEND", &[]));
+                Ok(png)
+            }
+        }
+    }
+}
+
+#[test]
+fn test_gif() {
+    let bounds = (4, 3);
+    let mut gif = Encoder::gif(bounds, 10).unwrap();
+    gif.add_frame(&[0; 12]).unwrap();
+    gif.add_frame(&[200; 36]).unwrap();
+    let data = gif.finish().unwrap();
+    assert!(data.starts_with(b"GIF89a"));
+    assert_eq!(data.last(), Some(&0x3b));
+    assert!(data.windows(11).any(|w| w == b"NETSCAPE2.0"));
+    assert!(Encoder::gif((70_000, 1), 10).is_err());
+}
+
+#[test]
+fn test_apng() {
+    let bounds = (4, 3);
+    let mut apng = Encoder::apng(bounds, 3, 25);
+    for level in [0, 100, 255] {
+        apng.add_frame(&[level; 12]).unwrap();
+    }
+    let data = apng.finish().unwrap();
+    let chunks = share::png_chunks(&data).unwrap();
+    let kinds: Vec<&[u8]> = chunks.iter().map(|(kind, _)| *kind).collect();
+    assert_eq!(kinds[..3], [b"IHDR", b"acTL", b"fcTL"]);
+    assert_eq!(kinds.last(), Some(&&b"IEND"[..]));
+    assert_eq!(kinds.iter().filter(|kind| **kind == b"fcTL").count(), 3);
+    assert_eq!(kinds.iter().filter(|kind| **kind == b"fdAT").count(), 2);
+    assert_eq!(chunks[1].1, [0, 0, 0, 3, 0, 0, 0, 0]);
+
+    // sequence numbers count up across fcTL and fdAT chunks
+    let sequence: Vec<u32> = chunks
+        .iter()
+        .filter(|(kind, _)| *kind == b"fcTL" || *kind == b"fdAT")
+        .map(|(_, data)| u32::from_be_bytes(data[..4].try_into().unwrap()))
+        .collect();
+    assert_eq!(sequence, (0..sequence.len() as u32).collect::<Vec<_>>());
+
+    // viewers that don't know APNG show the first frame
+    let first = image::load_from_memory(&data).unwrap().to_luma();
+    assert_eq!(first.into_raw(), [0; 12]);
+}
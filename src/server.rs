@@ -0,0 +1,741 @@
+//! Serving renders over HTTP, for web viewers.
+//!
+//! The server speaks just enough HTTP/1.1 to answer `GET` requests, one per
+//! connection, on a fixed pool of worker threads. It answers:
+//!
+//! - `/render?cx=&cy=&zoom=&w=&h=&palette=&format=` with a PNG image of the
+//!   view centered on `cx,cy`, as `--center` and `--zoom` would render it,
+//!   colored with a built-in palette if one is named;
+//! - `/tiles/{z}/{x}/{y}.png` with a 256x256 tile in the XYZ scheme used by
+//!   web map libraries, the single tile at zoom 0 covering the whole set;
+//! - `/health` with `ok`, for load balancers and service managers.
+//!
+//! Tiles never change, so the most recently used ones are kept in memory,
+//! and renders of both kinds in the disk cache if there is one, see `cache`.
+
+use std::{
+    collections::HashMap,
+    io::{BufRead, BufReader, Read, Write},
+    net::{TcpListener, TcpStream},
+    path::PathBuf,
+    sync::Mutex,
+    time::Duration,
+};
+
+use num::Complex;
+
+#[cfg(all(feature = "sandbox", target_os = "linux"))]
+use crate::sandbox;
+use crate::{
+    cache::{self, DiskCache},
+    encode::{Encoding, Renderer},
+    error,
+    limit::MaxIters,
+    output::OutputSink,
+    palette,
+    render::{self, Strategy},
+    units::Zoom,
+    Error, Result,
+};
+
+/// The width and height of a tile, in pixels.
+pub const TILE_SIZE: usize = 256;
+
+/// The deepest tile zoom served; below it neighbouring pixels are too close
+/// together for `f64` to tell apart.
+const MAX_TILE_ZOOM: u32 = 40;
+
+/// The largest width or height `/render` will produce.
+const MAX_RENDER_SIDE: usize = 4096;
+
+/// How long a connection may take to send its request.
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long the server waits for a connection before checking whether it
+/// should stop.
+const ACCEPT_POLL: Duration = Duration::from_millis(50);
+
+/// How the server is set up.
+pub struct Config {
+    /// How many requests are handled at the same time.
+    pub workers: usize,
+    /// How many tiles the cache holds.
+    pub cache_tiles: usize,
+    pub strategy: Strategy,
+    /// The iteration limit of renders, or `None` for the default one.
+    pub max_iters: Option<MaxIters>,
+    /// The built-in palette renders and tiles are colored with, unless a
+    /// render asks for another, or `None` for gray levels.
+    pub palette: Option<&'static str>,
+    /// How long a render may take before the request is answered with a
+    /// 503 instead.
+    pub timeout: Option<Duration>,
+    /// The directory of the disk cache, if renders are kept in one.
+    pub disk_cache: Option<PathBuf>,
+    /// How much space the disk cache takes at most, in bytes.
+    pub disk_cache_bytes: u64,
+    /// This program, if renders run in a restricted child process running
+    /// it, see `sandbox`.
+    pub sandbox: Option<PathBuf>,
+}
+
+/// A least recently used cache of encoded tiles, keyed by `(z, x, y)`.
+pub struct TileCache {
+    capacity: usize,
+    tiles: HashMap<(u32, u64, u64), (Vec<u8>, u64)>,
+    /// Bumped on every access, and stored with the tile accessed.
+    clock: u64,
+}
+
+impl TileCache {
+    pub fn new(capacity: usize) -> TileCache {
+        TileCache {
+            capacity,
+            tiles: HashMap::new(),
+            clock: 0,
+        }
+    }
+
+    pub fn get(&mut self, key: (u32, u64, u64)) -> Option<Vec<u8>> {
+        self.clock += 1;
+        let clock = self.clock;
+        self.tiles.get_mut(&key).map(|(png, used)| {
+            *used = clock;
+            png.clone()
+        })
+    }
+
+    /// Add a tile, evicting the least recently used one if the cache is full.
+    pub fn insert(&mut self, key: (u32, u64, u64), png: Vec<u8>) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.tiles.len() >= self.capacity && !self.tiles.contains_key(&key) {
+            let oldest = self
+                .tiles
+                .iter()
+                .min_by_key(|(_, (_, used))| *used)
+                .map(|(key, _)| *key);
+            if let Some(oldest) = oldest {
+                self.tiles.remove(&oldest);
+            }
+        }
+        self.clock += 1;
+        self.tiles.insert(key, (png, self.clock));
+    }
+
+    pub fn len(&self) -> usize {
+        self.tiles.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tiles.is_empty()
+    }
+}
+
+#[test]
+fn test_tile_cache() {
+    let mut cache = TileCache::new(2);
+    cache.insert((0, 0, 0), vec![0]);
+    cache.insert((1, 0, 0), vec![1]);
+    assert_eq!(cache.get((0, 0, 0)), Some(vec![0]));
+    // (1, 0, 0) is now the least recently used
+    cache.insert((1, 1, 0), vec![2]);
+    assert_eq!(cache.len(), 2);
+    assert_eq!(cache.get((1, 0, 0)), None);
+    assert_eq!(cache.get((0, 0, 0)), Some(vec![0]));
+    assert_eq!(cache.get((1, 1, 0)), Some(vec![2]));
+
+    let mut cache = TileCache::new(0);
+    cache.insert((0, 0, 0), vec![0]);
+    assert!(cache.is_empty());
+}
+
+/// An HTTP response.
+#[derive(Debug, PartialEq)]
+pub struct Response {
+    pub status: u16,
+    pub content_type: &'static str,
+    pub body: Vec<u8>,
+}
+
+/// The body of a response, which encoded images are written to like to any
+/// other output.
+impl OutputSink for Response {
+    fn write_all(&mut self, contents: &[u8]) -> Result<()> {
+        self.body.extend_from_slice(contents);
+        Ok(())
+    }
+}
+
+impl Response {
+    fn png(body: Vec<u8>) -> Response {
+        Response::image(Encoding::Png, body)
+    }
+
+    fn image(format: Encoding, body: Vec<u8>) -> Response {
+        Response {
+            status: 200,
+            content_type: format.content_type(),
+            body,
+        }
+    }
+
+    pub(crate) fn text(status: u16, body: &str) -> Response {
+        Response {
+            status,
+            content_type: "text/plain; charset=utf-8",
+            body: format!("{}\n", body).into_bytes(),
+        }
+    }
+
+    fn reason(&self) -> &'static str {
+        match self.status {
+            200 => "OK",
+            204 => "No Content",
+            400 => "Bad Request",
+            404 => "Not Found",
+            405 => "Method Not Allowed",
+            410 => "Gone",
+            413 => "Payload Too Large",
+            503 => "Service Unavailable",
+            _ => "Internal Server Error",
+        }
+    }
+}
+
+/// Answers requests; shared by the worker threads.
+pub struct Server {
+    strategy: Strategy,
+    max_iters: Option<MaxIters>,
+    palette: Option<&'static str>,
+    timeout: Option<Duration>,
+    cache: Mutex<TileCache>,
+    disk_cache: Option<DiskCache>,
+    sandbox: Option<PathBuf>,
+}
+
+impl Server {
+    pub fn new(config: &Config) -> Server {
+        Server {
+            strategy: config.strategy,
+            max_iters: config.max_iters,
+            palette: config.palette,
+            timeout: config.timeout,
+            cache: Mutex::new(TileCache::new(config.cache_tiles)),
+            disk_cache: config
+                .disk_cache
+                .as_ref()
+                .map(|dir| DiskCache::new(dir, config.disk_cache_bytes)),
+            sandbox: config.sandbox.clone(),
+        }
+    }
+
+    /// Answer a `GET` request for `target`, the path and query of the URL.
+    pub fn handle(&self, target: &str) -> Response {
+        let (path, query) = target.split_once('?').unwrap_or((target, ""));
+        let result = if path == "/health" {
+            Ok(Response::text(200, "ok"))
+        } else if path == "/render" {
+            self.render(query)
+        } else if let Some(tile) = path.strip_prefix("/tiles/") {
+            match parse_tile(tile) {
+                Some(key) => self.tile(key),
+                None => return Response::text(404, "no such tile"),
+            }
+        } else {
+            return Response::text(404, "not found");
+        };
+
+        result.unwrap_or_else(|e| match e {
+            Error::Parse { .. } | Error::Viewport(_) | Error::Usage(_) => {
+                Response::text(400, &e.to_string())
+            }
+            Error::Interrupted(_) => Response::text(503, &e.to_string()),
+            _ => Response::text(500, &e.to_string()),
+        })
+    }
+
+    fn render(&self, query: &str) -> Result<Response> {
+        let mut center = Complex { re: -0.5, im: 0.0 };
+        let mut zoom: f64 = 1.0;
+        let mut bounds = (800, 600);
+        let mut format = Encoding::Png;
+        let mut palette = self.palette;
+        // the query comes from anyone, so every problem with it is reported
+        let mut problems = Vec::new();
+        let mut seen = Vec::new();
+        for parameter in query.split('&').filter(|p| !p.is_empty()) {
+            let (name, value) = parameter.split_once('=').unwrap_or((parameter, ""));
+            if seen.contains(&name) {
+                problems.push(format!("{} is given more than once", name));
+                continue;
+            }
+            seen.push(name);
+
+            let finite = || match value.parse::<f64>() {
+                Ok(number) if number.is_finite() => Ok(number),
+                _ => Err(format!("{} {:?} is not a finite number", name, value)),
+            };
+            let side = || match value.parse::<usize>() {
+                Ok(side) if (1..=MAX_RENDER_SIDE).contains(&side) => Ok(side),
+                _ => Err(format!(
+                    "{} {:?} is not a number of pixels from 1 to {}",
+                    name, value, MAX_RENDER_SIDE
+                )),
+            };
+            let parsed = match name {
+                "cx" => finite().map(|re| center.re = re),
+                "cy" => finite().map(|im| center.im = im),
+                "zoom" => match finite() {
+                    Ok(number) if number > 0.0 => {
+                        zoom = number;
+                        Ok(())
+                    }
+                    _ => Err(format!("zoom {:?} is not a positive number", value)),
+                },
+                "w" => side().map(|width| bounds.0 = width),
+                "h" => side().map(|height| bounds.1 = height),
+                "format" => value.parse().map(|f| format = f),
+                "palette" => match palette::preset_name(value) {
+                    Some(name) => {
+                        palette = Some(name);
+                        Ok(())
+                    }
+                    None => {
+                        let names: Vec<&str> =
+                            palette::PRESETS.iter().map(|(name, _, _)| *name).collect();
+                        Err(format!(
+                            "palette {:?} is not a built-in palette: {}",
+                            value,
+                            names.join(", ")
+                        ))
+                    }
+                },
+                _ => Err(format!("unknown parameter {:?}", name)),
+            };
+            if let Err(problem) = parsed {
+                problems.push(problem);
+            }
+        }
+        if !problems.is_empty() {
+            return Err(Error::Usage(problems.join("; ")));
+        }
+
+        let (upper_left, lower_right) = render::corners_from_center(center, Zoom(zoom), bounds);
+        error::validate_viewport(bounds, upper_left, lower_right)?;
+        let mut response = Response::image(format, Vec::new());
+        let view = (bounds, upper_left, lower_right);
+        self.encode(&mut response, view, palette, format)?;
+        Ok(response)
+    }
+
+    fn tile(&self, key: (u32, u64, u64)) -> Result<Response> {
+        if let Some(png) = self.cache.lock().unwrap().get(key) {
+            return Ok(Response::png(png));
+        }
+        let (upper_left, lower_right) = tile_corners(key);
+        let mut png = Vec::new();
+        let bounds = (TILE_SIZE, TILE_SIZE);
+        let view = (bounds, upper_left, lower_right);
+        self.encode(&mut png, view, self.palette, Encoding::Png)?;
+        self.cache.lock().unwrap().insert(key, png.clone());
+        Ok(Response::png(png))
+    }
+
+    /// Render and encode a view into `sink`, colored with `palette`, unless
+    /// the disk cache has it already. The worker pool already keeps the cores busy, so each
+    /// render runs on the calling thread, or in a child process it waits
+    /// for when renders are sandboxed.
+    fn encode(
+        &self,
+        sink: &mut dyn OutputSink,
+        (bounds, upper_left, lower_right): ((usize, usize), Complex<f64>, Complex<f64>),
+        palette: Option<&'static str>,
+        format: Encoding,
+    ) -> Result<()> {
+        let mut renderer = Renderer::new(bounds, upper_left, lower_right);
+        renderer.strategy = self.strategy;
+        if let Some(max_iters) = self.max_iters {
+            renderer.limit = max_iters.resolve(bounds, upper_left, lower_right);
+        }
+        renderer.timeout = self.timeout;
+        renderer.palette = palette;
+        let key = cache::Key {
+            bounds,
+            upper_left,
+            lower_right,
+            fractal: "mandelbrot",
+            limit: renderer.limit,
+            strategy: renderer.strategy,
+            palette: palette.unwrap_or("gray"),
+            format: format.content_type(),
+        };
+        if let Some(encoded) = self.disk_cache.as_ref().and_then(|cache| cache.get(&key)) {
+            return sink.write_all(&encoded);
+        }
+        let encoded = match &self.sandbox {
+            #[cfg(all(feature = "sandbox", target_os = "linux"))]
+            Some(program) => sandbox::render(program, &renderer, format)?,
+            #[cfg(not(all(feature = "sandbox", target_os = "linux")))]
+            Some(_) => {
+                return Err(Error::Sandbox(
+                    "needs a Linux build with the sandbox feature".to_string(),
+                ))
+            }
+            None => renderer.render_to_encoded_bytes(format)?,
+        };
+        if let Some(disk_cache) = &self.disk_cache {
+            if let Err(e) = disk_cache.put(&key, &encoded) {
+                eprintln!("error caching a render: {}", e);
+            }
+        }
+        sink.write_all(&encoded)
+    }
+}
+
+#[test]
+fn test_handle() {
+    let server = Server::new(&Config {
+        workers: 1,
+        cache_tiles: 4,
+        strategy: Strategy::Naive,
+        max_iters: None,
+        palette: None,
+        timeout: None,
+        disk_cache: None,
+        disk_cache_bytes: 0,
+        sandbox: None,
+    });
+    assert_eq!(server.handle("/health"), Response::text(200, "ok"));
+    assert_eq!(server.handle("/nothing").status, 404);
+    assert_eq!(server.handle("/tiles/1/2/0.png").status, 404);
+
+    let response = server.handle("/render?cx=-0.75&cy=0.1&zoom=4&w=40&h=30&palette=gray");
+    assert_eq!(response.status, 200);
+    let image = image::load_from_memory(&response.body).unwrap();
+    assert_eq!(image.raw_pixels().len(), 40 * 30);
+    let response = server.handle("/render?w=40&h=30&palette=fire");
+    assert_eq!(response.status, 200);
+    let image = image::load_from_memory(&response.body).unwrap();
+    assert_eq!(image.raw_pixels().len(), 3 * 40 * 30);
+    let response = server.handle("/render?w=40&h=30&format=gif");
+    assert_eq!(response.content_type, "image/gif");
+    assert!(response.body.starts_with(b"GIF89a"));
+    let response = server.handle("/render?w=40&h=30&format=pgm");
+    assert!(response.body.starts_with(b"P5\n40 30\n"));
+
+    assert_eq!(server.handle("/render?format=jpeg").status, 400);
+    assert_eq!(server.handle("/render?zoom=0").status, 400);
+    assert_eq!(server.handle("/render?w=x").status, 400);
+    assert_eq!(server.handle("/render?w=0").status, 400);
+    assert_eq!(server.handle("/render?palette=plaid").status, 400);
+    assert_eq!(server.handle("/render?w=100000").status, 400);
+    assert_eq!(server.handle("/render?w=-1").status, 400);
+    assert_eq!(server.handle("/render?cx=NaN").status, 400);
+    assert_eq!(server.handle("/render?cy=inf").status, 400);
+    assert_eq!(server.handle("/render?zoom=1e999").status, 400);
+    assert_eq!(server.handle("/render?cx=0&cx=1").status, 400);
+    assert_eq!(server.handle("/render?what=1").status, 400);
+    let response = server.handle("/render?w=0&h=x&zoom=-2&cx=nan");
+    let message = String::from_utf8(response.body).unwrap();
+    assert_eq!(message.split("; ").count(), 4, "{}", message);
+
+    // renders that run out of time aren't cached, or answered with an image
+    let hurried = Server::new(&Config {
+        workers: 1,
+        cache_tiles: 4,
+        strategy: Strategy::Naive,
+        max_iters: None,
+        palette: None,
+        timeout: Some(Duration::ZERO),
+        disk_cache: None,
+        disk_cache_bytes: 0,
+        sandbox: None,
+    });
+    assert_eq!(hurried.handle("/render?w=40&h=30").status, 503);
+    assert_eq!(hurried.handle("/tiles/1/0/1.png").status, 503);
+    assert_eq!(hurried.cache.lock().unwrap().len(), 0);
+
+    let tile = server.handle("/tiles/1/0/1.png");
+    assert_eq!(tile.status, 200);
+    assert_eq!(server.cache.lock().unwrap().len(), 1);
+    assert_eq!(server.handle("/tiles/1/0/1.png"), tile);
+
+    // the palette and iterations of the server apply to tiles too
+    let colored = Server::new(&Config {
+        workers: 1,
+        cache_tiles: 4,
+        strategy: Strategy::Naive,
+        max_iters: Some(MaxIters::Fixed(20)),
+        palette: Some("magma"),
+        timeout: None,
+        disk_cache: None,
+        disk_cache_bytes: 0,
+        sandbox: None,
+    });
+    let response = colored.handle("/tiles/1/0/1.png");
+    let image = image::load_from_memory(&response.body).unwrap();
+    assert_eq!(image.raw_pixels().len(), 3 * TILE_SIZE * TILE_SIZE);
+    let response = colored.handle("/render?w=40&h=30&palette=gray&format=pgm");
+    assert_ne!(response, server.handle("/render?w=40&h=30&format=pgm"));
+
+    // servers share their renders through the disk cache
+    let dir = std::env::temp_dir().join(format!("mandelbrot-server-{}", std::process::id()));
+    let config = Config {
+        workers: 1,
+        cache_tiles: 0,
+        strategy: Strategy::Naive,
+        max_iters: None,
+        palette: None,
+        timeout: None,
+        disk_cache: Some(dir.clone()),
+        disk_cache_bytes: cache::DEFAULT_MAX_BYTES,
+        sandbox: None,
+    };
+    assert_eq!(Server::new(&config).handle("/tiles/1/0/1.png"), tile);
+    let render = Server::new(&config).handle("/render?w=40&h=30&format=pgm");
+    let disk_cache = DiskCache::new(&dir, 0);
+    assert_eq!(disk_cache.usage().unwrap().tiles, 2);
+    // a server that would render something else if it rendered at all
+    let hurried = Server::new(&Config {
+        timeout: Some(Duration::ZERO),
+        ..config
+    });
+    assert_eq!(hurried.handle("/tiles/1/0/1.png"), tile);
+    assert_eq!(hurried.handle("/render?w=40&h=30&format=pgm"), render);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+/// Parse the `{z}/{x}/{y}.png` part of a tile path, checking that the tile
+/// exists at that zoom.
+fn parse_tile(path: &str) -> Option<(u32, u64, u64)> {
+    let path = path.strip_suffix(".png")?;
+    let mut parts = path.split('/');
+    let z: u32 = parts.next()?.parse().ok()?;
+    let x: u64 = parts.next()?.parse().ok()?;
+    let y: u64 = parts.next()?.parse().ok()?;
+    let tiles = 1u64.checked_shl(z)?;
+    if parts.next().is_some() || z > MAX_TILE_ZOOM || x >= tiles || y >= tiles {
+        return None;
+    }
+    Some((z, x, y))
+}
+
+#[test]
+fn test_parse_tile() {
+    assert_eq!(parse_tile("0/0/0.png"), Some((0, 0, 0)));
+    assert_eq!(parse_tile("3/7/5.png"), Some((3, 7, 5)));
+    assert_eq!(parse_tile("3/8/5.png"), None);
+    assert_eq!(parse_tile("3/7/5"), None);
+    assert_eq!(parse_tile("3/7/5/1.png"), None);
+    assert_eq!(parse_tile("99/0/0.png"), None);
+}
+
+/// The corners of a tile. The tile at zoom 0 is the square view of zoom 1
+/// centered on -0.5, and each zoom level splits every tile in four.
+pub fn tile_corners((z, x, y): (u32, u64, u64)) -> (Complex<f64>, Complex<f64>) {
+    let (world_upper_left, _) =
+        render::corners_from_center(Complex { re: -0.5, im: 0.0 }, Zoom::NONE, (1, 1));
+    let side = render::UNZOOMED_WIDTH / (1u64 << z) as f64;
+    let upper_left = world_upper_left
+        + Complex {
+            re: x as f64 * side,
+            im: -(y as f64) * side,
+        };
+    let lower_right = upper_left
+        + Complex {
+            re: side,
+            im: -side,
+        };
+    (upper_left, lower_right)
+}
+
+#[test]
+fn test_tile_corners() {
+    assert_eq!(
+        tile_corners((0, 0, 0)),
+        (Complex { re: -2.5, im: 2.0 }, Complex { re: 1.5, im: -2.0 })
+    );
+    assert_eq!(
+        tile_corners((1, 1, 0)),
+        (Complex { re: -0.5, im: 2.0 }, Complex { re: 1.5, im: 0.0 })
+    );
+}
+
+/// Read an HTTP request from `stream`, and return its method, its target,
+/// and its body, of at most `max_body` bytes; a longer one is cut short.
+pub(crate) fn read_request(
+    stream: &TcpStream,
+    max_body: u64,
+) -> std::io::Result<(String, String, Vec<u8>)> {
+    let mut reader = BufReader::new(stream).take(16 * 1024);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    let mut length = 0;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 || header.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("content-length") {
+                length = value.trim().parse().unwrap_or(0);
+            }
+        }
+    }
+    let mut body = Vec::new();
+    if length > 0 {
+        reader.set_limit(length.min(max_body));
+        reader.read_to_end(&mut body)?;
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or_default().to_string();
+    let target = parts.next().unwrap_or_default().to_string();
+    Ok((method, target, body))
+}
+
+/// Write `response` to `stream`, with the extra header lines `headers`, and
+/// without its body if `head_only`.
+pub(crate) fn write_response(
+    stream: &mut TcpStream,
+    response: &Response,
+    headers: &str,
+    head_only: bool,
+) -> std::io::Result<()> {
+    let head = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n{}\r\n",
+        response.status,
+        response.reason(),
+        response.content_type,
+        response.body.len(),
+        headers
+    );
+    stream.write_all(head.as_bytes())?;
+    if !head_only {
+        stream.write_all(&response.body)?;
+    }
+    stream.flush()
+}
+
+/// Serve one connection: read its request, answer it, and close it.
+fn handle_connection(server: &Server, mut stream: TcpStream) -> std::io::Result<()> {
+    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+    let (method, target, _) = read_request(&stream, 0)?;
+    let response = match method.as_str() {
+        "GET" | "HEAD" => server.handle(&target),
+        _ => Response::text(405, "only GET is supported"),
+    };
+
+    let headers = if response.status == 200 && target.starts_with("/tiles/") {
+        "Cache-Control: public, max-age=31536000, immutable\r\n"
+    } else {
+        ""
+    };
+    write_response(&mut stream, &response, headers, method == "HEAD")
+}
+
+/// Answer the connections made to `listener` on `config.workers` threads,
+/// until `stop` returns true: then no more are accepted, and those accepted
+/// already are answered before returning. Errors on single connections are
+/// reported on stderr; the server fails if its workers are all gone.
+pub fn serve(listener: TcpListener, config: &Config, stop: impl Fn() -> bool) -> Result<()> {
+    let server = Server::new(config);
+    let address = listener
+        .local_addr()
+        .map_or_else(|_| "the server".to_string(), |address| address.to_string());
+    // accepting without blocking, to check `stop` in between
+    listener
+        .set_nonblocking(true)
+        .map_err(Error::io(&address))?;
+    let (sender, receiver) = crossbeam::channel::bounded::<TcpStream>(config.workers * 4);
+
+    crossbeam::scope(|spawner| {
+        for _ in 0..config.workers {
+            let receiver = receiver.clone();
+            let server = &server;
+            spawner.spawn(move |_| {
+                for stream in receiver {
+                    if let Err(e) = handle_connection(server, stream) {
+                        eprintln!("error serving a connection: {}", e);
+                    }
+                }
+            });
+        }
+        drop(receiver);
+
+        while !stop() {
+            match listener.accept() {
+                Ok((stream, _)) => {
+                    // some platforms pass not blocking on to the connection
+                    if let Err(e) = stream.set_nonblocking(false) {
+                        eprintln!("error accepting a connection: {}", e);
+                        continue;
+                    }
+                    sender.send(stream).map_err(|_| {
+                        Error::io(&address)(std::io::Error::other("every worker has stopped"))
+                    })?;
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+                    std::thread::sleep(ACCEPT_POLL)
+                }
+                Err(e) => eprintln!("error accepting a connection: {}", e),
+            }
+        }
+        // the workers answer what is queued, then see the channel closed
+        drop(sender);
+        Ok(())
+    })
+    .map_err(|_| Error::io(&address)(std::io::Error::other("a worker panicked")))?
+}
+
+#[test]
+fn test_serve() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    let stop = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+    let server = std::thread::spawn({
+        let stop = stop.clone();
+        move || {
+            serve(
+                listener,
+                &Config {
+                    workers: 2,
+                    cache_tiles: 16,
+                    strategy: Strategy::Naive,
+                    max_iters: None,
+                    palette: None,
+                    timeout: None,
+                    disk_cache: None,
+                    disk_cache_bytes: 0,
+                    sandbox: None,
+                },
+                || stop.load(std::sync::atomic::Ordering::Relaxed),
+            )
+        }
+    });
+
+    let mut stream = TcpStream::connect(address).unwrap();
+    stream
+        .write_all(b"GET /health HTTP/1.1\r\nHost: localhost\r\n\r\n")
+        .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(response.ends_with("\r\n\r\nok\n"));
+
+    // a request in progress when told to stop is still answered
+    let mut stream = TcpStream::connect(address).unwrap();
+    stream.write_all(b"GET /health HTTP/1.1\r\n").unwrap();
+    std::thread::sleep(ACCEPT_POLL * 4);
+    stop.store(true, std::sync::atomic::Ordering::Relaxed);
+    std::thread::sleep(ACCEPT_POLL * 2);
+    stream.write_all(b"Host: localhost\r\n\r\n").unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+    server.join().unwrap().unwrap();
+    // and then no more connections are
+    assert!(TcpStream::connect(address).is_err());
+}
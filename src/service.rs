@@ -0,0 +1,143 @@
+//! Behaving like a well-mannered systemd service.
+//!
+//! `notify` implements the `sd_notify` protocol, so that a `Type=notify` unit
+//! knows when the program is ready or stopping, and `log` sends structured
+//! entries to the journal, falling back to stderr with syslog priority
+//! prefixes (which journald understands too) when there is no journal. Both
+//! speak the plain datagram protocols, so libsystemd isn't needed.
+
+use std::io;
+
+/// How important a log entry is, from the syslog priorities journald uses.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Priority {
+    Error = 3,
+    Warning = 4,
+    Info = 6,
+}
+
+/// The socket journald listens on for native protocol entries.
+#[cfg(unix)]
+const JOURNAL_SOCKET: &str = "/run/systemd/journal/socket";
+
+/// Send `state` (e.g. `READY=1`, or several assignments separated by
+/// newlines) to the service manager.
+///
+/// Returns `Ok(false)` when not running under a service manager that asked
+/// for notifications, i.e. when `NOTIFY_SOCKET` isn't set.
+#[cfg(unix)]
+pub fn notify(state: &str) -> io::Result<bool> {
+    use std::os::unix::net::UnixDatagram;
+
+    let path = match std::env::var_os("NOTIFY_SOCKET") {
+        Some(path) => path,
+        None => return Ok(false),
+    };
+    let socket = UnixDatagram::unbound()?;
+    let path = path.to_string_lossy();
+    match path.strip_prefix('@') {
+        #[cfg(target_os = "linux")]
+        Some(name) => {
+            use std::os::linux::net::SocketAddrExt;
+
+            let address = std::os::unix::net::SocketAddr::from_abstract_name(name)?;
+            socket.send_to_addr(state.as_bytes(), &address)?;
+        }
+        _ => {
+            socket.send_to(state.as_bytes(), &*path)?;
+        }
+    }
+    Ok(true)
+}
+
+/// There is no service manager to notify outside Unix.
+#[cfg(not(unix))]
+pub fn notify(_state: &str) -> io::Result<bool> {
+    Ok(false)
+}
+
+/// Build a journald native protocol entry out of `message`, `priority` and
+/// additional `fields`, whose names must be upper case.
+///
+/// Values containing a newline use the binary form of the protocol, with
+/// their length in front.
+pub fn journal_entry(priority: Priority, message: &str, fields: &[(&str, String)]) -> Vec<u8> {
+    let mut entry = Vec::new();
+    let priority = (priority as u8).to_string();
+    let all = [
+        ("MESSAGE", message),
+        ("PRIORITY", priority.as_str()),
+        ("SYSLOG_IDENTIFIER", "mandelbrot"),
+    ];
+    let extra = fields.iter().map(|(name, value)| (*name, value.as_str()));
+
+    for (name, value) in all.into_iter().chain(extra) {
+        entry.extend_from_slice(name.as_bytes());
+        if value.contains('\n') {
+            entry.push(b'\n');
+            entry.extend_from_slice(&(value.len() as u64).to_le_bytes());
+        } else {
+            entry.push(b'=');
+        }
+        entry.extend_from_slice(value.as_bytes());
+        entry.push(b'\n');
+    }
+    entry
+}
+
+#[test]
+fn test_journal_entry() {
+    let entry = journal_entry(Priority::Info, "done", &[("ROWS", "750".to_string())]);
+    assert_eq!(
+        entry,
+        b"MESSAGE=done\nPRIORITY=6\nSYSLOG_IDENTIFIER=mandelbrot\nROWS=750\n"
+    );
+
+    let entry = journal_entry(Priority::Error, "a\nb", &[]);
+    assert!(entry.starts_with(b"MESSAGE\n\x03\0\0\0\0\0\0\0a\nb\nPRIORITY=3\n"));
+}
+
+/// Log `message` to the journal with the given `priority` and extra `fields`,
+/// or to stderr if the journal can't be reached.
+pub fn log(priority: Priority, message: &str, fields: &[(&str, String)]) {
+    #[cfg(unix)]
+    {
+        use std::os::unix::net::UnixDatagram;
+
+        let entry = journal_entry(priority, message, fields);
+        let sent = UnixDatagram::unbound().and_then(|s| s.send_to(&entry, JOURNAL_SOCKET));
+        if sent.is_ok() {
+            return;
+        }
+    }
+
+    let details: Vec<String> = fields
+        .iter()
+        .map(|(name, value)| format!("{}={}", name.to_ascii_lowercase(), value))
+        .collect();
+    if details.is_empty() {
+        eprintln!("<{}>{}", priority as u8, message);
+    } else {
+        eprintln!("<{}>{} ({})", priority as u8, message, details.join(", "));
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn test_notify() {
+    use std::os::unix::net::UnixDatagram;
+
+    let path = std::env::temp_dir().join(format!("mandelbrot-notify-{}", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let listener = UnixDatagram::bind(&path).unwrap();
+
+    std::env::set_var("NOTIFY_SOCKET", &path);
+    assert!(notify("READY=1").unwrap());
+    std::env::remove_var("NOTIFY_SOCKET");
+    assert!(!notify("READY=1").unwrap());
+
+    let mut buffer = [0; 16];
+    let length = listener.recv(&mut buffer).unwrap();
+    assert_eq!(&buffer[..length], b"READY=1");
+    std::fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,281 @@
+//! Describing a view in a form that can be shared and read back: as a link,
+//! and as text embedded in the PNG files we write.
+
+use std::fs;
+
+use num::Complex;
+
+use crate::{
+    locator::Locator,
+    parse_complex, parse_pair, schema,
+    units::{ComplexWidth, Zoom},
+    DecimalSeparator, Error, Result,
+};
+
+/// The PNG text keyword under which the share link of an image is stored.
+pub const PNG_KEYWORD: &str = "mandelbrot";
+
+/// The parameters needed to render a view again.
+#[derive(Debug, PartialEq)]
+pub struct View {
+    pub bounds: (usize, usize),
+    pub upper_left: Complex<f64>,
+    pub lower_right: Complex<f64>,
+}
+
+impl View {
+    /// The view in every form other renderers and published locations give
+    /// it in, one `name value` a line: the corners, the center, the zoom
+    /// and the width and height on the complex plane, and the size of a
+    /// pixel.
+    pub fn describe(&self) -> String {
+        let width = ComplexWidth::between(self.upper_left, self.lower_right);
+        let center = (self.upper_left + self.lower_right) / 2.0;
+        let point = |point: Complex<f64>| format!("{},{}", point.re, point.im);
+        [
+            ("pixels", format!("{}x{}", self.bounds.0, self.bounds.1)),
+            ("upper_left", point(self.upper_left)),
+            ("lower_right", point(self.lower_right)),
+            ("center", point(center)),
+            ("zoom", width.zoom().0.to_string()),
+            ("width", width.0.to_string()),
+            (
+                "height",
+                (self.upper_left.im - self.lower_right.im).to_string(),
+            ),
+            ("pixel_size", width.pixel_size(self.bounds.0).0.to_string()),
+        ]
+        .iter()
+        .map(|(name, value)| format!("{} {}\n", name, value))
+        .collect()
+    }
+}
+
+#[test]
+fn test_describe() {
+    let view = View {
+        bounds: (400, 200),
+        upper_left: Complex { re: -1.5, im: 0.5 },
+        lower_right: Complex { re: 0.5, im: -0.5 },
+    };
+    assert_eq!(
+        view.describe(),
+        "pixels 400x200\n\
+         upper_left -1.5,0.5\n\
+         lower_right 0.5,-0.5\n\
+         center -0.5,0\n\
+         zoom 2\n\
+         width 2\n\
+         height 1\n\
+         pixel_size 0.005\n"
+    );
+}
+
+/// Build a link that describes the rendered view, so that it can be shared
+/// and reproduced.
+pub fn share_link(
+    bounds: (usize, usize),
+    upper_left: Complex<f64>,
+    lower_right: Complex<f64>,
+) -> String {
+    format!(
+        "mandelbrot://render?v={}&pixels={}x{}&upper_left={},{}&lower_right={},{}",
+        schema::VERSION,
+        bounds.0,
+        bounds.1,
+        upper_left.re,
+        upper_left.im,
+        lower_right.re,
+        lower_right.im
+    )
+}
+
+#[test]
+fn test_share_link() {
+    assert_eq!(
+        share_link(
+            (1000, 750),
+            Complex { re: -1.2, im: 0.35 },
+            Complex { re: -1.0, im: 0.2 }
+        ),
+        "mandelbrot://render?v=1&pixels=1000x750&upper_left=-1.2,0.35&lower_right=-1,0.2"
+    );
+}
+
+/// Parse a link built by `share_link`, or one giving the view as a compact
+/// location instead of its corners: `at=@-0.75,0.1,z=20`.
+///
+/// Parameters this version doesn't know are ignored, with a warning if the
+/// link says it was built by a later version, `v`.
+///
+/// Returns `None` if `link` isn't one, or if it is missing a parameter.
+pub fn parse_share_link(link: &str) -> Option<View> {
+    let query = link.trim().strip_prefix("mandelbrot://render?")?;
+
+    let (mut bounds, mut upper_left, mut lower_right, mut at) = (None, None, None, None);
+    let (mut version, mut unknown) = (1, Vec::new());
+    for parameter in query.split('&') {
+        // the first `=`: compact locations have more of them
+        let (name, value) = parameter.split_once('=')?;
+        match name {
+            "pixels" => bounds = parse_pair(value, 'x'),
+            "upper_left" => upper_left = parse_complex(value, DecimalSeparator::Point),
+            "lower_right" => lower_right = parse_complex(value, DecimalSeparator::Point),
+            "at" => at = Some(value.parse::<Locator>().ok()?),
+            "v" => version = value.parse().ok()?,
+            _ => unknown.push(name.to_string()),
+        }
+    }
+    if version > schema::VERSION {
+        schema::warn_newer("the link", version, schema::VERSION, &unknown);
+    }
+
+    let bounds = bounds?;
+    let (upper_left, lower_right) = match at {
+        Some(at) => at.corners(bounds, Zoom::NONE).ok()?,
+        None => (upper_left?, lower_right?),
+    };
+    Some(View {
+        bounds,
+        upper_left,
+        lower_right,
+    })
+}
+
+#[test]
+fn test_parse_share_link() {
+    let view = View {
+        bounds: (1000, 750),
+        upper_left: Complex { re: -1.2, im: 0.35 },
+        lower_right: Complex { re: -1.0, im: 0.2 },
+    };
+    let link = share_link(view.bounds, view.upper_left, view.lower_right);
+    assert_eq!(parse_share_link(&link), Some(view));
+    assert_eq!(parse_share_link("mandelbrot://render?pixels=10x10"), None);
+
+    let at = parse_share_link("mandelbrot://render?pixels=200x100&at=@-0.5,0,z=2").unwrap();
+    assert_eq!(at.upper_left, Complex { re: -1.5, im: 0.5 });
+    assert_eq!(at.lower_right, Complex { re: 0.5, im: -0.5 });
+    assert_eq!(
+        parse_share_link("mandelbrot://render?pixels=200x100&at=@-0.5,0,r=30"),
+        None
+    );
+    assert_eq!(parse_share_link("https://example.com"), None);
+
+    // links without a version, or from a later one, are read as best we can
+    let old = "mandelbrot://render?pixels=1000x750&upper_left=-1.2,0.35&lower_right=-1,0.2";
+    assert_eq!(parse_share_link(old), parse_share_link(&link));
+    let newer = format!("{}&v=2&tilt=30", old);
+    assert_eq!(parse_share_link(&newer), parse_share_link(&link));
+    assert_eq!(parse_share_link(&format!("{}&v=x", old)), None);
+}
+
+/// The CRC-32 used by PNG chunks (ISO 3309, as in zlib).
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xffff_ffffu32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+#[test]
+fn test_crc32() {
+    assert_eq!(crc32(b""), 0);
+    assert_eq!(crc32(b"IEND"), 0xae42_6082);
+    assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+}
+
+/// The signature every PNG file starts with.
+pub(crate) const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+/// A PNG chunk of type `kind` holding `data`, with its length and CRC.
+pub(crate) fn png_chunk(kind: &[u8; 4], data: &[u8]) -> Vec<u8> {
+    let mut chunk = Vec::with_capacity(data.len() + 12);
+    chunk.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    chunk.extend_from_slice(kind);
+    chunk.extend_from_slice(data);
+    chunk.extend_from_slice(&crc32(&chunk[4..]).to_be_bytes());
+    chunk
+}
+
+/// The type and the data of the chunks of the encoded PNG image `png`, in
+/// order, or `None` if it isn't a PNG image.
+pub(crate) fn png_chunks(png: &[u8]) -> Option<Vec<(&[u8], &[u8])>> {
+    let mut rest = png.strip_prefix(PNG_SIGNATURE)?;
+    let mut chunks = Vec::new();
+    while rest.len() >= 12 {
+        let length = u32::from_be_bytes(rest[..4].try_into().unwrap()) as usize;
+        chunks.push((&rest[4..8], rest.get(8..8 + length)?));
+        rest = rest.get(12 + length..)?;
+    }
+    Some(chunks)
+}
+
+/// Insert a `tEXt` chunk associating `text` with `keyword` into the encoded
+/// PNG image `png`, right after its header.
+///
+/// `keyword` must be 1 to 79 Latin-1 characters, as PNG requires.
+pub fn add_png_text(png: &mut Vec<u8>, keyword: &str, text: &str) {
+    assert!(png.starts_with(PNG_SIGNATURE));
+
+    let mut data = Vec::with_capacity(keyword.len() + 1 + text.len());
+    data.extend_from_slice(keyword.as_bytes());
+    data.push(0);
+    data.extend_from_slice(text.as_bytes());
+
+    let chunk = png_chunk(b"tEXt", &data);
+
+    // the IHDR chunk always comes first and has 13 bytes of data
+    let after_header = PNG_SIGNATURE.len() + 12 + 13;
+    png.splice(after_header..after_header, chunk);
+}
+
+/// Return the text stored under `keyword` in the encoded PNG image `png`, if
+/// there is any.
+pub fn png_text(png: &[u8], keyword: &str) -> Option<String> {
+    png_chunks(png)?
+        .into_iter()
+        .filter(|(kind, _)| kind == b"tEXt")
+        .find_map(|(_, data)| {
+            let text = data.strip_prefix(keyword.as_bytes())?.strip_prefix(b"\0")?;
+            Some(String::from_utf8_lossy(text).into_owned())
+        })
+}
+
+#[cfg(feature = "png")]
+#[test]
+fn test_png_text() {
+    use image::{png::PNGEncoder, ColorType};
+
+    let mut png = Vec::new();
+    PNGEncoder::new(&mut png)
+        .encode(&[0, 128, 255, 64], 2, 2, ColorType::Gray(8))
+        .unwrap();
+    assert_eq!(png_text(&png, PNG_KEYWORD), None);
+
+    add_png_text(&mut png, "Software", "mandelbrot");
+    add_png_text(&mut png, PNG_KEYWORD, "hello");
+    assert_eq!(png_text(&png, PNG_KEYWORD).as_deref(), Some("hello"));
+    assert_eq!(png_text(&png, "Software").as_deref(), Some("mandelbrot"));
+    assert_eq!(png_text(&png, "Comment"), None);
+
+    // the image itself must still decode
+    let decoded = image::load_from_memory(&png).unwrap();
+    assert_eq!(decoded.raw_pixels(), vec![0, 128, 255, 64]);
+}
+
+/// Read the view embedded in the PNG file named `filename` by `write_image`.
+pub fn read_png_view(filename: &str) -> Result<View> {
+    let png = fs::read(crate::platform::path(filename)).map_err(Error::io(filename))?;
+    png_text(&png, PNG_KEYWORD)
+        .and_then(|link| parse_share_link(&link))
+        .ok_or_else(|| Error::MissingMetadata(filename.to_string()))
+}
@@ -0,0 +1,132 @@
+//! Letting other processes interrogate a long render, on Unix.
+//!
+//! Once `install` is called, `kill -USR1 PID` asks for the progress to be
+//! printed and `kill -USR2 PID` asks for a checkpoint to be written. After
+//! `install_terminate`, `SIGTERM` asks the program to wrap up instead of
+//! killing it, and after `install_interrupt` so does `SIGINT`, Ctrl-C, the
+//! first time; a second Ctrl-C kills it as usual. The handlers only raise
+//! flags, which the program polls with the `take_*` functions; on other
+//! platforms those flags are simply never raised.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static PROGRESS_REQUESTED: AtomicBool = AtomicBool::new(false);
+static CHECKPOINT_REQUESTED: AtomicBool = AtomicBool::new(false);
+static TERMINATE_REQUESTED: AtomicBool = AtomicBool::new(false);
+static INTERRUPT_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+#[cfg(unix)]
+extern "C" fn on_signal(signal: libc::c_int) {
+    // only async-signal-safe things in here: storing to an atomic is one
+    match signal {
+        libc::SIGUSR1 => PROGRESS_REQUESTED.store(true, Ordering::Relaxed),
+        libc::SIGUSR2 => CHECKPOINT_REQUESTED.store(true, Ordering::Relaxed),
+        libc::SIGTERM => TERMINATE_REQUESTED.store(true, Ordering::Relaxed),
+        libc::SIGINT => {
+            INTERRUPT_REQUESTED.store(true, Ordering::Relaxed);
+            // signal is async-signal-safe too
+            unsafe { libc::signal(libc::SIGINT, libc::SIG_DFL) };
+        }
+        _ => {}
+    }
+}
+
+/// Install the handlers for `SIGUSR1` and `SIGUSR2`.
+#[cfg(unix)]
+pub fn install() {
+    let handler = on_signal as extern "C" fn(libc::c_int) as libc::sighandler_t;
+    unsafe {
+        libc::signal(libc::SIGUSR1, handler);
+        libc::signal(libc::SIGUSR2, handler);
+    }
+}
+
+/// There are no signals to handle outside Unix.
+#[cfg(not(unix))]
+pub fn install() {}
+
+/// Install the handler for `SIGTERM`, which otherwise terminates the program
+/// on the spot.
+#[cfg(unix)]
+pub fn install_terminate() {
+    let handler = on_signal as extern "C" fn(libc::c_int) as libc::sighandler_t;
+    unsafe {
+        libc::signal(libc::SIGTERM, handler);
+    }
+}
+
+#[cfg(not(unix))]
+pub fn install_terminate() {}
+
+/// Install the handler for `SIGINT`, until `restore_interrupt` is called.
+/// It only catches the first: the next one terminates the program.
+#[cfg(unix)]
+pub fn install_interrupt() {
+    let handler = on_signal as extern "C" fn(libc::c_int) as libc::sighandler_t;
+    unsafe {
+        libc::signal(libc::SIGINT, handler);
+    }
+}
+
+#[cfg(not(unix))]
+pub fn install_interrupt() {}
+
+/// Let `SIGINT` terminate the program on the spot again, once there is
+/// nothing left to wrap up.
+#[cfg(unix)]
+pub fn restore_interrupt() {
+    unsafe {
+        libc::signal(libc::SIGINT, libc::SIG_DFL);
+    }
+}
+
+#[cfg(not(unix))]
+pub fn restore_interrupt() {}
+
+/// Return whether the progress was asked for since the last call.
+pub fn take_progress_request() -> bool {
+    PROGRESS_REQUESTED.swap(false, Ordering::Relaxed)
+}
+
+/// Return whether a checkpoint was asked for since the last call.
+pub fn take_checkpoint_request() -> bool {
+    CHECKPOINT_REQUESTED.swap(false, Ordering::Relaxed)
+}
+
+/// Return whether the program was asked to terminate since the last call.
+pub fn take_terminate_request() -> bool {
+    TERMINATE_REQUESTED.swap(false, Ordering::Relaxed)
+}
+
+/// Return whether the program was interrupted since the last call.
+pub fn take_interrupt_request() -> bool {
+    INTERRUPT_REQUESTED.swap(false, Ordering::Relaxed)
+}
+
+#[cfg(unix)]
+#[test]
+fn test_signals() {
+    install();
+    assert!(!take_progress_request());
+    unsafe { libc::raise(libc::SIGUSR1) };
+    assert!(take_progress_request());
+    assert!(!take_progress_request());
+
+    unsafe { libc::raise(libc::SIGUSR2) };
+    assert!(take_checkpoint_request());
+    assert!(!take_checkpoint_request());
+
+    install_terminate();
+    unsafe { libc::raise(libc::SIGTERM) };
+    assert!(take_terminate_request());
+    assert!(!take_terminate_request());
+
+    install_interrupt();
+    unsafe { libc::raise(libc::SIGINT) };
+    assert!(take_interrupt_request());
+    assert!(!take_interrupt_request());
+    // the next one would be fatal, until the handler is installed again
+    let previous = unsafe { libc::signal(libc::SIGINT, libc::SIG_DFL) };
+    assert_eq!(previous, libc::SIG_DFL);
+    restore_interrupt();
+}
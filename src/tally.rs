@@ -0,0 +1,199 @@
+//! Running totals of the renders made, for users who like to keep track of
+//! their exploration: how many renders of which fractal, how many pixels,
+//! how much CPU time and how deep they went.
+//!
+//! Nothing is kept unless the user asks for it with `stats enable`, which
+//! creates `stats.json` in the configuration directory (see
+//! `platform::config_dir`); renders then add to it, and `stats show` prints
+//! it. It never leaves the machine.
+//!
+//! ```json
+//! {
+//!   "version": 1,
+//!   "renders": 12,
+//!   "pixels": 9000000,
+//!   "cpu_seconds": 840.5,
+//!   "deepest_zoom": 1048576.0,
+//!   "fractals": {"mandelbrot": 10, "newton": 2}
+//! }
+//! ```
+
+use std::{collections::BTreeMap, fmt, fs, sync::Mutex};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{output, platform, schema, Error, Result};
+
+/// The name of the file the totals are kept in, in the configuration
+/// directory.
+pub const FILE_NAME: &str = "stats.json";
+
+/// Keeps renders finishing at the same time in a batch from each writing
+/// over the totals of the other.
+static FILE_LOCK: Mutex<()> = Mutex::new(());
+
+/// A render, as it adds to the totals.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Render<'a> {
+    /// As `--fractal` takes it.
+    pub fractal: &'a str,
+    pub pixels: u64,
+    /// The time the render took times the threads it had.
+    pub cpu_seconds: f64,
+    pub zoom: f64,
+}
+
+/// The totals of the renders made since `stats enable`.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Tally {
+    pub version: u64,
+    pub renders: u64,
+    pub pixels: u64,
+    pub cpu_seconds: f64,
+    pub deepest_zoom: f64,
+    /// How many renders of each fractal.
+    pub fractals: BTreeMap<String, u64>,
+}
+
+impl Default for Tally {
+    fn default() -> Tally {
+        Tally {
+            version: schema::VERSION,
+            renders: 0,
+            pixels: 0,
+            cpu_seconds: 0.0,
+            deepest_zoom: 0.0,
+            fractals: BTreeMap::new(),
+        }
+    }
+}
+
+impl Tally {
+    /// The file the user's totals are kept in.
+    pub fn default_file() -> Result<String> {
+        platform::config_file(FILE_NAME)
+    }
+
+    /// Read the totals kept in the file named `file`, or `None` if there is
+    /// no such file, the user not having asked for them.
+    pub fn open(file: &str) -> Result<Option<Tally>> {
+        let path = platform::path(file);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let json = fs::read_to_string(&path).map_err(Error::io(file))?;
+        let invalid = |e: &dyn fmt::Display| Error::parse("stats", &format!("{}: {}", file, e));
+        let mut value: Value = serde_json::from_str(&json).map_err(|e| invalid(&e))?;
+        let version = schema::version("stats", &value)?;
+        if version > schema::VERSION {
+            let mut dropped = Vec::new();
+            schema::drop_unknown::<Tally>(&mut value, "", &mut dropped);
+            schema::warn_newer("the stats file", version, schema::VERSION, &dropped);
+        }
+        serde_json::from_value(value)
+            .map(Some)
+            .map_err(|e| invalid(&e))
+    }
+
+    /// Add `render` to the totals.
+    pub fn add(&mut self, render: &Render) {
+        self.renders += 1;
+        self.pixels += render.pixels;
+        self.cpu_seconds += render.cpu_seconds;
+        if render.zoom.is_finite() {
+            self.deepest_zoom = self.deepest_zoom.max(render.zoom);
+        }
+        *self.fractals.entry(render.fractal.to_string()).or_default() += 1;
+    }
+
+    /// Write the totals to the file named `file`, creating the directory it
+    /// goes in if need be.
+    pub fn save(&self, file: &str) -> Result<()> {
+        output::create_parent_dirs(file)?;
+        let json = serde_json::to_string_pretty(self).unwrap() + "\n";
+        output::write(file, json.as_bytes())
+    }
+}
+
+impl fmt::Display for Tally {
+    /// Write the totals for people to read, a line each.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "renders       {}", self.renders)?;
+        writeln!(f, "pixels        {}", self.pixels)?;
+        writeln!(f, "CPU hours     {:.2}", self.cpu_seconds / 3600.0)?;
+        // to six digits, leaving out the noise of computing it from corners
+        let zoom = format!("{:.5e}", self.deepest_zoom);
+        let (digits, exponent) = zoom.split_once('e').unwrap();
+        let digits = digits.trim_end_matches('0').trim_end_matches('.');
+        writeln!(f, "deepest zoom  {}e{}", digits, exponent)?;
+        for (fractal, renders) in &self.fractals {
+            writeln!(f, "{:<13} {}", fractal, renders)?;
+        }
+        Ok(())
+    }
+}
+
+/// Add `render` to the totals in the file named `file`, if the user keeps
+/// them. Totals written by a later version are left alone rather than
+/// written back without what this version doesn't know.
+pub fn record(file: &str, render: &Render) -> Result<()> {
+    let _lock = FILE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let Some(mut tally) = Tally::open(file)? else {
+        return Ok(());
+    };
+    if tally.version > schema::VERSION {
+        return Ok(());
+    }
+    tally.add(render);
+    tally.save(file)
+}
+
+#[test]
+fn test_tally() {
+    let dir = std::env::temp_dir().join(format!("mandelbrot-tally-{}", std::process::id()));
+    let file = dir.join("config").join(FILE_NAME);
+    let file = file.to_str().unwrap();
+    let render = |fractal, zoom| Render {
+        fractal,
+        pixels: 100,
+        cpu_seconds: 1800.0,
+        zoom,
+    };
+
+    // nothing is kept until asked for
+    record(file, &render("mandelbrot", 2.0)).unwrap();
+    assert_eq!(Tally::open(file).unwrap(), None);
+
+    Tally::default().save(file).unwrap();
+    record(file, &render("mandelbrot", 2.0)).unwrap();
+    record(file, &render("newton", 39.99999999999)).unwrap();
+    record(file, &render("mandelbrot", 8.0)).unwrap();
+    let tally = Tally::open(file).unwrap().unwrap();
+    assert_eq!((tally.renders, tally.pixels), (3, 300));
+    assert_eq!(tally.cpu_seconds, 5400.0);
+    assert_eq!(tally.deepest_zoom, 39.99999999999);
+    assert_eq!(tally.fractals["mandelbrot"], 2);
+    assert_eq!(
+        tally.to_string(),
+        "renders       3\n\
+         pixels        300\n\
+         CPU hours     1.50\n\
+         deepest zoom  4e1\n\
+         mandelbrot    2\n\
+         newton        1\n"
+    );
+
+    // totals of a later version are read, but not written back
+    let newer = r#"{"version": 2, "renders": 5, "pixels": 1, "cpu_seconds": 0,
+        "deepest_zoom": 1, "fractals": {}, "by_palette": {}}"#;
+    fs::write(file, newer).unwrap();
+    assert_eq!(Tally::open(file).unwrap().unwrap().renders, 5);
+    record(file, &render("mandelbrot", 2.0)).unwrap();
+    assert_eq!(fs::read_to_string(file).unwrap(), newer);
+
+    fs::write(file, "{}").unwrap();
+    assert!(Tally::open(file).is_err());
+    fs::remove_dir_all(&dir).unwrap();
+}
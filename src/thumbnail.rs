@@ -0,0 +1,94 @@
+//! Quick previews of a view, for applications embedding the renderer that
+//! want to show what a render will look like, or check it in their own
+//! tests, without paying for a full quality render.
+
+use image::RgbaImage;
+
+use crate::{
+    error,
+    render::{self, Schedule, Strategy},
+    share::View,
+};
+
+/// The largest side a thumbnail can have, whatever `max_px` asks for, so
+/// that the time a thumbnail takes stays bounded.
+pub const MAX_THUMBNAIL_SIDE: usize = 1024;
+
+/// The size of the thumbnail of an image `bounds` in size: the same shape,
+/// with its longer side `max_px` pixels, or the size of the image if that is
+/// smaller. Both sides are at least a pixel.
+pub fn thumbnail_bounds(bounds: (usize, usize), max_px: usize) -> (usize, usize) {
+    let longest = max_px.clamp(1, MAX_THUMBNAIL_SIDE);
+    let (width, height) = (bounds.0.max(1), bounds.1.max(1));
+    if width.max(height) <= longest {
+        return (width, height);
+    }
+    let scale = longest as f64 / width.max(height) as f64;
+    (
+        ((width as f64 * scale).round() as usize).max(1),
+        ((height as f64 * scale).round() as usize).max(1),
+    )
+}
+
+#[test]
+fn test_thumbnail_bounds() {
+    assert_eq!(thumbnail_bounds((1000, 750), 200), (200, 150));
+    assert_eq!(thumbnail_bounds((750, 1000), 200), (150, 200));
+    assert_eq!(thumbnail_bounds((100, 50), 200), (100, 50));
+    assert_eq!(thumbnail_bounds((10000, 1), 100), (100, 1));
+    assert_eq!(thumbnail_bounds((4000, 4000), 5000), (1024, 1024));
+    assert_eq!(thumbnail_bounds((0, 0), 0), (1, 1));
+}
+
+/// Render a thumbnail of `view`, at most `max_px` pixels on its longer side,
+/// as an RGBA image, gray like `render::render` draws it and opaque.
+///
+/// However large the view, the thumbnail is at most `MAX_THUMBNAIL_SIDE`
+/// pixels on a side and every pixel at most 255 iterations, so it always
+/// comes back quickly. A view that can't be rendered, with corners in the
+/// wrong order or not finite, gives a black thumbnail rather than an error.
+pub fn render_thumbnail(view: &View, max_px: usize) -> RgbaImage {
+    let bounds = thumbnail_bounds(view.bounds, max_px);
+    let mut pixels = vec![0; bounds.0 * bounds.1];
+    if error::validate_viewport(bounds, view.upper_left, view.lower_right).is_ok() {
+        render::render_parallel(
+            Strategy::BorderTrace,
+            &mut pixels,
+            bounds,
+            view.upper_left,
+            view.lower_right,
+            &Schedule::new(num_cpus::get()),
+        );
+    }
+    let rgba = pixels
+        .iter()
+        .flat_map(|&gray| [gray, gray, gray, 255])
+        .collect();
+    RgbaImage::from_raw(bounds.0 as u32, bounds.1 as u32, rgba).unwrap()
+}
+
+#[test]
+fn test_render_thumbnail() {
+    use num::Complex;
+
+    let view = View {
+        bounds: (4000, 3000),
+        upper_left: Complex { re: -2.0, im: 1.5 },
+        lower_right: Complex { re: 2.0, im: -1.5 },
+    };
+    let thumbnail = render_thumbnail(&view, 40);
+    assert_eq!(thumbnail.dimensions(), (40, 30));
+    // the center of the set is black, the corners white, all opaque
+    assert_eq!(thumbnail.get_pixel(15, 15).data, [0, 0, 0, 255]);
+    assert!(thumbnail.get_pixel(0, 0).data[0] > 200);
+    assert!(thumbnail.pixels().all(|p| p.data[3] == 255));
+
+    let backwards = View {
+        upper_left: view.lower_right,
+        lower_right: view.upper_left,
+        ..view
+    };
+    let black = render_thumbnail(&backwards, 8);
+    assert_eq!(black.dimensions(), (8, 6));
+    assert!(black.pixels().all(|p| p.data == [0, 0, 0, 255]));
+}
@@ -0,0 +1,268 @@
+//! Rendering a view a tile at a time. With the `tokio` feature, for async
+//! servers, `render_tiles` renders on Tokio's blocking thread pool and hands
+//! the tiles out as a `Stream` as they are finished, so that a handler can
+//! send a progressive response without blocking the reactor.
+
+use std::collections::BTreeMap;
+#[cfg(feature = "tokio")]
+use std::{
+    pin::Pin,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    task::{Context, Poll},
+};
+
+#[cfg(feature = "tokio")]
+use futures_core::Stream;
+use num::Complex;
+#[cfg(feature = "tokio")]
+use tokio::{sync::mpsc, task};
+
+use crate::render;
+#[cfg(feature = "tokio")]
+use crate::render::Strategy;
+
+/// A finished part of a view.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Tile {
+    /// The pixel of the view at the upper left corner of the tile.
+    pub origin: (usize, usize),
+    pub bounds: (usize, usize),
+    /// Grayscale, one byte a pixel, as `render::render` draws them.
+    pub pixels: Vec<u8>,
+}
+
+/// The tiles of a render, in the order they are finished.
+#[cfg(feature = "tokio")]
+#[derive(Debug)]
+pub struct TileStream {
+    tiles: mpsc::Receiver<Tile>,
+}
+
+#[cfg(feature = "tokio")]
+impl Stream for TileStream {
+    type Item = Tile;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Tile>> {
+        self.tiles.poll_recv(cx)
+    }
+}
+
+/// The origins and sizes of the tiles of an image whose dimensions are
+/// given by `bounds`, cut into squares `size` pixels wide, row by row. The
+/// last tiles of a row or a column are cut short by the edge of the image.
+pub fn layout(bounds: (usize, usize), size: usize) -> Vec<((usize, usize), (usize, usize))> {
+    let size = size.max(1);
+    let mut tiles = Vec::new();
+    for y in (0..bounds.1).step_by(size) {
+        for x in (0..bounds.0).step_by(size) {
+            let tile_bounds = (size.min(bounds.0 - x), size.min(bounds.1 - y));
+            tiles.push(((x, y), tile_bounds));
+        }
+    }
+    tiles
+}
+
+#[test]
+fn test_layout() {
+    assert_eq!(
+        layout((5, 3), 2),
+        [
+            ((0, 0), (2, 2)),
+            ((2, 0), (2, 2)),
+            ((4, 0), (1, 2)),
+            ((0, 2), (2, 1)),
+            ((2, 2), (2, 1)),
+            ((4, 2), (1, 1)),
+        ]
+    );
+    assert_eq!(layout((4, 4), 8), [((0, 0), (4, 4))]);
+    assert!(layout((0, 4), 8).is_empty());
+}
+
+/// The corners of the tile at `origin`, whose dimensions are given by
+/// `tile_bounds`, of the view from `upper_left` to `lower_right` whose
+/// dimensions are given by `bounds`.
+pub fn corners(
+    bounds: (usize, usize),
+    (origin, tile_bounds): ((usize, usize), (usize, usize)),
+    upper_left: Complex<f64>,
+    lower_right: Complex<f64>,
+) -> (Complex<f64>, Complex<f64>) {
+    let corner = |(x, y)| render::pixel_to_point(bounds, (x, y), upper_left, lower_right);
+    (
+        corner(origin),
+        corner((origin.0 + tile_bounds.0, origin.1 + tile_bounds.1)),
+    )
+}
+
+#[test]
+fn test_corners() {
+    let (upper_left, lower_right) = (Complex::new(-2.0, 1.0), Complex::new(2.0, -1.0));
+    assert_eq!(
+        corners((4, 2), ((2, 0), (2, 1)), upper_left, lower_right),
+        (Complex::new(0.0, 1.0), Complex::new(2.0, 0.0))
+    );
+}
+
+/// Render the view from `upper_left` to `lower_right`, whose dimensions are
+/// given by `bounds`, in tiles `tile_size` pixels wide, as `layout` cuts
+/// them, iterating at most `limit` times with `strategy`.
+///
+/// The tiles are rendered on as many blocking tasks as there are cores;
+/// dropping the stream stops them after the tiles in progress. Panics
+/// outside of a Tokio runtime.
+#[cfg(feature = "tokio")]
+pub fn render_tiles(
+    bounds: (usize, usize),
+    upper_left: Complex<f64>,
+    lower_right: Complex<f64>,
+    strategy: Strategy,
+    limit: usize,
+    tile_size: usize,
+) -> TileStream {
+    let tiles = Arc::new(layout(bounds, tile_size));
+    let workers = num_cpus::get().min(tiles.len()).max(1);
+    let (sender, receiver) = mpsc::channel(workers);
+    let next_tile = Arc::new(AtomicUsize::new(0));
+    for _ in 0..workers {
+        let (tiles, next_tile, sender) = (tiles.clone(), next_tile.clone(), sender.clone());
+        task::spawn_blocking(move || {
+            while let Some(&(origin, tile_bounds)) =
+                tiles.get(next_tile.fetch_add(1, Ordering::Relaxed))
+            {
+                let (tile_upper_left, tile_lower_right) =
+                    corners(bounds, (origin, tile_bounds), upper_left, lower_right);
+                let mut pixels = vec![0; tile_bounds.0 * tile_bounds.1];
+                render::render_with_limit(
+                    strategy,
+                    &mut pixels,
+                    tile_bounds,
+                    tile_upper_left,
+                    tile_lower_right,
+                    limit,
+                );
+                let tile = Tile {
+                    origin,
+                    bounds: tile_bounds,
+                    pixels,
+                };
+                if sender.blocking_send(tile).is_err() {
+                    // nobody is listening any more
+                    return;
+                }
+            }
+        });
+    }
+    TileStream { tiles: receiver }
+}
+
+/// Tiles put back together into whole rows, top to bottom, for writers
+/// that take rows, such as `encode::PngWriter`: the rows of a tile come out
+/// once every tile beside it and above it is in.
+#[derive(Debug)]
+pub struct TileRows {
+    width: usize,
+    /// The pixels of the rows of tiles not out yet, by their first row,
+    /// with how many of them are in.
+    bands: BTreeMap<usize, (Vec<u8>, usize)>,
+    next_row: usize,
+}
+
+impl TileRows {
+    /// Put together the tiles of a view whose dimensions are given by
+    /// `bounds`.
+    pub fn new(bounds: (usize, usize)) -> TileRows {
+        TileRows {
+            width: bounds.0,
+            bands: BTreeMap::new(),
+            next_row: 0,
+        }
+    }
+
+    /// Add `tile`, and take the rows it completes along with any below
+    /// them that were waiting for them, which may be none.
+    pub fn add(&mut self, tile: &Tile) -> Vec<u8> {
+        let width = self.width;
+        let (band, done) = self
+            .bands
+            .entry(tile.origin.1)
+            .or_insert_with(|| (vec![0; width * tile.bounds.1], 0));
+        for (row, pixels) in tile.pixels.chunks(tile.bounds.0.max(1)).enumerate() {
+            let start = row * width + tile.origin.0;
+            band[start..start + pixels.len()].copy_from_slice(pixels);
+        }
+        *done += tile.pixels.len();
+
+        let mut rows = Vec::new();
+        while let Some((band, done)) = self.bands.get(&self.next_row) {
+            if *done < band.len() {
+                break;
+            }
+            let (band, _) = self.bands.remove(&self.next_row).unwrap();
+            self.next_row += band.len() / width.max(1);
+            rows.extend(band);
+        }
+        rows
+    }
+}
+
+#[test]
+fn test_tile_rows() {
+    let bounds = (5, 3);
+    let tile = |(origin, bounds): ((usize, usize), (usize, usize))| Tile {
+        origin,
+        bounds,
+        pixels: vec![origin.0 as u8 + 10 * origin.1 as u8; bounds.0 * bounds.1],
+    };
+    let tiles: Vec<Tile> = layout(bounds, 2).into_iter().map(tile).collect();
+    let mut rows = TileRows::new(bounds);
+    // the second row of tiles first, then the first
+    for tile in &tiles[3..5] {
+        assert!(rows.add(tile).is_empty());
+    }
+    assert!(rows.add(&tiles[0]).is_empty());
+    assert!(rows.add(&tiles[1]).is_empty());
+    assert_eq!(rows.add(&tiles[2]), [0, 0, 2, 2, 4].repeat(2));
+    assert_eq!(rows.add(&tiles[5]), [20, 20, 22, 22, 24]);
+}
+
+#[cfg(feature = "tokio")]
+#[test]
+fn test_render_tiles() {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap();
+    let bounds = (50, 30);
+    let (upper_left, lower_right) = (Complex::new(-2.0, 1.0), Complex::new(1.0, -1.0));
+    let mut image = vec![1; bounds.0 * bounds.1];
+    let count = runtime.block_on(async {
+        let mut stream = render_tiles(bounds, upper_left, lower_right, Strategy::Naive, 255, 16);
+        let mut count = 0;
+        while let Some(tile) = std::future::poll_fn(|cx| Pin::new(&mut stream).poll_next(cx)).await
+        {
+            for (row, pixels) in tile.pixels.chunks(tile.bounds.0).enumerate() {
+                let start = (tile.origin.1 + row) * bounds.0 + tile.origin.0;
+                image[start..start + tile.bounds.0].copy_from_slice(pixels);
+            }
+            count += 1;
+        }
+        count
+    });
+    assert_eq!(count, 4 * 2);
+
+    // the same as rendering the view at once, but for rounding
+    let mut expected = vec![0; bounds.0 * bounds.1];
+    render::render_with_limit(
+        Strategy::Naive,
+        &mut expected,
+        bounds,
+        upper_left,
+        lower_right,
+        255,
+    );
+    let different = image.iter().zip(&expected).filter(|(a, b)| a != b);
+    assert!(different.count() < expected.len() / 100);
+}
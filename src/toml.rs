@@ -0,0 +1,113 @@
+//! Just enough TOML for the files this crate reads: `key = value` lines
+//! whose values are strings, numbers, booleans and arrays of them, possibly
+//! over several lines, in tables (`[name]`) and arrays of tables
+//! (`[[name]]`).
+//!
+//! Those values happen to be valid JSON too, so the lines are put together
+//! as a JSON value, which is then read like a JSON file would be.
+
+use serde_json::{Map, Value};
+
+/// Turn the TOML document `text` into the JSON value it stands for.
+pub fn to_json(text: &str) -> Result<Value, String> {
+    let mut root = Map::new();
+    // the table the lines go into, and whether it's in an array of tables
+    let mut table: Option<(String, bool)> = None;
+    let mut pending = String::new();
+    for line in text.lines() {
+        // `#` starts a comment, and whitespace goes, outside of strings
+        let mut in_string = false;
+        for c in line.chars() {
+            in_string ^= c == '"';
+            if !in_string && c == '#' {
+                break;
+            }
+            if in_string || !c.is_whitespace() {
+                pending.push(c);
+            }
+        }
+        let depth = pending.matches('[').count() as isize - pending.matches(']').count() as isize;
+        if depth > 0 || pending.is_empty() {
+            continue;
+        }
+
+        if pending.starts_with('[') && !pending.contains('=') {
+            if let Some(name) = pending
+                .strip_prefix("[[")
+                .and_then(|p| p.strip_suffix("]]"))
+            {
+                let tables = root.entry(name).or_insert_with(|| Value::Array(Vec::new()));
+                tables
+                    .as_array_mut()
+                    .ok_or_else(|| format!("{} is not an array of tables", name))?
+                    .push(Value::Object(Map::new()));
+                table = Some((name.to_string(), true));
+            } else {
+                let name = &pending[1..pending.len() - 1];
+                if root.contains_key(name) {
+                    return Err(format!("{} is defined twice", name));
+                }
+                root.insert(name.to_string(), Value::Object(Map::new()));
+                table = Some((name.to_string(), false));
+            }
+            pending.clear();
+            continue;
+        }
+
+        let (key, value) = pending
+            .split_once('=')
+            .ok_or_else(|| format!("expected key = value, not {:?}", pending))?;
+        // TOML allows a comma after the last element, JSON doesn't
+        let value: Value = serde_json::from_str(&value.replace(",]", "]"))
+            .map_err(|_| format!("invalid value of {}: {}", key, value))?;
+        let fields = match &table {
+            None => Some(&mut root),
+            Some((name, false)) => root[name].as_object_mut(),
+            Some((name, true)) => root[name]
+                .as_array_mut()
+                .and_then(|tables| tables.last_mut())
+                .and_then(Value::as_object_mut),
+        };
+        fields.unwrap().insert(key.to_string(), value);
+        pending.clear();
+    }
+    if !pending.is_empty() {
+        return Err(format!("unterminated value {:?}", pending));
+    }
+    Ok(Value::Object(root))
+}
+
+#[test]
+fn test_to_json() {
+    let text = r##"
+        # a comment
+        name = "fire # not a comment"
+        stops = [
+            [0, "#000000"],
+            [1, [255, 255, 255]],
+        ]
+
+        [render]
+        smooth = true
+
+        [[keyframes]]
+        zoom = 1
+        [[keyframes]]
+        zoom = 2.5
+    "##;
+    assert_eq!(
+        to_json(text).unwrap(),
+        serde_json::json!({
+            "name": "fire # not a comment",
+            "stops": [[0, "#000000"], [1, [255, 255, 255]]],
+            "render": {"smooth": true},
+            "keyframes": [{"zoom": 1}, {"zoom": 2.5}],
+        })
+    );
+
+    assert!(to_json("stops [[0, 1]]").is_err());
+    assert!(to_json("zoom = [1, 2").is_err());
+    assert!(to_json("zoom = nope").is_err());
+    assert!(to_json("[a]\n[a]").is_err());
+    assert!(to_json("a = 1\n[[a]]").is_err());
+}
@@ -0,0 +1,157 @@
+//! The order threads take the pixels of an image in, for `--traversal`.
+//!
+//! By default they take whole rows, top to bottom. Along a Hilbert or a
+//! Morton (Z-order) curve they take square blocks instead, each next to the
+//! one before, which keeps the pixels a thread works on close together in
+//! buffers like the orbits of `--progressive`, and fills checkpoints in
+//! evenly rather than from the top down.
+
+use std::str::FromStr;
+
+/// The width and the height of the blocks curves go through, in pixels.
+pub const BLOCK_SIZE: usize = 16;
+
+/// An order to go through the pixels of an image in.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Traversal {
+    /// Rows, top to bottom.
+    #[default]
+    Rows,
+    /// Blocks along a Hilbert curve, which never jumps.
+    Hilbert,
+    /// Blocks along a Morton curve, which jumps, but less far the more
+    /// often.
+    Morton,
+}
+
+impl FromStr for Traversal {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Traversal, String> {
+        match s {
+            "rows" => Ok(Traversal::Rows),
+            "hilbert" => Ok(Traversal::Hilbert),
+            "morton" => Ok(Traversal::Morton),
+            _ => Err(format!(
+                "unknown traversal {}, expected rows, hilbert or morton",
+                s
+            )),
+        }
+    }
+}
+
+#[test]
+fn test_parse_traversal() {
+    assert_eq!("hilbert".parse(), Ok(Traversal::Hilbert));
+    assert_eq!("morton".parse(), Ok(Traversal::Morton));
+    assert!("spiral".parse::<Traversal>().is_err());
+}
+
+/// The point `d` steps along the Hilbert curve through a square `side`
+/// points wide, `side` being a power of two.
+fn hilbert(side: usize, d: usize) -> (usize, usize) {
+    let (mut x, mut y, mut t) = (0, 0, d);
+    let mut s = 1;
+    while s < side {
+        let rx = 1 & (t / 2);
+        let ry = 1 & (t ^ rx);
+        if ry == 0 {
+            if rx == 1 {
+                x = s - 1 - x;
+                y = s - 1 - y;
+            }
+            std::mem::swap(&mut x, &mut y);
+        }
+        x += s * rx;
+        y += s * ry;
+        t /= 4;
+        s *= 2;
+    }
+    (x, y)
+}
+
+/// The point `d` steps along the Morton curve: the even bits of `d` are
+/// `x`, the odd ones `y`.
+fn morton(d: usize) -> (usize, usize) {
+    let (mut x, mut y) = (0, 0);
+    for bit in 0..usize::BITS as usize / 2 {
+        x |= (d >> (2 * bit) & 1) << bit;
+        y |= (d >> (2 * bit + 1) & 1) << bit;
+    }
+    (x, y)
+}
+
+#[test]
+fn test_curves() {
+    let steps: Vec<_> = (0..4).map(|d| hilbert(2, d)).collect();
+    assert_eq!(steps, [(0, 0), (0, 1), (1, 1), (1, 0)]);
+    let steps: Vec<_> = (0..4).map(morton).collect();
+    assert_eq!(steps, [(0, 0), (1, 0), (0, 1), (1, 1)]);
+    assert_eq!(morton(0b1110), (2, 3));
+
+    // the Hilbert curve goes through every point once, a step at a time
+    let side = 16;
+    let mut seen = vec![false; side * side];
+    let mut previous = hilbert(side, 0);
+    for d in 0..side * side {
+        let (x, y) = hilbert(side, d);
+        assert!(!seen[y * side + x]);
+        seen[y * side + x] = true;
+        assert!(x.abs_diff(previous.0) + y.abs_diff(previous.1) <= 1);
+        previous = (x, y);
+    }
+}
+
+/// The blocks of an image whose dimensions are given by `bounds`, each as
+/// the pixel at its upper left corner and its dimensions, in the order
+/// `traversal` goes through them: `BLOCK_SIZE` squares, cut short at the
+/// right and the bottom edges, or whole rows for `Traversal::Rows`.
+pub fn blocks(
+    bounds: (usize, usize),
+    traversal: Traversal,
+) -> Vec<((usize, usize), (usize, usize))> {
+    let (columns, rows) = (bounds.0.div_ceil(BLOCK_SIZE), bounds.1.div_ceil(BLOCK_SIZE));
+    let side = columns.max(rows).next_power_of_two();
+    let grid: Box<dyn Iterator<Item = (usize, usize)>> = match traversal {
+        Traversal::Rows => return (0..bounds.1).map(|y| ((0, y), (bounds.0, 1))).collect(),
+        Traversal::Hilbert => Box::new((0..side * side).map(|d| hilbert(side, d))),
+        Traversal::Morton => Box::new((0..side * side).map(morton)),
+    };
+    grid.filter(|&(column, row)| column < columns && row < rows)
+        .map(|(column, row)| {
+            let (x, y) = (column * BLOCK_SIZE, row * BLOCK_SIZE);
+            let size = (BLOCK_SIZE.min(bounds.0 - x), BLOCK_SIZE.min(bounds.1 - y));
+            ((x, y), size)
+        })
+        .collect()
+}
+
+#[test]
+fn test_blocks() {
+    assert_eq!(
+        blocks((3, 2), Traversal::Rows),
+        [((0, 0), (3, 1)), ((0, 1), (3, 1))]
+    );
+    let bounds = (40, 20);
+    for traversal in [Traversal::Hilbert, Traversal::Morton] {
+        let blocks = blocks(bounds, traversal);
+        assert_eq!(blocks.len(), 3 * 2, "{:?}", traversal);
+        // every pixel is in one block
+        let mut covered = vec![0; bounds.0 * bounds.1];
+        for ((x, y), (width, height)) in &blocks {
+            for row in *y..y + height {
+                for pixel in &mut covered[row * bounds.0 + x..row * bounds.0 + x + width] {
+                    *pixel += 1;
+                }
+            }
+        }
+        assert!(covered.iter().all(|&count| count == 1), "{:?}", traversal);
+        assert_eq!(blocks[0], ((0, 0), (16, 16)));
+    }
+    assert_eq!(
+        blocks(bounds, Traversal::Hilbert)[1..3],
+        [((16, 0), (16, 16)), ((16, 16), (16, 4))]
+    );
+    assert_eq!(blocks(bounds, Traversal::Morton)[1], ((16, 0), (16, 16)));
+    assert!(blocks((0, 0), Traversal::Hilbert).is_empty());
+}
@@ -0,0 +1,84 @@
+//! Units of the view, kept apart by their types: how much a view is
+//! magnified, how wide it is on the complex plane, and how much of the plane
+//! one pixel covers.
+//!
+//! All three describe the same thing, the scale of a view, and converting
+//! between them takes the number of pixels across the image where pixels are
+//! involved. Passing a width where a zoom is expected, or a zoom computed for
+//! another image size, is then a type error rather than a wrong picture.
+
+use num::Complex;
+
+use crate::render::UNZOOMED_WIDTH;
+
+/// A magnification, relative to a view `UNZOOMED_WIDTH` wide.
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+pub struct Zoom(pub f64);
+
+/// A width on the complex plane.
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+pub struct ComplexWidth(pub f64);
+
+/// The width on the complex plane of one pixel.
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+pub struct PixelSize(pub f64);
+
+impl Zoom {
+    /// The zoom of the whole set, which is `UNZOOMED_WIDTH` wide.
+    pub const NONE: Zoom = Zoom(1.0);
+
+    /// The width of a view magnified this much.
+    pub fn width(self) -> ComplexWidth {
+        ComplexWidth(UNZOOMED_WIDTH / self.0)
+    }
+
+    /// The size of a pixel of a view magnified this much, `pixels_across`
+    /// pixels wide.
+    pub fn pixel_size(self, pixels_across: usize) -> PixelSize {
+        self.width().pixel_size(pixels_across)
+    }
+}
+
+impl ComplexWidth {
+    /// The width of the view between `upper_left` and `lower_right`.
+    pub fn between(upper_left: Complex<f64>, lower_right: Complex<f64>) -> ComplexWidth {
+        ComplexWidth(lower_right.re - upper_left.re)
+    }
+
+    /// The zoom of a view this wide.
+    pub fn zoom(self) -> Zoom {
+        Zoom(UNZOOMED_WIDTH / self.0)
+    }
+
+    /// The size of a pixel of a view this wide, `pixels_across` pixels wide.
+    pub fn pixel_size(self, pixels_across: usize) -> PixelSize {
+        PixelSize(self.0 / pixels_across.max(1) as f64)
+    }
+}
+
+impl PixelSize {
+    /// The width of a view `pixels_across` pixels of this size wide.
+    pub fn width(self, pixels_across: usize) -> ComplexWidth {
+        ComplexWidth(self.0 * pixels_across as f64)
+    }
+
+    /// The zoom of a view `pixels_across` pixels of this size wide.
+    pub fn zoom(self, pixels_across: usize) -> Zoom {
+        self.width(pixels_across).zoom()
+    }
+}
+
+#[test]
+fn test_conversions() {
+    assert_eq!(Zoom::NONE.width(), ComplexWidth(UNZOOMED_WIDTH));
+    assert_eq!(Zoom(8.0).width(), ComplexWidth(0.5));
+    assert_eq!(ComplexWidth(0.5).zoom(), Zoom(8.0));
+    assert_eq!(Zoom(8.0).pixel_size(100), PixelSize(0.005));
+    assert_eq!(PixelSize(0.005).width(100), ComplexWidth(0.5));
+    assert_eq!(PixelSize(0.005).zoom(100), Zoom(8.0));
+    assert_eq!(
+        ComplexWidth::between(Complex::new(-2.0, 1.0), Complex::new(1.0, -1.0)),
+        ComplexWidth(3.0)
+    );
+    assert!(Zoom(2.0) > Zoom::NONE);
+}
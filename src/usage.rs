@@ -0,0 +1,315 @@
+//! Measuring what a render costs, for `--stats-json`: how long every thread
+//! was busy, with how many rows, and how many iterations the orbits took;
+//! and for `--cost-map`, the iterations spent on every pixel.
+//!
+//! Iterations are counted on the thread running them, by the escape time
+//! functions, so that the renderers don't need to pass counters around;
+//! `Usage::measure` collects them chunk by chunk, and `counting` pixel by
+//! pixel.
+
+use std::{
+    cell::Cell,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use serde::Serialize;
+
+thread_local! {
+    static ITERATIONS: Cell<u64> = const { Cell::new(0) };
+}
+
+/// Count `iterations` more iterations run on this thread.
+pub fn count_iterations(iterations: usize) {
+    ITERATIONS.with(|counted| counted.set(counted.get() + iterations as u64));
+}
+
+/// The iterations counted on this thread so far.
+fn iterations_counted() -> u64 {
+    ITERATIONS.with(Cell::get)
+}
+
+/// Run `work`, returning what it returns and the iterations it ran on this
+/// thread.
+pub fn counting<T>(work: impl FnOnce() -> T) -> (T, u32) {
+    let counted = iterations_counted();
+    let result = work();
+    let iterations = (iterations_counted() - counted).min(u32::MAX as u64);
+    (result, iterations as u32)
+}
+
+/// The iterations spent on every pixel of an image, filled in as its chunks
+/// are rendered. The pixels that weren't computed but filled in from others,
+/// by border tracing, interpolation or mirroring, cost nothing.
+pub struct Cost {
+    width: usize,
+    values: Mutex<Vec<u32>>,
+}
+
+impl Cost {
+    /// Room for an image whose dimensions are given by `bounds`.
+    pub fn new(bounds: (usize, usize)) -> Cost {
+        Cost {
+            width: bounds.0,
+            values: Mutex::new(vec![0; bounds.0 * bounds.1]),
+        }
+    }
+
+    /// Keep the costs of the block `width` pixels wide whose upper left
+    /// pixel is `origin`, row by row.
+    pub fn set_block(&self, origin: (usize, usize), width: usize, costs: &[u32]) {
+        let mut values = self.values.lock().unwrap();
+        for (row, line) in costs.chunks(width).enumerate() {
+            let start = (origin.1 + row) * self.width + origin.0;
+            values[start..start + line.len()].copy_from_slice(line);
+        }
+    }
+
+    /// The cost of every pixel, row by row.
+    pub fn values(&self) -> Vec<u32> {
+        self.values.lock().unwrap().clone()
+    }
+}
+
+/// The costs of `costs` as levels from 0 to 255, on a logarithmic scale up
+/// to the largest of them, so that both the pixels a few iterations away
+/// from each other far from the set and those at the limit near it show.
+pub fn cost_levels(costs: &[u32]) -> Vec<u8> {
+    let max = costs.iter().copied().max().unwrap_or(0).max(1) as f64;
+    costs
+        .iter()
+        .map(|&cost| (255.0 * (cost as f64).ln_1p() / max.ln_1p()).round() as u8)
+        .collect()
+}
+
+#[test]
+fn test_cost() {
+    let ((), iterations) = counting(|| {
+        count_iterations(40);
+        count_iterations(2);
+    });
+    assert_eq!(iterations, 42);
+    assert_eq!(counting(|| 7), (7, 0));
+
+    let cost = Cost::new((3, 3));
+    cost.set_block((1, 1), 2, &[5, 6, 7, 8]);
+    cost.set_block((0, 0), 3, &[1, 2, 3]);
+    assert_eq!(cost.values(), [1, 2, 3, 0, 5, 6, 0, 7, 8]);
+    assert_eq!(cost_levels(&[0, 1, 15, 255]), [0, 32, 128, 255]);
+    assert_eq!(cost_levels(&[0, 0]), [0, 0]);
+}
+
+/// What one thread of a render did.
+#[derive(Debug, Default)]
+struct Thread {
+    busy_nanos: AtomicU64,
+    rows: AtomicU64,
+    iterations: AtomicU64,
+}
+
+/// What the threads of a render did, filled in as they go.
+#[derive(Debug, Default)]
+pub struct Usage {
+    threads: Vec<Thread>,
+}
+
+impl Usage {
+    /// Room for the work of `threads` threads.
+    pub fn new(threads: usize) -> Usage {
+        Usage {
+            threads: (0..threads).map(|_| Thread::default()).collect(),
+        }
+    }
+
+    /// Run `work`, rendering `rows` rows on thread number `thread`, counting
+    /// the time it takes and the iterations it runs in `usage`, if any.
+    pub fn measure<T>(
+        usage: Option<&Usage>,
+        thread: usize,
+        rows: usize,
+        work: impl FnOnce() -> T,
+    ) -> T {
+        let Some(thread) = usage.and_then(|usage| usage.threads.get(thread)) else {
+            return work();
+        };
+        let (started, counted) = (Instant::now(), iterations_counted());
+        let result = work();
+        let busy = started.elapsed().as_nanos() as u64;
+        thread.busy_nanos.fetch_add(busy, Ordering::Relaxed);
+        thread.rows.fetch_add(rows as u64, Ordering::Relaxed);
+        let iterations = iterations_counted() - counted;
+        thread.iterations.fetch_add(iterations, Ordering::Relaxed);
+        result
+    }
+
+    /// The iterations run by all the threads.
+    pub fn iterations(&self) -> u64 {
+        self.threads
+            .iter()
+            .map(|thread| thread.iterations.load(Ordering::Relaxed))
+            .sum()
+    }
+
+    /// What each thread did, out of `elapsed`, the time the render took.
+    pub fn threads(&self, elapsed: Duration) -> Vec<ThreadStats> {
+        self.threads
+            .iter()
+            .map(|thread| {
+                let busy = Duration::from_nanos(thread.busy_nanos.load(Ordering::Relaxed));
+                ThreadStats {
+                    busy_seconds: busy.as_secs_f64(),
+                    utilization: busy.as_secs_f64() / elapsed.as_secs_f64().max(f64::EPSILON),
+                    rows: thread.rows.load(Ordering::Relaxed),
+                    iterations: thread.iterations.load(Ordering::Relaxed),
+                }
+            })
+            .collect()
+    }
+}
+
+/// What one thread did, as `--stats-json` reports it.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct ThreadStats {
+    pub busy_seconds: f64,
+    /// The share of the render the thread was busy, from 0 to 1.
+    pub utilization: f64,
+    pub rows: u64,
+    pub iterations: u64,
+}
+
+/// The numbers `--stats-json` writes at the end of a render. What isn't
+/// known for the fractal rendered is `null`.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Stats {
+    /// The version of the format, see `schema`.
+    pub version: u64,
+    /// From reading the arguments to writing the last file.
+    pub wall_seconds: f64,
+    pub render_seconds: f64,
+    pub pixels: usize,
+    pub pixels_per_second: f64,
+    pub iterations: Option<u64>,
+    /// The escape time kernel the processor ran, see `cpu`.
+    pub kernel: &'static str,
+    /// The share of the pixels in the set, in percent.
+    pub interior_percent: Option<f64>,
+    /// The most memory the process held at once, in bytes.
+    pub peak_memory_bytes: Option<u64>,
+    pub threads: Vec<ThreadStats>,
+    /// How much the points averaged in each pixel vary, when several are.
+    pub variance: Option<VarianceStats>,
+}
+
+impl Stats {
+    /// The statistics as pretty printed JSON.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("stats are plain numbers") + "\n"
+    }
+}
+
+/// How much the points averaged in the pixels of a supersampled render vary,
+/// as `--stats-json` reports it.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct VarianceStats {
+    /// The points averaged in each pixel.
+    pub samples: usize,
+    /// The mean and the largest variance of the pixels, in gray levels
+    /// squared.
+    pub mean: f64,
+    pub max: f64,
+    /// The share of the pixels whose average is likely off by more than a
+    /// gray level, its standard error being more than 1, in percent.
+    pub noisy_percent: f64,
+}
+
+impl VarianceStats {
+    /// The statistics of `variance`, the variance of every pixel, of
+    /// `samples` points each.
+    pub fn new(variance: &[f32], samples: usize) -> VarianceStats {
+        let count = variance.len().max(1) as f64;
+        let noisy = variance
+            .iter()
+            .filter(|&&variance| variance as f64 / samples as f64 > 1.0)
+            .count();
+        VarianceStats {
+            samples,
+            mean: variance
+                .iter()
+                .map(|&variance| variance as f64)
+                .sum::<f64>()
+                / count,
+            max: variance
+                .iter()
+                .fold(0.0, |max, &variance| max.max(variance as f64)),
+            noisy_percent: 100.0 * noisy as f64 / count,
+        }
+    }
+}
+
+/// The share of `pixels`, grayscale, that are black, as points in the set
+/// are, in percent.
+pub fn interior_percent(pixels: &[u8]) -> f64 {
+    let interior = pixels.iter().filter(|&&gray| gray == 0).count();
+    100.0 * interior as f64 / pixels.len().max(1) as f64
+}
+
+#[test]
+fn test_usage() {
+    let usage = Usage::new(2);
+    let sum = Usage::measure(Some(&usage), 1, 3, || {
+        count_iterations(40);
+        count_iterations(2);
+        7
+    });
+    assert_eq!(sum, 7);
+    // iterations outside of measured work, or not measured, don't count
+    count_iterations(1000);
+    Usage::measure(None, 0, 5, || count_iterations(1000));
+    Usage::measure(Some(&usage), 9, 5, || count_iterations(1000));
+    assert_eq!(usage.iterations(), 42);
+
+    let threads = usage.threads(Duration::from_secs(1));
+    assert_eq!((threads[0].rows, threads[0].iterations), (0, 0));
+    assert_eq!(threads[0].busy_seconds, 0.0);
+    assert_eq!((threads[1].rows, threads[1].iterations), (3, 42));
+    assert!(threads[1].utilization >= 0.0 && threads[1].utilization < 1.0);
+}
+
+#[test]
+fn test_stats() {
+    assert_eq!(interior_percent(&[0, 10, 0, 255]), 50.0);
+    assert_eq!(interior_percent(&[]), 0.0);
+    let variance = VarianceStats::new(&[0.0, 4.0, 9.0, 35.0], 4);
+    assert_eq!((variance.mean, variance.max), (12.0, 35.0));
+    assert_eq!(variance.noisy_percent, 50.0);
+
+    let stats = Stats {
+        version: 1,
+        wall_seconds: 1.5,
+        render_seconds: 1.0,
+        pixels: 100,
+        pixels_per_second: 100.0,
+        iterations: None,
+        kernel: "avx2",
+        interior_percent: Some(25.0),
+        peak_memory_bytes: Some(1 << 20),
+        threads: vec![ThreadStats {
+            busy_seconds: 0.5,
+            utilization: 0.5,
+            rows: 10,
+            iterations: 1000,
+        }],
+        variance: None,
+    };
+    let json: serde_json::Value = serde_json::from_str(&stats.to_json()).unwrap();
+    assert_eq!(json["version"], 1);
+    assert_eq!(json["iterations"], serde_json::Value::Null);
+    assert_eq!(json["kernel"], "avx2");
+    assert_eq!(json["interior_percent"], 25.0);
+    assert_eq!(json["peak_memory_bytes"], 1 << 20);
+    assert_eq!(json["threads"][0]["rows"], 10);
+    assert_eq!(json["variance"], serde_json::Value::Null);
+}
@@ -0,0 +1,285 @@
+//! Checking the files a long run reads without rendering them, for the
+//! `validate` subcommand: scenes, palettes, projects and configuration
+//! files, so that mistakes show up before an overnight animation rather than
+//! in the middle of it.
+//!
+//! A scene is checked the way `animate` would render it: every frame has to
+//! be a proper view, computed in numbers precise enough to tell its pixels
+//! apart, and how long the whole animation takes is estimated from small
+//! renders of a few of its frames.
+
+use std::{path::Path, time::Instant};
+
+use num::Complex;
+use serde_json::Value;
+
+use crate::{
+    animation::Scene,
+    error,
+    limit::MaxIters,
+    platform,
+    real::Numeric,
+    render::{self, Strategy},
+    toml, Error, Result,
+};
+
+/// What a file `validate` is given holds.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Kind {
+    Scene,
+    Palette,
+    Project,
+    Config,
+}
+
+impl Kind {
+    /// What the file `filename` holds, going by its name, and by its fields
+    /// for JSON and TOML files, which may be scenes or palettes.
+    pub fn of(filename: &str) -> Result<Kind> {
+        let lower = filename.to_ascii_lowercase();
+        let name = Path::new(&lower).file_name().unwrap_or_default();
+        if name == crate::config::FILE_NAME {
+            return Ok(Kind::Config);
+        }
+        if lower.ends_with(".mandel") {
+            return Ok(Kind::Project);
+        }
+        if lower.ends_with(".ugr") || lower.ends_with(".ggr") {
+            return Ok(Kind::Palette);
+        }
+        let unknown = || {
+            Error::Usage(format!(
+                "can't tell whether {} is a scene, a palette, a project or a configuration file",
+                filename
+            ))
+        };
+        let text =
+            std::fs::read_to_string(platform::path(filename)).map_err(Error::io(filename))?;
+        let value = if lower.ends_with(".toml") {
+            toml::to_json(&text).map_err(|e| Error::parse("TOML file", &e))?
+        } else if lower.ends_with(".json") {
+            serde_json::from_str(&text).map_err(|e| Error::parse("JSON file", &e.to_string()))?
+        } else {
+            return Err(unknown());
+        };
+        match value {
+            Value::Object(fields) if fields.contains_key("keyframes") => Ok(Kind::Scene),
+            Value::Object(fields) if fields.contains_key("stops") => Ok(Kind::Palette),
+            _ => Err(unknown()),
+        }
+    }
+}
+
+#[test]
+fn test_kind() {
+    let dir = std::env::temp_dir().join(format!("mandelbrot-validate-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let file = |name: &str, text: &str| {
+        let path = dir.join(name);
+        std::fs::write(&path, text).unwrap();
+        path.to_string_lossy().into_owned()
+    };
+    let kind = |name, text| Kind::of(&file(name, text));
+    assert_eq!(
+        kind("dive.toml", "[[keyframes]]\ncenter = [0, 0]\nzoom = 1").unwrap(),
+        Kind::Scene
+    );
+    assert_eq!(
+        kind("fire.json", r##"{"stops": [[0, "#000000"]]}"##).unwrap(),
+        Kind::Palette
+    );
+    assert_eq!(kind("config.toml", "").unwrap(), Kind::Config);
+    assert_eq!(Kind::of("dive.mandel").unwrap(), Kind::Project);
+    assert_eq!(Kind::of("sunset.ggr").unwrap(), Kind::Palette);
+    assert!(kind("other.json", "{}").is_err());
+    assert!(kind("notes.txt", "").is_err());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+/// How `animate` would render a scene.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Plan {
+    pub bounds: (usize, usize),
+    /// The frames between keyframes without times, as for `--frames`.
+    pub per_keyframe: usize,
+    pub fps: u16,
+    pub numeric: Numeric,
+    pub max_iters: Option<MaxIters>,
+}
+
+/// The view of a frame and its iteration limit.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FrameView {
+    pub upper_left: Complex<f64>,
+    pub lower_right: Complex<f64>,
+    pub limit: usize,
+}
+
+/// The frames of `scene` rendered as `plan` says, and what is wrong with
+/// them, a line per problem, naming the first frame it affects and how many
+/// more it does.
+pub fn check_scene(scene: &Scene, plan: &Plan) -> (Vec<FrameView>, Vec<String>) {
+    let frames = scene.frames(plan.per_keyframe, plan.fps);
+    if frames.is_empty() {
+        return (Vec::new(), vec!["the scene has no keyframes".to_string()]);
+    }
+    // each problem, the first frame it affects, and how many it does
+    let mut found: Vec<(String, usize, usize)> = Vec::new();
+    let mut views = Vec::with_capacity(frames.len());
+    for (number, frame) in frames.iter().enumerate() {
+        let (upper_left, lower_right) = frame.corners(plan.bounds);
+        let problem = if let Err(e) = error::validate_viewport(plan.bounds, upper_left, lower_right)
+        {
+            Some(e.to_string())
+        } else if !plan
+            .numeric
+            .resolves(upper_left, lower_right, plan.bounds.0)
+        {
+            Some(
+                if Numeric::DoubleDouble.resolves(upper_left, lower_right, plan.bounds.0) {
+                    "too deep for the numbers of --numeric, --numeric dd tells its pixels apart"
+                        .to_string()
+                } else {
+                    "too deep for any --numeric: neighboring pixels are the same point".to_string()
+                },
+            )
+        } else {
+            None
+        };
+        match problem {
+            Some(problem) => match found.iter_mut().find(|(known, _, _)| *known == problem) {
+                Some((_, _, count)) => *count += 1,
+                None => found.push((problem, number + 1, 1)),
+            },
+            None => views.push(FrameView {
+                upper_left,
+                lower_right,
+                limit: frame.limit(plan.max_iters, plan.bounds),
+            }),
+        }
+    }
+    let problems = found
+        .into_iter()
+        .map(|(problem, first, count)| match count {
+            1 => format!("frame {}: {}", first, problem),
+            _ => format!("frame {} and {} more: {}", first, count - 1, problem),
+        })
+        .collect();
+    (views, problems)
+}
+
+#[test]
+fn test_check_scene() {
+    let plan = Plan {
+        bounds: (1000, 750),
+        per_keyframe: 17,
+        fps: 25,
+        numeric: Numeric::F64,
+        max_iters: None,
+    };
+    // a frame every tenfold zoom, from 1 to 1e17
+    let scene = Scene::from_json(
+        r#"{"keyframes": [{"center": [-0.75, 0.1], "zoom": 1, "max_iters": 500},
+            {"center": [-0.75, 0.1], "zoom": 1e17, "max_iters": 500}]}"#,
+    )
+    .unwrap();
+    let (views, problems) = check_scene(&scene, &plan);
+    assert_eq!(
+        problems[..2],
+        [
+            "frame 15 and 1 more: too deep for the numbers of --numeric, --numeric dd tells its \
+             pixels apart",
+            "frame 17: too deep for any --numeric: neighboring pixels are the same point",
+        ]
+    );
+    assert!(problems[2].starts_with("frame 18: invalid viewport"));
+    assert_eq!(problems.len(), 3);
+    assert_eq!(views.len(), 14);
+    assert_eq!(views[0].limit, 500);
+
+    let plan = Plan {
+        numeric: Numeric::DoubleDouble,
+        bounds: (0, 75),
+        ..plan
+    };
+    let (views, problems) = check_scene(&scene, &plan);
+    assert!(views.is_empty());
+    assert_eq!(problems.len(), 1);
+    assert!(problems[0].starts_with("frame 1 and 17 more: invalid viewport"));
+
+    let (_, problems) = check_scene(&Scene::default(), &plan);
+    assert_eq!(problems, ["the scene has no keyframes"]);
+}
+
+/// The widest the renders `estimate_seconds` times are.
+const PROBE_WIDTH: usize = 64;
+
+/// Roughly how many seconds rendering `frames` into images of `bounds`
+/// pixels takes with `threads` threads, timing small renders of a few of
+/// them, evenly spread, and scaling up.
+pub fn estimate_seconds(
+    frames: &[FrameView],
+    bounds: (usize, usize),
+    numeric: Numeric,
+    threads: usize,
+) -> f64 {
+    if frames.is_empty() {
+        return 0.0;
+    }
+    let scale = (PROBE_WIDTH as f64 / bounds.0 as f64).min(1.0);
+    let probe = (
+        ((bounds.0 as f64 * scale).round() as usize).max(1),
+        ((bounds.1 as f64 * scale).round() as usize).max(1),
+    );
+    let probes = frames.len().min(8);
+    let mut seconds_per_pixel = 0.0;
+    for i in 0..probes {
+        let frame = &frames[i * (frames.len() - 1) / (probes - 1).max(1)];
+        let mut pixels = vec![0; probe.0 * probe.1];
+        let started = Instant::now();
+        render::render_parallel(
+            Strategy::Naive,
+            &mut pixels,
+            probe,
+            frame.upper_left,
+            frame.lower_right,
+            &render::Schedule {
+                limit: frame.limit,
+                numeric,
+                ..render::Schedule::new(threads)
+            },
+        );
+        seconds_per_pixel += started.elapsed().as_secs_f64() / pixels.len() as f64;
+    }
+    seconds_per_pixel / probes as f64 * (bounds.0 * bounds.1) as f64 * frames.len() as f64
+}
+
+#[test]
+fn test_estimate_seconds() {
+    let view = |limit| FrameView {
+        upper_left: Complex::new(-2.0, 1.5),
+        lower_right: Complex::new(1.0, -1.5),
+        limit,
+    };
+    assert_eq!(estimate_seconds(&[], (100, 100), Numeric::F64, 2), 0.0);
+    let seconds = estimate_seconds(&[view(100), view(2000)], (640, 480), Numeric::F64, 2);
+    assert!(seconds > 0.0 && seconds.is_finite());
+}
+
+/// `seconds` for people to read: `42 s`, `3 min 20 s` or `2 h 5 min`.
+pub fn duration(seconds: f64) -> String {
+    let seconds = seconds.round() as u64;
+    match seconds {
+        0..=59 => format!("{} s", seconds),
+        60..=3599 => format!("{} min {} s", seconds / 60, seconds % 60),
+        _ => format!("{} h {} min", seconds / 3600, seconds % 3600 / 60),
+    }
+}
+
+#[test]
+fn test_duration() {
+    assert_eq!(duration(0.2), "0 s");
+    assert_eq!(duration(42.0), "42 s");
+    assert_eq!(duration(200.0), "3 min 20 s");
+    assert_eq!(duration(7500.0), "2 h 5 min");
+}
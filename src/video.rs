@@ -0,0 +1,187 @@
+//! Encoding animations straight to video, by piping raw frames into an
+//! `ffmpeg` child process, so that long animations don't need a PNG file
+//! per frame on disk.
+//!
+//! Writing a frame blocks while ffmpeg is busy with the ones before, so the
+//! renderer never gets ahead of the encoder by more than what the pipe holds.
+
+use std::{
+    io::{Read, Write},
+    process::{Child, ChildStdin, Command, Stdio},
+    thread::JoinHandle,
+};
+
+use crate::{platform, Error, Result};
+
+/// The codec videos are encoded with unless told otherwise.
+pub const DEFAULT_CODEC: &str = "libx264";
+
+/// How many lines of what ffmpeg printed go into the error when it fails.
+const STDERR_LINES: usize = 10;
+
+/// A video being encoded by ffmpeg.
+pub struct Video {
+    child: Child,
+    stdin: Option<ChildStdin>,
+    stderr: Option<JoinHandle<String>>,
+    frame_len: usize,
+}
+
+/// The ffmpeg command encoding raw frames whose dimensions are given by
+/// `bounds`, grayscale or RGB, from stdin into `output`.
+pub fn ffmpeg_command(
+    output: &str,
+    bounds: (usize, usize),
+    rgb: bool,
+    fps: u16,
+    codec: &str,
+) -> Command {
+    let mut command = Command::new("ffmpeg");
+    command
+        .args(["-hide_banner", "-loglevel", "error", "-y"])
+        .args(["-f", "rawvideo"])
+        .args(["-pix_fmt", if rgb { "rgb24" } else { "gray" }])
+        .args(["-video_size", &format!("{}x{}", bounds.0, bounds.1)])
+        .args(["-framerate", &fps.to_string()])
+        .args(["-i", "-"])
+        .args(["-c:v", codec])
+        // what players expect
+        .args(["-pix_fmt", "yuv420p"])
+        .arg(platform::path(output));
+    command
+}
+
+#[test]
+fn test_ffmpeg_command() {
+    let command = ffmpeg_command("dive.mp4", (640, 480), true, 60, "libx265");
+    let args: Vec<_> = command.get_args().map(|a| a.to_str().unwrap()).collect();
+    assert_eq!(command.get_program(), "ffmpeg");
+    assert!(args.windows(2).any(|a| a == ["-pix_fmt", "rgb24"]));
+    assert!(args.windows(2).any(|a| a == ["-video_size", "640x480"]));
+    assert!(args.windows(2).any(|a| a == ["-framerate", "60"]));
+    assert!(args.windows(2).any(|a| a == ["-c:v", "libx265"]));
+    assert_eq!(args.last(), Some(&"dive.mp4"));
+}
+
+impl Video {
+    /// Start encoding `output` with ffmpeg, which must be on the `PATH`.
+    /// The frames' dimensions are given by `bounds`, and they are grayscale,
+    /// one byte a pixel, or RGB, three bytes a pixel, as `rgb` says.
+    pub fn start(
+        output: &str,
+        bounds: (usize, usize),
+        rgb: bool,
+        fps: u16,
+        codec: &str,
+    ) -> Result<Video> {
+        let frame_len = bounds.0 * bounds.1 * if rgb { 3 } else { 1 };
+        Video::spawn(ffmpeg_command(output, bounds, rgb, fps, codec), frame_len)
+    }
+
+    /// Start `command`, which reads frames of `frame_len` bytes from stdin.
+    pub fn spawn(mut command: Command, frame_len: usize) -> Result<Video> {
+        let program = command.get_program().to_string_lossy().into_owned();
+        let mut child = command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| Error::Video(format!("couldn't start {}: {}", program, e)))?;
+
+        // read stderr as it comes, so that ffmpeg never blocks on it
+        let mut stderr = child.stderr.take().unwrap();
+        let stderr = std::thread::spawn(move || {
+            let mut text = String::new();
+            let _ = stderr.read_to_string(&mut text);
+            text
+        });
+        Ok(Video {
+            stdin: child.stdin.take(),
+            child,
+            stderr: Some(stderr),
+            frame_len,
+        })
+    }
+
+    /// Send the frame `pixels` to the encoder, waiting while it is busy.
+    pub fn add_frame(&mut self, pixels: &[u8]) -> Result<()> {
+        assert_eq!(pixels.len(), self.frame_len);
+        let stdin = self
+            .stdin
+            .as_mut()
+            .expect("frames are added before finishing");
+        if stdin.write_all(pixels).is_ok() {
+            return Ok(());
+        }
+        // the encoder has stopped reading; what it says is the better error
+        self.stdin = None;
+        Err(self.wait().err().unwrap_or_else(|| {
+            Error::Video("the encoder exited before the last frame".to_string())
+        }))
+    }
+
+    /// Tell the encoder there are no more frames, and wait for it to finish
+    /// writing the video.
+    pub fn finish(mut self) -> Result<()> {
+        self.stdin = None;
+        self.wait()
+    }
+
+    fn wait(&mut self) -> Result<()> {
+        let status = self
+            .child
+            .wait()
+            .map_err(|e| Error::Video(format!("couldn't wait for the encoder: {}", e)))?;
+        let stderr = match self.stderr.take() {
+            Some(stderr) => stderr.join().unwrap_or_default(),
+            None => String::new(),
+        };
+        if status.success() {
+            return Ok(());
+        }
+        let lines: Vec<&str> = stderr.lines().collect();
+        let last = &lines[lines.len().saturating_sub(STDERR_LINES)..];
+        Err(Error::Video(format!(
+            "the encoder failed ({}){}{}",
+            status,
+            if last.is_empty() { "" } else { ":\n" },
+            last.join("\n")
+        )))
+    }
+}
+
+impl Drop for Video {
+    fn drop(&mut self) {
+        // don't leave the encoder waiting for frames that will never come
+        self.stdin = None;
+        let _ = self.child.wait();
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn test_video() {
+    let shell = |script: &str| {
+        let mut command = Command::new("sh");
+        command.args(["-c", script]);
+        command
+    };
+
+    let mut video = Video::spawn(shell("cat > /dev/null"), 4).unwrap();
+    for _ in 0..3 {
+        video.add_frame(&[1, 2, 3, 4]).unwrap();
+    }
+    video.finish().unwrap();
+
+    // an encoder that fails before reading anything
+    let mut video = Video::spawn(shell("echo 'unknown codec' >&2; exit 3"), 1 << 16).unwrap();
+    let frame = vec![0; 1 << 16];
+    let error = (0..64)
+        .map(|_| video.add_frame(&frame))
+        .find_map(|result| result.err())
+        .unwrap_or_else(|| video.finish().unwrap_err());
+    assert!(error.to_string().contains("unknown codec"), "{}", error);
+
+    let missing = Video::spawn(Command::new("no-such-encoder-anywhere"), 1);
+    assert!(matches!(missing, Err(Error::Video(_))));
+}
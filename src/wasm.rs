@@ -0,0 +1,110 @@
+//! The renderer, for JavaScript: build with
+//! `wasm-pack build --target web --no-default-features --features wasm`, and
+//!
+//! ```js
+//! import init, { render } from "./pkg/mandelbrot.js";
+//! await init();
+//! const pixels = render(800, 600, -0.75, 0.1, 20, 1000);
+//! ctx.putImageData(new ImageData(new Uint8ClampedArray(pixels.buffer), 800, 600), 0, 0);
+//! ```
+//!
+//! `render_encoded` returns an image file instead, to download or to show
+//! through a `Blob` URL.
+//!
+//! Browsers run WebAssembly on one thread, so this renders on the calling
+//! one rather than through `render::render_parallel`.
+
+use num::Complex;
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    encode::{Encoding, Renderer},
+    render,
+    units::Zoom,
+};
+
+/// Render the view centered on `cx + cy i`, magnified `zoom` times as with
+/// `--zoom`, into a `width` by `height` image, giving up on points that
+/// haven't escaped after `max_iter` iterations.
+///
+/// Returns the pixels as RGBA, four bytes per pixel and row after row, which
+/// is what `ImageData` expects. The image is gray, from black for points in
+/// the set to white for points that escape at once.
+#[wasm_bindgen]
+pub fn render(width: u32, height: u32, cx: f64, cy: f64, zoom: f64, max_iter: u32) -> Vec<u8> {
+    let bounds = (width as usize, height as usize);
+    let mut rgba = Vec::with_capacity(bounds.0 * bounds.1 * 4);
+    if bounds.0 == 0 || bounds.1 == 0 || !(zoom > 0.0 && zoom.is_finite()) {
+        return rgba;
+    }
+
+    let center = Complex { re: cx, im: cy };
+    let (upper_left, lower_right) = render::corners_from_center(center, Zoom(zoom), bounds);
+    let limit = max_iter.max(1) as usize;
+    for row in 0..bounds.1 {
+        for column in 0..bounds.0 {
+            let point = render::pixel_to_point(bounds, (column, row), upper_left, lower_right);
+            let gray = render::gray_level(render::escape_time(point, limit), limit);
+            rgba.extend_from_slice(&[gray, gray, gray, 255]);
+        }
+    }
+    rgba
+}
+
+#[test]
+fn test_render() {
+    let rgba = render(8, 6, -0.5, 0.0, 1.0, 255);
+    assert_eq!(rgba.len(), 8 * 6 * 4);
+    assert!(rgba
+        .chunks(4)
+        .all(|p| p[0] == p[1] && p[1] == p[2] && p[3] == 255));
+
+    // with the default limit it draws what `render::render` does
+    let bounds = (8, 6);
+    let (upper_left, lower_right) =
+        render::corners_from_center(Complex { re: -0.5, im: 0.0 }, Zoom::NONE, bounds);
+    let mut gray = vec![0; 48];
+    render::render(&mut gray, bounds, upper_left, lower_right);
+    let red: Vec<u8> = rgba.chunks(4).map(|p| p[0]).collect();
+    assert_eq!(red, gray);
+
+    assert!(render(0, 6, -0.5, 0.0, 1.0, 255).is_empty());
+    assert!(render(8, 6, -0.5, 0.0, 0.0, 255).is_empty());
+}
+
+/// Render the view like `render` does, and encode it as an image in
+/// `format`: `pgm`, or `png` and `gif` when built with the `png` feature too.
+#[wasm_bindgen]
+pub fn render_encoded(
+    width: u32,
+    height: u32,
+    cx: f64,
+    cy: f64,
+    zoom: f64,
+    max_iter: u32,
+    format: &str,
+) -> Result<Vec<u8>, String> {
+    let format: Encoding = format.parse()?;
+    let bounds = (width as usize, height as usize);
+    if bounds.0 == 0 || bounds.1 == 0 || !(zoom > 0.0 && zoom.is_finite()) {
+        return Err("the image must have pixels and the zoom be positive".to_string());
+    }
+    let center = Complex { re: cx, im: cy };
+    let (upper_left, lower_right) = render::corners_from_center(center, Zoom(zoom), bounds);
+    let mut renderer = Renderer::new(bounds, upper_left, lower_right);
+    renderer.limit = max_iter.max(1) as usize;
+    renderer
+        .render_to_encoded_bytes(format)
+        .map_err(|e| e.to_string())
+}
+
+#[test]
+fn test_render_encoded() {
+    let pgm = render_encoded(8, 6, -0.5, 0.0, 1.0, 255, "pgm").unwrap();
+    let rgba = render(8, 6, -0.5, 0.0, 1.0, 255);
+    let red: Vec<u8> = rgba.chunks(4).map(|p| p[0]).collect();
+    assert_eq!(pgm, [&b"P5\n8 6\n255\n"[..], &red].concat());
+
+    assert!(render_encoded(8, 6, -0.5, 0.0, 1.0, 255, "bmp").is_err());
+    assert!(render_encoded(0, 6, -0.5, 0.0, 1.0, 255, "pgm").is_err());
+}
@@ -0,0 +1,347 @@
+//! Stamping where an image was rendered onto a corner of it, for
+//! `--watermark`: the view as a compact location, which `--center` takes
+//! back, and any text or logo of the user's, in white on a darkened box, so
+//! that an image shared without its metadata can still be rendered again.
+//!
+//! The text is drawn with a built-in 5x7 bitmap font of the printable ASCII
+//! characters, scaled up with the image; others are drawn as `?`.
+
+use std::str::FromStr;
+
+use num::Complex;
+
+use crate::{locator::Locator, resample, units::ComplexWidth};
+
+/// The corner the stamp goes in.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Corner {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    #[default]
+    BottomRight,
+}
+
+impl FromStr for Corner {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Corner, String> {
+        match s {
+            "top-left" => Ok(Corner::TopLeft),
+            "top-right" => Ok(Corner::TopRight),
+            "bottom-left" => Ok(Corner::BottomLeft),
+            "bottom-right" => Ok(Corner::BottomRight),
+            _ => Err(format!("unknown corner {}", s)),
+        }
+    }
+}
+
+/// What to stamp, and where.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Watermark {
+    pub corner: Corner,
+    /// A line of the user's under the location.
+    pub text: Option<String>,
+    /// A PNG image drawn above the text.
+    pub logo: Option<String>,
+}
+
+/// The rows of each glyph, from space to `~`, the leftmost pixel in the
+/// highest of the five bits.
+const GLYPHS: [[u8; 7]; 95] = [
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // ' '
+    [0x04, 0x04, 0x04, 0x04, 0x04, 0x00, 0x04], // '!'
+    [0x0a, 0x0a, 0x0a, 0x00, 0x00, 0x00, 0x00], // '"'
+    [0x0a, 0x0a, 0x1f, 0x0a, 0x1f, 0x0a, 0x0a], // '#'
+    [0x04, 0x0f, 0x14, 0x0e, 0x05, 0x1e, 0x04], // '$'
+    [0x18, 0x19, 0x02, 0x04, 0x08, 0x13, 0x03], // '%'
+    [0x0c, 0x12, 0x14, 0x08, 0x15, 0x12, 0x0d], // '&'
+    [0x04, 0x04, 0x04, 0x00, 0x00, 0x00, 0x00], // "'"
+    [0x02, 0x04, 0x08, 0x08, 0x08, 0x04, 0x02], // '('
+    [0x08, 0x04, 0x02, 0x02, 0x02, 0x04, 0x08], // ')'
+    [0x00, 0x04, 0x15, 0x0e, 0x15, 0x04, 0x00], // '*'
+    [0x00, 0x04, 0x04, 0x1f, 0x04, 0x04, 0x00], // '+'
+    [0x00, 0x00, 0x00, 0x00, 0x0c, 0x04, 0x08], // ','
+    [0x00, 0x00, 0x00, 0x1f, 0x00, 0x00, 0x00], // '-'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x0c, 0x0c], // '.'
+    [0x00, 0x01, 0x02, 0x04, 0x08, 0x10, 0x00], // '/'
+    [0x0e, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0e], // '0'
+    [0x04, 0x0c, 0x04, 0x04, 0x04, 0x04, 0x0e], // '1'
+    [0x0e, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1f], // '2'
+    [0x1f, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0e], // '3'
+    [0x02, 0x06, 0x0a, 0x12, 0x1f, 0x02, 0x02], // '4'
+    [0x1f, 0x10, 0x1e, 0x01, 0x01, 0x11, 0x0e], // '5'
+    [0x06, 0x08, 0x10, 0x1e, 0x11, 0x11, 0x0e], // '6'
+    [0x1f, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08], // '7'
+    [0x0e, 0x11, 0x11, 0x0e, 0x11, 0x11, 0x0e], // '8'
+    [0x0e, 0x11, 0x11, 0x0f, 0x01, 0x02, 0x0c], // '9'
+    [0x00, 0x0c, 0x0c, 0x00, 0x0c, 0x0c, 0x00], // ':'
+    [0x00, 0x0c, 0x0c, 0x00, 0x0c, 0x04, 0x08], // ';'
+    [0x02, 0x04, 0x08, 0x10, 0x08, 0x04, 0x02], // '<'
+    [0x00, 0x00, 0x1f, 0x00, 0x1f, 0x00, 0x00], // '='
+    [0x08, 0x04, 0x02, 0x01, 0x02, 0x04, 0x08], // '>'
+    [0x0e, 0x11, 0x01, 0x02, 0x04, 0x00, 0x04], // '?'
+    [0x0e, 0x11, 0x01, 0x0d, 0x15, 0x15, 0x0e], // '@'
+    [0x0e, 0x11, 0x11, 0x1f, 0x11, 0x11, 0x11], // 'A'
+    [0x1e, 0x11, 0x11, 0x1e, 0x11, 0x11, 0x1e], // 'B'
+    [0x0e, 0x11, 0x10, 0x10, 0x10, 0x11, 0x0e], // 'C'
+    [0x1c, 0x12, 0x11, 0x11, 0x11, 0x12, 0x1c], // 'D'
+    [0x1f, 0x10, 0x10, 0x1e, 0x10, 0x10, 0x1f], // 'E'
+    [0x1f, 0x10, 0x10, 0x1e, 0x10, 0x10, 0x10], // 'F'
+    [0x0e, 0x11, 0x10, 0x17, 0x11, 0x11, 0x0f], // 'G'
+    [0x11, 0x11, 0x11, 0x1f, 0x11, 0x11, 0x11], // 'H'
+    [0x0e, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0e], // 'I'
+    [0x07, 0x02, 0x02, 0x02, 0x02, 0x12, 0x0c], // 'J'
+    [0x11, 0x12, 0x14, 0x18, 0x14, 0x12, 0x11], // 'K'
+    [0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x1f], // 'L'
+    [0x11, 0x1b, 0x15, 0x15, 0x11, 0x11, 0x11], // 'M'
+    [0x11, 0x11, 0x19, 0x15, 0x13, 0x11, 0x11], // 'N'
+    [0x0e, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0e], // 'O'
+    [0x1e, 0x11, 0x11, 0x1e, 0x10, 0x10, 0x10], // 'P'
+    [0x0e, 0x11, 0x11, 0x11, 0x15, 0x12, 0x0d], // 'Q'
+    [0x1e, 0x11, 0x11, 0x1e, 0x14, 0x12, 0x11], // 'R'
+    [0x0f, 0x10, 0x10, 0x0e, 0x01, 0x01, 0x1e], // 'S'
+    [0x1f, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04], // 'T'
+    [0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0e], // 'U'
+    [0x11, 0x11, 0x11, 0x11, 0x11, 0x0a, 0x04], // 'V'
+    [0x11, 0x11, 0x11, 0x15, 0x15, 0x15, 0x0a], // 'W'
+    [0x11, 0x11, 0x0a, 0x04, 0x0a, 0x11, 0x11], // 'X'
+    [0x11, 0x11, 0x11, 0x0a, 0x04, 0x04, 0x04], // 'Y'
+    [0x1f, 0x01, 0x02, 0x04, 0x08, 0x10, 0x1f], // 'Z'
+    [0x0e, 0x08, 0x08, 0x08, 0x08, 0x08, 0x0e], // '['
+    [0x00, 0x10, 0x08, 0x04, 0x02, 0x01, 0x00], // '\\'
+    [0x0e, 0x02, 0x02, 0x02, 0x02, 0x02, 0x0e], // ']'
+    [0x04, 0x0a, 0x11, 0x00, 0x00, 0x00, 0x00], // '^'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x1f], // '_'
+    [0x08, 0x04, 0x02, 0x00, 0x00, 0x00, 0x00], // '`'
+    [0x00, 0x00, 0x0e, 0x01, 0x0f, 0x11, 0x0f], // 'a'
+    [0x10, 0x10, 0x16, 0x19, 0x11, 0x11, 0x1e], // 'b'
+    [0x00, 0x00, 0x0e, 0x10, 0x10, 0x11, 0x0e], // 'c'
+    [0x01, 0x01, 0x0d, 0x13, 0x11, 0x11, 0x0f], // 'd'
+    [0x00, 0x00, 0x0e, 0x11, 0x1f, 0x10, 0x0e], // 'e'
+    [0x06, 0x09, 0x08, 0x1c, 0x08, 0x08, 0x08], // 'f'
+    [0x00, 0x0f, 0x11, 0x11, 0x0f, 0x01, 0x0e], // 'g'
+    [0x10, 0x10, 0x16, 0x19, 0x11, 0x11, 0x11], // 'h'
+    [0x04, 0x00, 0x0c, 0x04, 0x04, 0x04, 0x0e], // 'i'
+    [0x02, 0x00, 0x06, 0x02, 0x02, 0x12, 0x0c], // 'j'
+    [0x10, 0x10, 0x12, 0x14, 0x18, 0x14, 0x12], // 'k'
+    [0x0c, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0e], // 'l'
+    [0x00, 0x00, 0x1a, 0x15, 0x15, 0x11, 0x11], // 'm'
+    [0x00, 0x00, 0x16, 0x19, 0x11, 0x11, 0x11], // 'n'
+    [0x00, 0x00, 0x0e, 0x11, 0x11, 0x11, 0x0e], // 'o'
+    [0x00, 0x00, 0x1e, 0x11, 0x1e, 0x10, 0x10], // 'p'
+    [0x00, 0x00, 0x0d, 0x13, 0x0f, 0x01, 0x01], // 'q'
+    [0x00, 0x00, 0x16, 0x19, 0x10, 0x10, 0x10], // 'r'
+    [0x00, 0x00, 0x0e, 0x10, 0x0e, 0x01, 0x1e], // 's'
+    [0x08, 0x08, 0x1c, 0x08, 0x08, 0x09, 0x06], // 't'
+    [0x00, 0x00, 0x11, 0x11, 0x11, 0x13, 0x0d], // 'u'
+    [0x00, 0x00, 0x11, 0x11, 0x11, 0x0a, 0x04], // 'v'
+    [0x00, 0x00, 0x11, 0x11, 0x15, 0x15, 0x0a], // 'w'
+    [0x00, 0x00, 0x11, 0x0a, 0x04, 0x0a, 0x11], // 'x'
+    [0x00, 0x00, 0x11, 0x11, 0x0f, 0x01, 0x0e], // 'y'
+    [0x00, 0x00, 0x1f, 0x02, 0x04, 0x08, 0x1f], // 'z'
+    [0x02, 0x04, 0x04, 0x08, 0x04, 0x04, 0x02], // '{'
+    [0x04, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04], // '|'
+    [0x08, 0x04, 0x04, 0x02, 0x04, 0x04, 0x08], // '}'
+    [0x00, 0x00, 0x08, 0x15, 0x02, 0x00, 0x00], // '~'
+];
+
+/// The glyph of `c`.
+fn glyph(c: char) -> [u8; 7] {
+    match c {
+        ' '..='~' => GLYPHS[c as usize - ' ' as usize],
+        _ => GLYPHS['?' as usize - ' ' as usize],
+    }
+}
+
+/// The lines stamped on the view from `upper_left` to `lower_right`: its
+/// location, with the zoom, then `text`.
+pub fn lines(
+    upper_left: Complex<f64>,
+    lower_right: Complex<f64>,
+    text: Option<&str>,
+) -> Vec<String> {
+    let locator = Locator {
+        center: (upper_left + lower_right) / 2.0,
+        zoom: Some(ComplexWidth::between(upper_left, lower_right).zoom()),
+        rotation: 0.0,
+    };
+    let mut lines = vec![locator.to_string()];
+    lines.extend(text.map(str::to_string));
+    lines
+}
+
+#[test]
+fn test_lines() {
+    let (upper_left, lower_right) = (Complex::new(-2.5, 1.5), Complex::new(1.5, -1.5));
+    assert_eq!(
+        lines(upper_left, lower_right, Some("by me")),
+        ["@-0.5,0,z=1e0", "by me"]
+    );
+    let locator: Locator = lines(upper_left, lower_right, None)[0].parse().unwrap();
+    assert_eq!(locator.center, Complex::new(-0.5, 0.0));
+}
+
+/// The pixels of the PNG image `file` for a logo, in RGB, and its
+/// dimensions.
+#[cfg(feature = "png")]
+pub fn read_logo(file: &str) -> crate::Result<(Vec<u8>, (usize, usize))> {
+    let (pixels, size) = crate::read_image(file)?;
+    if pixels.len() == size.0 * size.1 {
+        Ok((crate::compare::gray_to_rgb(&pixels), size))
+    } else {
+        Ok((pixels, size))
+    }
+}
+
+/// Stamp `lines`, and above them `logo`, RGB pixels whose dimensions are
+/// its second half, onto `image`, gray, RGB or RGBA, whose dimensions are
+/// given by `bounds`, in `corner`. The stamp is scaled with the height of
+/// the image, and the logo shrunk to fit a third of its width.
+pub fn stamp(
+    image: &mut [u8],
+    bounds: (usize, usize),
+    lines: &[String],
+    logo: Option<(&[u8], (usize, usize))>,
+    corner: Corner,
+) {
+    let (width, height) = bounds;
+    let channels = image.len() / (width * height).max(1);
+    let scale = (height / 400).max(1);
+    let pad = 2 * scale;
+    let logo = logo.map(|(pixels, size)| {
+        let most = (width / 3).max(1);
+        if size.0 <= most {
+            (pixels.to_vec(), size)
+        } else {
+            let to = (most, (size.1 * most / size.0).max(1));
+            let shrunk = resample::resample(pixels, size, to, 3, resample::Filter::Mitchell);
+            (shrunk, to)
+        }
+    });
+    let logo_size = logo.as_ref().map_or((0, 0), |(_, size)| *size);
+    let text_width = lines
+        .iter()
+        .map(|line| line.chars().count())
+        .max()
+        .unwrap_or(0)
+        * 6
+        * scale;
+    let text_height = lines.len() * 8 * scale;
+    let gap = if logo.is_some() && !lines.is_empty() {
+        pad
+    } else {
+        0
+    };
+    let box_size = (
+        text_width.max(logo_size.0) + 2 * pad,
+        text_height + logo_size.1 + gap + 2 * pad,
+    );
+    let left = match corner {
+        Corner::TopLeft | Corner::BottomLeft => pad,
+        Corner::TopRight | Corner::BottomRight => width.saturating_sub(box_size.0 + pad),
+    };
+    let top = match corner {
+        Corner::TopLeft | Corner::TopRight => pad,
+        Corner::BottomLeft | Corner::BottomRight => height.saturating_sub(box_size.1 + pad),
+    };
+
+    // pixels off the image are left out
+    let mut set = |x: usize, y: usize, color: &dyn Fn([u8; 3]) -> [u8; 3]| {
+        if x >= width || y >= height {
+            return;
+        }
+        let pixel = &mut image[(y * width + x) * channels..][..channels];
+        let old = match *pixel {
+            [gray] => [gray; 3],
+            [r, g, b, ..] => [r, g, b],
+            _ => unreachable!("images are gray, RGB or RGBA"),
+        };
+        let [r, g, b] = color(old);
+        match pixel {
+            [gray] => *gray = ((r as usize + g as usize + b as usize) / 3) as u8,
+            [pr, pg, pb, rest @ ..] => {
+                (*pr, *pg, *pb) = (r, g, b);
+                if let [alpha] = rest {
+                    *alpha = 255;
+                }
+            }
+            _ => unreachable!("images are gray, RGB or RGBA"),
+        }
+    };
+
+    for y in top..top + box_size.1 {
+        for x in left..left + box_size.0 {
+            set(x, y, &|old| old.map(|level| level / 3));
+        }
+    }
+    let mut y = top + pad;
+    if let Some((pixels, size)) = &logo {
+        for (i, rgb) in pixels.chunks(3).enumerate() {
+            let rgb = [rgb[0], rgb[1], rgb[2]];
+            set(left + pad + i % size.0, y + i / size.0, &|_| rgb);
+        }
+        y += size.1 + gap;
+    }
+    for line in lines {
+        for (column, c) in line.chars().enumerate() {
+            for (row, bits) in glyph(c).iter().enumerate() {
+                for bit in 0..5 {
+                    if bits & (0x10 >> bit) == 0 {
+                        continue;
+                    }
+                    let (x, y) = (left + pad + (column * 6 + bit) * scale, y + row * scale);
+                    for dy in 0..scale {
+                        for dx in 0..scale {
+                            set(x + dx, y + dy, &|_| [255; 3]);
+                        }
+                    }
+                }
+            }
+        }
+        y += 8 * scale;
+    }
+}
+
+#[test]
+fn test_stamp() {
+    assert_eq!("top-left".parse(), Ok(Corner::TopLeft));
+    assert!("middle".parse::<Corner>().is_err());
+    assert_eq!(glyph('\u{e9}'), glyph('?'));
+
+    // "1" in the bottom right corner of a gray image: a darkened box with
+    // the glyph in white
+    let bounds = (20, 15);
+    let mut image = vec![90; 20 * 15];
+    stamp(
+        &mut image,
+        bounds,
+        &["1".to_string()],
+        None,
+        Corner::BottomRight,
+    );
+    // the box is 6 + 4 pixels wide and 8 + 4 high, 2 from the corner
+    assert_eq!(image[0], 90);
+    assert_eq!(image[14 * 20 + 19], 90);
+    assert_eq!(image[20 + 8], 30);
+    assert_eq!(image[20 + 17], 30);
+    assert_eq!(image[12 * 20 + 8], 30);
+    assert_eq!(image[12 * 20 + 18], 90);
+    // the top of the 1, ..#.., at the text origin (10, 3)
+    assert_eq!(&image[3 * 20 + 10..][..5], [30, 30, 255, 30, 30]);
+    assert_eq!(&image[9 * 20 + 10..][..5], [30, 255, 255, 255, 30]);
+
+    // RGBA becomes opaque under the stamp, and the logo goes above the text
+    let mut rgba = vec![0; 4 * 40 * 30];
+    let logo = [255, 0, 0].repeat(4);
+    stamp(
+        &mut rgba,
+        (40, 30),
+        &["a".to_string()],
+        Some((&logo, (2, 2))),
+        Corner::TopLeft,
+    );
+    assert_eq!(&rgba[4 * (4 * 40 + 4)..][..4], [255, 0, 0, 255]);
+    assert_eq!(&rgba[4 * (2 * 40 + 2)..][..4], [0, 0, 0, 255]);
+    assert_eq!(&rgba[4 * (29 * 40 + 39)..][..4], [0, 0, 0, 0]);
+}
@@ -0,0 +1,280 @@
+//! Golden images: every renderer draws a few small canonical views, and the
+//! pixels have to match the references checked in under `tests/golden`,
+//! within a tolerance, so that an optimization can't change the output
+//! without anyone noticing.
+//!
+//! A change that is meant to alter the images writes new references with
+//!
+//! ```text
+//! GOLDEN_UPDATE=1 cargo test --test golden
+//! ```
+//!
+//! after which the differences show in the images of the commit.
+
+use std::{env, path::PathBuf};
+
+use mandelbrot::{
+    animation::Frame,
+    buddhabrot::{self, Buddhabrot},
+    compare,
+    deepen::Deepening,
+    encode,
+    expmap::Strip,
+    formula::{self, Formula},
+    newton::{self, Newton},
+    read_image,
+    real::Numeric,
+    render::{self, Schedule, Strategy},
+    sampling::Sampling,
+    traversal::Traversal,
+    units::ComplexWidth,
+};
+use num::Complex;
+
+const BOUNDS: (usize, usize) = (64, 48);
+
+/// How many gray levels a pixel may be off by, for rounding.
+const TOLERANCE: u8 = 4;
+
+/// The share of the pixels that may be off by more, for the points so near
+/// the boundary that the last bit of a computation takes them in or out.
+const MAX_OFF: f64 = 0.005;
+
+/// The canonical views, by name: the whole set, centered on the real axis
+/// so that mirroring is used, the seahorse valley, full of boundary, and a
+/// deeper zoom into it.
+const VIEWS: &[(&str, Complex<f64>, Complex<f64>)] = &[
+    (
+        "whole",
+        Complex { re: -2.2, im: 1.2 },
+        Complex { re: 1.0, im: -1.2 },
+    ),
+    (
+        "seahorse",
+        Complex {
+            re: -0.76,
+            im: 0.12,
+        },
+        Complex {
+            re: -0.72,
+            im: 0.09,
+        },
+    ),
+    (
+        "deep",
+        Complex {
+            re: -0.743_94,
+            im: 0.132_055,
+        },
+        Complex {
+            re: -0.743_34,
+            im: 0.131_605,
+        },
+    ),
+];
+
+/// A way of rendering, drawing one view into pixels with as many bytes a
+/// pixel as they have.
+struct Renderer {
+    name: &'static str,
+    channels: usize,
+    render: fn(&mut [u8], Complex<f64>, Complex<f64>),
+}
+
+fn schedule<'a>() -> Schedule<'a> {
+    Schedule {
+        chunk_rows: 5,
+        limit: 500,
+        ..Schedule::new(2)
+    }
+}
+
+fn parallel(pixels: &mut [u8], ul: Complex<f64>, lr: Complex<f64>, schedule: &Schedule) {
+    render::render_parallel(Strategy::Naive, pixels, BOUNDS, ul, lr, schedule);
+}
+
+const RENDERERS: &[Renderer] = &[
+    Renderer {
+        name: "naive",
+        channels: 1,
+        render: |pixels, ul, lr| {
+            render::render_with_limit(Strategy::Naive, pixels, BOUNDS, ul, lr, 500)
+        },
+    },
+    Renderer {
+        name: "border-trace",
+        channels: 1,
+        render: |pixels, ul, lr| {
+            render::render_with_limit(Strategy::BorderTrace, pixels, BOUNDS, ul, lr, 500)
+        },
+    },
+    Renderer {
+        name: "adaptive",
+        channels: 1,
+        render: |pixels, ul, lr| render::render_adaptive(pixels, BOUNDS, ul, lr, 500, 8),
+    },
+    Renderer {
+        name: "parallel",
+        channels: 1,
+        render: |pixels, ul, lr| parallel(pixels, ul, lr, &schedule()),
+    },
+    Renderer {
+        name: "hilbert",
+        channels: 1,
+        render: |pixels, ul, lr| {
+            let schedule = Schedule {
+                traversal: Traversal::Hilbert,
+                ..schedule()
+            };
+            parallel(pixels, ul, lr, &schedule)
+        },
+    },
+    Renderer {
+        name: "samples",
+        channels: 1,
+        render: |pixels, ul, lr| {
+            let schedule = Schedule {
+                sampling: Sampling {
+                    samples: 4,
+                    seed: 1,
+                    jitter: true,
+                },
+                ..schedule()
+            };
+            parallel(pixels, ul, lr, &schedule)
+        },
+    },
+    Renderer {
+        name: "f32",
+        channels: 1,
+        render: |pixels, ul, lr| {
+            let schedule = Schedule {
+                numeric: Numeric::F32,
+                ..schedule()
+            };
+            parallel(pixels, ul, lr, &schedule)
+        },
+    },
+    Renderer {
+        name: "double-double",
+        channels: 1,
+        render: |pixels, ul, lr| {
+            let schedule = Schedule {
+                numeric: Numeric::DoubleDouble,
+                ..schedule()
+            };
+            parallel(pixels, ul, lr, &schedule)
+        },
+    },
+    Renderer {
+        name: "deepen",
+        channels: 1,
+        render: |pixels, ul, lr| {
+            let mut deepening = Deepening::new(BOUNDS, ul, lr);
+            deepening.deepen(100, &schedule());
+            deepening.deepen(500, &schedule());
+            pixels.copy_from_slice(&deepening.pixels());
+        },
+    },
+    Renderer {
+        name: "exp-map",
+        channels: 1,
+        render: |pixels, ul, lr| {
+            let frame = Frame {
+                center: (ul + lr) / 2.0,
+                zoom: ComplexWidth::between(ul, lr).zoom(),
+                rotation: 0.0,
+                max_iters: None,
+            };
+            let mut strip = Strip::plan(&[frame], BOUNDS).unwrap();
+            strip.render(&schedule());
+            pixels.copy_from_slice(&strip.reproject(BOUNDS, ul, lr));
+        },
+    },
+    Renderer {
+        name: "formula",
+        channels: 1,
+        render: |pixels, ul, lr| {
+            let formula = Formula {
+                expression: "z^3 + c".parse().unwrap(),
+                ..Formula::default()
+            };
+            formula::render(pixels, BOUNDS, ul, lr, &formula, &schedule())
+        },
+    },
+    Renderer {
+        name: "newton",
+        channels: 3,
+        render: |pixels, ul, lr| {
+            newton::render(pixels, BOUNDS, ul, lr, &Newton::default(), &schedule())
+        },
+    },
+    Renderer {
+        name: "buddhabrot",
+        channels: 1,
+        render: |pixels, ul, lr| {
+            let buddhabrot = Buddhabrot {
+                samples: 16,
+                max_iters: 500,
+                ..Buddhabrot::default()
+            };
+            buddhabrot::render(pixels, BOUNDS, ul, lr, &buddhabrot, &schedule())
+        },
+    },
+];
+
+/// The reference image of `renderer` drawing `view`.
+fn reference(renderer: &str, view: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/golden")
+        .join(format!("{}-{}.png", renderer, view))
+}
+
+/// How many pixels of `a` and `b`, with `channels` bytes a pixel, differ by
+/// more than `TOLERANCE` in some channel.
+fn off(a: &[u8], b: &[u8], channels: usize) -> usize {
+    a.chunks(channels)
+        .zip(b.chunks(channels))
+        .filter(|(a, b)| a.iter().zip(*b).any(|(a, b)| a.abs_diff(*b) > TOLERANCE))
+        .count()
+}
+
+#[test]
+fn test_golden_images() {
+    let update = env::var_os("GOLDEN_UPDATE").is_some();
+    let mut failures = Vec::new();
+    for renderer in RENDERERS {
+        for &(view, upper_left, lower_right) in VIEWS {
+            let mut pixels = vec![0; renderer.channels * BOUNDS.0 * BOUNDS.1];
+            (renderer.render)(&mut pixels, upper_left, lower_right);
+            let file = reference(renderer.name, view);
+            if update {
+                std::fs::create_dir_all(file.parent().unwrap()).unwrap();
+                std::fs::write(&file, encode::png(&pixels, BOUNDS).unwrap()).unwrap();
+                continue;
+            }
+            let (expected, bounds) = read_image(file.to_str().unwrap())
+                .unwrap_or_else(|e| panic!("{}; GOLDEN_UPDATE=1 writes it", e));
+            assert_eq!(bounds, BOUNDS, "{}", file.display());
+            let off = off(&pixels, &expected, renderer.channels);
+            if off as f64 > MAX_OFF * (BOUNDS.0 * BOUNDS.1) as f64 {
+                let difference = compare::compare(&pixels, &expected, renderer.channels);
+                failures.push(format!(
+                    "{} {}: {} pixels off by more than {}\n{}",
+                    renderer.name,
+                    view,
+                    off,
+                    TOLERANCE,
+                    difference.describe()
+                ));
+            }
+        }
+    }
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
+
+#[test]
+fn test_off() {
+    assert_eq!(off(&[0, 10, 200], &[4, 5, 0], 1), 2);
+    assert_eq!(off(&[0, 0, 0, 9, 9, 9], &[0, 0, 5, 9, 9, 9], 3), 1);
+}